@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+use std::hint::black_box;
+
+// Games stepped per sample. One 60Hz tick is 16.7ms, so the largest N whose
+// "step" time stays well under that is roughly what one core can run.
+const GAME_COUNTS: [usize; 4] = [1, 10, 100, 500];
+const TICK_MS: f64 = 1000.0 / 60.0;
+// ticks run before measuring so balls and pucks are moving and colliding
+// rather than resting at kickoff
+const WARMUP_TICKS: usize = 120;
+
+fn games(count: usize) -> Vec<SoccerGame> {
+    return (0..count)
+        .map(|seed| {
+            let mut game = SoccerGame::with_config(SoccerGameConfig {
+                serve_speed: Some(300.0),
+                ..SoccerGameConfig::default()
+            });
+            game.reseed(seed as u64);
+            for _ in 0..WARMUP_TICKS {
+                game.update(TICK_MS);
+            }
+            return game;
+        })
+        .collect();
+}
+
+// The cost of one server tick's worth of work for N games: stepping every
+// one once, and encoding every one for a State broadcast.
+fn soccer_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("soccer");
+    for count in GAME_COUNTS {
+        let mut games = games(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("step", count), &count, |b, _| {
+            b.iter(|| {
+                for game in games.iter_mut() {
+                    game.update(black_box(TICK_MS));
+                }
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("to_bytes", count), &count, |b, _| {
+            b.iter(|| {
+                for game in games.iter() {
+                    black_box(game.to_bytes());
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, soccer_update);
+criterion_main!(benches);
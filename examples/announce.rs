@@ -0,0 +1,97 @@
+mod common;
+
+use common::{error, next, rally, settle, Events, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::message::{AnnouncedMessage, ErrorCode, MessageType, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18106";
+const ADMIN_TOKEN: &str = "letmein";
+
+// An admin's Announce reaches every connection whatever it is doing: one
+// waiting in the queue, one playing and the admin's own firehose
+// connection. Anyone else is refused, and a connection that has gone away
+// is just left out.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        // the queued player must still be queued when the news comes
+        queue_timeout: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (queued, mut queued_events) = join("queued", ClientOptions::default()).await;
+    let practice = ClientOptions {
+        practice: true,
+        ..ClientOptions::default()
+    };
+    let (player, mut player_events) = join("player", practice).await;
+    let admin = ClientOptions {
+        firehose: true,
+        auth_token: Some(ADMIN_TOKEN.to_string()),
+        ..ClientOptions::default()
+    };
+    let (admin, mut admin_events) = join("admin", admin).await;
+
+    player.announce("not from me");
+    assert_eq!(error(&mut player_events).await, ErrorCode::Unauthorized);
+    println!("non-admin Announce refused");
+
+    admin.announce("maintenance in 5 minutes");
+    assert_eq!(delivered(&mut admin_events).await, 3);
+    for events in [&mut queued_events, &mut player_events, &mut admin_events] {
+        assert_eq!(announcement(events).await, "maintenance in 5 minutes");
+    }
+    println!("queued, playing and admin connections all told");
+
+    queued.close();
+    sleep(Duration::from_millis(200)).await;
+    admin.announce("back soon");
+    assert_eq!(delivered(&mut admin_events).await, 2);
+    assert_eq!(announcement(&mut player_events).await, "back soon");
+    println!("closed connection left out");
+}
+
+async fn join(name: &str, options: ClientOptions) -> (GameClient, Events) {
+    let options = ClientOptions {
+        mode: Some(RALLY),
+        reconnect: false,
+        state_poll_interval: None,
+        time_sync_interval: None,
+        ..options
+    };
+    let (client, mut events) = common::connect(ADDR, name, options).await;
+    settle(&client, &mut events).await;
+    return (client, events);
+}
+
+async fn delivered(events: &mut Events) -> u32 {
+    return next(events, |event| match event {
+        ClientEvent::Message(MessageType::Announce, payload) => {
+            let reply = WsMessage {
+                msg_type: MessageType::Announce,
+                payload,
+            };
+            Some(reply.decode::<AnnouncedMessage>().unwrap().delivered)
+        }
+        _ => None,
+    })
+    .await;
+}
+
+async fn announcement(events: &mut Events) -> String {
+    return next(events, |event| match event {
+        ClientEvent::Announcement(text) => Some(text),
+        _ => None,
+    })
+    .await;
+}
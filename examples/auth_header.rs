@@ -0,0 +1,104 @@
+mod common;
+
+use common::{drain, rally, raw_welcome, RALLY};
+use rust_backend::game::Games;
+use rust_backend::server::{Server, ServerConfig};
+use rust_backend::stats::PlayerId;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+const BEARER_ADDR: &str = "127.0.0.1:18155";
+const CUSTOM_ADDR: &str = "127.0.0.1:18156";
+
+async fn start(addr: &str, auth_header: &str, auth_scheme: Option<&str>) -> Games {
+    let config = ServerConfig {
+        addr: addr.to_string(),
+        http_addr: None,
+        health_addr: None,
+        auth_header: auth_header.to_string(),
+        auth_scheme: auth_scheme.map(str::to_string),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    return games;
+}
+
+// Connects to a practice game as name with the given headers and returns
+// who the server took it for.
+async fn identity(
+    games: &Games,
+    addr: &str,
+    name: &str,
+    headers: &[(&'static str, &str)],
+) -> PlayerId {
+    let mut request = format!("ws://{}/?name={}&mode=rally&practice=1", addr, name)
+        .into_client_request()
+        .unwrap();
+    for (header, value) in headers {
+        let value = HeaderValue::from_str(value).unwrap();
+        request.headers_mut().insert(*header, value);
+    }
+    let (mut stream, _) = connect_async(request).await.unwrap();
+    let welcome = raw_welcome(&mut stream).await;
+    drain(stream);
+    let game = games.read().await[&(welcome.game_id as usize)].clone();
+    let game = game.read().await;
+    return game
+        .player(welcome.player_index as usize)
+        .unwrap()
+        .id
+        .clone();
+}
+
+// The token is read from auth_header with auth_scheme taken off the front:
+// "Bearer tok-1" and a bare "tok-1" are the same account. With a custom
+// header and no scheme, the token is read from that header and
+// Authorization is ignored.
+#[tokio::main]
+async fn main() {
+    let bearer = start(BEARER_ADDR, "Authorization", Some("Bearer")).await;
+    let custom = start(CUSTOM_ADDR, "X-Api-Key", None).await;
+    sleep(Duration::from_millis(200)).await;
+
+    let tok1 = PlayerId::new(Some("tok-1"), "");
+    let prefixed = identity(
+        &bearer,
+        BEARER_ADDR,
+        "alice",
+        &[("Authorization", "Bearer tok-1")],
+    )
+    .await;
+    let bare = identity(&bearer, BEARER_ADDR, "alice", &[("Authorization", "tok-1")]).await;
+    let lower = identity(
+        &bearer,
+        BEARER_ADDR,
+        "alice",
+        &[("Authorization", "bearer tok-1")],
+    )
+    .await;
+    assert_eq!(prefixed, tok1);
+    assert_eq!(bare, tok1);
+    assert_eq!(lower, tok1);
+    println!(
+        "Bearer-prefixed, bare and lower-case scheme all play as {}",
+        tok1
+    );
+
+    let tok2 = PlayerId::new(Some("tok-2"), "");
+    let keyed = identity(&custom, CUSTOM_ADDR, "bob", &[("X-Api-Key", "tok-2")]).await;
+    assert_eq!(keyed, tok2);
+    let ignored = identity(
+        &custom,
+        CUSTOM_ADDR,
+        "carol",
+        &[("Authorization", "Bearer tok-1")],
+    )
+    .await;
+    assert_eq!(ignored, PlayerId::Guest("carol".to_string()));
+    println!("X-Api-Key plays as {}; Authorization ignored", tok2);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::{drain, rally, raw_next, RALLY};
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType, WelcomeMessage};
+use rust_backend::server::{Server, ServerConfig};
+use rust_backend::stats::PlayerId;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+const ADDR: &str = "127.0.0.1:18101";
+const AUTH_ADDR: &str = "127.0.0.1:18102";
+
+// With an auth_url the name a connection asks for doesn't matter: mallory's
+// valid token with ?name=alice still plays as mallory, under her own
+// subject, and a connection the service doesn't vouch for is turned away.
+#[tokio::main]
+async fn main() {
+    tokio::spawn(auth_service());
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        auth_url: Some(format!("http://{}/validate", AUTH_ADDR)),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = welcome(Some("alice-token"), "name=someone&mode=rally&practice=1")
+        .await
+        .expect("alice was refused");
+    let query = format!(
+        "name=alice&mode=rally&game={}&game_token={}",
+        alice.game_id, alice.game_token
+    );
+    let mallory = welcome(Some("mallory-token"), &query)
+        .await
+        .expect("mallory was refused");
+    assert_eq!(mallory.game_id, alice.game_id);
+
+    let game = games.read().await.get(&(alice.game_id as usize)).cloned();
+    let game = game.expect("no game");
+    let game = game.read().await;
+    let (first, second) = (game.player(0).unwrap(), game.player(1).unwrap());
+    assert_eq!(first.name, "alice");
+    assert_eq!(first.id, PlayerId::Subject("u-1".to_string()));
+    assert_eq!(second.name, "mallory");
+    assert_eq!(second.id, PlayerId::Subject("u-2".to_string()));
+    println!(
+        "?name=alice with mallory's token plays as {} ({})",
+        second.name, second.id
+    );
+
+    for token in [None, Some("forged-token")] {
+        let refused = welcome(token, "name=alice&mode=rally&practice=1").await;
+        assert_eq!(refused.err(), Some(ErrorCode::Unauthorized));
+        println!("token {:?} refused", token);
+    }
+}
+
+// A stand-in auth service: 200 with an identity for the tokens it knows, 401
+// for anything else.
+async fn auth_service() {
+    let listener = TcpListener::bind(AUTH_ADDR).await.unwrap();
+    loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        tokio::spawn(async move {
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            // header names are case-insensitive, and HTTP/1.1 clients
+            // send them lowercased
+            let request = String::from_utf8_lossy(&request[..read]).to_lowercase();
+            let identity = if request.contains("authorization: bearer alice-token\r\n") {
+                Some(r#"{"sub":"u-1","name":"alice"}"#)
+            } else if request.contains("authorization: bearer mallory-token\r\n") {
+                Some(r#"{"sub":"u-2","name":"mallory"}"#)
+            } else {
+                None
+            };
+            let response = match identity {
+                Some(body) => format!(
+                    "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                ),
+                None => "HTTP/1.0 401 Unauthorized\r\nContent-Length: 0\r\n\r\n".to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+// Connects with query and the token, if any, in the auth header, and returns
+// its Welcome, or the code of the Error it was refused with. A welcomed
+// connection is left open so the player stays in the game.
+async fn welcome(token: Option<&str>, query: &str) -> Result<WelcomeMessage, ErrorCode> {
+    let mut request = format!("ws://{}/?{}", ADDR, query)
+        .into_client_request()
+        .unwrap();
+    if let Some(token) = token {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        request.headers_mut().insert("Authorization", value);
+    }
+    let (mut stream, _) = connect_async(request).await.unwrap();
+    let answer = raw_next(&mut stream, &[MessageType::Welcome, MessageType::Error]).await;
+    drain(stream);
+    return match answer.msg_type {
+        MessageType::Welcome => Ok(answer.decode::<WelcomeMessage>().unwrap()),
+        _ => Err(answer.decode::<ErrorMessage>().unwrap().code),
+    };
+}
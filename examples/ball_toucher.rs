@@ -0,0 +1,40 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, HistoryEvent, SoccerGame, SoccerGameConfig};
+
+// A puck knocking the ball into its own goal: the other side gets the goal,
+// and the credit names the player who put it in as an own goal.
+fn main() {
+    let mut game = SoccerGame::with_config(SoccerGameConfig::default());
+    let ball = game.balls[0];
+    // slot 0 defends the left goal
+    let puck = game.teams[0].pucks[0];
+    game.bodies[ball].set_translation(vector![-220.0, 0.0], true);
+    game.bodies[puck].set_translation(vector![-160.0, 0.0], true);
+    game.bodies[puck].set_linvel(vector![-800.0, 0.0], true);
+    assert_eq!(game.last_ball_toucher, None);
+
+    let mut goals = vec![];
+    for _ in 0..120 {
+        game.update(1000.0 / 60.0);
+        goals.extend(game.take_goals());
+        if !goals.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(goals, vec![1], "the right side should have scored");
+    assert_eq!(game.last_ball_toucher, Some(0));
+    assert_eq!(game.last_toucher(ball), Some(0));
+    let credits: Vec<_> = game
+        .take_history()
+        .into_iter()
+        .filter(|event| matches!(event, HistoryEvent::GoalCredit { .. }))
+        .collect();
+    assert_eq!(
+        credits,
+        vec![HistoryEvent::GoalCredit {
+            player: 0,
+            own_goal: true
+        }]
+    );
+    println!("own goal credited to slot 0");
+}
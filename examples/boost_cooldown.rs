@@ -0,0 +1,53 @@
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+use std::time::Duration;
+
+// a tick at 60Hz, in ms
+const TICK: f64 = 1000.0 / 60.0;
+
+// A boost sends the puck off at boost_speed and starts the player's
+// cooldown: a second one inside it is refused with the time left, which
+// counts down with game time, and once it has run out the next boost goes
+// through. A boost that moves nothing doesn't start the cooldown, and each
+// player's runs on its own.
+fn main() {
+    let config = SoccerGameConfig {
+        boost_cooldown: Duration::from_secs(1),
+        ..SoccerGameConfig::default()
+    };
+    let mut game = SoccerGame::with_config(config);
+    let puck = game.team_puck(0, 0).unwrap();
+    assert_eq!(game.boost(0, 0, 1.0, 0.0), Ok(()));
+    let speed = game.bodies[puck].linvel().x;
+    assert!(
+        (speed - game.boost_speed).abs() < 1e-3,
+        "boosted to {}",
+        speed
+    );
+    println!("boosted to {}", speed);
+
+    assert_eq!(game.boost(0, 0, 1.0, 0.0), Err(1000));
+    for _ in 0..30 {
+        game.update(TICK);
+    }
+    let left = game.boost(0, 0, 1.0, 0.0).unwrap_err();
+    assert!((499..=501).contains(&left), "{}ms left", left);
+    assert_eq!(game.boost_remaining_ms(0), left);
+    println!(
+        "second boost refused, then {}ms left half a second on",
+        left
+    );
+
+    assert_eq!(game.boost(1, 99, 1.0, 0.0), Ok(()));
+    assert_eq!(game.boost(1, 0, 0.0, 0.0), Ok(()));
+    assert_eq!(game.boost_remaining_ms(1), 0);
+    assert_eq!(game.boost(1, 0, -1.0, 0.0), Ok(()));
+    assert!(game.boost_remaining_ms(1) > 0);
+    println!("bob's no-op boosts didn't start his cooldown; a real one did");
+
+    for _ in 0..31 {
+        game.update(TICK);
+    }
+    assert_eq!(game.boost_remaining_ms(0), 0);
+    assert_eq!(game.boost(0, 0, 0.0, 1.0), Ok(()));
+    println!("after the cooldown alice boosts again");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::StepClock;
+use rust_backend::game::{Game, GameCommand, GamePhase, SoccerGame, SoccerGameConfig};
+use rust_backend::message::{ErrorCode, ErrorMessage};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const TICK: Duration = Duration::from_millis(16);
+
+// Boosts go through the game's command queue like moves: nothing happens
+// until the tick loop takes them off it, the first then sends the puck off,
+// and a second inside the cooldown is refused through replies.
+fn main() {
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    let config = SoccerGameConfig {
+        boost_cooldown: Duration::from_secs(1),
+        ..SoccerGameConfig::default()
+    };
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(SoccerGame::with_config(config), players);
+    game.set_clock(clock.clone());
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        clock.advance(TICK);
+        game.update();
+    }
+
+    let (reply_to, mut replies) = mpsc::unbounded_channel();
+    let boost = || GameCommand::Boost {
+        player: 0,
+        target: 0,
+        dx: 1.0,
+        dy: 0.0,
+        replies: reply_to.clone(),
+    };
+    let puck = game.downcast::<SoccerGame>().unwrap().teams[0].pucks[0];
+    game.commands().try_send(boost()).unwrap();
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    assert_eq!(soccer.bodies[puck].linvel().x, 0.0);
+    assert_eq!(soccer.boost_remaining_ms(0), 0);
+    println!("queued boost not applied before the tick");
+
+    clock.advance(TICK);
+    game.update();
+    assert!(replies.try_recv().is_err());
+    assert!(game.downcast::<SoccerGame>().unwrap().boost_remaining_ms(0) > 0);
+    println!("tick applied the boost");
+
+    game.commands().try_send(boost()).unwrap();
+    clock.advance(TICK);
+    game.update();
+    let refused: ErrorMessage = replies
+        .try_recv()
+        .expect("second boost taken")
+        .decode()
+        .unwrap();
+    assert_eq!(refused.code, ErrorCode::BoostCooldown);
+    println!("second boost refused: {}", refused.message);
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::Empty;
+use rust_backend::game::Game;
+use rust_backend::stats::PlayerId;
+
+// A bot gets an identity of its own, by slot, rather than being one more
+// guest: a human who picked the name "bot" is still told apart from it, and
+// from the leaderboard's point of view so are two bots.
+fn main() {
+    let guest = PlayerId::Guest("bot".to_string());
+    let mut game = Game::new(Empty, vec![(guest.clone(), "bot".to_string())]);
+    let first = game.add_bot();
+    let second = game.add_bot();
+    let ids: Vec<PlayerId> = game.players.iter().map(|p| p.id.clone()).collect();
+    assert_eq!(
+        ids,
+        vec![guest, PlayerId::Bot(first), PlayerId::Bot(second)]
+    );
+    assert_ne!(ids[1], ids[2]);
+    println!(
+        "{}",
+        ids.iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
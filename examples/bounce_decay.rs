@@ -0,0 +1,60 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+
+const SPEED: f32 = 600.0;
+
+// The ball's speed after seconds of bouncing straight up and down between
+// the top and bottom walls.
+fn speed_after(config: &SoccerGameConfig, seconds: u32) -> f32 {
+    let mut game = SoccerGame::with_config(config.clone());
+    let ball = game.balls[0];
+    game.bodies[ball].set_linvel(vector![0.0, SPEED], true);
+    for _ in 0..60 * seconds {
+        game.update(1000.0 / 60.0);
+    }
+    return game.bodies[ball].linvel().norm();
+}
+
+// With nothing to slow it, a ball between fully elastic walls keeps its
+// speed bounce after bounce. bounce_decay takes a share off every bounce,
+// so the rally winds down, and the longer it runs the slower it gets.
+fn main() {
+    let mut elastic = SoccerGameConfig {
+        pucks_per_team: 1,
+        puck_damping: 0.0,
+        puck_restitution: 1.0,
+        ..SoccerGameConfig::default()
+    };
+    for wall in &mut elastic.walls {
+        wall.material.restitution = 1.0;
+    }
+    let decaying = SoccerGameConfig {
+        bounce_decay: Some(0.8),
+        ..elastic.clone()
+    };
+    assert!(decaying.validate().is_ok());
+
+    let endless = speed_after(&elastic, 10);
+    assert!(
+        endless > SPEED * 0.95,
+        "slowed to {} without decay",
+        endless
+    );
+    println!("without decay: {:.0} after 10s", endless);
+
+    let (early, late) = (speed_after(&decaying, 5), speed_after(&decaying, 10));
+    assert!(early < SPEED * 0.5, "only slowed to {} after 5s", early);
+    assert!(late < early);
+    println!(
+        "bounce_decay 0.8: {:.0} after 5s, {:.0} after 10s",
+        early, late
+    );
+
+    for decay in [0.0, 1.5, f32::NAN] {
+        let config = SoccerGameConfig {
+            bounce_decay: Some(decay),
+            ..elastic.clone()
+        };
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use rust_backend::game::{Game, GameLogic, SoccerGame};
+use rust_backend::message::{MessageType, WsMessage};
+use rust_backend::stats::PlayerId;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// Counts heap allocations so the two fan-out strategies can be compared.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.alloc(layout);
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const RECEIVERS: usize = 50;
+const FRAMES: usize = 60 * 10;
+
+// One game, 50 connections, ten seconds of 60hz broadcasts. "cloned" is the
+// old path, where every receiver got its own WsMessage and encoded it;
+// "shared" is Game::broadcast, which encodes once and hands out the same
+// buffer, copied only at the sink.
+fn main() {
+    let soccer_game = SoccerGame::new();
+    let message = WsMessage {
+        msg_type: MessageType::State,
+        payload: soccer_game.to_bytes(),
+    };
+    let players = ["a", "b"]
+        .map(|name| (PlayerId::Guest(name.to_string()), name.to_string()))
+        .to_vec();
+    let game = Game::new(soccer_game, players);
+
+    let (allocations, elapsed) = measure(|| {
+        for _ in 0..FRAMES {
+            for _ in 0..RECEIVERS {
+                let frame = message.clone().to_bytes();
+                std::hint::black_box(frame);
+            }
+        }
+    });
+    report("cloned", allocations, elapsed);
+
+    let mut receivers: Vec<_> = (0..RECEIVERS).map(|_| game.subscribe()).collect();
+    let (allocations, elapsed) = measure(|| {
+        for _ in 0..FRAMES {
+            game.broadcast(message.clone());
+            for receiver in &mut receivers {
+                if let Ok(frame) = receiver.try_recv() {
+                    std::hint::black_box(frame.to_vec());
+                }
+            }
+        }
+    });
+    report("shared", allocations, elapsed);
+}
+
+fn measure(run: impl FnOnce()) -> (usize, std::time::Duration) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    run();
+    return (
+        ALLOCATIONS.load(Ordering::Relaxed) - before,
+        started.elapsed(),
+    );
+}
+
+fn report(name: &str, allocations: usize, elapsed: std::time::Duration) {
+    println!(
+        "{}: {} allocations ({:.1} per frame per receiver), {:?}",
+        name,
+        allocations,
+        allocations as f64 / (FRAMES * RECEIVERS) as f64,
+        elapsed
+    );
+}
@@ -0,0 +1,92 @@
+use rust_backend::message::{ByteOrder, ProtocolVersion, SoccerMoveMessage, SoccerStateSnapshot};
+
+// A v5 State payload built field by field in either byte order: one own
+// puck, one puck and one ball, one power-up and one effect.
+fn v5_state(order: ByteOrder) -> Vec<u8> {
+    let mut data = vec![];
+    let put = |data: &mut Vec<u8>, le: &[u8]| match order {
+        ByteOrder::Little => data.extend_from_slice(le),
+        ByteOrder::Big => data.extend(le.iter().rev()),
+    };
+    put(&mut data, &77u32.to_le_bytes());
+    put(&mut data, &123_456_789u64.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(&[1, 0]);
+    for value in [10.5f32, -20.25, 1.0, -1.0] {
+        put(&mut data, &value.to_le_bytes());
+    }
+    data.extend_from_slice(&[1, 1]);
+    for value in [10.5f32, -20.25, 0.5, 2.0, 300.0, 400.0, 0.0, -3.0] {
+        put(&mut data, &value.to_le_bytes());
+    }
+    data.push(1);
+    put(&mut data, &9u32.to_le_bytes());
+    data.push(1);
+    for value in [50.0f32, 60.0] {
+        put(&mut data, &value.to_le_bytes());
+    }
+    data.extend_from_slice(&[1, 0, 1]);
+    put(&mut data, &2500u32.to_le_bytes());
+    put(&mut data, &42u32.to_le_bytes());
+    put(&mut data, &800u32.to_le_bytes());
+    return data;
+}
+
+// State and SoccerMove payloads round-trip in both byte orders: a
+// big-endian payload converts to exactly the little-endian one and decodes
+// to the same snapshot, and converting back restores it.
+fn main() {
+    let little = v5_state(ByteOrder::Little);
+    let big = v5_state(ByteOrder::Big);
+    assert_ne!(little, big);
+    let expected = SoccerStateSnapshot::decode(ProtocolVersion::V5, &little).unwrap();
+    assert_eq!(expected.tick, 77);
+    assert_eq!(expected.own_pucks[0].x, 10.5);
+    assert_eq!(expected.power_ups[0].id, 9);
+    assert_eq!(expected.effects[0].remaining_ms, 2500);
+    assert_eq!(expected.boost_cooldown_ms, 800);
+
+    let mut converted = big.clone();
+    assert!(ByteOrder::Big.swap_state(ProtocolVersion::V5, &mut converted));
+    assert_eq!(converted, little);
+    let decoded = SoccerStateSnapshot::decode(ProtocolVersion::V5, &converted).unwrap();
+    assert_eq!(decoded, expected);
+    assert!(ByteOrder::Big.swap_state(ProtocolVersion::V5, &mut converted));
+    assert_eq!(converted, big);
+    println!("v5 State round-trips big-endian");
+
+    // the v4 layout is v5 without the own pucks
+    let strip = |data: &[u8]| [&data[..13], &data[13 + 1 + 17..]].concat();
+    let (little_v4, mut big_v4) = (strip(&little), strip(&big));
+    assert!(ByteOrder::Big.swap_state(ProtocolVersion::V4, &mut big_v4));
+    assert_eq!(big_v4, little_v4);
+    println!("v4 State round-trips big-endian");
+
+    let mut truncated = big[..big.len() - 3].to_vec();
+    assert!(!ByteOrder::Big.swap_state(ProtocolVersion::V5, &mut truncated));
+    let mut unchanged = little.clone();
+    assert!(ByteOrder::Little.swap_state(ProtocolVersion::V5, &mut unchanged));
+    assert_eq!(unchanged, little);
+
+    let message = SoccerMoveMessage {
+        vx: 1.5,
+        vy: -2.0,
+        target: 3,
+        angular: 0.25,
+        seq: 1000,
+    };
+    for order in [ByteOrder::Little, ByteOrder::Big] {
+        let payload = message.encode(order);
+        let vx = match order {
+            ByteOrder::Little => f32::from_le_bytes(payload[..4].try_into().unwrap()),
+            ByteOrder::Big => f32::from_be_bytes(payload[..4].try_into().unwrap()),
+        };
+        assert_eq!(vx, 1.5);
+        assert_eq!(SoccerMoveMessage::decode(order, &payload), Some(message));
+        // an older client's 9 byte move converts too
+        let short = &payload[..9];
+        let short = SoccerMoveMessage::decode(order, short).unwrap();
+        assert_eq!((short.vx, short.vy, short.target), (1.5, -2.0, 3));
+        println!("SoccerMove round-trips {}", order.as_param());
+    }
+}
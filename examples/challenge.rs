@@ -0,0 +1,116 @@
+mod common;
+
+use common::{error, next, rally, settle, welcome, Events, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::message::{ChallengeOutcome, ChallengeReceivedMessage, ErrorCode};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration, Instant};
+
+const ADDR: &str = "127.0.0.1:18105";
+
+// Lobby connections are never matched with each other, only through a
+// challenge: one left unanswered times out on both sides, one sent to a
+// target already answering another is refused, one whose target drops is
+// reported unavailable, and an accepted one puts both players in the same
+// new game.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        challenge_timeout: Duration::from_secs(1),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (alice, mut alice_events) = join("alice").await;
+    let (bob, mut bob_events) = join("bob").await;
+    let (carol, mut carol_events) = join("carol").await;
+
+    alice.challenge("alice");
+    assert_eq!(error(&mut alice_events).await, ErrorCode::InvalidParams);
+    alice.challenge("nobody");
+    assert_eq!(error(&mut alice_events).await, ErrorCode::PlayerNotFound);
+    println!("challenging yourself or no one is refused");
+
+    let sent = Instant::now();
+    alice.challenge("bob");
+    let received = challenged(&mut bob_events).await;
+    assert_eq!(received.from, "alice");
+    assert_eq!(received.timeout_ms, 1000);
+    carol.challenge("bob");
+    assert_eq!(error(&mut carol_events).await, ErrorCode::ChallengeBusy);
+    println!("bob can only answer one challenge at a time");
+    assert_eq!(
+        result(&mut alice_events).await,
+        (received.challenge_id, ChallengeOutcome::TimedOut)
+    );
+    assert_eq!(
+        result(&mut bob_events).await,
+        (received.challenge_id, ChallengeOutcome::Withdrawn)
+    );
+    println!("unanswered challenge timed out after {:?}", sent.elapsed());
+
+    alice.challenge("bob");
+    let received = challenged(&mut bob_events).await;
+    bob.answer_challenge(received.challenge_id, false);
+    assert_eq!(
+        result(&mut alice_events).await,
+        (received.challenge_id, ChallengeOutcome::Declined)
+    );
+    println!("declined challenge reported to alice");
+
+    let (dave, mut dave_events) = join("dave").await;
+    carol.challenge("dave");
+    let received = challenged(&mut dave_events).await;
+    dave.close();
+    assert_eq!(
+        result(&mut carol_events).await,
+        (received.challenge_id, ChallengeOutcome::Unavailable)
+    );
+    println!("carol told dave went away");
+
+    alice.challenge("bob");
+    let received = challenged(&mut bob_events).await;
+    bob.answer_challenge(received.challenge_id, true);
+    let alice_game = welcome(&mut alice_events).await.game_id;
+    let bob_game = welcome(&mut bob_events).await.game_id;
+    assert_eq!(alice_game, bob_game);
+    println!("accepted challenge started game {}", alice_game);
+
+    // a late answer to a challenge that is over is ignored
+    carol.answer_challenge(received.challenge_id, true);
+    carol.challenge("bob");
+    assert_eq!(error(&mut carol_events).await, ErrorCode::PlayerNotFound);
+    println!("bob left the lobby for the game");
+}
+
+async fn join(name: &str) -> (GameClient, Events) {
+    let options = ClientOptions {
+        lobby: true,
+        ..common::options()
+    };
+    let (client, mut events) = common::connect(ADDR, name, options).await;
+    settle(&client, &mut events).await;
+    return (client, events);
+}
+
+async fn challenged(events: &mut Events) -> ChallengeReceivedMessage {
+    return next(events, |event| match event {
+        ClientEvent::ChallengeReceived(challenge) => Some(challenge),
+        _ => None,
+    })
+    .await;
+}
+
+async fn result(events: &mut Events) -> (u32, ChallengeOutcome) {
+    return next(events, |event| match event {
+        ClientEvent::ChallengeResult(result) => Some((result.challenge_id, result.outcome)),
+        _ => None,
+    })
+    .await;
+}
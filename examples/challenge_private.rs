@@ -0,0 +1,141 @@
+mod common;
+
+use common::{error, next, rally, settle, welcome, Events, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::game::GameLogic;
+use rust_backend::message::{ChallengeReceivedMessage, ErrorCode};
+use rust_backend::server::{Server, ServerConfig, ServerState};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18163";
+const TRIO: u8 = 8;
+
+// An empty game with room for three.
+struct Trio;
+
+impl GameLogic for Trio {
+    fn game_type(&self) -> u8 {
+        return TRIO;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![TRIO];
+    }
+    fn max_players(&self) -> usize {
+        return 3;
+    }
+}
+
+fn trio(_state: &ServerState, _practice: bool) -> Box<dyn GameLogic> {
+    return Box::new(Trio);
+}
+
+// A challenge names its target, but finds them by player id: a signed-in
+// alice challenging "alice" reaches the guest of that name, not herself.
+// The game a challenge starts is private, so a free seat in it is neither
+// matched nor joinable by id, and a challenge accepted on a full server
+// ends both connections with ServerFull like a join would.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        max_games: Some(2),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    server.register_mode("trio", TRIO, trio);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (signed_in, _signed_in_events) = join("alice", RALLY, Some("alice-token")).await;
+    let (guest, mut guest_events) = join("alice", RALLY, None).await;
+    signed_in.challenge("alice");
+    let received = challenged(&mut guest_events).await;
+    guest.answer_challenge(received.challenge_id, true);
+    let game_id = welcome(&mut guest_events).await.game_id as usize;
+    let game = games.read().await[&game_id].clone();
+    let game = game.read().await;
+    assert_ne!(game.players[0].id, game.players[1].id);
+    println!(
+        "{} challenged \"alice\" and got {}",
+        game.players[0].id, game.players[1].id
+    );
+    drop(game);
+
+    let (bob, _bob_events) = join("bob", TRIO, None).await;
+    let (carol, mut carol_events) = join("carol", TRIO, None).await;
+    bob.challenge("carol");
+    let received = challenged(&mut carol_events).await;
+    carol.answer_challenge(received.challenge_id, true);
+    let started = welcome(&mut carol_events).await;
+    let private = games.read().await[&(started.game_id as usize)].clone();
+    assert!(private.read().await.private);
+
+    let matched = ClientOptions {
+        mode: Some(TRIO),
+        ..common::options()
+    };
+    let (dave, mut dave_events) = common::connect(ADDR, "dave", matched).await;
+    settle(&dave, &mut dave_events).await;
+    assert_eq!(private.read().await.players.len(), 2);
+    println!("dave was queued rather than matched into the free seat");
+    let named = ClientOptions {
+        mode: Some(TRIO),
+        game: Some(started.game_id as usize),
+        game_token: Some(started.game_token.clone()),
+        ..common::options()
+    };
+    let (_erin, mut erin_events) = common::connect(ADDR, "erin", named).await;
+    assert_eq!(error(&mut erin_events).await, ErrorCode::GameFull);
+    assert_eq!(private.read().await.players.len(), 2);
+    println!("erin was refused the free seat by id");
+
+    let (frank, mut frank_events) = join("frank", RALLY, None).await;
+    let (grace, mut grace_events) = join("grace", RALLY, None).await;
+    frank.challenge("grace");
+    let received = challenged(&mut grace_events).await;
+    grace.answer_challenge(received.challenge_id, true);
+    assert_eq!(error(&mut grace_events).await, ErrorCode::ServerFull);
+    assert_eq!(error(&mut frank_events).await, ErrorCode::ServerFull);
+    for client in [&frank, &grace] {
+        let closed = async {
+            while client.is_connected() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("still connected after ServerFull");
+    }
+    println!("a challenge accepted on a full server closed both sides");
+}
+
+async fn join(name: &str, mode: u8, auth_token: Option<&str>) -> (GameClient, Events) {
+    let options = ClientOptions {
+        mode: Some(mode),
+        lobby: true,
+        auth_token: auth_token.map(str::to_string),
+        ..common::options()
+    };
+    let (client, mut events) = common::connect(ADDR, name, options).await;
+    settle(&client, &mut events).await;
+    return (client, events);
+}
+
+async fn challenged(events: &mut Events) -> ChallengeReceivedMessage {
+    return next(events, |event| match event {
+        ClientEvent::ChallengeReceived(challenge) => Some(challenge),
+        _ => None,
+    })
+    .await;
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{StepClock, Steps};
+use rust_backend::game::{Game, GamePhase};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Steps a game with its clock jumping back a second midway. The step across
+// the jump must see no time pass, and the steps after it must carry on at
+// the normal rate rather than wait for the clock to catch up.
+fn main() {
+    let start = Instant::now() + Duration::from_secs(10);
+    let clock = Arc::new(StepClock::new(start));
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(Steps::default(), players);
+    game.set_clock(clock.clone());
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        game.update();
+    }
+
+    let mut now = start;
+    for step_ms in [16i64, 16, -1000, 16, 16] {
+        let step = Duration::from_millis(step_ms.unsigned_abs());
+        now = match step_ms < 0 {
+            true => now - step,
+            false => now + step,
+        };
+        clock.set(now);
+        game.update();
+    }
+    let elapsed = &game.downcast::<Steps>().unwrap().elapsed;
+    // the step that started play, then the five above
+    assert_eq!(elapsed, &[0.0, 16.0, 16.0, 0.0, 16.0, 16.0]);
+    println!("steps across a backwards clock: {:?}", elapsed);
+}
@@ -0,0 +1,391 @@
+// Fixtures the examples share: stand-in games, a clock moved by hand, and
+// helpers for connecting and waiting on what a connection is sent, through
+// the client SDK or a raw websocket. Each example uses some of them.
+#![allow(dead_code)]
+
+use futures::{SinkExt, Stream, StreamExt};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::game::{Clock, Game, GameLogic, Games};
+use rust_backend::message::{
+    ByteOrder, ErrorCode, ErrorMessage, EventMessage, HelloMessage, MatchPhase, MessageType,
+    ProtocolVersion, SoccerStateSnapshot, WelcomeMessage, WsMessage,
+};
+use rust_backend::server::ServerState;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+pub const RALLY: u8 = 7;
+// big enough that a peer who stops reading fills its socket in a few ticks
+pub const STATE_BYTES: usize = 256 * 1024;
+
+pub type Events = Pin<Box<dyn Stream<Item = ClientEvent> + Send>>;
+pub type States = Pin<Box<dyn Stream<Item = SoccerStateSnapshot> + Send>>;
+pub type RawStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// A game with nothing in it, for examples about everything around one.
+pub struct Empty;
+
+impl GameLogic for Empty {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![RALLY];
+    }
+}
+
+// The rally mode's factory, for register_mode.
+pub fn rally(_state: &ServerState, _practice: bool) -> Box<dyn GameLogic> {
+    return Box::new(Empty);
+}
+
+// An empty game whose State is STATE_BYTES long.
+pub struct Bulky;
+
+impl GameLogic for Bulky {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![RALLY; STATE_BYTES];
+    }
+}
+
+// Keeps the elapsed time of every step it is given.
+#[derive(Default)]
+pub struct Steps {
+    pub elapsed: Vec<f64>,
+}
+
+impl GameLogic for Steps {
+    fn game_type(&self) -> u8 {
+        return 0;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, elapsed: f64) {
+        self.elapsed.push(elapsed);
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+}
+
+// Moves one unit per ms of play, so its position is how much time it was
+// handed.
+#[derive(Default)]
+pub struct Drift {
+    pub x: f64,
+}
+
+impl GameLogic for Drift {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, elapsed: f64) {
+        self.x += elapsed;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return self.x.to_le_bytes().to_vec();
+    }
+}
+
+// How far a Drift game has moved.
+pub async fn drifted(game: &Arc<RwLock<Game>>) -> f64 {
+    let game = game.read().await;
+    return game.logic.as_any().downcast_ref::<Drift>().unwrap().x;
+}
+
+// A clock the example moves by hand, including backwards.
+pub struct StepClock(Mutex<Instant>);
+
+impl StepClock {
+    pub fn new(at: Instant) -> Self {
+        return StepClock(Mutex::new(at));
+    }
+    pub fn set(&self, at: Instant) {
+        *self.0.lock().unwrap() = at;
+    }
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for StepClock {
+    fn now(&self) -> Instant {
+        return *self.0.lock().unwrap();
+    }
+}
+
+// A corrupt game panics whichever server task finds it; this ends the run
+// there instead of carrying on without that task.
+pub fn exit_on_panic() {
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report(info);
+        std::process::exit(101);
+    }));
+}
+
+// Client options for a rally player that doesn't reconnect or poll, so
+// the example sees only what it asks for.
+pub fn options() -> ClientOptions {
+    return ClientOptions {
+        mode: Some(RALLY),
+        reconnect: false,
+        state_poll_interval: None,
+        time_sync_interval: None,
+        ..ClientOptions::default()
+    };
+}
+
+pub async fn connect(addr: &str, name: &str, options: ClientOptions) -> (GameClient, Events) {
+    let client = GameClient::connect(&format!("ws://{}/", addr), name, options)
+        .await
+        .unwrap();
+    let events: Events = Box::pin(client.subscribe_events());
+    return (client, events);
+}
+
+// Waits for the server to answer a Ping, which it does once it has the
+// connection where it is going: queued, in a game or watching.
+pub async fn settle(client: &GameClient, events: &mut Events) {
+    client.ping();
+    next(events, |event| match event {
+        ClientEvent::Pong { .. } => Some(()),
+        _ => None,
+    })
+    .await;
+}
+
+// Sends Ready and waits for the first State of the match it starts,
+// within five seconds. Moves sent before then are refused by the ready
+// check, and after it until the resume countdown ends.
+pub async fn kick_off(client: &GameClient, states: &mut States) -> SoccerStateSnapshot {
+    client.ready();
+    let read = async {
+        while let Some(snapshot) = states.next().await {
+            if snapshot.match_phase == Some(MatchPhase::Playing) {
+                return snapshot;
+            }
+        }
+        panic!("connection closed");
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("the match never started");
+}
+
+// The first event pick takes, within five seconds.
+pub async fn next<T>(events: &mut Events, mut pick: impl FnMut(ClientEvent) -> Option<T>) -> T {
+    let read = async {
+        while let Some(event) = events.next().await {
+            if let Some(picked) = pick(event) {
+                return picked;
+            }
+        }
+        panic!("connection closed");
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("event never came");
+}
+
+pub async fn error(events: &mut Events) -> ErrorCode {
+    return next(events, |event| match event {
+        ClientEvent::Message(MessageType::Error, payload) => {
+            let error = WsMessage {
+                msg_type: MessageType::Error,
+                payload,
+            };
+            Some(error.decode::<ErrorMessage>().unwrap().code)
+        }
+        _ => None,
+    })
+    .await;
+}
+
+pub async fn welcome(events: &mut Events) -> WelcomeMessage {
+    return next(events, |event| match event {
+        ClientEvent::Welcome(welcome) => Some(welcome),
+        _ => None,
+    })
+    .await;
+}
+
+// The game a player called name ends up in, once it does, within five
+// seconds.
+pub async fn game_of(games: &Games, name: &str) -> Option<(usize, Arc<RwLock<Game>>)> {
+    let find = async {
+        loop {
+            for (id, game) in games.read().await.iter() {
+                if game.read().await.players.iter().any(|p| p.name == name) {
+                    return (*id, Arc::clone(game));
+                }
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    return timeout(Duration::from_secs(5), find).await.ok();
+}
+
+// A raw websocket to addr with query, past the handshake.
+pub async fn raw_connect(addr: &str, query: &str) -> RawStream {
+    let url = format!("ws://{}/?{}", addr, query);
+    let (stream, _) = connect_async(url.as_str()).await.unwrap();
+    return stream;
+}
+
+// raw_connect speaking protocol, with the Hello v6 and later open with
+// asking for binary State in the same version.
+pub async fn raw_connect_as(addr: &str, query: &str, protocol: ProtocolVersion) -> RawStream {
+    let mut request = format!("ws://{}/?{}", addr, query)
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static(protocol.as_str()),
+    );
+    let (mut stream, _) = connect_async(request).await.unwrap();
+    if protocol >= ProtocolVersion::V6 {
+        let hello = HelloMessage {
+            protocol: protocol as u8,
+            build: "example".to_string(),
+            state_version: protocol as u8,
+            format: 0,
+            byte_order: ByteOrder::Little.code(),
+            role: 0,
+        };
+        let hello = WsMessage::from_payload(MessageType::Hello, &hello);
+        stream
+            .send(Message::Binary(hello.to_bytes()))
+            .await
+            .unwrap();
+        raw_next(&mut stream, &[MessageType::Hello]).await;
+    }
+    return stream;
+}
+
+// The next frame of one of types on a raw websocket, within five seconds.
+pub async fn raw_next(stream: &mut RawStream, types: &[MessageType]) -> WsMessage {
+    let read = async {
+        while let Some(Ok(message)) = stream.next().await {
+            if let Message::Binary(data) = message {
+                let message = WsMessage::from_bytes(&data)
+                    .and_then(unwrap_event)
+                    .filter(|message| types.iter().any(|t| *t as u8 == message.msg_type as u8));
+                if let Some(message) = message {
+                    return message;
+                }
+            }
+        }
+        panic!("closed before a {:?}", types);
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("frame never came");
+}
+
+// A broadcast frame as the game sent it, out of its Event envelope.
+fn unwrap_event(message: WsMessage) -> Option<WsMessage> {
+    if message.msg_type as u8 != MessageType::Event as u8 {
+        return Some(message);
+    }
+    let event = message.decode::<EventMessage>()?;
+    return WsMessage::from_bytes(&event.frame);
+}
+
+pub async fn raw_welcome(stream: &mut RawStream) -> WelcomeMessage {
+    let welcome = raw_next(stream, &[MessageType::Welcome]).await;
+    return welcome.decode::<WelcomeMessage>().unwrap();
+}
+
+// Connects with query, ready to play, and returns its Welcome, along with
+// the task that keeps the connection open; aborting it drops the
+// connection.
+pub async fn join_ready(addr: &str, query: &str) -> (WelcomeMessage, JoinHandle<()>) {
+    let mut stream = raw_connect(addr, query).await;
+    let ready = WsMessage {
+        msg_type: MessageType::Ready,
+        payload: vec![],
+    };
+    stream
+        .send(Message::Binary(ready.to_bytes()))
+        .await
+        .unwrap();
+    let welcome = raw_welcome(&mut stream).await;
+    let link = tokio::spawn(async move { while let Some(Ok(_)) = stream.next().await {} });
+    return (welcome, link);
+}
+
+// Reads the rest of a raw websocket in the background, so the server never
+// finds it full, and keeps it open until the example ends.
+pub fn drain(mut stream: RawStream) {
+    tokio::spawn(async move { while let Some(Ok(_)) = stream.next().await {} });
+}
+
+// The body of a plain GET to an HTTP listener at addr.
+pub async fn get(addr: &str, path: &str) -> String {
+    return fetch(addr, path).await.1;
+}
+
+// The status code and body of a plain GET to an HTTP listener at addr.
+pub async fn fetch(addr: &str, path: &str) -> (u16, String) {
+    return fetch_as(addr, path, None).await;
+}
+
+// Like fetch, sending token as a bearer credential when there is one.
+pub async fn fetch_as(addr: &str, path: &str, token: Option<&str>) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let authorization = token
+        .map(|token| format!("Authorization: Bearer {}\r\n", token))
+        .unwrap_or_default();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n",
+        path, authorization
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0);
+    return (status, body.to_string());
+}
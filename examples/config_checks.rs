@@ -0,0 +1,87 @@
+use rust_backend::config::{validate, Config, ConfigError};
+use rust_backend::server::ServerConfig;
+
+// Settings that are each fine alone but can't work together are refused
+// when the config is built, with the reason, instead of surfacing later as
+// a broken or clamped game.
+fn main() {
+    let refused = [
+        ("[server]\ntick_rate = 0", "tick_rate"),
+        ("[server]\ntick_rate = 1001", "tick_rate"),
+        ("[server]\nmax_state_rate_hz = 0", "max_state_rate_hz"),
+        (
+            "[server]\nmin_state_rate_hz = 30\ndefault_state_rate_hz = 10",
+            "default_state_rate_hz",
+        ),
+        (
+            "[server]\nmax_message_size = 1024\nmax_frame_size = 4096",
+            "max_frame_size",
+        ),
+        ("[server]\ngame_state_interval_secs = 5", "persist_path"),
+        (
+            "[server]\nauth_url = \"ftp://auth\"\nauth_scheme = \"Bearer\"",
+            "auth_url",
+        ),
+        (
+            "[server]\nauth_url = \"not a url\"\nauth_scheme = \"Bearer\"",
+            "auth_url",
+        ),
+        (
+            "[server]\nauth_url = \"http://127.0.0.1:9000/validate\"",
+            "auth_scheme",
+        ),
+        ("[soccer]\npucks_per_team = 0", "pucks_per_team"),
+        ("[soccer]\npucks_per_team = 11", "pucks_per_team"),
+        ("[soccer]\nballs = 6", "balls"),
+        ("[soccer]\nwidth = 300\nheight = 300", "too small"),
+        ("[soccer]\npuck_radius = 100", "too small"),
+        ("[soccer]\npuck_radius = -5", "puck_radius"),
+        ("[soccer]\nbounce_decay = 0", "bounce_decay"),
+    ];
+    for (toml, expected) in refused {
+        match server_config(toml) {
+            Err(ConfigError::Invalid(reason)) => {
+                assert!(reason.contains(expected), "{:?}: {}", toml, reason);
+                println!("{:<60} {}", toml.replace('\n', " "), reason);
+            }
+            other => panic!("{:?} was not refused: {:?}", toml, other.map(|_| ())),
+        }
+    }
+
+    let accepted = [
+        "",
+        "[server]\ntick_rate = 1000",
+        "[server]\nmin_state_rate_hz = 10\ndefault_state_rate_hz = 10\nmax_state_rate_hz = 10",
+        "[server]\npersist_path = \"asyncws.state\"\ngame_state_interval_secs = 5",
+        "[server]\nauth_url = \"http://127.0.0.1:9000/validate\"\nauth_scheme = \"Bearer\"",
+        "[server]\nauth_url = \"https://auth.example.com/validate\"\nauth_scheme = \"\"",
+        "[soccer]\npucks_per_team = 10\nballs = 5",
+    ];
+    for toml in accepted {
+        if let Err(e) = server_config(toml) {
+            panic!("{:?} was refused: {}", toml, e);
+        }
+    }
+    println!("{} sound configs accepted", accepted.len());
+
+    // a config built in code goes through the same checks
+    let config = ServerConfig {
+        tick_rate: 0,
+        ..ServerConfig::default()
+    };
+    assert!(validate(&config).is_err());
+    let mut config = ServerConfig::default();
+    config.soccer.puck_radius = -5.0;
+    match validate(&config) {
+        Err(ConfigError::Invalid(reason)) => assert!(reason.contains("puck_radius"), "{}", reason),
+        other => panic!(
+            "a negative puck_radius was not refused: {:?}",
+            other.map(|_| ())
+        ),
+    }
+    assert!(validate(&ServerConfig::default()).is_ok());
+}
+
+fn server_config(toml: &str) -> Result<ServerConfig, ConfigError> {
+    return Config::from_toml(toml, []).and_then(|config| config.server_config());
+}
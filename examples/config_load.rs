@@ -0,0 +1,68 @@
+use rust_backend::config::Config;
+use rust_backend::game::{SoccerGame, SoccerGameConfig};
+use rust_backend::server::ServerConfig;
+use std::fs;
+
+const SAMPLE: &str = r#"
+[server]
+addr = "127.0.0.1:9999"
+tick_rate = 30
+
+[soccer]
+width = 800
+height = 500
+puck_radius = 12
+puck_damping = 0.3
+pucks_per_team = 3
+"#;
+
+// A config file's settings reach the server config and every SoccerGame
+// built from it, an ASYNCWS__ variable beats the file, and with no file at
+// all everything keeps its default.
+fn main() {
+    let dir = std::env::temp_dir();
+    let missing = Config::load(&dir.join("asyncws-no-such-config.toml")).unwrap();
+    let server = missing.server_config().unwrap();
+    let defaults = ServerConfig::default();
+    assert_eq!(server.addr, defaults.addr);
+    assert_eq!(server.tick_rate, defaults.tick_rate);
+    assert_eq!(
+        missing.soccer_config().unwrap(),
+        SoccerGameConfig::default()
+    );
+    println!("no file: {} at {}Hz", server.addr, server.tick_rate);
+
+    let path = dir.join("asyncws-config-load.toml");
+    fs::write(&path, SAMPLE).unwrap();
+    let config = Config::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let server = config.server_config().unwrap();
+    assert_eq!(server.addr, "127.0.0.1:9999");
+    assert_eq!(server.tick_rate, 30);
+    let game = SoccerGame::with_config(config.soccer_config().unwrap());
+    assert_eq!((game.width, game.height), (800.0, 500.0));
+    assert_eq!(game.puck_radius, 12.0);
+    assert_eq!(game.puck_damping, 0.3);
+    assert_eq!(game.pucks.len(), 6);
+    let puck = game.pucks[0];
+    assert_eq!(game.bodies[puck].linear_damping(), 0.3);
+    println!(
+        "from the file: {} at {}Hz, a {}x{} field with {} pucks",
+        server.addr,
+        server.tick_rate,
+        game.width,
+        game.height,
+        game.pucks.len()
+    );
+
+    let env = [(
+        "ASYNCWS__SOCCER__PUCK_DAMPING".to_string(),
+        "0.5".to_string(),
+    )];
+    let config = Config::from_toml(SAMPLE, env).unwrap();
+    let game = SoccerGame::with_config(config.soccer_config().unwrap());
+    assert_eq!(game.puck_damping, 0.5);
+    assert_eq!(game.bodies[game.pucks[0]].linear_damping(), 0.5);
+    assert_eq!(game.width, 800.0);
+    println!("ASYNCWS__SOCCER__PUCK_DAMPING=0.5 over the file's 0.3");
+}
@@ -0,0 +1,144 @@
+use rust_backend::config::{Config, ConfigError, ConfigSource};
+use rust_backend::server::{startable, ServerConfig};
+
+const EXAMPLE: &str = include_str!("../config.example.toml");
+
+fn layered(
+    text: &str,
+    profile: Option<&str>,
+    env: &[(&str, &str)],
+    flags: &[(&str, &str)],
+) -> Result<Config, ConfigError> {
+    let env: Vec<(String, String)> = env
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    let flags: Vec<(String, String)> = flags
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    return Config::layered(text, profile, env, &flags);
+}
+
+fn server_config(
+    text: &str,
+    profile: Option<&str>,
+    env: &[(&str, &str)],
+    flags: &[(&str, &str)],
+) -> Result<ServerConfig, ConfigError> {
+    return layered(text, profile, env, flags).and_then(|config| config.server_config());
+}
+
+// The shipped config is sound as it is and under every profile but prod,
+// which is refused until its TLS files exist and, since the server doesn't
+// terminate TLS itself, isn't started even then. Flags beat environment
+// variables, which beat the profile, which beats the file. Unknown keys are
+// skipped with a warning saying where they were, and a bad value or missing
+// requirement is reported by its key and profile.
+fn main() {
+    for profile in [None, Some("dev"), Some("test")] {
+        let config = server_config(EXAMPLE, profile, &[], &[]).unwrap();
+        let file = layered(EXAMPLE, profile, &[], &[]).unwrap();
+        assert!(file.warnings.is_empty(), "{:?}", file.warnings);
+        println!("config.example.toml as {:?}: {}", profile, config.addr);
+    }
+    let dev = server_config(EXAMPLE, Some("dev"), &[], &[]).unwrap();
+    assert_eq!(dev.auth_url, None);
+    assert_eq!(dev.log_level.as_deref(), Some("debug"));
+
+    match server_config(EXAMPLE, Some("prod"), &[], &[]) {
+        Err(ConfigError::InProfile(profile, e)) => {
+            assert_eq!(profile, "prod");
+            assert!(e.to_string().contains("tls_cert '/etc/asyncws/cert.pem'"));
+            println!("prod without its certificate: {}", e);
+        }
+        other => panic!("prod started without TLS files: {:?}", other.map(|_| ())),
+    }
+    let dir = std::env::temp_dir();
+    let (cert, key) = (dir.join("asyncws-cert.pem"), dir.join("asyncws-key.pem"));
+    std::fs::write(&cert, "cert").unwrap();
+    std::fs::write(&key, "key").unwrap();
+    let (cert, key) = (cert.display().to_string(), key.display().to_string());
+    let flags = [
+        ("server.tls_cert", cert.as_str()),
+        ("server.tls_key", key.as_str()),
+    ];
+    let prod = server_config(EXAMPLE, Some("prod"), &[], &flags).unwrap();
+    assert_eq!(prod.tls.as_ref().unwrap().cert.display().to_string(), cert);
+    assert!(prod.auth_url.is_some());
+    assert_eq!(prod.max_connections_per_ip, Some(4));
+    let refused = startable(&prod).unwrap_err();
+    assert!(refused.contains("tls_cert"), "{}", refused);
+    println!(
+        "prod with its files in place loads, but won't start: {}",
+        refused
+    );
+
+    // a requirement can't be dropped from underneath the profile
+    let no_auth = [("ASYNCWS__SERVER__AUTH_URL", "\"\"")];
+    let error = server_config(EXAMPLE, Some("prod"), &no_auth, &flags).unwrap_err();
+    assert!(error.to_string().contains("auth_url"), "{}", error);
+    println!("prod without auth: {}", error);
+
+    let text = "[server]\ntick_rate = 30\n\n[profiles.slow.server]\ntick_rate = 40\n";
+    let env = [("ASYNCWS__SERVER__TICK_RATE", "50")];
+    let flags = [("server.tick_rate", "60")];
+    let rate = |profile, env, flags| {
+        return server_config(text, profile, env, flags).unwrap().tick_rate;
+    };
+    assert_eq!(rate(Some("slow"), &env, &flags), 60);
+    assert_eq!(rate(Some("slow"), &env, &[]), 50);
+    assert_eq!(rate(Some("slow"), &[], &[]), 40);
+    assert_eq!(rate(None, &[], &[]), 30);
+    assert_eq!(rate(None, &[("ASYNCWS_PROFILE", "slow")], &[]), 40);
+    assert_eq!(
+        server_config("", None, &[], &[]).unwrap().tick_rate,
+        ServerConfig::default().tick_rate
+    );
+    println!("flag 60 > env 50 > profile 40 > file 30 > default");
+
+    let text = "[server]\nadr = \"0.0.0.0:1\"\n\n[profiles.dev.soccer]\nwidht = 900\n";
+    let config = layered(
+        text,
+        Some("dev"),
+        &[("ASYNCWS__SERVER__NOPE", "1")],
+        &[("soccer.power_ups.often", "2")],
+    )
+    .unwrap();
+    for expected in [
+        "server.adr in the file",
+        "soccer.widht in profile dev",
+        "server.nope in ASYNCWS__SERVER__NOPE",
+        "soccer.power_ups.often in --soccer.power_ups.often",
+    ] {
+        assert!(
+            config.warnings.iter().any(|w| w.contains(expected)),
+            "{:?} missing {}",
+            config.warnings,
+            expected
+        );
+    }
+    assert!(config.server_config().is_ok());
+    println!("unknown keys skipped: {:?}", config.warnings);
+
+    let text = "[profiles.dev.server]\ntick_rate = \"fast\"\n";
+    let error = server_config(text, Some("dev"), &[], &[]).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("server.tick_rate") && message.contains("profile dev"));
+    println!("{}", message);
+    let error = server_config(text, Some("staging"), &[], &[]).unwrap_err();
+    assert!(error.to_string().contains("'staging'"));
+    println!("{}", error);
+
+    let args = ["my.toml", "--profile", "prod", "--server.addr=0.0.0.0:1"];
+    let source = ConfigSource::from_args(args.map(String::from)).unwrap();
+    assert_eq!(source.path.display().to_string(), "my.toml");
+    assert_eq!(source.profile.as_deref(), Some("prod"));
+    assert_eq!(
+        source.overrides,
+        [("server.addr".to_string(), "0.0.0.0:1".to_string())]
+    );
+    assert!(ConfigSource::from_args(["--verbose".to_string()]).is_err());
+    assert!(ConfigSource::from_args(["--server.addr".to_string()]).is_err());
+    println!("command line: {:?}", source);
+}
@@ -0,0 +1,39 @@
+use rust_backend::game::{GameLogic, GameRng, SoccerGame};
+
+const STEPS: usize = 20_000;
+const SPEED: f32 = 20_000.0;
+
+// Fires every puck and ball off in a random direction at a speed far beyond
+// anything a client can send, step after step, and checks nothing ever ends
+// up outside the walls (nets included). The watchdog would quietly put an
+// escaped body back, so it must not have fired either.
+fn main() {
+    let mut game = SoccerGame::new();
+    let mut rng = GameRng::new(7);
+    let bodies: Vec<_> = game.pucks.iter().chain(&game.balls).copied().collect();
+    for step in 0..STEPS {
+        for handle in &bodies {
+            let angle = rng.next_f32() * std::f32::consts::TAU;
+            game.apply_move(*handle, angle.cos() * SPEED, angle.sin() * SPEED, 0.0);
+        }
+        game.update(1000.0 / 60.0);
+        for (i, handle) in bodies.iter().enumerate() {
+            let pos = *game.bodies[*handle].translation();
+            assert!(
+                pos.x.abs() <= game.bounds.x && pos.y.abs() <= game.bounds.y,
+                "body {} escaped at step {}: [{}, {}]",
+                i,
+                step,
+                pos.x,
+                pos.y
+            );
+        }
+        assert_eq!(game.watchdog_resets, 0, "watchdog fired at step {}", step);
+    }
+    println!(
+        "{} bodies stayed inside {:?} for {} steps",
+        bodies.len(),
+        game.bounds,
+        STEPS
+    );
+}
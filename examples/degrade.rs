@@ -0,0 +1,282 @@
+mod common;
+
+use common::{rally, raw_connect_as, RawStream, RALLY};
+use futures::stream::SplitStream;
+use futures::{SinkExt, StreamExt};
+use rust_backend::degrade::{self, DegradeConfig, Degrader};
+use rust_backend::message::{
+    DegradeLevel, MessageType, PingMessage, ProtocolVersion, SnapshotHeader, SubscribeMessage,
+    WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18130";
+const THROUGH_AND_BACK: [DegradeLevel; 7] = [
+    DegradeLevel::Full,
+    DegradeLevel::HalfRate,
+    DegradeLevel::Quantized,
+    DegradeLevel::InterestOnly,
+    DegradeLevel::Quantized,
+    DegradeLevel::HalfRate,
+    DegradeLevel::Full,
+];
+
+// Bytes a snapshot costs at each level, and how many ticks apart they go.
+fn snapshot_cost(level: DegradeLevel) -> (usize, u64) {
+    return match level {
+        DegradeLevel::Full => (1000, 1),
+        DegradeLevel::HalfRate => (1000, 2),
+        DegradeLevel::Quantized => (500, 2),
+        DegradeLevel::InterestOnly => (150, 2),
+    };
+}
+
+// A link that gets capacity bytes through a tick, with every snapshot
+// queued behind what it hasn't sent yet.
+fn drive(degrader: &mut Degrader, link: &mut VecDeque<usize>, capacity: usize, ticks: u64) {
+    for tick in 0..ticks {
+        let level = degrader.observe(link.len(), None);
+        let (bytes, every) = snapshot_cost(level);
+        if tick % every == 0 {
+            link.push_back(bytes);
+        }
+        let mut budget = capacity;
+        while let Some(front) = link.front_mut() {
+            if *front > budget {
+                *front -= budget;
+                break;
+            }
+            budget -= *front;
+            link.pop_front();
+        }
+    }
+}
+
+// The levels a sequence of observations went through, without repeats.
+fn levels_seen(seen: &mut Vec<DegradeLevel>, level: DegradeLevel) {
+    if seen.last() != Some(&level) {
+        seen.push(level);
+    }
+}
+
+// A connection that can't keep up is stepped down a level at a time, full
+// rate to half rate to quantized to interest only, and back up in the
+// same order once it recovers; one hovering at the edge doesn't change
+// level at all. A v9 client whose Pongs come back slowly sees the levels go
+// by in its snapshot headers, and the server counts it at each one, though
+// it sends frames all the while. A v8 one goes no further than half rate.
+#[tokio::main]
+async fn main() {
+    let config = DegradeConfig {
+        down_after_ticks: 10,
+        up_after_ticks: 60,
+        ..DegradeConfig::default()
+    };
+    let mut degrader = Degrader::new(config.clone(), DegradeLevel::InterestOnly);
+    for tick in 0..600 {
+        let queued = if tick % 2 == 0 { 50 } else { 0 };
+        degrader.observe(queued, None);
+    }
+    assert_eq!(degrader.level(), DegradeLevel::Full);
+    println!("a connection struggling every other tick stays at full");
+
+    let mut link = VecDeque::new();
+    let mut seen = vec![degrader.level()];
+    for _ in 0..60 {
+        drive(&mut degrader, &mut link, 50, 10);
+        levels_seen(&mut seen, degrader.level());
+    }
+    assert_eq!(degrader.level(), DegradeLevel::InterestOnly);
+    for _ in 0..60 {
+        drive(&mut degrader, &mut link, 5000, 10);
+        levels_seen(&mut seen, degrader.level());
+    }
+    assert_eq!(seen, THROUGH_AND_BACK);
+    println!("throttled link went through {:?}", seen);
+    drop(degrader);
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        server_ping_interval: None,
+        degrade: Some(DegradeConfig {
+            rtt_high: Duration::from_millis(150),
+            rtt_low: Duration::from_millis(60),
+            down_after_ticks: 6,
+            up_after_ticks: 20,
+            probe_every: Duration::from_millis(100),
+            rtt_max_age: Duration::from_secs(1),
+            ..DegradeConfig::default()
+        }),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let stream = raw_connect_as(
+        ADDR,
+        "name=alice&mode=rally&practice=1",
+        ProtocolVersion::V9,
+    )
+    .await;
+    let mut link = Link::open(stream);
+    timeout(
+        Duration::from_secs(10),
+        link.read_until(DegradeLevel::InterestOnly),
+    )
+    .await
+    .expect("never degraded");
+    assert_eq!(degrade::connections_at(DegradeLevel::InterestOnly), 1);
+    link.pong_delay_ms.store(0, Ordering::Relaxed);
+    timeout(Duration::from_secs(10), link.read_until(DegradeLevel::Full))
+        .await
+        .expect("never recovered");
+    assert_eq!(link.seen, THROUGH_AND_BACK);
+    assert_eq!(degrade::connections_at(DegradeLevel::Full), 1);
+    println!(
+        "slow Pongs took the client's snapshots through {:?}",
+        link.seen
+    );
+    link.close();
+    let gone = async {
+        while degrade::connections_at(DegradeLevel::Full) > 0 {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), gone)
+        .await
+        .expect("never let go of the connection");
+
+    let stream = raw_connect_as(ADDR, "name=bob&mode=rally&practice=1", ProtocolVersion::V8).await;
+    let mut link = Link::open(stream);
+    timeout(
+        Duration::from_secs(10),
+        link.answer_for(Duration::from_secs(2)),
+    )
+    .await
+    .expect("closed while answering");
+    assert_eq!(degrade::connections_at(DegradeLevel::HalfRate), 1);
+    assert_eq!(degrade::connections_at(DegradeLevel::Quantized), 0);
+    assert_eq!(degrade::connections_at(DegradeLevel::InterestOnly), 0);
+    println!("a v8 client with the same slow Pongs stays at half rate");
+}
+
+// A raw connection that answers Pings pong_delay_ms late and sends a
+// Subscribe every 20ms besides, and keeps the levels its v9 snapshot
+// headers went through.
+struct Link {
+    stream: SplitStream<RawStream>,
+    pongs: mpsc::UnboundedSender<WsMessage>,
+    pong_delay_ms: Arc<AtomicU64>,
+    writer: JoinHandle<()>,
+    seen: Vec<DegradeLevel>,
+    // tick and level of the last State
+    last: Option<(u32, DegradeLevel)>,
+}
+
+impl Link {
+    // Starts answering 400ms late.
+    fn open(stream: RawStream) -> Self {
+        let (mut sink, stream) = stream.split();
+        let (pongs, mut to_send) = mpsc::unbounded_channel::<WsMessage>();
+        let writer = tokio::spawn(async move {
+            let subscribe = SubscribeMessage { state_rate_hz: 60 };
+            let subscribe = WsMessage::from_payload(MessageType::Subscribe, &subscribe);
+            let mut busy = interval(Duration::from_millis(20));
+            loop {
+                let message = tokio::select! {
+                    _ = busy.tick() => subscribe.clone(),
+                    pong = to_send.recv() => match pong {
+                        Some(pong) => pong,
+                        None => return,
+                    },
+                };
+                if sink
+                    .send(Message::Binary(message.to_bytes()))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+        return Link {
+            stream,
+            pongs,
+            pong_delay_ms: Arc::new(AtomicU64::new(400)),
+            writer,
+            seen: vec![],
+            last: None,
+        };
+    }
+
+    fn close(self) {
+        self.writer.abort();
+    }
+
+    async fn next(&mut self) -> WsMessage {
+        while let Some(Ok(message)) = self.stream.next().await {
+            let message = match message {
+                Message::Binary(data) => WsMessage::from_bytes(&data),
+                _ => None,
+            };
+            let message = match message {
+                Some(message) => message,
+                None => continue,
+            };
+            if let MessageType::Ping = message.msg_type {
+                let delay = self.pong_delay_ms.load(Ordering::Relaxed);
+                let ping = message.decode::<PingMessage>().unwrap();
+                let pongs = self.pongs.clone();
+                tokio::spawn(async move {
+                    sleep(Duration::from_millis(delay)).await;
+                    let _ = pongs.send(WsMessage::from_payload(MessageType::Pong, &ping));
+                });
+                continue;
+            }
+            return message;
+        }
+        panic!("closed");
+    }
+
+    async fn read_until(&mut self, until: DegradeLevel) {
+        loop {
+            let message = self.next().await;
+            if let MessageType::State = message.msg_type {
+                let (header, _) = SnapshotHeader::from_bytes_v9(&message.payload).unwrap();
+                let level = header.level.unwrap();
+                if let Some((tick, was)) = self.last {
+                    if was >= DegradeLevel::HalfRate && level >= DegradeLevel::HalfRate {
+                        assert!(header.tick - tick >= 2, "full rate at {:?}", level);
+                    }
+                }
+                self.last = Some((header.tick, level));
+                levels_seen(&mut self.seen, level);
+                if level == until {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Reads, answering Pings, for how_long.
+    async fn answer_for(&mut self, how_long: Duration) {
+        let _ = timeout(how_long, async {
+            loop {
+                self.next().await;
+            }
+        })
+        .await;
+    }
+}
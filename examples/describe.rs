@@ -0,0 +1,49 @@
+use rust_backend::game::{
+    Game, GamePhase, SoccerGame, SoccerGameConfig, SoccerPhase, SOCCER_GAME_TYPE,
+};
+use rust_backend::stats::PlayerId;
+
+// A soccer game's description, read without downcasting, reflects the
+// score and phase a scripted match left it in.
+fn main() {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(
+        SoccerGame::with_config(SoccerGameConfig::default()),
+        players,
+    );
+    let fresh = game.describe();
+    assert_eq!(fresh.game_type, SOCCER_GAME_TYPE);
+    assert_eq!(fresh.phase, "ready_check");
+    assert_eq!(fresh.max_players, 2);
+    assert_eq!(fresh.players.len(), 2);
+    assert_eq!(fresh.details["teams"][0]["score"], 0);
+
+    // alice scores twice, bob once, and the last goal's kickoff is running
+    game.phase = GamePhase::Playing;
+    game.players[1].connected = false;
+    {
+        let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+        soccer.teams[0].score = 2;
+        soccer.teams[1].score = 1;
+        soccer.phase = SoccerPhase::Kickoff { until_tick: 30 };
+    }
+    let description = game.describe();
+    assert_eq!(description.phase, "playing");
+    assert!(description.players[0].connected);
+    assert!(!description.players[1].connected);
+    assert_eq!(description.details["phase"], "kickoff");
+    assert_eq!(description.details["teams"][0]["score"], 2);
+    assert_eq!(description.details["teams"][1]["score"], 1);
+    assert_eq!(description.details["phase_ticks_left"], 30);
+    assert_eq!(description.details["balls"].as_array().unwrap().len(), 1);
+
+    let json = serde_json::to_string(&description).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap()["tick"],
+        0
+    );
+    println!("{}", json);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::StepClock;
+use rust_backend::game::{Game, GameDescription, GameLogic, GamePhase};
+use rust_backend::stats::PlayerId;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_millis(16);
+
+// Counts how often it is asked to describe itself.
+struct Counted(Arc<AtomicUsize>);
+
+impl GameLogic for Counted {
+    fn game_type(&self) -> u8 {
+        return 0;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+    fn describe(&self) -> GameDescription {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        return GameDescription::default();
+    }
+}
+
+// Describing a game over and over within one tick asks its logic once; the
+// next tick, or a change made through downcast_mut, asks again. What the
+// game itself knows, like who is connected, is current every time.
+fn main() {
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(Counted(calls.clone()), players);
+    game.set_clock(clock.clone());
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        clock.advance(TICK);
+        game.update();
+    }
+
+    for _ in 0..10 {
+        game.describe();
+    }
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    game.players[1].connected = false;
+    assert!(!game.describe().players[1].connected);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    println!("ten descriptions in one tick, logic asked once");
+
+    let tick = game.tick();
+    while game.tick() == tick {
+        clock.advance(TICK);
+        game.update();
+    }
+    game.describe();
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    game.downcast_mut::<Counted>().unwrap();
+    game.describe();
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+    println!("asked again after a tick and after downcast_mut");
+}
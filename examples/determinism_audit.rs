@@ -0,0 +1,81 @@
+use rapier2d::prelude::*;
+use rust_backend::audit::Divergence;
+use rust_backend::game::{Client, CommandLink, Game, GamePhase, SoccerGame, SoccerGameConfig};
+use rust_backend::message::SoccerMoveMessage;
+use rust_backend::stats::PlayerId;
+use tokio::sync::mpsc;
+
+// ticks between two comparisons with the shadow
+const EVERY: u64 = 10;
+
+// Runs game for ticks updates, both sides shooting every few, and returns
+// the first divergence the audit reports.
+fn play(game: &mut Game, link: &CommandLink, ticks: u64) -> Option<Divergence> {
+    for tick in 0..ticks {
+        if tick % 7 == 0 {
+            for player in 0..2 {
+                let shot = SoccerMoveMessage {
+                    vx: 300.0,
+                    vy: (tick as f32).sin() * 200.0,
+                    target: 0,
+                    angular: 0.0,
+                    seq: 0,
+                };
+                link.send_move(player, &shot);
+            }
+        }
+        game.update();
+        if let Some(divergence) = game.take_divergence() {
+            return Some(divergence);
+        }
+    }
+    return None;
+}
+
+// A game audited from before kickoff plays out exactly like its shadow,
+// shots and all. Nudging a puck of the shadow's by hand is caught at the
+// next check, which names the tick and that puck, and is only reported
+// once. A match already under way can't start being audited.
+fn main() {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(
+        SoccerGame::with_config(SoccerGameConfig::default()),
+        players,
+    );
+    // a game nobody is connected to stands still, and so would its audit
+    let alice = Client::new(1);
+    game.bind_connection(0, alice.slot_connection());
+    game.start_audit(EVERY).unwrap();
+    game.phase = GamePhase::Playing;
+    let (replies, _refused) = mpsc::unbounded_channel();
+    let link = CommandLink {
+        commands: game.commands(),
+        replies,
+    };
+    assert_eq!(play(&mut game, &link, 20 * EVERY), None);
+    assert_eq!(game.tick(), 20 * EVERY);
+    println!("{} ticks in step with the shadow", game.tick());
+
+    let audit = game.audit_mut().unwrap();
+    let shadow = audit.shadow_mut().expect("shadow out on a check");
+    // moved by hand, as a move could still be cooling down from a shot
+    let puck = shadow.pucks[0];
+    let nudged = shadow.bodies[puck].translation() + vector![0.0, 5.0];
+    shadow.bodies[puck].set_translation(nudged, true);
+    let divergence = play(&mut game, &link, EVERY).expect("divergence missed");
+    assert_eq!(divergence.tick, game.tick());
+    assert_eq!(divergence.body, Some(0));
+    println!(
+        "nudged shadow caught at tick {}, body {:?}",
+        divergence.tick, divergence.body
+    );
+    assert!(!game.audit_mut().unwrap().is_checking());
+    assert_eq!(play(&mut game, &link, 5 * EVERY), None);
+
+    assert!(game.start_audit(EVERY).is_err());
+    assert!(game.stop_audit());
+    println!("no audit once the match is on");
+}
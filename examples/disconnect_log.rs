@@ -0,0 +1,93 @@
+mod common;
+
+use common::{fetch, fetch_as, rally, RALLY};
+use futures::SinkExt;
+use rust_backend::events::ServerEvent;
+use rust_backend::message::CloseReason;
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18097";
+const HTTP_ADDR: &str = "127.0.0.1:18098";
+const ADMIN_TOKEN: &str = "disconnect-log-admin";
+
+// Every ended connection leaves one record, on the event bus and in the log
+// behind GET /disconnects, which only the admin may read: alice is closed
+// for an oversized message while she waits for an opponent, bob just hangs
+// up. Each record says how long the connection lasted.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: Some(HTTP_ADDR.to_string()),
+        health_addr: None,
+        max_message_size: 1024,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let mut events = server.subscribe_events();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let url = format!("ws://{}/?name=alice&mode=rally", ADDR);
+    let (mut alice, _) = connect_async(url.as_str()).await.unwrap();
+    let _ = alice.send(Message::Binary(vec![0; 4096])).await;
+    let record = closed(&mut events).await;
+    assert_eq!(record.name.as_deref(), Some("alice"));
+    assert_eq!(record.reason, Some(CloseReason::MessageTooBig));
+    println!(
+        "alice: {:?} after {:?}",
+        record.reason, record.traffic.duration
+    );
+
+    let url = format!("ws://{}/?name=bob&mode=rally", ADDR);
+    let (bob, _) = connect_async(url.as_str()).await.unwrap();
+    sleep(Duration::from_millis(300)).await;
+    drop(bob);
+    let record = closed(&mut events).await;
+    assert_eq!(record.name.as_deref(), Some("bob"));
+    assert_eq!(record.reason, None);
+    println!("bob: hung up");
+
+    let (status, _) = fetch(HTTP_ADDR, "/disconnects").await;
+    assert_eq!(status, 401);
+    println!("GET /disconnects without the admin token: 401");
+    let body = admin_get("/disconnects?name=alice").await;
+    println!("GET /disconnects?name=alice: {}", body);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["name"], "alice");
+    let body = admin_get("/disconnects?ip=127.0.0.1").await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["name"], "bob");
+    assert_eq!(body[0]["close"], serde_json::Value::Null);
+    assert!(body[0]["duration_ms"].as_u64().unwrap() >= 300);
+    println!(
+        "GET /disconnects?ip=127.0.0.1 has both, bob after {}ms",
+        body[0]["duration_ms"]
+    );
+}
+
+async fn admin_get(path: &str) -> serde_json::Value {
+    let (status, body) = fetch_as(HTTP_ADDR, path, Some(ADMIN_TOKEN)).await;
+    assert_eq!(status, 200);
+    return serde_json::from_str(&body).unwrap();
+}
+
+async fn closed(
+    events: &mut tokio::sync::broadcast::Receiver<ServerEvent>,
+) -> rust_backend::disconnects::DisconnectRecord {
+    let wait = async {
+        loop {
+            if let Ok(ServerEvent::ConnectionClosed { record }) = events.recv().await {
+                return record;
+            }
+        }
+    };
+    return timeout(Duration::from_secs(5), wait)
+        .await
+        .expect("no ConnectionClosed");
+}
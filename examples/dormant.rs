@@ -0,0 +1,66 @@
+mod common;
+
+use common::{drifted, join_ready, Drift, RALLY};
+use rust_backend::game::{GameLogic, PauseConfig};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18100";
+
+// With both players gone the game stops moving and its clock stops with
+// it; when one comes back it carries on from where it was instead of
+// catching up in one step. Left alone past dormant_timeout it is removed.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        pause: PauseConfig {
+            resume_countdown: Duration::ZERO,
+            ..PauseConfig::default()
+        },
+        dormant_timeout: Duration::from_secs(2),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Drift::default()) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let ((alice, alice_link), (_, bob_link)) = tokio::join!(
+        join_ready(ADDR, "name=alice&mode=rally"),
+        join_ready(ADDR, "name=bob&mode=rally")
+    );
+    let game = games.read().await.get(&(alice.game_id as usize)).cloned();
+    let game = game.expect("no game");
+    sleep(Duration::from_millis(500)).await;
+    assert!(drifted(&game).await > 0.0, "the game never started");
+
+    alice_link.abort();
+    bob_link.abort();
+    sleep(Duration::from_millis(200)).await;
+    assert!(game.read().await.dormant_for().is_some());
+    let before = drifted(&game).await;
+    sleep(Duration::from_secs(1)).await;
+    assert_eq!(drifted(&game).await, before, "a dormant game moved");
+    println!("dormant at x = {:.0}", before);
+
+    let query = format!("name=alice&mode=rally&session={}", alice.session_token);
+    let (_, alice_link) = join_ready(ADDR, &query).await;
+    while game.read().await.dormant_for().is_some() {
+        sleep(Duration::from_millis(1)).await;
+    }
+    let after = drifted(&game).await;
+    // a tick or two of play, not the second spent dormant
+    assert!(after - before < 100.0, "jumped {:.0}", after - before);
+    println!("woke at x = {:.0}", after);
+
+    alice_link.abort();
+    sleep(Duration::from_secs(3)).await;
+    assert!(games.read().await.is_empty(), "the dormant game was kept");
+    println!("removed after dormant_timeout");
+}
@@ -0,0 +1,74 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{
+    default_walls, GameLogic, SoccerGame, SoccerGameConfig, GOAL_NET_DEPTH, GOAL_WIDTH,
+    WALL_THICKNESS,
+};
+
+// An arena big enough that nothing reaches a wall. It still needs its
+// walls: the watchdog puts back anything outside them.
+const ARENA: f32 = 30000.0;
+const FAST: f32 = 1500.0;
+const SLOW: f32 = 150.0;
+
+// How far a ball sent off at speed slides before it all but stops, and the
+// share of its speed it still has after the first second.
+fn slide(config: &SoccerGameConfig, speed: f32) -> (f32, f32) {
+    let mut game = SoccerGame::with_config(config.clone());
+    let ball = game.balls[0];
+    let start = *game.bodies[ball].translation();
+    // straight up, away from the pucks lined up across the middle
+    game.bodies[ball].set_linvel(vector![0.0, speed], true);
+    let mut kept = 0.0;
+    for tick in 1..=60 * 120 {
+        game.update(1000.0 / 60.0);
+        if tick == 60 {
+            kept = game.bodies[ball].linvel().norm() / speed;
+        }
+        if game.bodies[ball].linvel().norm() < 1.0 {
+            break;
+        }
+    }
+    assert_eq!(game.watchdog_resets, 0);
+    return ((game.bodies[ball].translation() - start).norm(), kept);
+}
+
+// Under linear damping a shot ten times as fast slides ten times as far.
+// Quadratic drag takes most of a fast shot's speed away early and leaves a
+// slow one sliding, so the gap between them shrinks. Linear damping takes
+// the same share of either speed in a second.
+fn main() {
+    let arena = SoccerGameConfig {
+        width: ARENA,
+        height: ARENA,
+        walls: default_walls(ARENA, ARENA, GOAL_WIDTH, GOAL_NET_DEPTH, WALL_THICKNESS),
+        pucks_per_team: 1,
+        ..SoccerGameConfig::default()
+    };
+    let linear = SoccerGameConfig {
+        puck_damping: 0.5,
+        ..arena.clone()
+    };
+    let quadratic = SoccerGameConfig {
+        puck_damping: 0.05,
+        quadratic_drag: 0.002,
+        ..arena
+    };
+    let (linear_fast, linear_fast_kept) = slide(&linear, FAST);
+    let (linear_slow, linear_slow_kept) = slide(&linear, SLOW);
+    let (quadratic_fast, quadratic_fast_kept) = slide(&quadratic, FAST);
+    let (quadratic_slow, quadratic_slow_kept) = slide(&quadratic, SLOW);
+    println!(
+        "linear: {:.0} fast, {:.0} slow; quadratic: {:.0} fast, {:.0} slow",
+        linear_fast, linear_slow, quadratic_fast, quadratic_slow
+    );
+    println!(
+        "speed kept after a second, linear: {:.2} fast, {:.2} slow; quadratic: {:.2} fast, {:.2} slow",
+        linear_fast_kept, linear_slow_kept, quadratic_fast_kept, quadratic_slow_kept
+    );
+    assert!(linear_slow > 0.0 && quadratic_slow > 0.0, "nothing moved");
+    assert!((linear_fast_kept - linear_slow_kept).abs() < 0.01);
+    assert!(quadratic_fast_kept < quadratic_slow_kept);
+    assert!(quadratic_fast < linear_fast);
+    assert!(quadratic_slow > linear_slow);
+    assert!(quadratic_fast / quadratic_slow < linear_fast / linear_slow);
+}
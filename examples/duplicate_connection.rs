@@ -0,0 +1,128 @@
+mod common;
+
+use common::{connect, next, rally, welcome, RALLY};
+use futures::StreamExt;
+use rust_backend::client::{ClientEvent, GameClient};
+use rust_backend::game::DuplicateConnection;
+use rust_backend::message::{CloseReason, ErrorCode, ErrorMessage, MessageType, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const REJECT_ADDR: &str = "127.0.0.1:18092";
+const REPLACE_ADDR: &str = "127.0.0.1:18093";
+const QUEUE_ADDR: &str = "127.0.0.1:18160";
+
+// A second connection into a slot that is still connected, with the slot's
+// own session token. Under Reject it is refused with AlreadyConnected and
+// closed with ReplacedByNewConnection; under Replace it is welcomed into the
+// slot and the first connection is closed with ReplacedByNewConnection. A
+// player waiting in the queue is pushed out the same way by a second
+// connection of theirs.
+#[tokio::main]
+async fn main() {
+    let (alice, _bob, game_id, session) = start(REJECT_ADDR, DuplicateConnection::Reject).await;
+    let (first, close) = second_connection(REJECT_ADDR, game_id, &session).await;
+    let error = first
+        .decode::<ErrorMessage>()
+        .expect("no error for the duplicate");
+    assert_eq!(error.code, ErrorCode::AlreadyConnected);
+    assert_eq!(close, Some(CloseReason::ReplacedByNewConnection.code()));
+    assert!(alice.is_connected(), "the first connection was dropped");
+    println!("reject: the duplicate got AlreadyConnected and a 4004 close");
+
+    let (alice, _bob, game_id, session) = start(REPLACE_ADDR, DuplicateConnection::Replace).await;
+    let mut events: common::Events = Box::pin(alice.subscribe_events());
+    let (first, _) = second_connection(REPLACE_ADDR, game_id, &session).await;
+    assert!(matches!(first.msg_type, MessageType::Welcome));
+    let reason = next(&mut events, |event| match event {
+        ClientEvent::Closed(reason) => Some(reason),
+        _ => None,
+    })
+    .await;
+    assert_eq!(reason, Some(CloseReason::ReplacedByNewConnection));
+    println!("replace: the first connection was closed with 4004");
+
+    serve(QUEUE_ADDR, DuplicateConnection::Replace).await;
+    let (carol, mut events) = connect(QUEUE_ADDR, "carol", common::options()).await;
+    sleep(Duration::from_millis(100)).await;
+    let (_carol_again, _) = connect(QUEUE_ADDR, "carol", common::options()).await;
+    let reason = next(&mut events, |event| match event {
+        ClientEvent::Closed(reason) => Some(reason),
+        _ => None,
+    })
+    .await;
+    assert_eq!(reason, Some(CloseReason::ReplacedByNewConnection));
+    // the Closed event goes out just before the client's task ends
+    let stopped = async {
+        while carol.is_connected() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), stopped)
+        .await
+        .expect("carol's queued connection stayed up");
+    println!("replace: carol's queued connection was closed with 4004");
+}
+
+// A server with the policy and a running match; alice's and bob's clients,
+// the game and alice's session token.
+async fn start(addr: &str, policy: DuplicateConnection) -> (GameClient, GameClient, usize, String) {
+    serve(addr, policy).await;
+    let (alice, mut events) = connect(addr, "alice", common::options()).await;
+    let (bob, _) = connect(addr, "bob", common::options()).await;
+    let welcome = welcome(&mut events).await;
+    return (alice, bob, welcome.game_id as usize, welcome.session_token);
+}
+
+// A server on addr with the policy and nobody connected yet.
+async fn serve(addr: &str, policy: DuplicateConnection) {
+    let config = ServerConfig {
+        addr: addr.to_string(),
+        http_addr: None,
+        health_addr: None,
+        duplicate_connection: policy,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+}
+
+// Opens another connection as alice into her slot and returns the first
+// message it gets and, if the server closes it, the close code.
+async fn second_connection(addr: &str, game_id: usize, session: &str) -> (WsMessage, Option<u16>) {
+    let url = format!(
+        "ws://{}/?name=alice&mode={}&game={}&session={}",
+        addr, RALLY, game_id, session
+    );
+    let (mut stream, _) = connect_async(url.as_str()).await.unwrap();
+    let read = async {
+        let mut first = None;
+        while let Some(Ok(message)) = stream.next().await {
+            match message {
+                Message::Binary(data) if first.is_none() => {
+                    first = WsMessage::from_bytes(&data);
+                    if matches!(
+                        first,
+                        Some(WsMessage {
+                            msg_type: MessageType::Welcome,
+                            ..
+                        })
+                    ) {
+                        return (first, None);
+                    }
+                }
+                Message::Close(frame) => return (first, frame.map(|f| u16::from(f.code))),
+                _ => (),
+            }
+        }
+        return (first, None);
+    };
+    let (first, close) = timeout(Duration::from_secs(5), read)
+        .await
+        .expect("the second connection hung");
+    return (first.expect("no message on the second connection"), close);
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::{connect, next, rally, raw_connect, raw_next, Events, RALLY};
+use futures::SinkExt;
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::message::{
+    ByteOrder, EchoReply, ErrorCode, ErrorMessage, MessageType, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use serde_json::Value;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18104";
+
+// Echo as a pipe-health probe: it comes back verbatim with the server's
+// stamps while the client is still waiting for a match, an oversize payload
+// or a spent budget gets an Error instead, and a JSON connection gets its
+// text back as JSON.
+#[tokio::main]
+async fn main() {
+    let reply = EchoReply {
+        payload: b"hi".to_vec(),
+        received_us: 0x0102,
+        sent_us: 0x0304,
+    };
+    let mut golden = b"hi".to_vec();
+    golden.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0, 0, 0x03, 0x04]);
+    assert_eq!(reply.to_bytes(ByteOrder::Big), golden);
+    assert_eq!(
+        EchoReply::from_bytes(&golden, ByteOrder::Big),
+        Some(reply.clone())
+    );
+    assert_eq!(EchoReply::from_bytes(&golden[..15], ByteOrder::Big), None);
+    println!("reply layout {:02x?}", golden);
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        max_echo_bytes: 64,
+        echo_burst: 3,
+        echo_rate_hz: 1,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    // alone in matchmaking, so never in a game
+    let options = ClientOptions {
+        byte_order: ByteOrder::Big,
+        ..common::options()
+    };
+    let (client, mut events) = connect(ADDR, "alice", options).await;
+    let rtt = answer(&client, &mut events, b"probe".to_vec())
+        .await
+        .expect("probe refused");
+    println!("queued client echoed in {:?}", rtt);
+    // 8 bytes of the 64 go on the SDK's send time
+    assert_eq!(
+        answer(&client, &mut events, vec![0; 57]).await,
+        Err(ErrorCode::InvalidParams)
+    );
+    println!("57 bytes refused as oversize");
+    assert!(answer(&client, &mut events, vec![0; 56]).await.is_ok());
+    assert!(answer(&client, &mut events, vec![1]).await.is_ok());
+    assert_eq!(
+        answer(&client, &mut events, vec![2]).await,
+        Err(ErrorCode::RateLimited)
+    );
+    println!("fourth echo in a burst of 3 refused");
+    sleep(Duration::from_millis(1100)).await;
+    assert!(answer(&client, &mut events, vec![3]).await.is_ok());
+    println!("budget refilled");
+
+    let mut stream = raw_connect(ADDR, "name=bob&mode=rally&practice=1&format=json").await;
+    for payload in [&b"hello"[..], &[0xFF, 0xFE][..]] {
+        let echo = WsMessage {
+            msg_type: MessageType::Echo,
+            payload: payload.to_vec(),
+        };
+        stream.send(Message::Binary(echo.to_bytes())).await.unwrap();
+    }
+    let mut answers = vec![];
+    for _ in 0..2 {
+        let answer = raw_next(&mut stream, &[MessageType::Echo, MessageType::Error]).await;
+        answers.push(answer);
+    }
+    let json: Value = serde_json::from_slice(&answers[0].payload).unwrap();
+    assert_eq!(json["payload"], "hello");
+    assert!(json["received_us"].as_u64() <= json["sent_us"].as_u64());
+    println!("json connection got {}", json);
+    let error = answers[1].decode::<ErrorMessage>().unwrap();
+    assert_eq!(error.code, ErrorCode::InvalidParams);
+    println!("non-UTF-8 payload refused: {}", error.message);
+}
+
+// Echoes payload from client and waits for the answer: the round trip, or
+// the code of the Error it was refused with.
+async fn answer(
+    client: &GameClient,
+    events: &mut Events,
+    payload: Vec<u8>,
+) -> Result<Duration, ErrorCode> {
+    assert!(client.echo(&payload));
+    return next(events, |event| match event {
+        ClientEvent::Echo {
+            payload: echoed,
+            rtt,
+            server_received_us,
+            server_sent_us,
+        } => {
+            assert_eq!(echoed, payload);
+            assert!(server_received_us <= server_sent_us);
+            Some(Ok(rtt))
+        }
+        ClientEvent::Message(MessageType::Error, payload) => {
+            let error = WsMessage {
+                msg_type: MessageType::Error,
+                payload,
+            };
+            Some(Err(error.decode::<ErrorMessage>().unwrap().code))
+        }
+        _ => None,
+    })
+    .await;
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::{rally, raw_connect, raw_connect_as, raw_welcome, RawStream, RALLY};
+use futures::{SinkExt, StreamExt};
+use rust_backend::message::{
+    EventMessage, EventsSinceMessage, EventsSinceResponse, MessageType, ProtocolVersion, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18148";
+const LOG_SIZE: usize = 4;
+
+// The next frame of one of types as it came off the wire, Event envelope and
+// all.
+async fn next_bare(stream: &mut RawStream, types: &[MessageType]) -> WsMessage {
+    let read = async {
+        while let Some(Ok(message)) = stream.next().await {
+            if let Message::Binary(data) = message {
+                let message = WsMessage::from_bytes(&data)
+                    .filter(|message| types.iter().any(|t| *t as u8 == message.msg_type as u8));
+                if let Some(message) = message {
+                    return message;
+                }
+            }
+        }
+        panic!("closed before a {:?}", types);
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("frame never came");
+}
+
+fn echo(n: usize) -> WsMessage {
+    return WsMessage {
+        msg_type: MessageType::Echo,
+        payload: format!("event {}", n).into_bytes(),
+    };
+}
+
+// Asks for the events after seq and collects the seqs replayed, up to the
+// answer.
+async fn events_since(stream: &mut RawStream, seq: u64) -> (Vec<u64>, EventsSinceResponse) {
+    let request = WsMessage::from_payload(MessageType::EventsSince, &EventsSinceMessage { seq });
+    stream
+        .send(Message::Binary(request.to_bytes()))
+        .await
+        .unwrap();
+    let mut replayed = vec![];
+    loop {
+        let frame = next_bare(stream, &[MessageType::Event, MessageType::EventsSince]).await;
+        if frame.msg_type as u8 == MessageType::EventsSince as u8 {
+            return (replayed, frame.decode::<EventsSinceResponse>().unwrap());
+        }
+        replayed.push(frame.decode::<EventMessage>().unwrap().seq);
+    }
+}
+
+// A v10 client and a v1 client in the same game hear the same broadcasts:
+// the v10 one wrapped in sequenced Events, the v1 one bare, as it always
+// has. Asking for events from before what the log still holds gets what is
+// left and an answer saying it isn't all of them; asking from within the
+// log gets exactly what came after.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        event_log_size: LOG_SIZE,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect_as(ADDR, "name=alice&mode=rally", ProtocolVersion::V10).await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let welcome = raw_welcome(&mut alice).await;
+    raw_welcome(&mut bob).await;
+    let game = games.read().await[&(welcome.game_id as usize)].clone();
+
+    let mut last_seq = 0;
+    for n in 0..LOG_SIZE + 2 {
+        game.read().await.broadcast(echo(n));
+        let wrapped = next_bare(&mut alice, &[MessageType::Event]).await;
+        let event = wrapped.decode::<EventMessage>().unwrap();
+        let inner = WsMessage::from_bytes(&event.frame).unwrap();
+        assert_eq!(inner.msg_type as u8, MessageType::Echo as u8);
+        assert_eq!(inner.payload, echo(n).payload);
+        assert!(event.seq > last_seq);
+        last_seq = event.seq;
+        let bare = next_bare(&mut bob, &[MessageType::Echo, MessageType::Event]).await;
+        assert_eq!(bare.msg_type as u8, MessageType::Echo as u8);
+        assert_eq!(bare.payload, echo(n).payload);
+    }
+    println!(
+        "{} broadcasts: alice's in Events up to seq {}, bob's bare",
+        LOG_SIZE + 2,
+        last_seq
+    );
+
+    let (replayed, response) = events_since(&mut alice, 0).await;
+    let kept: Vec<u64> = (last_seq - LOG_SIZE as u64 + 1..=last_seq).collect();
+    assert_eq!(replayed, kept);
+    assert_eq!(response.latest_seq, last_seq);
+    assert!(!response.complete);
+    println!("from the start: {:?} replayed, resync required", replayed);
+
+    let (replayed, response) = events_since(&mut alice, last_seq - 2).await;
+    assert_eq!(replayed, [last_seq - 1, last_seq]);
+    assert!(response.complete);
+    println!(
+        "from seq {}: {:?} replayed, complete",
+        last_seq - 2,
+        replayed
+    );
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::RALLY;
+use futures::StreamExt;
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::game::{GameLogic, GamePhase, Games};
+use rust_backend::message::MultiStateMessage;
+use rust_backend::server::{Server, ServerConfig, ServerState};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18089";
+const ADMIN_TOKEN: &str = "overlay";
+
+// A game type with no rules, whose snapshot is just the updates it has run.
+struct Counter {
+    updates: u32,
+}
+
+impl GameLogic for Counter {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        self.updates += 1;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return self.updates.to_le_bytes().to_vec();
+    }
+}
+
+fn rally(_state: &ServerState, _practice: bool) -> Box<dyn GameLogic> {
+    return Box::new(Counter { updates: 0 });
+}
+
+// An admin firehose connection sees all three running games in one
+// MultiState batch without joining any of them, and a batch over the byte
+// limit is split across messages.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut players = vec![];
+    for name in ["a1", "a2", "b1", "b2", "c1", "c2"] {
+        players.push(connect(name, false, None).await);
+    }
+    for player in &players {
+        while player.session_token().is_none() {
+            sleep(Duration::from_millis(10)).await;
+        }
+        player.ready();
+    }
+    let running = async {
+        while running_games(&games).await.len() < 3 {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), running)
+        .await
+        .expect("three games never started");
+    let mut expected = running_games(&games).await;
+    expected.sort();
+
+    let overlay = connect("overlay", true, Some(ADMIN_TOKEN)).await;
+    let mut events = Box::pin(overlay.subscribe_events());
+    overlay.subscribe_all(5);
+    let batch = async {
+        loop {
+            if let Some(ClientEvent::MultiState(batch)) = events.next().await {
+                return batch;
+            }
+        }
+    };
+    let batch = timeout(Duration::from_secs(5), batch)
+        .await
+        .expect("no MultiState batch");
+    let mut seen: Vec<u32> = batch.games.iter().map(|g| g.game_id).collect();
+    seen.sort();
+    assert_eq!(seen, expected);
+    for game in &batch.games {
+        assert_eq!(game.game_type, RALLY);
+        let updates = u32::from_le_bytes(game.snapshot[..].try_into().unwrap());
+        println!("game {} at update {}", game.game_id, updates);
+    }
+    for (_, game) in games.read().await.iter() {
+        assert_eq!(game.read().await.players.len(), 2, "overlay took a slot");
+    }
+
+    let intruder = connect("intruder", true, None).await;
+    let refused = async {
+        while intruder.is_connected() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), refused)
+        .await
+        .expect("firehose open without the admin token");
+    println!("firehose refused without the admin token");
+
+    let snapshots: Vec<(u32, u8, Vec<u8>)> = batch
+        .games
+        .iter()
+        .map(|g| (g.game_id, g.game_type, g.snapshot.clone()))
+        .collect();
+    // room for two 4 byte snapshots and their headers, not three
+    let frames = MultiStateMessage::frames(
+        snapshots
+            .iter()
+            .map(|(game_id, game_type, snapshot)| (*game_id, *game_type, &snapshot[..])),
+        40,
+    );
+    assert_eq!(frames.len(), 2);
+    let rejoined: Vec<_> = frames
+        .iter()
+        .flat_map(|frame| MultiStateMessage::decode(&frame[1..]).unwrap().games)
+        .collect();
+    assert_eq!(rejoined, batch.games);
+    println!("batch of 3 split into {} messages", frames.len());
+}
+
+async fn connect(name: &str, firehose: bool, auth_token: Option<&str>) -> GameClient {
+    let options = ClientOptions {
+        firehose,
+        auth_token: auth_token.map(str::to_string),
+        ..common::options()
+    };
+    return common::connect(ADDR, name, options).await.0;
+}
+
+async fn running_games(games: &Games) -> Vec<u32> {
+    let mut running = vec![];
+    for (game_id, game) in games.read().await.iter() {
+        if matches!(game.read().await.phase, GamePhase::Playing) {
+            running.push(*game_id as u32);
+        }
+    }
+    return running;
+}
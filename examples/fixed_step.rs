@@ -0,0 +1,61 @@
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig, Stepping};
+
+// Every body's position and velocity, bit for bit.
+type World = Vec<(u32, u32, u32, u32)>;
+
+// A second of play from a served ball, fed to update in frames of these
+// lengths in ms, over and over; the frames total 1000ms.
+fn play(stepping: Stepping, frames: &[f64]) -> World {
+    let mut game = SoccerGame::with_config(SoccerGameConfig {
+        stepping,
+        serve_speed: Some(150.0),
+        ..SoccerGameConfig::default()
+    });
+    game.reseed(11);
+    let frames = frames.iter().cycle().take(1000);
+    let mut played = 0.0;
+    for elapsed in frames {
+        if played >= 1000.0 {
+            break;
+        }
+        game.update(*elapsed);
+        played += elapsed;
+    }
+    assert_eq!(played, 1000.0);
+    return game
+        .pucks
+        .iter()
+        .chain(&game.balls)
+        .map(|handle| {
+            let body = &game.bodies[*handle];
+            let (position, velocity) = (body.translation(), body.linvel());
+            return (
+                position.x.to_bits(),
+                position.y.to_bits(),
+                velocity.x.to_bits(),
+                velocity.y.to_bits(),
+            );
+        })
+        .collect();
+}
+
+// Fixed stepping ends a second of play in exactly the same world whether
+// the ticks come every 10ms, every 20ms or unevenly, and variable stepping
+// at a steady 10ms matches fixed 10ms steps. Stepping once per update, the
+// default, runs the physics at whatever rate the ticks come, so half as
+// many ticks get half as far.
+fn main() {
+    let fixed = Stepping::Fixed(0.01);
+    let steady = play(fixed, &[10.0]);
+    assert_eq!(play(fixed, &[20.0]), steady);
+    assert_eq!(play(fixed, &[5.0, 15.0, 30.0, 10.0, 40.0]), steady);
+    assert_eq!(play(fixed, &[2.5, 7.5]), steady);
+    println!("fixed 10ms steps: the same world at every cadence");
+
+    assert_eq!(play(Stepping::Variable, &[10.0]), steady);
+    println!("variable steps at a steady 10ms match");
+
+    let per_update = play(Stepping::PerUpdate, &[10.0]);
+    assert_ne!(play(Stepping::PerUpdate, &[20.0]), per_update);
+    println!("one step per update depends on the cadence");
+}
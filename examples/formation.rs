@@ -0,0 +1,62 @@
+use rust_backend::game::{Formation, SoccerGame, SoccerGameConfig};
+use rust_backend::message::{encode_payload, SetFormationMessage};
+
+// Where slot's pucks are, in team order.
+fn positions(game: &SoccerGame, slot: usize) -> Vec<(f32, f32)> {
+    return game.teams[slot]
+        .pucks
+        .iter()
+        .map(|puck| {
+            let at = game.bodies[*puck].translation();
+            (at.x, at.y)
+        })
+        .collect();
+}
+
+// A formation moves only its player's pucks, onto the mirrored spots for
+// their side, and those become their kickoff spots until the game is reset.
+fn main() {
+    let request = SetFormationMessage {
+        formation: "attacking".to_string(),
+    };
+    let mut golden = vec![9, 0, 0, 0, 0, 0, 0, 0];
+    golden.extend_from_slice(b"attacking");
+    assert_eq!(encode_payload(&request).unwrap(), golden);
+    assert_eq!(
+        Formation::from_name("attacking"),
+        Some(Formation::Attacking)
+    );
+    assert_eq!(Formation::from_name("park_the_bus"), None);
+
+    let mut game = SoccerGame::with_config(SoccerGameConfig::default());
+    let balanced = (positions(&game, 0), positions(&game, 1));
+    game.set_formation(0, Formation::Attacking).unwrap();
+    let attacking = positions(&game, 0);
+    // slot 0 defends the left goal, so its spots are mirrored
+    assert_eq!(attacking[0], (-60.0, -200.0));
+    assert_eq!(attacking[4], (-250.0, 0.0));
+    assert_ne!(attacking, balanced.0);
+    assert_eq!(positions(&game, 1), balanced.1);
+    println!("attacking: {:?}", attacking);
+
+    game.set_formation(1, Formation::Defensive).unwrap();
+    let defensive = positions(&game, 1);
+    assert_eq!(defensive[1], (230.0, 0.0));
+    assert!(defensive.iter().all(|(x, _)| *x >= 150.0));
+    println!("defensive: {:?}", defensive);
+    assert!(game.is_clean());
+
+    // a 480 wide field fits the default layout, but neither of the ones
+    // that put a puck deep in goal
+    let mut narrow = SoccerGame::with_config(SoccerGameConfig {
+        width: 480.0,
+        ..SoccerGameConfig::default()
+    });
+    assert!(narrow.set_formation(1, Formation::Defensive).is_err());
+    assert!(narrow.set_formation(1, Formation::Attacking).is_err());
+    assert!(narrow.set_formation(1, Formation::Balanced).is_ok());
+
+    game.reset();
+    assert_eq!((positions(&game, 0), positions(&game, 1)), balanced);
+    println!("reset back to balanced");
+}
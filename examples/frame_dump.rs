@@ -0,0 +1,114 @@
+use rust_backend::frame_dump::{DumpFormat, FrameDumper};
+use rust_backend::game::{Game, GameLogic};
+use rust_backend::message::SnapshotHeader;
+use rust_backend::stats::PlayerId;
+use serde_json::Value;
+use std::time::Duration;
+
+const RALLY: u8 = 7;
+const TICKS: u64 = 25;
+
+// A game whose State is just how many updates it has had.
+struct Rally {
+    updates: u8,
+}
+
+impl GameLogic for Rally {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        self.updates += 1;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![RALLY, self.updates];
+    }
+    fn remaining_time(&self) -> Option<Duration> {
+        return Some(Duration::from_secs(60));
+    }
+    fn scores(&self) -> Vec<u32> {
+        return vec![1, 2];
+    }
+}
+
+// N ticks of a game with a dumper attached make N records, in either
+// format, and a limit stops the dump short without touching the game.
+#[tokio::main]
+async fn main() {
+    let dir = std::env::temp_dir();
+    let json = dir.join("frame_dump_example.ndjson");
+    let dumper = FrameDumper::create(&json, DumpFormat::Json, None)
+        .await
+        .unwrap();
+    let game = run(dumper, TICKS).await;
+    let lines: Vec<Value> = std::fs::read_to_string(&json)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len() as u64, TICKS);
+    for (tick, line) in (1..).zip(&lines) {
+        assert_eq!(line["tick"], tick);
+        assert_eq!(line["remaining_ds"], 600);
+        assert_eq!(line["scores"], serde_json::json!([1, 2]));
+    }
+    assert_eq!(lines[0]["snapshot"][0], RALLY);
+    println!("{} ticks, {} lines: {}", game.tick(), lines.len(), lines[0]);
+
+    let binary = dir.join("frame_dump_example.bin");
+    let dumper = FrameDumper::create(&binary, DumpFormat::Binary, None)
+        .await
+        .unwrap();
+    run(dumper, TICKS).await;
+    let data = std::fs::read(&binary).unwrap();
+    let mut rest = &data[..];
+    let mut ticks = vec![];
+    while !rest.is_empty() {
+        let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (header, body) = SnapshotHeader::from_bytes(&rest[4..4 + len]).unwrap();
+        assert_eq!(body[0], RALLY);
+        ticks.push(header.tick as u64);
+        rest = &rest[4 + len..];
+    }
+    assert_eq!(ticks, (1..=TICKS).collect::<Vec<_>>());
+    println!("{} binary records in {} bytes", ticks.len(), data.len());
+
+    let limited = dir.join("frame_dump_example_limited.ndjson");
+    let dumper = FrameDumper::create(&limited, DumpFormat::Json, Some(10))
+        .await
+        .unwrap();
+    let done = dumper.done();
+    let mut game = run(dumper, TICKS).await;
+    assert_eq!(game.tick(), TICKS);
+    // a full dumper lets go of itself, and its writer finishes on its own
+    assert!(game.take_frame_dumper().is_none());
+    assert_eq!(done.wait().await, Ok(10));
+    let lines = std::fs::read_to_string(&limited).unwrap().lines().count();
+    assert_eq!(lines, 10);
+    println!("limit of 10 kept {} of {} ticks", lines, TICKS);
+
+    for path in [json, binary, limited] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+// Runs ticks updates of a fresh game with dumper attached, then lets the
+// dumper go and waits for everything it queued to be on disk.
+async fn run(dumper: FrameDumper, ticks: u64) -> Game {
+    let players = vec![(PlayerId::Guest("alice".to_string()), "alice".to_string())];
+    let mut game = Game::new(Rally { updates: 0 }, players);
+    game.attach_frame_dumper(dumper);
+    for _ in 0..ticks {
+        game.update();
+    }
+    if let Some(dumper) = game.take_frame_dumper() {
+        assert_eq!(dumper.finish().await, Ok(ticks));
+    }
+    return game;
+}
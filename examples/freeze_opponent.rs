@@ -0,0 +1,64 @@
+use rust_backend::game::{ControlMode, GameLogic, SoccerGame, SoccerGameConfig};
+
+// Where each of player's pucks is.
+fn positions(game: &SoccerGame, player: usize) -> Vec<(f32, f32)> {
+    return game
+        .team(player)
+        .unwrap()
+        .pucks
+        .iter()
+        .map(|puck| {
+            let at = game.bodies[*puck].translation();
+            (at.x, at.y)
+        })
+        .collect();
+}
+
+// Shoves every puck player has as hard as moves allow.
+fn shove(game: &mut SoccerGame, player: usize) {
+    let pucks = game.team(player).unwrap().pucks.clone();
+    for puck in pucks {
+        game.apply_move(puck, -400.0, 150.0, 5.0);
+    }
+}
+
+// bob's pucks, frozen, stay exactly where they were through moves of his
+// own, under velocity and impulse control alike, and through alice's pucks
+// being driven into them. Released, they take moves again. Freezing a slot
+// the game doesn't have does nothing.
+fn main() {
+    let (alice, bob) = (0, 1);
+    for control_mode in [
+        ControlMode::Velocity,
+        ControlMode::Impulse { max_impulse: 1e6 },
+    ] {
+        let mut game = SoccerGame::with_config(SoccerGameConfig::default());
+        game.control_mode = control_mode;
+        assert!(game.freeze_pucks(bob, true));
+        assert!(game.is_frozen(bob) && !game.is_frozen(alice));
+        let held = positions(&game, bob);
+        for _ in 0..120 {
+            shove(&mut game, bob);
+            let pucks = game.team(alice).unwrap().pucks.clone();
+            for puck in pucks {
+                game.apply_move(puck, 400.0, 0.0, 0.0);
+            }
+            game.update(1000.0 / 60.0);
+        }
+        assert_eq!(positions(&game, bob), held);
+        println!("{:?}: frozen pucks held through 120 updates", control_mode);
+
+        assert!(game.freeze_pucks(bob, false));
+        assert!(!game.is_frozen(bob));
+        shove(&mut game, bob);
+        for _ in 0..10 {
+            game.update(1000.0 / 60.0);
+        }
+        assert_ne!(positions(&game, bob), held);
+        println!("{:?}: released pucks move again", control_mode);
+    }
+
+    let mut game = SoccerGame::with_config(SoccerGameConfig::default());
+    assert!(!game.freeze_pucks(5, true));
+    assert!(!game.is_frozen(5));
+}
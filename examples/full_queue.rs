@@ -0,0 +1,77 @@
+mod common;
+
+use common::{connect, game_of, rally, RALLY};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18085";
+
+// With room for one game, the first pair plays and the second is told it is
+// queued. Once the first game ends the second pair must be matched without
+// anyone reconnecting.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        queue_timeout: None,
+        max_games: Some(1),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (alice, _) = connect(ADDR, "alice", common::options()).await;
+    let (_bob, _) = connect(ADDR, "bob", common::options()).await;
+    let first = game_of(&games, "alice")
+        .await
+        .map(|(id, _)| id)
+        .expect("alice never placed");
+    assert_eq!(game_of(&games, "bob").await.map(|(id, _)| id), Some(first));
+    println!("alice and bob playing game {}", first);
+
+    let (carol, _) = connect(ADDR, "carol", common::options()).await;
+    let (dave, _) = connect(ADDR, "dave", common::options()).await;
+    for (name, client) in [("carol", &carol), ("dave", &dave)] {
+        let queued = async {
+            loop {
+                if let Some(queued) = client.queued() {
+                    return queued;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        let queued = timeout(Duration::from_secs(5), queued)
+            .await
+            .expect("never told it was queued");
+        println!("{} queued at position {}", name, queued.position);
+    }
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(games.read().await.len(), 1, "a second game started");
+
+    // leaving mid-match forfeits and ends the game, freeing its room
+    let welcomed = async {
+        while alice.session_token().is_none() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), welcomed)
+        .await
+        .expect("no Welcome");
+    alice.leave_game();
+    let second = game_of(&games, "carol")
+        .await
+        .map(|(id, _)| id)
+        .expect("carol never promoted");
+    assert_ne!(second, first);
+    assert_eq!(
+        game_of(&games, "dave").await.map(|(id, _)| id),
+        Some(second)
+    );
+    assert!(carol.queued().is_none());
+    println!("carol and dave promoted to game {}", second);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{connect, next, Bulky, RALLY};
+use futures::SinkExt;
+use rust_backend::client::ClientEvent;
+use rust_backend::game::GameLogic;
+use rust_backend::message::{MessageType, SubscribeMessage, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration, Instant};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18119";
+
+// A player whose connection stopped reading mid-match is stuck writing to
+// it. When the other player leaves and the game is removed, that stuck
+// connection gives up on its write within a second and lets go of the game
+// instead of keeping it in memory, so the only reference left is the one
+// taken here to check.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ready_timeout: Duration::from_millis(100),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Bulky) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (alice, mut events) = connect(ADDR, "alice", common::options()).await;
+    // bob is matched with alice and then never reads another frame
+    let url = format!("ws://{}/?name=bob&mode=rally", ADDR);
+    let (mut bob, _) = connect_async(url).await.unwrap();
+    let game_id = next(&mut events, |event| match event {
+        ClientEvent::Welcome(welcome) => Some(welcome.game_id as usize),
+        _ => None,
+    })
+    .await;
+    let game = games.read().await.get(&game_id).cloned().unwrap();
+    assert_eq!(games.read().await.len(), 1);
+    sleep(Duration::from_millis(100)).await;
+    let subscribe = SubscribeMessage { state_rate_hz: 60 };
+    let subscribe = WsMessage::from_payload(MessageType::Subscribe, &subscribe);
+    bob.send(Message::Binary(subscribe.to_bytes()))
+        .await
+        .unwrap();
+
+    // long enough for bob's socket to fill and his writes to stall
+    sleep(Duration::from_secs(2)).await;
+    let held = Arc::strong_count(&game);
+    println!("game {} in play, {} references", game_id, held);
+    assert!(held > 2, "both connections should hold game {}", game_id);
+
+    alice.leave_game();
+    let started = Instant::now();
+    while Arc::strong_count(&game) > 1 {
+        assert!(
+            started.elapsed() < Duration::from_secs(3),
+            "game {} still held {} times after it was removed",
+            game_id,
+            Arc::strong_count(&game) - 1
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert!(games.read().await.get(&game_id).is_none());
+    println!(
+        "game {} let go by both connections {:?} after alice left",
+        game_id,
+        started.elapsed()
+    );
+}
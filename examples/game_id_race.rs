@@ -0,0 +1,82 @@
+mod common;
+
+use common::{exit_on_panic, rally, raw_connect, raw_welcome, RawStream, RALLY};
+use rust_backend::events::ServerEvent;
+use rust_backend::invariants::InvariantChecks;
+use rust_backend::message::WelcomeMessage;
+use rust_backend::server::{Server, ServerConfig};
+use std::collections::HashSet;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18123";
+const PLAYERS: usize = 64;
+
+// Players all asking for a practice game at once each get a game of their
+// own under an id nobody else got, and the server announces exactly that
+// many distinct games.
+#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        max_connections_per_ip: None,
+        check_invariants: InvariantChecks::Panic,
+        ..ServerConfig::default()
+    };
+    exit_on_panic();
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    let mut server_events = server.subscribe_events();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let joins: Vec<_> = (0..PLAYERS)
+        .map(|n| tokio::spawn(welcome(format!("p{}", n))))
+        .collect();
+    let mut streams = vec![];
+    let mut ids = HashSet::new();
+    for join in joins {
+        let (welcome, stream) = join.await.unwrap();
+        assert!(
+            ids.insert(welcome.game_id),
+            "game {} handed out twice",
+            welcome.game_id
+        );
+        // kept open so every game stays
+        streams.push(stream);
+    }
+    assert_eq!(games.read().await.len(), PLAYERS);
+    println!(
+        "{} simultaneous joins, {} distinct game ids",
+        PLAYERS,
+        ids.len()
+    );
+
+    let mut created = HashSet::new();
+    while created.len() < PLAYERS {
+        let event = timeout(Duration::from_secs(5), server_events.recv())
+            .await
+            .expect("GameCreated missing")
+            .unwrap();
+        if let ServerEvent::GameCreated { game_id, .. } = event {
+            assert!(
+                created.insert(game_id as u32),
+                "game {} created twice",
+                game_id
+            );
+        }
+    }
+    assert_eq!(created, ids);
+    println!("one GameCreated per id");
+}
+
+// Asks for a practice game as name; its Welcome, and the connection, left
+// open.
+async fn welcome(name: String) -> (WelcomeMessage, RawStream) {
+    let query = format!("name={}&mode=rally&practice=1", name);
+    let mut stream = raw_connect(ADDR, &query).await;
+    let welcome = raw_welcome(&mut stream).await;
+    return (welcome, stream);
+}
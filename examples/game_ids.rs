@@ -0,0 +1,93 @@
+mod common;
+
+use common::{game_of, rally, RALLY};
+use rust_backend::client::{ClientOptions, GameClient};
+use rust_backend::events::ServerEvent;
+use rust_backend::game::Games;
+use rust_backend::server::{Server, ServerConfig};
+use std::time::SystemTime;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18087";
+
+// A game removed and another created must never share an id, and a join
+// carrying the wrong game token must be refused as not found rather than
+// placed in whatever game has that id.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    let games = server.games();
+    let mut server_events = server.subscribe_events();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = connect("alice", None).await;
+    let (first, _) = game_token(&games, "alice").await;
+    match timeout(Duration::from_secs(5), server_events.recv()).await {
+        Ok(Ok(ServerEvent::GameCreated {
+            game_id,
+            created_at,
+        })) => {
+            assert_eq!(game_id, first);
+            assert!(created_at <= SystemTime::now());
+        }
+        other => panic!("expected GameCreated, got {:?}", other),
+    }
+    while alice.session_token().is_none() {
+        sleep(Duration::from_millis(10)).await;
+    }
+    alice.leave_game();
+    let removed = async {
+        while games.read().await.contains_key(&first) {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), removed)
+        .await
+        .expect("game never removed");
+
+    let _bob = connect("bob", None).await;
+    let (second, token) = game_token(&games, "bob").await;
+    assert!(second > first, "id {} reused after {}", second, first);
+    println!("game {} removed, next game is {}", first, second);
+
+    let stale = connect("carol", Some((second, "00000000".to_string()))).await;
+    let closed = async {
+        while stale.is_connected() {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), closed)
+        .await
+        .expect("stale link kept open");
+    let players = games.read().await[&second].read().await.players.len();
+    assert_eq!(players, 1, "stale link joined game {}", second);
+    println!("wrong token for game {} refused", second);
+
+    let _dave = connect("dave", Some((second, token))).await;
+    assert_eq!(game_token(&games, "dave").await.0, second);
+    println!("right token joined game {}", second);
+}
+
+async fn connect(name: &str, game: Option<(usize, String)>) -> GameClient {
+    let options = ClientOptions {
+        practice: game.is_none(),
+        game: game.as_ref().map(|(id, _)| *id),
+        game_token: game.map(|(_, token)| token),
+        ..common::options()
+    };
+    return common::connect(ADDR, name, options).await.0;
+}
+
+// (id, token) of the game name ends up in.
+async fn game_token(games: &Games, name: &str) -> (usize, String) {
+    let (id, game) = game_of(games, name).await.expect("never placed in a game");
+    return (id, game.read().await.token.clone());
+}
@@ -0,0 +1,59 @@
+use rust_backend::matchmaking::{GameOwners, Owner};
+use rust_backend::stats::PlayerId;
+use std::net::{IpAddr, Ipv4Addr};
+
+const MAX: Option<usize> = Some(2);
+
+// Walks GameOwners through the cases the per-identity game cap depends on:
+// the cap itself, room coming back when a game is removed, identities not
+// sharing a budget, and guests with the same name on different addresses
+// counting separately.
+fn main() {
+    let owners = GameOwners::default();
+    let home = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    let alice = Owner::new(PlayerId::new(Some("alice-token"), "alice"), home);
+    let bob = Owner::new(PlayerId::new(Some("bob-token"), "bob"), home);
+
+    assert_eq!(owners.claim(&[alice.clone()], 1, MAX), Ok(()));
+    assert_eq!(owners.claim(&[alice.clone()], 2, MAX), Ok(()));
+    assert_eq!(owners.claim(&[alice.clone()], 3, MAX), Err(vec![0]));
+    assert_eq!(owners.count(&alice), 2);
+    println!("alice capped at {}", owners.count(&alice));
+
+    // a match between the two is refused as a whole, naming only alice
+    assert_eq!(
+        owners.claim(&[bob.clone(), alice.clone()], 4, MAX),
+        Err(vec![1])
+    );
+    assert_eq!(owners.count(&bob), 0);
+    assert_eq!(owners.claim(&[bob.clone()], 5, MAX), Ok(()));
+    println!("bob unaffected: {}", owners.count(&bob));
+
+    owners.remove_game(1);
+    assert_eq!(owners.count(&alice), 1);
+    // removing twice, or a game alice isn't in, changes nothing
+    owners.remove_game(1);
+    owners.remove_game(5);
+    assert_eq!(owners.count(&alice), 1);
+    assert_eq!(owners.count(&bob), 0);
+    assert_eq!(owners.claim(&[alice.clone(), bob.clone()], 6, MAX), Ok(()));
+    assert_eq!(owners.count(&alice), 2);
+    println!("alice back to {} after a removal", owners.count(&alice));
+
+    let guest = PlayerId::new(None, "guest");
+    let here = Owner::new(guest.clone(), home);
+    let there = Owner::new(guest, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+    assert_eq!(owners.claim(&[here.clone()], 7, MAX), Ok(()));
+    assert_eq!(owners.claim(&[here.clone()], 8, MAX), Ok(()));
+    assert_eq!(owners.claim(&[there.clone()], 9, MAX), Ok(()));
+    assert_eq!((owners.count(&here), owners.count(&there)), (2, 1));
+    // accounts aren't tied to an address
+    assert_eq!(
+        Owner::new(PlayerId::new(Some("alice-token"), "a"), here.ip.unwrap()),
+        alice
+    );
+
+    for (owner, games) in owners.list() {
+        println!("{}: {:?}", owner, games);
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{connect, error, next, options, rally, welcome, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions};
+use rust_backend::message::{
+    ErrorCode, GameParams, HandicapConfig, MessageType, SetGameParamsMessage, TeamHandicap,
+    WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18143";
+const ADMIN_TOKEN: &str = "letmein";
+
+// A SetGameParams naming a game that doesn't exist, or one that isn't
+// soccer, is refused and leaves the config new games are built from as it
+// was; one naming no game changes it. A handicap is refused the same way,
+// and a SetGameParams from before handicaps decodes without one.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    // the soccer damping each new game could have been built with
+    let built_with = Arc::new(Mutex::new(vec![]));
+    let seen = built_with.clone();
+    server.register_mode("rally", RALLY, move |state, practice| {
+        let damping = state.soccer.lock().unwrap().config.puck_damping;
+        seen.lock().unwrap().push(damping);
+        return rally(state, practice);
+    });
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let options = ClientOptions {
+        practice: true,
+        auth_token: Some(ADMIN_TOKEN.to_string()),
+        ..options()
+    };
+    let (admin, mut events) = connect(ADDR, "admin", options.clone()).await;
+    let rally_game = welcome(&mut events).await.game_id;
+    let damping = GameParams {
+        puck_damping: Some(0.9),
+        ..GameParams::default()
+    };
+    admin.set_game_params(Some(999), damping);
+    assert_eq!(error(&mut events).await, ErrorCode::GameNotFound);
+    admin.set_game_params(Some(rally_game), damping);
+    assert_eq!(error(&mut events).await, ErrorCode::WrongGameType);
+    let handicap = HandicapConfig {
+        right: TeamHandicap {
+            score: 1,
+            ..TeamHandicap::default()
+        },
+        ..HandicapConfig::default()
+    };
+    admin.set_handicap(999, handicap);
+    assert_eq!(error(&mut events).await, ErrorCode::GameNotFound);
+    admin.set_handicap(rally_game, handicap);
+    assert_eq!(error(&mut events).await, ErrorCode::WrongGameType);
+    let before = new_game(&options, &built_with).await;
+    println!(
+        "missing and rally game refused; new games still get {}",
+        before
+    );
+    assert!(before != 0.9);
+
+    let legacy = WsMessage::from_payload(MessageType::SetGameParams, &(Some(7u32), damping));
+    let decoded = SetGameParamsMessage::decode(&legacy.payload).unwrap();
+    assert_eq!(
+        (decoded.game_id, decoded.params, decoded.handicap),
+        (Some(7), damping, None)
+    );
+    println!("a SetGameParams without a handicap still decodes");
+
+    admin.set_game_params(None, damping);
+    next(&mut events, |event| match event {
+        ClientEvent::Message(MessageType::SetGameParams, _) => Some(()),
+        _ => None,
+    })
+    .await;
+    assert_eq!(new_game(&options, &built_with).await, 0.9);
+    println!("with no game named, new games get 0.9");
+}
+
+// The damping the next game was built with.
+async fn new_game(options: &ClientOptions, built_with: &Mutex<Vec<f32>>) -> f32 {
+    let (_client, mut events) = connect(ADDR, "probe", options.clone()).await;
+    welcome(&mut events).await;
+    return *built_with.lock().unwrap().last().unwrap();
+}
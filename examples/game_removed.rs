@@ -0,0 +1,74 @@
+mod common;
+
+use common::{drain, rally, raw_connect, raw_welcome, RawStream, RALLY};
+use futures::StreamExt;
+use rust_backend::message::CloseReason;
+use rust_backend::server::{Server, ServerConfig};
+use std::sync::Arc;
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18132";
+
+// A game taken out of the map and closed, the way the sweeps and shutdown
+// remove one, ends the connections still playing it: each is closed with
+// 1000 rather than left working against a game nobody else can see, and
+// once they are gone nothing holds on to the game.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let game_id = raw_welcome(&mut alice).await.game_id as usize;
+    assert_eq!(raw_welcome(&mut bob).await.game_id as usize, game_id);
+    let mut carol = raw_connect(ADDR, "name=carol&mode=rally&practice=1").await;
+    let other = raw_welcome(&mut carol).await.game_id as usize;
+    drain(carol);
+
+    let game = games.write().await.remove(&game_id).expect("no game");
+    game.read().await.close();
+    for (name, stream) in [("alice", &mut alice), ("bob", &mut bob)] {
+        let code = closed(stream).await;
+        assert_eq!(code, Some(CloseReason::NormalLobbyExit));
+        println!("{}'s connection closed with {:?}", name, code);
+    }
+    let released = async {
+        while Arc::strong_count(&game) > 1 {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), released)
+        .await
+        .expect("a connection task kept the game");
+    println!("nothing else holds game {}", game_id);
+
+    assert!(games.read().await.contains_key(&other));
+    assert!(!games.read().await[&other].read().await.is_closed());
+    println!("game {} played on", other);
+}
+
+// How the server closed stream, within five seconds.
+async fn closed(stream: &mut RawStream) -> Option<CloseReason> {
+    let read = async {
+        while let Some(Ok(message)) = stream.next().await {
+            if let Message::Close(frame) = message {
+                return frame.and_then(|frame| CloseReason::from_code(u16::from(frame.code)));
+            }
+        }
+        panic!("dropped without a close frame");
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("connection never closed");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{drain, fetch, rally, raw_connect, raw_welcome, RALLY};
+use rust_backend::server::{Server, ServerConfig};
+use serde_json::Value;
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18137";
+const HTTP_ADDR: &str = "127.0.0.1:18138";
+
+// GET /game/{id} returns one game as JSON, players and all, and for a
+// soccer game its bodies and score too; an id nobody is playing is a 404.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: Some(HTTP_ADDR.to_string()),
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let game_id = raw_welcome(&mut alice).await.game_id;
+    raw_welcome(&mut bob).await;
+    let (status, body) = fetch(HTTP_ADDR, &format!("/game/{}", game_id)).await;
+    assert_eq!(status, 200);
+    let game: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(game["id"], game_id as u64);
+    assert_eq!(game["game_type"], RALLY as u64);
+    let names: Vec<&str> = game["players"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|player| player["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["alice", "bob"]);
+    assert!(game["state"].is_null(), "a rally game has no soccer state");
+    println!("game {}: {:?} in {}", game_id, names, game["phase"]);
+
+    for path in ["/game/999", "/game/nope"] {
+        let (status, body) = fetch(HTTP_ADDR, path).await;
+        assert_eq!(status, 404);
+        println!("{}: {} {}", path, status, body);
+    }
+
+    let mut carol = raw_connect(ADDR, "name=carol&practice=1").await;
+    let soccer_id = raw_welcome(&mut carol).await.game_id;
+    drain(carol);
+    let (status, body) = fetch(HTTP_ADDR, &format!("/game/{}", soccer_id)).await;
+    assert_eq!(status, 200);
+    let game: Value = serde_json::from_str(&body).unwrap();
+    let state = &game["state"];
+    assert!(!state["pucks"].as_array().unwrap().is_empty());
+    assert_eq!(state["balls"].as_array().unwrap().len(), 1);
+    assert_eq!(state["teams"][0]["score"], 0);
+    println!("soccer game {}: {}", soccer_id, state);
+}
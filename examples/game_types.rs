@@ -0,0 +1,96 @@
+mod common;
+
+use common::{game_of, RALLY};
+use rust_backend::client::{ClientOptions, GameClient};
+use rust_backend::game::{GameLogic, Games};
+use rust_backend::server::{Server, ServerConfig, ServerState};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18083";
+const TAG: u8 = 8;
+
+// A game type with no rules at all, enough to be matched and stepped.
+struct Empty(u8);
+
+impl GameLogic for Empty {
+    fn game_type(&self) -> u8 {
+        return self.0;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![self.0];
+    }
+}
+
+fn rally(_state: &ServerState, _practice: bool) -> Box<dyn GameLogic> {
+    return Box::new(Empty(RALLY));
+}
+
+fn tag(_state: &ServerState, _practice: bool) -> Box<dyn GameLogic> {
+    return Box::new(Empty(TAG));
+}
+
+// Two players queueing for different types must never meet: each waits out
+// the queue timeout and gets a bot. Two queueing for the same type are
+// paired straight away.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        queue_timeout: Some(Duration::from_secs(1)),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    server.register_game_type(TAG, tag);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let _alice = join("alice", RALLY).await;
+    let _bob = join("bob", TAG).await;
+    let alice = game_type_of(&games, "alice").await;
+    let bob = game_type_of(&games, "bob").await;
+    assert_ne!(alice.0, bob.0, "different types shared a game");
+    for (game_id, game_type, humans) in [alice, bob] {
+        assert_eq!(humans, 1, "game {} has {} players", game_id, humans);
+        println!(
+            "game {} (type {}) has one player and a bot",
+            game_id, game_type
+        );
+    }
+    assert_eq!((alice.1, bob.1), (RALLY, TAG));
+
+    let _carol = join("carol", RALLY).await;
+    let _dave = join("dave", RALLY).await;
+    let carol = game_type_of(&games, "carol").await;
+    let dave = game_type_of(&games, "dave").await;
+    assert_eq!(carol, dave);
+    assert_eq!(carol.1, RALLY);
+    assert_eq!(carol.2, 2);
+    println!("carol and dave paired in game {}", carol.0);
+}
+
+async fn join(name: &str, mode: u8) -> GameClient {
+    let options = ClientOptions {
+        mode: Some(mode),
+        ..common::options()
+    };
+    return common::connect(ADDR, name, options).await.0;
+}
+
+// (game id, game type, players who aren't bots) of the game name ends up in.
+async fn game_type_of(games: &Games, name: &str) -> (usize, u8, usize) {
+    let (id, game) = game_of(games, name).await.expect("never placed in a game");
+    let game = game.read().await;
+    let humans = game.players.iter().filter(|p| !p.bot).count();
+    return (id, game.game_type, humans);
+}
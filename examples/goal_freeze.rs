@@ -0,0 +1,147 @@
+mod common;
+
+use common::StepClock;
+use rapier2d::prelude::*;
+use rust_backend::game::{
+    Game, GameCommand, GameLogic, GamePhase, SoccerGame, SoccerGameConfig, SoccerPhase,
+    KICKOFF_TIMER,
+};
+use rust_backend::message::{ErrorCode, ErrorMessage, WsMessage};
+use rust_backend::stats::PlayerId;
+use rust_backend::timers::TimerChange;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const TICK: Duration = Duration::from_millis(16);
+
+// Queues a move for alice's first puck, steps once, and returns what the
+// game sent back about it: None when it was taken.
+fn try_move(
+    game: &mut Game,
+    clock: &StepClock,
+    replies: &mut mpsc::UnboundedReceiver<WsMessage>,
+    reply_to: &mpsc::UnboundedSender<WsMessage>,
+) -> Option<ErrorMessage> {
+    let command = GameCommand::Move {
+        player: 0,
+        target: 0,
+        vx: 200.0,
+        vy: 0.0,
+        angular: 0.0,
+        seq: 0,
+        replies: reply_to.clone(),
+    };
+    game.commands().try_send(command).unwrap();
+    clock.advance(TICK);
+    game.update();
+    return replies.try_recv().ok().map(|reply| reply.decode().unwrap());
+}
+
+fn phase(game: &Game) -> SoccerPhase {
+    return game.downcast::<SoccerGame>().unwrap().phase;
+}
+
+// Steps until the soccer phase is one that matches, for up to a second.
+fn step_until(game: &mut Game, clock: &StepClock, want: impl Fn(SoccerPhase) -> bool) {
+    for _ in 0..60 {
+        if want(phase(game)) {
+            return;
+        }
+        clock.advance(TICK);
+        game.update();
+    }
+    panic!("stuck in {:?}", phase(game));
+}
+
+// From a goal until kickoff is over the game takes no input: a move while
+// the ball sits in the goal and one during the kickoff freeze are both
+// refused as InputFrozen, naming the phase, with everything put back on its
+// spot in between. Once the freeze runs out the same move is taken. A
+// SoccerGame updated without a Game to deliver its kickoff timer, as the
+// bench runs it, kicks off by its own tick count all the same, and a reset
+// one drops any kickoff its game still has pending.
+fn main() {
+    let start = Instant::now();
+    let clock = Arc::new(StepClock::new(start));
+    let config = SoccerGameConfig {
+        goal_reset_ticks: 3,
+        kickoff_freeze_ticks: 3,
+        ..SoccerGameConfig::default()
+    };
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(SoccerGame::with_config(config), players);
+    game.set_clock(clock.clone());
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        clock.advance(TICK);
+        game.update();
+    }
+    let (reply_to, mut replies) = mpsc::unbounded_channel();
+    assert!(try_move(&mut game, &clock, &mut replies, &reply_to).is_none());
+    println!("in play: move taken");
+
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    let ball = soccer.balls[0];
+    let kickoff = *soccer.bodies[ball].translation();
+    soccer.bodies[ball].set_translation(vector![-260.0, 0.0], true);
+    soccer.bodies[ball].set_linvel(vector![-800.0, 0.0], true);
+    step_until(&mut game, &clock, |phase| {
+        matches!(phase, SoccerPhase::GoalScored { .. })
+    });
+    let refused = try_move(&mut game, &clock, &mut replies, &reply_to);
+    let refused = refused.expect("move taken with the ball in the goal");
+    assert_eq!(refused.code, ErrorCode::InputFrozen);
+    assert_eq!(refused.message, "goal_scored");
+    println!("goal scored: move refused as {}", refused.message);
+
+    step_until(&mut game, &clock, |phase| {
+        matches!(phase, SoccerPhase::Kickoff { .. })
+    });
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    assert_eq!(*soccer.bodies[ball].translation(), kickoff);
+    let refused = try_move(&mut game, &clock, &mut replies, &reply_to);
+    let refused = refused.expect("move taken during the kickoff freeze");
+    assert_eq!(refused.code, ErrorCode::InputFrozen);
+    assert_eq!(refused.message, "kickoff");
+    println!("reset to kickoff: move refused as {}", refused.message);
+
+    step_until(&mut game, &clock, |phase| phase == SoccerPhase::Play);
+    assert!(try_move(&mut game, &clock, &mut replies, &reply_to).is_none());
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    let puck = soccer.teams[0].pucks[0];
+    assert!(soccer.bodies[puck].linvel().x > 0.0);
+    println!("kickoff over: move taken");
+
+    let mut alone = SoccerGame::with_config(SoccerGameConfig {
+        goal_reset_ticks: 3,
+        kickoff_freeze_ticks: 3,
+        ..SoccerGameConfig::default()
+    });
+    let ball = alone.balls[0];
+    alone.bodies[ball].set_translation(vector![-260.0, 0.0], true);
+    alone.bodies[ball].set_linvel(vector![-800.0, 0.0], true);
+    let mut kicked_off = false;
+    for _ in 0..120 {
+        alone.update(TICK.as_secs_f64() * 1000.0);
+        kicked_off |= matches!(alone.phase, SoccerPhase::Kickoff { .. });
+        if kicked_off && alone.phase == SoccerPhase::Play {
+            break;
+        }
+    }
+    assert!(kicked_off, "never scored");
+    assert_eq!(alone.phase, SoccerPhase::Play, "stuck in kickoff");
+    println!("updated on its own: back in play after kickoff");
+
+    alone.reset();
+    assert_eq!(
+        alone.take_timer_changes(),
+        [TimerChange::Cancel(KICKOFF_TIMER)]
+    );
+    println!("reset: a pending kickoff is cancelled");
+}
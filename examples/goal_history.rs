@@ -0,0 +1,78 @@
+mod common;
+
+use common::StepClock;
+use rapier2d::prelude::*;
+use rust_backend::game::{
+    Game, GamePhase, HistoryEvent, SoccerGame, SoccerGameConfig, SoccerPhase,
+};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_millis(16);
+
+// Steps until the soccer phase is one that matches, for up to a second.
+fn step_until(game: &mut Game, clock: &StepClock, want: impl Fn(SoccerPhase) -> bool) {
+    for _ in 0..60 {
+        if want(game.downcast::<SoccerGame>().unwrap().phase) {
+            return;
+        }
+        clock.advance(TICK);
+        game.update();
+    }
+    panic!("no such phase within a second");
+}
+
+// A goal in a real soccer game must land in the game's history after the
+// joins, followed by the kickoff reset, and still be handed on through
+// take_goals. The ball is sent into alice's goal untouched, so the goal is
+// bob's and nobody is credited with it.
+fn main() {
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    let config = SoccerGameConfig {
+        goal_reset_ticks: 3,
+        kickoff_freeze_ticks: 3,
+        ..SoccerGameConfig::default()
+    };
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(SoccerGame::with_config(config), players);
+    game.set_clock(clock.clone());
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        clock.advance(TICK);
+        game.update();
+    }
+
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    let ball = soccer.balls[0];
+    soccer.bodies[ball].set_translation(vector![-260.0, 0.0], true);
+    soccer.bodies[ball].set_linvel(vector![-800.0, 0.0], true);
+    step_until(&mut game, &clock, |phase| {
+        matches!(phase, SoccerPhase::Kickoff { .. })
+    });
+
+    let events: Vec<_> = game.history().map(|entry| entry.event.clone()).collect();
+    assert_eq!(
+        events,
+        vec![
+            HistoryEvent::Joined {
+                player: 0,
+                name: "alice".to_string()
+            },
+            HistoryEvent::Joined {
+                player: 1,
+                name: "bob".to_string()
+            },
+            HistoryEvent::Goal { player: 1 },
+            HistoryEvent::Reset { reason: "kickoff" },
+        ]
+    );
+    assert_eq!(game.take_goals(), vec![1]);
+    let goal = game.history().nth(2).unwrap();
+    println!("goal recorded at tick {} ({} ms)", goal.tick, goal.at_ms);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{rally, raw_connect_as, raw_next, raw_welcome, RawStream, RALLY};
+use futures::SinkExt;
+use rust_backend::message::{
+    EventsSinceMessage, GoalScoredMessage, MessageType, ProtocolVersion, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18164";
+
+fn goal() -> WsMessage {
+    let goal = GoalScoredMessage {
+        scorer: 0,
+        toucher: Some(0),
+        puck: Some(1),
+        own_goal: false,
+    };
+    return WsMessage::from_payload(MessageType::GoalScored, &goal);
+}
+
+fn echo() -> WsMessage {
+    return WsMessage {
+        msg_type: MessageType::Echo,
+        payload: b"after the goal".to_vec(),
+    };
+}
+
+// The type of the next GoalScored or Echo broadcast.
+async fn next_broadcast(stream: &mut RawStream) -> u8 {
+    let watched = [MessageType::GoalScored, MessageType::Echo];
+    return raw_next(stream, &watched).await.msg_type as u8;
+}
+
+// GoalScored is new in v11: a v11 connection hears it, live and replayed
+// through EventsSince, and a v10 one in the same game hears only what it
+// already knew, the broadcast after it included.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect_as(ADDR, "name=alice&mode=rally", ProtocolVersion::V11).await;
+    let mut bob = raw_connect_as(ADDR, "name=bob&mode=rally", ProtocolVersion::V10).await;
+    let welcome = raw_welcome(&mut alice).await;
+    raw_welcome(&mut bob).await;
+    let game = games.read().await[&(welcome.game_id as usize)].clone();
+
+    game.read().await.broadcast(goal());
+    game.read().await.broadcast(echo());
+    assert_eq!(
+        next_broadcast(&mut alice).await,
+        MessageType::GoalScored as u8
+    );
+    assert_eq!(next_broadcast(&mut alice).await, MessageType::Echo as u8);
+    assert_eq!(next_broadcast(&mut bob).await, MessageType::Echo as u8);
+    println!("v11 heard the goal, v10 only the echo after it");
+
+    let request = WsMessage::from_payload(MessageType::EventsSince, &EventsSinceMessage { seq: 0 });
+    for stream in [&mut alice, &mut bob] {
+        stream
+            .send(Message::Binary(request.to_bytes()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        next_broadcast(&mut alice).await,
+        MessageType::GoalScored as u8
+    );
+    assert_eq!(next_broadcast(&mut bob).await, MessageType::Echo as u8);
+    println!("and the same again replayed through EventsSince");
+}
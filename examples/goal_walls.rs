@@ -0,0 +1,99 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig, WallMaterial};
+
+// a tick at 60Hz, in ms
+const TICK: f64 = 1000.0 / 60.0;
+
+// A game with every puck parked along the top wall, out of the way.
+fn parked(config: SoccerGameConfig) -> SoccerGame {
+    let mut game = SoccerGame::with_config(config);
+    let top = game.height / 2.0 - 30.0;
+    for (i, puck) in game.pucks.clone().into_iter().enumerate() {
+        let x = -200.0 + 40.0 * i as f32;
+        game.bodies[puck].set_translation(vector![x, top], true);
+    }
+    return game;
+}
+
+// Sends the ball, or else a puck with the ball out of its way, at the left
+// goal mouth. Returns the furthest left it got and the goals scored.
+fn shoot(config: SoccerGameConfig, ball: bool) -> (f32, Vec<usize>) {
+    let mut game = parked(config);
+    let shot = if ball { game.balls[0] } else { game.pucks[0] };
+    if !ball {
+        // off the centre spot, where the puck coming back would hit it
+        let bottom = 30.0 - game.height / 2.0;
+        game.bodies[game.balls[0]].set_translation(vector![0.0, bottom], true);
+    }
+    game.bodies[shot].set_translation(vector![-100.0, 0.0], true);
+    game.bodies[shot].set_linvel(vector![-600.0, 0.0], true);
+    let mut furthest = f32::INFINITY;
+    let mut goals = vec![];
+    for _ in 0..90 {
+        game.update(TICK);
+        // before the goal ends the loop, as the tick that scored is the
+        // one that carried it over the line
+        furthest = furthest.min(game.bodies[shot].translation().x);
+        goals.extend(game.take_goals());
+        if !goals.is_empty() {
+            break;
+        }
+    }
+    return (furthest, goals);
+}
+
+// The ball passes through a goal segment and scores, where a puck sent the
+// same way bounces off it. The walls are a plain list: unmark the goal and
+// the ball bounces too, give a wall another material and it plays
+// differently.
+fn main() {
+    let config = SoccerGameConfig::default();
+    let goal_line = -config.width / 2.0;
+
+    let (furthest, goals) = shoot(config.clone(), true);
+    assert_eq!(goals, vec![1], "the ball didn't score in the left goal");
+    assert!(furthest < goal_line, "the ball never crossed the line");
+    println!("ball through the left goal mouth to x = {}", furthest);
+
+    let (furthest, goals) = shoot(config.clone(), false);
+    assert!(goals.is_empty());
+    assert!(furthest > goal_line, "a puck went through the goal");
+    println!("puck bounced off the goal mouth at x = {}", furthest);
+
+    let mut closed = config.clone();
+    for wall in closed.walls.iter_mut() {
+        wall.goal = None;
+    }
+    let (furthest, goals) = shoot(closed.clone(), true);
+    assert!(goals.is_empty());
+    assert!(furthest > goal_line, "the ball went through a plain wall");
+    println!(
+        "with the goal unmarked the ball bounced at x = {}",
+        furthest
+    );
+
+    // a dead wall keeps none of the ball's speed
+    for wall in closed.walls.iter_mut() {
+        wall.material = WallMaterial {
+            restitution: 0.0,
+            friction: 0.4,
+        };
+    }
+    let mut lively = parked(config.clone());
+    let mut dead = parked(closed);
+    let mut speeds = vec![];
+    for game in [&mut lively, &mut dead] {
+        let ball = game.balls[0];
+        game.bodies[ball].set_translation(vector![0.0, 0.0], true);
+        game.bodies[ball].set_linvel(vector![0.0, -600.0], true);
+        for _ in 0..40 {
+            game.update(TICK);
+        }
+        speeds.push(game.bodies[ball].linvel().norm());
+    }
+    assert!(speeds[1] < speeds[0], "the material made no difference");
+    println!(
+        "off the default walls {}, off dead ones {}",
+        speeds[0], speeds[1]
+    );
+}
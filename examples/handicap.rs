@@ -0,0 +1,82 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+use rust_backend::message::{
+    ChallengeMessage, HandicapConfig, MessageType, TeamHandicap, WsMessage, MAX_HANDICAP,
+    MAX_HANDICAP_SCORE,
+};
+use std::time::Duration;
+
+const FRAME_MS: f64 = 1000.0 / 60.0;
+
+// player's first puck's velocity straight after a move of vx, 0, from rest.
+fn shot(game: &mut SoccerGame, player: usize, vx: f32) -> f32 {
+    let puck = game.team(player).unwrap().pucks[0];
+    game.bodies[puck].set_linvel(vector![0.0, 0.0], true);
+    game.apply_move(puck, vx, 0.0, 0.0);
+    return game.bodies[puck].linvel().x;
+}
+
+// A Challenge can ask for uneven rules: each side's shot speed cap and move
+// cooldown are scaled, a side can start goals up, and a rematch can swap
+// the two. Multipliers are clamped, and a Challenge from a client that
+// predates handicaps still decodes. In the game, the same shot comes out at
+// different speeds for the two sides.
+fn main() {
+    let legacy = WsMessage::from_payload(MessageType::Challenge, &"bob".to_string());
+    let challenge = ChallengeMessage::decode(&legacy.payload).unwrap();
+    assert_eq!(challenge.target_name, "bob");
+    assert_eq!(challenge.handicap, None);
+    println!("an old Challenge decodes with no handicap");
+
+    let handicap = HandicapConfig {
+        left: TeamHandicap {
+            shot_speed: 1.0,
+            move_cooldown: 10.0,
+            score: 0,
+        },
+        right: TeamHandicap {
+            shot_speed: 0.5,
+            move_cooldown: f32::NAN,
+            score: 9,
+        },
+        swap_on_rematch: true,
+    };
+    let clamped = handicap.clamped();
+    assert_eq!(clamped.left.move_cooldown, MAX_HANDICAP);
+    assert_eq!(clamped.right.move_cooldown, 1.0);
+    assert_eq!(clamped.right.score, MAX_HANDICAP_SCORE);
+    let rematch = clamped.rematch();
+    assert_eq!((rematch.left, rematch.right), (clamped.right, clamped.left));
+    let kept = HandicapConfig {
+        swap_on_rematch: false,
+        ..clamped
+    };
+    assert_eq!(kept.rematch(), kept);
+    println!("clamped to {:?}, swapped for a rematch", clamped);
+
+    let mut game = SoccerGame::with_config(SoccerGameConfig {
+        max_shot_speed: 500.0,
+        move_cooldown: Duration::from_millis(100),
+        ..SoccerGameConfig::default()
+    });
+    game.set_handicap(handicap);
+    assert_eq!(game.handicap, Some(clamped));
+    assert_eq!(game.scores(), vec![0, MAX_HANDICAP_SCORE]);
+    let (left, right) = (shot(&mut game, 0, 800.0), shot(&mut game, 1, 800.0));
+    assert_eq!((left, right), (500.0, 250.0));
+    println!("the same 800 shot left at {} and {}", left, right);
+
+    // 200ms on: past the right's 100ms cooldown, inside the left's 400ms
+    for _ in 0..12 {
+        game.update(FRAME_MS);
+    }
+    assert_eq!(shot(&mut game, 0, 300.0), 0.0);
+    assert_eq!(shot(&mut game, 1, 300.0), 250.0);
+    println!("the left side is still cooling down when the right can move");
+
+    game.reset();
+    assert_eq!(game.handicap, None);
+    assert_eq!(game.scores(), vec![0, 0]);
+    assert_eq!(shot(&mut game, 1, 800.0), 500.0);
+    println!("a reset world plays even again");
+}
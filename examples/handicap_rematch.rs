@@ -0,0 +1,110 @@
+mod common;
+
+use common::{next, rally, settle, welcome, Events, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::message::{ChallengeReceivedMessage, HandicapConfig, TeamHandicap};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18167";
+
+// A challenge that names no handicap after a handicapped game between the
+// same two players is their rematch: it is played with the last one's
+// handicap, swapped when that one's swap_on_rematch is set, whoever
+// challenges. The target is shown it before accepting, as with one the
+// challenger asked for.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let handicap = HandicapConfig {
+        left: TeamHandicap {
+            shot_speed: 2.0,
+            ..TeamHandicap::default()
+        },
+        right: TeamHandicap {
+            move_cooldown: 0.5,
+            score: 2,
+            ..TeamHandicap::default()
+        },
+        swap_on_rematch: true,
+    };
+    let first = play("alice", "bob", Some(handicap)).await;
+    assert_eq!(first, Some(handicap.clamped()));
+    println!("alice vs bob with {:?}", first.unwrap());
+
+    // alice on the left again, each side with the other's half
+    let second = play("alice", "bob", None).await;
+    assert_eq!(second, Some(handicap.clamped().rematch()));
+    println!("the rematch swapped it: {:?}", second.unwrap());
+
+    // swapped back for the third, which bob starts from the left
+    let third = play("bob", "alice", None).await;
+    let expected = handicap.clamped().mirrored();
+    assert_eq!(third, Some(expected));
+    assert_eq!(third.unwrap().right, handicap.clamped().left);
+    println!("bob's challenge gave alice her first game's half back");
+
+    let even = play("carol", "dave", None).await;
+    assert_eq!(even, None);
+    println!("players who never had a handicap still play even");
+}
+
+// Challenger and target meet in the lobby, the challenge goes out, and the
+// game it starts is played and left. The handicap the target was shown.
+async fn play(
+    challenger: &str,
+    target: &str,
+    handicap: Option<HandicapConfig>,
+) -> Option<HandicapConfig> {
+    let (challenging, mut challenging_events) = join(challenger).await;
+    let (targeted, mut targeted_events) = join(target).await;
+    match handicap {
+        Some(handicap) => challenging.challenge_with_handicap(target, handicap),
+        None => challenging.challenge(target),
+    };
+    let received = challenged(&mut targeted_events).await;
+    targeted.answer_challenge(received.challenge_id, true);
+    welcome(&mut challenging_events).await;
+    welcome(&mut targeted_events).await;
+    for client in [&challenging, &targeted] {
+        client.leave_game();
+        let closed = async {
+            while client.is_connected() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("still connected after leaving");
+    }
+    return received.handicap;
+}
+
+async fn join(name: &str) -> (GameClient, Events) {
+    let options = ClientOptions {
+        mode: Some(RALLY),
+        lobby: true,
+        ..common::options()
+    };
+    let (client, mut events) = common::connect(ADDR, name, options).await;
+    settle(&client, &mut events).await;
+    return (client, events);
+}
+
+async fn challenged(events: &mut Events) -> ChallengeReceivedMessage {
+    return next(events, |event| match event {
+        ClientEvent::ChallengeReceived(challenge) => Some(challenge),
+        _ => None,
+    })
+    .await;
+}
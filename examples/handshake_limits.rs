@@ -0,0 +1,93 @@
+use rust_backend::server::{Server, ServerConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18109";
+const AUTH_ADDR: &str = "127.0.0.1:18110";
+
+// Every garbage identity is turned away in the upgrade itself, with its own
+// status: an oversized auth header with 431, a token or name with odd
+// characters and a name or game id that don't parse with 400, and a token
+// without its scheme with 401 once an auth service is in use.
+#[tokio::main]
+async fn main() {
+    for (addr, auth_url) in [(ADDR, None), (AUTH_ADDR, Some("http://127.0.0.1:9/"))] {
+        let config = ServerConfig {
+            addr: addr.to_string(),
+            http_addr: None,
+            health_addr: None,
+            auth_url: auth_url.map(str::to_string),
+            max_auth_header_bytes: 64,
+            max_name_len: 8,
+            ..ServerConfig::default()
+        };
+        tokio::spawn(Server::new(config).run());
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    let huge = format!("Bearer {}", "x".repeat(1024 * 1024));
+    let cases = [
+        ("name=alice", Some("Bearer alice-token"), 101),
+        ("name=alice", Some(huge.as_str()), 431),
+        ("name=alice", Some("Bearer tab\there"), 400),
+        ("name=alice", Some("Bearer "), 400),
+        ("name=Ana%20Li", None, 101),
+        ("name=averylongname", None, 400),
+        ("name=al%0Aice", None, 400),
+        ("name=%20alice", None, 400),
+        ("name=", None, 400),
+        ("name=alice&game=abc", None, 400),
+        ("name=alice&game=7", None, 101),
+    ];
+    for (query, auth, status) in cases {
+        let got = upgrade(ADDR, query, auth).await;
+        let shown = auth.map(|auth| &auth[..auth.len().min(20)]);
+        println!("?{} with {:?}: {}", query, shown, got);
+        assert_eq!(got, status, "?{} with {:?}", query, shown);
+    }
+
+    // the auth service is never reached for these
+    assert_eq!(
+        upgrade(AUTH_ADDR, "name=alice", Some("alice-token")).await,
+        401
+    );
+    assert_eq!(
+        upgrade(AUTH_ADDR, "name=alice", Some("Basic alice")).await,
+        401
+    );
+    println!("a token without its scheme refused with 401 once auth is on");
+}
+
+// The status the server answers a websocket upgrade for query with, auth as
+// the Authorization header if there is one.
+async fn upgrade(addr: &str, query: &str, auth: Option<&str>) -> u16 {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let auth = auth.map_or(String::new(), |auth| format!("Authorization: {}\r\n", auth));
+    let request = format!(
+        "GET /?{} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n{}\r\n",
+        query, addr, auth
+    );
+    // a server that gives up on the request early may close before all of
+    // it is written; its answer is still there to read
+    let _ = stream.write_all(request.as_bytes()).await;
+    let mut response = vec![];
+    let mut byte = [0u8; 1];
+    let read = async {
+        while !response.ends_with(b"\r\n") {
+            match stream.read(&mut byte).await {
+                Ok(1) => response.push(byte[0]),
+                _ => break,
+            }
+        }
+    };
+    timeout(Duration::from_secs(5), read)
+        .await
+        .expect("no status line");
+    let line = String::from_utf8_lossy(&response);
+    return line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .unwrap_or(0);
+}
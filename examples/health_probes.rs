@@ -0,0 +1,55 @@
+mod common;
+
+use common::fetch;
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration, Instant};
+
+const ADDR: &str = "127.0.0.1:18150";
+const HEALTH: &str = "127.0.0.1:18151";
+
+// Polls path until it answers want, for up to five seconds, and returns how
+// long that took.
+async fn until(path: &str, want: u16) -> Duration {
+    let started = Instant::now();
+    while fetch(HEALTH, path).await.0 != want {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "{} never answered {}",
+            path,
+            want
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+    return started.elapsed();
+}
+
+// /ready and /live come up once the server is listening and ticking. With
+// the games lock held the tick loop wedges on it: /live turns 503 within
+// live_tick_periods of its last tick, while /ready, which only says the
+// server got going, stays 200. Letting go of the lock brings /live back.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: Some(HEALTH.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    until("/ready", 200).await;
+    until("/live", 200).await;
+    println!("ready and live");
+
+    let wedged = games.write().await;
+    let took = until("/live", 503).await;
+    assert_eq!(fetch(HEALTH, "/ready").await.0, 200);
+    println!("/live went 503 {:?} after the tick loop wedged", took);
+
+    drop(wedged);
+    until("/live", 200).await;
+    println!("/live back to 200 with the lock let go");
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use common::{connect, rally, welcome, RALLY};
+use futures::{SinkExt, StreamExt};
+use rust_backend::message::{
+    ErrorCode, ErrorMessage, HelloMessage, MessageType, ProtocolVersion, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18095";
+
+fn hello() -> HelloMessage {
+    return HelloMessage {
+        protocol: ProtocolVersion::V6 as u8,
+        build: "hello-example".to_string(),
+        state_version: ProtocolVersion::V4 as u8,
+        format: 0,
+        byte_order: 1,
+        role: 0,
+    };
+}
+
+fn frame(hello: &HelloMessage) -> Message {
+    return Message::Binary(WsMessage::from_payload(MessageType::Hello, hello).to_bytes());
+}
+
+// A v6 connection has to open with a Hello the server can honour; every
+// other opening ends in a 1002 close, with an UnsupportedHello error first
+// when the Hello itself was the problem.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        handshake_timeout: Duration::from_secs(1),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (first, close) = open(Some(frame(&hello()))).await;
+    let agreed = first
+        .filter(|first| matches!(first.msg_type, MessageType::Hello))
+        .and_then(|first| first.decode::<HelloMessage>())
+        .expect("no Hello back");
+    assert_eq!(agreed.state_version, 4);
+    assert_eq!(agreed.byte_order, 1);
+    assert_eq!(agreed.build, env!("CARGO_PKG_VERSION"));
+    assert_eq!(close, None);
+    println!("accepted: {:?}", agreed);
+
+    let ping = WsMessage {
+        msg_type: MessageType::Ping,
+        payload: vec![],
+    };
+    let openings = [
+        ("silence", None),
+        ("a text frame", Some(Message::Text("hello".into()))),
+        ("a Ping", Some(Message::Binary(ping.to_bytes()))),
+    ];
+    for (what, opening) in openings {
+        let (_, close) = open(opening).await;
+        assert_eq!(close, Some(1002), "{} closed with {:?}", what, close);
+        println!("{} first: 1002", what);
+    }
+
+    let unsupported = [
+        HelloMessage {
+            protocol: 5,
+            ..hello()
+        },
+        HelloMessage {
+            state_version: 9,
+            ..hello()
+        },
+        HelloMessage {
+            format: 1,
+            ..hello()
+        },
+        HelloMessage {
+            format: 4,
+            byte_order: 0,
+            ..hello()
+        },
+        HelloMessage { role: 7, ..hello() },
+    ];
+    for hello in unsupported {
+        let (first, close) = open(Some(frame(&hello))).await;
+        let error = first
+            .filter(|first| matches!(first.msg_type, MessageType::Error))
+            .and_then(|first| first.decode::<ErrorMessage>())
+            .expect("no error for an unsupported Hello");
+        assert_eq!(error.code, ErrorCode::UnsupportedHello);
+        assert_eq!(close, Some(1002));
+        println!("refused: {}", error.message);
+    }
+
+    // the SDK says hello on its own
+    let (_alice, mut events) = connect(ADDR, "alice", common::options()).await;
+    let (_bob, _) = connect(ADDR, "bob", common::options()).await;
+    welcome(&mut events).await;
+    println!("the SDK joined after its Hello");
+}
+
+// Opens a v6 connection, sends opening if there is one, and returns the
+// first message back and the close code, if the server closed it.
+async fn open(opening: Option<Message>) -> (Option<WsMessage>, Option<u16>) {
+    let url = format!("ws://{}/?name=hi&mode={}", ADDR, RALLY);
+    let mut request = url.into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static("asyncws.v6"),
+    );
+    let (mut stream, _) = connect_async(request).await.unwrap();
+    if let Some(opening) = opening {
+        stream.send(opening).await.unwrap();
+    }
+    let read = async {
+        let mut first = None;
+        while let Some(Ok(message)) = stream.next().await {
+            match message {
+                Message::Binary(data) if first.is_none() => {
+                    first = WsMessage::from_bytes(&data);
+                    // an accepted Hello leaves the connection open
+                    if matches!(
+                        first,
+                        Some(WsMessage {
+                            msg_type: MessageType::Hello,
+                            ..
+                        })
+                    ) {
+                        return (first, None);
+                    }
+                }
+                Message::Close(frame) => return (first, frame.map(|f| u16::from(f.code))),
+                _ => (),
+            }
+        }
+        return (first, None);
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("the connection hung");
+}
@@ -0,0 +1,179 @@
+mod common;
+
+use common::{connect, rally, welcome, RALLY};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use rust_backend::game::DuplicateConnection;
+use rust_backend::hooks::ServerHooks;
+use rust_backend::message::{
+    GameOverMessage, GameOverReason, MessageType, SoccerMoveMessage, WsMessage,
+};
+use rust_backend::middleware::ConnCtx;
+use rust_backend::server::{Server, ServerConfig};
+use std::sync::{Arc, Mutex};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18128";
+
+// Writes down every hook it is given, in the order they come.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Recorder {
+    fn push(&self, line: String) -> BoxFuture<'_, ()> {
+        return Box::pin(async move {
+            self.0.lock().unwrap().push(line);
+        });
+    }
+    fn lines(&self) -> Vec<String> {
+        return self.0.lock().unwrap().clone();
+    }
+}
+
+fn name(ctx: &ConnCtx) -> &str {
+    return ctx.name().unwrap_or("?");
+}
+
+impl ServerHooks for Recorder {
+    fn on_connect<'a>(&'a self, ctx: &'a ConnCtx) -> BoxFuture<'a, ()> {
+        return self.push(format!("{} connected", name(ctx)));
+    }
+    fn on_join_game<'a>(&'a self, ctx: &'a ConnCtx, player: usize) -> BoxFuture<'a, ()> {
+        let game_id = ctx.game_id().unwrap();
+        return self.push(format!(
+            "{} joined {} in slot {}",
+            name(ctx),
+            game_id,
+            player
+        ));
+    }
+    fn on_move<'a>(
+        &'a self,
+        ctx: &'a ConnCtx,
+        player: usize,
+        soccer_move: &'a SoccerMoveMessage,
+    ) -> BoxFuture<'a, ()> {
+        return self.push(format!(
+            "{} in slot {} moved {} at {},{}",
+            name(ctx),
+            player,
+            soccer_move.target,
+            soccer_move.vx,
+            soccer_move.vy
+        ));
+    }
+    fn on_leave<'a>(&'a self, ctx: &'a ConnCtx, left: bool) -> BoxFuture<'a, ()> {
+        let how = if left { "left" } else { "dropped out of" };
+        return self.push(format!("{} {} {}", name(ctx), how, ctx.game_id().unwrap()));
+    }
+    fn on_game_over<'a>(
+        &'a self,
+        game_id: usize,
+        game_over: &'a GameOverMessage,
+    ) -> BoxFuture<'a, ()> {
+        assert_eq!(game_over.reason, GameOverReason::Forfeit);
+        return self.push(format!(
+            "game {} over, slot {:?} won",
+            game_id, game_over.winner
+        ));
+    }
+}
+
+// Hooks set before run hear each connection arrive, get placed, move and
+// leave, and the match its leaving forfeits end, in that order. A
+// connection another one of the same player took the slot from doesn't
+// report leaving when it goes.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        duplicate_connection: DuplicateConnection::Replace,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let recorder = Recorder::default();
+    server.set_hooks(Box::new(recorder.clone()));
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (alice, mut alice_events) = connect(ADDR, "alice", common::options()).await;
+    wait_for(&recorder, 1).await;
+    let (_bob, mut bob_events) = connect(ADDR, "bob", common::options()).await;
+    let game_id = welcome(&mut alice_events).await.game_id;
+    let session = welcome(&mut bob_events).await.session_token;
+    let mut joins = recorder.lines().split_off(2);
+    joins.sort();
+    assert_eq!(recorder.lines()[..2], ["alice connected", "bob connected"]);
+    assert_eq!(
+        joins,
+        [
+            format!("alice joined {} in slot 0", game_id),
+            format!("bob joined {} in slot 1", game_id),
+        ]
+    );
+
+    alice.send_move(2, 3.0, -4.0);
+    wait_for(&recorder, 5).await;
+    assert_eq!(recorder.lines()[4], "alice in slot 0 moved 2 at 3,-4");
+
+    // bob again, into his own slot; his first connection is closed
+    let url = format!(
+        "ws://{}/?name=bob&mode={}&game={}&session={}",
+        ADDR, RALLY, game_id, session
+    );
+    let (mut bob_again, _) = connect_async(url.as_str()).await.unwrap();
+    let welcomed = async {
+        while let Some(Ok(message)) = bob_again.next().await {
+            if let Message::Binary(data) = message {
+                let message = WsMessage::from_bytes(&data);
+                if message.map_or(false, |message| {
+                    matches!(message.msg_type, MessageType::Welcome)
+                }) {
+                    return;
+                }
+            }
+        }
+        panic!("bob's second connection wasn't welcomed");
+    };
+    timeout(Duration::from_secs(5), welcomed).await.unwrap();
+    sleep(Duration::from_millis(300)).await;
+    assert!(
+        !recorder
+            .lines()
+            .iter()
+            .any(|line| line.contains("dropped out")),
+        "the replaced connection reported leaving: {:?}",
+        recorder.lines()
+    );
+
+    let heard = recorder.lines().len();
+    alice.leave_game();
+    wait_for(&recorder, heard + 2).await;
+    assert_eq!(
+        recorder.lines()[heard..],
+        [
+            format!("alice left {}", game_id),
+            format!("game {} over, slot Some(1) won", game_id),
+        ]
+    );
+    for line in recorder.lines() {
+        println!("{}", line);
+    }
+}
+
+// Until the recorder has heard at least count hooks.
+async fn wait_for(recorder: &Recorder, count: usize) {
+    let heard = async {
+        while recorder.lines().len() < count {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), heard)
+        .await
+        .unwrap_or_else(|_| panic!("only heard {:?}", recorder.lines()));
+}
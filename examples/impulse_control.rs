@@ -0,0 +1,64 @@
+use rust_backend::game::{ControlMode, GameLogic, SoccerGame};
+
+// a tick at 60Hz, in ms
+const TICK: f64 = 1000.0 / 60.0;
+
+fn game(control_mode: ControlMode) -> SoccerGame {
+    let mut soccer = SoccerGame::new();
+    soccer.control_mode = control_mode;
+    return soccer;
+}
+
+fn puck_vx(soccer: &SoccerGame) -> f32 {
+    return soccer.body_states()[0].linvel[0];
+}
+
+// The same moves under each control mode. Velocity sets a puck's velocity
+// outright, so one move starts it at full speed and the next reverses it;
+// Impulse pushes it by at most max_impulse a tick, scaled by its mass, so
+// the puck picks up speed over several ticks, a second move in the same
+// tick adds nothing once the cap is spent, and turning it around takes as
+// long as getting it going did.
+fn main() {
+    let mut set = game(ControlMode::Velocity);
+    let puck = set.pucks[0];
+    let mass = set.bodies[puck].mass();
+    // a quarter of the way to the speed asked for, each tick
+    let mut pushed = game(ControlMode::Impulse {
+        max_impulse: 50.0 * mass,
+    });
+    for soccer in [&mut set, &mut pushed] {
+        soccer.apply_move(puck, 200.0, 0.0, 0.0);
+    }
+    assert!((puck_vx(&set) - 200.0).abs() < 1e-3);
+    assert!((puck_vx(&pushed) - 50.0).abs() < 1e-3);
+    println!(
+        "from rest: set {}, pushed {}",
+        puck_vx(&set),
+        puck_vx(&pushed)
+    );
+
+    pushed.apply_move(puck, 200.0, 0.0, 0.0);
+    assert!((puck_vx(&pushed) - 50.0).abs() < 1e-3);
+    println!("a second move in the same tick added nothing");
+
+    for _ in 0..3 {
+        pushed.update(TICK);
+        pushed.apply_move(puck, 200.0, 0.0, 0.0);
+    }
+    let going = puck_vx(&pushed);
+    assert!(going > 190.0 && going <= 200.0, "pushed reached {}", going);
+    println!("pushed up to {} over four ticks", going);
+
+    for soccer in [&mut set, &mut pushed] {
+        soccer.update(TICK);
+        soccer.apply_move(puck, -200.0, 0.0, 0.0);
+    }
+    assert!((puck_vx(&set) + 200.0).abs() < 1e-3);
+    assert!(puck_vx(&pushed) > 100.0, "momentum cancelled in one tick");
+    println!(
+        "reversing: set {} at once, pushed still {}",
+        puck_vx(&set),
+        puck_vx(&pushed)
+    );
+}
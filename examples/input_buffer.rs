@@ -0,0 +1,61 @@
+use rust_backend::game::{CommandLink, Game, GamePhase, SoccerGame, SoccerGameConfig, SoccerPhase};
+use rust_backend::message::SoccerMoveMessage;
+use rust_backend::stats::PlayerId;
+use tokio::sync::mpsc;
+
+// A game in play, its command link, and what is sent on it before each
+// update, returning the last move seq applied after each.
+fn play(depth: u64, sends: &[&[u32]]) -> Vec<u32> {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(
+        SoccerGame::with_config(SoccerGameConfig::default()),
+        players,
+    );
+    game.input_buffer_ticks = depth;
+    game.phase = GamePhase::Playing;
+    game.downcast_mut::<SoccerGame>().unwrap().phase = SoccerPhase::Play;
+    let (replies, _refused) = mpsc::unbounded_channel();
+    let link = CommandLink {
+        commands: game.commands(),
+        replies,
+    };
+    let mut applied = vec![];
+    for seqs in sends {
+        for &seq in *seqs {
+            let shot = SoccerMoveMessage {
+                vx: 100.0 * seq as f32,
+                vy: 0.0,
+                target: 0,
+                angular: 0.0,
+                seq,
+            };
+            assert!(link.send_move(0, &shot));
+        }
+        game.update();
+        applied.push(game.players[0].last_move_seq);
+    }
+    return applied;
+}
+
+// With a buffer two ticks deep, moves that arrive out of cadence (one, then
+// two at once, then none) are each applied exactly two ticks after they
+// arrived, one a tick. A flood is held to twice the depth, its newest move
+// standing in for the rest, and with no buffer a burst lands all at once.
+fn main() {
+    let sends: &[&[u32]] = &[&[1], &[2, 3], &[], &[4], &[], &[]];
+    let applied = play(2, sends);
+    assert_eq!(applied, [0, 0, 1, 2, 3, 4]);
+    println!("buffered two ticks: {:?}", applied);
+
+    let applied = play(0, sends);
+    assert_eq!(applied, [1, 3, 3, 4, 4, 4]);
+    println!("unbuffered: {:?}", applied);
+
+    let flood: Vec<u32> = (1..=10).collect();
+    let applied = play(2, &[&flood, &[], &[], &[], &[], &[]]);
+    assert_eq!(applied, [0, 0, 1, 2, 10, 10]);
+    println!("ten at once, two ticks deep: {:?}", applied);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{connect, Events};
+use futures::StreamExt;
+use rust_backend::client::{ClientEvent, ClientOptions};
+use rust_backend::message::{
+    ByteOrder, InterestGroup, MatchPhase, PartialState, ProtocolVersion, SnapshotHeader,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18121";
+// frames checked after each change of interest
+const FRAMES: usize = 20;
+
+// A minimap that only wants the ball asks for it and gets one body a frame,
+// the one after every puck. Switching to its own pucks mid-game takes effect
+// within a frame or two, and going back to full interest brings plain State
+// back. The client runs big-endian so the layout is converted both ways.
+#[tokio::main]
+async fn main() {
+    let partial = PartialState {
+        header: SnapshotHeader {
+            tick: 9,
+            phase: MatchPhase::Playing,
+            remaining_ds: None,
+            scores: vec![1, 2],
+            level: None,
+        },
+        interest: 0b1010,
+        positions: vec![(1.0, 2.0), (3.0, 4.0)],
+    };
+    let mut bytes = partial.to_bytes();
+    assert!(ByteOrder::Big.swap_partial_state(ProtocolVersion::V8, &mut bytes));
+    assert!(ByteOrder::Big.swap_partial_state(ProtocolVersion::V8, &mut bytes));
+    assert_eq!(PartialState::from_bytes(&bytes), Some(partial.clone()));
+    assert_eq!(partial.body(3), Some((3.0, 4.0)));
+    assert_eq!(partial.body(2), None);
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let options = ClientOptions {
+        practice: true,
+        reconnect: false,
+        byte_order: ByteOrder::Big,
+        state_poll_interval: None,
+        ..ClientOptions::default()
+    };
+    let (client, mut events) = connect(ADDR, "minimap", options).await;
+    let mut states = Box::pin(client.subscribe_state());
+    client.set_state_rate(60);
+    let full = timeout(Duration::from_secs(5), states.next())
+        .await
+        .expect("no State")
+        .expect("stream ended");
+    let pucks = full.pucks.len();
+    println!(
+        "{} pucks and {} balls in full State",
+        pucks,
+        full.balls.len()
+    );
+
+    client.set_interest(0, InterestGroup::Ball.bit());
+    let frames = partial_states(&mut events, FRAMES).await;
+    for frame in &frames {
+        assert_eq!(frame.interest, 1 << pucks, "not just the ball");
+        assert_eq!(frame.positions.len(), 1);
+        assert_eq!(frame.body(pucks), Some(frame.positions[0]));
+    }
+    println!("{} ball-only frames, one body each", frames.len());
+
+    client.set_interest(0, InterestGroup::MyPucks.bit());
+    let frames = partial_states(&mut events, FRAMES).await;
+    // a frame or two may have been built before the change landed
+    let mine = frames
+        .iter()
+        .skip_while(|frame| frame.interest == 1 << pucks);
+    let mine: Vec<&PartialState> = mine.collect();
+    assert!(
+        mine.len() >= FRAMES - 2,
+        "interest change never took effect"
+    );
+    for frame in &mine {
+        assert_eq!(frame.interest, mine[0].interest);
+        assert!(
+            frame.interest != 0 && frame.interest < 1 << pucks,
+            "not just pucks"
+        );
+        assert_eq!(frame.positions.len(), frame.interest.count_ones() as usize);
+    }
+    println!(
+        "switched to own pucks: {} bodies a frame",
+        mine[0].positions.len()
+    );
+
+    client.set_interest(0, 0);
+    timeout(Duration::from_secs(5), states.next())
+        .await
+        .expect("full State never came back")
+        .expect("stream ended");
+    println!("full State again");
+}
+
+// The next count PartialState events, within a few seconds.
+async fn partial_states(events: &mut Events, count: usize) -> Vec<PartialState> {
+    let read = async {
+        let mut frames = vec![];
+        while let Some(event) = events.next().await {
+            if let ClientEvent::PartialState(frame) = event {
+                frames.push(frame);
+                if frames.len() == count {
+                    return frames;
+                }
+            }
+        }
+        panic!("connection closed");
+    };
+    return timeout(Duration::from_secs(5), read)
+        .await
+        .expect("PartialState stopped coming");
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::{Empty, StepClock};
+use rust_backend::game::{Game, Games, SlotConnection};
+use rust_backend::invariants::{
+    check, tick_stall, Problem, Violation, CLOSED_LINGER, TICK_STALL,
+};
+use rust_backend::stats::PlayerId;
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
+
+// the tick loop's period at 60 Hz, and at the slowest debug tick rate
+const FRAME: Duration = Duration::from_micros(16_667);
+const SLOWEST: Duration = Duration::from_secs(10);
+
+fn game(names: &[&str]) -> Arc<RwLock<Game>> {
+    let players = names
+        .iter()
+        .map(|name| (PlayerId::Guest(name.to_string()), name.to_string()))
+        .collect();
+    return Arc::new(RwLock::new(Game::new(Empty, players)));
+}
+
+fn connection(client_id: usize) -> SlotConnection {
+    return SlotConnection {
+        client_id,
+        evict: mpsc::unbounded_channel().0,
+        inbox: mpsc::unbounded_channel().0,
+    };
+}
+
+// Games put together by hand and then broken one way at a time: each break
+// is reported against the game it is in, and the map is clean again once
+// it is undone. A game closed a moment ago is on its way out of the map and
+// passes; one still there CLOSED_LINGER later doesn't, and neither does an
+// open one nothing has updated for TICK_STALL, or for a few periods of a
+// loop slowed down past that.
+#[tokio::main]
+async fn main() {
+    let games: Games = Arc::new(RwLock::new(BTreeMap::new()));
+    let last_game_id = AtomicUsize::new(2);
+    let (first, second) = (game(&["alice", "bob"]), game(&["carol"]));
+    games.write().await.insert(1, Arc::clone(&first));
+    games.write().await.insert(2, Arc::clone(&second));
+    first.write().await.bind_connection(0, connection(10));
+    second.write().await.bind_connection(0, connection(20));
+    assert_eq!(check(&games, &last_game_id, FRAME).await, vec![]);
+    println!("two healthy games pass");
+
+    let extra = first
+        .write()
+        .await
+        .add_player(PlayerId::Guest("dave".to_string()), "dave".to_string());
+    let found = check(&games, &last_game_id, FRAME).await;
+    assert_eq!(
+        found,
+        vec![
+            Violation {
+                game_id: 1,
+                problem: Problem::OverCapacity { players: 3, max: 2 },
+            },
+            Violation {
+                game_id: 1,
+                problem: Problem::SlotOutOfRange { index: 2, max: 2 },
+            },
+        ]
+    );
+    println!("{}\n{}", found[0], found[1]);
+    first.write().await.remove_player(extra);
+
+    second
+        .write()
+        .await
+        .add_player(PlayerId::Guest("erin".to_string()), "erin".to_string());
+    second.write().await.players[1].index = 0;
+    let found = check(&games, &last_game_id, FRAME).await;
+    assert_eq!(found[0].problem, Problem::SlotTaken { index: 0 });
+    println!("{}", found[0]);
+    second.write().await.players[1].index = 1;
+
+    second.write().await.bind_connection(1, connection(10));
+    let found = check(&games, &last_game_id, FRAME).await;
+    let twice = Problem::ConnectionTwice {
+        client_id: 10,
+        index: 1,
+        also: (1, 0),
+    };
+    assert_eq!(found[0].problem, twice);
+    println!("{}", found[0]);
+    second.write().await.unbind_connection(1, 10);
+    assert_eq!(check(&games, &last_game_id, FRAME).await, vec![]);
+
+    games.write().await.insert(9, game(&["frank"]));
+    let found = check(&games, &last_game_id, FRAME).await;
+    assert_eq!(found[0].problem, Problem::UnknownId { last_game_id: 2 });
+    println!("{}", found[0]);
+    games.write().await.remove(&9);
+
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    first.write().await.set_clock(clock.clone());
+    second.write().await.set_clock(clock.clone());
+    clock.advance(TICK_STALL);
+    let found = check(&games, &last_game_id, FRAME).await;
+    assert_eq!(found.len(), 2);
+    let stalled = Problem::NotTicking {
+        stalled_for: TICK_STALL,
+    };
+    assert_eq!(found[0].problem, stalled);
+    println!("{}", found[0]);
+    first.write().await.update();
+    second.write().await.update();
+    assert_eq!(check(&games, &last_game_id, FRAME).await, vec![]);
+
+    clock.advance(SLOWEST);
+    assert_eq!(check(&games, &last_game_id, SLOWEST).await, vec![]);
+    clock.advance(tick_stall(SLOWEST) - SLOWEST);
+    assert_eq!(check(&games, &last_game_id, SLOWEST).await.len(), 2);
+    println!("a loop at 0.1 Hz stalls after {:?}", tick_stall(SLOWEST));
+    first.write().await.update();
+    second.write().await.update();
+
+    second.read().await.close();
+    clock.advance(CLOSED_LINGER / 2);
+    assert_eq!(check(&games, &last_game_id, FRAME).await, vec![]);
+    clock.advance(CLOSED_LINGER);
+    first.write().await.update();
+    let found = check(&games, &last_game_id, FRAME).await;
+    assert_eq!(found.len(), 1);
+    assert!(matches!(found[0].problem, Problem::ClosedInMap { .. }));
+    println!("{}", found[0]);
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use common::{connect, exit_on_panic, get, options, rally, welcome, Events, RALLY};
+use futures::future::join_all;
+use rust_backend::client::GameClient;
+use rust_backend::invariants::InvariantChecks;
+use rust_backend::server::{Server, ServerConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18116";
+const HTTP_ADDR: &str = "127.0.0.1:18117";
+const FLAPPERS: usize = 60;
+
+// Connections that drop as soon as they are matched, or before, don't leave
+// games behind, and one landing in a game with a real player frees its slot
+// again before kickoff rather than holding it for a reconnect. Players who
+// stay through the flapping each end up in a live game, and once they go
+// too the server has no games left.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: Some(HTTP_ADDR.to_string()),
+        health_addr: None,
+        check_invariants: InvariantChecks::Panic,
+        ..ServerConfig::default()
+    };
+    exit_on_panic();
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    join_all((0..FLAPPERS).map(flap)).await;
+    sleep(Duration::from_millis(500)).await;
+    let left = games().await;
+    assert!(left.is_empty(), "{} games leaked: {:?}", left.len(), left);
+    println!("{} flapping connections left no games", FLAPPERS);
+
+    let steady = async {
+        return join_all((0..4).map(|i| join(format!("steady{}", i)))).await;
+    };
+    let flapping = join_all((FLAPPERS..2 * FLAPPERS).map(flap));
+    let (steady, _) = tokio::join!(steady, flapping);
+    sleep(Duration::from_millis(500)).await;
+    let listed = games().await;
+    let players: Vec<&serde_json::Value> = listed
+        .iter()
+        .flat_map(|game| game["players"].as_array().unwrap())
+        .collect();
+    assert_eq!(players.len(), 4, "{:?}", listed);
+    assert!(players.iter().all(|player| player["connected"] == true));
+    for (_, _, game_id) in &steady {
+        assert!(listed.iter().any(|game| game["id"] == *game_id));
+    }
+    println!(
+        "4 players kept through the flapping, in {} games",
+        listed.len()
+    );
+
+    drop(steady);
+    sleep(Duration::from_millis(500)).await;
+    let left = games().await;
+    assert!(left.is_empty(), "{} games leaked: {:?}", left.len(), left);
+    println!("none left once they go");
+}
+
+// Connects and drops again at one of a few points: straight after asking
+// to upgrade, once accepted, or a moment into its game.
+async fn flap(i: usize) {
+    let mut stream = TcpStream::connect(ADDR).await.unwrap();
+    let request = format!(
+        "GET /?name=flap{}&mode={} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        i, RALLY, ADDR
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    if i % 3 == 0 {
+        return;
+    }
+    let mut byte = [0u8; 1];
+    let _ = timeout(Duration::from_secs(5), stream.read(&mut byte)).await;
+    if i % 3 == 2 {
+        sleep(Duration::from_millis(10 + (i % 7) as u64)).await;
+    }
+}
+
+// A matchmade player that stays, with its game id.
+async fn join(name: String) -> (GameClient, Events, u32) {
+    let (client, mut events) = connect(ADDR, &name, options()).await;
+    let game_id = welcome(&mut events).await.game_id;
+    return (client, events, game_id);
+}
+
+// GET /games
+async fn games() -> Vec<serde_json::Value> {
+    let listing: serde_json::Value = serde_json::from_str(&get(HTTP_ADDR, "/games").await).unwrap();
+    return listing["games"].as_array().unwrap().clone();
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{rally, raw_connect, raw_welcome, RALLY};
+use futures::future::join_all;
+use rust_backend::matchmaking::{Candidate, Joining, MatchmakingStrategy, Placement};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18146";
+const PLAYERS: usize = 100;
+
+// Joins the fullest open game, or opens one to wait in.
+struct OpenOrJoin;
+
+impl MatchmakingStrategy for OpenOrJoin {
+    fn place(&self, candidates: &[Candidate], _player: &Joining) -> Placement {
+        return candidates
+            .iter()
+            .filter(|candidate| candidate.players < candidate.max_players)
+            .max_by_key(|candidate| candidate.players)
+            .map_or(Placement::Create, |candidate| {
+                Placement::Join(candidate.game_id)
+            });
+    }
+}
+
+// A hundred players arriving at once under a strategy that opens a game
+// whenever it finds none to join: each join looks for an open game and
+// joins or opens one in a single step, so none of them opens a game next
+// to someone else's half-empty one, and they all end up in pairs.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    server.set_matchmaking(Box::new(OpenOrJoin));
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let joins = (0..PLAYERS).map(|i| {
+        tokio::spawn(async move {
+            let query = format!("name=racer{}&mode=rally", i);
+            let mut stream = raw_connect(ADDR, &query).await;
+            let welcome = raw_welcome(&mut stream).await;
+            return (welcome.game_id, stream);
+        })
+    });
+    let joined: Vec<_> = join_all(joins)
+        .await
+        .into_iter()
+        .map(|joined| joined.unwrap())
+        .collect();
+    assert_eq!(joined.len(), PLAYERS);
+
+    let games: Vec<_> = games.read().await.values().cloned().collect();
+    let mut alone = 0;
+    for game in &games {
+        let players = game.read().await.players.len();
+        assert!(players <= 2);
+        alone += (players == 1) as usize;
+    }
+    assert_eq!(games.len(), PLAYERS / 2);
+    assert_eq!(alone, 0, "{} players waiting alone", alone);
+    println!("{} players in {} games, none alone", PLAYERS, games.len());
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use common::{join_ready, raw_connect, raw_welcome, Drift, RALLY};
+use futures::SinkExt;
+use rust_backend::game::{GameLogic, GamePhase, Games, PauseConfig, ShortHanded};
+use rust_backend::message::{LeaveGameMessage, MessageType, PlayerRecord, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use rust_backend::stats::{PlayerId, Stats};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18157";
+const CLOCKED: u8 = 9;
+const CLOCKED_DOUBLES: u8 = 10;
+const MATCH_MS: f64 = 300.0;
+
+// Two teams, one slot each or, doubled, slots 0 and 1 against 2 and 3, with
+// team 0 a goal up from the start, playing MATCH_MS of match clock.
+#[derive(Default)]
+struct Clocked {
+    played_ms: f64,
+    doubles: bool,
+}
+
+impl GameLogic for Clocked {
+    fn game_type(&self) -> u8 {
+        return match self.doubles {
+            true => CLOCKED_DOUBLES,
+            false => CLOCKED,
+        };
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, elapsed: f64) {
+        self.played_ms += elapsed;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![CLOCKED];
+    }
+    fn max_players(&self) -> usize {
+        return match self.doubles {
+            true => 4,
+            false => 2,
+        };
+    }
+    fn team_of(&self, player: usize) -> Option<u8> {
+        return match self.doubles {
+            true => Some((player / 2) as u8),
+            false => Some(player as u8),
+        };
+    }
+    fn scores(&self) -> Vec<u32> {
+        return vec![1, 0];
+    }
+    fn remaining_time(&self) -> Option<Duration> {
+        let left = (MATCH_MS - self.played_ms).max(0.0);
+        return Some(Duration::from_secs_f64(left / 1000.0));
+    }
+}
+
+async fn record(stats: &Stats, name: &str) -> PlayerRecord {
+    return stats.read().await.get(&PlayerId::Guest(name.to_string()));
+}
+
+async fn playing(games: &Games, game_id: u32) {
+    let game = games.read().await[&(game_id as usize)].clone();
+    let playing = async {
+        while game.read().await.phase != GamePhase::Playing {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), playing)
+        .await
+        .expect("the game never started");
+}
+
+async fn gone(games: &Games, game_id: u32, why: &str) {
+    let gone = async {
+        while games.read().await.contains_key(&(game_id as usize)) {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), gone).await.expect(why);
+}
+
+// Every way a started match can end puts a win and a loss on the
+// leaderboard: a player leaving, the clock running out, and both players
+// dropping, where the game is removed once it has sat dormant and the one
+// who dropped first forfeits. When the clock runs out on teams, the loss
+// goes to the other team, never to the winner's teammate.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        pause: PauseConfig {
+            resume_countdown: Duration::ZERO,
+            short_handed: ShortHanded::Wait(Some(Duration::from_secs(60))),
+            ..PauseConfig::default()
+        },
+        dormant_timeout: Duration::from_secs(1),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Drift::default()) as Box<dyn GameLogic>;
+    });
+    server.register_mode("clocked", CLOCKED, |_state, _practice| {
+        return Box::new(Clocked::default()) as Box<dyn GameLogic>;
+    });
+    server.register_mode("doubles", CLOCKED_DOUBLES, |_state, _practice| {
+        let doubles = Clocked {
+            doubles: true,
+            ..Clocked::default()
+        };
+        return Box::new(doubles) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    let stats = server.stats();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let ready = WsMessage {
+        msg_type: MessageType::Ready,
+        payload: vec![],
+    };
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let alice_welcome = raw_welcome(&mut alice).await;
+    raw_welcome(&mut bob).await;
+    for stream in [&mut alice, &mut bob] {
+        stream
+            .send(Message::Binary(ready.to_bytes()))
+            .await
+            .unwrap();
+    }
+    playing(&games, alice_welcome.game_id).await;
+    let leave = LeaveGameMessage {
+        session_token: alice_welcome.session_token,
+    };
+    let leave = WsMessage::from_payload(MessageType::LeaveGame, &leave);
+    alice.send(Message::Binary(leave.to_bytes())).await.unwrap();
+    gone(
+        &games,
+        alice_welcome.game_id,
+        "alice's game outlived her leaving",
+    )
+    .await;
+    assert_eq!(record(&stats, "alice").await.losses, 1);
+    assert_eq!(record(&stats, "bob").await.wins, 1);
+    println!("alice left: bob wins");
+
+    let ((carol, _carol_link), (_, _dave_link)) = tokio::join!(
+        join_ready(ADDR, "name=carol&mode=clocked"),
+        join_ready(ADDR, "name=dave&mode=clocked")
+    );
+    gone(&games, carol.game_id, "the clock never ran out").await;
+    // slot 0's team is the one ahead
+    let (ahead, behind) = match carol.player_index {
+        0 => ("carol", "dave"),
+        _ => ("dave", "carol"),
+    };
+    let (winner, loser) = (record(&stats, ahead).await, record(&stats, behind).await);
+    assert_eq!((winner.wins, winner.losses), (1, 0));
+    assert_eq!((loser.wins, loser.losses), (0, 1));
+    println!("time up: {}, a goal ahead, wins", ahead);
+
+    let names = ["gina", "hank", "ivan", "judy"];
+    let queries = names.map(|name| format!("name={}&mode=doubles", name));
+    let (gina, hank, ivan, judy) = tokio::join!(
+        join_ready(ADDR, &queries[0]),
+        join_ready(ADDR, &queries[1]),
+        join_ready(ADDR, &queries[2]),
+        join_ready(ADDR, &queries[3])
+    );
+    let _links = [gina.1, hank.1, ivan.1, judy.1];
+    let game_id = gina.0.game_id;
+    let mut slots = [gina.0, hank.0, ivan.0, judy.0]
+        .iter()
+        .zip(names)
+        .map(|(welcome, name)| (welcome.player_index, name))
+        .collect::<Vec<_>>();
+    slots.sort();
+    gone(&games, game_id, "the doubles clock never ran out").await;
+    let mut results = vec![];
+    for (_, name) in &slots {
+        let record = record(&stats, name).await;
+        results.push((record.wins, record.losses));
+    }
+    // slot 0 for team 0, and slot 2, not its teammate in slot 1, for team 1
+    assert_eq!(results, [(1, 0), (0, 0), (0, 1), (0, 0)]);
+    println!(
+        "time up on teams: {} beats {}, {}'s teammate {} isn't charged the loss",
+        slots[0].1, slots[2].1, slots[0].1, slots[1].1
+    );
+
+    let ((erin, erin_link), (_, frank_link)) = tokio::join!(
+        join_ready(ADDR, "name=erin&mode=rally"),
+        join_ready(ADDR, "name=frank&mode=rally")
+    );
+    playing(&games, erin.game_id).await;
+    let game = games.read().await[&(erin.game_id as usize)].clone();
+    frank_link.abort();
+    let waiting = async {
+        while !matches!(game.read().await.phase, GamePhase::WaitingForPlayers { .. }) {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), waiting)
+        .await
+        .expect("the game didn't wait for frank");
+    erin_link.abort();
+    gone(&games, erin.game_id, "the abandoned game was never removed").await;
+    let (erin, frank) = (record(&stats, "erin").await, record(&stats, "frank").await);
+    assert_eq!((erin.wins, erin.losses), (1, 0));
+    assert_eq!((frank.wins, frank.losses), (0, 1));
+    println!("both dropped: frank, first to go, forfeits to erin");
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{connect, next, welcome, Events};
+use rust_backend::client::{ClientEvent, ClientOptions};
+use rust_backend::message::LobbyStatus;
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18081";
+
+// One connection waits in the queue with a lobby subscription while another
+// starts a practice game; the watcher must be told about the new game, and
+// about it going away once the other player leaves.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        // the watcher must stay queued rather than get a bot match
+        queue_timeout: None,
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (watcher, mut events) = connect(ADDR, "watcher", ClientOptions::default()).await;
+    watcher.subscribe_lobby();
+    let snapshot = next_update(&mut events).await;
+    assert!(snapshot.is_empty(), "fresh server lists {:?}", snapshot);
+
+    let options = ClientOptions {
+        practice: true,
+        reconnect: false,
+        ..ClientOptions::default()
+    };
+    let (player, mut player_events) = connect(ADDR, "player", options).await;
+    let update = next_update(&mut events).await;
+    assert_eq!(update.len(), 1);
+    assert_ne!(update[0].1, LobbyStatus::Removed);
+    println!("game {} appeared as {:?}", update[0].0, update[0].1);
+
+    // leaving needs the session token from Welcome
+    welcome(&mut player_events).await;
+    player.leave_game();
+    let update = next_update(&mut events).await;
+    assert_eq!(update, vec![(update[0].0, LobbyStatus::Removed)]);
+    println!("game {} removed", update[0].0);
+}
+
+// (game id, status) pairs from the next LobbyUpdate.
+async fn next_update(events: &mut Events) -> Vec<(u32, LobbyStatus)> {
+    return next(events, |event| match event {
+        ClientEvent::LobbyUpdate(update) => Some(
+            update
+                .games
+                .iter()
+                .map(|game| (game.game_id, game.status))
+                .collect(),
+        ),
+        _ => None,
+    })
+    .await;
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{StepClock, Steps};
+use rust_backend::game::{CommandLink, Game, GamePhase};
+use rust_backend::message::{
+    ErrorCode, ErrorMessage, EventMessage, LockstepInputMessage, LockstepMove, LockstepStepMessage,
+    MessageType, SoccerMoveMessage, WsMessage, LOCKSTEP_WINDOW,
+};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+
+const WAIT: Duration = Duration::from_millis(100);
+
+fn input(tick: u64, vx: f32) -> LockstepInputMessage {
+    let moves = vec![LockstepMove {
+        target: 0,
+        vx,
+        vy: 0.0,
+        angular: 0.0,
+    }];
+    return LockstepInputMessage {
+        tick: tick as u32,
+        moves,
+    };
+}
+
+// The LockstepStep broadcasts since the last call.
+fn steps(events: &mut broadcast::Receiver<bytes::Bytes>) -> Vec<LockstepStepMessage> {
+    let mut steps = vec![];
+    while let Ok(frame) = events.try_recv() {
+        let inner = WsMessage::from_bytes(&frame)
+            .and_then(|message| message.decode::<EventMessage>())
+            .and_then(|event| WsMessage::from_bytes(&event.frame));
+        if let Some(message) = inner {
+            if matches!(message.msg_type, MessageType::LockstepStep) {
+                steps.extend(message.decode::<LockstepStepMessage>());
+            }
+        }
+    }
+    return steps;
+}
+
+// Once in play a lockstep game doesn't step until both players have sent
+// input for the tick, and then applies both together and says so. A player
+// who goes quiet holds the game up only until the wait runs out, after
+// which it steps without them. Every step is one fixed tick of game time.
+// Free-running moves, and input for a tick already stepped or too far
+// ahead, are refused.
+fn main() {
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(Steps::default(), players);
+    game.set_clock(clock.clone());
+    game.lockstep = Some(WAIT);
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        game.update();
+    }
+    let (replies, mut refused) = mpsc::unbounded_channel();
+    let link = CommandLink {
+        commands: game.commands(),
+        replies,
+    };
+    let mut events = game.subscribe();
+    assert_eq!(game.describe().tick_mode, "lockstep");
+
+    let start = game.tick();
+    link.send_input(0, input(start, 1.0));
+    for _ in 0..5 {
+        game.update();
+    }
+    assert_eq!(game.tick(), start, "stepped without bob");
+    println!("tick {}: alice's input in, waiting on bob", start);
+
+    link.send_input(1, input(start, -1.0));
+    link.send_input(0, input(start + 1, 2.0));
+    game.update();
+    assert_eq!(game.tick(), start + 1);
+    let step = steps(&mut events);
+    assert_eq!(step.len(), 1);
+    assert_eq!(step[0].tick as u64, start);
+    let players: Vec<u8> = step[0].inputs.iter().map(|(player, _)| *player).collect();
+    assert_eq!(players, [0, 1]);
+    assert_eq!(step[0].inputs[1].1[0].vx, -1.0);
+    assert!(step[0].missing.is_empty());
+    println!("tick {}: both inputs applied together", start);
+
+    game.update();
+    assert_eq!(game.tick(), start + 1, "stepped without bob");
+    clock.advance(WAIT);
+    game.update();
+    assert_eq!(game.tick(), start + 2);
+    let step = steps(&mut events);
+    assert_eq!(step[0].missing, [1]);
+    assert_eq!(step[0].inputs.len(), 1);
+    println!("tick {}: bob timed out after {:?}", start + 1, WAIT);
+
+    let elapsed = &game.downcast::<Steps>().unwrap().elapsed;
+    let played = &elapsed[elapsed.len() - 2..];
+    assert!(played.iter().all(|ms| *ms == game.tick_ms), "{:?}", played);
+
+    link.send_input(1, input(start, 0.0));
+    link.send_input(1, input(start + 3 + LOCKSTEP_WINDOW as u64, 0.0));
+    let shot = SoccerMoveMessage {
+        vx: 1.0,
+        vy: 0.0,
+        target: 0,
+        angular: 0.0,
+        seq: 0,
+    };
+    link.send_move(0, &shot);
+    game.update();
+    for _ in 0..3 {
+        let refusal = refused.try_recv().expect("input not refused");
+        let refusal = refusal.decode::<ErrorMessage>().unwrap();
+        assert_eq!(refusal.code, ErrorCode::LockstepInput);
+        println!("refused: {}", refusal.message);
+    }
+}
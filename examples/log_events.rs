@@ -0,0 +1,19 @@
+use rust_backend::server::{Server, ServerConfig};
+use tokio::sync::broadcast::error::RecvError;
+
+// Runs the server and prints every event on its event bus.
+#[tokio::main]
+async fn main() {
+    let server = Server::new(ServerConfig::default());
+    let mut events = server.subscribe_events();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => println!("{:?}", event),
+                Err(RecvError::Lagged(missed)) => println!("Missed {} events", missed),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+    server.run().await;
+}
@@ -0,0 +1,197 @@
+use rust_backend::game::{
+    Game, GameLogic, GamePhase, Recipient, SlotConnection, SoccerGame, SoccerGameConfig, TickMode,
+    MAX_LOGIC_MESSAGES,
+};
+use rust_backend::message::{
+    AnnouncementMessage, EventMessage, MessageType, TurnMessage, WsMessage,
+};
+use rust_backend::stats::PlayerId;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+
+// Sends what it is told to on its next update, and always tells slot 1
+// alone that it serves.
+#[derive(Default)]
+struct Rally {
+    queued: Vec<(Recipient, WsMessage)>,
+    sent: Vec<(Recipient, WsMessage)>,
+}
+
+impl GameLogic for Rally {
+    fn game_type(&self) -> u8 {
+        return 7;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        self.sent.push((Recipient::Slot(1), text("you serve")));
+    }
+    fn take_messages(&mut self) -> Vec<(Recipient, WsMessage)> {
+        let mut messages = std::mem::take(&mut self.queued);
+        messages.append(&mut self.sent);
+        return messages;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+}
+
+fn text(text: &str) -> WsMessage {
+    return WsMessage::from_payload(
+        MessageType::Announcement,
+        &AnnouncementMessage {
+            text: text.to_string(),
+        },
+    );
+}
+
+fn game<G: GameLogic + 'static>(logic: G) -> (Game, Vec<mpsc::UnboundedReceiver<WsMessage>>) {
+    let players = ["alice", "bob"]
+        .iter()
+        .map(|name| (PlayerId::Guest(name.to_string()), name.to_string()))
+        .collect();
+    let mut game = Game::new(logic, players);
+    let mut inboxes = vec![];
+    for index in 0..2 {
+        let (inbox, received) = mpsc::unbounded_channel();
+        game.bind_connection(
+            index,
+            SlotConnection {
+                client_id: 10 + index,
+                evict: mpsc::unbounded_channel().0,
+                inbox,
+            },
+        );
+        inboxes.push(received);
+        game.mark_ready(index);
+    }
+    game.pause_config.resume_countdown = Duration::ZERO;
+    // the update that starts play is the logic's first
+    while game.phase != GamePhase::Playing {
+        game.update();
+    }
+    return (game, inboxes);
+}
+
+// Texts waiting in an inbox.
+fn texts(inbox: &mut mpsc::UnboundedReceiver<WsMessage>) -> Vec<String> {
+    let mut texts = vec![];
+    while let Ok(message) = inbox.try_recv() {
+        if let Some(announcement) = message.decode::<AnnouncementMessage>() {
+            texts.push(announcement.text);
+        }
+    }
+    return texts;
+}
+
+// Texts broadcast as Events since the last call.
+fn events(events: &mut broadcast::Receiver<bytes::Bytes>) -> Vec<String> {
+    let mut texts = vec![];
+    while let Ok(frame) = events.try_recv() {
+        let inner = WsMessage::from_bytes(&frame)
+            .and_then(|event| event.decode::<EventMessage>())
+            .and_then(|event| WsMessage::from_bytes(&event.frame))
+            .and_then(|message| message.decode::<AnnouncementMessage>());
+        texts.extend(inner.map(|announcement| announcement.text));
+    }
+    return texts;
+}
+
+// A game's logic sends messages of its own from update: one for a single
+// slot reaches that slot's connection and no one else's, AllPlayers reaches
+// every slot without being logged, and Everyone goes out as an Event. What
+// is queued while the game is held back still goes out, and past
+// MAX_LOGIC_MESSAGES in one update the rest are dropped. In turn-based
+// soccer each Turn reaches only the slot whose turn it is, and a slot that
+// reconnects during its turn is told again with the ticks it has left.
+#[tokio::main]
+async fn main() {
+    let (mut rally, mut inboxes) = game(Rally::default());
+    let mut broadcast = rally.subscribe();
+    assert_eq!(texts(&mut inboxes[0]), Vec::<String>::new());
+    assert_eq!(texts(&mut inboxes[1]), vec!["you serve"]);
+    assert!(events(&mut broadcast).is_empty());
+    println!("only slot 1 heard it serves");
+    rally.update();
+    texts(&mut inboxes[1]);
+
+    let logic = rally.downcast_mut::<Rally>().unwrap();
+    logic.queued.push((Recipient::AllPlayers, text("rally on")));
+    logic.queued.push((Recipient::Everyone, text("score 0-0")));
+    rally.set_tick_mode(TickMode::Manual);
+    // the game's own notice that it is held back
+    events(&mut broadcast);
+    rally.update();
+    assert_eq!(texts(&mut inboxes[0]), vec!["rally on"]);
+    assert_eq!(texts(&mut inboxes[1]), vec!["rally on"]);
+    assert_eq!(events(&mut broadcast), vec!["score 0-0"]);
+    println!("held back, the game still sent to all players and everyone");
+
+    rally.set_tick_mode(TickMode::Normal);
+    let logic = rally.downcast_mut::<Rally>().unwrap();
+    for n in 0..MAX_LOGIC_MESSAGES * 2 {
+        logic
+            .queued
+            .push((Recipient::Slot(0), text(&format!("spam {}", n))));
+    }
+    rally.update();
+    let flooded = texts(&mut inboxes[0]);
+    assert_eq!(flooded.len(), MAX_LOGIC_MESSAGES);
+    assert_eq!(texts(&mut inboxes[1]), Vec::<String>::new());
+    println!(
+        "{} messages in one update: slot 0 got the first {}, the serve was dropped",
+        MAX_LOGIC_MESSAGES * 2 + 1,
+        flooded.len()
+    );
+
+    let soccer = SoccerGame::with_config(SoccerGameConfig {
+        turn_ticks: Some(3),
+        ..SoccerGameConfig::default()
+    });
+    let (mut soccer, mut inboxes) = game(soccer);
+    let mut turns = vec![];
+    for _ in 0..7 {
+        for (index, inbox) in inboxes.iter_mut().enumerate() {
+            while let Ok(message) = inbox.try_recv() {
+                let turn = message.decode::<TurnMessage>().unwrap();
+                assert_eq!(turn.player as usize, index);
+                turns.push(turn.player);
+            }
+        }
+        soccer.update();
+    }
+    assert_eq!(turns, vec![0, 1, 0]);
+    let soccer_game = soccer.downcast::<SoccerGame>().unwrap();
+    assert!(soccer_game.has_turn(0) && !soccer_game.has_turn(1));
+    println!("soccer turns went to slots {:?}, each told alone", turns);
+
+    let token = soccer.session_token(0).unwrap().to_string();
+    soccer.unbind_connection(0, 10);
+    soccer.set_connected(0, false);
+    let (inbox, mut received) = mpsc::unbounded_channel();
+    let rejoined = soccer.rejoin(
+        &PlayerId::Guest("alice".to_string()),
+        "alice",
+        Some(&token),
+        SlotConnection {
+            client_id: 20,
+            evict: mpsc::unbounded_channel().0,
+            inbox,
+        },
+    );
+    assert_eq!(rejoined, Ok(Some(0)));
+    soccer.update();
+    let turn = std::iter::from_fn(|| received.try_recv().ok())
+        .find_map(|message| message.decode::<TurnMessage>())
+        .expect("no Turn after reconnecting");
+    assert_eq!(turn.player, 0);
+    assert!(turn.ticks > 0 && turn.ticks <= 3);
+    println!(
+        "slot 0 reconnected mid-turn and was told it has {} ticks",
+        turn.ticks
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::Empty;
+use rust_backend::game::{Game, Games};
+use rust_backend::server::handle_frame;
+use rust_backend::stats::PlayerId;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::{sleep, timeout, Duration};
+
+const GAMES: usize = 20;
+
+fn game() -> Arc<RwLock<Game>> {
+    let players = vec![(PlayerId::Guest("alice".to_string()), "alice".to_string())];
+    return Arc::new(RwLock::new(Game::new(Empty, players)));
+}
+
+// A connection holds one game's lock while the tick loop runs: the frame
+// waits on that game, but never while holding the map, so games are added
+// and removed all the while it is stuck. Removed games the frame already
+// copied out are skipped once it gets going again.
+#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
+async fn main() {
+    let games: Games = Arc::new(RwLock::new(BTreeMap::new()));
+    for game_id in 0..GAMES {
+        games.write().await.insert(game_id, game());
+    }
+    let busy = games.read().await[&0].clone();
+    let held = busy.write().await;
+
+    let (events, _) = broadcast::channel(1024);
+    let frames = Arc::new(AtomicUsize::new(0));
+    let ticking = Arc::clone(&games);
+    let counted = Arc::clone(&frames);
+    tokio::spawn(async move {
+        loop {
+            handle_frame(Arc::clone(&ticking), &events).await;
+            counted.fetch_add(1, Ordering::Relaxed);
+            sleep(Duration::from_millis(1)).await;
+        }
+    });
+    sleep(Duration::from_millis(50)).await;
+    let stuck_at = frames.load(Ordering::Relaxed);
+
+    for game_id in GAMES..GAMES + 100 {
+        let mut map = timeout(Duration::from_secs(5), games.write())
+            .await
+            .expect("the frame held the map while it waited on a game");
+        map.insert(game_id, game());
+        let removed = map.remove(&(game_id - GAMES / 2));
+        drop(map);
+        // removal closes the game, as remove_game does
+        if let Some(removed) = removed {
+            removed.read().await.close();
+        }
+    }
+    assert_eq!(
+        frames.load(Ordering::Relaxed),
+        stuck_at,
+        "the frame didn't wait on the held game"
+    );
+    println!("100 inserts and removals while the frame waited on a held game");
+
+    drop(held);
+    let moving = async {
+        while frames.load(Ordering::Relaxed) < stuck_at + 3 {
+            sleep(Duration::from_millis(5)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), moving)
+        .await
+        .expect("the frame never got going again");
+    println!("frames resumed once the game was let go");
+}
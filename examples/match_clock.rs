@@ -0,0 +1,80 @@
+mod common;
+
+use common::StepClock;
+use rust_backend::game::{Game, GamePhase, SoccerGame, SoccerGameConfig, Stepping};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MATCH: Duration = Duration::from_secs(3);
+const CATCH_UP: Duration = Duration::from_millis(250);
+const FRAME: Duration = Duration::from_micros(16_667);
+
+fn played(game: &Game) -> Duration {
+    return game.downcast::<SoccerGame>().unwrap().played();
+}
+
+// The match clock counts simulated play, not the wall clock. A ten second
+// stall between two ticks takes at most max_catch_up off it, the snapshot
+// header says the same as the logic, and the clock only runs out once the
+// whole match has been stepped through, which is reported once.
+fn main() {
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    let config = SoccerGameConfig {
+        stepping: Stepping::Fixed(1.0 / 120.0),
+        match_duration: Some(MATCH),
+        max_catch_up: CATCH_UP,
+        ..SoccerGameConfig::default()
+    };
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(SoccerGame::with_config(config), players);
+    game.set_clock(clock.clone());
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        clock.advance(FRAME);
+        game.update();
+    }
+
+    for _ in 0..30 {
+        clock.advance(FRAME);
+        game.update();
+    }
+    let before = played(&game);
+    assert!(before > Duration::ZERO && before < MATCH);
+    println!("{:?} played after 30 frames", before);
+
+    clock.advance(Duration::from_secs(10));
+    game.update();
+    let stalled = played(&game) - before;
+    assert!(stalled <= CATCH_UP, "a stall took {:?}", stalled);
+    let remaining = game.logic.remaining_time().unwrap();
+    assert_eq!(remaining, MATCH - played(&game));
+    let header = game.snapshot_header();
+    assert_eq!(
+        header.remaining_ds,
+        Some((remaining.as_millis() / 100) as u16)
+    );
+    assert!(!game.take_time_up());
+    println!("a 10s stall cost {:?}, {:?} left", stalled, remaining);
+
+    let mut frames = 0;
+    while !game.take_time_up() {
+        clock.advance(FRAME);
+        game.update();
+        frames += 1;
+        assert!(frames < 10_000, "match never ended");
+    }
+    assert_eq!(game.logic.remaining_time(), Some(Duration::ZERO));
+    assert!(played(&game) >= MATCH);
+    let frames_left = (remaining.as_micros() / FRAME.as_micros()) as u64;
+    assert!(frames + 1 >= frames_left, "ran out after {} frames", frames);
+    clock.advance(FRAME);
+    game.update();
+    assert!(!game.take_time_up());
+    println!("time up {} frames later, reported once", frames);
+}
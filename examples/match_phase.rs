@@ -0,0 +1,47 @@
+mod common;
+
+use common::{drain, rally, raw_connect, raw_welcome, RALLY};
+use rust_backend::message::{MatchPhase, WelcomeMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18099";
+
+// A game with one of its two players is Waiting, so a client shows the
+// matchmaking screen rather than a live field; once the second player is in
+// it is Ready.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = welcome("name=alice&mode=rally&practice=1").await;
+    assert_eq!(alice.phase, MatchPhase::Waiting);
+    println!("alice alone in game {}: {:?}", alice.game_id, alice.phase);
+
+    let query = format!(
+        "name=bob&mode=rally&game={}&game_token={}",
+        alice.game_id, alice.game_token
+    );
+    let bob = welcome(&query).await;
+    assert_eq!(bob.game_id, alice.game_id);
+    assert_eq!(bob.phase, MatchPhase::Ready);
+    println!("bob joined: {:?}", bob.phase);
+}
+
+// Connects with query and returns its Welcome, leaving the connection open
+// so the player stays in the game.
+async fn welcome(query: &str) -> WelcomeMessage {
+    let mut stream = raw_connect(ADDR, query).await;
+    let welcome = raw_welcome(&mut stream).await;
+    drain(stream);
+    return welcome;
+}
@@ -0,0 +1,138 @@
+use rust_backend::game::{
+    CommandLink, Game, GameLogic, GamePhase, SoccerGame, SoccerGameConfig, SoccerPhase,
+};
+use rust_backend::message::{
+    GameOverMessage, GameOverReason, MessageType, SlotStats, SoccerMoveMessage, WsMessage,
+};
+use rust_backend::stats::{Competitor, PlayerId, StatsStore};
+use tokio::sync::mpsc;
+
+// (player, target, vx, vy), sent together before one update
+type Tick = &'static [(usize, u8, f32, f32)];
+
+// Alice shoots with two pucks and sends one move that stops a puck dead,
+// which isn't a shot; bob shoots once. Then the game runs on its own while
+// the ball goes where it goes.
+const SCRIPT: &[Tick] = &[
+    &[(0, 0, 300.0, 0.0), (0, 1, 0.0, -200.0), (1, 0, -400.0, 0.0)],
+    &[(0, 0, 0.0, 0.0)],
+    &[(0, 0, 5000.0, 0.0)],
+];
+
+// A match played from a script counts each slot's shots, their average
+// speed and the pucks that took them exactly, and its possession adds up
+// to the ticks each slot was the last to touch the ball, credited only
+// when the touch changes hands. Warm-up clears the counts for the next
+// kickoff, and the totals feed the players' records.
+fn main() {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(
+        SoccerGame::with_config(SoccerGameConfig::default()),
+        players,
+    );
+    game.phase = GamePhase::Playing;
+    game.downcast_mut::<SoccerGame>().unwrap().phase = SoccerPhase::Play;
+    let (replies, _refused) = mpsc::unbounded_channel();
+    let link = CommandLink {
+        commands: game.commands(),
+        replies,
+    };
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    let speed = |vx: f32, vy: f32| {
+        let vy = soccer.max_shot_vy.map_or(vy, |max| vy.clamp(-max, max));
+        return (vx * vx + vy * vy).sqrt().min(soccer.max_shot_speed);
+    };
+    let alice_speeds = [speed(300.0, 0.0), speed(0.0, -200.0), speed(5000.0, 0.0)];
+    let bob_speed = speed(-400.0, 0.0);
+
+    let mut seq = 0;
+    let mut held = [0u64; 2];
+    for tick in 0..240 {
+        for &(player, target, vx, vy) in SCRIPT.get(tick).copied().unwrap_or(&[]) {
+            seq += 1;
+            let shot = SoccerMoveMessage {
+                vx,
+                vy,
+                target,
+                angular: 0.0,
+                seq,
+            };
+            assert!(link.send_move(player, &shot));
+        }
+        let before = game.downcast::<SoccerGame>().unwrap().last_ball_toucher;
+        game.update();
+        if let Some(holder) = before {
+            held[holder] += 1;
+        }
+    }
+
+    let stats = game.downcast::<SoccerGame>().unwrap().match_stats();
+    let (alice, bob) = (stats[0], stats[1]);
+    assert_eq!((alice.player, alice.shots, alice.pucks_used), (0, 3, 2));
+    let average = alice_speeds.iter().sum::<f32>() / 3.0;
+    assert!((alice.avg_shot_speed - average).abs() < 0.01, "{:?}", alice);
+    assert_eq!((bob.player, bob.shots, bob.pucks_used), (1, 1, 1));
+    assert!((bob.avg_shot_speed - bob_speed).abs() < 0.01, "{:?}", bob);
+    assert_eq!([alice.possession_ticks, bob.possession_ticks], held);
+    println!("after 240 ticks: {:?}", stats);
+
+    let game_over = GameOverMessage {
+        winner: Some(0),
+        reason: GameOverReason::Forfeit,
+        stats: stats.clone(),
+    };
+    let sent = WsMessage::from_payload(MessageType::GameOver, &game_over);
+    assert_eq!(sent.decode::<GameOverMessage>().unwrap().stats, stats);
+
+    let mut store = StatsStore::new(10);
+    let (alice_id, bob_id) = (
+        PlayerId::Guest("alice".into()),
+        PlayerId::Guest("bob".into()),
+    );
+    let earlier = SlotStats {
+        player: 0,
+        shots: 1,
+        avg_shot_speed: 100.0,
+        pucks_used: 1,
+        possession_ticks: 10,
+    };
+    for alice_stats in [earlier, alice] {
+        store.record_result(
+            Competitor {
+                id: &alice_id,
+                name: "alice",
+                goals: 0,
+                own_goals: 0,
+                stats: alice_stats,
+            },
+            Competitor {
+                id: &bob_id,
+                name: "bob",
+                goals: 0,
+                own_goals: 0,
+                stats: bob,
+            },
+        );
+    }
+    let record = store.get(&alice_id);
+    assert_eq!(record.shots, 4);
+    assert_eq!(record.possession_ticks, 10 + alice.possession_ticks);
+    let weighted = (100.0 + alice_speeds.iter().sum::<f32>()) / 4.0;
+    assert!((record.avg_shot_speed - weighted).abs() < 0.01);
+    assert_eq!(store.get(&bob_id).shots, 2);
+    println!("alice over two matches: {:?}", record);
+
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    GameLogic::warm_up(soccer, true);
+    GameLogic::warm_up(soccer, false);
+    for slot in soccer.match_stats() {
+        assert_eq!(
+            (slot.shots, slot.pucks_used, slot.possession_ticks),
+            (0, 0, 0)
+        );
+    }
+    println!("cleared for the next kickoff");
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{connect, options, rally, welcome, RALLY};
+use rust_backend::client::GameClient;
+use rust_backend::matchmaking::{Candidate, Joining, MatchmakingStrategy, Placement};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18094";
+
+// Everyone gets a game of their own, whatever is already open.
+struct AlwaysCreate;
+
+impl MatchmakingStrategy for AlwaysCreate {
+    fn place(&self, _candidates: &[Candidate], _player: &Joining) -> Placement {
+        return Placement::Create;
+    }
+}
+
+// With a strategy that always creates, two players who would have been
+// paired each end up alone in a game of their own.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    server.set_matchmaking(Box::new(AlwaysCreate));
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let _alice = join("alice").await;
+    let _bob = join("bob").await;
+    // the game each of them is in, and who else is
+    let mut placed = vec![];
+    for (id, game) in games.read().await.iter() {
+        let names: Vec<String> = game
+            .read()
+            .await
+            .players
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
+        placed.push((*id, names));
+    }
+    placed.sort();
+    assert_eq!(placed.len(), 2, "expected two games, got {:?}", placed);
+    assert_eq!(placed[0].1, vec!["alice".to_string()]);
+    assert_eq!(placed[1].1, vec!["bob".to_string()]);
+    println!("alice in game {}, bob in game {}", placed[0].0, placed[1].0);
+}
+
+// A connected client, once it has been placed in a game.
+async fn join(name: &str) -> GameClient {
+    let (client, mut events) = connect(ADDR, name, options()).await;
+    welcome(&mut events).await;
+    return client;
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, RawStream, RALLY};
+use futures::future::join_all;
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18120";
+const MAX_GAMES: usize = 3;
+const FLOOD: usize = 20;
+
+// A flood of practice joins arriving all at once, each wanting a game of its
+// own. Exactly max_games of them get one; the rest are told ServerFull, and
+// the games map never grows past the cap. Once a game goes away there is
+// room for the next one.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        max_games: Some(MAX_GAMES),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let joins = (0..FLOOD).map(|i| join(format!("flood{}", i)));
+    let mut admitted = vec![];
+    let mut full = 0;
+    for (stream, answer) in join_all(joins).await {
+        match answer.msg_type {
+            MessageType::Welcome => admitted.push(stream),
+            _ => {
+                let error = answer.decode::<ErrorMessage>().unwrap();
+                assert_eq!(error.code, ErrorCode::ServerFull);
+                full += 1;
+            }
+        }
+    }
+    assert_eq!(admitted.len(), MAX_GAMES);
+    assert_eq!(full, FLOOD - MAX_GAMES);
+    assert_eq!(games.read().await.len(), MAX_GAMES);
+    println!(
+        "{} joins at once: {} games made, {} turned away",
+        FLOOD,
+        admitted.len(),
+        full
+    );
+
+    let (_stream, answer) = join("late".to_string()).await;
+    assert!(matches!(answer.msg_type, MessageType::Error));
+    assert_eq!(games.read().await.len(), MAX_GAMES);
+    println!("still full after the flood");
+
+    drop(admitted.pop());
+    let mut next = join("next".to_string()).await;
+    for _ in 0..20 {
+        if matches!(next.1.msg_type, MessageType::Welcome) {
+            break;
+        }
+        // the game of the one who left is removed shortly after
+        sleep(Duration::from_millis(100)).await;
+        next = join("next".to_string()).await;
+    }
+    assert!(matches!(next.1.msg_type, MessageType::Welcome));
+    assert_eq!(games.read().await.len(), MAX_GAMES);
+    println!("a game ended, the next player got its place");
+}
+
+// A practice join as name, with the Welcome or Error it was answered with.
+async fn join(name: String) -> (RawStream, WsMessage) {
+    let query = format!("name={}&mode=rally&practice=1", name);
+    let mut stream = raw_connect(ADDR, &query).await;
+    let answer = raw_next(&mut stream, &[MessageType::Welcome, MessageType::Error]).await;
+    return (stream, answer);
+}
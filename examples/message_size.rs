@@ -0,0 +1,74 @@
+use rust_backend::server::{Server, ServerConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18091";
+const LIMIT: usize = 1024;
+
+// A binary frame past max_frame_size must be refused with 1009 rather than
+// buffered and handed to the decoder.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        max_message_size: LIMIT,
+        max_frame_size: LIMIT,
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut stream = upgrade().await;
+    let len = (LIMIT * 4) as u16;
+    // FIN, binary; masked, 16-bit length
+    let mut frame = vec![0x82, 0x80 | 126];
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(&[1, 2, 3, 4]);
+    frame.extend(std::iter::repeat(0u8).take(len as usize));
+    // the server may hang up before it has read all of it
+    let _ = stream.write_all(&frame).await;
+    let code = close_code(&mut stream).await;
+    assert_eq!(code, Some(1009), "oversized frame closed with {:?}", code);
+    println!("{} byte frame closed with 1009", len);
+}
+
+async fn upgrade() -> TcpStream {
+    let mut stream = TcpStream::connect(ADDR).await.unwrap();
+    let request = format!(
+        "GET /?name=flooder HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        ADDR
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = vec![];
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).await.unwrap();
+        response.push(byte[0]);
+    }
+    assert!(response.starts_with(b"HTTP/1.1 101"));
+    return stream;
+}
+
+// Skips frames until a close and returns its status code.
+async fn close_code(stream: &mut TcpStream) -> Option<u16> {
+    let read = async {
+        loop {
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).await.ok()?;
+            let len = match header[1] & 0x7f {
+                126 => stream.read_u16().await.ok()? as usize,
+                127 => stream.read_u64().await.ok()? as usize,
+                len => len as usize,
+            };
+            let mut payload = vec![0u8; len];
+            stream.read_exact(&mut payload).await.ok()?;
+            if header[0] & 0x0f == 0x8 && len >= 2 {
+                return Some(u16::from_be_bytes([payload[0], payload[1]]));
+            }
+        }
+    };
+    return timeout(Duration::from_secs(5), read).await.ok().flatten();
+}
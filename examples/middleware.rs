@@ -0,0 +1,123 @@
+mod common;
+
+use common::{connect, next, options, rally, welcome, Events, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions};
+use rust_backend::game::TickMode;
+use rust_backend::message::{
+    AnnouncementMessage, ChatMessage, ChatScope, CloseReason, MessageType, WsMessage,
+};
+use rust_backend::middleware::{
+    ChatLengthFilter, ConnCtx, ConnectionMiddleware, MiddlewareDecision,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18086";
+
+// Closes any connection that mentions spoilers.
+struct NoSpoilers;
+
+impl ConnectionMiddleware for NoSpoilers {
+    fn on_inbound(&self, ctx: &ConnCtx, msg: &WsMessage) -> MiddlewareDecision {
+        let chat = match msg.msg_type {
+            MessageType::Chat => msg.decode::<ChatMessage>(),
+            _ => None,
+        };
+        if chat.map_or(false, |chat| chat.text.contains("spoiler")) {
+            println!("{:?} ({}) posted a spoiler", ctx.name(), ctx.peer());
+            return MiddlewareDecision::Close(CloseReason::PolicyViolation);
+        }
+        return MiddlewareDecision::Continue;
+    }
+}
+
+// Rewrites every chat line and announcement on its way out.
+struct Shout;
+
+impl ConnectionMiddleware for Shout {
+    fn on_outbound(&self, _ctx: &ConnCtx, msg: &mut WsMessage) -> MiddlewareDecision {
+        match msg.msg_type {
+            MessageType::Chat => {
+                if let Some(mut chat) = msg.decode::<ChatMessage>() {
+                    chat.text = chat.text.to_uppercase();
+                    *msg = WsMessage::from_payload(MessageType::Chat, &chat);
+                }
+            }
+            MessageType::Announcement => {
+                if let Some(mut announcement) = msg.decode::<AnnouncementMessage>() {
+                    announcement.text = announcement.text.to_uppercase();
+                    *msg = WsMessage::from_payload(MessageType::Announcement, &announcement);
+                }
+            }
+            _ => (),
+        }
+        return MiddlewareDecision::Continue;
+    }
+}
+
+// A chat line past the length filter must vanish without ending the
+// connection, lines that pass must come back rewritten on the way out, and
+// a spoiler must close the connection with 1008. A game broadcast reaches
+// middleware as itself, not as the Event it travels in, so it is rewritten
+// too.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    server.add_middleware(ChatLengthFilter { max_chars: 10 });
+    server.add_middleware(NoSpoilers);
+    server.add_middleware(Shout);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let options = ClientOptions {
+        practice: true,
+        ..options()
+    };
+    let (client, mut events) = connect(ADDR, "chatty", options).await;
+    welcome(&mut events).await;
+
+    client.chat(ChatScope::All, "hello");
+    assert_eq!(next_chat(&mut events).await, "HELLO");
+    println!("short line came back as HELLO");
+
+    client.chat(ChatScope::All, "far too long to pass");
+    client.chat(ChatScope::All, "still here");
+    assert_eq!(next_chat(&mut events).await, "STILL HERE");
+    println!("long line dropped, connection kept");
+
+    let game = games.read().await.values().next().unwrap().clone();
+    game.write().await.set_tick_mode(TickMode::Manual);
+    let announcement = next(&mut events, |event| match event {
+        ClientEvent::Announcement(text) => Some(text),
+        _ => None,
+    })
+    .await;
+    assert_eq!(announcement, "AN ADMIN IS STEPPING THIS GAME BY HAND");
+    game.write().await.set_tick_mode(TickMode::Normal);
+    println!("broadcast announcement came through as {}", announcement);
+
+    client.chat(ChatScope::All, "spoiler!");
+    let reason = next(&mut events, |event| match event {
+        ClientEvent::Closed(reason) => Some(reason),
+        _ => None,
+    })
+    .await;
+    assert_eq!(reason, Some(CloseReason::PolicyViolation));
+    println!("spoiler closed the connection with 1008");
+}
+
+async fn next_chat(events: &mut Events) -> String {
+    return next(events, |event| match event {
+        ClientEvent::Chat(chat) => Some(chat.text),
+        _ => None,
+    })
+    .await;
+}
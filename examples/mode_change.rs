@@ -0,0 +1,57 @@
+use rust_backend::game::{Game, GameLogic, SoccerGame, SOCCER_GAME_TYPE};
+use rust_backend::message::{EventMessage, MessageType, ModeChangedMessage, WsMessage};
+use rust_backend::stats::PlayerId;
+
+const DRILLS: u8 = 9;
+
+// A stand-in second mode with no rules.
+struct Drills;
+
+impl GameLogic for Drills {
+    fn game_type(&self) -> u8 {
+        return DRILLS;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+}
+
+// Swaps a running soccer game for another mode. The game type must follow,
+// the players must keep their slots, and a subscriber from before the swap
+// must hear about it on the same receiver.
+fn main() {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(SoccerGame::new(), players);
+    let mut events = game.subscribe();
+    assert_eq!(game.game_type, SOCCER_GAME_TYPE);
+
+    game.replace_logic(Box::new(Drills));
+    assert_eq!(game.game_type, DRILLS);
+    assert!(game.downcast::<Drills>().is_some());
+    assert_eq!(game.players.len(), 2);
+
+    let frame = events.try_recv().expect("subscriber heard nothing");
+    let event = WsMessage::from_bytes(&frame)
+        .and_then(|message| message.decode::<EventMessage>())
+        .expect("not an Event");
+    let inner = WsMessage::from_bytes(&event.frame).unwrap();
+    assert!(matches!(inner.msg_type, MessageType::ModeChanged));
+    let changed = inner.decode::<ModeChangedMessage>().unwrap();
+    assert_eq!(changed.game_type, DRILLS);
+    assert_eq!(changed.seed, game.seed);
+    game.update();
+    println!(
+        "now playing type {} with seed {}",
+        changed.game_type, changed.seed
+    );
+}
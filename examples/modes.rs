@@ -0,0 +1,53 @@
+mod common;
+
+use common::{rally, raw_connect, raw_welcome, RALLY};
+use rust_backend::server::{Server, ServerConfig};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::connect_async;
+
+const ADDR: &str = "127.0.0.1:18096";
+
+// A mode registered by name is asked for with ?mode=name and built by its
+// closure like any built-in one; a name nobody registered is refused at the
+// upgrade.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    let built = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&built);
+    server.register_mode("rally", RALLY, move |state, practice| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        return rally(state, practice);
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = tokio::spawn(welcome("alice", "rally"));
+    let bob = tokio::spawn(welcome("bob", "rally"));
+    let (alice, bob) = (alice.await.unwrap(), bob.await.unwrap());
+    assert_eq!(alice, bob, "alice and bob ended up apart");
+    assert_eq!(built.load(Ordering::Relaxed), 1);
+    let game = games.read().await.get(&alice).cloned().expect("no game");
+    assert_eq!(game.read().await.game_type, RALLY);
+    println!("?mode=rally built game {} of type {}", alice, RALLY);
+
+    let url = format!("ws://{}/?name=carol&mode=croquet", ADDR);
+    assert!(connect_async(url.as_str()).await.is_err());
+    println!("?mode=croquet refused");
+}
+
+// Connects with ?mode=mode and returns the game id from its Welcome.
+async fn welcome(name: &str, mode: &str) -> usize {
+    let query = format!("name={}&mode={}", name, mode);
+    let mut stream = raw_connect(ADDR, &query).await;
+    return raw_welcome(&mut stream).await.game_id as usize;
+}
@@ -0,0 +1,65 @@
+use rust_backend::game::{CommandLink, Game, GamePhase, SoccerGame, SoccerGameConfig, SoccerPhase};
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType, SoccerMoveMessage};
+use rust_backend::stats::PlayerId;
+use tokio::sync::mpsc;
+
+// Moves queued on a game's command channel take effect on its next update,
+// not before, and a move the game refuses comes back on the replies
+// channel instead.
+fn main() {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(
+        SoccerGame::with_config(SoccerGameConfig::default()),
+        players,
+    );
+    game.phase = GamePhase::Playing;
+    game.downcast_mut::<SoccerGame>().unwrap().phase = SoccerPhase::Play;
+    let (replies, mut refused) = mpsc::unbounded_channel();
+    let link = CommandLink {
+        commands: game.commands(),
+        replies,
+    };
+
+    let shot = SoccerMoveMessage {
+        vx: 400.0,
+        vy: 0.0,
+        target: 0,
+        angular: 0.0,
+        seq: 7,
+    };
+    assert!(link.send_move(0, &shot));
+    let puck = game
+        .downcast::<SoccerGame>()
+        .unwrap()
+        .team_puck(0, 0)
+        .unwrap();
+    let speed = |game: &Game| {
+        let soccer = game.downcast::<SoccerGame>().unwrap();
+        return soccer.bodies[puck].linvel().x;
+    };
+    assert_eq!(speed(&game), 0.0, "move applied before the owner ran");
+    assert_eq!(game.players[0].last_move_seq, 0);
+
+    game.update();
+    assert!(speed(&game) > 0.0, "queued move never applied");
+    assert_eq!(game.players[0].last_move_seq, 7);
+    assert!(refused.try_recv().is_err());
+    println!("queued move applied on the next update");
+
+    // paused games refuse moves, and say so to the sender only
+    game.phase = GamePhase::Paused {
+        by: 1,
+        until: std::time::Instant::now() + std::time::Duration::from_secs(60),
+    };
+    assert!(link.send_move(0, &SoccerMoveMessage { seq: 8, ..shot }));
+    game.update();
+    let refusal = refused.try_recv().expect("paused game took the move");
+    assert!(matches!(refusal.msg_type, MessageType::Error));
+    let error = refusal.decode::<ErrorMessage>().unwrap();
+    assert!(matches!(error.code, ErrorCode::GamePaused));
+    assert_eq!(game.players[0].last_move_seq, 7);
+    println!("paused game refused the move: {}", error.message);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, raw_welcome, RALLY};
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18133";
+
+// A second player called alice asking for the game the first alice is
+// playing is refused with NameTaken, and the first keeps her slot; bob
+// joins the same game under his own name. A second carol queueing while the
+// first waits is refused with AlreadyConnected, and the first is paired with
+// someone else.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        queue_timeout: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally&practice=1").await;
+    let welcome = raw_welcome(&mut alice).await;
+    let target = format!("game={}&game_token={}", welcome.game_id, welcome.game_token);
+    let mut again = raw_connect(ADDR, &format!("name=alice&mode=rally&{}", target)).await;
+    let refused = raw_next(&mut again, &[MessageType::Welcome, MessageType::Error]).await;
+    let error = refused
+        .decode::<ErrorMessage>()
+        .expect("the second alice got in");
+    assert_eq!(error.code, ErrorCode::NameTaken);
+    println!("second alice refused: {}", error.message);
+
+    let mut bob = raw_connect(ADDR, &format!("name=bob&mode=rally&{}", target)).await;
+    assert_eq!(raw_welcome(&mut bob).await.game_id, welcome.game_id);
+    let game = games.read().await[&(welcome.game_id as usize)].clone();
+    let names: Vec<String> = game
+        .read()
+        .await
+        .players
+        .iter()
+        .map(|player| player.name.clone())
+        .collect();
+    assert_eq!(names, ["alice", "bob"]);
+    println!("bob joined alice's game under his own name");
+
+    let mut first = raw_connect(ADDR, "name=carol&mode=rally").await;
+    let mut second = raw_connect(ADDR, "name=carol&mode=rally").await;
+    let refused = raw_next(&mut second, &[MessageType::Welcome, MessageType::Error]).await;
+    let refused = refused
+        .decode::<ErrorMessage>()
+        .expect("the second carol got in");
+    assert_eq!(refused.code, ErrorCode::AlreadyConnected);
+    let mut dave = raw_connect(ADDR, "name=dave&mode=rally").await;
+    let with_dave = raw_welcome(&mut dave).await.game_id;
+    let carol = raw_welcome(&mut first).await.game_id;
+    assert_eq!(carol, with_dave, "the two carols were paired together");
+    let game = games.read().await[&(carol as usize)].clone();
+    let carols = game
+        .read()
+        .await
+        .players
+        .iter()
+        .filter(|player| player.name == "carol")
+        .count();
+    assert_eq!(carols, 1);
+    println!("second carol refused; the first waited for dave");
+}
@@ -0,0 +1,76 @@
+use rust_backend::server::{Server, ServerConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18114";
+// the same, allowing every origin as servers do by default
+const OPEN_ADDR: &str = "127.0.0.1:18115";
+
+// A server with an origin allow-list refuses an upgrade from any other
+// page with 403 and takes the listed ones however they are cased, while a
+// client that sends no Origin isn't a web page and gets in. Without a list
+// every origin is let in.
+#[tokio::main]
+async fn main() {
+    for (addr, allowed_origins) in [
+        (ADDR, vec!["https://play.example.com".to_string()]),
+        (OPEN_ADDR, vec![]),
+    ] {
+        let config = ServerConfig {
+            addr: addr.to_string(),
+            http_addr: None,
+            health_addr: None,
+            allowed_origins,
+            ..ServerConfig::default()
+        };
+        tokio::spawn(Server::new(config).run());
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    let cases = [
+        (ADDR, Some("https://play.example.com"), 101),
+        (ADDR, Some("HTTPS://Play.Example.com/"), 101),
+        (ADDR, Some("https://evil.example.net"), 403),
+        (ADDR, Some("http://play.example.com"), 403),
+        (ADDR, Some("null"), 403),
+        (ADDR, None, 101),
+        (OPEN_ADDR, Some("https://evil.example.net"), 101),
+    ];
+    for (addr, origin, status) in cases {
+        let got = upgrade(addr, origin).await;
+        println!("{} from {:?}: {}", addr, origin, got);
+        assert_eq!(got, status, "{} from {:?}", addr, origin);
+    }
+}
+
+// The status the server answers a websocket upgrade with, sent with origin
+// as the Origin header if there is one.
+async fn upgrade(addr: &str, origin: Option<&str>) -> u16 {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let origin = origin.map_or(String::new(), |origin| format!("Origin: {}\r\n", origin));
+    let request = format!(
+        "GET /?name=alice HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n{}\r\n",
+        addr, origin
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = vec![];
+    let mut byte = [0u8; 1];
+    let read = async {
+        while !response.ends_with(b"\r\n") {
+            match stream.read(&mut byte).await {
+                Ok(1) => response.push(byte[0]),
+                _ => break,
+            }
+        }
+    };
+    timeout(Duration::from_secs(5), read)
+        .await
+        .expect("no status line");
+    let line = String::from_utf8_lossy(&response);
+    return line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .unwrap_or(0);
+}
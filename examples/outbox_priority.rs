@@ -0,0 +1,38 @@
+use bytes::Bytes;
+use rust_backend::outbox::{Outbox, Priority, BULK_CAPACITY, CONTROL_CAPACITY};
+
+// Floods a connection's outbox with bulk frames and snapshots, then queues
+// one control frame. The writer must take the control frame first, then
+// only the newest snapshot, then what is left of the bulk backlog; and a
+// control queue that keeps growing must be refused.
+fn main() {
+    let mut outbox = Outbox::new();
+    for i in 0..1000u32 {
+        outbox
+            .push(Priority::Bulk, Bytes::from(format!("bulk {}", i)))
+            .unwrap();
+    }
+    for i in 0..10u32 {
+        outbox
+            .push(Priority::State, Bytes::from(format!("state {}", i)))
+            .unwrap();
+    }
+    outbox.push(Priority::Control, Bytes::from("ack")).unwrap();
+
+    assert_eq!(outbox.pop().unwrap(), "ack");
+    assert_eq!(outbox.pop().unwrap(), "state 9");
+    let backlog: Vec<Bytes> = std::iter::from_fn(|| outbox.pop()).collect();
+    assert_eq!(backlog.len(), BULK_CAPACITY);
+    assert_eq!(backlog[0], format!("bulk {}", 1000 - BULK_CAPACITY));
+    println!(
+        "control first, then the newest snapshot, then {} bulk frames; {} discarded",
+        backlog.len(),
+        outbox.discarded
+    );
+
+    for _ in 0..CONTROL_CAPACITY {
+        outbox.push(Priority::Control, Bytes::from("ack")).unwrap();
+    }
+    assert!(outbox.push(Priority::Control, Bytes::from("ack")).is_err());
+    println!("control overflow refused at {}", CONTROL_CAPACITY);
+}
@@ -0,0 +1,79 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+use rust_backend::message::{GoalScoredMessage, MessageType};
+
+// Knocks the ball into the left goal, which slot 0 defends, off the first
+// puck of slot and returns the GoalScored broadcast for it. Every other puck
+// is lined up along the top wall first: slot 0's second one starts right
+// in front of its goal, and would be the last to touch the ball.
+fn deflect(game: &mut SoccerGame, slot: usize) -> GoalScoredMessage {
+    let ball = game.balls[0];
+    let puck = game.teams[slot].pucks[0];
+    let others: Vec<_> = game
+        .pucks
+        .iter()
+        .filter(|other| **other != puck)
+        .copied()
+        .collect();
+    for (i, other) in others.into_iter().enumerate() {
+        let x = i as f32 * 50.0 - 200.0;
+        game.bodies[other].set_translation(vector![x, 260.0], true);
+    }
+    game.bodies[ball].set_translation(vector![-220.0, 0.0], true);
+    game.bodies[puck].set_translation(vector![-160.0, 0.0], true);
+    game.bodies[puck].set_linvel(vector![-800.0, 0.0], true);
+    for _ in 0..120 {
+        game.update(1000.0 / 60.0);
+        let goal = game
+            .take_events()
+            .into_iter()
+            .find(|event| matches!(event.msg_type, MessageType::GoalScored));
+        if let Some(goal) = goal {
+            return goal.decode().unwrap();
+        }
+    }
+    panic!("no goal");
+}
+
+// A deflection off the defenders' own puck is an own goal credited to that
+// puck, one off the attackers' is a plain goal, and a touch older than the
+// touch window leaves the goal unattributed. Own goals are tallied against
+// the side that put them in.
+fn main() {
+    let mut game = SoccerGame::with_config(SoccerGameConfig::default());
+    let own = deflect(&mut game, 0);
+    assert_eq!(
+        own,
+        GoalScoredMessage {
+            scorer: 1,
+            toucher: Some(0),
+            puck: Some(0),
+            own_goal: true,
+        }
+    );
+    assert_eq!(game.teams[0].own_goals, 1);
+    assert_eq!(game.teams[1].score, 1);
+    println!("own goal by slot 0: {:?}", own);
+
+    let mut game = SoccerGame::with_config(SoccerGameConfig::default());
+    let goal = deflect(&mut game, 1);
+    assert_eq!((goal.toucher, goal.puck), (Some(1), Some(0)));
+    assert!(!goal.own_goal);
+    assert_eq!(game.teams[0].own_goals, 0);
+    assert_eq!(game.teams[1].own_goals, 0);
+    println!("plain goal by slot 1: {:?}", goal);
+
+    // a touch counts only on the tick it happened
+    let config = SoccerGameConfig {
+        touch_window_ticks: Some(0),
+        ..SoccerGameConfig::default()
+    };
+    let mut game = SoccerGame::with_config(config);
+    let stale = deflect(&mut game, 0);
+    assert_eq!(stale.toucher, None);
+    assert_eq!(stale.puck, None);
+    assert!(!stale.own_goal);
+    assert_eq!(game.teams[0].own_goals, 0);
+    assert_eq!(game.last_toucher(game.balls[0]), Some(0));
+    println!("stale touch left unattributed: {:?}", stale);
+}
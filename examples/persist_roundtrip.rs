@@ -0,0 +1,86 @@
+mod common;
+
+use common::{rally, raw_connect, raw_welcome, RALLY};
+use rust_backend::message::PlayerRecord;
+use rust_backend::persistence::{load, save, SavedGame, SavedRecord, Snapshot};
+use rust_backend::server::{Server, ServerConfig};
+use rust_backend::stats::PlayerId;
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18158";
+
+// A saved leaderboard loads back exactly as it was written, account ids
+// included: they are a SHA-256 of the token, the same in every build. A
+// server started on the file has the records and carries on the game id
+// counter from where the last run left it, not from its highest running
+// game.
+#[tokio::main]
+async fn main() {
+    let alice = PlayerId::new(Some("alice-token"), "alice");
+    assert_eq!(alice, PlayerId::Account(0x9c220f200955d76c));
+    let record = PlayerRecord {
+        wins: 3,
+        losses: 1,
+        goals_scored: 12,
+        goals_conceded: 5,
+        own_goals: 1,
+        shots: 40,
+        avg_shot_speed: 410.5,
+        possession_ticks: 9000,
+    };
+    let snapshot = Snapshot {
+        records: vec![
+            SavedRecord {
+                id: alice.clone(),
+                name: "alice".to_string(),
+                record: record.clone(),
+            },
+            SavedRecord {
+                id: PlayerId::Guest("bob".to_string()),
+                name: "bob".to_string(),
+                record: PlayerRecord {
+                    losses: 3,
+                    ..PlayerRecord::default()
+                },
+            },
+        ],
+        games: vec![SavedGame {
+            id: 7,
+            game_type: RALLY,
+            seed: 99,
+            players: vec!["alice".to_string(), "bob".to_string()],
+            scores: vec![2, 1],
+        }],
+        resumable: vec![],
+        last_game_id: 41,
+    };
+    let path = std::env::temp_dir().join(format!("persist_roundtrip_{}.bin", std::process::id()));
+    save(&path, &snapshot).unwrap();
+    let loaded = load(&path).unwrap().expect("nothing saved");
+    assert_eq!(loaded, snapshot);
+    assert_eq!(loaded.last_game_id(), 41);
+    println!(
+        "{} records round-tripped, last game id 41",
+        loaded.records.len()
+    );
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        persist_path: Some(path.clone()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let stats = server.stats();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(stats.read().await.get(&alice), record);
+
+    let mut carol = raw_connect(ADDR, "name=carol&mode=rally&practice=1").await;
+    let welcome = raw_welcome(&mut carol).await;
+    assert_eq!(welcome.game_id, 42);
+    println!("restarted: alice's record is back and the next game is 42");
+    let _ = std::fs::remove_file(&path);
+}
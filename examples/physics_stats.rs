@@ -0,0 +1,49 @@
+use rust_backend::game::{GameLogic, PhysicsPreset, PhysicsStats, SoccerGame};
+use std::time::Duration;
+
+// a tick at 60Hz, in ms
+const TICK: f64 = 1000.0 / 60.0;
+
+// With record_stats on, every update leaves the cost of its physics step
+// behind: how many steps have run, how long the last took and how many
+// contacts it resolved. Left off, the stats stay empty. The presets change
+// how hard the solver works.
+fn main() {
+    let mut quiet = SoccerGame::new();
+    quiet.update(TICK);
+    assert_eq!(quiet.physics_stats, PhysicsStats::default());
+    println!("stats off: nothing recorded");
+
+    let mut soccer = SoccerGame::new();
+    soccer.record_stats = true;
+    let puck = soccer.pucks[0];
+    let mut contacts = 0;
+    for tick in 1..=120 {
+        soccer.apply_move(puck, -400.0, 0.0, 0.0);
+        soccer.update(TICK);
+        assert_eq!(soccer.physics_stats.steps, tick);
+        assert!(soccer.physics_stats.last_step > Duration::ZERO);
+        contacts = contacts.max(soccer.physics_stats.contact_count);
+    }
+    assert!(contacts > 0, "the puck never touched the wall");
+    println!(
+        "120 steps recorded, last took {:?}, up to {} contacts",
+        soccer.physics_stats.last_step, contacts
+    );
+
+    for (preset, iterations) in [(PhysicsPreset::Accurate, 8), (PhysicsPreset::Fast, 1)] {
+        soccer.set_preset(preset);
+        assert_eq!(
+            soccer.integration_parameters.num_solver_iterations.get(),
+            iterations
+        );
+        soccer.update(TICK);
+        println!("{:?}: {} solver iterations", preset, iterations);
+    }
+    soccer.set_preset(PhysicsPreset::Balanced);
+    let default = SoccerGame::new()
+        .integration_parameters
+        .num_solver_iterations;
+    assert_eq!(soccer.integration_parameters.num_solver_iterations, default);
+    println!("Balanced: back to the default");
+}
@@ -0,0 +1,45 @@
+use rust_backend::game::{Client, PING_EXPIRY};
+use std::time::{Duration, Instant};
+
+// Pongs to server Pings are matched by id, not by order: answering the
+// newest first still times each one against its own Ping, and takes only
+// that Ping off the list. A Pong for an id answered before, never sent, or
+// sent longer than PING_EXPIRY ago is unsolicited and leaves the smoothed
+// round trip alone.
+fn main() {
+    let mut client = Client::new(1);
+    let ids: Vec<u32> = (0..3).map(|_| client.start_ping()).collect();
+    assert_eq!(ids, vec![0, 1, 2]);
+    let sent = Instant::now();
+
+    let at = |ms: u64| sent + Duration::from_millis(ms);
+    let third = client
+        .record_pong_at(2, at(40))
+        .expect("third Pong unmatched");
+    let first = client
+        .record_pong_at(0, at(60))
+        .expect("first Pong unmatched");
+    assert!(third >= Duration::from_millis(40) && third < first);
+    assert!(first >= Duration::from_millis(60));
+    println!(
+        "out of order: ping 2 took {:?}, ping 0 took {:?}",
+        third, first
+    );
+
+    let rtt = client.rtt;
+    assert_eq!(client.record_pong_at(2, at(80)), None);
+    assert_eq!(client.record_pong_at(7, at(80)), None);
+    assert_eq!(client.rtt, rtt);
+    println!("a repeated Pong and one for an unsent id are unsolicited");
+
+    // ping 1 is still outstanding until it expires
+    assert_eq!(
+        client.record_pong_at(1, sent + PING_EXPIRY + Duration::from_secs(1)),
+        None
+    );
+    assert_eq!(client.rtt, rtt);
+    let late = client.start_ping();
+    assert_eq!(late, 3);
+    assert!(client.record_pong_at(late, Instant::now()).is_some());
+    println!("a Pong for an expired ping is unsolicited; the next ping matches");
+}
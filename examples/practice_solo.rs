@@ -0,0 +1,50 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, raw_welcome, RALLY};
+use rust_backend::message::MessageType;
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18147";
+
+// A practice game is never in the matchmaking pool, even for a mode whose
+// logic would take a second player: the next player looking for a game is
+// queued rather than put in alice's and is paired with whoever comes after.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        queue_timeout: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally&practice=1").await;
+    let practice = raw_welcome(&mut alice).await;
+    let game = games.read().await[&(practice.game_id as usize)].clone();
+    assert!(game.read().await.practice);
+    println!("alice practising in game {}", practice.game_id);
+
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let placed = raw_next(&mut bob, &[MessageType::Welcome, MessageType::QueueStatus]).await;
+    assert_eq!(
+        placed.msg_type as u8,
+        MessageType::QueueStatus as u8,
+        "bob was put in alice's practice game"
+    );
+    println!("bob queued instead of joining alice");
+
+    let mut carol = raw_connect(ADDR, "name=carol&mode=rally").await;
+    let carol = raw_welcome(&mut carol).await;
+    let bob = raw_welcome(&mut bob).await;
+    assert_eq!(bob.game_id, carol.game_id);
+    assert_ne!(bob.game_id, practice.game_id);
+    assert_eq!(game.read().await.players.len(), 1);
+    println!("bob and carol paired in game {}", bob.game_id);
+}
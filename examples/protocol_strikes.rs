@@ -0,0 +1,79 @@
+use rust_backend::server::{Server, ServerConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18080";
+const STRIKES: u32 = 3;
+
+// Connects with a raw TCP client that completes the upgrade and then sends a
+// frame with a reserved opcode, which no websocket library would produce.
+// Each attempt must be closed with 1002, and once the strikes run out the
+// address must be refused before the handshake.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        protocol_strikes: STRIKES,
+        protocol_ban: Duration::from_secs(60),
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    for strike in 1..=STRIKES {
+        let mut stream = upgrade().await.expect("handshake refused before the ban");
+        // FIN, opcode 0x3; masked, empty payload
+        stream.write_all(&[0x83, 0x80, 1, 2, 3, 4]).await.unwrap();
+        let code = close_code(&mut stream).await;
+        assert_eq!(code, Some(1002), "strike {} closed with {:?}", strike, code);
+        println!("strike {}: closed with 1002", strike);
+    }
+    assert!(upgrade().await.is_none(), "banned address got a handshake");
+    println!("banned after {} strikes", STRIKES);
+}
+
+// The upgraded stream, or None when the server hung up instead of answering.
+async fn upgrade() -> Option<TcpStream> {
+    let mut stream = TcpStream::connect(ADDR).await.unwrap();
+    let request = format!(
+        "GET /?name=striker HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        ADDR
+    );
+    if stream.write_all(request.as_bytes()).await.is_err() {
+        return None;
+    }
+    let mut response = vec![];
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        match stream.read(&mut byte).await {
+            Ok(1) => response.push(byte[0]),
+            _ => return None,
+        }
+    }
+    assert!(response.starts_with(b"HTTP/1.1 101"));
+    return Some(stream);
+}
+
+// Skips frames until a close and returns its status code.
+async fn close_code(stream: &mut TcpStream) -> Option<u16> {
+    let read = async {
+        loop {
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).await.ok()?;
+            let len = match header[1] & 0x7f {
+                126 => stream.read_u16().await.ok()? as usize,
+                127 => stream.read_u64().await.ok()? as usize,
+                len => len as usize,
+            };
+            let mut payload = vec![0u8; len];
+            stream.read_exact(&mut payload).await.ok()?;
+            if header[0] & 0x0f == 0x8 && len >= 2 {
+                return Some(u16::from_be_bytes([payload[0], payload[1]]));
+            }
+        }
+    };
+    return timeout(Duration::from_secs(5), read).await.ok().flatten();
+}
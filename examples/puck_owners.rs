@@ -0,0 +1,42 @@
+use rust_backend::game::{
+    GameLogic, Side, SoccerGame, SoccerGameConfig, MAX_PUCKS_PER_TEAM, PLAYERS_PER_GAME,
+};
+
+const COLORS: [u8; 2] = [3, 9];
+
+// Whatever pucks_per_team is, every puck in State order is named with the
+// slot that steers it and its SoccerMove target, each slot gets exactly
+// pucks_per_team of them numbered from 0, and the target matches team_puck.
+// Colors follow the side, and are left out when none are set.
+fn main() {
+    for pucks_per_team in 1..=MAX_PUCKS_PER_TEAM {
+        let config = SoccerGameConfig {
+            pucks_per_team,
+            team_colors: COLORS.to_vec(),
+            ..SoccerGameConfig::default()
+        };
+        let game = SoccerGame::with_config(config);
+        let owners = game.puck_owners();
+        assert_eq!(owners.len(), game.pucks.len());
+        assert_eq!(owners.len(), pucks_per_team * PLAYERS_PER_GAME);
+        for player in 0..PLAYERS_PER_GAME {
+            let targets: Vec<u8> = owners
+                .iter()
+                .filter(|owner| owner.player as usize == player)
+                .map(|owner| owner.target)
+                .collect();
+            assert_eq!(targets, (0..pucks_per_team as u8).collect::<Vec<_>>());
+        }
+        for (owner, handle) in owners.iter().zip(&game.pucks) {
+            let player = owner.player as usize;
+            assert_eq!(game.team_puck(player, owner.target), Some(*handle));
+            let side = Side::for_slot(player) as usize;
+            assert_eq!(owner.color, Some(COLORS[side]));
+        }
+        println!("{} a side: {:?}", pucks_per_team, owners);
+    }
+
+    let game = SoccerGame::with_config(SoccerGameConfig::default());
+    assert!(game.puck_owners().iter().all(|owner| owner.color.is_none()));
+    println!("no colors set, none sent");
+}
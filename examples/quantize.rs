@@ -0,0 +1,112 @@
+use rust_backend::game::{GameRng, SoccerGame};
+use rust_backend::message::{
+    dequantize, dequantize_position, quantize, quantize_position, ByteOrder, ProtocolVersion,
+    SoccerStateSnapshot, QUANTIZED_ANGLE_SCALE, QUANTIZED_ANGVEL_SCALE, QUANTIZED_VELOCITY_SCALE,
+};
+use rust_backend::serializer::{CompactBinary, Quantized, StateSerializer, StateView};
+use std::f32::consts::PI;
+
+const SAMPLES: usize = 100_000;
+
+// Every quantized value comes back within the bound the layout documents,
+// anything out of range saturates instead of wrapping, and a soccer State
+// decodes to the same bodies as the f32 one in a little over half the bytes.
+fn main() {
+    let mut rng = GameRng::new(634);
+    for half in [1, 300, 1000, u16::MAX] {
+        let bound = half as f32 / 65535.0;
+        let worst = worst_error(&mut rng, half as f32, |value| {
+            return dequantize_position(quantize_position(value, half), half);
+        });
+        // f32 arithmetic adds a hair to the bound
+        assert!(
+            worst <= bound * 1.01,
+            "half {}: {} > {}",
+            half,
+            worst,
+            bound
+        );
+        println!(
+            "positions within half {}: off by {} of {}",
+            half, worst, bound
+        );
+    }
+    for (name, range, scale, bound) in [
+        ("velocity", 4000.0, QUANTIZED_VELOCITY_SCALE, 1.0 / 16.0),
+        ("angle", PI, QUANTIZED_ANGLE_SCALE, 0.00005),
+        ("angvel", 60.0, QUANTIZED_ANGVEL_SCALE, 1.0 / 1024.0),
+    ] {
+        let worst = worst_error(&mut rng, range, |value| {
+            return dequantize(quantize(value, scale), scale);
+        });
+        assert!(worst <= bound, "{}: {} > {}", name, worst, bound);
+        println!("{} off by at most {} of {}", name, worst, bound);
+    }
+
+    assert_eq!(quantize_position(-1000.0, 300), 0);
+    assert_eq!(quantize_position(1000.0, 300), u16::MAX);
+    assert_eq!(quantize_position(f32::INFINITY, 300), u16::MAX);
+    assert_eq!(quantize_position(f32::NAN, 300), 0);
+    assert_eq!(quantize(1e9, QUANTIZED_VELOCITY_SCALE), i16::MAX);
+    assert_eq!(quantize(-1e9, QUANTIZED_VELOCITY_SCALE), i16::MIN);
+    assert_eq!(quantize(f32::NAN, QUANTIZED_VELOCITY_SCALE), 0);
+    println!("out of range values saturate");
+
+    let game = SoccerGame::new();
+    let view = StateView {
+        player: Some(0),
+        ..StateView::default()
+    };
+    for (protocol, order) in [
+        (ProtocolVersion::V6, ByteOrder::Little),
+        (ProtocolVersion::V7, ByteOrder::Big),
+    ] {
+        let mut full = CompactBinary(protocol, order).encode(&game, &view);
+        let mut small = Quantized(protocol, order).encode(&game, &view);
+        let (full_len, small_len) = (full.len(), small.len());
+        // the headers stay as they were, so a little over half
+        assert!(
+            small_len * 3 <= full_len * 2,
+            "{} quantized bytes against {}",
+            small_len,
+            full_len
+        );
+        assert!(order.swap_state(protocol, &mut full));
+        assert!(order.swap_quantized_state(protocol, &mut small));
+        let full = SoccerStateSnapshot::decode(protocol, &full).unwrap();
+        let small = SoccerStateSnapshot::decode_quantized(protocol, &small).unwrap();
+        let bound = game.bounds.x.max(game.bounds.y).ceil() / 65535.0 * 1.01;
+        let bodies = |snapshot: &SoccerStateSnapshot| -> Vec<(f32, f32)> {
+            return snapshot
+                .pucks
+                .iter()
+                .chain(&snapshot.balls)
+                .copied()
+                .collect();
+        };
+        for ((x, y), (qx, qy)) in bodies(&full).into_iter().zip(bodies(&small)) {
+            assert!((x - qx).abs() <= bound && (y - qy).abs() <= bound);
+        }
+        assert_eq!(full.own_pucks.len(), small.own_pucks.len());
+        assert_eq!(full.match_phase, small.match_phase);
+        println!(
+            "{} {:?}: {} bytes quantized against {}, {:.0}%",
+            protocol.as_str(),
+            order,
+            small_len,
+            full_len,
+            small_len as f32 / full_len as f32 * 100.0
+        );
+    }
+}
+
+// The furthest round trip from what went in, over SAMPLES values spread
+// evenly across [-range, range].
+fn worst_error(rng: &mut GameRng, range: f32, round_trip: impl Fn(f32) -> f32) -> f32 {
+    let mut worst: f32 = 0.0;
+    for _ in 0..SAMPLES {
+        let value = (rng.next_f32() * 2.0 - 1.0) * range;
+        worst = worst.max((round_trip(value) - value).abs());
+    }
+    return worst;
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{game_of, rally, raw_connect, raw_welcome, RALLY};
+use futures::future::join_all;
+use rust_backend::server::{Server, ServerConfig};
+use std::collections::BTreeMap;
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18131";
+const PLAYERS: usize = 50;
+
+// Fifty players queue for a match at once. The matchmaker pairs them off
+// into exactly twenty-five games of two, and nobody is placed twice: each
+// Welcome names the game its player is in, and the games hold everyone
+// once between them.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        // nobody may fall back to a bot game while the burst is paired
+        queue_timeout: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let joins = (0..PLAYERS).map(|i| async move {
+        let query = format!("name=queued{}&mode=rally", i);
+        let mut stream = raw_connect(ADDR, &query).await;
+        let welcome = raw_welcome(&mut stream).await;
+        return (stream, welcome.game_id as usize);
+    });
+    let joined = join_all(joins).await;
+    let mut welcomed: BTreeMap<usize, usize> = BTreeMap::new();
+    for (_, game_id) in &joined {
+        *welcomed.entry(*game_id).or_default() += 1;
+    }
+    assert_eq!(welcomed.len(), PLAYERS / 2, "{:?}", welcomed);
+    assert!(welcomed.values().all(|players| *players == 2));
+    assert_eq!(games.read().await.len(), PLAYERS / 2);
+    println!(
+        "{} queued at once, {} games of two",
+        PLAYERS,
+        welcomed.len()
+    );
+
+    let mut seen = 0;
+    for game in games.read().await.values() {
+        seen += game.read().await.players.len();
+    }
+    assert_eq!(seen, PLAYERS);
+    for (i, (_, welcomed_to)) in joined.iter().enumerate() {
+        let name = format!("queued{}", i);
+        let (game_id, _) = game_of(&games, &name).await.expect("never placed");
+        assert_eq!(game_id, *welcomed_to, "{} was welcomed elsewhere", name);
+    }
+    println!("every player is in exactly one game");
+    drop(joined);
+}
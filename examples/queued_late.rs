@@ -0,0 +1,45 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, raw_welcome, Empty, RALLY};
+use rust_backend::game::GameLogic;
+use rust_backend::message::{MessageType, QueuedMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18159";
+const TAG: u8 = 8;
+
+// erin queues for tag while there is still room, so she is only waiting for
+// an opponent. Once alice and bob take the one game the server runs, erin
+// is waiting for a game to end instead and must be told with Queued.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        queue_timeout: None,
+        max_games: Some(1),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    server.register_mode("tag", TAG, |_state, _practice| {
+        return Box::new(Empty) as Box<dyn GameLogic>;
+    });
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut erin = raw_connect(ADDR, "name=erin&mode=tag").await;
+    sleep(Duration::from_millis(100)).await;
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let game_id = raw_welcome(&mut alice).await.game_id;
+    raw_welcome(&mut bob).await;
+    println!("alice and bob took the only game, {}", game_id);
+
+    let queued = raw_next(&mut erin, &[MessageType::Queued]).await;
+    let queued = queued.decode::<QueuedMessage>().unwrap();
+    assert_eq!(queued.position, 1);
+    println!("erin told she is queued at position {}", queued.position);
+}
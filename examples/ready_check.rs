@@ -0,0 +1,74 @@
+mod common;
+
+use common::{drain, drifted, raw_connect, raw_next, raw_welcome, Drift, RALLY};
+use futures::SinkExt;
+use rust_backend::game::{GameLogic, GamePhase};
+use rust_backend::message::{GameResumingMessage, MessageType, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18136";
+
+// A full game holds still until both players send Ready: alice readying up
+// on her own doesn't start it, and once bob does too both are sent the
+// countdown to kick-off and the game starts stepping.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ready_timeout: Duration::from_secs(60),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Drift::default()) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let ready = WsMessage {
+        msg_type: MessageType::Ready,
+        payload: vec![],
+    };
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let game_id = raw_welcome(&mut alice).await.game_id as usize;
+    assert_eq!(raw_welcome(&mut bob).await.game_id as usize, game_id);
+    alice.send(Message::Binary(ready.to_bytes())).await.unwrap();
+    sleep(Duration::from_millis(500)).await;
+    let game = games.read().await[&game_id].clone();
+    assert!(matches!(
+        game.read().await.phase,
+        GamePhase::ReadyCheck { .. }
+    ));
+    assert_eq!(drifted(&game).await, 0.0);
+    println!("only alice ready: game {} hasn't moved", game_id);
+
+    bob.send(Message::Binary(ready.to_bytes())).await.unwrap();
+    for (name, stream) in [("alice", &mut alice), ("bob", &mut bob)] {
+        let resuming = raw_next(stream, &[MessageType::GameResuming]).await;
+        let resuming = resuming.decode::<GameResumingMessage>().unwrap();
+        assert_eq!(resuming.by, None);
+        assert!(resuming.countdown_ms > 0);
+        println!(
+            "{} counted down {}ms to kick-off",
+            name, resuming.countdown_ms
+        );
+    }
+    drain(alice);
+    drain(bob);
+    let started = async {
+        while drifted(&game).await == 0.0 {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), started)
+        .await
+        .expect("the game never started");
+    assert_eq!(game.read().await.phase, GamePhase::Playing);
+    println!("both ready: game {} is playing", game_id);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{connect, drifted, join_ready, next, options, Drift, Events, RALLY};
+use rapier2d::prelude::*;
+use rust_backend::client::ClientEvent;
+use rust_backend::game::{
+    Game, GameLogic, GamePhase, PauseConfig, ShortHanded, SlotConnection, SoccerGame,
+};
+use rust_backend::message::{GameOverReason, MessageType, OpponentDisconnectedMessage};
+use rust_backend::server::{Server, ServerConfig};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use tokio::sync::{mpsc, RwLock};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18129";
+
+// bob drops mid-match: the game freezes and alice is told how long he has
+// to come back, again and again as it runs down. He rejoins in time and
+// play resumes after the countdown. The second time he drops he stays
+// gone, and forfeits once the wait is over. A soccer match, warm-up on,
+// doesn't move a body or run its clock while it waits either.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        pause: PauseConfig {
+            resume_countdown: Duration::from_millis(200),
+            short_handed: ShortHanded::Wait(Some(Duration::from_secs(2))),
+            reconnect_notice_every: Duration::from_millis(500),
+            ..PauseConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Drift::default()) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (alice, mut alice_events) = connect(ADDR, "alice", options()).await;
+    let (bob, bob_link) = join_ready(ADDR, "name=bob&mode=rally").await;
+    while alice.session_token().is_none() {
+        sleep(Duration::from_millis(10)).await;
+    }
+    alice.ready();
+    let game = games.read().await.get(&(bob.game_id as usize)).cloned();
+    let game = game.expect("no game");
+    wait_for_phase(&game, |phase| *phase == GamePhase::Playing).await;
+
+    bob_link.abort();
+    let first = notice(&mut alice_events).await;
+    assert_eq!(first.player_index, 1);
+    let second = notice(&mut alice_events).await;
+    let (first_left, second_left) = (first.forfeit_in_ticks, second.forfeit_in_ticks);
+    assert!(second_left.unwrap() < first_left.unwrap());
+    println!(
+        "alice told bob forfeits in {:?}ms, then {:?}ms",
+        first.forfeit_in_ms, second.forfeit_in_ms
+    );
+    let frozen = drifted(&game).await;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(drifted(&game).await, frozen, "the game played on one short");
+
+    let query = format!("name=bob&mode=rally&session={}", bob.session_token);
+    let (_, bob_link) = join_ready(ADDR, &query).await;
+    let countdown = next(&mut alice_events, |event| match event {
+        ClientEvent::Message(MessageType::GameResuming, _) => Some(()),
+        _ => None,
+    });
+    countdown.await;
+    wait_for_phase(&game, |phase| *phase == GamePhase::Playing).await;
+    println!("bob rejoined in time and play resumed");
+
+    bob_link.abort();
+    notice(&mut alice_events).await;
+    let game_over = next(&mut alice_events, |event| match event {
+        ClientEvent::GameOver(game_over) => Some(game_over),
+        _ => None,
+    })
+    .await;
+    assert_eq!(game_over.reason, GameOverReason::Forfeit);
+    assert_eq!(game_over.winner, Some(0));
+    println!("bob stayed gone and forfeited");
+
+    soccer_wait();
+}
+
+// A headless soccer match loses a player with the ball moving; nothing in
+// the world changes until he is back.
+fn soccer_wait() {
+    let players = ["alice", "bob"]
+        .iter()
+        .map(|name| (PlayerId::Guest(name.to_string()), name.to_string()))
+        .collect();
+    let mut game = Game::new(SoccerGame::new(), players);
+    game.warm_up = true;
+    game.pause_config.short_handed = ShortHanded::Wait(None);
+    game.pause_config.resume_countdown = Duration::ZERO;
+    for index in 0..2 {
+        game.bind_connection(
+            index,
+            SlotConnection {
+                client_id: 10 + index,
+                evict: mpsc::unbounded_channel().0,
+                inbox: mpsc::unbounded_channel().0,
+            },
+        );
+        game.mark_ready(index);
+    }
+    while game.phase != GamePhase::Playing {
+        game.update();
+    }
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    let ball = soccer.balls[0];
+    soccer.bodies[ball].set_linvel(vector![300.0, 120.0], true);
+    game.update();
+
+    game.unbind_connection(1, 11);
+    game.set_connected(1, false);
+    assert!(matches!(game.phase, GamePhase::WaitingForPlayers { .. }));
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    let (bodies, clock) = (soccer.body_states(), soccer.remaining_time());
+    for _ in 0..30 {
+        game.update();
+    }
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    assert_eq!(soccer.body_states(), bodies, "bodies moved during the wait");
+    assert_eq!(soccer.remaining_time(), clock);
+    assert!(!game.is_warming_up());
+    println!("the soccer match stood still for 30 updates one short");
+}
+
+async fn wait_for_phase(game: &Arc<RwLock<Game>>, check: impl Fn(&GamePhase) -> bool) {
+    let reached = async {
+        while !check(&game.read().await.phase) {
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), reached)
+        .await
+        .expect("phase never reached");
+}
+
+async fn notice(events: &mut Events) -> OpponentDisconnectedMessage {
+    return next(events, |event| match event {
+        ClientEvent::OpponentDisconnected(notice) => Some(notice),
+        _ => None,
+    })
+    .await;
+}
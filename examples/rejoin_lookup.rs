@@ -0,0 +1,93 @@
+use rust_backend::game::{Client, Game, SoccerGame};
+use rust_backend::matchmaking::OpenSlots;
+use rust_backend::stats::PlayerId;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+
+const GAMES: usize = 2000;
+const LOOKUPS: usize = 1000;
+
+// Finds the game holding a disconnected player's slot on a full server, the
+// old way (write-lock every game and check its roster) and through
+// OpenSlots. The player sits in the last game so the scan is worst case, and
+// comes back under a different name, which must not matter.
+#[tokio::main]
+async fn main() {
+    let mut games = BTreeMap::new();
+    let open_slots = OpenSlots::default();
+    let mut session = String::new();
+    for id in 1..=GAMES {
+        let players = vec![
+            (account(&format!("a{}", id)), format!("a{}", id)),
+            (account(&format!("b{}", id)), format!("b{}", id)),
+        ];
+        let mut game = Game::new(SoccerGame::new(), players);
+        game.set_connected(1, false);
+        open_slots.insert(account(&format!("b{}", id)), id);
+        session = game.session_token(1).unwrap().to_string();
+        games.insert(id, Arc::new(RwLock::new(game)));
+    }
+    let games = Arc::new(RwLock::new(games));
+    let player = account(&format!("b{}", GAMES));
+
+    let started = Instant::now();
+    for _ in 0..LOOKUPS {
+        let candidates: Vec<(usize, Arc<RwLock<Game>>)> = games
+            .read()
+            .await
+            .iter()
+            .map(|(id, game)| (*id, Arc::clone(game)))
+            .collect();
+        let mut found = None;
+        for (id, game) in candidates {
+            let game = game.write().await;
+            if game.players.iter().any(|p| p.id == player && !p.connected) {
+                found = Some(id);
+                break;
+            }
+        }
+        assert_eq!(found, Some(GAMES));
+    }
+    report("scan", started.elapsed());
+
+    let started = Instant::now();
+    for _ in 0..LOOKUPS {
+        let id = open_slots.get(&player);
+        let game = match id {
+            Some(id) => games.read().await.get(&id).cloned(),
+            None => None,
+        };
+        let game = game.expect("slot is indexed");
+        let game = game.write().await;
+        assert!(game.players.iter().any(|p| p.id == player && !p.connected));
+    }
+    report("indexed", started.elapsed());
+
+    let game = games.read().await.get(&GAMES).cloned().unwrap();
+    let mut game = game.write().await;
+    let connection = Client::new(1).slot_connection();
+    let rejoined = game.rejoin(&player, "renamed", Some(&session), connection.clone());
+    assert_eq!(rejoined, Ok(Some(1)));
+    assert_eq!(game.player(1).unwrap().name, "renamed");
+    // the same name under another identity is someone else
+    let stranger = account("stranger");
+    assert_eq!(
+        game.rejoin(&stranger, "renamed", Some(&session), connection),
+        Ok(None)
+    );
+}
+
+fn account(token: &str) -> PlayerId {
+    return PlayerId::new(Some(token), "");
+}
+
+fn report(name: &str, elapsed: std::time::Duration) {
+    println!(
+        "{}: {:?} per join across {} games",
+        name,
+        elapsed / LOOKUPS as u32,
+        GAMES
+    );
+}
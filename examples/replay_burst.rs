@@ -0,0 +1,154 @@
+mod common;
+
+use common::StepClock;
+use rust_backend::game::{Game, GameLogic, GamePhase, SlotConnection};
+use rust_backend::message::{
+    EventMessage, MessageType, ReplayBurstMessage, WsMessage, MAX_REPLAY_FRAMES,
+};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+
+const GOAL_AT: u32 = 200;
+const REPLAY_TICKS: usize = 90;
+
+// Counts its updates, records the count as its replay state, and scores
+// once on update GOAL_AT.
+#[derive(Default)]
+struct Scorer {
+    steps: u32,
+}
+
+impl GameLogic for Scorer {
+    fn game_type(&self) -> u8 {
+        return 0;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        self.steps += 1;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+    fn take_goals(&mut self) -> Vec<usize> {
+        return match self.steps == GOAL_AT {
+            true => vec![0],
+            false => vec![],
+        };
+    }
+    fn replay_frame(&self) -> Option<Vec<u8>> {
+        return Some(self.steps.to_le_bytes().to_vec());
+    }
+}
+
+fn recording(replay_ticks: usize) -> (Game, Arc<StepClock>) {
+    let clock = Arc::new(StepClock::new(Instant::now()));
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(Scorer::default(), players);
+    game.set_clock(clock.clone());
+    game.replay_ticks = replay_ticks;
+    game.pause_config.resume_countdown = Duration::ZERO;
+    for index in 0..2 {
+        // someone connected, so the game isn't dormant
+        game.bind_connection(
+            index,
+            SlotConnection {
+                client_id: index,
+                evict: mpsc::unbounded_channel().0,
+                inbox: mpsc::unbounded_channel().0,
+            },
+        );
+        game.mark_ready(index);
+    }
+    return (game, clock);
+}
+
+// Steps until the logic scores and returns the game's tick then.
+fn play_to_goal(game: &mut Game, clock: &StepClock) -> u64 {
+    while game.phase != GamePhase::Playing {
+        clock.advance(Duration::from_millis(16));
+        game.update();
+    }
+    loop {
+        clock.advance(Duration::from_millis(16));
+        game.update();
+        if !game.take_goals().is_empty() {
+            return game.tick();
+        }
+    }
+}
+
+// The ReplayBursts among what the game sent, out of any Event envelope.
+fn bursts(events: &mut broadcast::Receiver<bytes::Bytes>) -> Vec<ReplayBurstMessage> {
+    let mut bursts = vec![];
+    while let Ok(frame) = events.try_recv() {
+        let mut message = WsMessage::from_bytes(&frame).unwrap();
+        if message.msg_type as u8 == MessageType::Event as u8 {
+            let event = message.decode::<EventMessage>().unwrap();
+            message = WsMessage::from_bytes(&event.frame).unwrap();
+        }
+        if message.msg_type as u8 == MessageType::ReplayBurst as u8 {
+            bursts.push(message.decode().unwrap());
+        }
+    }
+    return bursts;
+}
+
+// The burst after a goal is the last REPLAY_TICKS updates thinned out to
+// MAX_REPLAY_FRAMES, oldest first, ending on the goal tick itself with the
+// state the goal was scored in. It is logged as an event, so a reconnecting
+// player can catch up on it. Recording starts over after it, and a game
+// with replays off sends none.
+fn main() {
+    let (mut game, clock) = recording(REPLAY_TICKS);
+    let mut events = game.subscribe();
+    let goal_tick = play_to_goal(&mut game, &clock);
+    game.broadcast_replay();
+    let burst = match &bursts(&mut events)[..] {
+        [burst] => burst.clone(),
+        other => panic!("{} bursts for one goal", other.len()),
+    };
+    assert_eq!(burst.goal_tick as u64, goal_tick);
+    assert_eq!(burst.frames.len(), MAX_REPLAY_FRAMES);
+    let last = burst.frames.last().unwrap();
+    assert_eq!(last.tick, burst.goal_tick);
+    assert_eq!(last.state, GOAL_AT.to_le_bytes());
+    let first = burst.frames[0].tick;
+    assert_eq!(first as usize, burst.goal_tick as usize + 1 - REPLAY_TICKS);
+    assert!(burst
+        .frames
+        .windows(2)
+        .all(|pair| pair[0].tick < pair[1].tick));
+    println!(
+        "goal at tick {}: {} frames from tick {}",
+        burst.goal_tick,
+        burst.frames.len(),
+        first
+    );
+    let (logged, _) = game.events_since(0);
+    let replay = MessageType::ReplayBurst as u8;
+    assert!(logged
+        .iter()
+        .any(|frame| EventMessage::inner_type(frame) == Some(replay)));
+    println!("burst kept in the event log");
+
+    game.broadcast_replay();
+    assert!(bursts(&mut events).is_empty());
+    println!("nothing recorded since: no second burst");
+
+    let (mut game, clock) = recording(0);
+    let mut events = game.subscribe();
+    play_to_goal(&mut game, &clock);
+    game.broadcast_replay();
+    assert!(bursts(&mut events).is_empty());
+    println!("replays off: no burst");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{connect, options, rally, RALLY};
+use futures::StreamExt;
+use rust_backend::client::{ClientEvent, ClientOptions};
+use rust_backend::message::{CloseReason, MessageType, WelcomeMessage, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18124";
+const REQUIRED: Duration = Duration::from_secs(1);
+
+// Welcome tells every player how often it owes a Ping. One that keeps
+// reading but never pings is closed with 4001 once that long has passed,
+// even though its socket is fine; the SDK picks the cadence up from Welcome
+// and stays in well past it.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        // the server's own probes would be answered by the SDK and count
+        server_ping_interval: None,
+        required_ping_interval: Some(REQUIRED),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let url = format!("ws://{}/?name=quiet&mode=rally&practice=1", ADDR);
+    let (mut quiet, _) = connect_async(url).await.unwrap();
+    let welcomed = Instant::now();
+    let read = async {
+        let mut welcome = None;
+        while let Some(Ok(message)) = quiet.next().await {
+            match message {
+                Message::Binary(data) => {
+                    let decoded = WsMessage::from_bytes(&data)
+                        .filter(|message| matches!(message.msg_type, MessageType::Welcome))
+                        .and_then(|message| message.decode::<WelcomeMessage>());
+                    welcome = welcome.or(decoded);
+                }
+                Message::Close(frame) => {
+                    let code = frame.map(|frame| u16::from(frame.code));
+                    return (welcome, code);
+                }
+                _ => (),
+            }
+        }
+        panic!("closed without a close frame");
+    };
+    let (welcome, code) = timeout(REQUIRED * 5, read)
+        .await
+        .expect("the silent player was never closed");
+    let welcome = welcome.expect("no Welcome");
+    assert_eq!(welcome.ping_interval_ms, REQUIRED.as_millis() as u32);
+    let took = welcomed.elapsed();
+    assert_eq!(
+        code.and_then(CloseReason::from_code),
+        Some(CloseReason::HeartbeatTimeout)
+    );
+    assert!(
+        took >= REQUIRED && took < REQUIRED * 2,
+        "closed after {:?}",
+        took
+    );
+    println!(
+        "Welcome asked for a Ping every {}ms; the silent player was closed after {:?}",
+        welcome.ping_interval_ms, took
+    );
+
+    let options = ClientOptions {
+        practice: true,
+        ..options()
+    };
+    let (_sdk, mut events) = connect(ADDR, "sdk", options).await;
+    let outlived = REQUIRED * 4;
+    let dropped = timeout(outlived, async {
+        while let Some(event) = events.next().await {
+            if let ClientEvent::Closed(_) | ClientEvent::Disconnected = event {
+                return event;
+            }
+        }
+        return ClientEvent::Disconnected;
+    })
+    .await;
+    assert!(dropped.is_err(), "the SDK was dropped: {:?}", dropped);
+    println!("the SDK kept pinging and stayed {:?}", outlived);
+}
@@ -0,0 +1,90 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig, KICKOFF_TIMER};
+use rust_backend::message::{HandicapConfig, TeamHandicap};
+use rust_backend::persistence::{self, SavedMatch, SavedSlot, Snapshot};
+use rust_backend::stats::PlayerId;
+
+// A match saved mid-play and written to disk comes back with every body
+// where it was, moving the way it was, and the score intact, handicap
+// included.
+fn main() {
+    let config = SoccerGameConfig::default();
+    let mut game = SoccerGame::with_config(config.clone());
+    game.set_handicap(HandicapConfig {
+        right: TeamHandicap {
+            shot_speed: 0.5,
+            score: 1,
+            ..TeamHandicap::default()
+        },
+        ..HandicapConfig::default()
+    });
+    let ball = game.balls[0];
+    game.bodies[ball].set_linvel(vector![300.0, -120.0], true);
+    game.bodies[game.pucks[0]].set_angvel(4.0, true);
+    for _ in 0..30 {
+        game.update(1000.0 / 60.0);
+    }
+    game.teams[0].score = 2;
+    game.teams[1].score = 1;
+
+    let saved = SavedMatch {
+        id: 7,
+        game_type: game.game_type(),
+        seed: 42,
+        token: "abcd1234".to_string(),
+        practice: false,
+        slots: vec![
+            SavedSlot {
+                id: PlayerId::Guest("alice".to_string()),
+                name: "alice".to_string(),
+                session_token: "alice-token".to_string(),
+                bot: false,
+            },
+            SavedSlot {
+                id: PlayerId::Guest("bob".to_string()),
+                name: "bob".to_string(),
+                session_token: "bob-token".to_string(),
+                bot: false,
+            },
+        ],
+        scores: game.teams.iter().map(|team| team.score).collect(),
+        bodies: game.body_states(),
+        timers: vec![(KICKOFF_TIMER, 12)],
+        handicap: game.handicap,
+    };
+    let path = std::env::temp_dir().join("asyncws-resume-example.state");
+    let snapshot = Snapshot {
+        resumable: vec![saved.clone()],
+        ..Snapshot::default()
+    };
+    persistence::save(&path, &snapshot).expect("save failed");
+    let loaded = persistence::load(&path)
+        .expect("load failed")
+        .expect("nothing saved");
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded.resumable, vec![saved.clone()]);
+
+    // as persistence::resume does it: the handicap, then what was saved
+    let mut restored = SoccerGame::with_config(config.clone());
+    restored.set_handicap(loaded.resumable[0].handicap.unwrap());
+    assert!(restored.restore(&loaded.resumable[0].bodies, &loaded.resumable[0].scores));
+    assert_eq!(restored.body_states(), game.body_states());
+    assert_eq!(restored.teams[0].score, 2);
+    assert_eq!(restored.teams[1].score, 1);
+    assert_eq!(restored.teams[1].handicap, game.teams[1].handicap);
+
+    // a save from another arena is refused and changes nothing
+    let mut bigger = SoccerGame::with_config(SoccerGameConfig {
+        pucks_per_team: config.pucks_per_team + 1,
+        ..config
+    });
+    let before = bigger.body_states();
+    assert!(!bigger.restore(&saved.bodies, &saved.scores));
+    assert_eq!(bigger.body_states(), before);
+    println!(
+        "restored {} bodies, score {}-{}",
+        restored.body_states().len(),
+        restored.teams[0].score,
+        restored.teams[1].score
+    );
+}
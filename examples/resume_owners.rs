@@ -0,0 +1,84 @@
+mod common;
+
+use common::raw_next;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType};
+use rust_backend::persistence::{self, SavedMatch, SavedSlot, Snapshot};
+use rust_backend::server::{Server, ServerConfig};
+use rust_backend::stats::PlayerId;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+const ADDR: &str = "127.0.0.1:18161";
+
+// A match resumed after a restart counts against its players' game cap as
+// it did before: with max_games_per_identity at 1, alice, signed in and
+// sitting in a resumed match, is refused a practice game of her own.
+#[tokio::main]
+async fn main() {
+    let game = SoccerGame::with_config(SoccerGameConfig::default());
+    let saved = SavedMatch {
+        id: 7,
+        game_type: game.game_type(),
+        seed: 42,
+        token: "abcd1234".to_string(),
+        practice: false,
+        slots: vec![
+            SavedSlot {
+                id: PlayerId::new(Some("alice-token"), "alice"),
+                name: "alice".to_string(),
+                session_token: "alice-session".to_string(),
+                bot: false,
+            },
+            SavedSlot {
+                id: PlayerId::Guest("bob".to_string()),
+                name: "bob".to_string(),
+                session_token: "bob-session".to_string(),
+                bot: false,
+            },
+        ],
+        scores: game.teams.iter().map(|team| team.score).collect(),
+        bodies: game.body_states(),
+        timers: vec![],
+        handicap: None,
+    };
+    let path = std::env::temp_dir().join(format!("resume_owners_{}.bin", std::process::id()));
+    let snapshot = Snapshot {
+        resumable: vec![saved],
+        last_game_id: 7,
+        ..Snapshot::default()
+    };
+    persistence::save(&path, &snapshot).expect("save failed");
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        persist_path: Some(path.clone()),
+        max_games_per_identity: Some(1),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+    assert!(games.read().await.contains_key(&7), "match not resumed");
+
+    let mut request = format!("ws://{}/?name=alice&practice=1", ADDR)
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "Authorization",
+        HeaderValue::from_static("Bearer alice-token"),
+    );
+    let (mut alice, _) = connect_async(request).await.unwrap();
+    let refused = raw_next(&mut alice, &[MessageType::Welcome, MessageType::Error]).await;
+    let refused = refused
+        .decode::<ErrorMessage>()
+        .expect("alice got a second game");
+    assert_eq!(refused.code, ErrorCode::TooManyGames);
+    println!("alice's resumed match counts: {}", refused.message);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{
+    connect, error, fetch, fetch_as, game_of, next, options, rally, welcome, Events, RALLY,
+};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::game::GamePhase;
+use rust_backend::message::{ErrorCode, RosterMessage, SlotStatus};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18107";
+const HTTP_ADDR: &str = "127.0.0.1:18108";
+const ADMIN_TOKEN: &str = "roster-admin";
+
+// The roster of a game tells a connected player from one who dropped and
+// may still come back, over the Roster message and GET /game/{id}/roster
+// alike, and a solo game shows its other slot empty. Someone else's game
+// is only listed for an admin, and the HTTP route only answers one.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: Some(HTTP_ADDR.to_string()),
+        health_addr: None,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (alice, mut alice_events) = join("alice", false).await;
+    let (bob, mut bob_events) = join("bob", false).await;
+    let game_id = welcome(&mut alice_events).await.game_id;
+    assert_eq!(welcome(&mut bob_events).await.game_id, game_id);
+
+    alice.roster(None);
+    let roster = listed(&mut alice_events).await;
+    assert_eq!(roster.game_id, game_id);
+    let statuses: Vec<_> = roster.slots.iter().map(|slot| slot.status).collect();
+    assert_eq!(statuses, [SlotStatus::Connected, SlotStatus::Connected]);
+    println!("both connected: {:?}", roster.slots);
+
+    // a slot is only held for a reconnect once the match has started
+    alice.ready();
+    bob.ready();
+    let (_, game) = game_of(&games, "bob").await.expect("bob has no game");
+    while matches!(game.read().await.phase, GamePhase::ReadyCheck { .. }) {
+        sleep(Duration::from_millis(20)).await;
+    }
+    bob.close();
+    sleep(Duration::from_millis(300)).await;
+    alice.roster(Some(game_id));
+    let roster = listed(&mut alice_events).await;
+    assert_eq!(roster.slots[1].name, "bob");
+    assert_eq!(roster.slots[1].status, SlotStatus::Reconnecting);
+    println!("bob dropped: {:?}", roster.slots[1]);
+    let path = format!("/game/{}/roster", game_id);
+    assert_eq!(fetch(HTTP_ADDR, &path).await.0, 401);
+    assert_eq!(fetch_as(HTTP_ADDR, &path, Some("guess")).await.0, 401);
+    let (status, body) = fetch_as(HTTP_ADDR, &path, Some(ADMIN_TOKEN)).await;
+    assert_eq!(status, 200);
+    println!("GET /game/{}/roster: {}", game_id, body);
+    assert!(body.contains("\"name\":\"bob\",\"id\":\"guest:bob\",\"status\":\"reconnecting\""));
+
+    let (carol, mut carol_events) = join("carol", true).await;
+    let solo = welcome(&mut carol_events).await.game_id;
+    carol.roster(None);
+    let roster = listed(&mut carol_events).await;
+    assert_eq!(roster.slots[1].status, SlotStatus::Empty);
+    assert_eq!(roster.slots[1].name, "");
+    println!("solo game's second slot: {:?}", roster.slots[1]);
+
+    carol.roster(Some(game_id));
+    assert_eq!(error(&mut carol_events).await, ErrorCode::Unauthorized);
+    let path = format!("/game/{}/roster", solo);
+    assert!(fetch_as(HTTP_ADDR, &path, Some(ADMIN_TOKEN))
+        .await
+        .1
+        .contains("\"empty\""));
+    println!("another game's roster refused without the admin token");
+}
+
+async fn join(name: &str, practice: bool) -> (GameClient, Events) {
+    let options = ClientOptions {
+        practice,
+        ..options()
+    };
+    return connect(ADDR, name, options).await;
+}
+
+async fn listed(events: &mut Events) -> RosterMessage {
+    return next(events, |event| match event {
+        ClientEvent::Roster(roster) => Some(roster),
+        _ => None,
+    })
+    .await;
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, raw_welcome, RawStream, RALLY};
+use futures::SinkExt;
+use rust_backend::message::{
+    LeaveGameMessage, MessageType, PlayerJoinedMessage, PlayerLeftMessage, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18144";
+
+// The PlayerJoined naming who, skipping any about someone else.
+async fn joined(stream: &mut RawStream, name: &str) -> PlayerJoinedMessage {
+    loop {
+        let frame = raw_next(stream, &[MessageType::PlayerJoined]).await;
+        let joined = frame.decode::<PlayerJoinedMessage>().unwrap();
+        if joined.name == name {
+            return joined;
+        }
+    }
+}
+
+// When bob is matched into alice's game she is told he joined, with his
+// slot; when his connection drops mid-match she is told he left and may be
+// back, and when erin leaves dave's game on purpose dave is told she left
+// for good.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    raw_welcome(&mut alice).await;
+    let bob_welcome = raw_welcome(&mut bob).await;
+    let bob_joined = joined(&mut alice, "bob").await;
+    assert_eq!(bob_joined.player_index, bob_welcome.player_index);
+    assert!(!bob_joined.bot);
+    println!("alice told bob joined in slot {}", bob_joined.player_index);
+
+    // once the match is under way a dropped slot is held for a reconnect
+    let ready = WsMessage {
+        msg_type: MessageType::Ready,
+        payload: vec![],
+    };
+    for stream in [&mut alice, &mut bob] {
+        stream
+            .send(Message::Binary(ready.to_bytes()))
+            .await
+            .unwrap();
+    }
+    raw_next(&mut alice, &[MessageType::GameResuming]).await;
+    drop(bob);
+    let frame = raw_next(&mut alice, &[MessageType::PlayerLeft]).await;
+    let bob_left = frame.decode::<PlayerLeftMessage>().unwrap();
+    assert_eq!(bob_left.player_index, bob_welcome.player_index);
+    assert_eq!(bob_left.name, "bob");
+    assert!(!bob_left.left, "a dropped connection can still come back");
+    println!("alice told bob dropped");
+
+    let mut dave = raw_connect(ADDR, "name=dave&mode=rally").await;
+    let mut erin = raw_connect(ADDR, "name=erin&mode=rally").await;
+    raw_welcome(&mut dave).await;
+    let erin_welcome = raw_welcome(&mut erin).await;
+    joined(&mut dave, "erin").await;
+    let leave = LeaveGameMessage {
+        session_token: erin_welcome.session_token,
+    };
+    let leave = WsMessage::from_payload(MessageType::LeaveGame, &leave);
+    erin.send(Message::Binary(leave.to_bytes())).await.unwrap();
+    let frame = raw_next(&mut dave, &[MessageType::PlayerLeft]).await;
+    let erin_left = frame.decode::<PlayerLeftMessage>().unwrap();
+    assert_eq!(erin_left.name, "erin");
+    assert!(erin_left.left);
+    println!("dave told erin left the game");
+}
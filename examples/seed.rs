@@ -0,0 +1,41 @@
+mod common;
+
+use common::{rally, raw_connect, raw_welcome, RALLY};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18134";
+
+// Both players of a game are welcomed with the same seed, the one the game
+// draws its randomness from, so each client can reproduce it; a game of
+// its own gets a seed of its own.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice_stream = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob_stream = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let alice = raw_welcome(&mut alice_stream).await;
+    let bob = raw_welcome(&mut bob_stream).await;
+    assert_eq!(alice.game_id, bob.game_id);
+    assert_eq!(alice.seed, bob.seed);
+    let game = games.read().await[&(alice.game_id as usize)].clone();
+    assert_eq!(game.read().await.seed, alice.seed);
+    println!("alice and bob both got seed {}", alice.seed);
+
+    let mut carol_stream = raw_connect(ADDR, "name=carol&mode=rally&practice=1").await;
+    let carol = raw_welcome(&mut carol_stream).await;
+    assert_ne!(carol.game_id, alice.game_id);
+    assert_ne!(carol.seed, alice.seed);
+    println!("carol's game has seed {}", carol.seed);
+}
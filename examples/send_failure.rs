@@ -0,0 +1,69 @@
+mod common;
+
+use common::{exit_on_panic, raw_connect, raw_next, raw_welcome, Bulky, RALLY};
+use futures::SinkExt;
+use rust_backend::game::GameLogic;
+use rust_backend::message::{MessageType, SubscribeMessage, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18135";
+const DROPS: usize = 5;
+
+// Players whose connections drop in the middle of a State, each a quarter
+// of a megabyte, don't panic the server: the failed write is a disconnect
+// like any other, their practice games are removed, and the next player
+// is served as usual.
+#[tokio::main]
+async fn main() {
+    exit_on_panic();
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Bulky) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    for i in 0..DROPS {
+        let query = format!("name=flaky{}&mode=rally&practice=1", i);
+        let mut stream = raw_connect(ADDR, &query).await;
+        raw_welcome(&mut stream).await;
+        let subscribe = SubscribeMessage { state_rate_hz: 60 };
+        let subscribe = WsMessage::from_payload(MessageType::Subscribe, &subscribe);
+        stream
+            .send(Message::Binary(subscribe.to_bytes()))
+            .await
+            .unwrap();
+        raw_next(&mut stream, &[MessageType::State]).await;
+        // gone without a close, with the next State on its way
+        drop(stream);
+    }
+    let cleaned = async {
+        while !games.read().await.is_empty() {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), cleaned)
+        .await
+        .expect("a dropped player's game was kept");
+    println!("{} dropped mid-State, their games removed", DROPS);
+
+    let mut steady = raw_connect(ADDR, "name=steady&mode=rally&practice=1").await;
+    let welcome = raw_welcome(&mut steady).await;
+    let echo = WsMessage {
+        msg_type: MessageType::Echo,
+        payload: b"still serving".to_vec(),
+    };
+    steady.send(Message::Binary(echo.to_bytes())).await.unwrap();
+    let reply = raw_next(&mut steady, &[MessageType::Echo]).await;
+    assert!(reply.payload.starts_with(b"still serving"));
+    println!("steady welcomed to game {} and echoed", welcome.game_id);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{connect, next, Bulky, RALLY};
+use futures::SinkExt;
+use rust_backend::client::ClientEvent;
+use rust_backend::events::ServerEvent;
+use rust_backend::game::GameLogic;
+use rust_backend::message::{CloseReason, MessageType, SubscribeMessage, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::net::TcpSocket;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio_tungstenite::client_async;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18122";
+const SEND_TIMEOUT: Duration = Duration::from_millis(500);
+
+// bob finishes the websocket handshake over a socket with a tiny receive
+// buffer and then never reads again, while keeping the TCP connection up.
+// Once a write to him has made no progress for send_timeout the server
+// closes him as too slow and alice hears he is gone, without waiting on the
+// OS to give up on the socket.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ready_timeout: Duration::from_millis(100),
+        send_timeout: Some(SEND_TIMEOUT),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Bulky) as Box<dyn GameLogic>;
+    });
+    let mut server_events = server.subscribe_events();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (_alice, mut events) = connect(ADDR, "alice", common::options()).await;
+
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(1024).unwrap();
+    let stream = socket.connect(ADDR.parse().unwrap()).await.unwrap();
+    let url = format!("ws://{}/?name=bob&mode=rally", ADDR);
+    let (mut bob, _) = client_async(url, stream).await.unwrap();
+    next(&mut events, |event| match event {
+        ClientEvent::Welcome(_) => Some(()),
+        _ => None,
+    })
+    .await;
+    let subscribe = SubscribeMessage { state_rate_hz: 60 };
+    let subscribe = WsMessage::from_payload(MessageType::Subscribe, &subscribe);
+    bob.send(Message::Binary(subscribe.to_bytes()))
+        .await
+        .unwrap();
+    let stalled = Instant::now();
+
+    let left = next(&mut events, |event| match event {
+        ClientEvent::PlayerLeft(left) if left.name == "bob" => Some(left),
+        _ => None,
+    })
+    .await;
+    let took = stalled.elapsed();
+    assert!(took < SEND_TIMEOUT * 4, "bob held his slot for {:?}", took);
+    println!(
+        "alice told bob is gone (left: {}) {:?} after he stopped reading",
+        left.left, took
+    );
+
+    let reason = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(ServerEvent::ConnectionClosed { record }) = server_events.recv().await {
+                if record.name.as_deref() == Some("bob") {
+                    return record.reason;
+                }
+            }
+        }
+    })
+    .await
+    .expect("bob's connection never closed");
+    assert_eq!(reason, Some(CloseReason::TooSlow));
+    println!("bob closed as {:?}", reason);
+    // bob holds his end open the whole time
+    drop(bob);
+}
@@ -0,0 +1,41 @@
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+
+const SERVE_SPEED: f32 = 300.0;
+
+// A game with serve_speed set must send its ball off at that speed once it
+// is seeded, the same way for the same seed; without it the ball stays put.
+fn main() {
+    let served = |seed: u64| {
+        let mut game = SoccerGame::with_config(SoccerGameConfig {
+            serve_speed: Some(SERVE_SPEED),
+            ..SoccerGameConfig::default()
+        });
+        game.reseed(seed);
+        return *game.bodies[game.balls[0]].linvel();
+    };
+    let velocity = served(42);
+    assert!(
+        (velocity.norm() - SERVE_SPEED).abs() < 0.01,
+        "served at {:?}",
+        velocity
+    );
+    // within 30 degrees of straight at a goal
+    assert!(
+        velocity.x.abs() >= SERVE_SPEED * 0.86,
+        "served at {:?}",
+        velocity
+    );
+    assert_eq!(served(42), velocity);
+    println!("served at [{}, {}]", velocity.x, velocity.y);
+
+    let mut still = SoccerGame::with_config(SoccerGameConfig::default());
+    still.reseed(42);
+    let velocity = *still.bodies[still.balls[0]].linvel();
+    assert_eq!(
+        velocity.norm(),
+        0.0,
+        "unserved ball moving at {:?}",
+        velocity
+    );
+    println!("unserved ball stays still");
+}
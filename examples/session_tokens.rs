@@ -0,0 +1,122 @@
+mod common;
+
+use common::{connect, rally, raw_connect, raw_next, raw_welcome, welcome, RawStream, RALLY};
+use futures::SinkExt;
+use rust_backend::client::ClientOptions;
+use rust_backend::message::{ErrorCode, ErrorMessage, LeaveGameMessage, MessageType, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18145";
+
+async fn send(stream: &mut RawStream, message: &WsMessage) {
+    stream
+        .send(Message::Binary(message.to_bytes()))
+        .await
+        .unwrap();
+}
+
+// The Error a connection is answered with, or a panic if it got in.
+async fn refused(stream: &mut RawStream) -> ErrorCode {
+    let reply = raw_next(stream, &[MessageType::Welcome, MessageType::Error]).await;
+    return reply.decode::<ErrorMessage>().expect("got in").code;
+}
+
+// Each slot gets its own random token in Welcome. Leaving takes the
+// leaver's own token, and reclaiming a dropped slot takes its token: bob's
+// name alone doesn't get anyone in, and the SDK given it in ClientOptions
+// takes the slot back on its first connect. Once the game is torn down the
+// token is worth nothing.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally").await;
+    let mut bob = raw_connect(ADDR, "name=bob&mode=rally").await;
+    let alice_welcome = raw_welcome(&mut alice).await;
+    let bob_welcome = raw_welcome(&mut bob).await;
+    for token in [&alice_welcome.session_token, &bob_welcome.session_token] {
+        assert!(token.len() >= 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+    assert_ne!(alice_welcome.session_token, bob_welcome.session_token);
+    println!("two slots, two tokens");
+
+    // started, so a dropped slot is held for its owner
+    let ready = WsMessage {
+        msg_type: MessageType::Ready,
+        payload: vec![],
+    };
+    send(&mut alice, &ready).await;
+    send(&mut bob, &ready).await;
+    raw_next(&mut alice, &[MessageType::GameResuming]).await;
+
+    let leave = LeaveGameMessage {
+        session_token: bob_welcome.session_token.clone(),
+    };
+    send(
+        &mut alice,
+        &WsMessage::from_payload(MessageType::LeaveGame, &leave),
+    )
+    .await;
+    let reply = raw_next(&mut alice, &[MessageType::Error]).await;
+    let error = reply.decode::<ErrorMessage>().unwrap();
+    assert_eq!(error.code, ErrorCode::Unauthorized);
+    println!("alice can't leave with bob's token: {}", error.message);
+
+    drop(bob);
+    raw_next(&mut alice, &[MessageType::PlayerLeft]).await;
+    let game = format!(
+        "name=bob&mode=rally&game={}&game_token={}",
+        bob_welcome.game_id, bob_welcome.game_token
+    );
+    let mut mallory = raw_connect(ADDR, &game).await;
+    assert_eq!(refused(&mut mallory).await, ErrorCode::Unauthorized);
+    let forged = format!("{}&session={}", game, alice_welcome.session_token);
+    let mut mallory = raw_connect(ADDR, &forged).await;
+    assert_eq!(refused(&mut mallory).await, ErrorCode::Unauthorized);
+    println!("bob's slot refused without his token");
+
+    let with_token = format!("{}&session={}", game, bob_welcome.session_token);
+    let mut bob = raw_connect(ADDR, &with_token).await;
+    let back = raw_welcome(&mut bob).await;
+    assert_eq!(back.game_id, bob_welcome.game_id);
+    assert_eq!(back.player_index, bob_welcome.player_index);
+    println!("bob back in slot {} with his token", back.player_index);
+
+    drop(bob);
+    raw_next(&mut alice, &[MessageType::PlayerLeft]).await;
+    let options = ClientOptions {
+        session: Some(bob_welcome.session_token.clone()),
+        ..common::options()
+    };
+    let (bob, mut events) = connect(ADDR, "bob", options).await;
+    let back = welcome(&mut events).await;
+    assert_eq!(
+        (back.game_id, back.player_index),
+        (bob_welcome.game_id, bob_welcome.player_index)
+    );
+    println!("the SDK reclaimed the slot with the session it was given");
+
+    let torn_down = games
+        .write()
+        .await
+        .remove(&(bob_welcome.game_id as usize))
+        .unwrap();
+    torn_down.read().await.close();
+    drop(bob);
+    let mut stale = raw_connect(ADDR, &with_token).await;
+    assert_eq!(refused(&mut stale).await, ErrorCode::GameNotFound);
+    println!("after teardown the token gets nowhere");
+}
@@ -0,0 +1,55 @@
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig};
+
+const EPSILON: f32 = 1e-3;
+const DT: f64 = 1000.0 / 60.0;
+
+// The furthest any puck or ball is from its kickoff spot.
+fn drift(game: &SoccerGame) -> f32 {
+    return game
+        .pucks
+        .iter()
+        .chain(&game.balls)
+        .map(|handle| (game.bodies[*handle].translation() - game.kickoff[handle]).norm())
+        .fold(0.0, f32::max);
+}
+
+// With nobody moving, every body is where it spawned once the settle phase
+// is over, and stays there for a while after. A move during the settle
+// phase goes through rather than being undone.
+fn main() {
+    let config = SoccerGameConfig {
+        settle_ticks: 5,
+        ..SoccerGameConfig::default()
+    };
+    let mut game = SoccerGame::with_config(config.clone());
+    for _ in 0..config.settle_ticks {
+        game.update(DT);
+    }
+    assert!(
+        drift(&game) <= EPSILON,
+        "drifted {} while settling",
+        drift(&game)
+    );
+    for _ in 0..120 {
+        game.update(DT);
+    }
+    assert!(
+        drift(&game) <= EPSILON,
+        "drifted {} after settling",
+        drift(&game)
+    );
+    println!(
+        "{} settle ticks, then 2s untouched: off by {}",
+        config.settle_ticks,
+        drift(&game)
+    );
+
+    let mut game = SoccerGame::with_config(config);
+    game.update(DT);
+    let puck = game.pucks[0];
+    game.apply_move(puck, 300.0, 0.0, 0.0);
+    game.update(DT);
+    let moved = (game.bodies[puck].translation() - game.kickoff[&puck]).norm();
+    assert!(moved > EPSILON, "move undone by settling");
+    println!("puck moved {} on the first tick after its move", moved);
+}
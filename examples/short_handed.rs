@@ -0,0 +1,83 @@
+mod common;
+
+use common::{connect, options, rally, RALLY};
+use rust_backend::client::GameClient;
+use rust_backend::game::{GamePhase, Games, PauseConfig, ShortHanded};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18088";
+
+// With short_handed = "wait" a match that loses a connection freezes
+// instead of playing on, and the absent player forfeits once the wait runs
+// out.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        pause: PauseConfig {
+            short_handed: ShortHanded::Wait(Some(Duration::from_secs(1))),
+            ..PauseConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = join("alice").await;
+    let bob = join("bob").await;
+    for client in [&alice, &bob] {
+        while client.session_token().is_none() {
+            sleep(Duration::from_millis(10)).await;
+        }
+        client.ready();
+    }
+    let game_id = wait_for(&games, "game never started", |phase| {
+        matches!(phase, GamePhase::Playing)
+    })
+    .await;
+    println!("game {} started", game_id);
+
+    bob.close();
+    wait_for(&games, "game kept playing one short", |phase| {
+        matches!(phase, GamePhase::WaitingForPlayers { missing: 1, .. })
+    })
+    .await;
+    println!("game {} waits for bob", game_id);
+
+    let gone = async {
+        while games.read().await.contains_key(&game_id) {
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    timeout(Duration::from_secs(5), gone)
+        .await
+        .expect("bob never forfeited");
+    println!("bob forfeited game {}", game_id);
+    drop(alice);
+}
+
+async fn join(name: &str) -> GameClient {
+    let (client, _events) = connect(ADDR, name, options()).await;
+    return client;
+}
+
+// The id of the first game whose phase passes check.
+async fn wait_for(games: &Games, failure: &str, check: impl Fn(&GamePhase) -> bool) -> usize {
+    let find = async {
+        loop {
+            for (id, game) in games.read().await.iter() {
+                if check(&game.read().await.phase) {
+                    return *id;
+                }
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+    };
+    return timeout(Duration::from_secs(5), find).await.expect(failure);
+}
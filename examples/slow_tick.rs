@@ -0,0 +1,81 @@
+mod common;
+
+use common::{get, join_ready, RALLY};
+use rust_backend::game::GameLogic;
+use rust_backend::server::{Server, ServerConfig};
+use serde_json::Value;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18139";
+const HTTP_ADDR: &str = "127.0.0.1:18140";
+const STALL: Duration = Duration::from_millis(30);
+
+// A game whose every step takes longer than a whole 60Hz tick.
+struct Slow;
+
+impl GameLogic for Slow {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn max_players(&self) -> usize {
+        return 1;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        std::thread::sleep(STALL);
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+}
+
+// Ticks held up by a slow update are counted as over budget, the game that
+// held them up is named, and /ticks shows the time went on the step rather
+// than on input, encoding or broadcasting.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: Some(HTTP_ADDR.to_string()),
+        health_addr: None,
+        tick_rate: 60,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Slow) as Box<dyn GameLogic>;
+    });
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (welcome, _link) = join_ready(ADDR, "name=alice&mode=rally&practice=1").await;
+    let slow = async {
+        loop {
+            let ticks: Value = serde_json::from_str(&get(HTTP_ADDR, "/ticks").await).unwrap();
+            if ticks["slow_ticks"].as_u64().unwrap() > 0 {
+                return ticks;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    };
+    let ticks = timeout(Duration::from_secs(10), slow)
+        .await
+        .expect("no tick went over budget");
+    let ms = |value: &Value| value.as_f64().unwrap();
+    assert_eq!(ticks["worst_game"]["id"], welcome.game_id as u64);
+    assert!(ms(&ticks["worst_game"]["ms"]) >= ms(&ticks["budget_ms"]));
+    let step = &ticks["phases"]["step"];
+    assert!(ms(&step["max_ms"]) >= STALL.as_millis() as f64);
+    for phase in ["input", "encode", "broadcast"] {
+        assert!(ms(&ticks["phases"][phase]["max_ms"]) < ms(&step["max_ms"]));
+    }
+    println!(
+        "{} slow ticks, worst game {}, step up to {}ms",
+        ticks["slow_ticks"], ticks["worst_game"]["id"], step["max_ms"]
+    );
+}
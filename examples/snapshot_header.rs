@@ -0,0 +1,147 @@
+use futures::{SinkExt, StreamExt};
+use rust_backend::game::GameLogic;
+use rust_backend::message::{
+    ByteOrder, HelloMessage, MatchPhase, MessageType, ProtocolVersion, SnapshotHeader, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18103";
+const RALLY: u8 = 7;
+
+// A timed game with two teams that knows nothing about the header; the
+// server puts it in front of the body.
+struct Rally;
+
+impl GameLogic for Rally {
+    fn game_type(&self) -> u8 {
+        return RALLY;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![0xAB, 0xCD];
+    }
+    fn remaining_time(&self) -> Option<Duration> {
+        return Some(Duration::from_millis(90_050));
+    }
+    fn scores(&self) -> Vec<u32> {
+        return vec![3, 300];
+    }
+}
+
+// The v8 header layout is pinned byte for byte, in both byte orders, and a
+// v8 connection to a game type of its own gets it ahead of the game's body.
+#[tokio::main]
+async fn main() {
+    let header = SnapshotHeader {
+        tick: 0x01020304,
+        phase: MatchPhase::Playing,
+        remaining_ds: Some(1234),
+        scores: vec![2, 1],
+        level: None,
+    };
+    let little = header.to_bytes();
+    assert_eq!(little, [0x04, 0x03, 0x02, 0x01, 2, 0xD2, 0x04, 2, 2, 1]);
+    let mut big = little.clone();
+    assert!(ByteOrder::Big.swap_snapshot_header(&mut big));
+    assert_eq!(big, [0x01, 0x02, 0x03, 0x04, 2, 0x04, 0xD2, 2, 2, 1]);
+    let mut body = little.clone();
+    body.push(0xEE);
+    assert_eq!(
+        SnapshotHeader::from_bytes(&body),
+        Some((header.clone(), &[0xEE][..]))
+    );
+    println!("header {:02x?}", little);
+
+    let untimed = SnapshotHeader {
+        remaining_ds: None,
+        scores: vec![],
+        ..header
+    };
+    assert_eq!(
+        untimed.to_bytes(),
+        [0x04, 0x03, 0x02, 0x01, 2, 0xFF, 0xFF, 0]
+    );
+    assert_eq!(
+        SnapshotHeader::from_bytes(&untimed.to_bytes()),
+        Some((untimed, &[][..]))
+    );
+    println!("untimed: remaining 0xffff, no teams");
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Rally) as Box<dyn GameLogic>;
+    });
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut request = format!("ws://{}/?name=alice&mode=rally&practice=1", ADDR)
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static(ProtocolVersion::V8.as_str()),
+    );
+    let (mut stream, _) = connect_async(request).await.unwrap();
+    let hello = HelloMessage {
+        protocol: ProtocolVersion::V8 as u8,
+        build: "snapshot-header-example".to_string(),
+        state_version: ProtocolVersion::V8 as u8,
+        format: 0,
+        byte_order: ByteOrder::Big.code(),
+        role: 0,
+    };
+    let hello = WsMessage::from_payload(MessageType::Hello, &hello);
+    stream
+        .send(Message::Binary(hello.to_bytes()))
+        .await
+        .unwrap();
+    let state = WsMessage {
+        msg_type: MessageType::State,
+        payload: vec![],
+    };
+    stream
+        .send(Message::Binary(state.to_bytes()))
+        .await
+        .unwrap();
+    let read = async {
+        while let Some(Ok(message)) = stream.next().await {
+            if let Message::Binary(data) = message {
+                let message = WsMessage::from_bytes(&data)
+                    .filter(|message| matches!(message.msg_type, MessageType::State));
+                if let Some(message) = message {
+                    return message.payload;
+                }
+            }
+        }
+        panic!("closed before a State");
+    };
+    let mut payload = timeout(Duration::from_secs(5), read)
+        .await
+        .expect("no State");
+    assert!(ByteOrder::Big.swap_snapshot_header(&mut payload));
+    let (header, body) = SnapshotHeader::from_bytes(&payload).expect("no header");
+    assert_eq!(header.phase, MatchPhase::Waiting);
+    // 90.05s rounds down to 900 deciseconds; 300 goals cap at 255
+    assert_eq!(header.remaining_ds, Some(900));
+    assert_eq!(header.scores, [3, 255]);
+    assert_eq!(body, [0xAB, 0xCD]);
+    println!("rally State: {:?} then body {:02x?}", header, body);
+}
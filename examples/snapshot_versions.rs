@@ -0,0 +1,274 @@
+use rust_backend::message::{HandicapConfig, PlayerRecord, TeamHandicap};
+use rust_backend::persistence::{
+    load, save, SavedGame, SavedMatch, SavedRecord, SavedSlot, Snapshot,
+};
+use rust_backend::stats::PlayerId;
+use serde::Serialize;
+use std::path::Path;
+
+// The record layouts older files hold, flat the way bincode writes them.
+#[derive(Serialize)]
+struct FourFields {
+    id: PlayerId,
+    name: String,
+    wins: u32,
+    losses: u32,
+    goals_scored: u32,
+    goals_conceded: u32,
+}
+
+#[derive(Serialize)]
+struct WithOwnGoals {
+    id: PlayerId,
+    name: String,
+    wins: u32,
+    losses: u32,
+    goals_scored: u32,
+    goals_conceded: u32,
+    own_goals: u32,
+}
+
+// A SavedMatch before it kept timers.
+#[derive(Serialize)]
+struct Untimed {
+    id: usize,
+    game_type: u8,
+    seed: u64,
+    token: String,
+    practice: bool,
+    slots: Vec<SavedSlot>,
+    scores: Vec<u32>,
+    bodies: Vec<()>,
+}
+
+// A SavedMatch before it kept its handicap.
+#[derive(Serialize)]
+struct Timed {
+    id: usize,
+    game_type: u8,
+    seed: u64,
+    token: String,
+    practice: bool,
+    slots: Vec<SavedSlot>,
+    scores: Vec<u32>,
+    bodies: Vec<()>,
+    timers: Vec<()>,
+}
+
+fn write(path: &Path, version: u8, contents: &impl Serialize) {
+    let mut data = vec![version];
+    bincode::serialize_into(&mut data, contents).unwrap();
+    std::fs::write(path, data).unwrap();
+}
+
+fn alice() -> PlayerId {
+    return PlayerId::Guest("alice".to_string());
+}
+
+fn four_fields() -> Vec<FourFields> {
+    return vec![FourFields {
+        id: alice(),
+        name: "alice".to_string(),
+        wins: 3,
+        losses: 1,
+        goals_scored: 12,
+        goals_conceded: 5,
+    }];
+}
+
+fn with_own_goals() -> Vec<WithOwnGoals> {
+    return vec![WithOwnGoals {
+        id: alice(),
+        name: "alice".to_string(),
+        wins: 3,
+        losses: 1,
+        goals_scored: 12,
+        goals_conceded: 5,
+        own_goals: 2,
+    }];
+}
+
+fn record(own_goals: u32, shots: u32) -> SavedRecord {
+    return SavedRecord {
+        id: alice(),
+        name: "alice".to_string(),
+        record: PlayerRecord {
+            wins: 3,
+            losses: 1,
+            goals_scored: 12,
+            goals_conceded: 5,
+            own_goals,
+            shots,
+            ..PlayerRecord::default()
+        },
+    };
+}
+
+fn games() -> Vec<SavedGame> {
+    return vec![SavedGame {
+        id: 7,
+        game_type: 1,
+        seed: 99,
+        players: vec!["alice".to_string(), "bob".to_string()],
+        scores: vec![2, 1],
+    }];
+}
+
+fn slots() -> Vec<SavedSlot> {
+    return vec![SavedSlot {
+        id: alice(),
+        name: "alice".to_string(),
+        session_token: "alice-session".to_string(),
+        bot: false,
+    }];
+}
+
+fn untimed() -> Vec<Untimed> {
+    return vec![Untimed {
+        id: 7,
+        game_type: 1,
+        seed: 99,
+        token: "abcd1234".to_string(),
+        practice: false,
+        slots: slots(),
+        scores: vec![2, 1],
+        bodies: vec![],
+    }];
+}
+
+fn timed() -> Vec<Timed> {
+    return vec![Timed {
+        id: 7,
+        game_type: 1,
+        seed: 99,
+        token: "abcd1234".to_string(),
+        practice: false,
+        slots: slots(),
+        scores: vec![2, 1],
+        bodies: vec![],
+        timers: vec![],
+    }];
+}
+
+fn resumable(handicap: Option<HandicapConfig>) -> Vec<SavedMatch> {
+    return vec![SavedMatch {
+        id: 7,
+        game_type: 1,
+        seed: 99,
+        token: "abcd1234".to_string(),
+        practice: false,
+        slots: slots(),
+        scores: vec![2, 1],
+        bodies: vec![],
+        timers: vec![],
+        handicap,
+    }];
+}
+
+// A file of every snapshot version loads as the snapshot it held, with
+// whatever it didn't keep at its default: version 1's four-field records,
+// version 2's in both the layouts it was written in, version 3's untimed
+// matches, version 4's missing game id counter and version 5's matches
+// without a handicap, which resume even.
+fn main() {
+    let path = std::env::temp_dir().join(format!("snapshot_versions_{}.bin", std::process::id()));
+    let handicap = HandicapConfig {
+        right: TeamHandicap {
+            score: 1,
+            ..TeamHandicap::default()
+        },
+        ..HandicapConfig::default()
+    };
+    let current = Snapshot {
+        records: vec![record(2, 40)],
+        games: games(),
+        resumable: resumable(Some(handicap)),
+        last_game_id: 12,
+    };
+    let files: Vec<(&str, Box<dyn Fn(&Path)>, Snapshot)> = vec![
+        (
+            "1",
+            Box::new(|path| write(path, 1, &(four_fields(), games()))),
+            Snapshot {
+                records: vec![record(0, 0)],
+                games: games(),
+                ..Snapshot::default()
+            },
+        ),
+        (
+            "2 before own goals",
+            Box::new(|path| write(path, 2, &(four_fields(), games(), untimed()))),
+            Snapshot {
+                records: vec![record(0, 0)],
+                games: games(),
+                resumable: resumable(None),
+                last_game_id: 0,
+            },
+        ),
+        (
+            "2 with own goals",
+            Box::new(|path| write(path, 2, &(with_own_goals(), games(), untimed()))),
+            Snapshot {
+                records: vec![record(2, 0)],
+                games: games(),
+                resumable: resumable(None),
+                last_game_id: 0,
+            },
+        ),
+        (
+            "3",
+            Box::new(|path| write(path, 3, &(vec![record(2, 40)], games(), untimed()))),
+            Snapshot {
+                records: vec![record(2, 40)],
+                games: games(),
+                resumable: resumable(None),
+                last_game_id: 0,
+            },
+        ),
+        (
+            "4",
+            Box::new(|path| write(path, 4, &(vec![record(2, 40)], games(), timed()))),
+            Snapshot {
+                records: vec![record(2, 40)],
+                games: games(),
+                resumable: resumable(None),
+                last_game_id: 0,
+            },
+        ),
+        (
+            "5",
+            Box::new(|path| write(path, 5, &(vec![record(2, 40)], games(), timed(), 12usize))),
+            Snapshot {
+                records: vec![record(2, 40)],
+                games: games(),
+                resumable: resumable(None),
+                last_game_id: 12,
+            },
+        ),
+        (
+            "6",
+            Box::new({
+                let current = current.clone();
+                move |path| save(path, &current).unwrap()
+            }),
+            current,
+        ),
+    ];
+    for (version, write, expected) in files {
+        write(&path);
+        let loaded = load(&path).unwrap().expect("no snapshot");
+        assert_eq!(loaded, expected, "version {}", version);
+        println!(
+            "version {}: {} own goals, {} shots, last game id {}",
+            version,
+            loaded.records[0].record.own_goals,
+            loaded.records[0].record.shots,
+            loaded.last_game_id()
+        );
+    }
+
+    write(&path, 9, &(four_fields(), games()));
+    assert!(load(&path).is_err());
+    println!("an unknown version is refused");
+    let _ = std::fs::remove_file(&path);
+}
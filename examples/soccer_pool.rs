@@ -0,0 +1,67 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{Game, GameLogic, SoccerGame, SoccerGameConfig};
+use rust_backend::pool::SoccerPool;
+use rust_backend::stats::PlayerId;
+use std::sync::atomic::Ordering;
+
+const CYCLES: usize = 50;
+// updates each game runs before it is removed
+const UPDATES: usize = 120;
+
+// A short match: two players join, a ball gets knocked around, the game is
+// removed. Returns where its world lives and the logic back.
+fn cycle(config: &SoccerGameConfig, pool: &SoccerPool) -> (usize, Box<dyn GameLogic>) {
+    let logic = match pool.take(config) {
+        Some(logic) => logic,
+        None => Box::new(SoccerGame::with_config(config.clone())),
+    };
+    let world = logic.as_any() as *const dyn std::any::Any as *const () as usize;
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::with_logic(logic, players);
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    let ball = soccer.balls[0];
+    soccer.bodies[ball].set_linvel(vector![400.0, 250.0], true);
+    for _ in 0..UPDATES {
+        game.logic.update(1000.0 / 60.0);
+    }
+    game.close();
+    return (world, game.retire_logic());
+}
+
+// After the first game every create is served from the pool, reusing the
+// one world rather than building another. The pooled world has to come back
+// clean: nothing moving and everything on its kickoff spot.
+fn main() {
+    let config = SoccerGameConfig::default();
+    let pool = SoccerPool::default();
+    let mut worlds = vec![];
+    for _ in 0..CYCLES {
+        let (world, logic) = cycle(&config, &pool);
+        worlds.push(world);
+        pool.put(logic, 1);
+        let pooled = pool.take(&config).expect("world wasn't pooled");
+        let soccer = pooled.as_any().downcast_ref::<SoccerGame>().unwrap();
+        assert!(soccer.is_clean(), "pooled world wasn't reset");
+        pool.put(pooled, 1);
+    }
+    assert!(worlds.iter().all(|world| *world == worlds[0]));
+    assert_eq!(pool.misses.load(Ordering::Relaxed), 1);
+    assert_eq!(pool.hits.load(Ordering::Relaxed), 2 * CYCLES as u64 - 1);
+    assert_eq!(pool.len(), 1);
+
+    // a different config never gets a pooled world
+    let bigger = SoccerGameConfig {
+        pucks_per_team: config.pucks_per_team + 1,
+        ..config.clone()
+    };
+    assert!(pool.take(&bigger).is_none());
+    assert_eq!(pool.misses.load(Ordering::Relaxed), 2);
+    println!(
+        "{} games on one world: {} pool hits, 1 build",
+        CYCLES,
+        pool.hits.load(Ordering::Relaxed)
+    );
+}
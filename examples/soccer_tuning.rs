@@ -0,0 +1,131 @@
+mod common;
+
+use common::{connect, next, options, welcome, Events};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::game::{Games, SoccerGame};
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType, SoccerTunedMessage, WsMessage};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18127";
+const ADMIN_TOKEN: &str = "letmein";
+
+// An admin retunes soccer while a game is running. Games created afterwards
+// are built with the new section on top of the current config, field size
+// included, while the running one keeps what it had; asked to reapply, the
+// running games take the new damping too but keep their field, and a reset
+// doesn't undo it. A section that only sets damping leaves the 900 wide
+// field in place for new games. A section the config file would refuse
+// changes nothing.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (admin, mut events, before) = join("admin", Some(ADMIN_TOKEN)).await;
+    let (damping, width, version) = soccer(&games, before).await;
+    assert_eq!((damping, version), (0.1, 0));
+    println!(
+        "game {} built from v{}: damping {}, {} wide",
+        before, version, damping, width
+    );
+
+    admin.tune_soccer("width = 900\nheight = 700\npuck_damping = 0.5\n", false);
+    let tuned = soccer_tuned(&mut events).await;
+    assert_eq!(tuned.version, 1);
+    assert!(tuned.reapplied.is_empty());
+    assert_eq!(soccer(&games, before).await, (0.1, width, 0));
+    println!("v1 for new games only; game {} untouched", before);
+
+    let (_player, _, after) = join("player", None).await;
+    assert_eq!(soccer(&games, after).await, (0.5, 900.0, 1));
+    println!("game {} created after: damping 0.5, 900 wide, v1", after);
+
+    admin.tune_soccer("puck_damping = 0.3\n", true);
+    let tuned = soccer_tuned(&mut events).await;
+    assert_eq!(tuned.version, 2);
+    assert!(tuned.reapplied.contains(&before) && tuned.reapplied.contains(&after));
+    let params = next(&mut events, |event| match event {
+        ClientEvent::GameParamsChanged(params) => Some(params),
+        _ => None,
+    })
+    .await;
+    assert_eq!(params.puck_damping, Some(0.3));
+    assert_eq!(soccer(&games, before).await, (0.3, width, 2));
+    assert_eq!(soccer(&games, after).await, (0.3, 900.0, 2));
+    println!(
+        "v2 reapplied to games {:?}, each keeping its field",
+        tuned.reapplied
+    );
+
+    {
+        let game = games.read().await[&(before as usize)].clone();
+        let mut game = game.write().await;
+        game.downcast_mut::<SoccerGame>().unwrap().reset();
+    }
+    assert_eq!(soccer(&games, before).await, (0.3, width, 2));
+    println!("game {} keeps damping 0.3 through a reset", before);
+
+    admin.tune_soccer("balls = 99\n", true);
+    let refused = next(&mut events, |event| match event {
+        ClientEvent::Message(MessageType::Error, payload) => WsMessage {
+            msg_type: MessageType::Error,
+            payload,
+        }
+        .decode::<ErrorMessage>(),
+        _ => None,
+    })
+    .await;
+    assert_eq!(refused.code, ErrorCode::InvalidParams);
+    assert_eq!(soccer(&games, before).await, (0.3, width, 2));
+    println!("refused: {}", refused.message);
+
+    let (_late, _, last) = join("late", None).await;
+    assert_eq!(soccer(&games, last).await, (0.3, 900.0, 2));
+    println!(
+        "game {} created after the refusal is still on v2, 900 wide",
+        last
+    );
+}
+
+// Joins a practice soccer game and returns its id.
+async fn join(name: &str, auth_token: Option<&str>) -> (GameClient, Events, u32) {
+    let options = ClientOptions {
+        mode: None,
+        practice: true,
+        auth_token: auth_token.map(str::to_string),
+        ..options()
+    };
+    let (client, mut events) = connect(ADDR, name, options).await;
+    let game_id = welcome(&mut events).await.game_id;
+    return (client, events, game_id);
+}
+
+// A game's puck damping, field width and tuning version.
+async fn soccer(games: &Games, game_id: u32) -> (f32, f32, u64) {
+    let game = games.read().await[&(game_id as usize)].clone();
+    let game = game.read().await;
+    let soccer_game = game.downcast::<SoccerGame>().unwrap();
+    return (
+        soccer_game.puck_damping,
+        soccer_game.config().width,
+        soccer_game.tuning_version,
+    );
+}
+
+async fn soccer_tuned(events: &mut Events) -> SoccerTunedMessage {
+    return next(events, |event| match event {
+        ClientEvent::SoccerTuned(tuned) => Some(tuned),
+        _ => None,
+    })
+    .await;
+}
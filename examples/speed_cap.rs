@@ -0,0 +1,42 @@
+use rust_backend::game::{GameLogic, GameRng, SoccerGame, SoccerGameConfig};
+
+const STEPS: usize = 2_000;
+const CAP: f32 = 900.0;
+// far past anything a client is allowed to send
+const SHOT: f32 = 20_000.0;
+
+// The fastest any body gets over STEPS of every puck being fired off as hard
+// as possible in a random direction each step.
+fn fastest(config: SoccerGameConfig) -> f32 {
+    let mut game = SoccerGame::with_config(config);
+    let mut rng = GameRng::new(11);
+    let mut fastest: f32 = 0.0;
+    for _ in 0..STEPS {
+        for puck in game.pucks.clone() {
+            let angle = rng.next_f32() * std::f32::consts::TAU;
+            game.apply_move(puck, angle.cos() * SHOT, angle.sin() * SHOT, 0.0);
+        }
+        game.update(1000.0 / 60.0);
+        for handle in game.pucks.iter().chain(&game.balls) {
+            fastest = fastest.max(game.bodies[*handle].linvel().norm());
+        }
+    }
+    return fastest;
+}
+
+// With max_body_speed set, no puck or ball comes out of a step faster than
+// it however hard and often the pucks are struck; without it the same
+// pounding sends them far past it.
+fn main() {
+    let uncapped = fastest(SoccerGameConfig::default());
+    assert!(uncapped > CAP, "never got past {} uncapped", CAP);
+    let capped = fastest(SoccerGameConfig {
+        max_body_speed: Some(CAP),
+        ..SoccerGameConfig::default()
+    });
+    assert!(capped <= CAP * 1.001, "{} over a cap of {}", capped, CAP);
+    println!(
+        "uncapped reached {:.0}, capped at {} reached {:.0}",
+        uncapped, CAP, capped
+    );
+}
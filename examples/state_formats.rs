@@ -0,0 +1,61 @@
+use rust_backend::game::{GameLogic, SoccerGame};
+use rust_backend::message::{ByteOrder, MatchPhase, ProtocolVersion, SoccerStateSnapshot};
+use rust_backend::serializer::{CompactBinary, Json, Quantized, StateSerializer, StateView};
+
+// a tick at 60Hz, in ms
+const TICK: f64 = 1000.0 / 60.0;
+
+// One moving game encoded by each serializer and decoded again: JSON comes
+// back as exactly the snapshot binary does, every field of it, and the
+// quantized one puts every body within its documented bound of the same
+// place.
+fn main() {
+    let mut soccer = SoccerGame::new();
+    let puck = soccer.pucks[0];
+    soccer.apply_move(puck, 180.0, -90.0, 0.0);
+    for _ in 0..10 {
+        soccer.update(TICK);
+    }
+    let view = StateView {
+        player: Some(0),
+        ack_seq: 7,
+        boost_cooldown_ms: 1200,
+        tick: 42,
+        server_time_us: 1_234_567,
+        match_phase: MatchPhase::Playing,
+    };
+    let protocol = ProtocolVersion::V7;
+
+    let binary = CompactBinary(protocol, ByteOrder::Little).encode(&soccer, &view);
+    let binary = SoccerStateSnapshot::decode(protocol, &binary).expect("binary didn't decode");
+    let json = Json.encode(&soccer, &view);
+    let json = SoccerStateSnapshot::from_json(&json).expect("JSON didn't decode");
+    assert_eq!(json, binary);
+    assert_eq!(json.tick, 42);
+    assert_eq!(json.ack_seq, 7);
+    assert_eq!(json.own_pucks.len(), soccer.teams[0].pucks.len());
+    println!(
+        "binary and JSON agree on {} pucks and {} balls",
+        json.pucks.len(),
+        json.balls.len()
+    );
+
+    let quantized = Quantized(protocol, ByteOrder::Little).encode(&soccer, &view);
+    let quantized = SoccerStateSnapshot::decode_quantized(protocol, &quantized)
+        .expect("quantized didn't decode");
+    let bound = soccer.bounds.x.max(soccer.bounds.y).ceil() / 65535.0 * 1.01;
+    let bodies = |snapshot: &SoccerStateSnapshot| -> Vec<(f32, f32)> {
+        return snapshot
+            .pucks
+            .iter()
+            .chain(&snapshot.balls)
+            .copied()
+            .collect();
+    };
+    for ((x, y), (qx, qy)) in bodies(&binary).into_iter().zip(bodies(&quantized)) {
+        assert!((x - qx).abs() <= bound && (y - qy).abs() <= bound);
+    }
+    assert_eq!(quantized.tick, binary.tick);
+    assert_eq!(quantized.match_phase, binary.match_phase);
+    println!("quantized within {} of binary", bound);
+}
@@ -0,0 +1,41 @@
+use rust_backend::message::{ProtocolVersion, SoccerStateSnapshot, StatePayload};
+
+// A State payload survives a trip through its bytes exactly, NaN and
+// infinities included, and its bytes are the documented little-endian
+// pairs a v1 client reads as pucks followed by the ball. Anything that
+// isn't a whole number of pairs is refused.
+fn main() {
+    let payload = StatePayload {
+        positions: vec![(-120.5, 40.0), (120.5, -40.25), (0.0, 0.0)],
+    };
+    let bytes = payload.encode();
+    assert_eq!(bytes.len(), 24);
+    assert_eq!(bytes[..4], (-120.5f32).to_le_bytes());
+    assert_eq!(bytes[20..], 0.0f32.to_le_bytes());
+    assert_eq!(StatePayload::decode(&bytes), Some(payload.clone()));
+    println!("{} bytes round trip: {:?}", bytes.len(), payload.positions);
+
+    let snapshot = SoccerStateSnapshot::decode(ProtocolVersion::V1, &bytes).unwrap();
+    assert_eq!(snapshot.pucks, payload.positions[..2]);
+    assert_eq!(snapshot.balls, payload.positions[2..]);
+    println!("read by a v1 client as pucks {:?}", snapshot.pucks);
+
+    let odd = StatePayload {
+        positions: vec![(f32::NAN, f32::INFINITY), (f32::MIN_POSITIVE, -0.0)],
+    };
+    let back = StatePayload::decode(&odd.encode()).unwrap();
+    let bits = |payload: &StatePayload| -> Vec<(u32, u32)> {
+        return payload
+            .positions
+            .iter()
+            .map(|(x, y)| (x.to_bits(), y.to_bits()))
+            .collect();
+    };
+    assert_eq!(bits(&back), bits(&odd));
+    println!("bit for bit: {:?}", back.positions);
+
+    for bad in [&bytes[..0], &bytes[..7], &bytes[..23]] {
+        assert_eq!(StatePayload::decode(bad), None);
+    }
+    println!("partial pairs refused");
+}
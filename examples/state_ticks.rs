@@ -0,0 +1,74 @@
+mod common;
+
+use common::{join_ready, raw_connect_as, raw_next, raw_welcome};
+use futures::SinkExt;
+use rust_backend::message::{
+    MessageType, ProtocolVersion, SoccerStateSnapshot, SubscribeMessage, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18154";
+const FRAMES: usize = 20;
+
+// Every v4 State opens with the game's tick and the server time. Streamed
+// at the tick rate, the tick goes up from each frame to the next, never
+// past the game's own count, and the time never goes back, so a client can
+// order frames and spot a dropped one.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let tick_rate = config.tick_rate as u8;
+    let server = Server::new(config);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let protocol = ProtocolVersion::V4;
+    let mut alice = raw_connect_as(ADDR, "name=alice&mode=soccer", protocol).await;
+    let ready = WsMessage {
+        msg_type: MessageType::Ready,
+        payload: vec![],
+    };
+    alice.send(Message::Binary(ready.to_bytes())).await.unwrap();
+    let (welcome, _bob_link) = join_ready(ADDR, "name=bob&mode=soccer").await;
+    assert_eq!(raw_welcome(&mut alice).await.game_id, welcome.game_id);
+    // the queue keeps a Ready for the match but has no stream to subscribe
+    // to, so this waits for the Welcome
+    let subscribe = WsMessage::from_payload(
+        MessageType::Subscribe,
+        &SubscribeMessage {
+            state_rate_hz: tick_rate,
+        },
+    );
+    alice
+        .send(Message::Binary(subscribe.to_bytes()))
+        .await
+        .unwrap();
+
+    let mut frames = vec![];
+    while frames.len() < FRAMES {
+        let state = raw_next(&mut alice, &[MessageType::State]).await;
+        let state = SoccerStateSnapshot::decode(protocol, &state.payload);
+        frames.push(state.expect("State didn't decode as v4"));
+    }
+    for pair in frames.windows(2) {
+        assert!(pair[1].tick > pair[0].tick, "a tick repeated or went back");
+        assert!(pair[1].server_time_us >= pair[0].server_time_us);
+    }
+    let game = games.read().await[&(welcome.game_id as usize)].clone();
+    assert!(frames[FRAMES - 1].tick as u64 <= game.read().await.tick());
+    println!(
+        "{} States, ticks {} to {}, {}us apart end to end",
+        FRAMES,
+        frames[0].tick,
+        frames[FRAMES - 1].tick,
+        frames[FRAMES - 1].server_time_us - frames[0].server_time_us
+    );
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, raw_welcome, RawStream, RALLY};
+use futures::SinkExt;
+use rust_backend::game::GameLogic;
+use rust_backend::message::{
+    ChatMessage, ChatScope, ErrorCode, ErrorMessage, MessageType, MuteMessage, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18149";
+const DOUBLES: u8 = 8;
+
+// Two a side: even slots play left, odd slots right.
+struct Doubles;
+
+impl GameLogic for Doubles {
+    fn game_type(&self) -> u8 {
+        return DOUBLES;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![DOUBLES];
+    }
+    fn max_players(&self) -> usize {
+        return 4;
+    }
+    fn team_of(&self, player: usize) -> Option<u8> {
+        return Some((player % 2) as u8);
+    }
+}
+
+async fn send(stream: &mut RawStream, message: WsMessage) {
+    stream
+        .send(Message::Binary(message.to_bytes()))
+        .await
+        .unwrap();
+}
+
+// Sends a chat line and waits for it to come back, so it has gone out to
+// everyone before the next one. What the sender hears on the way goes in
+// its heard.
+async fn say(stream: &mut RawStream, heard: &mut Vec<String>, scope: ChatScope, text: &str) {
+    let chat = ChatMessage {
+        scope,
+        from: 0,
+        text: text.to_string(),
+    };
+    send(stream, WsMessage::from_payload(MessageType::Chat, &chat)).await;
+    while heard.last().map_or(true, |line| line != text) {
+        heard.push(hear(stream).await);
+    }
+}
+
+async fn hear(stream: &mut RawStream) -> String {
+    let chat = raw_next(stream, &[MessageType::Chat]).await;
+    return chat.decode::<ChatMessage>().unwrap().text;
+}
+
+// Adds the lines a player heard, up to and including "end".
+async fn hear_out(stream: &mut RawStream, heard: &mut Vec<String>) {
+    while heard.last().map_or(true, |line| line != "end") {
+        heard.push(hear(stream).await);
+    }
+}
+
+async fn refused(stream: &mut RawStream, message: WsMessage) -> ErrorMessage {
+    send(stream, message).await;
+    let error = raw_next(stream, &[MessageType::Error]).await;
+    return error.decode::<ErrorMessage>().unwrap();
+}
+
+fn mute(slot: u8) -> WsMessage {
+    return WsMessage::from_payload(MessageType::Mute, &MuteMessage { slot });
+}
+
+// The routing matrix in a game of two teams of two: team chat reaches the
+// sender's side only, all chat reaches everyone but those who muted the
+// sender. Muting yourself or an empty slot is refused, and so is team chat
+// in a game type without teams.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    server.register_mode("doubles", DOUBLES, |_state, _practice| {
+        return Box::new(Doubles) as Box<dyn GameLogic>;
+    });
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut first = raw_connect(ADDR, "name=p0&mode=8&practice=1").await;
+    let welcome = raw_welcome(&mut first).await;
+    let mut players = vec![first];
+    for slot in 1..4 {
+        let query = format!(
+            "name=p{}&mode=8&game={}&game_token={}",
+            slot, welcome.game_id, welcome.game_token
+        );
+        let mut stream = raw_connect(ADDR, &query).await;
+        assert_eq!(raw_welcome(&mut stream).await.player_index, slot);
+        players.push(stream);
+    }
+
+    let mut heard = vec![vec![]; 4];
+    say(&mut players[0], &mut heard[0], ChatScope::Team, "left plan").await;
+    say(
+        &mut players[1],
+        &mut heard[1],
+        ChatScope::Team,
+        "right plan",
+    )
+    .await;
+    send(&mut players[2], mute(0)).await;
+    // answered after the Mute, so the mute is in place
+    let echo = WsMessage {
+        msg_type: MessageType::Echo,
+        payload: b"muted".to_vec(),
+    };
+    send(&mut players[2], echo).await;
+    loop {
+        let frame = raw_next(&mut players[2], &[MessageType::Chat, MessageType::Echo]).await;
+        match frame.decode::<ChatMessage>() {
+            Some(chat) if frame.msg_type as u8 == MessageType::Chat as u8 => {
+                heard[2].push(chat.text)
+            }
+            _ => break,
+        }
+    }
+    say(&mut players[0], &mut heard[0], ChatScope::All, "hello all").await;
+    say(&mut players[3], &mut heard[3], ChatScope::All, "end").await;
+
+    let expected: [&[&str]; 4] = [
+        &["left plan", "hello all", "end"],
+        &["right plan", "hello all", "end"],
+        &["left plan", "end"],
+        &["right plan", "hello all", "end"],
+    ];
+    for (slot, expected) in expected.iter().enumerate() {
+        hear_out(&mut players[slot], &mut heard[slot]).await;
+        assert_eq!(heard[slot], *expected, "slot {}", slot);
+    }
+    println!("team lines stayed on their side, and p2 muted p0's all chat");
+
+    let error = refused(&mut players[1], mute(1)).await;
+    assert_eq!(error.code, ErrorCode::InvalidParams);
+    println!("muting yourself: {}", error.message);
+    let error = refused(&mut players[1], mute(9)).await;
+    assert_eq!(error.code, ErrorCode::InvalidParams);
+    println!("muting slot 9: {}", error.message);
+
+    let mut solo = raw_connect(ADDR, "name=solo&mode=rally&practice=1").await;
+    raw_welcome(&mut solo).await;
+    let chat = ChatMessage {
+        scope: ChatScope::Team,
+        from: 0,
+        text: "anyone?".to_string(),
+    };
+    let error = refused(&mut solo, WsMessage::from_payload(MessageType::Chat, &chat)).await;
+    assert_eq!(error.code, ErrorCode::NoTeams);
+    println!("team chat without teams: {}", error.message);
+}
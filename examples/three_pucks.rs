@@ -0,0 +1,79 @@
+use rust_backend::game::{
+    CommandLink, Game, GameLogic, GamePhase, SoccerGame, SoccerGameConfig, SoccerPhase,
+};
+use rust_backend::message::{ErrorCode, ErrorMessage, SoccerMoveMessage};
+use rust_backend::stats::PlayerId;
+use tokio::sync::mpsc;
+
+const PUCKS: usize = 3;
+
+// A game of three pucks a side: six pucks in all, each player's numbered 0
+// to 2, and the State carries all six. A move to target 2 drives the
+// player's third puck; one to target 3 is refused with InvalidParams rather
+// than reaching the other side's pucks, and a reset puts all six back.
+fn main() {
+    let config = SoccerGameConfig {
+        pucks_per_team: PUCKS,
+        ..SoccerGameConfig::default()
+    };
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(SoccerGame::with_config(config), players);
+    game.phase = GamePhase::Playing;
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    soccer.phase = SoccerPhase::Play;
+    assert_eq!(soccer.pucks.len(), 2 * PUCKS);
+    for player in 0..2 {
+        assert_eq!(soccer.team(player).unwrap().pucks.len(), PUCKS);
+        assert!(soccer.team_puck(player, PUCKS as u8 - 1).is_some());
+        assert_eq!(soccer.team_puck(player, PUCKS as u8), None);
+    }
+    assert_eq!(soccer.puck_owners().len(), 2 * PUCKS);
+    let start = soccer.body_states();
+    let bytes = soccer.to_bytes().len();
+    println!("{} pucks, {} bytes of State", soccer.pucks.len(), bytes);
+
+    let (replies, mut refused) = mpsc::unbounded_channel();
+    let link = CommandLink {
+        commands: game.commands(),
+        replies,
+    };
+    for target in [2, 3] {
+        let shot = SoccerMoveMessage {
+            vx: 0.0,
+            vy: 300.0,
+            target,
+            angular: 0.0,
+            seq: 0,
+        };
+        assert!(link.send_move(1, &shot));
+    }
+    game.update();
+    let soccer = game.downcast::<SoccerGame>().unwrap();
+    let third = soccer.team_puck(1, 2).unwrap();
+    assert!(
+        soccer.bodies[third].linvel().y > 0.0,
+        "target 2 didn't move"
+    );
+    let moved = soccer
+        .pucks
+        .iter()
+        .filter(|puck| soccer.bodies[**puck].linvel().y > 100.0)
+        .count();
+    assert_eq!(moved, 1, "a refused move reached another puck");
+    let refusal = refused.try_recv().expect("target 3 was taken");
+    let error = refusal.decode::<ErrorMessage>().unwrap();
+    assert_eq!(error.code, ErrorCode::InvalidParams);
+    assert!(refused.try_recv().is_err());
+    println!(
+        "bob's third puck moved; target 3 refused: {}",
+        error.message
+    );
+
+    let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+    soccer.reset();
+    assert_eq!(soccer.body_states(), start);
+    println!("reset put all {} back", soccer.pucks.len());
+}
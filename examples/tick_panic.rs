@@ -0,0 +1,112 @@
+mod common;
+
+use common::{drifted, get, join_ready, Drift, RALLY};
+use rust_backend::game::{GameLogic, PauseConfig};
+use rust_backend::server::{Server, ServerConfig};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::time::{sleep, Duration, Instant};
+
+const ADDR: &str = "127.0.0.1:18152";
+const HEALTH: &str = "127.0.0.1:18153";
+const FUSE: u8 = 8;
+
+// set to make the next Fuse update panic, once
+static ARMED: AtomicBool = AtomicBool::new(false);
+
+// A game that does nothing until ARMED, then panics in the middle of a tick.
+struct Fuse;
+
+impl GameLogic for Fuse {
+    fn game_type(&self) -> u8 {
+        return FUSE;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        if ARMED.swap(false, Ordering::Relaxed) {
+            panic!("fuse blew");
+        }
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![FUSE];
+    }
+}
+
+async fn restarts() -> u64 {
+    let metrics = get(HEALTH, "/metrics").await;
+    let line = metrics
+        .lines()
+        .find(|line| line.starts_with("asyncws_tick_restarts_total "))
+        .expect("no restart counter");
+    return line.split_whitespace().nth(1).unwrap().parse().unwrap();
+}
+
+// One game's update panicking takes the tick loop down with it; the
+// supervisor counts the restart and starts a fresh loop, and every game,
+// the one that panicked included, carries on ticking.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: Some(HEALTH.to_string()),
+        pause: PauseConfig {
+            resume_countdown: Duration::ZERO,
+            ..PauseConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Drift::default()) as Box<dyn GameLogic>;
+    });
+    server.register_mode("fuse", FUSE, |_state, _practice| {
+        return Box::new(Fuse) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let ((alice, _alice_link), (_, _carol_link), (bob, _bob_link), (_, _dave_link)) = tokio::join!(
+        join_ready(ADDR, "name=alice&mode=rally"),
+        join_ready(ADDR, "name=carol&mode=rally"),
+        join_ready(ADDR, "name=bob&mode=fuse"),
+        join_ready(ADDR, "name=dave&mode=fuse")
+    );
+    let game = games.read().await[&(alice.game_id as usize)].clone();
+    sleep(Duration::from_millis(300)).await;
+    assert!(drifted(&game).await > 0.0, "alice's game never started");
+    assert_eq!(restarts().await, 0);
+
+    ARMED.store(true, Ordering::Relaxed);
+    let started = Instant::now();
+    while restarts().await == 0 {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "the panic was never counted"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert!(
+        !ARMED.load(Ordering::Relaxed),
+        "the fuse game was never updated"
+    );
+    println!("the tick loop panicked and was restarted");
+
+    let before = drifted(&game).await;
+    let started = Instant::now();
+    while drifted(&game).await == before {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "alice's game stopped after the restart"
+        );
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert!(games.read().await.contains_key(&(bob.game_id as usize)));
+    assert_eq!(restarts().await, 1);
+    println!("alice's game moves on and bob's game is still there");
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::{connect, error, get, next, options, rally, welcome, Events, RALLY};
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::message::{
+    ErrorCode, GameSteppedMessage, MessageType, SetTickRateMessage, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18111";
+const HTTP_ADDR: &str = "127.0.0.1:18112";
+// the same, with debug_ticks off
+const LOCKED_ADDR: &str = "127.0.0.1:18113";
+const ADMIN_TOKEN: &str = "letmein";
+
+// An admin can slow one game down, freeze it and step it by hand, and put
+// it back to normal without it racing to catch up; its players are told
+// each time and GET /games shows the tick mode. The whole tick loop can be
+// slowed the same way. Anyone else is refused, and so is everyone on a
+// server with debug_ticks off.
+#[tokio::main]
+async fn main() {
+    for (addr, http_addr, debug_ticks) in
+        [(ADDR, Some(HTTP_ADDR), true), (LOCKED_ADDR, None, false)]
+    {
+        let config = ServerConfig {
+            addr: addr.to_string(),
+            http_addr: http_addr.map(str::to_string),
+            health_addr: None,
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+            debug_ticks,
+            ..ServerConfig::default()
+        };
+        let server = Server::new(config);
+        server.register_mode("rally", RALLY, rally);
+        tokio::spawn(server.run());
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    let (player, mut player_events, _) = join(ADDR, "player", None).await;
+    player.set_tick_rate(None, 5.0);
+    assert_eq!(error(&mut player_events).await, ErrorCode::Unauthorized);
+    println!("non-admin SetTickRate refused");
+
+    let (admin, mut events, game_id) = join(ADDR, "admin", Some(ADMIN_TOKEN)).await;
+    admin.set_tick_rate(Some(game_id), 5.0);
+    assert_eq!(rate(&mut events).await.hz, 5.0);
+    assert!(announcement(&mut events).await.contains("slowed"));
+    let ticked = ticks_over(game_id, Duration::from_secs(1)).await;
+    assert!((3..=7).contains(&ticked), "{} ticks at 5hz", ticked);
+    assert_eq!(tick_mode(game_id).await, "slowed");
+    println!(
+        "game {} slowed to 5hz: {} ticks in a second",
+        game_id, ticked
+    );
+
+    admin.step_game(game_id, 0);
+    let frozen = stepped(&mut events).await;
+    assert!(announcement(&mut events).await.contains("by hand"));
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(tick(game_id).await, frozen);
+    assert_eq!(tick_mode(game_id).await, "manual");
+    // the reply comes before the steps are taken, not after
+    admin.step_game(game_id, 30);
+    assert_eq!(stepped(&mut events).await, frozen + 30);
+    assert!(tick(game_id).await < frozen + 30);
+    sleep(Duration::from_secs(1)).await;
+    assert_eq!(tick(game_id).await, frozen + 30);
+    println!("frozen at tick {}, stepped to {}", frozen, frozen + 30);
+
+    // normal ticking picks up from now, not from when stepping began
+    admin.set_tick_rate(Some(game_id), 0.0);
+    assert_eq!(rate(&mut events).await.hz, 60.0);
+    assert!(announcement(&mut events).await.contains("normal"));
+    let ticked = ticks_over(game_id, Duration::from_millis(500)).await;
+    assert!((20..=40).contains(&ticked), "{} ticks in 500ms", ticked);
+    assert_eq!(tick_mode(game_id).await, "normal");
+    println!("back to normal: {} ticks in 500ms", ticked);
+
+    admin.set_tick_rate(None, 10.0);
+    assert_eq!(rate(&mut events).await.hz, 10.0);
+    let ticked = ticks_over(game_id, Duration::from_secs(1)).await;
+    assert!((7..=13).contains(&ticked), "{} ticks at 10hz", ticked);
+    assert_eq!(tick_mode(game_id).await, "slowed");
+    admin.set_tick_rate(None, 0.0);
+    assert_eq!(rate(&mut events).await.hz, 60.0);
+    println!("whole loop at 10hz: {} ticks in a second", ticked);
+
+    let (locked, mut locked_events, locked_game) =
+        join(LOCKED_ADDR, "admin", Some(ADMIN_TOKEN)).await;
+    locked.step_game(locked_game, 1);
+    assert_eq!(error(&mut locked_events).await, ErrorCode::DebugDisabled);
+    println!("StepGame refused with debug_ticks off");
+}
+
+// A practice game of its own for name, and its id.
+async fn join(addr: &str, name: &str, auth_token: Option<&str>) -> (GameClient, Events, u32) {
+    let options = ClientOptions {
+        practice: true,
+        auth_token: auth_token.map(str::to_string),
+        ..options()
+    };
+    let (client, mut events) = connect(addr, name, options).await;
+    let game_id = welcome(&mut events).await.game_id;
+    return (client, events, game_id);
+}
+
+// The payload of the first message of msg_type.
+async fn reply(events: &mut Events, msg_type: MessageType) -> WsMessage {
+    return next(events, |event| match event {
+        ClientEvent::Message(got, payload) if got as u8 == msg_type as u8 => Some(WsMessage {
+            msg_type: got,
+            payload,
+        }),
+        _ => None,
+    })
+    .await;
+}
+
+async fn rate(events: &mut Events) -> SetTickRateMessage {
+    let reply = reply(events, MessageType::SetTickRate).await;
+    return reply.decode().unwrap();
+}
+
+async fn stepped(events: &mut Events) -> u64 {
+    let reply = reply(events, MessageType::StepGame).await;
+    return reply.decode::<GameSteppedMessage>().unwrap().tick;
+}
+
+async fn announcement(events: &mut Events) -> String {
+    return next(events, |event| match event {
+        ClientEvent::Announcement(text) => Some(text),
+        _ => None,
+    })
+    .await;
+}
+
+// The game's entry in GET /games.
+async fn described(game_id: u32) -> serde_json::Value {
+    let listing: serde_json::Value = serde_json::from_str(&get(HTTP_ADDR, "/games").await).unwrap();
+    return listing["games"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .cloned()
+        .find(|game| game["id"] == game_id)
+        .expect("game not listed");
+}
+
+async fn tick(game_id: u32) -> u64 {
+    return described(game_id).await["tick"].as_u64().unwrap();
+}
+
+async fn tick_mode(game_id: u32) -> String {
+    return described(game_id).await["tick_mode"]
+        .as_str()
+        .unwrap()
+        .to_string();
+}
+
+async fn ticks_over(game_id: u32, period: Duration) -> u64 {
+    let start = tick(game_id).await;
+    sleep(period).await;
+    return tick(game_id).await - start;
+}
@@ -0,0 +1,145 @@
+use rust_backend::game::{Game, GameLogic, GamePhase};
+use rust_backend::stats::PlayerId;
+use rust_backend::timers::{GameTimerId, GameTimers, TimerChange};
+
+// After it goes off, ECHO asks for ECHO_BACK two ticks later.
+const ECHO: GameTimerId = GameTimerId(10);
+const ECHO_BACK: GameTimerId = GameTimerId(11);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Seen {
+    Timer(u32),
+    Update,
+}
+
+// Notes every timer and update in the order they reach it.
+#[derive(Default)]
+struct Alarms {
+    seen: Vec<Seen>,
+    changes: Vec<TimerChange>,
+}
+
+impl GameLogic for Alarms {
+    fn game_type(&self) -> u8 {
+        return 0;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {
+        self.seen.push(Seen::Update);
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+    fn on_timer(&mut self, id: GameTimerId) {
+        self.seen.push(Seen::Timer(id.0));
+        if id == ECHO {
+            self.changes.push(TimerChange::After {
+                ticks: 2,
+                id: ECHO_BACK,
+            });
+        }
+    }
+    fn take_timer_changes(&mut self) -> Vec<TimerChange> {
+        return std::mem::take(&mut self.changes);
+    }
+}
+
+// What the logic saw since the last call.
+fn seen(game: &mut Game) -> Vec<Seen> {
+    return std::mem::take(&mut game.downcast_mut::<Alarms>().unwrap().seen);
+}
+
+// Timers due on the same update reach the logic in id order, whatever order
+// they were set in, and before that update's step. A cancelled timer never goes off,
+// one set again goes off at its new tick only, and one set for the tick
+// already reached goes off at the start of the next update. Logic can set
+// timers from a timer, and a new mode starts with none. What a save keeps
+// is the ticks each timer has left.
+fn main() {
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(Alarms::default(), players);
+    game.phase = GamePhase::Playing;
+    game.update();
+    seen(&mut game);
+
+    let now = game.tick();
+    for id in [3, 1, 7, 2] {
+        game.timers.schedule_at(now + 2, GameTimerId(id));
+    }
+    game.update();
+    game.update();
+    assert_eq!(seen(&mut game), [Seen::Update, Seen::Update]);
+    game.update();
+    let expected = [
+        Seen::Timer(1),
+        Seen::Timer(2),
+        Seen::Timer(3),
+        Seen::Timer(7),
+        Seen::Update,
+    ];
+    assert_eq!(seen(&mut game), expected);
+    assert!(game.timers.is_empty());
+    println!("due together, in id order, before the step: {:?}", expected);
+
+    let now = game.tick();
+    game.timers.schedule_at(now + 1, GameTimerId(5));
+    assert!(game.timers.cancel(GameTimerId(5)));
+    assert!(!game.timers.cancel(GameTimerId(5)));
+    game.timers.schedule_at(now + 1, GameTimerId(6));
+    game.timers.schedule_at(now + 3, GameTimerId(6));
+    for _ in 0..3 {
+        game.update();
+    }
+    assert_eq!(seen(&mut game), [Seen::Update; 3]);
+    game.update();
+    assert_eq!(seen(&mut game), [Seen::Timer(6), Seen::Update]);
+    println!("cancelled never went off, moved went off once at its new tick");
+
+    let now = game.tick();
+    game.timers.schedule_at(now, GameTimerId(4));
+    game.update();
+    assert_eq!(seen(&mut game), [Seen::Timer(4), Seen::Update]);
+    println!(
+        "set for tick {} went off at the start of the next update",
+        now
+    );
+
+    game.timers.schedule_at(game.tick(), ECHO);
+    game.update();
+    assert_eq!(game.timers.due_at(ECHO_BACK), Some(game.tick() + 1));
+    game.update();
+    game.update();
+    let expected = [
+        Seen::Timer(10),
+        Seen::Update,
+        Seen::Update,
+        Seen::Timer(11),
+        Seen::Update,
+    ];
+    assert_eq!(seen(&mut game), expected);
+    println!("a timer set from a timer: {:?}", expected);
+
+    let now = game.tick();
+    game.timers.schedule_at(now + 5, GameTimerId(2));
+    game.timers.schedule_at(now + 9, GameTimerId(1));
+    let kept = game.timers.remaining(now);
+    assert_eq!(kept, [(GameTimerId(1), 9), (GameTimerId(2), 5)]);
+    let mut resumed = GameTimers::default();
+    for (id, left) in &kept {
+        resumed.schedule_at(*left, *id);
+    }
+    assert_eq!(resumed.remaining(0), kept);
+    println!("saved as ticks left: {:?}", kept);
+
+    game.replace_logic(Box::new(Alarms::default()));
+    assert!(game.timers.is_empty());
+    println!("a new mode starts without the old one's timers");
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{rally, RALLY};
+use rust_backend::server::{Server, ServerConfig, TlsFiles};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18165";
+
+// A server given a certificate and key doesn't serve plain ws in place of
+// the wss:// they ask for: run returns without ever listening.
+#[tokio::main]
+async fn main() {
+    let dir = std::env::temp_dir();
+    let (cert, key) = (
+        dir.join("tls-refused-cert.pem"),
+        dir.join("tls-refused-key.pem"),
+    );
+    std::fs::write(&cert, "cert").unwrap();
+    std::fs::write(&key, "key").unwrap();
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        tls: Some(TlsFiles { cert, key }),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    timeout(Duration::from_secs(2), server.run())
+        .await
+        .expect("started serving with tls set");
+    assert!(TcpStream::connect(ADDR).await.is_err());
+    println!("refused to start with tls set, and nothing listening");
+}
@@ -0,0 +1,65 @@
+use rust_backend::message::MessageType;
+use rust_backend::traffic::{ConnectionTraffic, Direction, TRAFFIC};
+use std::hint::black_box;
+use std::thread;
+use std::time::Instant;
+
+const CONNECTIONS: u64 = 4;
+const MESSAGES: u64 = 1_000_000;
+
+// Accounting runs on every frame in and out, so it has to stay at a few
+// relaxed atomic adds, and those must still add up with every connection
+// recording at once. Each connection's task records a million State frames
+// out and as many SoccerMoves in; each connection, and the server-wide
+// totals across all of them, have to come to exactly what was sent. The
+// time per message is printed for comparison, not checked.
+fn main() {
+    let state = u8::from(MessageType::State);
+    let moves = u8::from(MessageType::SoccerMove);
+    let started = Instant::now();
+    let summaries: Vec<_> = thread::scope(|scope| {
+        let tasks: Vec<_> = (0..CONNECTIONS)
+            .map(|_| {
+                scope.spawn(move || {
+                    let traffic = ConnectionTraffic::new();
+                    for i in 0..MESSAGES {
+                        let odd = (i & 1) as usize;
+                        traffic.record(Direction::Out, black_box(state), black_box(64 + odd));
+                        traffic.record(Direction::In, black_box(moves), black_box(12));
+                    }
+                    return traffic.summary();
+                })
+            })
+            .collect();
+        return tasks.into_iter().map(|task| task.join().unwrap()).collect();
+    });
+    let per_message = started.elapsed() / (CONNECTIONS * MESSAGES * 2) as u32;
+
+    for summary in &summaries {
+        assert_eq!(summary.messages_out, MESSAGES);
+        assert_eq!(summary.bytes_out, MESSAGES * 64 + MESSAGES / 2);
+        assert_eq!(summary.messages_in, MESSAGES);
+        assert_eq!(summary.bytes_in, MESSAGES * 12);
+    }
+    let total = CONNECTIONS * MESSAGES;
+    let out = TRAFFIC.by_type(Direction::Out);
+    assert!(
+        matches!(out[..], [(MessageType::State, messages, bytes)]
+            if messages == total && bytes == total * 64 + total / 2),
+        "server-wide totals out {:?}",
+        out
+    );
+    let inbound = TRAFFIC.by_type(Direction::In);
+    assert!(
+        matches!(inbound[..], [(MessageType::SoccerMove, messages, bytes)]
+            if messages == total && bytes == total * 12),
+        "server-wide totals in {:?}",
+        inbound
+    );
+    println!(
+        "{} messages over {} connections, none lost, {:?} per message",
+        total * 2,
+        CONNECTIONS,
+        per_message
+    );
+}
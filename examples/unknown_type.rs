@@ -0,0 +1,73 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, RALLY};
+use futures::SinkExt;
+use rust_backend::message::{
+    ErrorCode, ErrorMessage, MessageType, ProtocolVersion, UnsupportedTypeMessage, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18118";
+const ANSWERS: &[MessageType] = &[MessageType::Echo, MessageType::Error];
+
+// A client a protocol ahead of the server sends a type byte the server has
+// never heard of. It gets an Error naming the newest type and protocol
+// version the server speaks, whatever the connection negotiated, readable as a plain ErrorMessage too, and its
+// connection carries on: in a game and while still waiting for a match.
+#[tokio::main]
+async fn main() {
+    let last = MessageType::LAST as u8;
+    assert!(MessageType::try_from(last).is_ok());
+    assert!(MessageType::try_from(last + 1).is_err());
+    assert_eq!(WsMessage::unknown_type(&[last + 1, 1, 2]), Some(last + 1));
+    assert_eq!(WsMessage::unknown_type(&[last]), None);
+    assert_eq!(WsMessage::unknown_type(&[]), None);
+    assert!(WsMessage::from_bytes(&[last + 1]).is_none());
+    for msg_type in 0..=last {
+        let parsed = WsMessage::from_bytes(&[msg_type, 9]).unwrap();
+        assert_eq!(parsed.msg_type as u8, msg_type);
+        assert_eq!(parsed.payload, [9]);
+    }
+    println!("type bytes 0..={} parse, {} doesn't", last, last + 1);
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    for (name, query) in [("bob", "&practice=1"), ("carol", "")] {
+        let query = format!("name={}&mode=rally{}", name, query);
+        let mut stream = raw_connect(ADDR, &query).await;
+        for msg_type in [last + 1, u8::MAX] {
+            let frame = vec![msg_type, 1, 2, 3];
+            stream.send(Message::Binary(frame)).await.unwrap();
+            let reply = raw_next(&mut stream, ANSWERS).await;
+            let error = reply.decode::<UnsupportedTypeMessage>().unwrap();
+            assert_eq!(error.code, ErrorCode::UnsupportedMessageType);
+            assert_eq!(error.msg_type, msg_type);
+            assert_eq!(error.max_type, last);
+            assert_eq!(error.protocol, ProtocolVersion::MAX as u8);
+            let plain = reply.decode::<ErrorMessage>().unwrap();
+            assert_eq!(plain.code, ErrorCode::UnsupportedMessageType);
+            println!("{}: type {} refused: {}", name, msg_type, plain.message);
+        }
+
+        let echo = WsMessage {
+            msg_type: MessageType::Echo,
+            payload: b"still here".to_vec(),
+        };
+        stream.send(Message::Binary(echo.to_bytes())).await.unwrap();
+        let reply = raw_next(&mut stream, ANSWERS).await;
+        assert!(matches!(reply.msg_type, MessageType::Echo));
+        assert!(reply.payload.starts_with(b"still here"));
+        println!("{}: connection still open", name);
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{StepClock, Steps};
+use rust_backend::game::{Game, GamePhase, MAX_UPDATE_MS};
+use rust_backend::stats::PlayerId;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// get_and_update_duration against a clock that steps both ways: back a
+// second reads as no time passing instead of underflowing, ten seconds ahead
+// is capped at MAX_UPDATE_MS, and the steps after either carry on at the
+// normal rate. A game stepped across the forward jump moves its logic by
+// the cap, not by ten seconds.
+fn main() {
+    let start = Instant::now() + Duration::from_secs(10);
+    let clock = Arc::new(StepClock::new(start));
+    let players = vec![
+        (PlayerId::Guest("alice".to_string()), "alice".to_string()),
+        (PlayerId::Guest("bob".to_string()), "bob".to_string()),
+    ];
+    let mut game = Game::new(Steps::default(), players);
+    game.set_clock(clock.clone());
+
+    let mut now = start;
+    let mut durations = vec![];
+    for step_ms in [16i64, 0, 10_000, 16, -1000, 16] {
+        let step = Duration::from_millis(step_ms.unsigned_abs());
+        now = match step_ms < 0 {
+            true => now - step,
+            false => now + step,
+        };
+        clock.set(now);
+        durations.push(game.get_and_update_duration());
+    }
+    assert_eq!(durations, [16, 0, MAX_UPDATE_MS, 16, 0, 16]);
+    println!("durations across both jumps: {:?}", durations);
+
+    game.pause_config.resume_countdown = Duration::ZERO;
+    game.mark_ready(0);
+    game.mark_ready(1);
+    while game.phase != GamePhase::Playing {
+        game.update();
+    }
+    for step in [16, 60_000, 16] {
+        clock.advance(Duration::from_millis(step));
+        game.update();
+    }
+    let elapsed = &game.downcast::<Steps>().unwrap().elapsed;
+    // the step that started play, then the three above
+    assert_eq!(elapsed, &[0.0, 16.0, MAX_UPDATE_MS as f64, 16.0]);
+    println!("steps across a minute's jump: {:?}", elapsed);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{connect, get, options, rally, welcome, RALLY};
+use futures::{SinkExt, Stream, StreamExt};
+use rust_backend::client::{ClientEvent, ClientOptions};
+use rust_backend::message::{
+    HelloMessage, MessageType, ProtocolVersion, VersionMessage, WsMessage,
+};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+const ADDR: &str = "127.0.0.1:18125";
+const HEALTH_ADDR: &str = "127.0.0.1:18126";
+const HTTP_ADDR: &str = "127.0.0.1:18166";
+
+// A Version request is answered before the Hello on a v8 connection, in a
+// game through the SDK, over HTTP on the health port and at the head of the
+// debug port's game listing, the same every time: this crate's version and
+// a protocol range that takes in whatever the connection negotiated.
+// Welcome carries the range too.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: Some(HTTP_ADDR.to_string()),
+        health_addr: Some(HEALTH_ADDR.to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let version = before_hello().await;
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+    assert!(version.supports(ProtocolVersion::V8 as u8));
+    assert_eq!(version, VersionMessage::current());
+    println!("before Hello: {:?}", version);
+
+    let options = ClientOptions {
+        practice: true,
+        ..options()
+    };
+    let (sdk, mut events) = connect(ADDR, "sdk", options).await;
+    let welcome = welcome(&mut events).await;
+    let negotiated = welcome.connection.protocol;
+    assert!((welcome.min_protocol..=welcome.max_protocol).contains(&negotiated));
+    assert!(sdk.version());
+    let answer = common::next(&mut events, |event| match event {
+        ClientEvent::Version(version) => Some(version),
+        _ => None,
+    })
+    .await;
+    assert_eq!(answer, version);
+    assert!(answer.supports(negotiated));
+    println!(
+        "in game on protocol {}, Welcome and Version both say {}-{}",
+        negotiated, welcome.min_protocol, welcome.max_protocol
+    );
+
+    let body = get(HEALTH_ADDR, "/version").await;
+    let served: VersionMessage = serde_json::from_str(&body).unwrap();
+    assert_eq!(served, version);
+    println!("GET /version: {}", body);
+
+    let listing: serde_json::Value = serde_json::from_str(&get(HTTP_ADDR, "/games").await).unwrap();
+    let listed: VersionMessage = serde_json::from_value(listing["version"].clone()).unwrap();
+    assert_eq!(listed, version);
+    assert_eq!(listing["games"].as_array().unwrap().len(), 1);
+    println!("GET /games lists the same version with its games");
+}
+
+// Asks for the version on a fresh v8 connection before saying Hello, then
+// checks the Hello is still taken.
+async fn before_hello() -> VersionMessage {
+    let url = format!("ws://{}/?name=curious&mode=rally&practice=1", ADDR);
+    let mut request = url.into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static("asyncws.v8"),
+    );
+    let (mut stream, _) = connect_async(request).await.unwrap();
+    let ask = WsMessage {
+        msg_type: MessageType::Version,
+        payload: vec![],
+    };
+    stream.send(Message::Binary(ask.to_bytes())).await.unwrap();
+    let version = next(&mut stream, MessageType::Version)
+        .await
+        .and_then(|reply| reply.decode::<VersionMessage>())
+        .expect("no Version before Hello");
+    let hello = HelloMessage {
+        protocol: ProtocolVersion::V8 as u8,
+        build: "version-example".to_string(),
+        state_version: ProtocolVersion::V8 as u8,
+        format: 0,
+        byte_order: 0,
+        role: 0,
+    };
+    let hello = WsMessage::from_payload(MessageType::Hello, &hello);
+    stream
+        .send(Message::Binary(hello.to_bytes()))
+        .await
+        .unwrap();
+    next(&mut stream, MessageType::Hello)
+        .await
+        .expect("the Hello after a Version was refused");
+    return version;
+}
+
+// The next binary frame, if it is of msg_type.
+async fn next<S>(stream: &mut S, msg_type: MessageType) -> Option<WsMessage>
+where
+    S: Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let read = async {
+        while let Some(Ok(message)) = stream.next().await {
+            if let Message::Binary(data) = message {
+                return WsMessage::from_bytes(&data);
+            }
+        }
+        return None;
+    };
+    let message = timeout(Duration::from_secs(5), read).await.ok().flatten();
+    return message.filter(|message| message.msg_type as u8 == msg_type as u8);
+}
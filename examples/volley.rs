@@ -0,0 +1,50 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{GameLogic, SoccerGame, SoccerGameConfig, VOLLEY_GAME_TYPE};
+
+// A puck dropped above the floor of a volley arena falls and comes to rest
+// on it, the floor taking the bounce out of it, and moves can't throw it up
+// faster than max_shot_vy.
+fn main() {
+    let config = SoccerGameConfig {
+        pucks_per_team: 1,
+        ..SoccerGameConfig::default()
+    }
+    .volley();
+    let floor = -config.height / 2.0;
+    let radius = config.puck_radius;
+    let max_vy = config.max_shot_vy.unwrap();
+    let mut game = SoccerGame::with_config(config);
+    assert_eq!(game.game_type(), VOLLEY_GAME_TYPE);
+
+    let puck = game.teams[0].pucks[0];
+    game.bodies[puck].set_translation(vector![-150.0, 100.0], true);
+    game.bodies[puck].set_linvel(vector![0.0, 0.0], true);
+    // where it was a second before the end
+    let mut settled_at = 0.0;
+    for tick in 1..=60 * 5 {
+        game.update(1000.0 / 60.0);
+        if tick == 60 * 4 {
+            settled_at = game.bodies[puck].translation().y;
+        }
+    }
+    let body = &game.bodies[puck];
+    let resting = floor + radius;
+    assert!(
+        (body.translation().y - resting).abs() < 1.0,
+        "puck at {:?}, floor contact at {}",
+        body.translation(),
+        resting
+    );
+    // the solver leaves a resting body a step's worth of gravity in its
+    // velocity, so rest is judged by where it is
+    assert!(
+        (body.translation().y - settled_at).abs() < 0.1,
+        "still moving at {:?}",
+        body.linvel()
+    );
+    println!("puck at rest on the floor at y = {}", body.translation().y);
+
+    game.apply_move(puck, 0.0, 5000.0, 0.0);
+    assert_eq!(game.bodies[puck].linvel().y, max_vy);
+    println!("throw capped at {} up", max_vy);
+}
@@ -0,0 +1,45 @@
+use rust_backend::game::{Game, GamePhase, SoccerGame, SoccerGameConfig, WARM_UP_PHASE_CODE};
+use rust_backend::stats::PlayerId;
+
+// A lone player in a soccer game can move their pucks while waiting for an
+// opponent, and the board goes back to kickoff once the opponent arrives.
+// A match waiting for someone who dropped out is not warm-up.
+fn main() {
+    let alice = PlayerId::Guest("alice".to_string());
+    let mut game = Game::new(
+        SoccerGame::with_config(SoccerGameConfig::default()),
+        vec![(alice, "alice".to_string())],
+    );
+    game.warm_up = true;
+    game.update();
+    assert!(game.is_warming_up());
+    assert_eq!(game.describe().details["phase"], "warm_up");
+    {
+        let soccer = game.downcast_mut::<SoccerGame>().unwrap();
+        assert_eq!(soccer.phase_code(), WARM_UP_PHASE_CODE);
+    }
+    for _ in 0..30 {
+        game.update();
+    }
+    let history = game.history().count();
+
+    game.add_player(PlayerId::Guest("bob".to_string()), "bob".to_string());
+    assert!(!game.is_warming_up());
+    let description = game.describe();
+    assert_eq!(description.details["phase"], "play");
+    assert_eq!(description.details["teams"][0]["score"], 0);
+    assert_eq!(description.details["teams"][1]["score"], 0);
+    // only bob's join was recorded
+    assert_eq!(game.history().count(), history + 1);
+    println!("warm-up ended at kickoff");
+
+    game.phase = GamePhase::WaitingForPlayers {
+        missing: 1,
+        deadline_tick: None,
+    };
+    for _ in 0..30 {
+        game.update();
+    }
+    assert!(!game.is_warming_up());
+    println!("no warm-up while waiting for bob to come back");
+}
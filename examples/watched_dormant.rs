@@ -0,0 +1,75 @@
+mod common;
+
+use common::{drifted, join_ready, Drift, RALLY};
+use rust_backend::client::ClientOptions;
+use rust_backend::game::{GameLogic, PauseConfig, ShortHanded};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18162";
+const ADMIN_TOKEN: &str = "overlay";
+
+// A firehose spectator counts as someone watching: with both players gone
+// the game keeps moving for as long as the overlay is subscribed, and only
+// goes dormant once it leaves too. The match plays on short-handed, so it
+// isn't waiting on the players instead.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        pause: PauseConfig {
+            resume_countdown: Duration::ZERO,
+            short_handed: ShortHanded::Play,
+            ..PauseConfig::default()
+        },
+        dormant_timeout: Duration::from_secs(60),
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, |_state, _practice| {
+        return Box::new(Drift::default()) as Box<dyn GameLogic>;
+    });
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let ((alice, alice_link), (_, bob_link)) = tokio::join!(
+        join_ready(ADDR, "name=alice&mode=rally"),
+        join_ready(ADDR, "name=bob&mode=rally")
+    );
+    let game = games.read().await.get(&(alice.game_id as usize)).cloned();
+    let game = game.expect("no game");
+    sleep(Duration::from_millis(500)).await;
+    assert!(drifted(&game).await > 0.0, "the game never started");
+
+    let options = ClientOptions {
+        firehose: true,
+        auth_token: Some(ADMIN_TOKEN.to_string()),
+        ..common::options()
+    };
+    let overlay = common::connect(ADDR, "overlay", options).await.0;
+    overlay.subscribe_all(5);
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(game.read().await.live_connections(), 3);
+
+    alice_link.abort();
+    bob_link.abort();
+    sleep(Duration::from_millis(200)).await;
+    assert!(
+        game.read().await.dormant_for().is_none(),
+        "dormant while watched"
+    );
+    let before = drifted(&game).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(drifted(&game).await > before, "a watched game stopped");
+    println!("players gone, still moving for the overlay");
+
+    overlay.close();
+    sleep(Duration::from_millis(300)).await;
+    assert_eq!(game.read().await.live_connections(), 0);
+    assert!(game.read().await.dormant_for().is_some());
+    println!("dormant once the overlay left");
+}
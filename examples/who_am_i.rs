@@ -0,0 +1,76 @@
+mod common;
+
+use common::{connect, next, options, rally, welcome, RALLY};
+use rust_backend::client::ClientEvent;
+use rust_backend::message::{encode_payload, WhoAmIMessage};
+use rust_backend::server::{forwarded_peer, Server, ServerConfig};
+use std::net::{IpAddr, SocketAddr};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18090";
+
+// The wire layout of WhoAmI, the X-Forwarded-For handling behind it, and a
+// client seeing the same answer in its Welcome and on request.
+#[tokio::main]
+async fn main() {
+    let who = WhoAmIMessage {
+        address: "10.1.2.3".to_string(),
+        port: 443,
+        protocol: 2,
+        server_version: "1.0".to_string(),
+    };
+    let mut golden = vec![8, 0, 0, 0, 0, 0, 0, 0];
+    golden.extend_from_slice(b"10.1.2.3");
+    golden.extend_from_slice(&[0xbb, 0x01, 2, 3, 0, 0, 0, 0, 0, 0, 0]);
+    golden.extend_from_slice(b"1.0");
+    assert_eq!(encode_payload(&who).unwrap(), golden);
+    println!("WhoAmI layout matches");
+
+    let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+    let via_proxy = SocketAddr::new(proxy, 40000);
+    let stranger: SocketAddr = "203.0.113.9:5555".parse().unwrap();
+    let trusted = [proxy];
+    let forwarded = forwarded_peer(via_proxy, Some("1.2.3.4, 198.51.100.7"), &trusted);
+    assert_eq!(forwarded, "198.51.100.7:0".parse().unwrap());
+    // the proxy's own hop is skipped, a forged header from anyone else isn't
+    // believed, and garbage falls back to the socket
+    let chained = forwarded_peer(via_proxy, Some("198.51.100.7, 10.0.0.1"), &trusted);
+    assert_eq!(chained, forwarded);
+    assert_eq!(
+        forwarded_peer(stranger, Some("198.51.100.7"), &trusted),
+        stranger
+    );
+    assert_eq!(
+        forwarded_peer(via_proxy, Some("nonsense"), &trusted),
+        via_proxy
+    );
+    assert_eq!(forwarded_peer(via_proxy, None, &trusted), via_proxy);
+    println!("X-Forwarded-For is only taken from trusted proxies");
+
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_game_type(RALLY, rally);
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let (client, mut events) = connect(ADDR, "alice", options()).await;
+    // a Welcome needs a match to be placed in
+    let _bob = connect(ADDR, "bob", options()).await;
+    let welcomed = welcome(&mut events).await.connection;
+    client.who_am_i();
+    let asked = next(&mut events, |event| match event {
+        ClientEvent::WhoAmI(who) => Some(who),
+        _ => None,
+    })
+    .await;
+    assert_eq!(welcomed, asked);
+    assert_eq!(asked.address, "127.0.0.1");
+    assert_ne!(asked.port, 0);
+    assert_eq!(asked.server_version, env!("CARGO_PKG_VERSION"));
+    println!("connected as {}:{}", asked.address, asked.port);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::{rally, raw_connect, raw_next, raw_welcome, RALLY};
+use rust_backend::message::{ErrorCode, ErrorMessage, MessageType};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18142";
+
+// Asking for a rally game by id while wanting soccer is refused with
+// WrongGameType at join time, and the game is left as it was; asking for it
+// as rally gets in.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::new(config);
+    server.register_mode("rally", RALLY, rally);
+    let games = server.games();
+    tokio::spawn(server.run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut alice = raw_connect(ADDR, "name=alice&mode=rally&practice=1").await;
+    let welcome = raw_welcome(&mut alice).await;
+    let target = format!("game={}&game_token={}", welcome.game_id, welcome.game_token);
+
+    // no mode asks for soccer
+    let mut bob = raw_connect(ADDR, &format!("name=bob&{}", target)).await;
+    let refused = raw_next(&mut bob, &[MessageType::Welcome, MessageType::Error]).await;
+    let error = refused
+        .decode::<ErrorMessage>()
+        .expect("bob got into a rally game as soccer");
+    assert_eq!(error.code, ErrorCode::WrongGameType);
+    let game = games.read().await[&(welcome.game_id as usize)].clone();
+    assert_eq!(game.read().await.players.len(), 1);
+    println!(
+        "soccer into rally game {}: {}",
+        welcome.game_id, error.message
+    );
+
+    let mut carol = raw_connect(ADDR, &format!("name=carol&mode=rally&{}", target)).await;
+    assert_eq!(raw_welcome(&mut carol).await.game_id, welcome.game_id);
+    assert_eq!(game.read().await.players.len(), 2);
+    println!("carol asked for rally and joined");
+}
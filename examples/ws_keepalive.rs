@@ -0,0 +1,65 @@
+use rust_backend::server::{Server, ServerConfig};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18082";
+const PING_INTERVAL: Duration = Duration::from_millis(200);
+
+// A raw TCP client that completes the upgrade and then never answers a
+// websocket ping, like a peer whose network vanished. The server must ping
+// it, give up once the next ping is due, and close with 4001 long before the
+// idle timeout would.
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        queue_timeout: None,
+        ws_ping_interval: Some(PING_INTERVAL),
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut stream = TcpStream::connect(ADDR).await.unwrap();
+    let request = format!(
+        "GET /?name=silent HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        ADDR
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = vec![];
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).await.unwrap();
+        response.push(byte[0]);
+    }
+    assert!(response.starts_with(b"HTTP/1.1 101"));
+
+    let started = Instant::now();
+    let mut pings = 0;
+    let code = timeout(Duration::from_secs(5), async {
+        loop {
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).await.ok()?;
+            let mut payload = vec![0u8; (header[1] & 0x7f) as usize];
+            stream.read_exact(&mut payload).await.ok()?;
+            match header[0] & 0x0f {
+                0x9 => pings += 1,
+                0x8 => return Some(u16::from_be_bytes([payload[0], payload[1]])),
+                _ => (),
+            }
+        }
+    })
+    .await
+    .expect("never closed");
+    assert_eq!(code, Some(4001));
+    assert!(pings >= 1, "closed without pinging");
+    println!(
+        "closed with 4001 after {} unanswered ping(s), {:?}",
+        pings,
+        started.elapsed()
+    );
+}
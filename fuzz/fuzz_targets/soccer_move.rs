@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_backend::message::{MessageType, SoccerMoveMessage, WsMessage};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = SoccerMoveMessage::from_bytes(data);
+
+    // same path the server takes: frame first, then decode the payload
+    if let Some(message) = WsMessage::from_bytes(data) {
+        if let MessageType::SoccerMove = message.msg_type {
+            let _ = SoccerMoveMessage::from_bytes(&message.payload);
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_backend::message::{MessageType, WsMessage};
+
+fuzz_target!(|data: &[u8]| {
+    match WsMessage::from_bytes(data) {
+        Some(message) => {
+            // a parsed message must round trip byte for byte
+            assert_eq!(message.to_bytes(), data);
+            assert!(MessageType::try_from(data[0]).is_ok());
+        }
+        None => {
+            assert!(data.is_empty() || MessageType::try_from(data[0]).is_err());
+        }
+    }
+});
@@ -0,0 +1,247 @@
+use crate::game::{Formation, GameLogic, SoccerGame, SoccerGameConfig};
+use crate::message::{GameParams, StatePayload};
+use crate::timers::GameTimerId;
+use futures::FutureExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::task::JoinHandle;
+
+// Divergences found by every audit since start, for /metrics.
+pub static AUDIT_DIVERGENCES: AtomicU64 = AtomicU64::new(0);
+
+// One thing done to a game's logic. The audit replays them on its shadow in
+// the order the live game saw them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditInput {
+    // a logic update with this many ms elapsed
+    Update(f64),
+    Move {
+        player: usize,
+        target: u8,
+        vx: f32,
+        vy: f32,
+        angular: f32,
+    },
+    Boost {
+        player: usize,
+        target: u8,
+        dx: f32,
+        dy: f32,
+    },
+    Params(GameParams),
+    Formation {
+        player: usize,
+        formation: Formation,
+    },
+    WarmUp(bool),
+    AddBot(usize),
+    Freeze {
+        player: usize,
+        frozen: bool,
+    },
+    Timer(GameTimerId),
+    Retune(Box<SoccerGameConfig>),
+}
+
+// Where a shadow first stopped matching its game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divergence {
+    // the game tick of the check that caught it
+    pub tick: u64,
+    // the first body, in snapshot order, whose position differs; None when
+    // every position matches but the rest of the bytes don't
+    pub body: Option<usize>,
+}
+
+type Checked = (SoccerGame, Option<Divergence>);
+
+// A second copy of a soccer game fed the same inputs at the same ticks. Every
+// `every` ticks the inputs since the last check are replayed on the shadow,
+// off the tick loop when there is a runtime to do it on, and its State bytes
+// compared with what the game showed at that tick. A divergence means the
+// simulation isn't deterministic, which breaks replays from a seed. Only the
+// first one is reported; after that the audit stops checking.
+pub struct DeterminismAudit {
+    every: u64,
+    // inputs since the last check began
+    inputs: Vec<AuditInput>,
+    since_check: u64,
+    // out on the blocking pool while a check runs
+    shadow: Option<SoccerGame>,
+    running: Option<JoinHandle<Checked>>,
+    divergence: Option<Divergence>,
+}
+
+impl DeterminismAudit {
+    pub fn new(shadow: SoccerGame, every: u64) -> Self {
+        return DeterminismAudit {
+            every: every.max(1),
+            inputs: vec![],
+            since_check: 0,
+            shadow: Some(shadow),
+            running: None,
+            divergence: None,
+        };
+    }
+
+    pub fn every(&self) -> u64 {
+        return self.every;
+    }
+
+    pub fn divergence(&self) -> Option<Divergence> {
+        return self.divergence;
+    }
+
+    // Whether checks are still being made: not after a divergence, or once
+    // a check died and took the shadow with it.
+    pub fn is_checking(&self) -> bool {
+        return self.divergence.is_none() && (self.shadow.is_some() || self.running.is_some());
+    }
+
+    pub fn record(&mut self, input: AuditInput) {
+        if self.is_checking() {
+            self.inputs.push(input);
+        }
+    }
+
+    // The shadow as of the last check, to poke at by hand; None while a
+    // check has it.
+    pub fn shadow_mut(&mut self) -> Option<&mut SoccerGame> {
+        return self.shadow.as_mut();
+    }
+
+    // Called once per game tick, after the update. Collects a finished
+    // check and starts the next one when it is due. Returns the divergence
+    // the first time one is found.
+    pub fn tick(&mut self, tick: u64, live: &SoccerGame) -> Option<Divergence> {
+        if !self.is_checking() {
+            return None;
+        }
+        if let Some(checked) = self
+            .running
+            .as_mut()
+            .and_then(|running| running.now_or_never())
+        {
+            self.running = None;
+            match checked {
+                Ok((shadow, divergence)) => {
+                    self.shadow = Some(shadow);
+                    self.divergence = divergence;
+                }
+                Err(e) => log::warn!("Determinism audit check failed, auditing stops: {}", e),
+            }
+            if let Some(divergence) = self.divergence {
+                return Some(self.found(divergence));
+            }
+        }
+        self.since_check += 1;
+        if self.since_check < self.every {
+            return None;
+        }
+        // None while the last check is still going; this one waits for it
+        let mut shadow = self.shadow.take()?;
+        self.since_check = 0;
+        let inputs = std::mem::take(&mut self.inputs);
+        let expected = live.to_bytes();
+        let run = move || {
+            let divergence = check(&mut shadow, inputs, tick, &expected);
+            return (shadow, divergence);
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => self.running = Some(runtime.spawn_blocking(run)),
+            Err(_) => {
+                let (shadow, divergence) = run();
+                self.shadow = Some(shadow);
+                self.divergence = divergence;
+                return divergence.map(|divergence| self.found(divergence));
+            }
+        }
+        return None;
+    }
+
+    fn found(&self, divergence: Divergence) -> Divergence {
+        AUDIT_DIVERGENCES.fetch_add(1, Ordering::Relaxed);
+        return divergence;
+    }
+}
+
+// Replays inputs on shadow and compares the result with the live bytes.
+fn check(
+    shadow: &mut SoccerGame,
+    inputs: Vec<AuditInput>,
+    tick: u64,
+    expected: &[u8],
+) -> Option<Divergence> {
+    for input in inputs {
+        replay(shadow, input);
+    }
+    let got = shadow.to_bytes();
+    if got == expected {
+        return None;
+    }
+    let body = match (StatePayload::decode(&got), StatePayload::decode(expected)) {
+        // bitwise, so a NaN on both sides still matches; a body only one
+        // side has differs where the shorter list ends
+        (Some(got), Some(expected)) => {
+            (0..got.positions.len().max(expected.positions.len())).find(|&body| {
+                let bits = |positions: &[(f32, f32)]| {
+                    positions.get(body).map(|(x, y)| (x.to_bits(), y.to_bits()))
+                };
+                bits(&got.positions) != bits(&expected.positions)
+            })
+        }
+        _ => None,
+    };
+    return Some(Divergence { tick, body });
+}
+
+fn replay(shadow: &mut SoccerGame, input: AuditInput) {
+    match input {
+        AuditInput::Update(elapsed) => {
+            // what the live game does with these comes from the live game
+            let logic: &mut dyn GameLogic = shadow;
+            logic.update(elapsed);
+            logic.take_events();
+            logic.take_goals();
+            logic.take_history();
+            logic.take_timer_changes();
+            logic.take_messages();
+        }
+        AuditInput::Move {
+            player,
+            target,
+            vx,
+            vy,
+            angular,
+        } => {
+            if let Some(puck) = shadow.team_puck(player, target) {
+                shadow.apply_move(puck, vx, vy, angular);
+            }
+        }
+        AuditInput::Boost {
+            player,
+            target,
+            dx,
+            dy,
+        } => {
+            let _ = shadow.boost(player, target, dx, dy);
+        }
+        AuditInput::Params(params) => shadow.apply_params(&params),
+        AuditInput::Formation { player, formation } => {
+            let _ = shadow.set_formation(player, formation);
+        }
+        AuditInput::WarmUp(on) => {
+            shadow.warm_up(on);
+        }
+        AuditInput::AddBot(player) => shadow.add_bot(player),
+        AuditInput::Timer(id) => {
+            let logic: &mut dyn GameLogic = shadow;
+            logic.on_timer(id);
+            logic.take_timer_changes();
+            logic.take_messages();
+        }
+        AuditInput::Freeze { player, frozen } => {
+            shadow.freeze_pucks(player, frozen);
+        }
+        AuditInput::Retune(config) => shadow.retune(&config),
+    }
+}
@@ -0,0 +1,85 @@
+use reqwest::header::ACCEPT;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Who the auth service says a token belongs to. The subject is what the
+// player is keyed by; the name is what they are shown as, whatever
+// ?name= the connection asked for.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AuthIdentity {
+    #[serde(rename = "sub")]
+    pub subject: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    // no token, or the service answered 401/403
+    Rejected,
+    // the service couldn't be reached or gave an answer that isn't an
+    // identity; the token may well be fine
+    Unavailable(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            AuthError::Rejected => write!(f, "rejected"),
+            AuthError::Unavailable(why) => write!(f, "auth service unavailable: {}", why),
+        };
+    }
+}
+
+// One client for every check, so connections to the service are reused.
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    return CLIENT.get_or_init(reqwest::Client::new);
+}
+
+// Asks the service at url who the token belongs to: a GET carrying the
+// token in header, the way the client sent it, answered with
+// {"sub": "...", "name": "..."}.
+pub async fn validate(
+    url: &str,
+    header: &str,
+    scheme: Option<&str>,
+    token: &str,
+    limit: Duration,
+) -> Result<AuthIdentity, AuthError> {
+    let value = match scheme {
+        Some(scheme) => format!("{} {}", scheme, token),
+        None => token.to_string(),
+    };
+    let response = client()
+        .get(url)
+        .header(header, value)
+        .header(ACCEPT, "application/json")
+        .timeout(limit)
+        .send()
+        .await
+        .map_err(unavailable)?;
+    return match response.status() {
+        StatusCode::OK => match response.json::<AuthIdentity>().await {
+            Ok(identity) if identity.subject.is_empty() => {
+                Err(AuthError::Unavailable("empty subject".to_string()))
+            }
+            Ok(identity) => Ok(identity),
+            Err(e) => Err(AuthError::Unavailable(format!("bad identity: {}", e))),
+        },
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(AuthError::Rejected),
+        status => Err(AuthError::Unavailable(format!(
+            "status {}",
+            status.as_u16()
+        ))),
+    };
+}
+
+fn unavailable(e: reqwest::Error) -> AuthError {
+    if e.is_timeout() {
+        return AuthError::Unavailable("timed out".to_string());
+    }
+    return AuthError::Unavailable(e.to_string());
+}
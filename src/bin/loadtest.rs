@@ -0,0 +1,433 @@
+// Plays many simulated players against a running server through the client
+// SDK, then prints how it held up:
+//
+//   cargo run --release --bin loadtest -- --url ws://127.0.0.1:8080/ \
+//       --players 200 --secs 60 --move_hz 10 --reconnect_secs 20
+//
+// Each player connects, is matched like anyone else, sends random moves at
+// move_hz to its own pucks and keeps the SDK's heartbeat going. With
+// reconnect_secs it drops its socket about that often and comes back under
+// the same name with the session token of its last Welcome, so it reclaims
+// its slot while the match is still on. Latency is from sending a move to
+// the first State acking it, for moves that weren't refused; State is
+// polled at the SDK's default rate, so that is part of it. Exits 1 when
+// more than max_errors error replies and unexpected closes came back, or
+// the 99th percentile is over max_p99_ms, so a small run works as a CI
+// smoke test.
+use futures::StreamExt;
+use rust_backend::client::{ClientEvent, ClientOptions, GameClient};
+use rust_backend::game::GameRng;
+use rust_backend::message::{CloseReason, ErrorCode, ErrorMessage, MessageType, WsMessage};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::time::{interval, sleep, sleep_until, Duration, Instant, MissedTickBehavior};
+
+// fastest component of a random move, well under any shot cap
+const MOVE_SPEED: f32 = 400.0;
+// between a failed connect or a dropped socket and the next try
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+// refusals a move gets in a healthy game, while the match is paused for a
+// dropped player or frozen after a goal; tallied but not errors
+const EXPECTED: [ErrorCode; 3] = [
+    ErrorCode::GamePaused,
+    ErrorCode::InputFrozen,
+    ErrorCode::NotYourTurn,
+];
+
+#[derive(Debug, Clone)]
+struct Options {
+    url: String,
+    // game type to ask for; None plays soccer
+    mode: Option<u8>,
+    players: usize,
+    secs: u64,
+    move_hz: f32,
+    // None keeps every connection for the whole run
+    reconnect_secs: Option<u64>,
+    max_errors: u64,
+    max_p99_ms: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        return Options {
+            url: "ws://127.0.0.1:8080/".to_string(),
+            mode: None,
+            players: 100,
+            secs: 30,
+            move_hz: 10.0,
+            reconnect_secs: None,
+            max_errors: 0,
+            max_p99_ms: None,
+        };
+    }
+}
+
+impl Options {
+    // --key value or --key=value, for each field above.
+    fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None => return Err(format!("unexpected argument '{}'", arg)),
+            };
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (flag.to_string(), None),
+            };
+            let value = match value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(format!("--{} needs a value", key)),
+            };
+            let invalid = || format!("--{} can't be '{}'", key, value);
+            match key.as_str() {
+                "url" => options.url = value.clone(),
+                "mode" => options.mode = Some(value.parse().map_err(|_| invalid())?),
+                "players" => options.players = value.parse().map_err(|_| invalid())?,
+                "secs" => options.secs = value.parse().map_err(|_| invalid())?,
+                "move_hz" => {
+                    options.move_hz = value.parse().map_err(|_| invalid())?;
+                    if !(options.move_hz > 0.0) {
+                        return Err("--move_hz must be positive".to_string());
+                    }
+                }
+                "reconnect_secs" => {
+                    let secs: u64 = value.parse().map_err(|_| invalid())?;
+                    options.reconnect_secs = Some(secs).filter(|secs| *secs > 0);
+                }
+                "max_errors" => options.max_errors = value.parse().map_err(|_| invalid())?,
+                "max_p99_ms" => options.max_p99_ms = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(format!("unknown flag --{}", key)),
+            }
+        }
+        return Ok(options);
+    }
+}
+
+// What one player saw; merged across all of them for the summary.
+#[derive(Debug, Default)]
+struct Tally {
+    connects: u64,
+    connect_failures: u64,
+    // Welcomes, so a reclaimed slot counts again
+    placed: u64,
+    // Welcomes back into the game the last connection was in
+    reclaimed: u64,
+    // drops on the reconnect schedule
+    dropped: u64,
+    games_over: u64,
+    moves: u64,
+    latencies: Vec<Duration>,
+    pongs: u64,
+    // error replies by code, those in EXPECTED apart, and closes by reason
+    errors: BTreeMap<String, u64>,
+    refusals: BTreeMap<String, u64>,
+    closes: BTreeMap<String, u64>,
+}
+
+impl Tally {
+    fn merge(&mut self, other: Tally) {
+        self.connects += other.connects;
+        self.connect_failures += other.connect_failures;
+        self.placed += other.placed;
+        self.reclaimed += other.reclaimed;
+        self.dropped += other.dropped;
+        self.games_over += other.games_over;
+        self.moves += other.moves;
+        self.latencies.extend(other.latencies);
+        self.pongs += other.pongs;
+        for (code, count) in other.errors {
+            *self.errors.entry(code).or_default() += count;
+        }
+        for (code, count) in other.refusals {
+            *self.refusals.entry(code).or_default() += count;
+        }
+        for (reason, count) in other.closes {
+            *self.closes.entry(reason).or_default() += count;
+        }
+    }
+
+    // Error replies, failed connects and closes the server chose for a
+    // reason other than the game being over.
+    fn error_count(&self) -> u64 {
+        let closes: u64 = self
+            .closes
+            .iter()
+            .filter(|(reason, _)| *reason != &format!("{:?}", CloseReason::NormalLobbyExit))
+            .map(|(_, count)| count)
+            .sum();
+        return self.errors.values().sum::<u64>() + self.connect_failures + closes;
+    }
+
+    fn percentile(&self, share: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let at = ((self.latencies.len() - 1) as f64 * share).round() as usize;
+        return Some(self.latencies[at]);
+    }
+}
+
+// How a connection's stint ended.
+enum Stint {
+    // the run is over
+    Done,
+    // to come back after RETRY_DELAY
+    Again,
+}
+
+struct Player {
+    index: usize,
+    options: Arc<Options>,
+    deadline: Instant,
+    rng: GameRng,
+    // carried across connections: a reclaimed slot keeps its last acked seq
+    // on the server, so starting over would look acked straight away
+    seq: u32,
+    // game id and session token of the last Welcome, to reclaim the slot
+    // with after a drop
+    slot: Option<(u32, String)>,
+    tally: Tally,
+}
+
+impl Player {
+    async fn run(mut self) -> Tally {
+        // spread the connects so the server isn't hit by all of them at once
+        let stagger = Duration::from_millis(self.index as u64 * 5 % 1000);
+        sleep(stagger).await;
+        while Instant::now() < self.deadline {
+            if let Stint::Done = self.stint().await {
+                break;
+            }
+            sleep(RETRY_DELAY).await;
+        }
+        return self.tally;
+    }
+
+    fn until_drop(&mut self) -> Instant {
+        let secs = match self.options.reconnect_secs {
+            Some(secs) => secs as f32,
+            None => return self.deadline,
+        };
+        // somewhere in the half either side of it, so drops don't line up
+        let after = Duration::from_secs_f32(secs * (0.5 + self.rng.next_f32()));
+        return self.deadline.min(Instant::now() + after);
+    }
+
+    // One connection, from connect until it is dropped or closed.
+    async fn stint(&mut self) -> Stint {
+        let options = ClientOptions {
+            mode: self.options.mode,
+            reconnect: false,
+            session: self.slot.as_ref().map(|(_, session)| session.clone()),
+            ..ClientOptions::default()
+        };
+        let name = format!("load{}", self.index);
+        let client = match GameClient::connect(&self.options.url, &name, options).await {
+            Ok(client) => client,
+            Err(e) => {
+                log::debug!("{} failed to connect: {}", name, e);
+                self.tally.connect_failures += 1;
+                return Stint::Again;
+            }
+        };
+        self.tally.connects += 1;
+        let mut events = Box::pin(client.subscribe_events());
+        let mut states = Box::pin(client.subscribe_state());
+        let mut moves = interval(Duration::from_secs_f32(1.0 / self.options.move_hz));
+        moves.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let until = self.until_drop();
+        let mut targets: Vec<u8> = vec![];
+        let mut pending: BTreeMap<u32, Instant> = BTreeMap::new();
+        loop {
+            tokio::select! {
+                _ = sleep_until(until) => {
+                    if until >= self.deadline {
+                        return Stint::Done;
+                    }
+                    self.tally.dropped += 1;
+                    client.close();
+                    return Stint::Again;
+                }
+                _ = moves.tick(), if !targets.is_empty() => {
+                    self.seq += 1;
+                    let target = targets[self.rng.next_u64() as usize % targets.len()];
+                    let vx = (self.rng.next_f32() * 2.0 - 1.0) * MOVE_SPEED;
+                    let vy = (self.rng.next_f32() * 2.0 - 1.0) * MOVE_SPEED;
+                    if client.send_move_sequenced(target, vx, vy, 0.0, self.seq) {
+                        pending.insert(self.seq, Instant::now());
+                        self.tally.moves += 1;
+                    }
+                }
+                Some(state) = states.next() => {
+                    let later = pending.split_off(&(state.ack_seq + 1));
+                    for sent in std::mem::replace(&mut pending, later).into_values() {
+                        self.tally.latencies.push(sent.elapsed());
+                    }
+                }
+                event = events.next() => match event {
+                    Some(ClientEvent::Welcome(welcome)) => {
+                        self.tally.placed += 1;
+                        if self.slot.as_ref().map_or(false, |(game_id, _)| *game_id == welcome.game_id) {
+                            self.tally.reclaimed += 1;
+                        }
+                        self.slot = Some((welcome.game_id, welcome.session_token.clone()));
+                        client.ready();
+                        targets = welcome
+                            .pucks
+                            .iter()
+                            .filter(|puck| puck.player == welcome.player_index)
+                            .map(|puck| puck.target)
+                            .collect();
+                        // a server too old to say whose pucks are whose
+                        if targets.is_empty() {
+                            targets.push(0);
+                        }
+                    }
+                    Some(ClientEvent::Pong { .. }) => self.tally.pongs += 1,
+                    Some(ClientEvent::GameOver(_)) => self.tally.games_over += 1,
+                    Some(ClientEvent::Message(MessageType::Error, payload)) => {
+                        let error = WsMessage {
+                            msg_type: MessageType::Error,
+                            payload,
+                        }
+                        .decode::<ErrorMessage>();
+                        let tally = match &error {
+                            Some(error) if EXPECTED.contains(&error.code) => {
+                                // refused in the order sent, and never acked
+                                // but by the next move that isn't, so the
+                                // oldest one still out is this one
+                                pending.pop_first();
+                                &mut self.tally.refusals
+                            }
+                            _ => &mut self.tally.errors,
+                        };
+                        let code = error.map_or("Undecodable".to_string(), |error| {
+                            return format!("{:?}", error.code);
+                        });
+                        *tally.entry(code).or_default() += 1;
+                    }
+                    Some(ClientEvent::Closed(reason)) => {
+                        let reason = reason.map_or("Unknown".to_string(), |reason| {
+                            return format!("{:?}", reason);
+                        });
+                        *self.tally.closes.entry(reason).or_default() += 1;
+                        return Stint::Again;
+                    }
+                    Some(ClientEvent::Disconnected) | None => return Stint::Again,
+                    Some(_) => (),
+                },
+            }
+        }
+    }
+}
+
+fn print_summary(options: &Options, tally: &Tally, took: Duration) {
+    let ms = |latency: Option<Duration>| {
+        return latency.map_or("-".to_string(), |latency| {
+            return format!("{:.1} ms", latency.as_secs_f64() * 1000.0);
+        });
+    };
+    let acked = tally.latencies.len() as u64;
+    let rows = [
+        ("players", options.players.to_string()),
+        ("ran for", format!("{:.1} s", took.as_secs_f64())),
+        ("connects", tally.connects.to_string()),
+        ("failed connects", tally.connect_failures.to_string()),
+        ("placed in a game", tally.placed.to_string()),
+        ("slots reclaimed", tally.reclaimed.to_string()),
+        ("scheduled drops", tally.dropped.to_string()),
+        ("games over", tally.games_over.to_string()),
+        ("moves sent", tally.moves.to_string()),
+        ("moves acked", acked.to_string()),
+        ("moves never acked", (tally.moves - acked).to_string()),
+        ("move to state p50", ms(tally.percentile(0.50))),
+        ("move to state p90", ms(tally.percentile(0.90))),
+        ("move to state p99", ms(tally.percentile(0.99))),
+        ("move to state max", ms(tally.latencies.last().copied())),
+        ("pongs", tally.pongs.to_string()),
+    ];
+    println!();
+    for (name, value) in rows {
+        println!("{:<20} {:>12}", name, value);
+    }
+    for (code, count) in &tally.errors {
+        println!("{:<20} {:>12}", format!("error {}", code), count);
+    }
+    for (code, count) in &tally.refusals {
+        println!("{:<20} {:>12}", format!("refused {}", code), count);
+    }
+    for (reason, count) in &tally.closes {
+        println!("{:<20} {:>12}", format!("closed {}", reason), count);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => Arc::new(options),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("error")).init();
+    println!(
+        "{} players against {} for {}s",
+        options.players, options.url, options.secs
+    );
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(options.secs);
+    let players: Vec<_> = (0..options.players)
+        .map(|index| {
+            let player = Player {
+                index,
+                options: Arc::clone(&options),
+                deadline,
+                rng: GameRng::new(index as u64),
+                seq: 0,
+                slot: None,
+                tally: Tally::default(),
+            };
+            return tokio::spawn(player.run());
+        })
+        .collect();
+    let mut tally = Tally::default();
+    for player in players {
+        match player.await {
+            Ok(own) => tally.merge(own),
+            Err(e) => {
+                eprintln!("a player task failed: {}", e);
+                *tally.errors.entry("Panicked".to_string()).or_default() += 1;
+            }
+        }
+    }
+    tally.latencies.sort();
+    print_summary(&options, &tally, started.elapsed());
+
+    let mut failed = vec![];
+    if tally.error_count() > options.max_errors {
+        failed.push(format!(
+            "{} errors, more than the {} allowed",
+            tally.error_count(),
+            options.max_errors
+        ));
+    }
+    if let (Some(p99), Some(max)) = (tally.percentile(0.99), options.max_p99_ms) {
+        if p99 > Duration::from_millis(max) {
+            failed.push(format!("p99 of {:?}, over {} ms", p99, max));
+        }
+    }
+    if tally.moves > 0 && tally.latencies.is_empty() {
+        failed.push("no move was ever acked".to_string());
+    }
+    if !failed.is_empty() {
+        println!();
+        for failure in &failed {
+            println!("FAILED: {}", failure);
+        }
+        std::process::exit(1);
+    }
+}
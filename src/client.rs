@@ -0,0 +1,1224 @@
+use crate::message::{
+    AnnounceMessage, AnnouncementMessage, AuditGameMessage, BoostMessage, ByteOrder,
+    ChallengeMessage, ChallengeReceivedMessage, ChallengeReplyMessage, ChallengeResultMessage,
+    ChatMessage, ChatScope, CloseReason, DegradeLevel, EchoReply, EventMessage, EventsSinceMessage,
+    EventsSinceResponse, FreezeOpponentMessage, GameOverMessage, GameParams, GoalScoredMessage,
+    HandicapConfig, HelloMessage, LeaveGameMessage, LobbyUpdateMessage, LockstepInputMessage,
+    LockstepMove, LockstepStepMessage, MessageType, ModeChangedMessage, MultiStateMessage,
+    MuteMessage, OpponentDisconnectedMessage, PartialState, PlayerJoinedMessage, PlayerLeftMessage,
+    PowerUpMessage, ProtocolVersion, QueueStatusMessage, QueuedMessage, ReplayBurstMessage, Role,
+    RosterMessage, RosterRequest, ServerInfoMessage, SetFormationMessage, SetGameParamsMessage,
+    SetInterestMessage, SetTickRateMessage, SnapshotHeader, SoccerMoveMessage, SoccerStateSnapshot,
+    SoccerTunedMessage, StatsResponse, StepGameMessage, SubscribeAllMessage, SubscribeMessage,
+    TimeSyncRequest, TimeSyncResponse, TuneSoccerMessage, TurnMessage, VersionMessage,
+    WelcomeMessage, WhoAmIMessage, WsMessage,
+};
+use crate::serializer::StateFormat;
+use futures::{SinkExt, Stream, StreamExt};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, Duration};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::tungstenite::Error;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type ClientStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    pub game: Option<usize>,
+    // from the game's Welcome or lobby entry; with it a stale game id is
+    // refused instead of joining whatever game has that id now
+    pub game_token: Option<String>,
+    // game_type to play; the server assumes soccer when None and refuses a
+    // `game` of any other type
+    pub mode: Option<u8>,
+    // solo free play instead of matchmaking; `game` is ignored
+    pub practice: bool,
+    // wait without being matched, for a challenge to send or answer
+    pub lobby: bool,
+    // watch every game with subscribe_all instead of playing; needs the
+    // admin token as auth_token
+    pub firehose: bool,
+    // byte order of the numbers in State and SoccerMove payloads
+    pub byte_order: ByteOrder,
+    // fixed-point soccer State, about half the bytes of the f32 layout;
+    // the server refuses it below protocol v5
+    pub quantized: bool,
+    pub auth_token: Option<String>,
+    // session token from an earlier Welcome, to take that slot back on the
+    // first connect too; after that the SDK keeps the latest one itself
+    pub session: Option<String>,
+    // sent in front of auth_token, as the server's auth_scheme expects;
+    // None sends the token alone
+    pub auth_scheme: Option<String>,
+    pub heartbeat_interval: Duration,
+    // the server only answers State requests, so the SDK polls at this rate
+    // to feed subscribe_state; None disables polling
+    pub state_poll_interval: Option<Duration>,
+    // how often to send TimeSync to keep the server clock estimate fresh;
+    // None only syncs when sync_time is called
+    pub time_sync_interval: Option<Duration>,
+    pub reconnect: bool,
+    pub max_reconnect_attempts: usize,
+    pub reconnect_delay: Duration,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        return ClientOptions {
+            game: None,
+            game_token: None,
+            mode: None,
+            practice: false,
+            lobby: false,
+            firehose: false,
+            byte_order: ByteOrder::Little,
+            quantized: false,
+            auth_token: None,
+            session: None,
+            auth_scheme: Some("Bearer".to_string()),
+            heartbeat_interval: Duration::from_secs(5),
+            state_poll_interval: Some(Duration::from_millis(1000 / 60)),
+            time_sync_interval: Some(Duration::from_secs(10)),
+            reconnect: true,
+            max_reconnect_attempts: 5,
+            reconnect_delay: Duration::from_millis(500),
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    Connected,
+    Pong {
+        rtt: Duration,
+    },
+    Disconnected,
+    // the server's close code, when it is one the protocol defines
+    Closed(Option<CloseReason>),
+    Reconnected {
+        attempts: usize,
+    },
+    Welcome(WelcomeMessage),
+    GameOver(GameOverMessage),
+    Stats(StatsResponse),
+    ServerInfo(ServerInfoMessage),
+    WhoAmI(WhoAmIMessage),
+    Version(VersionMessage),
+    // what a v6 server agreed to in answer to the Hello sent on connect
+    Hello(HelloMessage),
+    PowerUp(PowerUpMessage),
+    // a ball went in, with who last touched it and whether it was an own
+    // goal
+    GoalScored(GoalScoredMessage),
+    // the server is full, so this connection waits for a game to end
+    Queued(QueuedMessage),
+    // still waiting in the matchmaking queue
+    QueueStatus(QueueStatusMessage),
+    // games that changed, after subscribe_lobby; the first one lists them all
+    LobbyUpdate(LobbyUpdateMessage),
+    // someone waiting for the same game type wants to play us; answer with
+    // answer_challenge
+    ChallengeReceived(ChallengeReceivedMessage),
+    // a challenge we sent or were sent ended without a game
+    ChallengeResult(ChallengeResultMessage),
+    // server-wide news from an admin, whatever this connection is doing
+    Announcement(String),
+    // answer to roster: every slot of the game and its connection status
+    Roster(RosterMessage),
+    // running games, after subscribe_all on a firehose connection
+    MultiState(MultiStateMessage),
+    // an admin changed the rules of the current game
+    GameParamsChanged(GameParams),
+    // the answer to tune_soccer
+    SoccerTuned(SoccerTunedMessage),
+    // our turn in turn-based soccer has started; moves are taken for its
+    // ticks updates
+    Turn(TurnMessage),
+    // the match is waiting for another player to rejoin; sent again every
+    // few seconds with the time left before they forfeit
+    OpponentDisconnected(OpponentDisconnectedMessage),
+    // a chat line, our own included
+    Chat(ChatMessage),
+    // the lead-up to a goal as (tick, snapshot) pairs, oldest first
+    ReplayBurst {
+        goal_tick: u32,
+        frames: Vec<(u32, SoccerStateSnapshot)>,
+    },
+    // the server swapped the game being played; drop any state kept for the
+    // old game type
+    ModeChanged(ModeChangedMessage),
+    // events were missed during a reconnect and are no longer on the
+    // server; anything derived from them (scores, roster) may be stale
+    ResyncRequired,
+    // the opponent connected, disconnected or left
+    PlayerJoined(PlayerJoinedMessage),
+    PlayerLeft(PlayerLeftMessage),
+    // a TimeSync reply arrived; offset_us is the smoothed estimate
+    TimeSync {
+        offset_us: f64,
+        rtt: Duration,
+        tick: u64,
+    },
+    // an echo() came back; the server stamps are on the TimeSync clock
+    Echo {
+        payload: Vec<u8>,
+        rtt: Duration,
+        server_received_us: u64,
+        server_sent_us: u64,
+    },
+    // a lockstep game stepped, with every player's input for the tick
+    LockstepStep(LockstepStepMessage),
+    // a snapshot of just the bodies asked for with set_interest
+    PartialState(PartialState),
+    // any message the SDK has no typed handling for yet
+    Message(MessageType, Vec<u8>),
+}
+
+// Weight of each new TimeSync sample in the smoothed offset.
+const CLOCK_SMOOTHING: f64 = 0.2;
+
+// Estimate of the server clock relative to this client's, refined by every
+// TimeSync reply. Samples are smoothed so one delayed reply can't yank the
+// estimate around.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockSync {
+    epoch: Instant,
+    // server_time_us - local time, once at least one reply has arrived
+    pub offset_us: Option<f64>,
+    pub rtt: Option<Duration>,
+    // game tick reported by the latest reply
+    pub tick: u64,
+}
+
+impl ClockSync {
+    fn new() -> Self {
+        return ClockSync {
+            epoch: Instant::now(),
+            offset_us: None,
+            rtt: None,
+            tick: 0,
+        };
+    }
+
+    // Local clock in the units TimeSync carries.
+    pub fn local_us(&self) -> u64 {
+        return self.epoch.elapsed().as_micros() as u64;
+    }
+
+    // The server's clock right now, by the current estimate.
+    pub fn server_time_us(&self) -> Option<u64> {
+        let offset = self.offset_us?;
+        return Some((self.local_us() as f64 + offset).max(0.0) as u64);
+    }
+
+    // The server stamps its reply the moment the request arrives, so its
+    // receive and send times are the same and the NTP offset reduces to
+    // server time minus the midpoint of our send and receive.
+    fn record(&mut self, reply: &TimeSyncResponse, received_us: u64) {
+        let sent = reply.client_time_us as f64;
+        let received = received_us as f64;
+        let sample = reply.server_time_us as f64 - (sent + received) / 2.0;
+        self.offset_us = Some(match self.offset_us {
+            Some(offset) => offset + (sample - offset) * CLOCK_SMOOTHING,
+            None => sample,
+        });
+        self.rtt = Some(Duration::from_micros(
+            received_us.saturating_sub(reply.client_time_us),
+        ));
+        self.tick = reply.tick;
+    }
+
+    fn request(&self) -> WsMessage {
+        return WsMessage::from_payload(
+            MessageType::TimeSync,
+            &TimeSyncRequest {
+                client_time_us: self.local_us(),
+            },
+        );
+    }
+}
+
+pub struct GameClient {
+    outgoing: mpsc::UnboundedSender<WsMessage>,
+    clock: Arc<Mutex<ClockSync>>,
+    // session token from the last Welcome
+    session: Arc<Mutex<Option<String>>>,
+    // the last Queued, until a Welcome
+    queued: Arc<Mutex<Option<QueuedMessage>>>,
+    states: broadcast::Sender<SoccerStateSnapshot>,
+    events: broadcast::Sender<ClientEvent>,
+    byte_order: ByteOrder,
+    task: JoinHandle<()>,
+}
+
+impl GameClient {
+    pub async fn connect(url: &str, name: &str, options: ClientOptions) -> Result<Self, Error> {
+        let (stream, protocol) =
+            open_stream(url, name, &options, options.session.as_deref()).await?;
+        let byte_order = options.byte_order;
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let (states, _) = broadcast::channel(64);
+        let (events, _) = broadcast::channel(64);
+        let clock = Arc::new(Mutex::new(ClockSync::new()));
+        let session = Arc::new(Mutex::new(options.session.clone()));
+        let queued = Arc::new(Mutex::new(None));
+        let connection = Connection {
+            url: url.to_string(),
+            name: name.to_string(),
+            options,
+            outgoing_rx,
+            states: states.clone(),
+            events: events.clone(),
+            last_ping: None,
+            protocol,
+            clock: clock.clone(),
+            session: session.clone(),
+            queued: queued.clone(),
+            last_event: None,
+            catching_up: None,
+            pending: None,
+            heartbeat_every: None,
+        };
+        let _ = events.send(ClientEvent::Connected);
+        let task = tokio::spawn(connection.run(stream));
+        return Ok(GameClient {
+            outgoing,
+            clock,
+            session,
+            queued,
+            states,
+            events,
+            byte_order,
+            task,
+        });
+    }
+
+    pub fn ping(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::Ping,
+            payload: vec![],
+        });
+    }
+
+    pub fn request_state(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::State,
+            payload: vec![],
+        });
+    }
+
+    pub fn send_move(&self, target: u8, vx: f32, vy: f32) -> bool {
+        return self.send_move_with_spin(target, vx, vy, 0.0);
+    }
+
+    pub fn send_move_with_spin(&self, target: u8, vx: f32, vy: f32, angular: f32) -> bool {
+        return self.send_move_sequenced(target, vx, vy, angular, 0);
+    }
+
+    // seq comes back as ack_seq on v2 state snapshots once the move has been
+    // applied, for measuring input-to-state latency.
+    pub fn send_move_sequenced(
+        &self,
+        target: u8,
+        vx: f32,
+        vy: f32,
+        angular: f32,
+        seq: u32,
+    ) -> bool {
+        let message = SoccerMoveMessage {
+            vx,
+            vy,
+            target,
+            angular,
+            seq,
+        };
+        return self.send(WsMessage {
+            msg_type: MessageType::SoccerMove,
+            payload: message.encode(self.byte_order),
+        });
+    }
+
+    // A lockstep game's replacement for the moves above: everything this
+    // player does on tick, possibly nothing. The game doesn't step past tick
+    // in play until it has everyone's or gives up waiting.
+    pub fn send_lockstep_input(&self, tick: u32, moves: Vec<LockstepMove>) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::LockstepInput,
+            &LockstepInputMessage { tick, moves },
+        ));
+    }
+
+    pub fn chat(&self, scope: ChatScope, text: &str) -> bool {
+        let chat = ChatMessage {
+            scope,
+            from: 0,
+            text: text.to_string(),
+        };
+        return self.send(WsMessage::from_payload(MessageType::Chat, &chat));
+    }
+
+    // Stops chat from that slot reaching us for the rest of the game.
+    pub fn mute(&self, slot: u8) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Mute,
+            &MuteMessage { slot },
+        ));
+    }
+
+    // Refused with a BoostCooldown error while on cooldown; the time left is
+    // in v3 State snapshots.
+    pub fn boost(&self, target: u8, dx: f32, dy: f32) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Boost,
+            &BoostMessage { target, dx, dy },
+        ));
+    }
+
+    // Gives up waiting for a match; the server then closes the connection.
+    pub fn leave_queue(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::LeaveQueue,
+            payload: vec![],
+        });
+    }
+
+    // Follow games being created, filling and going away while queued. Ends
+    // once the connection is placed in a game.
+    pub fn subscribe_lobby(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::SubscribeLobby,
+            payload: vec![],
+        });
+    }
+
+    // Asks a named player waiting for the same game type to play us. A
+    // Welcome follows if they accept; otherwise a ChallengeResult says why not.
+    pub fn challenge(&self, target_name: &str) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Challenge,
+            &ChallengeMessage {
+                target_name: target_name.to_string(),
+                handicap: None,
+            },
+        ));
+    }
+
+    // The same, for a soccer game played with uneven rules. We're the left
+    // side; the server clamps what it is sent.
+    pub fn challenge_with_handicap(&self, target_name: &str, handicap: HandicapConfig) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Challenge,
+            &ChallengeMessage {
+                target_name: target_name.to_string(),
+                handicap: Some(handicap),
+            },
+        ));
+    }
+
+    pub fn answer_challenge(&self, challenge_id: u32, accept: bool) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::ChallengeReply,
+            &ChallengeReplyMessage {
+                challenge_id,
+                accept,
+            },
+        ));
+    }
+
+    // Only answered on a firehose connection.
+    pub fn subscribe_all(&self, rate_hz: u8) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::SubscribeAll,
+            &SubscribeAllMessage { rate_hz },
+        ));
+    }
+
+    // Refused with Unauthorized until a Welcome has arrived.
+    pub fn leave_game(&self) -> bool {
+        let leave = LeaveGameMessage {
+            session_token: self.session_token().unwrap_or_default(),
+        };
+        return self.send(WsMessage::from_payload(MessageType::LeaveGame, &leave));
+    }
+
+    pub fn session_token(&self) -> Option<String> {
+        return self.session.lock().unwrap().clone();
+    }
+
+    // Set while the connection waits for a full server to free a game.
+    pub fn queued(&self) -> Option<QueuedMessage> {
+        return *self.queued.lock().unwrap();
+    }
+
+    // Tells the server this client has loaded and the match can start.
+    pub fn ready(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::Ready,
+            payload: vec![],
+        });
+    }
+
+    pub fn pause(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::PauseRequest,
+            payload: vec![],
+        });
+    }
+
+    pub fn resume(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::ResumeRequest,
+            payload: vec![],
+        });
+    }
+
+    // Has the server push State at this rate; pair it with a None
+    // state_poll_interval to stop polling.
+    pub fn set_state_rate(&self, state_rate_hz: u8) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Subscribe,
+            &SubscribeMessage { state_rate_hz },
+        ));
+    }
+
+    // Narrows pushed and polled snapshots to the bodies in the mask, in
+    // State order, plus whole InterestGroups; they come as PartialState
+    // events instead of states. Both 0 goes back to full State.
+    pub fn set_interest(&self, bodies: u64, groups: u8) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::SetInterest,
+            &SetInterestMessage { bodies, groups },
+        ));
+    }
+
+    pub fn server_info(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::ServerInfo,
+            payload: vec![],
+        });
+    }
+
+    // Asks which build the server is and the protocol versions it speaks;
+    // the answer arrives as ClientEvent::Version.
+    pub fn version(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::Version,
+            payload: vec![],
+        });
+    }
+
+    // Asks what address and protocol the server sees this connection with;
+    // the answer arrives as ClientEvent::WhoAmI.
+    pub fn who_am_i(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::WhoAmI,
+            payload: vec![],
+        });
+    }
+
+    // Sends payload for the server to send straight back, in any state and
+    // without touching the game; the answer arrives as ClientEvent::Echo, or
+    // as an Error when it is over the server's max_echo_bytes (8 of which
+    // the SDK uses for its send time) or the echo budget is spent.
+    pub fn echo(&self, payload: &[u8]) -> bool {
+        let mut data = self.clock.lock().unwrap().local_us().to_le_bytes().to_vec();
+        data.extend_from_slice(payload);
+        return self.send(WsMessage {
+            msg_type: MessageType::Echo,
+            payload: data,
+        });
+    }
+
+    pub fn get_stats(&self) -> bool {
+        return self.send(WsMessage {
+            msg_type: MessageType::GetStats,
+            payload: vec![],
+        });
+    }
+
+    // Picks the kickoff layout for our pucks by name; only taken during the
+    // ready check, before the match starts.
+    pub fn set_formation(&self, formation: &str) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::SetFormation,
+            &SetFormationMessage {
+                formation: formation.to_string(),
+            },
+        ));
+    }
+
+    // Who holds each slot of our game, answered with a Roster event;
+    // another game_id needs the admin token.
+    pub fn roster(&self, game_id: Option<u32>) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Roster,
+            &RosterRequest { game_id },
+        ));
+    }
+
+    // Admin only, like set_game_params: every open connection is sent an
+    // Announcement with text.
+    pub fn announce(&self, text: &str) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::Announce,
+            &AnnounceMessage {
+                text: text.to_string(),
+            },
+        ));
+    }
+
+    // Admin only: needs auth_token set to the server's admin token.
+    pub fn set_game_params(&self, game_id: Option<u32>, params: GameParams) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::SetGameParams,
+            &SetGameParamsMessage {
+                game_id,
+                params,
+                handicap: None,
+            },
+        ));
+    }
+
+    // Admin only: uneven rules for a soccer game that hasn't kicked off yet.
+    pub fn set_handicap(&self, game_id: u32, handicap: HandicapConfig) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::SetGameParams,
+            &SetGameParamsMessage {
+                game_id: Some(game_id),
+                params: GameParams::default(),
+                handicap: Some(handicap),
+            },
+        ));
+    }
+
+    // Admin only: soccer is a [soccer] config section, without the header,
+    // for every game created from now on, and with reapply for the running
+    // ones as far as they can change.
+    pub fn tune_soccer(&self, soccer: &str, reapply: bool) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::TuneSoccer,
+            &TuneSoccerMessage {
+                soccer: soccer.to_string(),
+                reapply,
+            },
+        ));
+    }
+
+    // Admin only, on a server with debug_ticks: runs a game, or with None
+    // the whole tick loop, at hz; 0 goes back to normal.
+    pub fn set_tick_rate(&self, game_id: Option<u32>, hz: f32) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::SetTickRate,
+            &SetTickRateMessage { game_id, hz },
+        ));
+    }
+
+    // Admin only, on a server with debug_ticks: stops a game ticking on its
+    // own and advances it steps updates.
+    pub fn step_game(&self, game_id: u32, steps: u32) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::StepGame,
+            &StepGameMessage { game_id, steps },
+        ));
+    }
+
+    // Admin only, on a server with debug_ticks: audits a game for
+    // determinism every `every` ticks from before its kickoff; 0 stops.
+    pub fn audit_game(&self, game_id: u32, every: u32) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::AuditGame,
+            &AuditGameMessage { game_id, every },
+        ));
+    }
+
+    // Admin only, on a server with debug_ticks: pins player's pucks in a
+    // soccer game where they stand, or lets them go again.
+    pub fn freeze_opponent(&self, game_id: u32, player: u8, frozen: bool) -> bool {
+        return self.send(WsMessage::from_payload(
+            MessageType::FreezeOpponent,
+            &FreezeOpponentMessage {
+                game_id,
+                player,
+                frozen,
+            },
+        ));
+    }
+
+    // Sends a TimeSync now instead of waiting for time_sync_interval.
+    pub fn sync_time(&self) -> bool {
+        let request = self.clock.lock().unwrap().request();
+        return self.send(request);
+    }
+
+    pub fn clock(&self) -> ClockSync {
+        return *self.clock.lock().unwrap();
+    }
+
+    pub fn send(&self, message: WsMessage) -> bool {
+        return self.outgoing.send(message).is_ok();
+    }
+
+    pub fn subscribe_state(&self) -> impl Stream<Item = SoccerStateSnapshot> {
+        return receiver_stream(self.states.subscribe());
+    }
+
+    pub fn subscribe_events(&self) -> impl Stream<Item = ClientEvent> {
+        return receiver_stream(self.events.subscribe());
+    }
+
+    pub fn is_connected(&self) -> bool {
+        return !self.task.is_finished();
+    }
+
+    pub fn close(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for GameClient {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Connection {
+    url: String,
+    name: String,
+    options: ClientOptions,
+    outgoing_rx: mpsc::UnboundedReceiver<WsMessage>,
+    states: broadcast::Sender<SoccerStateSnapshot>,
+    events: broadcast::Sender<ClientEvent>,
+    last_ping: Option<Instant>,
+    protocol: ProtocolVersion,
+    clock: Arc<Mutex<ClockSync>>,
+    session: Arc<Mutex<Option<String>>>,
+    queued: Arc<Mutex<Option<QueuedMessage>>>,
+    // game id and seq of the last Event handled, for catching up after a
+    // reconnect
+    last_event: Option<(u32, u64)>,
+    // seqs of the Events handled since EventsSince went out, until its
+    // answer. Live Events can overtake the replay, so seq order alone would
+    // drop what they overtook.
+    catching_up: Option<HashSet<u64>>,
+    // sent as soon as the current incoming frame is handled
+    pending: Option<WsMessage>,
+    // a faster Ping cadence the server asked for in Welcome, until the
+    // heartbeat picks it up
+    heartbeat_every: Option<Duration>,
+}
+
+impl Connection {
+    async fn run(mut self, mut stream: ClientStream) {
+        loop {
+            let closed_by_user = self.drive(stream).await;
+            let _ = self.events.send(ClientEvent::Disconnected);
+            if closed_by_user || !self.options.reconnect {
+                return;
+            }
+            // the server resumes a player by name and session token, so
+            // reconnecting with both puts us back in our slot
+            match self.reconnect().await {
+                Some(new_stream) => stream = new_stream,
+                None => return,
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> Option<ClientStream> {
+        for attempt in 1..=self.options.max_reconnect_attempts {
+            sleep(self.options.reconnect_delay * attempt as u32).await;
+            let session = self.session.lock().unwrap().clone();
+            match open_stream(&self.url, &self.name, &self.options, session.as_deref()).await {
+                Ok((stream, protocol)) => {
+                    self.protocol = protocol;
+                    let _ = self
+                        .events
+                        .send(ClientEvent::Reconnected { attempts: attempt });
+                    return Some(stream);
+                }
+                Err(e) => {
+                    log::debug!("Reconnect attempt {} failed: {}", attempt, e);
+                }
+            }
+        }
+        return None;
+    }
+
+    // returns true when the loop should stop rather than reconnect: the
+    // GameClient was dropped, or another connection took over our slot
+    async fn drive(&mut self, stream: ClientStream) -> bool {
+        let (mut sender, mut receiver) = stream.split();
+        let mut heartbeat = interval(self.options.heartbeat_interval);
+        let mut state_poll = interval(
+            self.options
+                .state_poll_interval
+                .unwrap_or(Duration::from_secs(3600)),
+        );
+        let mut time_sync = interval(
+            self.options
+                .time_sync_interval
+                .unwrap_or(Duration::from_secs(3600)),
+        );
+        loop {
+            if let Some(every) = self.heartbeat_every.take() {
+                heartbeat = interval(every);
+            }
+            let outbound = tokio::select! {
+                outgoing = self.outgoing_rx.recv() => match outgoing {
+                    Some(message) => message,
+                    None => {
+                        let _ = sender.send(Message::Close(None)).await;
+                        return true;
+                    }
+                },
+                _ = heartbeat.tick() => WsMessage {
+                    msg_type: MessageType::Ping,
+                    payload: vec![],
+                },
+                _ = state_poll.tick(), if self.options.state_poll_interval.is_some() => WsMessage {
+                    msg_type: MessageType::State,
+                    payload: vec![],
+                },
+                _ = time_sync.tick(), if self.options.time_sync_interval.is_some() => {
+                    self.clock.lock().unwrap().request()
+                }
+                incoming = receiver.next() => {
+                    match incoming {
+                        Some(Ok(Message::Binary(data))) => self.handle_incoming(&data),
+                        Some(Ok(Message::Close(frame))) => {
+                            let reason = frame.and_then(|frame| CloseReason::from_code(frame.code.into()));
+                            let _ = self.events.send(ClientEvent::Closed(reason));
+                            // reconnecting would only push the newer connection out
+                            return reason == Some(CloseReason::ReplacedByNewConnection);
+                        }
+                        None => return false,
+                        Some(Ok(_)) => (),
+                        Some(Err(e)) => {
+                            log::debug!("Error receiving message: {}", e);
+                            return false;
+                        }
+                    }
+                    match self.pending.take() {
+                        Some(message) => message,
+                        None => continue,
+                    }
+                }
+            };
+            if let MessageType::Ping = outbound.msg_type {
+                self.last_ping = Some(Instant::now());
+            }
+            if sender
+                .send(Message::Binary(outbound.to_bytes()))
+                .await
+                .is_err()
+            {
+                return false;
+            }
+        }
+    }
+
+    fn handle_incoming(&mut self, data: &[u8]) {
+        let ws_msg = match WsMessage::from_bytes(data) {
+            Some(ws_msg) => ws_msg,
+            None => return,
+        };
+        match ws_msg.msg_type {
+            // a server probe; echo its id back
+            MessageType::Ping => {
+                self.pending = Some(WsMessage {
+                    msg_type: MessageType::Pong,
+                    payload: ws_msg.payload,
+                });
+            }
+            MessageType::Pong => {
+                if let Some(sent) = self.last_ping.take() {
+                    let _ = self.events.send(ClientEvent::Pong {
+                        rtt: sent.elapsed(),
+                    });
+                }
+            }
+            MessageType::State => {
+                let mut payload = ws_msg.payload;
+                let order = self.options.byte_order;
+                // a struggling v9 connection is switched to quantized
+                let degraded = self.protocol >= ProtocolVersion::V9
+                    && SnapshotHeader::peek_level(&payload)
+                        .map_or(false, |level| level >= DegradeLevel::Quantized);
+                let snapshot = match self.options.quantized || degraded {
+                    true => order
+                        .swap_quantized_state(self.protocol, &mut payload)
+                        .then(|| SoccerStateSnapshot::decode_quantized(self.protocol, &payload))
+                        .flatten(),
+                    false => order
+                        .swap_state(self.protocol, &mut payload)
+                        .then(|| SoccerStateSnapshot::decode(self.protocol, &payload))
+                        .flatten(),
+                };
+                if let Some(snapshot) = snapshot {
+                    let _ = self.states.send(snapshot);
+                }
+            }
+            MessageType::PartialState => {
+                let mut payload = ws_msg.payload;
+                let snapshot = self
+                    .options
+                    .byte_order
+                    .swap_partial_state(self.protocol, &mut payload)
+                    .then(|| PartialState::decode(self.protocol, &payload))
+                    .flatten();
+                if let Some(snapshot) = snapshot {
+                    let _ = self.events.send(ClientEvent::PartialState(snapshot));
+                }
+            }
+            MessageType::Welcome => {
+                if let Some(welcome) = ws_msg.decode::<WelcomeMessage>() {
+                    *self.session.lock().unwrap() = Some(welcome.session_token.clone());
+                    // ping at twice the rate the server requires, so one
+                    // late Ping doesn't cost the connection
+                    if welcome.ping_interval_ms > 0 {
+                        let every = (Duration::from_millis(welcome.ping_interval_ms as u64) / 2)
+                            .min(self.options.heartbeat_interval);
+                        self.heartbeat_every = Some(every);
+                    }
+                    *self.queued.lock().unwrap() = None;
+                    match self.last_event {
+                        // back in the same game after a reconnect: ask for
+                        // whatever was broadcast while we were gone
+                        Some((game_id, seq)) if game_id == welcome.game_id => {
+                            let request = EventsSinceMessage { seq };
+                            self.pending =
+                                Some(WsMessage::from_payload(MessageType::EventsSince, &request));
+                            // kept from a catch-up the drop cut short
+                            self.catching_up.get_or_insert_with(HashSet::new);
+                        }
+                        _ => {
+                            self.last_event = Some((welcome.game_id, 0));
+                            self.catching_up = None;
+                        }
+                    }
+                    let _ = self.events.send(ClientEvent::Welcome(welcome));
+                }
+            }
+            MessageType::GameOver => {
+                if let Some(game_over) = ws_msg.decode::<GameOverMessage>() {
+                    let _ = self.events.send(ClientEvent::GameOver(game_over));
+                }
+            }
+            MessageType::GetStats => {
+                if let Some(stats) = ws_msg.decode::<StatsResponse>() {
+                    let _ = self.events.send(ClientEvent::Stats(stats));
+                }
+            }
+            MessageType::ServerInfo => {
+                if let Some(info) = ws_msg.decode::<ServerInfoMessage>() {
+                    let _ = self.events.send(ClientEvent::ServerInfo(info));
+                }
+            }
+            MessageType::WhoAmI => {
+                if let Some(who) = ws_msg.decode::<WhoAmIMessage>() {
+                    let _ = self.events.send(ClientEvent::WhoAmI(who));
+                }
+            }
+            MessageType::Version => {
+                if let Some(version) = ws_msg.decode::<VersionMessage>() {
+                    let _ = self.events.send(ClientEvent::Version(version));
+                }
+            }
+            MessageType::Hello => {
+                if let Some(agreed) = ws_msg.decode::<HelloMessage>() {
+                    let _ = self.events.send(ClientEvent::Hello(agreed));
+                }
+            }
+            MessageType::TimeSync => {
+                if let Some(reply) = ws_msg.decode::<TimeSyncResponse>() {
+                    let mut clock = self.clock.lock().unwrap();
+                    let received_us = clock.local_us();
+                    clock.record(&reply, received_us);
+                    let _ = self.events.send(ClientEvent::TimeSync {
+                        offset_us: clock.offset_us.unwrap_or(0.0),
+                        rtt: clock.rtt.unwrap_or_default(),
+                        tick: reply.tick,
+                    });
+                }
+            }
+            MessageType::Echo => {
+                let reply = EchoReply::from_bytes(&ws_msg.payload, self.options.byte_order)
+                    .filter(|reply| reply.payload.len() >= 8);
+                if let Some(mut reply) = reply {
+                    let payload = reply.payload.split_off(8);
+                    let sent_us = u64::from_le_bytes(reply.payload.try_into().unwrap());
+                    let now_us = self.clock.lock().unwrap().local_us();
+                    let _ = self.events.send(ClientEvent::Echo {
+                        payload,
+                        rtt: Duration::from_micros(now_us.saturating_sub(sent_us)),
+                        server_received_us: reply.received_us,
+                        server_sent_us: reply.sent_us,
+                    });
+                }
+            }
+            MessageType::TuneSoccer => {
+                if let Some(tuned) = ws_msg.decode::<SoccerTunedMessage>() {
+                    let _ = self.events.send(ClientEvent::SoccerTuned(tuned));
+                }
+            }
+            MessageType::Turn => {
+                if let Some(turn) = ws_msg.decode::<TurnMessage>() {
+                    let _ = self.events.send(ClientEvent::Turn(turn));
+                }
+            }
+            MessageType::OpponentDisconnected => {
+                if let Some(notice) = ws_msg.decode::<OpponentDisconnectedMessage>() {
+                    let _ = self.events.send(ClientEvent::OpponentDisconnected(notice));
+                }
+            }
+            MessageType::GameParamsChanged => {
+                if let Some(params) = ws_msg.decode::<GameParams>() {
+                    let _ = self.events.send(ClientEvent::GameParamsChanged(params));
+                }
+            }
+            MessageType::Event => {
+                if let Some(event) = ws_msg.decode::<EventMessage>() {
+                    // replays can overlap live broadcasts
+                    if let Some((game_id, last_seq)) = self.last_event {
+                        if event.seq <= last_seq {
+                            return;
+                        }
+                        match &mut self.catching_up {
+                            Some(seen) => {
+                                if !seen.insert(event.seq) {
+                                    return;
+                                }
+                            }
+                            None => self.last_event = Some((game_id, event.seq)),
+                        }
+                    }
+                    self.handle_incoming(&event.frame);
+                }
+            }
+            MessageType::EventsSince => {
+                if let Some(response) = ws_msg.decode::<EventsSinceResponse>() {
+                    let seen = self.catching_up.take().unwrap_or_default();
+                    if let Some((game_id, last_seq)) = self.last_event {
+                        let handled = seen.into_iter().max().unwrap_or(0);
+                        let last_seq = last_seq.max(response.latest_seq).max(handled);
+                        self.last_event = Some((game_id, last_seq));
+                    }
+                    if !response.complete {
+                        let _ = self.events.send(ClientEvent::ResyncRequired);
+                    }
+                }
+            }
+            MessageType::Chat => {
+                if let Some(chat) = ws_msg.decode::<ChatMessage>() {
+                    let _ = self.events.send(ClientEvent::Chat(chat));
+                }
+            }
+            MessageType::ReplayBurst => {
+                if let Some(burst) = ws_msg.decode::<ReplayBurstMessage>() {
+                    let frames = burst
+                        .frames
+                        .iter()
+                        .filter_map(|frame| {
+                            SoccerStateSnapshot::from_bytes_v3(&frame.state)
+                                .map(|snapshot| (frame.tick, snapshot))
+                        })
+                        .collect();
+                    let _ = self.events.send(ClientEvent::ReplayBurst {
+                        goal_tick: burst.goal_tick,
+                        frames,
+                    });
+                }
+            }
+            MessageType::PlayerJoined => {
+                if let Some(joined) = ws_msg.decode::<PlayerJoinedMessage>() {
+                    let _ = self.events.send(ClientEvent::PlayerJoined(joined));
+                }
+            }
+            MessageType::PlayerLeft => {
+                if let Some(left) = ws_msg.decode::<PlayerLeftMessage>() {
+                    let _ = self.events.send(ClientEvent::PlayerLeft(left));
+                }
+            }
+            MessageType::Queued => {
+                if let Some(queued) = ws_msg.decode::<QueuedMessage>() {
+                    *self.queued.lock().unwrap() = Some(queued);
+                    let _ = self.events.send(ClientEvent::Queued(queued));
+                }
+            }
+            MessageType::QueueStatus => {
+                if let Some(status) = ws_msg.decode::<QueueStatusMessage>() {
+                    let _ = self.events.send(ClientEvent::QueueStatus(status));
+                }
+            }
+            MessageType::LobbyUpdate => {
+                if let Some(update) = ws_msg.decode::<LobbyUpdateMessage>() {
+                    let _ = self.events.send(ClientEvent::LobbyUpdate(update));
+                }
+            }
+            MessageType::ChallengeReceived => {
+                if let Some(challenge) = ws_msg.decode::<ChallengeReceivedMessage>() {
+                    let _ = self.events.send(ClientEvent::ChallengeReceived(challenge));
+                }
+            }
+            MessageType::Announcement => {
+                if let Some(announcement) = ws_msg.decode::<AnnouncementMessage>() {
+                    let _ = self
+                        .events
+                        .send(ClientEvent::Announcement(announcement.text));
+                }
+            }
+            MessageType::Roster => {
+                if let Some(roster) = ws_msg.decode::<RosterMessage>() {
+                    let _ = self.events.send(ClientEvent::Roster(roster));
+                }
+            }
+            MessageType::ChallengeResult => {
+                if let Some(result) = ws_msg.decode::<ChallengeResultMessage>() {
+                    let _ = self.events.send(ClientEvent::ChallengeResult(result));
+                }
+            }
+            MessageType::MultiState => {
+                if let Some(batch) = MultiStateMessage::decode(&ws_msg.payload) {
+                    let _ = self.events.send(ClientEvent::MultiState(batch));
+                }
+            }
+            MessageType::ModeChanged => {
+                if let Some(changed) = ws_msg.decode::<ModeChangedMessage>() {
+                    let _ = self.events.send(ClientEvent::ModeChanged(changed));
+                }
+            }
+            MessageType::PowerUp => {
+                if let Some(power_up) = ws_msg.decode::<PowerUpMessage>() {
+                    let _ = self.events.send(ClientEvent::PowerUp(power_up));
+                }
+            }
+            MessageType::GoalScored => {
+                if let Some(goal) = ws_msg.decode::<GoalScoredMessage>() {
+                    let _ = self.events.send(ClientEvent::GoalScored(goal));
+                }
+            }
+            MessageType::LockstepStep => {
+                if let Some(step) = ws_msg.decode::<LockstepStepMessage>() {
+                    let _ = self.events.send(ClientEvent::LockstepStep(step));
+                }
+            }
+            _ => {
+                let _ = self
+                    .events
+                    .send(ClientEvent::Message(ws_msg.msg_type, ws_msg.payload));
+            }
+        }
+    }
+}
+
+async fn open_stream(
+    url: &str,
+    name: &str,
+    options: &ClientOptions,
+    session: Option<&str>,
+) -> Result<(ClientStream, ProtocolVersion), Error> {
+    let query = {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        query.append_pair("name", name);
+        if let Some(game) = options.game {
+            query.append_pair("game", &game.to_string());
+        }
+        if let Some(game_token) = &options.game_token {
+            query.append_pair("game_token", game_token);
+        }
+        if let Some(mode) = options.mode {
+            query.append_pair("mode", &mode.to_string());
+        }
+        if options.practice {
+            query.append_pair("practice", "1");
+        }
+        if options.lobby {
+            query.append_pair("lobby", "1");
+        }
+        if options.firehose {
+            query.append_pair("firehose", "1");
+        }
+        if options.byte_order != ByteOrder::Little {
+            query.append_pair("byte_order", options.byte_order.as_param());
+        }
+        if options.quantized {
+            query.append_pair("format", "quantized");
+        }
+        if let Some(session) = session {
+            query.append_pair("session", session);
+        }
+        query.finish()
+    };
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}{}", url, separator, query);
+    let mut request = url.into_client_request()?;
+    let protocols: Vec<&str> = ProtocolVersion::SUPPORTED
+        .iter()
+        .map(|version| version.as_str())
+        .collect();
+    if let Ok(value) = HeaderValue::from_str(&protocols.join(", ")) {
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", value);
+    }
+    if let Some(token) = &options.auth_token {
+        let value = match &options.auth_scheme {
+            Some(scheme) => format!("{} {}", scheme, token),
+            None => token.clone(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            request.headers_mut().insert("Authorization", value);
+        }
+    }
+    let (mut stream, response) = connect_async(request).await?;
+    // servers that don't echo a subprotocol only speak v1
+    let protocol = response
+        .headers()
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|value| value.to_str().ok())
+        .and_then(ProtocolVersion::from_subprotocol)
+        .unwrap_or(ProtocolVersion::V1);
+    // v6 servers wait for Hello before anything else
+    if protocol >= ProtocolVersion::V6 {
+        let role = match options.firehose {
+            true => Role::Firehose,
+            false => Role::Player,
+        };
+        let hello = HelloMessage {
+            protocol: protocol as u8,
+            build: format!("rust-backend-client/{}", env!("CARGO_PKG_VERSION")),
+            state_version: protocol as u8,
+            format: match options.quantized {
+                true => StateFormat::Quantized.code(),
+                false => StateFormat::Binary.code(),
+            },
+            byte_order: options.byte_order.code(),
+            role: role.code(),
+        };
+        let hello = WsMessage::from_payload(MessageType::Hello, &hello);
+        stream.send(Message::Binary(hello.to_bytes())).await?;
+    }
+    return Ok((stream, protocol));
+}
+
+fn receiver_stream<T: Clone + Send + 'static>(
+    receiver: broadcast::Receiver<T>,
+) -> impl Stream<Item = T> {
+    return futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(value) => return Some((value, receiver)),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+}
@@ -0,0 +1,1217 @@
+use crate::frame_dump::DumpFormat;
+use crate::game::{
+    default_walls, validate_params, ControlMode, DuplicateConnection, PhysicsPreset, ShortHanded,
+    SoccerGameConfig, StallAction, Stepping, DEFAULT_SHORT_HANDED_TIMEOUT, GOAL_NET_DEPTH,
+    GOAL_WIDTH, STALL_NUDGE_SPEED, WALL_THICKNESS,
+};
+use crate::invariants::InvariantChecks;
+use crate::message::GameParams;
+use crate::server::{ServerConfig, TlsFiles};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Settings read from a TOML file at startup. Every field is optional and
+// anything left out keeps the ServerConfig / SoccerGameConfig default, so an
+// empty or missing file gives the stock server.
+//
+// Each setting is taken from the first of these that has it:
+//   1. a command line flag, --<section>.<key> <value>, e.g.
+//      --server.addr 0.0.0.0:9000
+//   2. an environment variable, ASYNCWS__<SECTION>__<KEY>, e.g.
+//      ASYNCWS__SERVER__ADDR=0.0.0.0:9000 or
+//      ASYNCWS__SOCCER__POWER_UPS__INTERVAL_SECS=5
+//   3. the selected profile, [profiles.<name>.server] and
+//      [profiles.<name>.soccer], picked by --profile <name> or
+//      ASYNCWS_PROFILE=<name>
+//   4. the file's own [server] and [soccer]
+//   5. the ServerConfig / SoccerGameConfig default
+// Flag and environment values are parsed as TOML, falling back to a plain
+// string. Keys the server doesn't know are left out with a warning rather
+// than failing the load.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerSection,
+    pub soccer: SoccerSection,
+    // the profile laid over the file's sections, if one was picked
+    #[serde(skip)]
+    pub profile: Option<String>,
+    // what the profile insists the result has: "tls", "auth"
+    #[serde(skip)]
+    pub requirements: Vec<String>,
+    // unknown keys that were skipped, for the caller to log
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+// Where a config is loaded from: the file, the profile asked for on the
+// command line, and the command line's overrides as (section.key, value).
+// Kept by the server so a reload reads the same way startup did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub profile: Option<String>,
+    pub overrides: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    pub addr: Option<String>,
+    // "off" disables the HTTP endpoints
+    pub http_addr: Option<String>,
+    // "off" disables the health endpoints
+    pub health_addr: Option<String>,
+    pub live_tick_periods: Option<u32>,
+    pub tick_rate: Option<u64>,
+    pub tick_window_secs: Option<u64>,
+    pub close_on_leave: Option<bool>,
+    pub collect_stats: Option<bool>,
+    pub max_stats_entries: Option<usize>,
+    // "accurate", "balanced" or "fast"
+    pub physics_preset: Option<String>,
+    pub record_physics_stats: Option<bool>,
+    // per-tick impulse cap; setting it switches moves to impulse control
+    pub max_impulse: Option<f32>,
+    pub min_state_rate_hz: Option<u8>,
+    pub max_state_rate_hz: Option<u8>,
+    pub default_state_rate_hz: Option<u8>,
+    // step connections that can't keep up down to cheaper snapshots, past
+    // degrade_queue_high frames waiting or degrade_rtt_high_ms, and back up
+    // once under degrade_rtt_low_ms with nothing waiting
+    pub degrade: Option<bool>,
+    pub degrade_queue_high: Option<usize>,
+    pub degrade_rtt_high_ms: Option<u64>,
+    pub degrade_rtt_low_ms: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    // 0 never pings quiet connections
+    pub server_ping_interval_secs: Option<u64>,
+    // 0 doesn't require players to ping
+    pub required_ping_interval_secs: Option<u64>,
+    // 0 never sends websocket-level pings
+    pub ws_ping_interval_secs: Option<u64>,
+    // 0 lets a write to a stalled socket wait forever
+    pub send_timeout_ms: Option<u64>,
+    pub handshake_timeout_secs: Option<u64>,
+    pub ready_timeout_secs: Option<u64>,
+    pub dormant_timeout_secs: Option<u64>,
+    // 0 waits in the queue indefinitely
+    pub queue_timeout_secs: Option<u64>,
+    // 0 allows any number of live games
+    pub max_games: Option<usize>,
+    // 0 waits for a full server indefinitely
+    pub full_queue_timeout_secs: Option<u64>,
+    pub challenge_timeout_secs: Option<u64>,
+    pub max_message_size: Option<usize>,
+    pub max_frame_size: Option<usize>,
+    pub max_echo_bytes: Option<usize>,
+    pub echo_burst: Option<u32>,
+    pub echo_rate_hz: Option<u32>,
+    // 0 allows any number of connections from one address
+    pub max_connections_per_ip: Option<usize>,
+    pub trusted_proxies: Option<Vec<IpAddr>>,
+    // empty allows every origin
+    pub allowed_origins: Option<Vec<String>>,
+    // 0 never bans
+    pub protocol_strikes: Option<u32>,
+    pub protocol_ban_secs: Option<u64>,
+    // 0 lets one player hold any number of games
+    pub max_games_per_identity: Option<usize>,
+    pub admin_token: Option<String>,
+    pub debug_ticks: Option<bool>,
+    // "off", "log" or "panic"; the default panics in debug builds and is
+    // off in release ones
+    pub check_invariants: Option<String>,
+    pub auth_header: Option<String>,
+    // "" takes the whole header value as the token; required with auth_url
+    pub auth_scheme: Option<String>,
+    // "" turns auth off and lets ?name= pick the name
+    pub auth_url: Option<String>,
+    // PEM certificate chain and key; both or neither, and both must exist
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    // env_logger filter ("info", "debug", "rust_backend=trace"); RUST_LOG
+    // wins over it
+    pub log_level: Option<String>,
+    pub auth_timeout_secs: Option<u64>,
+    pub max_auth_header_bytes: Option<usize>,
+    pub max_name_len: Option<usize>,
+    pub event_log_size: Option<usize>,
+    pub replay_ticks: Option<usize>,
+    pub history_size: Option<usize>,
+    pub input_buffer_ticks: Option<u64>,
+    // 0 leaves games free-running
+    pub lockstep_timeout_ms: Option<u64>,
+    // unset keeps everything in memory
+    pub persist_path: Option<String>,
+    pub persist_interval_secs: Option<u64>,
+    // 0 saves game summaries only
+    pub game_state_interval_secs: Option<u64>,
+    // "" dumps nothing
+    pub frame_dump_dir: Option<String>,
+    // "json" or "binary"
+    pub frame_dump_format: Option<String>,
+    // 0 runs until the game ends
+    pub frame_dump_limit: Option<u64>,
+    pub pauses_per_player: Option<u8>,
+    pub max_pause_secs: Option<u64>,
+    pub resume_countdown_secs: Option<u64>,
+    // "play" carries on when a match loses a connection, "wait" freezes it
+    // until they rejoin
+    pub short_handed: Option<String>,
+    // how long "wait" holds the game before the absent player forfeits; 0
+    // waits indefinitely
+    pub short_handed_timeout_secs: Option<u64>,
+    // how often the players left are reminded how long "wait" has left
+    pub reconnect_notice_secs: Option<u64>,
+    pub max_firehose_rate_hz: Option<u8>,
+    pub firehose_batch_bytes: Option<usize>,
+    pub warm_up: Option<bool>,
+    // "reject" refuses a second connection to a slot, "replace" lets it in
+    // and closes the first
+    pub duplicate_connection: Option<String>,
+    pub soccer_pool_size: Option<usize>,
+    pub disconnect_log_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoccerSection {
+    // changing any of these five rebuilds the default walls
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub goal_width: Option<f32>,
+    pub goal_depth: Option<f32>,
+    pub wall_thickness: Option<f32>,
+    pub pucks_per_team: Option<usize>,
+    pub balls: Option<usize>,
+    pub puck_radius: Option<f32>,
+    pub puck_damping: Option<f32>,
+    pub puck_restitution: Option<f32>,
+    // defaults to puck_damping
+    pub ball_damping: Option<f32>,
+    // 0 turns the speed-squared drag off
+    pub quadratic_drag: Option<f32>,
+    pub max_shot_speed: Option<f32>,
+    pub max_body_speed: Option<f32>,
+    // 0 leaves the ball still at kickoff
+    pub serve_speed: Option<f32>,
+    pub move_cooldown_ms: Option<u32>,
+    pub boost_speed: Option<f32>,
+    pub boost_cooldown_ms: Option<u64>,
+    pub goal_reset_ticks: Option<u64>,
+    pub kickoff_freeze_ticks: Option<u64>,
+    pub settle_ticks: Option<u64>,
+    // present enables power-ups
+    pub power_ups: Option<PowerUpSection>,
+    // present gets stalled balls moving again
+    pub anti_stall: Option<AntiStallSection>,
+    // share of speed kept per bounce; 1 turns it off
+    pub bounce_decay: Option<f32>,
+    // updates a touch still gets credit for a goal; 0 credits any touch
+    pub touch_window_ticks: Option<u64>,
+    // color id per side, left then right, sent with each puck in Welcome
+    pub team_colors: Option<Vec<u8>>,
+    // "per_update", "fixed" or "variable"; see Stepping
+    pub stepping: Option<String>,
+    // length of a fixed step, 1000/60 when unset
+    pub physics_dt_ms: Option<f32>,
+    // simulated seconds a match lasts; 0 plays until someone leaves
+    pub match_secs: Option<u64>,
+    // most simulated time one update makes up after a stall
+    pub max_catch_up_ms: Option<u64>,
+    // updates each slot moves alone for in turn; 0 lets everyone move at once
+    pub turn_ticks: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AntiStallSection {
+    pub speed: Option<f32>,
+    pub after_ms: Option<u64>,
+    // "nudge", "recenter" or "drop_ball"
+    pub action: Option<String>,
+    pub nudge_speed: Option<f32>,
+    pub pinned_radius: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerUpSection {
+    pub interval_secs: Option<u64>,
+    pub duration_secs: Option<u64>,
+    pub max_on_field: Option<usize>,
+    pub big_puck_scale: Option<f32>,
+    pub speed_boost: Option<f32>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+    // an error in a config built with the named profile
+    InProfile(String, Box<ConfigError>),
+}
+
+impl ConfigError {
+    fn in_profile(self, profile: Option<&str>) -> ConfigError {
+        return match profile {
+            Some(profile) => ConfigError::InProfile(profile.to_string(), Box::new(self)),
+            None => self,
+        };
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "could not parse config: {}", e),
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {}", reason),
+            ConfigError::InProfile(profile, e) => write!(f, "{} (profile {})", e, profile),
+        }
+    }
+}
+
+const ENV_PREFIX: &str = "ASYNCWS__";
+// names the profile when there is no --profile
+const PROFILE_ENV: &str = "ASYNCWS_PROFILE";
+const REQUIREMENTS: [&str; 2] = ["tls", "auth"];
+// the tick budget is whole milliseconds, so anything faster would leave it
+// at zero
+pub const MAX_TICK_RATE: u64 = 1000;
+
+impl ConfigSource {
+    pub fn new(path: PathBuf) -> Self {
+        return ConfigSource {
+            path,
+            ..ConfigSource::default()
+        };
+    }
+
+    // The config path is the first argument that isn't a flag, then
+    // ASYNCWS_CONFIG, then ./config.toml. --profile <name> picks a
+    // profile, and --<section>.<key> <value> (or =<value>) overrides one
+    // setting.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut source = ConfigSource::default();
+        let mut path = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None if path.is_none() => {
+                    path = Some(PathBuf::from(arg));
+                    continue;
+                }
+                None => return Err(format!("unexpected argument '{}'", arg)),
+            };
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key, Some(value.to_string())),
+                None => (flag, None),
+            };
+            if key != "profile" && !key.contains('.') {
+                return Err(format!(
+                    "unknown flag --{}, expected --profile or --<section>.<key>",
+                    key
+                ));
+            }
+            let value = match value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(format!("--{} needs a value", key)),
+            };
+            if key == "profile" {
+                source.profile = Some(value);
+            } else {
+                source.overrides.push((key.to_string(), value));
+            }
+        }
+        source.path = path
+            .or_else(|| std::env::var("ASYNCWS_CONFIG").ok().map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("config.toml"));
+        return Ok(source);
+    }
+
+    // Reads the file, or starts from defaults when it doesn't exist, and
+    // lays the profile, environment and overrides over it.
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(ConfigError::Io(e)),
+        };
+        return Config::layered(
+            &text,
+            self.profile.as_deref(),
+            std::env::vars(),
+            &self.overrides,
+        );
+    }
+}
+impl Config {
+    // Reads path, or starts from defaults when it doesn't exist, then
+    // applies ASYNCWS_PROFILE and environment overrides.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        return ConfigSource::new(path.to_path_buf()).load();
+    }
+
+    pub fn from_toml(
+        text: &str,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        return Config::layered(text, None, env, &[]);
+    }
+
+    // text with, in rising precedence, the profile (or the one env names
+    // in ASYNCWS_PROFILE), env's ASYNCWS__ variables and overrides laid
+    // over it.
+    pub fn layered(
+        text: &str,
+        profile: Option<&str>,
+        env: impl IntoIterator<Item = (String, String)>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let mut table = text.parse::<toml::Table>().map_err(ConfigError::Parse)?;
+        let profiles = match table.remove("profiles") {
+            None => toml::Table::new(),
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => {
+                return Err(ConfigError::Invalid(
+                    "profiles must be [profiles.<name>] sections".into(),
+                ))
+            }
+        };
+        let mut env_profile = None;
+        let mut env_overrides = vec![];
+        for (key, value) in env {
+            if key == PROFILE_ENV {
+                env_profile = Some(value);
+            } else if let Some(path) = key.strip_prefix(ENV_PREFIX) {
+                let path: Vec<String> = path.split("__").map(|part| part.to_lowercase()).collect();
+                env_overrides.push((key.clone(), path, value));
+            }
+        }
+        let profile = profile
+            .map(str::to_string)
+            .or(env_profile)
+            .filter(|profile| !profile.is_empty());
+        let known = known_keys();
+        let mut warnings = vec![];
+        strip_unknown(&mut table, &known, "", "the file", &mut warnings);
+        let mut requirements = vec![];
+        if let Some(name) = &profile {
+            let (mut section, needs) = profile_section(&profiles, name)?;
+            strip_unknown(
+                &mut section,
+                &known,
+                "",
+                &format!("profile {}", name),
+                &mut warnings,
+            );
+            merge(&mut table, section);
+            requirements = needs;
+        }
+        let flags = overrides.iter().map(|(key, value)| {
+            let path: Vec<String> = key.split('.').map(str::to_string).collect();
+            return (format!("--{}", key), path, value.clone());
+        });
+        for (from, path, value) in env_overrides.into_iter().chain(flags) {
+            if !is_known(&known, &path) {
+                warnings.push(format!("unknown key {} in {}", path.join("."), from));
+                continue;
+            }
+            set_path(&mut table, &path, parse_env_value(&value));
+        }
+        let mut config: Config = match toml::Value::Table(table.clone()).try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                let error = match offending_key(&table, &mut vec![]) {
+                    Some((key, e)) => ConfigError::Invalid(format!("bad value for {}: {}", key, e)),
+                    None => ConfigError::Parse(e),
+                };
+                return Err(error.in_profile(profile.as_deref()));
+            }
+        };
+        config.profile = profile;
+        config.requirements = requirements;
+        config.warnings = warnings;
+        return Ok(config);
+    }
+
+    // The ServerConfig these settings describe, checked against what the
+    // profile requires. Errors name the profile, if there is one.
+    pub fn server_config(&self) -> Result<ServerConfig, ConfigError> {
+        return self
+            .build_server_config()
+            .and_then(|config| {
+                self.check_requirements(&config)?;
+                return Ok(config);
+            })
+            .map_err(|e| e.in_profile(self.profile.as_deref()));
+    }
+
+    fn check_requirements(&self, config: &ServerConfig) -> Result<(), ConfigError> {
+        for requirement in &self.requirements {
+            let (met, keys) = match requirement.as_str() {
+                "tls" => (config.tls.is_some(), "tls_cert and tls_key"),
+                _ => (config.auth_url.is_some(), "auth_url"),
+            };
+            if !met {
+                return Err(ConfigError::Invalid(format!(
+                    "require = \"{}\" needs {} set",
+                    requirement, keys
+                )));
+            }
+        }
+        return Ok(());
+    }
+
+    fn build_server_config(&self) -> Result<ServerConfig, ConfigError> {
+        let mut config = ServerConfig::default();
+        let server = &self.server;
+        let secs = Duration::from_secs;
+        if let Some(addr) = &server.addr {
+            config.addr = addr.clone();
+        }
+        if let Some(http_addr) = &server.http_addr {
+            config.http_addr = match http_addr.as_str() {
+                "off" => None,
+                addr => Some(addr.to_string()),
+            };
+        }
+        if let Some(health_addr) = &server.health_addr {
+            config.health_addr = match health_addr.as_str() {
+                "off" => None,
+                addr => Some(addr.to_string()),
+            };
+        }
+        set(&mut config.live_tick_periods, server.live_tick_periods);
+        if let Some(tick_rate) = server.tick_rate {
+            check_tick_rate(tick_rate)?;
+            config.tick_rate = tick_rate;
+        }
+        set(&mut config.tick_window_secs, server.tick_window_secs);
+        set(&mut config.close_on_leave, server.close_on_leave);
+        set(&mut config.collect_stats, server.collect_stats);
+        set(&mut config.max_stats_entries, server.max_stats_entries);
+        if let Some(preset) = &server.physics_preset {
+            config.physics_preset = match preset.as_str() {
+                "accurate" => PhysicsPreset::Accurate,
+                "balanced" => PhysicsPreset::Balanced,
+                "fast" => PhysicsPreset::Fast,
+                other => {
+                    return Err(ConfigError::Invalid(format!(
+                        "unknown physics_preset '{}'",
+                        other
+                    )))
+                }
+            };
+        }
+        set(
+            &mut config.record_physics_stats,
+            server.record_physics_stats,
+        );
+        if let Some(max_impulse) = server.max_impulse {
+            config.control_mode = ControlMode::Impulse { max_impulse };
+        }
+        set(&mut config.min_state_rate_hz, server.min_state_rate_hz);
+        set(&mut config.max_state_rate_hz, server.max_state_rate_hz);
+        set(
+            &mut config.default_state_rate_hz,
+            server.default_state_rate_hz,
+        );
+        match server.degrade {
+            Some(true) => {
+                let mut degrade = config.degrade.take().unwrap_or_default();
+                set(&mut degrade.queue_high, server.degrade_queue_high);
+                set(
+                    &mut degrade.rtt_high,
+                    server.degrade_rtt_high_ms.map(Duration::from_millis),
+                );
+                set(
+                    &mut degrade.rtt_low,
+                    server.degrade_rtt_low_ms.map(Duration::from_millis),
+                );
+                config.degrade = Some(degrade);
+            }
+            Some(false) => config.degrade = None,
+            None => (),
+        }
+        set(&mut config.idle_timeout, server.idle_timeout_secs.map(secs));
+        if let Some(interval) = server.server_ping_interval_secs {
+            config.server_ping_interval = match interval {
+                0 => None,
+                interval => Some(secs(interval)),
+            };
+        }
+        if let Some(interval) = server.required_ping_interval_secs {
+            config.required_ping_interval = match interval {
+                0 => None,
+                interval => Some(secs(interval)),
+            };
+        }
+        if let Some(limit) = server.send_timeout_ms {
+            config.send_timeout = match limit {
+                0 => None,
+                limit => Some(Duration::from_millis(limit)),
+            };
+        }
+        if let Some(interval) = server.ws_ping_interval_secs {
+            config.ws_ping_interval = match interval {
+                0 => None,
+                interval => Some(secs(interval)),
+            };
+        }
+        set(
+            &mut config.handshake_timeout,
+            server.handshake_timeout_secs.map(secs),
+        );
+        set(
+            &mut config.ready_timeout,
+            server.ready_timeout_secs.map(secs),
+        );
+        set(
+            &mut config.dormant_timeout,
+            server.dormant_timeout_secs.map(secs),
+        );
+        if let Some(queue_timeout) = server.queue_timeout_secs {
+            config.queue_timeout = match queue_timeout {
+                0 => None,
+                queue_timeout => Some(secs(queue_timeout)),
+            };
+        }
+        if let Some(max_games) = server.max_games {
+            config.max_games = match max_games {
+                0 => None,
+                max_games => Some(max_games),
+            };
+        }
+        if let Some(full_queue_timeout) = server.full_queue_timeout_secs {
+            config.full_queue_timeout = match full_queue_timeout {
+                0 => None,
+                full_queue_timeout => Some(secs(full_queue_timeout)),
+            };
+        }
+        set(
+            &mut config.challenge_timeout,
+            server.challenge_timeout_secs.map(secs),
+        );
+        set(&mut config.max_message_size, server.max_message_size);
+        set(&mut config.max_frame_size, server.max_frame_size);
+        set(&mut config.max_echo_bytes, server.max_echo_bytes);
+        set(&mut config.echo_burst, server.echo_burst);
+        set(&mut config.echo_rate_hz, server.echo_rate_hz);
+        if let Some(max_connections) = server.max_connections_per_ip {
+            config.max_connections_per_ip = match max_connections {
+                0 => None,
+                max_connections => Some(max_connections),
+            };
+        }
+        set(&mut config.trusted_proxies, server.trusted_proxies.clone());
+        set(&mut config.allowed_origins, server.allowed_origins.clone());
+        set(&mut config.protocol_strikes, server.protocol_strikes);
+        set(&mut config.protocol_ban, server.protocol_ban_secs.map(secs));
+        if let Some(max_games) = server.max_games_per_identity {
+            config.max_games_per_identity = match max_games {
+                0 => None,
+                max_games => Some(max_games),
+            };
+        }
+        if let Some(admin_token) = &server.admin_token {
+            config.admin_token = Some(admin_token.clone());
+        }
+        set(&mut config.debug_ticks, server.debug_ticks);
+        config.check_invariants = match server.check_invariants.as_deref() {
+            None => config.check_invariants,
+            Some("off") => InvariantChecks::Off,
+            Some("log") => InvariantChecks::Log,
+            Some("panic") => InvariantChecks::Panic,
+            Some(other) => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown check_invariants '{}'",
+                    other
+                )))
+            }
+        };
+        set(&mut config.auth_header, server.auth_header.clone());
+        if let Some(auth_scheme) = &server.auth_scheme {
+            config.auth_scheme = match auth_scheme.as_str() {
+                "" => None,
+                auth_scheme => Some(auth_scheme.to_string()),
+            };
+        }
+        if let Some(auth_url) = &server.auth_url {
+            config.auth_url = match auth_url.as_str() {
+                "" => None,
+                auth_url => Some(auth_url.to_string()),
+            };
+        }
+        // the scheme decides which tokens the auth service ever sees, so a
+        // file that turns auth on says which one it expects
+        if config.auth_url.is_some() && server.auth_scheme.is_none() {
+            return Err(ConfigError::Invalid(
+                "auth_url needs auth_scheme set, \"Bearer\" or \"\" for a bare token".into(),
+            ));
+        }
+        set(&mut config.auth_timeout, server.auth_timeout_secs.map(secs));
+        set(
+            &mut config.max_auth_header_bytes,
+            server.max_auth_header_bytes,
+        );
+        set(&mut config.max_name_len, server.max_name_len);
+        set(&mut config.event_log_size, server.event_log_size);
+        set(&mut config.replay_ticks, server.replay_ticks);
+        set(&mut config.history_size, server.history_size);
+        set(&mut config.input_buffer_ticks, server.input_buffer_ticks);
+        if let Some(timeout) = server.lockstep_timeout_ms {
+            config.lockstep_timeout = match timeout {
+                0 => None,
+                timeout => Some(Duration::from_millis(timeout)),
+            };
+        }
+        if let Some(persist_path) = &server.persist_path {
+            config.persist_path = Some(PathBuf::from(persist_path));
+        }
+        set(
+            &mut config.persist_interval,
+            server.persist_interval_secs.map(secs),
+        );
+        if let Some(interval) = server.game_state_interval_secs {
+            config.game_state_interval = match interval {
+                0 => None,
+                interval => Some(secs(interval)),
+            };
+        }
+        if let Some(dir) = &server.frame_dump_dir {
+            config.frame_dump_dir = match dir.as_str() {
+                "" => None,
+                dir => Some(PathBuf::from(dir)),
+            };
+        }
+        if let Some(format) = &server.frame_dump_format {
+            config.frame_dump_format = DumpFormat::from_param(format).ok_or_else(|| {
+                ConfigError::Invalid(format!("unknown frame_dump_format '{}'", format))
+            })?;
+        }
+        if let Some(limit) = server.frame_dump_limit {
+            config.frame_dump_limit = match limit {
+                0 => None,
+                limit => Some(limit),
+            };
+        }
+        set(
+            &mut config.pause.pauses_per_player,
+            server.pauses_per_player,
+        );
+        set(&mut config.pause.max_pause, server.max_pause_secs.map(secs));
+        set(
+            &mut config.pause.resume_countdown,
+            server.resume_countdown_secs.map(secs),
+        );
+        let short_handed_timeout = match server.short_handed_timeout_secs {
+            Some(0) => None,
+            Some(timeout) => Some(secs(timeout)),
+            None => Some(DEFAULT_SHORT_HANDED_TIMEOUT),
+        };
+        set(
+            &mut config.pause.reconnect_notice_every,
+            server.reconnect_notice_secs.map(secs),
+        );
+        match server.short_handed.as_deref() {
+            None | Some("wait") => {
+                config.pause.short_handed = ShortHanded::Wait(short_handed_timeout)
+            }
+            Some("play") => config.pause.short_handed = ShortHanded::Play,
+            Some(other) => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown short_handed '{}'",
+                    other
+                )))
+            }
+        }
+        set(
+            &mut config.max_firehose_rate_hz,
+            server.max_firehose_rate_hz,
+        );
+        set(
+            &mut config.firehose_batch_bytes,
+            server.firehose_batch_bytes,
+        );
+        set(&mut config.warm_up, server.warm_up);
+        match server.duplicate_connection.as_deref() {
+            None | Some("reject") => (),
+            Some("replace") => config.duplicate_connection = DuplicateConnection::Replace,
+            Some(other) => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown duplicate_connection '{}'",
+                    other
+                )))
+            }
+        }
+        set(&mut config.soccer_pool_size, server.soccer_pool_size);
+        set(&mut config.disconnect_log_size, server.disconnect_log_size);
+        config.tls = match (&server.tls_cert, &server.tls_key) {
+            (None, None) => None,
+            (Some(cert), Some(key)) => Some(TlsFiles {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            }),
+            (Some(_), None) => return Err(ConfigError::Invalid("tls_cert needs a tls_key".into())),
+            (None, Some(_)) => return Err(ConfigError::Invalid("tls_key needs a tls_cert".into())),
+        };
+        if let Some(log_level) = &server.log_level {
+            config.log_level = Some(log_level.clone());
+        }
+        config.soccer = self.soccer_config()?;
+        validate(&config)?;
+        return Ok(config);
+    }
+
+    pub fn soccer_config(&self) -> Result<SoccerGameConfig, ConfigError> {
+        return self.soccer.to_config();
+    }
+}
+
+impl SoccerSection {
+    // The soccer config these settings describe, on top of the defaults.
+    pub fn to_config(&self) -> Result<SoccerGameConfig, ConfigError> {
+        return self.apply_to(SoccerGameConfig::default());
+    }
+
+    // The settings on top of base, which keeps everything the section leaves
+    // out. TuneSoccer applies the section it is sent to the current config
+    // this way. A reshaped field takes any goal size it isn't given from the
+    // defaults, since base only has its walls.
+    pub fn apply_to(&self, base: SoccerGameConfig) -> Result<SoccerGameConfig, ConfigError> {
+        let mut config = base;
+        let soccer = self;
+        let reshaped = soccer.width.is_some()
+            || soccer.height.is_some()
+            || soccer.goal_width.is_some()
+            || soccer.goal_depth.is_some()
+            || soccer.wall_thickness.is_some();
+        if reshaped {
+            set(&mut config.width, soccer.width);
+            set(&mut config.height, soccer.height);
+            let goal_width = soccer.goal_width.unwrap_or(GOAL_WIDTH);
+            let goal_depth = soccer.goal_depth.unwrap_or(GOAL_NET_DEPTH);
+            let thickness = soccer.wall_thickness.unwrap_or(WALL_THICKNESS);
+            if !(config.width > 0.0 && config.height > goal_width && goal_width > 0.0) {
+                return Err(ConfigError::Invalid(
+                    "field must be positive and taller than the goal".into(),
+                ));
+            }
+            if !(goal_depth > 0.0 && thickness > 0.0) {
+                return Err(ConfigError::Invalid(
+                    "goal_depth and wall_thickness must be positive".into(),
+                ));
+            }
+            config.walls = default_walls(
+                config.width,
+                config.height,
+                goal_width,
+                goal_depth,
+                thickness,
+            );
+        }
+        set(&mut config.pucks_per_team, soccer.pucks_per_team);
+        set(&mut config.balls, soccer.balls);
+        // the tunables share their checks with SetGameParams
+        let params = GameParams {
+            puck_radius: soccer.puck_radius,
+            puck_damping: soccer.puck_damping,
+            puck_restitution: soccer.puck_restitution,
+            max_shot_speed: soccer.max_shot_speed,
+            move_cooldown_ms: soccer.move_cooldown_ms,
+        };
+        validate_params(&params).map_err(|reason| ConfigError::Invalid(reason.to_string()))?;
+        config.apply_params(&params);
+        if let Some(max_body_speed) = soccer.max_body_speed {
+            if !(max_body_speed > 0.0) {
+                return Err(ConfigError::Invalid(
+                    "max_body_speed must be positive".into(),
+                ));
+            }
+            config.max_body_speed = Some(max_body_speed);
+        }
+        if let Some(damping) = soccer.ball_damping {
+            if !(damping >= 0.0 && damping.is_finite()) {
+                return Err(ConfigError::Invalid(
+                    "ball_damping must be a non-negative number".into(),
+                ));
+            }
+            config.ball_damping = Some(damping);
+        }
+        if let Some(drag) = soccer.quadratic_drag {
+            if !(drag >= 0.0 && drag.is_finite()) {
+                return Err(ConfigError::Invalid(
+                    "quadratic_drag must be a non-negative number".into(),
+                ));
+            }
+            config.quadratic_drag = drag;
+        }
+        if let Some(serve_speed) = soccer.serve_speed {
+            if !(serve_speed >= 0.0) {
+                return Err(ConfigError::Invalid("serve_speed can't be negative".into()));
+            }
+            config.serve_speed = Some(serve_speed).filter(|speed| *speed > 0.0);
+        }
+        if let Some(decay) = soccer.bounce_decay {
+            config.bounce_decay = Some(decay).filter(|decay| *decay != 1.0);
+        }
+        if let Some(window) = soccer.touch_window_ticks {
+            config.touch_window_ticks = Some(window).filter(|window| *window > 0);
+        }
+        if let Some(ticks) = soccer.turn_ticks {
+            config.turn_ticks = Some(ticks).filter(|ticks| *ticks > 0);
+        }
+        let dt_ms = soccer.physics_dt_ms.unwrap_or(1000.0 / 60.0);
+        if !(dt_ms > 0.0 && dt_ms.is_finite()) {
+            return Err(ConfigError::Invalid(
+                "physics_dt_ms must be positive".into(),
+            ));
+        }
+        config.stepping = match soccer.stepping.as_deref() {
+            Some("fixed") => Stepping::Fixed(dt_ms / 1000.0),
+            _ if soccer.physics_dt_ms.is_some() => {
+                return Err(ConfigError::Invalid(
+                    "physics_dt_ms only applies to stepping = \"fixed\"".into(),
+                ))
+            }
+            None => config.stepping,
+            Some("per_update") => Stepping::PerUpdate,
+            Some("variable") => Stepping::Variable,
+            Some(other) => {
+                return Err(ConfigError::Invalid(format!(
+                    "unknown stepping '{}', expected per_update, fixed or variable",
+                    other
+                )))
+            }
+        };
+        if let Some(colors) = &soccer.team_colors {
+            config.team_colors = colors.clone();
+        }
+        if let Some(secs) = soccer.match_secs {
+            config.match_duration = Some(Duration::from_secs(secs)).filter(|d| !d.is_zero());
+        }
+        if let Some(ms) = soccer.max_catch_up_ms {
+            if ms == 0 {
+                return Err(ConfigError::Invalid(
+                    "max_catch_up_ms must be positive".into(),
+                ));
+            }
+            config.max_catch_up = Duration::from_millis(ms);
+        }
+        set(&mut config.boost_speed, soccer.boost_speed);
+        set(
+            &mut config.boost_cooldown,
+            soccer.boost_cooldown_ms.map(Duration::from_millis),
+        );
+        set(&mut config.goal_reset_ticks, soccer.goal_reset_ticks);
+        set(
+            &mut config.kickoff_freeze_ticks,
+            soccer.kickoff_freeze_ticks,
+        );
+        set(&mut config.settle_ticks, soccer.settle_ticks);
+        if let Some(power_ups) = &soccer.power_ups {
+            let mut power_up_config = config.power_ups.clone().unwrap_or_default();
+            set(
+                &mut power_up_config.interval,
+                power_ups.interval_secs.map(Duration::from_secs),
+            );
+            set(
+                &mut power_up_config.duration,
+                power_ups.duration_secs.map(Duration::from_secs),
+            );
+            set(&mut power_up_config.max_on_field, power_ups.max_on_field);
+            set(
+                &mut power_up_config.big_puck_scale,
+                power_ups.big_puck_scale,
+            );
+            set(&mut power_up_config.speed_boost, power_ups.speed_boost);
+            config.power_ups = Some(power_up_config);
+        }
+        if let Some(anti_stall) = &soccer.anti_stall {
+            let mut anti_stall_config = config.anti_stall.clone().unwrap_or_default();
+            set(&mut anti_stall_config.speed, anti_stall.speed);
+            set(
+                &mut anti_stall_config.after,
+                anti_stall.after_ms.map(Duration::from_millis),
+            );
+            if !(anti_stall_config.speed > 0.0) {
+                return Err(ConfigError::Invalid(
+                    "anti_stall speed must be positive".into(),
+                ));
+            }
+            if let Some(radius) = anti_stall.pinned_radius {
+                if !(radius > 0.0) {
+                    return Err(ConfigError::Invalid(
+                        "anti_stall pinned_radius must be positive".into(),
+                    ));
+                }
+                anti_stall_config.pinned_radius = Some(radius);
+            }
+            let nudge_speed = anti_stall.nudge_speed.unwrap_or(STALL_NUDGE_SPEED);
+            anti_stall_config.action = match anti_stall.action.as_deref() {
+                None if anti_stall.nudge_speed.is_none() => anti_stall_config.action,
+                None | Some("nudge") if nudge_speed > 0.0 => StallAction::Nudge(nudge_speed),
+                None | Some("nudge") => {
+                    return Err(ConfigError::Invalid(
+                        "anti_stall nudge_speed must be positive".into(),
+                    ))
+                }
+                Some("recenter") => StallAction::Recenter,
+                Some("drop_ball") => StallAction::DropBall,
+                Some(other) => {
+                    return Err(ConfigError::Invalid(format!(
+                        "unknown anti_stall action '{}'",
+                        other
+                    )))
+                }
+            };
+            config.anti_stall = Some(anti_stall_config);
+        }
+        return Ok(config);
+    }
+}
+
+fn check_tick_rate(tick_rate: u64) -> Result<(), ConfigError> {
+    if !(1..=MAX_TICK_RATE).contains(&tick_rate) {
+        return Err(ConfigError::Invalid(format!(
+            "tick_rate must be between 1 and {}",
+            MAX_TICK_RATE
+        )));
+    }
+    return Ok(());
+}
+
+// The checks between settings that are each fine on their own, run on every
+// config server_config builds, so startup and reload refuse a combination
+// that can't work with what's wrong rather than failing inside a game.
+// ServerConfigs built in code can be passed through it too.
+pub fn validate(config: &ServerConfig) -> Result<(), ConfigError> {
+    check_tick_rate(config.tick_rate)?;
+    let (min, default, max) = (
+        config.min_state_rate_hz,
+        config.default_state_rate_hz,
+        config.max_state_rate_hz,
+    );
+    if max == 0 {
+        return Err(ConfigError::Invalid(
+            "max_state_rate_hz must be positive".into(),
+        ));
+    }
+    if !(min <= default && default <= max) {
+        return Err(ConfigError::Invalid(format!(
+            "default_state_rate_hz ({}) must be between min_state_rate_hz ({}) and max_state_rate_hz ({})",
+            default, min, max
+        )));
+    }
+    if let Some(degrade) = &config.degrade {
+        if degrade.rtt_low > degrade.rtt_high {
+            return Err(ConfigError::Invalid(format!(
+                "degrade_rtt_low_ms ({:?}) is above degrade_rtt_high_ms ({:?})",
+                degrade.rtt_low, degrade.rtt_high
+            )));
+        }
+    }
+    if config.max_frame_size > config.max_message_size {
+        return Err(ConfigError::Invalid(format!(
+            "max_frame_size ({}) is larger than max_message_size ({})",
+            config.max_frame_size, config.max_message_size
+        )));
+    }
+    if config.game_state_interval.is_some() && config.persist_path.is_none() {
+        return Err(ConfigError::Invalid(
+            "game_state_interval_secs needs a persist_path to save to".into(),
+        ));
+    }
+    if let Some(tls) = &config.tls {
+        for (key, path) in [("tls_cert", &tls.cert), ("tls_key", &tls.key)] {
+            if !path.is_file() {
+                return Err(ConfigError::Invalid(format!(
+                    "{} '{}' is not a readable file",
+                    key,
+                    path.display()
+                )));
+            }
+        }
+    }
+    if let Some(auth_url) = &config.auth_url {
+        match url::Url::parse(auth_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => (),
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "auth_url '{}' is not an http:// or https:// URL",
+                    auth_url
+                )))
+            }
+        }
+    }
+    config
+        .soccer
+        .validate()
+        .map_err(|reason| ConfigError::Invalid(format!("soccer: {}", reason)))?;
+    return Ok(());
+}
+
+// The named profile's settings and what it requires; a profile the file
+// doesn't have is an error rather than silently running without it.
+fn profile_section(
+    profiles: &toml::Table,
+    name: &str,
+) -> Result<(toml::Table, Vec<String>), ConfigError> {
+    let mut section = match profiles.get(name) {
+        Some(toml::Value::Table(section)) => section.clone(),
+        Some(_) => {
+            return Err(ConfigError::Invalid(format!(
+                "profiles.{} must be a section",
+                name
+            )))
+        }
+        None => {
+            let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            return Err(ConfigError::Invalid(format!(
+                "no profile '{}' in the file, expected one of: {}",
+                name,
+                names.join(", ")
+            )));
+        }
+    };
+    let mut requirements = vec![];
+    if let Some(require) = section.remove("require") {
+        let listed = require.as_array().cloned().unwrap_or_default();
+        for requirement in listed {
+            match requirement.as_str() {
+                Some(requirement) if REQUIREMENTS.contains(&requirement) => {
+                    requirements.push(requirement.to_string())
+                }
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "profiles.{}.require: expected a list of {}",
+                        name,
+                        REQUIREMENTS.join(", ")
+                    )))
+                }
+            }
+        }
+    }
+    return Ok((section, requirements));
+}
+
+// Every key a config file can set, in a JSON object of the same shape.
+fn known_keys() -> serde_json::Value {
+    let mut config = Config::default();
+    config.soccer.power_ups = Some(PowerUpSection::default());
+    config.soccer.anti_stall = Some(AntiStallSection::default());
+    return serde_json::to_value(config).unwrap_or_default();
+}
+
+fn is_known(known: &serde_json::Value, path: &[String]) -> bool {
+    return path
+        .iter()
+        .try_fold(known, |known, key| known.get(key))
+        .is_some();
+}
+
+// Drops the keys of table the server doesn't know, with a warning naming
+// each and where it came from.
+fn strip_unknown(
+    table: &mut toml::Table,
+    known: &serde_json::Value,
+    prefix: &str,
+    from: &str,
+    warnings: &mut Vec<String>,
+) {
+    let keys: Vec<String> = table.keys().cloned().collect();
+    for key in keys {
+        let path = format!("{}{}", prefix, key);
+        match known.get(&key) {
+            None => {
+                table.remove(&key);
+                warnings.push(format!("unknown key {} in {}", path, from));
+            }
+            Some(inner) if inner.is_object() => {
+                if let Some(toml::Value::Table(section)) = table.get_mut(&key) {
+                    strip_unknown(section, inner, &format!("{}.", path), from, warnings);
+                }
+            }
+            Some(_) => (),
+        }
+    }
+}
+
+// The first setting in table that doesn't deserialize on its own, with
+// why, so a bad value can be reported by its key.
+fn offending_key(table: &toml::Table, path: &mut Vec<String>) -> Option<(String, toml::de::Error)> {
+    for (key, value) in table.iter() {
+        path.push(key.clone());
+        let found = match value {
+            toml::Value::Table(inner) => offending_key(inner, path),
+            value => {
+                let mut alone = toml::Table::new();
+                set_path(&mut alone, path, value.clone());
+                toml::Value::Table(alone)
+                    .try_into::<Config>()
+                    .err()
+                    .map(|e| (path.join("."), e))
+            }
+        };
+        path.pop();
+        if found.is_some() {
+            return found;
+        }
+    }
+    return None;
+}
+
+// Lays overlay over base, section by section.
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(inner)), toml::Value::Table(value)) => merge(inner, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+// Env values are TOML literals when they parse as one ("60", "true",
+// "1.5"), otherwise plain strings, so addresses and tokens need no quoting.
+fn parse_env_value(raw: &str) -> toml::Value {
+    let wrapped = format!("value = {}", raw);
+    return match wrapped.parse::<toml::Table>() {
+        Ok(mut table) => table
+            .remove("value")
+            .unwrap_or_else(|| toml::Value::String(raw.to_string())),
+        Err(_) => toml::Value::String(raw.to_string()),
+    };
+}
+
+fn set_path(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    if rest.is_empty() {
+        table.insert(key.clone(), value);
+        return;
+    }
+    if !matches!(table.get(key), Some(toml::Value::Table(_))) {
+        table.insert(key.clone(), toml::Value::Table(toml::Table::new()));
+    }
+    if let Some(toml::Value::Table(inner)) = table.get_mut(key) {
+        set_path(inner, rest, value);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::message::DegradeLevel;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+// When a connection counts as struggling or as healthy, judged once a
+// tick from how many frames are still waiting in its outbox and its
+// smoothed RTT. Between the two it is neither, and stays where it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DegradeConfig {
+    // struggling with more than this many frames waiting, or a slower RTT
+    pub queue_high: usize,
+    pub rtt_high: Duration,
+    // healthy with at most this many waiting and a faster RTT
+    pub queue_low: usize,
+    pub rtt_low: Duration,
+    // ticks in a row either has to last before a step down, or up; up is
+    // meant to be the slower of the two
+    pub down_after_ticks: u32,
+    pub up_after_ticks: u32,
+    // how often a degrading connection is sent a Ping, busy or not, and how
+    // long its last RTT sample counts for
+    pub probe_every: Duration,
+    pub rtt_max_age: Duration,
+}
+
+impl Default for DegradeConfig {
+    fn default() -> Self {
+        return DegradeConfig {
+            queue_high: 4,
+            rtt_high: Duration::from_millis(300),
+            queue_low: 0,
+            rtt_low: Duration::from_millis(150),
+            down_after_ticks: 30,
+            up_after_ticks: 300,
+            probe_every: Duration::from_millis(500),
+            rtt_max_age: Duration::from_secs(5),
+        };
+    }
+}
+
+// Connections at each level right now, and steps taken since start, for
+// /metrics.
+static AT_LEVEL: [AtomicUsize; 4] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+pub static STEPS_DOWN: AtomicU64 = AtomicU64::new(0);
+pub static STEPS_UP: AtomicU64 = AtomicU64::new(0);
+
+pub fn connections_at(level: DegradeLevel) -> usize {
+    return AT_LEVEL[level as usize].load(Ordering::Relaxed);
+}
+
+// Steps one connection's snapshots down a DegradeLevel at a time while it
+// keeps struggling, and back up a level at a time once it has recovered.
+// Each step starts the count again, so a connection that hovers around a
+// threshold stays put rather than changing level every tick.
+#[derive(Debug)]
+pub struct Degrader {
+    config: DegradeConfig,
+    level: DegradeLevel,
+    // the furthest down this connection can follow
+    lowest: DegradeLevel,
+    // ticks in a row struggling, and healthy
+    struggling: u32,
+    healthy: u32,
+}
+
+impl Degrader {
+    pub fn new(config: DegradeConfig, lowest: DegradeLevel) -> Self {
+        AT_LEVEL[DegradeLevel::Full as usize].fetch_add(1, Ordering::Relaxed);
+        return Degrader {
+            config,
+            level: DegradeLevel::Full,
+            lowest,
+            struggling: 0,
+            healthy: 0,
+        };
+    }
+
+    pub fn level(&self) -> DegradeLevel {
+        return self.level;
+    }
+
+    pub fn config(&self) -> &DegradeConfig {
+        return &self.config;
+    }
+
+    // Takes this tick's signals and returns the level to send at, which
+    // is a step away from the last one at most, and never below the lowest
+    // it was made with. No RTT yet counts as fast.
+    pub fn observe(&mut self, queued: usize, rtt: Option<Duration>) -> DegradeLevel {
+        let rtt = rtt.unwrap_or_default();
+        let config = &self.config;
+        if queued > config.queue_high || rtt > config.rtt_high {
+            self.struggling = self.struggling.saturating_add(1);
+            self.healthy = 0;
+        } else if queued <= config.queue_low && rtt < config.rtt_low {
+            self.healthy = self.healthy.saturating_add(1);
+            self.struggling = 0;
+        } else {
+            self.struggling = 0;
+            self.healthy = 0;
+        }
+        if self.struggling >= config.down_after_ticks {
+            if let Some(lower) = self.level.lower().filter(|lower| *lower <= self.lowest) {
+                STEPS_DOWN.fetch_add(1, Ordering::Relaxed);
+                self.set_level(lower);
+            }
+        } else if self.healthy >= config.up_after_ticks {
+            if let Some(higher) = self.level.higher() {
+                STEPS_UP.fetch_add(1, Ordering::Relaxed);
+                self.set_level(higher);
+            }
+        }
+        return self.level;
+    }
+
+    fn set_level(&mut self, level: DegradeLevel) {
+        AT_LEVEL[self.level as usize].fetch_sub(1, Ordering::Relaxed);
+        AT_LEVEL[level as usize].fetch_add(1, Ordering::Relaxed);
+        self.level = level;
+        self.struggling = 0;
+        self.healthy = 0;
+    }
+}
+
+impl Drop for Degrader {
+    fn drop(&mut self) {
+        AT_LEVEL[self.level as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::message::CloseReason;
+use crate::traffic::TrafficSummary;
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+pub const DISCONNECT_LOG_SIZE: usize = 256;
+
+// How one connection ended, kept so "I keep getting kicked" can be looked
+// into after the fact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisconnectRecord {
+    // when it ended, and when it was accepted
+    pub at: SystemTime,
+    pub connected_at: SystemTime,
+    pub client_id: usize,
+    // the ?name= it asked for, if any, and who it was known as
+    pub name: Option<String>,
+    pub identity: String,
+    pub peer: SocketAddr,
+    // the game it was in when it ended
+    pub game_id: Option<usize>,
+    // None when the client went away rather than being closed
+    pub reason: Option<CloseReason>,
+    pub traffic: TrafficSummary,
+}
+
+// The most recent DisconnectRecords, oldest dropped first.
+#[derive(Default)]
+pub struct DisconnectLog {
+    records: Mutex<VecDeque<DisconnectRecord>>,
+}
+
+impl DisconnectLog {
+    pub fn push(&self, record: DisconnectRecord, capacity: usize) {
+        let mut records = self.records.lock().unwrap();
+        // a lowered capacity takes effect on the next push
+        while records.len() >= capacity.max(1) {
+            records.pop_front();
+        }
+        if capacity > 0 {
+            records.push_back(record);
+        }
+    }
+
+    // Newest first; a filter that is None matches everything.
+    pub fn find(&self, name: Option<&str>, ip: Option<IpAddr>) -> Vec<DisconnectRecord> {
+        return self
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|record| {
+                name.map_or(true, |name| {
+                    record.name.as_deref() == Some(name) || record.identity == name
+                })
+            })
+            .filter(|record| ip.map_or(true, |ip| record.peer.ip() == ip))
+            .cloned()
+            .collect();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.records.lock().unwrap().len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
@@ -0,0 +1,62 @@
+use crate::disconnects::DisconnectRecord;
+use crate::message::GameOverReason;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+// Lifecycle events published on the Server's event bus so an embedding
+// application can follow games without speaking the websocket protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    GameCreated {
+        game_id: usize,
+        created_at: SystemTime,
+    },
+    PlayerJoined {
+        game_id: usize,
+        player_index: usize,
+        name: String,
+    },
+    // `left` is true for an explicit LeaveGame and false for a dropped
+    // connection that may still rejoin its slot
+    PlayerLeft {
+        game_id: usize,
+        player_index: usize,
+        name: String,
+        left: bool,
+    },
+    GoalScored {
+        game_id: usize,
+        player_index: usize,
+    },
+    GameOver {
+        game_id: usize,
+        winner: Option<usize>,
+        reason: GameOverReason,
+    },
+    GameRemoved {
+        game_id: usize,
+    },
+    // restart_required names changed settings that were kept as they were
+    ConfigReloaded {
+        restart_required: Vec<String>,
+    },
+    ConfigReloadFailed {
+        reason: String,
+    },
+    // a determinism audit's shadow stopped matching its game at tick; body
+    // is the first one, in snapshot order, that ended up somewhere else
+    SimulationDiverged {
+        game_id: usize,
+        tick: u64,
+        body: Option<usize>,
+    },
+    // a connection ended, with the same record the disconnect log keeps
+    ConnectionClosed {
+        record: DisconnectRecord,
+    },
+}
+
+// Receivers that fall behind miss events instead of slowing the server down.
+pub type ServerEvents = broadcast::Sender<ServerEvent>;
+
+pub const EVENT_BUS_CAPACITY: usize = 256;
@@ -1,31 +1,474 @@
+use crate::audit::{AuditInput, DeterminismAudit, Divergence};
+use crate::frame_dump::FrameDumper;
+use crate::limiter::TokenBucket;
+use crate::message::{
+    quantize, quantize_position, AnnouncementMessage, BallResetMessage, BoostMessage, ByteOrder,
+    ChatMessage, CloseReason, ErrorCode, EventMessage, EventsSinceResponse, GameParams,
+    GamePausedMessage, GameResumingMessage, GoalScoredMessage, HandicapConfig, InterestGroup,
+    LockstepInputMessage, LockstepMove, LockstepStepMessage, MatchPhase, MessageType,
+    ModeChangedMessage, OpponentDisconnectedMessage, OwnPuck, PowerUpAction, PowerUpKind,
+    PowerUpMessage, ProtocolVersion, PuckOwner, ReplayBurstMessage, ReplayFrame, RosterEntry,
+    SetInterestMessage, SlotStats, SlotStatus, SnapshotHeader, SoccerMoveMessage, StatePayload,
+    TeamHandicap, TurnMessage, WaitingForPlayerMessage, WsMessage, LOCKSTEP_WINDOW,
+    MAX_REPLAY_FRAMES, QUANTIZED_ANGLE_SCALE, QUANTIZED_ANGVEL_SCALE, QUANTIZED_VELOCITY_SCALE,
+};
+use crate::middleware::MiddlewareChain;
+use crate::profiling::PhaseTimes;
+use crate::serializer::{CompactBinary, StateSerializer, StateView};
+use crate::stats::PlayerId;
+use crate::timers::{GameTimerId, GameTimers, TimerChange};
+use crate::traffic::ConnectionTraffic;
+use bytes::Bytes;
 use rapier2d::na::vector;
 use rapier2d::prelude::*;
-use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use uuid::Uuid;
 
 pub struct Client {
     pub id: usize,
-    pub last_ping_time: u64,
+    // last Ping or Pong from the client
+    pub last_ping: Instant,
+    // server Pings still waiting for their Pong, oldest first
+    outstanding_pings: VecDeque<(u32, Instant)>,
+    next_ping_id: u32,
+    // smoothed round trip measured with server Pings, and when its last
+    // sample came in
+    pub rtt: Option<Duration>,
+    rtt_at: Option<Instant>,
+    pub traffic: Arc<ConnectionTraffic>,
+    pub middleware: MiddlewareChain,
+    // the game being played, while there is one
+    pub commands: Option<CommandLink>,
+    // a newer connection to the same slot closing this one
+    evict: mpsc::UnboundedSender<CloseReason>,
+    pub evicted: mpsc::UnboundedReceiver<CloseReason>,
+    // server-wide messages for this connection, from ServerState::broadcast_all,
+    // and its game's messages for its slot alone
+    announce: mpsc::UnboundedSender<WsMessage>,
+    pub announcements: mpsc::UnboundedReceiver<WsMessage>,
+    // the close the server sent; the first one wins
+    pub close_reason: OnceLock<CloseReason>,
+    // Echoes this connection may still send; none until the server sets
+    // it from its config
+    pub echo_budget: Mutex<TokenBucket>,
+    // the bodies its snapshots carry, from SetInterest; all of them until
+    // then and again in every game it joins
+    pub interest: SetInterestMessage,
+    // how long one write to the socket may take; None until the server
+    // sets it from its config
+    pub send_timeout: Option<Duration>,
 }
+
+// Input for a game's single owner, the tick loop, which applies it in
+// arrival order right before the next step. Connections queue these rather
+// than take the game's write lock per message, so input never waits on an
+// update in progress and always lands on a step boundary.
+#[derive(Debug)]
+pub enum GameCommand {
+    // a refusal (paused, input frozen) goes back through replies
+    Move {
+        player: usize,
+        target: u8,
+        vx: f32,
+        vy: f32,
+        angular: f32,
+        seq: u32,
+        replies: mpsc::UnboundedSender<WsMessage>,
+    },
+    // applied as soon as it's taken off the queue, never held with moves;
+    // a refusal (cooldown, not your turn) goes back through replies
+    Boost {
+        player: usize,
+        target: u8,
+        dx: f32,
+        dy: f32,
+        replies: mpsc::UnboundedSender<WsMessage>,
+    },
+    // a lockstep game's input for one tick, held until that tick
+    Input {
+        player: usize,
+        tick: u64,
+        moves: Vec<LockstepMove>,
+        replies: mpsc::UnboundedSender<WsMessage>,
+    },
+}
+
+// commands a game holds between steps; past it moves are dropped, which a
+// client sending at its tick rate never sees
+pub const GAME_COMMAND_CAPACITY: usize = 256;
+
+// A connection's end of its game's command queue, and where the game sends
+// back what it refused.
+#[derive(Debug, Clone)]
+pub struct CommandLink {
+    pub commands: mpsc::Sender<GameCommand>,
+    pub replies: mpsc::UnboundedSender<WsMessage>,
+}
+
+impl CommandLink {
+    // False if the queue is full or the game is gone.
+    pub fn send_move(&self, player: usize, message: &SoccerMoveMessage) -> bool {
+        let command = GameCommand::Move {
+            player,
+            target: message.target,
+            vx: message.vx,
+            vy: message.vy,
+            angular: message.angular,
+            seq: message.seq,
+            replies: self.replies.clone(),
+        };
+        return self.commands.try_send(command).is_ok();
+    }
+
+    pub fn send_boost(&self, player: usize, message: &BoostMessage) -> bool {
+        let command = GameCommand::Boost {
+            player,
+            target: message.target,
+            dx: message.dx,
+            dy: message.dy,
+            replies: self.replies.clone(),
+        };
+        return self.commands.try_send(command).is_ok();
+    }
+
+    pub fn send_input(&self, player: usize, message: LockstepInputMessage) -> bool {
+        let command = GameCommand::Input {
+            player,
+            tick: message.tick as u64,
+            moves: message.moves,
+            replies: self.replies.clone(),
+        };
+        return self.commands.try_send(command).is_ok();
+    }
+}
+
+// a server Ping not answered within this long is given up on; a Pong for it
+// counts as unsolicited
+pub const PING_EXPIRY: Duration = Duration::from_secs(30);
+const MAX_OUTSTANDING_PINGS: usize = 8;
+
 impl Client {
     pub fn new(id: usize) -> Self {
+        let (evict, evicted) = mpsc::unbounded_channel();
+        let (announce, announcements) = mpsc::unbounded_channel();
         return Client {
             id,
-            last_ping_time: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            last_ping: Instant::now(),
+            outstanding_pings: VecDeque::new(),
+            next_ping_id: 0,
+            rtt: None,
+            rtt_at: None,
+            traffic: Arc::new(ConnectionTraffic::new()),
+            middleware: MiddlewareChain::default(),
+            commands: None,
+            evict,
+            evicted,
+            announce,
+            announcements,
+            close_reason: OnceLock::new(),
+            echo_budget: Mutex::new(TokenBucket::new(0, 0)),
+            interest: SetInterestMessage::default(),
+            send_timeout: None,
+        };
+    }
+    // What a game slot holds to reach this connection.
+    pub fn slot_connection(&self) -> SlotConnection {
+        return SlotConnection {
+            client_id: self.id,
+            evict: self.evict.clone(),
+            inbox: self.announce.clone(),
         };
     }
+    // Records a server Ping as sent and returns its id.
+    pub fn start_ping(&mut self) -> u32 {
+        let id = self.next_ping_id;
+        self.next_ping_id = self.next_ping_id.wrapping_add(1);
+        if self.outstanding_pings.len() == MAX_OUTSTANDING_PINGS {
+            self.outstanding_pings.pop_front();
+        }
+        self.outstanding_pings.push_back((id, Instant::now()));
+        return id;
+    }
+    // Matches a Pong to its Ping and folds the round trip into rtt. Pongs may
+    // arrive out of order, so only the matching entry is removed. None means
+    // no Ping with that id is outstanding: never sent, already answered or
+    // expired.
+    pub fn record_pong(&mut self, id: u32) -> Option<Duration> {
+        return self.record_pong_at(id, Instant::now());
+    }
+    // record_pong for a Pong that arrived at now.
+    pub fn record_pong_at(&mut self, id: u32, now: Instant) -> Option<Duration> {
+        self.outstanding_pings
+            .retain(|(_, sent)| now.duration_since(*sent) < PING_EXPIRY);
+        let position = self
+            .outstanding_pings
+            .iter()
+            .position(|(pending, _)| *pending == id)?;
+        let (_, sent) = self.outstanding_pings.remove(position)?;
+        let sample = now.duration_since(sent);
+        // same weighting TCP uses for its smoothed RTT
+        self.rtt = Some(match self.rtt {
+            Some(rtt) => (rtt * 7 + sample) / 8,
+            None => sample,
+        });
+        self.rtt_at = Some(now);
+        self.update_ping();
+        return Some(sample);
+    }
+    // rtt while its last sample is no older than max_age, or how long the
+    // oldest Ping sent since has gone unanswered if that is longer, so a
+    // connection that stops answering doesn't keep the speed it last had.
+    // One that never answered has no RTT at all.
+    pub fn recent_rtt(&self, max_age: Duration) -> Option<Duration> {
+        let now = Instant::now();
+        let at = self.rtt_at?;
+        let measured = self.rtt.filter(|_| now.duration_since(at) <= max_age);
+        let waiting = self
+            .outstanding_pings
+            .iter()
+            .find(|(_, sent)| *sent > at)
+            .map(|(_, sent)| now.duration_since(*sent));
+        return measured.max(waiting);
+    }
     pub fn update_ping(&mut self) {
-        self.last_ping_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
+        self.last_ping = Instant::now();
+    }
+}
+
+// Every open connection, whatever it is doing, so a message can reach all
+// of them. Each connection is registered from the moment it is admitted
+// until it ends, and picks up what was sent to it between its other work.
+#[derive(Default)]
+pub struct Connections {
+    connections: Mutex<HashMap<usize, SlotConnection>>,
+}
+
+impl Connections {
+    pub fn insert(&self, connection: SlotConnection) {
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(connection.client_id, connection);
+    }
+
+    pub fn remove(&self, client_id: usize) {
+        self.connections.lock().unwrap().remove(&client_id);
+    }
+
+    // Queues message for every connection and returns how many took it. A
+    // connection that went away without being removed is dropped here
+    // rather than failing the rest.
+    pub fn broadcast(&self, message: &WsMessage) -> usize {
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|_, connection| connection.inbox.send(message.clone()).is_ok());
+        return connections.len();
+    }
+
+    // Tells a connection it has to close, wherever it is. False if it is
+    // gone.
+    pub fn evict(&self, client_id: usize, reason: CloseReason) -> bool {
+        return self
+            .connections
+            .lock()
             .unwrap()
-            .as_secs();
+            .get(&client_id)
+            .map_or(false, |connection| connection.evict.send(reason).is_ok());
+    }
+}
+
+// The line a session leaves in the log however it ended, so its traffic
+// can be looked at afterwards without the metrics. Every connection leaves
+// one, so it is only shown at debug level.
+impl Drop for Client {
+    fn drop(&mut self) {
+        let traffic = self.traffic.summary();
+        log::debug!(
+            "Client {} closed after {:?}: {} messages ({} bytes) in, {} messages ({} bytes) out",
+            self.id,
+            traffic.duration,
+            traffic.messages_in,
+            traffic.bytes_in,
+            traffic.messages_out,
+            traffic.bytes_out
+        );
+    }
+}
+// A player's slot is assigned by the server when they join and never derived
+// from their position in the roster, so names can't shift puck ownership.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    pub name: String,
+    pub index: usize,
+    pub connected: bool,
+    // pushed State snapshot rate chosen with Subscribe; 0 means the client
+    // only gets state when it asks for it
+    pub state_rate_hz: u8,
+    pub ready: bool,
+    // seq of the last SoccerMove applied for this player
+    pub last_move_seq: u32,
+    // slot driven by the server rather than a connection
+    pub bot: bool,
+    // proves a connection owns this slot; handed out in Welcome and required
+    // to reclaim it or to leave. Dies with the game.
+    session_token: String,
+    // slots whose chat isn't relayed to this player
+    pub muted: HashSet<usize>,
+    // smoothed round trip from server Pings; None until one is answered
+    pub rtt_ms: Option<u32>,
+    // what the connection in this slot has sent and been sent; None for
+    // bots and until someone connects
+    pub traffic: Option<Arc<ConnectionTraffic>>,
+    // the connection bound to the slot, so a newer one can push it out
+    pub connection: Option<SlotConnection>,
+    // who holds the slot; name is only what they show
+    pub id: PlayerId,
+}
+
+// One slot of a game as roster sees it; name and id are None while it's
+// empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerSlot {
+    pub index: usize,
+    pub name: Option<String>,
+    pub id: Option<PlayerId>,
+    pub status: SlotStatus,
+}
+
+impl PlayerSlot {
+    pub fn to_entry(&self) -> RosterEntry {
+        return RosterEntry {
+            index: self.index as u8,
+            name: self.name.clone().unwrap_or_default(),
+            status: self.status,
+        };
+    }
+}
+
+// The connection playing in a slot: its id, where to tell it that it has
+// to close, and where to send it what only that slot should see.
+#[derive(Debug, Clone)]
+pub struct SlotConnection {
+    pub client_id: usize,
+    pub evict: mpsc::UnboundedSender<CloseReason>,
+    pub inbox: mpsc::UnboundedSender<WsMessage>,
+}
+
+// Two are the same binding when they are the same connection.
+impl PartialEq for SlotConnection {
+    fn eq(&self, other: &Self) -> bool {
+        return self.client_id == other.client_id;
+    }
+}
+
+// What happens when a player connects to a slot that already has a live
+// connection.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DuplicateConnection {
+    // the new connection is refused with AlreadyConnected
+    #[default]
+    Reject,
+    // with the slot's session token the new connection takes over and the
+    // old one is closed with ReplacedByNewConnection
+    Replace,
+}
+
+// splitmix64: tiny and fully determined by its seed, so clients handed the
+// seed can replay any random choice the server made for a game.
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        return GameRng { state: seed };
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        return z ^ (z >> 31);
+    }
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        return (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+    }
+}
+
+static SEED_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn fresh_seed() -> u64 {
+    // the counter alone still gives distinct seeds on a clock before the
+    // epoch
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let mut rng = GameRng::new(nanos ^ SEED_COUNTER.fetch_add(1, Ordering::Relaxed));
+    return rng.next_u64();
+}
+
+// 32 random bytes, hex encoded: two v4 UUIDs, which uuid fills from the OS
+// RNG, with their version and variant bits being the only fixed ones.
+fn session_token() -> String {
+    return format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+}
+
+// Compares every byte regardless of where the first mismatch is, so response
+// timing doesn't reveal how much of a guessed token was right.
+pub fn tokens_match(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    return a
+        .bytes()
+        .zip(b.bytes())
+        .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+        == 0;
+}
+
+// Ordered by id so every tick updates games in the same order, keeping logs
+// and profiles comparable across runs. Lookups become O(log n), which is
+// nothing next to a physics step at the game counts one server runs.
+pub type Games = Arc<RwLock<BTreeMap<usize, Arc<RwLock<Game>>>>>;
+
+// Where a game reads the time for its timers and update steps. Instant is
+// monotonic, so only a substituted clock can step backwards.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        return Instant::now();
     }
 }
-pub type Games = Arc<RwLock<HashMap<usize, Arc<RwLock<Game>>>>>;
+
+// Who a message from GameLogic::take_messages is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipient {
+    // the connection holding this slot, if it has one
+    Slot(usize),
+    // every slot's connection, but not as a logged Event
+    AllPlayers,
+    // everyone attached to the game, as a logged Event like take_events
+    Everyone,
+}
+
+// Most messages a game's logic can send in one update; the rest are dropped
+// and logged.
+pub const MAX_LOGIC_MESSAGES: usize = 64;
 
 pub trait GameLogic: Send + Sync {
     fn game_type(&self) -> u8;
@@ -33,219 +476,4603 @@ pub trait GameLogic: Send + Sync {
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
     fn update(&mut self, elapsed: f64);
     fn to_bytes(&self) -> Vec<u8>;
+    // slots that scored since the last call
+    fn take_goals(&mut self) -> Vec<usize> {
+        return vec![];
+    }
+    // messages to broadcast to everyone in the game since the last call
+    fn take_events(&mut self) -> Vec<WsMessage> {
+        return vec![];
+    }
+    // messages for some of the game's connections since the last call, from
+    // update, on_timer or input alike; delivered after each update, at most
+    // MAX_LOGIC_MESSAGES of them
+    fn take_messages(&mut self) -> Vec<(Recipient, WsMessage)> {
+        return vec![];
+    }
+    // entries for the game's debug history since the last call; goals are
+    // recorded from take_goals already
+    fn take_history(&mut self) -> Vec<HistoryEvent> {
+        return vec![];
+    }
+    // called once with the game's seed before the first update
+    fn reseed(&mut self, _seed: u64) {}
+    // the roster is full, and the ready check can start, at this many
+    fn max_players(&self) -> usize {
+        return PLAYERS_PER_GAME;
+    }
+    // hands a player slot over to server-side control
+    fn add_bot(&mut self, _player: usize) {}
+    // A slot got a connection back. Whatever it missed meanwhile and still
+    // needs goes out through take_messages.
+    fn rejoined(&mut self, _player: usize) {}
+    // team a slot plays for, or None for game types without teams
+    fn team_of(&self, _player: usize) -> Option<u8> {
+        return None;
+    }
+    // full state to record for goal replays; None for game types without
+    // them
+    fn replay_frame(&self) -> Option<Vec<u8>> {
+        return None;
+    }
+    // Turns warm-up physics on while a game waits for an opponent, and off
+    // again, back at kickoff, when the match is about to start. Warm-up
+    // play never scores or records anything. False if the game type has no
+    // warm-up, in which case it stays frozen while waiting.
+    fn warm_up(&mut self, _on: bool) -> bool {
+        return false;
+    }
+    // Match time left, for game types that play against a clock; None for
+    // ones that end some other way.
+    fn remaining_time(&self) -> Option<Duration> {
+        return None;
+    }
+    // goals or points per team, in team order; empty without teams
+    fn scores(&self) -> Vec<u32> {
+        return vec![];
+    }
+    // A timer asked for with take_timer_changes went off. Called at the
+    // start of an update, before input is applied or anything stepped.
+    fn on_timer(&mut self, _id: GameTimerId) {}
+    // timers to set or cancel since the last call
+    fn take_timer_changes(&mut self) -> Vec<TimerChange> {
+        return vec![];
+    }
+    // whose each puck is, in State order; empty without pucks
+    fn puck_owners(&self) -> Vec<PuckOwner> {
+        return vec![];
+    }
+    // What embedders and the admin listing see of this game without
+    // knowing its type. A game only needs to fill details; Game::describe
+    // sets the generic fields.
+    fn describe(&self) -> GameDescription {
+        return GameDescription {
+            game_type: self.game_type(),
+            ..GameDescription::default()
+        };
+    }
 }
 
-pub struct Game {
+// What a game is left with once its logic went back to a pool.
+struct Retired {
+    game_type: u8,
+}
+
+impl GameLogic for Retired {
+    fn game_type(&self) -> u8 {
+        return self.game_type;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, _elapsed: f64) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        return vec![];
+    }
+}
+
+// A typed summary of any game: the fields every game has, and details as
+// each game type sees fit (soccer puts its scores and ball there).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GameDescription {
     pub game_type: u8,
-    pub last_update_ms: u128,
-    pub logic: Box<dyn GameLogic>,
-    pub players: Vec<String>,
+    // GamePhase::as_str
+    pub phase: String,
+    // TickMode::as_str, or "slowed" while the whole server loop is
+    pub tick_mode: String,
+    pub tick: u64,
+    pub max_players: usize,
+    pub players: Vec<SlotDescription>,
+    pub details: serde_json::Value,
 }
 
-impl Game {
-    pub fn new<G: GameLogic + 'static>(logic: G, players: Vec<String>) -> Self {
-        let game_type = logic.game_type();
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SlotDescription {
+    pub index: usize,
+    pub name: String,
+    pub connected: bool,
+    pub ready: bool,
+    pub bot: bool,
+}
 
-        Self {
-            game_type,
-            last_update_ms: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_millis(),
-            logic: Box::new(logic),
-            players,
-        }
-    }
+// How a game is ticked. Anything but Normal is for debugging physics, set
+// by an admin: updates then come slower than the server loop, or only on
+// request, and count as one normal tick of game time each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TickMode {
+    Normal,
+    // at most one update this often
+    Slowed { every: Duration },
+    // only by Game::step
+    Manual,
+}
 
-    pub fn downcast<G: 'static>(&self) -> Option<&G> {
-        self.logic.as_any().downcast_ref::<G>()
+impl TickMode {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            TickMode::Normal => "normal",
+            TickMode::Slowed { .. } => "slowed",
+            TickMode::Manual => "manual",
+        };
     }
+}
 
-    pub fn downcast_mut<G: 'static>(&mut self) -> Option<&mut G> {
-        self.logic.as_any_mut().downcast_mut::<G>()
-    }
-    pub fn update(&mut self) {
-        let elapsed = self.get_and_update_duration() as f64;
-        self.logic.update(elapsed);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamePhase {
+    // waiting for every player to send Ready; the deadline starts once the
+    // roster is full
+    ReadyCheck {
+        deadline: Option<Instant>,
+    },
+    Playing,
+    Paused {
+        by: usize,
+        until: Instant,
+    },
+    // counting down to Playing after a pause
+    Resuming {
+        at: Instant,
+    },
+    // a full match lost a connection; missing forfeits once the game
+    // reaches deadline_tick, or the game waits for them indefinitely with
+    // None. A dormant game's ticks stand still, and so does the wait
+    WaitingForPlayers {
+        missing: usize,
+        deadline_tick: Option<u64>,
+    },
+}
+
+impl GamePhase {
+    // The same phase with every deadline pushed back by delay.
+    fn delayed(self, delay: Duration) -> GamePhase {
+        return match self {
+            GamePhase::ReadyCheck { deadline } => GamePhase::ReadyCheck {
+                deadline: deadline.map(|deadline| deadline + delay),
+            },
+            // a wait for a rejoin counts ticks, which stood still too
+            phase @ (GamePhase::Playing | GamePhase::WaitingForPlayers { .. }) => phase,
+            GamePhase::Paused { by, until } => GamePhase::Paused {
+                by,
+                until: until + delay,
+            },
+            GamePhase::Resuming { at } => GamePhase::Resuming { at: at + delay },
+        };
     }
-    pub fn get_and_update_duration(&mut self) -> u128 {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        let duration = now - self.last_update_ms;
-        self.last_update_ms = now;
-        return duration;
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            GamePhase::ReadyCheck { .. } => "ready_check",
+            GamePhase::Playing => "playing",
+            GamePhase::Paused { .. } => "paused",
+            GamePhase::Resuming { .. } => "resuming",
+            GamePhase::WaitingForPlayers { .. } => "waiting_for_players",
+        };
     }
 }
 
-pub struct SoccerGame {
-    pub pipeline: PhysicsPipeline,
-    pub integration_parameters: IntegrationParameters,
-    pub island_manager: IslandManager,
-    pub broad_phase: DefaultBroadPhase,
-    pub colliders: ColliderSet,
-    pub narrow_phase: NarrowPhase,
-    pub bodies: RigidBodySet,
-    pub pucks: [RigidBodyHandle; 10],
-    pub ball: RigidBodyHandle,
-    pub impulse_joints: ImpulseJointSet,
-    pub multibody_joints: MultibodyJointSet,
-    pub ccd_solver: CCDSolver,
+// What a full match does when a dropped connection leaves it short of
+// players.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShortHanded {
+    // carry on without them
+    Play,
+    // freeze until they rejoin; after the timeout, if there is one, they
+    // forfeit
+    Wait(Option<Duration>),
 }
 
-const RADIUS: f32 = 20.0;
-impl SoccerGame {
-    pub fn new() -> Self {
-        let integration_parameters = IntegrationParameters::default();
-        let mut physics_pipeline = PhysicsPipeline::new();
-        let mut broad_phase = DefaultBroadPhase::new();
-        let mut island_manager = IslandManager::new();
-        let mut narrow_phase = NarrowPhase::new();
-        let mut bodies = RigidBodySet::new();
-        let mut colliders = ColliderSet::new();
-        let mut impulse_joints = ImpulseJointSet::new();
-        let mut multibody_joints = MultibodyJointSet::new();
-        let mut ccd_solver = CCDSolver::new();
-        // Function to create a moving ball
-        let mut create_circle = |x: f32, y: f32| -> RigidBodyHandle {
-            let body = bodies.insert(
-                RigidBodyBuilder::dynamic()
-                    .translation(vector![x, y]) // Start position
-                    .linvel(vector![0.0, 0.0]) // Initial velocity
-                    .linear_damping(0.1) // friction
-                    .build(),
-            );
-            let collider = colliders.insert_with_parent(
-                ColliderBuilder::ball(RADIUS) // Circle with radius 1.0
-                    .restitution(1.0) // Perfectly elastic bounce
-                    .build(),
-                body,
-                &mut bodies,
-            );
-            return body;
-        };
-        let game_width: f32 = 600.0; // X-axis boundaries
-        let game_height: f32 = 600.0;
-        let mut start: f32 = -200.0;
-        let mut pucks = vec![];
-        for i in 0..3 {
-            let puck = create_circle(-200.0, start);
-            pucks.push(puck);
-            start += 200.0;
-        }
-        let puck11 = create_circle(-50.0, -150.0);
-        let puck12 = create_circle(-50.0, 150.0);
-        pucks.push(puck11);
-        pucks.push(puck12);
-        start = -200.0;
-        for i in 0..3 {
-            let puck2 = create_circle(200.0, start);
-            pucks.push(puck2);
-            start += 200.0;
-        }
-        let puck21 = create_circle(50.0, 150.0);
-        let puck22 = create_circle(50.0, -150.0);
-        pucks.push(puck21);
-        pucks.push(puck22);
-
-        let ball = create_circle(0.0, 0.0);
-        let wall_thickness = 1.0; //
-
-        // Create walls
-        let mut create_wall = |position: Vector<f32>, size: Vector<f32>| {
-            let body = bodies.insert(RigidBodyBuilder::fixed().translation(position).build());
-            colliders.insert_with_parent(
-                ColliderBuilder::cuboid(size.x, size.y)
-                    .restitution(0.7) // Optional: Bounciness
-                    .friction(0.4) // Optional: Surface friction
-                    .build(),
-                body,
-                &mut bodies,
-            );
+// how long a match waits for a dropped player when no timeout is given
+pub const DEFAULT_SHORT_HANDED_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct PauseConfig {
+    pub pauses_per_player: u8,
+    pub max_pause: Duration,
+    pub resume_countdown: Duration,
+    pub short_handed: ShortHanded,
+    // how often the players left in a match waiting for a rejoin are sent
+    // an OpponentDisconnected with what is left of the wait
+    pub reconnect_notice_every: Duration,
+}
+
+impl Default for PauseConfig {
+    fn default() -> Self {
+        return PauseConfig {
+            pauses_per_player: 2,
+            max_pause: Duration::from_secs(60),
+            resume_countdown: Duration::from_secs(3),
+            short_handed: ShortHanded::Wait(Some(DEFAULT_SHORT_HANDED_TIMEOUT)),
+            reconnect_notice_every: Duration::from_secs(5),
         };
+    }
+}
 
-        create_wall(
-            vector![-game_width / 2.0 - wall_thickness, 0.0],
-            vector![wall_thickness, game_height / 2.0],
-        );
+pub const PLAYERS_PER_GAME: usize = 2;
+pub const GAME_TOKEN_LEN: usize = 8;
 
-        create_wall(
-            vector![game_width / 2.0 + wall_thickness, 0.0],
-            vector![wall_thickness, game_height / 2.0],
-        );
+pub struct Game {
+    pub game_type: u8,
+    pub last_update: Instant,
+    // when the tick loop last ran update, whether or not it stepped anything
+    last_visit: Instant,
+    clock: Arc<dyn Clock>,
+    pub logic: Box<dyn GameLogic>,
+    pub players: Vec<Player>,
+    pub phase: GamePhase,
+    pub pause_config: PauseConfig,
+    // made for ?practice=1; never offered to matchmaking
+    pub practice: bool,
+    // started by an accepted challenge; nobody but its own players is
+    // matched or let into it
+    pub private: bool,
+    // let a lone player move around while the game waits for an opponent,
+    // if the game type supports it
+    pub warm_up: bool,
+    // warm-up physics are running
+    warming_up: bool,
+    pub duplicate_connection: DuplicateConnection,
+    // how long a full game waits for Ready before starting anyway
+    pub ready_timeout: Duration,
+    // all server-side randomness for this game comes from rng, which is
+    // seeded from seed; clients get the seed in Welcome
+    pub seed: u64,
+    pub rng: GameRng,
+    // when the game was made, for listings and logs
+    pub created_at: SystemTime,
+    // short random tag handed out with the id, so a link naming this game
+    // can't land in a later game that happens to share the id
+    pub token: String,
+    pauses_used: HashMap<usize, u8>,
+    // broadcasts kept for EventsSince; 0 keeps none
+    pub event_log_size: usize,
+    closed: watch::Sender<bool>,
+    // when close was first called, by the game's clock
+    closed_at: OnceLock<Instant>,
+    events: broadcast::Sender<Bytes>,
+    event_log: Mutex<EventLog>,
+    chat: broadcast::Sender<ChatLine>,
+    ticks: watch::Sender<u64>,
+    // updates of play kept for the replay sent after a goal; 0 records
+    // nothing
+    pub replay_ticks: usize,
+    replay: VecDeque<ReplayFrame>,
+    // writes every tick to a file for offline rendering, until it is full
+    frame_dumper: Option<FrameDumper>,
+    // what the logic asked to be told at a later tick, delivered to
+    // on_timer at the start of the update that reaches it
+    pub timers: GameTimers,
+    // a shadow copy stepped alongside to catch non-determinism; debug only
+    audit: Option<DeterminismAudit>,
+    // what the audit found on the last tick, until taken
+    diverged: Option<Divergence>,
+    // entries kept in the debug history
+    pub history_size: usize,
+    history: VecDeque<HistoryEntry>,
+    // scoring slots since the last take_goals
+    goals: Vec<usize>,
+    // a slot that waited out its reconnect timeout, until take_forfeit
+    forfeit: Option<usize>,
+    // the match clock ran out on the last update, until take_time_up
+    time_up: bool,
+    // since when nobody has been connected; updates do nothing meanwhile
+    dormant_since: Option<Instant>,
+    // firehose connections watching, shared by every game on the server
+    pub spectators: Arc<AtomicUsize>,
+    // the tick the next OpponentDisconnected goes out on
+    next_reconnect_notice: u64,
+    // logic.to_bytes() and the tick it was taken on, shared by everyone
+    // who wants the view-less State that tick
+    snapshot: Mutex<Option<(u64, Bytes)>>,
+    // logic.describe() and the tick it was taken on, so listing every game
+    // doesn't rebuild each one's details more than once a tick
+    described: Mutex<Option<(u64, GameDescription)>>,
+    // nanoseconds spent in to_bytes since the last update
+    encode_nanos: AtomicU64,
+    // where the last update spent its time
+    phase_times: PhaseTimes,
+    // GameCommands queued since the last update
+    commands: mpsc::Sender<GameCommand>,
+    command_queue: mpsc::Receiver<GameCommand>,
+    // updates a move waits between arriving and being applied, so moves
+    // delivered in bursts still land one a tick; 0 applies each on the
+    // update after it arrives
+    pub input_buffer_ticks: u64,
+    // moves held back by input_buffer_ticks, by player and target puck,
+    // each with the tick it is due on
+    input_buffer: BTreeMap<(usize, u8), VecDeque<(u64, GameCommand)>>,
+    // Some makes the game lockstep: in play, an update waits until every
+    // connected player has sent LockstepInput for its tick, or this long,
+    // and then applies them together with one fixed tick of game time
+    pub lockstep: Option<Duration>,
+    // LockstepInputs not yet applied, by tick and then player
+    lockstep_inputs: BTreeMap<u64, BTreeMap<usize, Vec<LockstepMove>>>,
+    // since when the current tick has been waiting for input
+    lockstep_waiting: Option<Instant>,
+    tick_mode: TickMode,
+    // when a Slowed game is next updated
+    next_tick: Option<Instant>,
+    // the server loop itself is running at a debug rate
+    pub slow_motion: bool,
+    // game time one normal tick stands for, counted per update instead of
+    // the time that really passed when the game isn't ticking normally
+    pub tick_ms: f64,
+}
 
-        create_wall(
-            vector![0.0, game_height / 2.0 + wall_thickness],
-            vector![game_width / 2.0, wall_thickness],
-        );
+// A chat line on its way to a game's connections. Each connection decides
+// whether to send it on, by team and by its player's mutes.
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub from: usize,
+    // Some for team chat
+    pub team: Option<u8>,
+    pub frame: Bytes,
+}
 
-        create_wall(
-            vector![0.0, -game_height / 2.0 - wall_thickness],
-            vector![game_width / 2.0, wall_thickness],
-        );
+pub const EVENT_LOG_SIZE: usize = 64;
 
-        SoccerGame {
-            pipeline: physics_pipeline,
-            colliders,
-            bodies,
-            pucks: pucks.try_into().unwrap(),
-            ball,
-            narrow_phase,
-            integration_parameters,
-            broad_phase,
-            island_manager,
-            impulse_joints,
-            multibody_joints,
-            ccd_solver,
+// The most recent broadcast frames with their sequence numbers, oldest
+// first.
+#[derive(Default)]
+struct EventLog {
+    last_seq: u64,
+    frames: VecDeque<(u64, Bytes)>,
+}
+
+pub const HISTORY_SIZE: usize = 100;
+
+// Something that happened to a game, kept so a report about a strange match
+// can be checked against what the server saw.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryEvent {
+    Joined { player: usize, name: String },
+    Rejoined { player: usize, name: String },
+    Disconnected { player: usize },
+    Left { player: usize },
+    Forfeit { winner: usize },
+    // the match clock ran out; None for a draw
+    TimeUp { winner: Option<usize> },
+    Goal { player: usize },
+    // whose puck last touched the ball that went in; own_goal when that was
+    // the defending side's
+    GoalCredit { player: usize, own_goal: bool },
+    // bodies put back on their kickoff spots, after a goal or by the
+    // watchdog
+    Reset { reason: &'static str },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    // wall clock, ms since the epoch
+    pub at_ms: u128,
+    pub tick: u64,
+    pub event: HistoryEvent,
+}
+
+impl Game {
+    pub fn new<G: GameLogic + 'static>(logic: G, players: Vec<(PlayerId, String)>) -> Self {
+        return Game::with_logic(Box::new(logic), players);
+    }
+
+    pub fn with_logic(mut logic: Box<dyn GameLogic>, players: Vec<(PlayerId, String)>) -> Self {
+        let game_type = logic.game_type();
+        let seed = fresh_seed();
+        logic.reseed(seed);
+        let (commands, command_queue) = mpsc::channel(GAME_COMMAND_CAPACITY);
+
+        let mut game = Self {
+            game_type,
+            last_update: Instant::now(),
+            last_visit: Instant::now(),
+            clock: Arc::new(MonotonicClock),
+            logic,
+            players: players
+                .into_iter()
+                .enumerate()
+                .map(|(index, (id, name))| Player {
+                    id,
+                    name,
+                    index,
+                    connected: true,
+                    state_rate_hz: 0,
+                    ready: false,
+                    last_move_seq: 0,
+                    bot: false,
+                    session_token: session_token(),
+                    muted: HashSet::new(),
+                    rtt_ms: None,
+                    traffic: None,
+                    connection: None,
+                })
+                .collect(),
+            phase: GamePhase::ReadyCheck { deadline: None },
+            pause_config: PauseConfig::default(),
+            practice: false,
+            private: false,
+            warm_up: false,
+            warming_up: false,
+            duplicate_connection: DuplicateConnection::default(),
+            ready_timeout: Duration::from_secs(10),
+            seed,
+            rng: GameRng::new(seed),
+            created_at: SystemTime::now(),
+            token: Uuid::new_v4().simple().to_string()[..GAME_TOKEN_LEN].to_string(),
+            pauses_used: HashMap::new(),
+            event_log_size: EVENT_LOG_SIZE,
+            replay_ticks: 0,
+            replay: VecDeque::new(),
+            frame_dumper: None,
+            timers: GameTimers::default(),
+            audit: None,
+            diverged: None,
+            closed: watch::channel(false).0,
+            closed_at: OnceLock::new(),
+            events: broadcast::channel(64).0,
+            event_log: Mutex::new(EventLog::default()),
+            chat: broadcast::channel(64).0,
+            ticks: watch::channel(0).0,
+            history_size: HISTORY_SIZE,
+            history: VecDeque::new(),
+            goals: vec![],
+            forfeit: None,
+            time_up: false,
+            dormant_since: None,
+            spectators: Arc::new(AtomicUsize::new(0)),
+            next_reconnect_notice: 0,
+            snapshot: Mutex::new(None),
+            described: Mutex::new(None),
+            encode_nanos: AtomicU64::new(0),
+            phase_times: PhaseTimes::default(),
+            commands,
+            command_queue,
+            input_buffer_ticks: 0,
+            input_buffer: BTreeMap::new(),
+            lockstep: None,
+            lockstep_inputs: BTreeMap::new(),
+            lockstep_waiting: None,
+            tick_mode: TickMode::Normal,
+            next_tick: None,
+            slow_motion: false,
+            tick_ms: 1000.0 / 60.0,
+        };
+        let joined: Vec<_> = game
+            .players
+            .iter()
+            .map(|p| HistoryEvent::Joined {
+                player: p.index,
+                name: p.name.clone(),
+            })
+            .collect();
+        for event in joined {
+            game.record(event);
         }
+        game.arm_ready_deadline();
+        return game;
     }
-}
 
-impl GameLogic for SoccerGame {
-    fn game_type(&self) -> u8 {
-        return 1;
+    pub fn downcast<G: 'static>(&self) -> Option<&G> {
+        self.logic.as_any().downcast_ref::<G>()
     }
-    fn as_any(&self) -> &dyn std::any::Any {
-        return self;
+
+    // Whoever changes the logic through this may do it between ticks, so
+    // the description cached for this one is dropped.
+    pub fn downcast_mut<G: 'static>(&mut self) -> Option<&mut G> {
+        *self.described.get_mut().unwrap() = None;
+        self.logic.as_any_mut().downcast_mut::<G>()
     }
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        return self;
+    // Swaps in a different game for the same players, keeping their slots,
+    // connections and subscribers. The new logic starts from a fresh seed
+    // and a fresh clock, takes over the bots, and anything recorded from
+    // the old logic (pending goals, replay frames) is dropped. Callers hold
+    // the write lock, so no update sees a mix of the two.
+    pub fn replace_logic(&mut self, mut logic: Box<dyn GameLogic>) {
+        let seed = fresh_seed();
+        logic.reseed(seed);
+        for player in self.players.iter().filter(|p| p.bot) {
+            logic.add_bot(player.index);
+        }
+        self.logic = logic;
+        // the shadow and the timers were the old logic's
+        self.audit = None;
+        self.timers.clear();
+        self.game_type = self.logic.game_type();
+        self.seed = seed;
+        self.rng = GameRng::new(seed);
+        self.last_update = self.clock.now();
+        self.goals.clear();
+        self.replay.clear();
+        *self.snapshot.lock().unwrap() = None;
+        *self.described.lock().unwrap() = None;
+        self.broadcast(WsMessage::from_payload(
+            MessageType::ModeChanged,
+            &ModeChangedMessage {
+                game_type: self.game_type,
+                seed,
+            },
+        ));
     }
-    fn update(&mut self, elapsed: f64) {
-        let physics_hooks = ();
-        let event_handler = ();
-        self.pipeline.step(
-            &vector![0.0, 0.0],
-            &self.integration_parameters,
-            &mut self.island_manager,
-            &mut self.broad_phase,
-            &mut self.narrow_phase,
-            &mut self.bodies,
-            &mut self.colliders,
-            &mut self.impulse_joints,
-            &mut self.multibody_joints,
-            &mut self.ccd_solver,
-            None,
-            &physics_hooks,
-            &event_handler,
-        );
+    // Adds a player to the lowest free slot and returns that slot.
+    pub fn add_player(&mut self, id: PlayerId, name: String) -> usize {
+        let mut index = 0;
+        while self.players.iter().any(|p| p.index == index) {
+            index += 1;
+        }
+        self.record(HistoryEvent::Joined {
+            player: index,
+            name: name.clone(),
+        });
+        self.players.push(Player {
+            id,
+            name,
+            index,
+            connected: true,
+            state_rate_hz: 0,
+            ready: false,
+            last_move_seq: 0,
+            bot: false,
+            session_token: session_token(),
+            muted: HashSet::new(),
+            rtt_ms: None,
+            traffic: None,
+            connection: None,
+        });
+        // the opponent is here, so back to kickoff for the ready check
+        if self.players.len() >= self.logic.max_players() {
+            self.end_warm_up();
+        }
+        self.arm_ready_deadline();
+        return index;
     }
-    fn to_bytes(&self) -> Vec<u8> {
-        let mut data = Vec::<u8>::with_capacity(24);
-        let mut encode_f32 = |value: f32| data.extend_from_slice(&value.to_le_bytes());
-        for puck in &self.pucks {
-            if let Some(body) = self.bodies.get(*puck) {
-                let pos = body.translation();
-                encode_f32(pos.x);
-                encode_f32(pos.y);
+    // Fills the next free slot with a server-controlled player, which is
+    // always ready.
+    pub fn add_bot(&mut self) -> usize {
+        let index = self.add_player(PlayerId::Bot(0), "bot".to_string());
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.id = PlayerId::Bot(index);
+            player.bot = true;
+            player.ready = true;
+        }
+        self.logic.add_bot(index);
+        self.audit_input(AuditInput::AddBot(index));
+        return index;
+    }
+    // Reclaims a disconnected player's slot by identity and session token,
+    // taking on whatever name they came back with, and binds connection to
+    // it. A slot that is still connected belongs to another connection of
+    // the same player: refused, or under DuplicateConnection::Replace taken
+    // over with the token, the old connection being unbound and told to
+    // close in the same step so the slot never has two. A disconnected slot
+    // can only be taken back with its token.
+    pub fn rejoin(
+        &mut self,
+        id: &PlayerId,
+        name: &str,
+        token: Option<&str>,
+        connection: SlotConnection,
+    ) -> Result<Option<usize>, ErrorCode> {
+        let replace = self.duplicate_connection == DuplicateConnection::Replace;
+        match self.players.iter_mut().find(|p| p.id == *id) {
+            // without the token it could be anyone who picked the name
+            Some(player) if !token.map_or(false, |t| tokens_match(t, &player.session_token)) => {
+                match player.connected {
+                    true => Err(ErrorCode::NameTaken),
+                    false => Err(ErrorCode::Unauthorized),
+                }
             }
+            Some(player) if player.connected && !replace => Err(ErrorCode::AlreadyConnected),
+            Some(player) => {
+                // once unbound the old connection's cleanup leaves the slot
+                // alone
+                if let Some(old) = player.connection.replace(connection) {
+                    let _ = old.evict.send(CloseReason::ReplacedByNewConnection);
+                }
+                player.connected = true;
+                player.name = name.to_string();
+                let player = player.index;
+                self.record(HistoryEvent::Rejoined {
+                    player,
+                    name: name.to_string(),
+                });
+                self.logic.rejoined(player);
+                self.resume_if_refilled(player);
+                Ok(Some(player))
+            }
+            None => Ok(None),
         }
-        if let Some(body) = self.bodies.get(self.ball) {
-            let pos = body.translation();
-            encode_f32(pos.x);
-            encode_f32(pos.y);
+    }
+    pub fn set_connected(&mut self, index: usize, connected: bool) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.connected = connected;
+            if !connected {
+                self.record(HistoryEvent::Disconnected { player: index });
+                self.wait_if_short_handed(index);
+            } else {
+                self.resume_if_refilled(index);
+            }
         }
-        return data;
+    }
+    fn wait_if_short_handed(&mut self, missing: usize) {
+        let timeout = match self.pause_config.short_handed {
+            ShortHanded::Play => return,
+            ShortHanded::Wait(timeout) => timeout,
+        };
+        let started = matches!(
+            self.phase,
+            GamePhase::Playing | GamePhase::Paused { .. } | GamePhase::Resuming { .. }
+        );
+        if !started || self.players.len() < self.logic.max_players() {
+            return;
+        }
+        self.phase = GamePhase::WaitingForPlayers {
+            missing,
+            deadline_tick: timeout.map(|timeout| self.tick() + self.ticks_in(timeout)),
+        };
+        self.broadcast(WsMessage::from_payload(
+            MessageType::WaitingForPlayer,
+            &WaitingForPlayerMessage {
+                player_index: missing as u8,
+                forfeit_in_ms: timeout.map(|timeout| timeout.as_millis() as u32),
+            },
+        ));
+        self.send_reconnect_notice();
+    }
+    // Updates it takes for duration of game time to pass, at least one.
+    fn ticks_in(&self, duration: Duration) -> u64 {
+        let ticks = (duration.as_secs_f64() * 1000.0 / self.tick_ms).ceil();
+        return (ticks as u64).max(1);
+    }
+    // Tells the players still connected how long the one the game waits for
+    // has left, and when to tell them again.
+    fn send_reconnect_notice(&mut self) {
+        let (missing, deadline_tick) = match self.phase {
+            GamePhase::WaitingForPlayers {
+                missing,
+                deadline_tick,
+            } => (missing, deadline_tick),
+            _ => return,
+        };
+        let ticks_left = deadline_tick.map(|deadline| deadline.saturating_sub(self.tick()));
+        let notice = WsMessage::from_payload(
+            MessageType::OpponentDisconnected,
+            &OpponentDisconnectedMessage {
+                player_index: missing as u8,
+                forfeit_in_ms: ticks_left.map(|ticks| (ticks as f64 * self.tick_ms) as u32),
+                forfeit_in_ticks: ticks_left.map(|ticks| ticks as u32),
+            },
+        );
+        let remaining = self
+            .players
+            .iter()
+            .filter(|player| player.index != missing)
+            .filter_map(|player| player.connection.as_ref());
+        for connection in remaining {
+            let _ = connection.inbox.send(notice.clone());
+        }
+        self.next_reconnect_notice =
+            self.tick() + self.ticks_in(self.pause_config.reconnect_notice_every);
+    }
+    fn resume_if_refilled(&mut self, index: usize) {
+        let waiting = matches!(self.phase, GamePhase::WaitingForPlayers { .. });
+        if waiting && self.players.iter().all(|p| p.connected) {
+            self.start_resume(Some(index));
+        }
+    }
+    // The slot that should forfeit because it didn't come back in time.
+    pub fn take_forfeit(&mut self) -> Option<usize> {
+        return self.forfeit.take();
+    }
+    // Whether the match clock just ran out, once.
+    pub fn take_time_up(&mut self) -> bool {
+        return std::mem::take(&mut self.time_up);
+    }
+    pub fn remove_player(&mut self, index: usize) -> Option<Player> {
+        let position = self.players.iter().position(|p| p.index == index)?;
+        self.record(HistoryEvent::Left { player: index });
+        // the ready check waits for the roster to fill again
+        if let GamePhase::ReadyCheck { .. } = self.phase {
+            self.phase = GamePhase::ReadyCheck { deadline: None };
+        }
+        return Some(self.players.remove(position));
+    }
+    // Appends to the debug history, dropping the oldest entries past
+    // history_size.
+    pub fn record(&mut self, event: HistoryEvent) {
+        self.history.push_back(HistoryEntry {
+            at_ms: now_ms(),
+            tick: self.tick(),
+            event,
+        });
+        while self.history.len() > self.history_size {
+            self.history.pop_front();
+        }
+    }
+    // oldest first
+    pub fn history(&self) -> impl Iterator<Item = &HistoryEntry> {
+        return self.history.iter();
+    }
+    // slots that scored since the last call
+    pub fn take_goals(&mut self) -> Vec<usize> {
+        return std::mem::take(&mut self.goals);
+    }
+    fn arm_ready_deadline(&mut self) {
+        if let GamePhase::ReadyCheck { deadline: None } = self.phase {
+            if self.players.len() >= self.logic.max_players() {
+                self.phase = GamePhase::ReadyCheck {
+                    deadline: Some(self.clock.now() + self.ready_timeout),
+                };
+            }
+        }
+    }
+    // Readying up outside the ready check is harmless and ignored.
+    pub fn mark_ready(&mut self, index: usize) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.ready = true;
+        }
+    }
+    fn all_ready(&self) -> bool {
+        self.players.len() >= self.logic.max_players() && self.players.iter().all(|p| p.ready)
+    }
+    pub fn player(&self, index: usize) -> Option<&Player> {
+        self.players.iter().find(|p| p.index == index)
+    }
+    // Milliseconds since the Unix epoch at creation.
+    pub fn created_ms(&self) -> u64 {
+        return self
+            .created_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+    }
+    pub fn session_token(&self, index: usize) -> Option<&str> {
+        return self.player(index).map(|p| p.session_token.as_str());
+    }
+    // Puts a slot back the way a previous run left it: its token, and a
+    // player who has to reconnect to take it, unless it was a bot's.
+    pub fn restore_slot(&mut self, index: usize, session_token: String, bot: bool) {
+        if bot {
+            self.logic.add_bot(index);
+            self.audit_input(AuditInput::AddBot(index));
+        }
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.session_token = session_token;
+            player.bot = bot;
+            player.connected = bot;
+        }
+    }
+    pub fn check_session(&self, index: usize, token: &str) -> bool {
+        return self
+            .session_token(index)
+            .map_or(false, |expected| tokens_match(token, expected));
+    }
+    pub fn player_index(&self, id: &PlayerId) -> Option<usize> {
+        self.players.iter().find(|p| p.id == *id).map(|p| p.index)
+    }
+    // Connection tasks hold on to a receiver so they can exit as soon as the
+    // game is removed instead of working against a stale Arc.
+    pub fn subscribe_closed(&self) -> watch::Receiver<bool> {
+        self.closed.subscribe()
+    }
+    pub fn close(&self) {
+        self.closed_at.get_or_init(|| self.clock.now());
+        self.closed.send_replace(true);
+        self.event_log.lock().unwrap().frames.clear();
+    }
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+    // How long ago the game was closed, or None while it is open.
+    // How long since update last ran. Every game in the map gets one a
+    // frame, held back or not, so this only grows while the tick loop isn't
+    // getting to it.
+    pub fn unvisited_for(&self) -> Duration {
+        return self.clock.now().saturating_duration_since(self.last_visit);
+    }
+    pub fn closed_for(&self) -> Option<Duration> {
+        return self
+            .closed_at
+            .get()
+            .map(|at| self.clock.now().saturating_duration_since(*at));
+    }
+    // Hands the logic of a closed game over for reuse, leaving one behind
+    // that does nothing.
+    pub fn retire_logic(&mut self) -> Box<dyn GameLogic> {
+        let retired = Box::new(Retired {
+            game_type: self.game_type,
+        });
+        self.timers.clear();
+        *self.described.lock().unwrap() = None;
+        return std::mem::replace(&mut self.logic, retired);
+    }
+    // Frames pushed to every connection attached to this game, already
+    // encoded Event frames.
+    // Receivers share one buffer, so fan-out costs a refcount bump per
+    // connection rather than a copy.
+    pub fn subscribe(&self) -> broadcast::Receiver<Bytes> {
+        self.events.subscribe()
+    }
+    // Wraps the message in a sequenced Event and logs it. The log lock is
+    // held across the send so channel order always matches seq order.
+    pub fn broadcast(&self, message: WsMessage) {
+        let mut log = self.event_log.lock().unwrap();
+        log.last_seq += 1;
+        let event = EventMessage {
+            seq: log.last_seq,
+            frame: message.to_bytes(),
+        };
+        let frame = Bytes::from(WsMessage::from_payload(MessageType::Event, &event).to_bytes());
+        if self.event_log_size > 0 {
+            let seq = log.last_seq;
+            log.frames.push_back((seq, frame.clone()));
+            while log.frames.len() > self.event_log_size {
+                log.frames.pop_front();
+            }
+        }
+        let _ = self.events.send(frame);
+    }
+    // Logged events after seq, and whether they are all of them.
+    pub fn events_since(&self, seq: u64) -> (Vec<Bytes>, EventsSinceResponse) {
+        let log = self.event_log.lock().unwrap();
+        let oldest = log.frames.front().map_or(log.last_seq + 1, |(seq, _)| *seq);
+        let frames = log
+            .frames
+            .iter()
+            .filter(|(logged, _)| *logged > seq)
+            .map(|(_, frame)| frame.clone())
+            .collect();
+        let response = EventsSinceResponse {
+            latest_seq: log.last_seq,
+            complete: seq + 1 >= oldest,
+        };
+        return (frames, response);
+    }
+    // Chat isn't logged as an event: it is per-recipient and not worth
+    // replaying after a reconnect.
+    pub fn subscribe_chat(&self) -> broadcast::Receiver<ChatLine> {
+        self.chat.subscribe()
+    }
+    pub fn send_chat(&self, from: usize, team: Option<u8>, message: &ChatMessage) {
+        let frame = WsMessage::from_payload(MessageType::Chat, message).to_bytes();
+        let _ = self.chat.send(ChatLine {
+            from,
+            team,
+            frame: Bytes::from(frame),
+        });
+    }
+    // Whether a chat line should reach the player in slot `to`.
+    pub fn chat_visible(&self, line: &ChatLine, to: usize) -> bool {
+        let muted = self
+            .player(to)
+            .map_or(false, |player| player.muted.contains(&line.from));
+        let on_team = line
+            .team
+            .map_or(true, |team| self.logic.team_of(to) == Some(team));
+        return !muted && on_team;
+    }
+    // Bumped once per update so connections can pace pushed snapshots.
+    pub fn subscribe_ticks(&self) -> watch::Receiver<u64> {
+        self.ticks.subscribe()
+    }
+    // The game's own State payload for the current tick, encoded by the
+    // first caller and handed out from the cache after that.
+    pub fn snapshot(&self) -> Bytes {
+        let tick = self.tick();
+        let mut cached = self.snapshot.lock().unwrap();
+        if let Some((at, snapshot)) = cached.as_ref() {
+            if *at == tick {
+                return snapshot.clone();
+            }
+        }
+        let started = Instant::now();
+        let snapshot = Bytes::from(self.logic.to_bytes());
+        self.encode_nanos
+            .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        *cached = Some((tick, snapshot.clone()));
+        return snapshot;
+    }
+    // The logic's description with the game-level fields filled in. The
+    // logic is only asked once a tick; the game-level fields are always
+    // current.
+    pub fn describe(&self) -> GameDescription {
+        let tick = self.tick();
+        let mut description = {
+            let mut cached = self.described.lock().unwrap();
+            match cached.as_ref() {
+                Some((at, description)) if *at == tick => description.clone(),
+                _ => {
+                    let description = self.logic.describe();
+                    *cached = Some((tick, description.clone()));
+                    description
+                }
+            }
+        };
+        description.game_type = self.game_type;
+        description.phase = self.phase.as_str().to_string();
+        description.tick_mode = self.tick_mode_str().to_string();
+        description.tick = self.tick();
+        description.max_players = self.logic.max_players();
+        description.players = self
+            .players
+            .iter()
+            .map(|player| SlotDescription {
+                index: player.index,
+                name: player.name.clone(),
+                connected: player.connected,
+                ready: player.ready,
+                bot: player.bot,
+            })
+            .collect();
+        return description;
+    }
+    // Every slot up to max_players, in index order, with who holds it and
+    // whether their connection is there.
+    pub fn roster(&self) -> Vec<PlayerSlot> {
+        let slots = self
+            .logic
+            .max_players()
+            .max(self.players.iter().map(|p| p.index + 1).max().unwrap_or(0));
+        return (0..slots)
+            .map(
+                |index| match self.players.iter().find(|p| p.index == index) {
+                    Some(player) => PlayerSlot {
+                        index,
+                        name: Some(player.name.clone()),
+                        id: Some(player.id.clone()),
+                        status: match (player.bot, player.connected) {
+                            (true, _) => SlotStatus::Bot,
+                            (false, true) => SlotStatus::Connected,
+                            (false, false) => SlotStatus::Reconnecting,
+                        },
+                    },
+                    None => PlayerSlot {
+                        index,
+                        name: None,
+                        id: None,
+                        status: SlotStatus::Empty,
+                    },
+                },
+            )
+            .collect();
+    }
+    // Short of players before the match: not yet full for the ready check.
+    // A match that lost someone isn't warm-up; it waits as it was left.
+    fn waiting_for_opponent(&self) -> bool {
+        return match self.phase {
+            GamePhase::ReadyCheck { deadline: None } => {
+                !self.players.is_empty() && self.players.len() < self.logic.max_players()
+            }
+            _ => false,
+        };
+    }
+    pub fn is_warming_up(&self) -> bool {
+        return self.warming_up;
+    }
+    // The coarse phase clients pick a screen by.
+    pub fn match_phase(&self) -> MatchPhase {
+        if self.is_closed() {
+            return MatchPhase::Over;
+        }
+        return match self.phase {
+            GamePhase::ReadyCheck { .. } if self.players.len() < self.logic.max_players() => {
+                MatchPhase::Waiting
+            }
+            GamePhase::ReadyCheck { .. } => MatchPhase::Ready,
+            GamePhase::WaitingForPlayers { .. } => MatchPhase::Waiting,
+            GamePhase::Playing | GamePhase::Paused { .. } | GamePhase::Resuming { .. } => {
+                MatchPhase::Playing
+            }
+        };
+    }
+    // What every v8 State leads with, whatever the game type.
+    pub fn snapshot_header(&self) -> SnapshotHeader {
+        return SnapshotHeader {
+            tick: self.tick() as u32,
+            phase: self.match_phase(),
+            remaining_ds: self.logic.remaining_time().map(|remaining| {
+                (remaining.as_millis() / 100).min(SnapshotHeader::UNTIMED as u128 - 1) as u16
+            }),
+            scores: self
+                .logic
+                .scores()
+                .iter()
+                .map(|score| (*score).min(u8::MAX as u32) as u8)
+                .collect(),
+            // set by the server for a v9 connection
+            level: None,
+        };
+    }
+    // Human players with a live connection, plus firehose spectators, who
+    // watch every game; bots don't count.
+    pub fn live_connections(&self) -> usize {
+        let players = self
+            .players
+            .iter()
+            .filter(|p| p.connected && !p.bot)
+            .count();
+        return players + self.spectators.load(Ordering::Relaxed);
+    }
+    // How long the game has had nobody connected, while that lasts.
+    pub fn dormant_for(&self) -> Option<Duration> {
+        return self
+            .dormant_since
+            .map(|since| self.clock.now().saturating_duration_since(since));
+    }
+    // Started and not held up waiting for someone, so worth watching.
+    pub fn is_running(&self) -> bool {
+        return !self.is_closed()
+            && self.dormant_since.is_none()
+            && !matches!(
+                self.phase,
+                GamePhase::ReadyCheck { .. } | GamePhase::WaitingForPlayers { .. }
+            );
+    }
+    // updates run so far
+    pub fn tick(&self) -> u64 {
+        return *self.ticks.borrow();
+    }
+    pub fn set_state_rate(&mut self, index: usize, state_rate_hz: u8) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.state_rate_hz = state_rate_hz;
+        }
+    }
+    pub fn set_rtt(&mut self, index: usize, rtt_ms: Option<u32>) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.rtt_ms = rtt_ms;
+        }
+    }
+    pub fn set_traffic(&mut self, index: usize, traffic: Arc<ConnectionTraffic>) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            player.traffic = Some(traffic);
+        }
+    }
+    // For slots filled without rejoin; one already bound keeps what it has,
+    // which may be a connection that replaced this one.
+    pub fn bind_connection(&mut self, index: usize, connection: SlotConnection) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.index == index) {
+            if player.connection.is_none() {
+                player.connection = Some(connection);
+            }
+        }
+    }
+    // Lets go of the slot if client_id still holds it. False when a newer
+    // connection took it over, in which case the slot isn't this
+    // connection's to mark disconnected.
+    pub fn unbind_connection(&mut self, index: usize, client_id: usize) -> bool {
+        let player = match self.players.iter_mut().find(|p| p.index == index) {
+            Some(player) => player,
+            None => return true,
+        };
+        match &player.connection {
+            Some(connection) if connection.client_id != client_id => return false,
+            _ => {
+                player.connection = None;
+                return true;
+            }
+        }
+    }
+    pub fn is_paused(&self) -> bool {
+        self.phase != GamePhase::Playing
+    }
+    pub fn request_pause(&mut self, player: usize) -> Result<(), ErrorCode> {
+        if self.phase != GamePhase::Playing {
+            return Err(ErrorCode::PauseUnavailable);
+        }
+        let used = self.pauses_used.entry(player).or_insert(0);
+        if *used >= self.pause_config.pauses_per_player {
+            return Err(ErrorCode::PauseUnavailable);
+        }
+        *used += 1;
+        let pauses_left = self.pause_config.pauses_per_player - *used;
+        self.phase = GamePhase::Paused {
+            by: player,
+            until: self.clock.now() + self.pause_config.max_pause,
+        };
+        self.broadcast(WsMessage::from_payload(
+            MessageType::GamePaused,
+            &GamePausedMessage {
+                by: player as u8,
+                max_duration_ms: self.pause_config.max_pause.as_millis() as u32,
+                pauses_left,
+            },
+        ));
+        return Ok(());
+    }
+    // Either player may resume; physics restarts after the countdown.
+    pub fn request_resume(&mut self, player: usize) -> Result<(), ErrorCode> {
+        match self.phase {
+            GamePhase::Paused { .. } => {
+                self.start_resume(Some(player));
+                return Ok(());
+            }
+            _ => return Err(ErrorCode::NotPaused),
+        }
+    }
+    fn end_warm_up(&mut self) {
+        if self.warming_up {
+            self.warming_up = false;
+            self.logic.warm_up(false);
+            self.audit_input(AuditInput::WarmUp(false));
+        }
+    }
+    fn start_resume(&mut self, by: Option<usize>) {
+        self.end_warm_up();
+        self.phase = GamePhase::Resuming {
+            at: self.clock.now() + self.pause_config.resume_countdown,
+        };
+        self.broadcast(WsMessage::from_payload(
+            MessageType::GameResuming,
+            &GameResumingMessage {
+                by: by.map(|index| index as u8),
+                countdown_ms: self.pause_config.resume_countdown.as_millis() as u32,
+            },
+        ));
+    }
+    // Meant to be swapped in before the first update; timers already armed
+    // keep the old clock's deadlines.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_update = clock.now();
+        self.last_visit = clock.now();
+        self.clock = clock;
+    }
+    // For connections to queue GameCommands on.
+    pub fn commands(&self) -> mpsc::Sender<GameCommand> {
+        return self.commands.clone();
+    }
+    // Moves are stamped with the tick they are drained on, which is the tick
+    // they arrived during.
+    fn apply_commands(&mut self) {
+        let tick = self.tick();
+        self.receive_commands();
+        // at most one move per puck each tick, oldest first
+        let mut due = vec![];
+        for queue in self.input_buffer.values_mut() {
+            if queue.front().map_or(false, |(at, _)| *at <= tick) {
+                due.extend(queue.pop_front().map(|(_, command)| command));
+            }
+        }
+        self.input_buffer.retain(|_, queue| !queue.is_empty());
+        for command in due {
+            self.apply_command(command);
+        }
+    }
+    // Takes everything queued since the last look: moves are applied or held
+    // for input_buffer_ticks, lockstep inputs are kept for their tick.
+    fn receive_commands(&mut self) {
+        let tick = self.tick();
+        while let Ok(command) = self.command_queue.try_recv() {
+            match command {
+                GameCommand::Input {
+                    player,
+                    tick: at,
+                    moves,
+                    replies,
+                } => {
+                    if let Err(refusal) = self.receive_input(player, at, moves) {
+                        let _ = replies.send(refusal);
+                    }
+                }
+                command @ GameCommand::Boost { .. } => self.apply_command(command),
+                command if self.input_buffer_ticks == 0 => self.apply_command(command),
+                command => self.buffer_command(tick, command),
+            }
+        }
+    }
+    fn receive_input(
+        &mut self,
+        player: usize,
+        at: u64,
+        moves: Vec<LockstepMove>,
+    ) -> Result<(), WsMessage> {
+        let tick = self.tick();
+        let refusal = if self.lockstep.is_none() {
+            "This game isn't lockstep; send SoccerMove"
+        } else if at < tick {
+            "That tick has already been stepped"
+        } else if at > tick + LOCKSTEP_WINDOW as u64 {
+            "That tick is too far ahead"
+        } else {
+            self.lockstep_inputs
+                .entry(at)
+                .or_default()
+                .insert(player, moves);
+            return Ok(());
+        };
+        return Err(WsMessage::error(ErrorCode::LockstepInput, refusal));
+    }
+    // Holds command until input_buffer_ticks after tick, or the tick after
+    // the last one held for the same puck if that is later. Once a puck has
+    // twice the depth waiting, a newer move replaces the newest held one
+    // instead, so a client sending faster than the tick rate doesn't build
+    // up lag.
+    fn buffer_command(&mut self, tick: u64, command: GameCommand) {
+        let key = match &command {
+            GameCommand::Move { player, target, .. } => (*player, *target),
+            // applied on arrival, or kept per tick by receive_input
+            GameCommand::Boost { .. } | GameCommand::Input { .. } => return,
+        };
+        let depth = self.input_buffer_ticks;
+        let queue = self.input_buffer.entry(key).or_default();
+        let due = queue.back().map_or(0, |(at, _)| at + 1).max(tick + depth);
+        if due > tick + 2 * depth {
+            if let Some((_, held)) = queue.back_mut() {
+                *held = command;
+            }
+            return;
+        }
+        queue.push_back((due, command));
+    }
+    fn apply_command(&mut self, command: GameCommand) {
+        match command {
+            GameCommand::Move {
+                player,
+                target,
+                vx,
+                vy,
+                angular,
+                seq,
+                replies,
+            } => {
+                if self.lockstep.is_some() {
+                    let refusal = "This game is lockstep; send LockstepInput";
+                    let _ = replies.send(WsMessage::error(ErrorCode::LockstepInput, refusal));
+                } else if let Err(refusal) = self.apply_move(player, target, vx, vy, angular, seq) {
+                    let _ = replies.send(refusal);
+                }
+            }
+            GameCommand::Boost {
+                player,
+                target,
+                dx,
+                dy,
+                replies,
+            } => {
+                if let Err(refusal) = self.apply_boost(player, target, dx, dy) {
+                    let _ = replies.send(refusal);
+                }
+            }
+            GameCommand::Input { .. } => (),
+        }
+    }
+    // Applies the lockstep inputs for the tick about to be stepped and drops
+    // any left over from ticks that passed without play. In play, everyone
+    // in the game hears what was applied.
+    fn apply_lockstep_inputs(&mut self) {
+        if self.lockstep.is_none() {
+            return;
+        }
+        self.lockstep_waiting = None;
+        let tick = self.tick();
+        let later = self.lockstep_inputs.split_off(&(tick + 1));
+        let inputs = std::mem::replace(&mut self.lockstep_inputs, later)
+            .remove(&tick)
+            .unwrap_or_default();
+        for (&player, moves) in &inputs {
+            for step in moves {
+                // a refusal is the same for everyone, so it isn't sent back
+                let _ = self.apply_move(player, step.target, step.vx, step.vy, step.angular, 0);
+            }
+        }
+        if self.phase != GamePhase::Playing {
+            return;
+        }
+        let missing = self
+            .players
+            .iter()
+            .filter(|p| p.connected && !p.bot && !inputs.contains_key(&p.index))
+            .map(|p| p.index as u8)
+            .collect();
+        let step = LockstepStepMessage {
+            tick: tick as u32,
+            inputs: inputs
+                .into_iter()
+                .map(|(player, moves)| (player as u8, moves))
+                .collect(),
+            missing,
+        };
+        self.broadcast(WsMessage::from_payload(MessageType::LockstepStep, &step));
+    }
+    fn apply_move(
+        &mut self,
+        player: usize,
+        target: u8,
+        vx: f32,
+        vy: f32,
+        angular: f32,
+        seq: u32,
+    ) -> Result<(), WsMessage> {
+        if self.is_paused() && !self.warming_up {
+            return Err(WsMessage::error(
+                ErrorCode::GamePaused,
+                "Moves are not accepted while the game is paused",
+            ));
+        }
+        let soccer_game = match self.downcast_mut::<SoccerGame>() {
+            Some(soccer_game) => soccer_game,
+            None => return Ok(()),
+        };
+        if !soccer_game.accepts_input() {
+            return Err(WsMessage::error(
+                ErrorCode::InputFrozen,
+                soccer_game.phase.as_str(),
+            ));
+        }
+        if !soccer_game.has_turn(player) {
+            return Err(WsMessage::error(
+                ErrorCode::NotYourTurn,
+                "Wait for a Turn before moving",
+            ));
+        }
+        let team = match soccer_game.team(player) {
+            Some(team) => team,
+            None => return Ok(()),
+        };
+        let puck = match team.pucks.get(target as usize) {
+            Some(puck) => *puck,
+            None => {
+                let refusal = format!("Target {} is past your {} pucks", target, team.pucks.len());
+                return Err(WsMessage::error(ErrorCode::InvalidParams, &refusal));
+            }
+        };
+        soccer_game.apply_move(puck, vx, vy, angular);
+        self.audit_input(AuditInput::Move {
+            player,
+            target,
+            vx,
+            vy,
+            angular,
+        });
+        if seq != 0 {
+            if let Some(player) = self.players.iter_mut().find(|p| p.index == player) {
+                player.last_move_seq = seq;
+            }
+        }
+        return Ok(());
+    }
+    fn apply_boost(
+        &mut self,
+        player: usize,
+        target: u8,
+        dx: f32,
+        dy: f32,
+    ) -> Result<(), WsMessage> {
+        if self.is_paused() {
+            return Err(WsMessage::error(
+                ErrorCode::GamePaused,
+                "Boosts are not accepted while the game is paused",
+            ));
+        }
+        let soccer_game = match self.downcast_mut::<SoccerGame>() {
+            Some(soccer_game) => soccer_game,
+            None => return Ok(()),
+        };
+        if !soccer_game.accepts_input() {
+            return Err(WsMessage::error(
+                ErrorCode::InputFrozen,
+                soccer_game.phase.as_str(),
+            ));
+        }
+        if !soccer_game.has_turn(player) {
+            return Err(WsMessage::error(
+                ErrorCode::NotYourTurn,
+                "Wait for a Turn before boosting",
+            ));
+        }
+        if let Err(remaining_ms) = soccer_game.boost(player, target, dx, dy) {
+            return Err(WsMessage::error(
+                ErrorCode::BoostCooldown,
+                &format!("Boost ready in {} ms", remaining_ms),
+            ));
+        }
+        self.audit_input(AuditInput::Boost {
+            player,
+            target,
+            dx,
+            dy,
+        });
+        return Ok(());
+    }
+    pub fn tick_mode(&self) -> TickMode {
+        return self.tick_mode;
+    }
+    pub fn tick_mode_str(&self) -> &'static str {
+        if self.tick_mode == TickMode::Normal && self.slow_motion {
+            return "slowed";
+        }
+        if self.tick_mode == TickMode::Normal && self.lockstep.is_some() {
+            return "lockstep";
+        }
+        return self.tick_mode.as_str();
+    }
+    // Tells everyone in the game when it stops ticking normally, so a
+    // crawling or frozen match isn't taken for lag. Going back to Normal
+    // starts the elapsed time over from now, so the first update doesn't
+    // try to make up for the time spent stepping.
+    pub fn set_tick_mode(&mut self, mode: TickMode) {
+        if mode == self.tick_mode {
+            return;
+        }
+        self.tick_mode = mode;
+        self.next_tick = None;
+        self.last_update = self.clock.now();
+        let text = match mode {
+            TickMode::Normal => "This game is back to normal speed".to_string(),
+            TickMode::Slowed { every } => format!(
+                "An admin slowed this game down to one update every {:?}",
+                every
+            ),
+            TickMode::Manual => "An admin is stepping this game by hand".to_string(),
+        };
+        self.broadcast(WsMessage::from_payload(
+            MessageType::Announcement,
+            &AnnouncementMessage { text },
+        ));
+    }
+    // One update of a game in Manual mode; false for any other game.
+    pub fn step(&mut self) -> bool {
+        if self.tick_mode != TickMode::Manual {
+            return false;
+        }
+        self.advance();
+        return true;
+    }
+    fn tick_due(&mut self) -> bool {
+        return match self.tick_mode {
+            TickMode::Normal => true,
+            TickMode::Manual => false,
+            TickMode::Slowed { every } => {
+                let now = self.clock.now();
+                if self.next_tick.map_or(false, |at| now < at) {
+                    return false;
+                }
+                self.next_tick = Some(now + every);
+                true
+            }
+        };
+    }
+    // In play, a lockstep game is due once everyone connected has sent input
+    // for the tick, or the wait for them is over. Anything else is due
+    // whenever tick_due says.
+    fn lockstep_due(&mut self) -> bool {
+        let wait = match self.lockstep {
+            Some(wait) if self.phase == GamePhase::Playing && !self.warming_up => wait,
+            _ => return true,
+        };
+        self.receive_commands();
+        let sent = self.lockstep_inputs.get(&self.tick());
+        let all_in = self
+            .players
+            .iter()
+            .filter(|p| p.connected && !p.bot)
+            .all(|p| sent.map_or(false, |sent| sent.contains_key(&p.index)));
+        if all_in {
+            return true;
+        }
+        let now = self.clock.now();
+        let since = *self.lockstep_waiting.get_or_insert(now);
+        return now.saturating_duration_since(since) >= wait;
+    }
+    pub fn update(&mut self) {
+        self.last_visit = self.clock.now();
+        let encode = self.encode_nanos.swap(0, Ordering::Relaxed);
+        self.phase_times = PhaseTimes {
+            encode: Duration::from_nanos(encode),
+            ..PhaseTimes::default()
+        };
+        if !self.lockstep_due() || !self.tick_due() {
+            // a game held back still goes dormant once everyone has left,
+            // and wakes when someone is back
+            let now = self.clock.now();
+            if self.live_connections() == 0 {
+                self.dormant_since.get_or_insert(now);
+            } else if let Some(since) = self.dormant_since.take() {
+                self.wake(now.saturating_duration_since(since));
+            }
+            self.deliver_messages();
+            return;
+        }
+        self.advance();
+        self.deliver_messages();
+    }
+    // Where the last update spent its time, with the snapshots encoded since
+    // the one before it.
+    pub fn phase_times(&self) -> PhaseTimes {
+        return self.phase_times;
+    }
+    // Routes what the logic sent since the last update, whether or not this
+    // one stepped anything, so input handled while the game is held back
+    // still gets its answers out.
+    fn deliver_messages(&mut self) {
+        let started = Instant::now();
+        let mut messages = self.logic.take_messages();
+        if messages.len() > MAX_LOGIC_MESSAGES {
+            log::warn!(
+                "Game of type {} sent {} messages at tick {}; dropped all past {}",
+                self.game_type,
+                messages.len(),
+                self.tick(),
+                MAX_LOGIC_MESSAGES
+            );
+            messages.truncate(MAX_LOGIC_MESSAGES);
+        }
+        for (to, message) in messages {
+            let slots = self.players.iter().filter(|player| match to {
+                Recipient::Slot(index) => player.index == index,
+                Recipient::AllPlayers => true,
+                Recipient::Everyone => false,
+            });
+            for connection in slots.filter_map(|player| player.connection.as_ref()) {
+                let _ = connection.inbox.send(message.clone());
+            }
+            if to == Recipient::Everyone {
+                self.broadcast(message);
+            }
+        }
+        self.phase_times.broadcast += started.elapsed();
+    }
+    fn advance(&mut self) {
+        let started = Instant::now();
+        self.fire_timers();
+        self.apply_commands();
+        self.phase_times.input = started.elapsed();
+        let now = self.clock.now();
+        // nobody to show it to: nothing is stepped, and the tick stays put so
+        // no snapshot gets encoded either
+        if self.live_connections() == 0 {
+            self.dormant_since.get_or_insert(now);
+            return;
+        }
+        if let Some(since) = self.dormant_since.take() {
+            self.wake(now.saturating_duration_since(since));
+        }
+        let started = Instant::now();
+        self.apply_lockstep_inputs();
+        self.phase_times.input += started.elapsed();
+        match self.phase {
+            GamePhase::ReadyCheck { deadline } => {
+                let timed_out = deadline.map_or(false, |deadline| now >= deadline);
+                if timed_out || self.all_ready() {
+                    self.start_resume(None);
+                }
+            }
+            GamePhase::Paused { until, .. } if now >= until => self.start_resume(None),
+            GamePhase::Resuming { at } if now >= at => self.phase = GamePhase::Playing,
+            GamePhase::WaitingForPlayers {
+                missing,
+                deadline_tick: Some(deadline),
+            } if self.tick() >= deadline => {
+                self.forfeit = Some(missing);
+                self.phase = GamePhase::WaitingForPlayers {
+                    missing,
+                    deadline_tick: None,
+                };
+            }
+            GamePhase::WaitingForPlayers { .. } if self.tick() >= self.next_reconnect_notice => {
+                self.send_reconnect_notice();
+            }
+            _ => (),
+        }
+        if self.warm_up && !self.warming_up && self.waiting_for_opponent() {
+            self.warming_up = self.logic.warm_up(true);
+            self.audit_input(AuditInput::WarmUp(true));
+        }
+        // the clock keeps ticking while paused so the first update after a
+        // resume only sees one frame of elapsed time
+        let mut elapsed = self.get_and_update_duration() as f64;
+        if self.tick_mode != TickMode::Normal || self.slow_motion || self.lockstep.is_some() {
+            elapsed = self.tick_ms;
+        }
+        let started = Instant::now();
+        // a match waiting for a player to come back is frozen, its logic
+        // and match clock included, whatever warm-up says
+        let frozen = matches!(self.phase, GamePhase::WaitingForPlayers { .. });
+        if self.warming_up && !frozen {
+            self.logic.update(elapsed);
+            self.phase_times.step = started.elapsed();
+            self.audit_input(AuditInput::Update(elapsed));
+            self.take_timer_changes();
+            // nothing from warm-up play is kept
+            self.logic.take_events();
+            self.logic.take_goals();
+            self.logic.take_history();
+        } else if self.phase == GamePhase::Playing {
+            let running = self.logic.remaining_time() != Some(Duration::ZERO);
+            self.logic.update(elapsed);
+            self.phase_times.step = started.elapsed();
+            self.audit_input(AuditInput::Update(elapsed));
+            self.take_timer_changes();
+            if running && self.logic.remaining_time() == Some(Duration::ZERO) {
+                self.time_up = true;
+            }
+            let started = Instant::now();
+            for event in self.logic.take_events() {
+                self.broadcast(event);
+            }
+            self.phase_times.broadcast = started.elapsed();
+            let goals = self.logic.take_goals();
+            for &player in &goals {
+                self.record(HistoryEvent::Goal { player });
+            }
+            self.goals.extend(goals);
+            for event in self.logic.take_history() {
+                self.record(event);
+            }
+        }
+        self.ticks.send_modify(|tick| *tick += 1);
+        if self.replay_ticks > 0 && self.phase == GamePhase::Playing {
+            self.record_replay_frame();
+        }
+        self.dump_frame();
+        self.check_audit();
+    }
+    // Hands every timer due by this tick to the logic, in id order, along
+    // with any it sets from them.
+    fn fire_timers(&mut self) {
+        for id in self.timers.take_due(self.tick()) {
+            self.logic.on_timer(id);
+            self.audit_input(AuditInput::Timer(id));
+        }
+        self.take_timer_changes();
+    }
+    fn take_timer_changes(&mut self) {
+        let changes = self.logic.take_timer_changes();
+        self.timers.apply(self.tick(), changes);
+    }
+    // Starts writing every tick to dumper, in place of any earlier one.
+    pub fn attach_frame_dumper(&mut self, dumper: FrameDumper) {
+        self.frame_dumper = Some(dumper);
+    }
+    pub fn take_frame_dumper(&mut self) -> Option<FrameDumper> {
+        return self.frame_dumper.take();
+    }
+    // Starts a determinism audit checking every `every` ticks, in place of
+    // any earlier one. Only soccer can be audited, and only from before
+    // kickoff, while a fresh world built from its config still matches it.
+    pub fn start_audit(&mut self, every: u64) -> Result<(), &'static str> {
+        if !matches!(self.phase, GamePhase::ReadyCheck { .. }) || self.warming_up {
+            return Err("A game can only be audited from before kickoff");
+        }
+        let seed = self.seed;
+        let shadow = match self.downcast::<SoccerGame>() {
+            Some(soccer_game) => soccer_game.shadow(seed),
+            None => return Err("Only soccer games can be audited"),
+        };
+        self.audit = Some(DeterminismAudit::new(shadow, every));
+        self.diverged = None;
+        return Ok(());
+    }
+    pub fn stop_audit(&mut self) -> bool {
+        self.diverged = None;
+        return self.audit.take().is_some();
+    }
+    pub fn audit_mut(&mut self) -> Option<&mut DeterminismAudit> {
+        return self.audit.as_mut();
+    }
+    // Passes on to the audit something done to the logic, for its shadow to
+    // have done too. Callers that change the logic outside an update (a
+    // Boost, new params) call this after a change that took.
+    pub fn audit_input(&mut self, input: AuditInput) {
+        if let Some(audit) = &mut self.audit {
+            audit.record(input);
+        }
+    }
+    // The first divergence the audit found, once.
+    pub fn take_divergence(&mut self) -> Option<Divergence> {
+        return self.diverged.take();
+    }
+    fn check_audit(&mut self) {
+        let tick = self.tick();
+        let live = self.logic.as_any().downcast_ref::<SoccerGame>();
+        if let (Some(audit), Some(live)) = (self.audit.as_mut(), live) {
+            if let Some(divergence) = audit.tick(tick, live) {
+                self.diverged = Some(divergence);
+            }
+        }
+    }
+    fn dump_frame(&mut self) {
+        let mut dumper = match self.frame_dumper.take() {
+            Some(dumper) => dumper,
+            None => return,
+        };
+        // a dumper that is full or whose writer stopped is let go, which
+        // closes its file once what it queued is written
+        match dumper.record(self) {
+            Ok(()) if dumper.is_full() => log::info!(
+                "Frame dump {} done after {} frames",
+                dumper.path().display(),
+                dumper.written()
+            ),
+            Ok(()) => self.frame_dumper = Some(dumper),
+            Err(e) => log::warn!("Frame dump {} failed: {}", dumper.path().display(), e),
+        }
+    }
+    // Picks up where the game left off when it went dormant: its timers
+    // were frozen, and the time away isn't handed to the logic as one step.
+    fn wake(&mut self, dormant: Duration) {
+        self.phase = self.phase.delayed(dormant);
+        self.last_update = self.clock.now();
+        log::info!("Game woke after {:?} dormant", dormant);
+    }
+    fn record_replay_frame(&mut self) {
+        if let Some(state) = self.logic.replay_frame() {
+            self.replay.push_back(ReplayFrame {
+                tick: self.tick() as u32,
+                state,
+            });
+            while self.replay.len() > self.replay_ticks {
+                self.replay.pop_front();
+            }
+        }
+    }
+    // Sends everyone the recorded lead-up to a goal scored this update,
+    // then starts recording afresh. The burst is logged like any other
+    // event; connections still queue it behind everything else.
+    pub fn broadcast_replay(&mut self) {
+        let recorded = std::mem::take(&mut self.replay);
+        let goal_tick = match recorded.back() {
+            Some(frame) => frame.tick,
+            None => return,
+        };
+        let count = recorded.len().min(MAX_REPLAY_FRAMES);
+        // evenly spaced, always keeping the first and the goal tick
+        let frames = (0..count)
+            .map(|i| match count {
+                1 => recorded.len() - 1,
+                _ => i * (recorded.len() - 1) / (count - 1),
+            })
+            .map(|index| recorded[index].clone())
+            .collect();
+        let burst = WsMessage::from_payload(
+            MessageType::ReplayBurst,
+            &ReplayBurstMessage { goal_tick, frames },
+        );
+        self.broadcast(burst);
+    }
+    // ms since the last call. A clock stepped backwards counts as no time
+    // passing, and a stalled tick is capped at MAX_UPDATE_MS so timers never
+    // leap ahead in one update.
+    pub fn get_and_update_duration(&mut self) -> u128 {
+        let now = self.clock.now();
+        if now < self.last_update {
+            log::warn!(
+                "Game clock stepped back {:?}; counting no time",
+                self.last_update - now
+            );
+        }
+        let duration = now
+            .saturating_duration_since(self.last_update)
+            .as_millis()
+            .min(MAX_UPDATE_MS);
+        self.last_update = now;
+        return duration;
+    }
+}
+
+pub const MAX_UPDATE_MS: u128 = 250;
+
+// Wall clock, only for timestamps people read.
+fn now_ms() -> u128 {
+    // a clock before the epoch reads as the epoch rather than panicking
+    return SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+}
+
+pub struct SoccerGame {
+    pub pipeline: PhysicsPipeline,
+    pub integration_parameters: IntegrationParameters,
+    pub island_manager: IslandManager,
+    pub broad_phase: DefaultBroadPhase,
+    pub colliders: ColliderSet,
+    pub narrow_phase: NarrowPhase,
+    pub bodies: RigidBodySet,
+    // every puck, team 0's first; this is the order snapshots use
+    pub pucks: Vec<RigidBodyHandle>,
+    pub teams: [TeamInfo; 2],
+    pub balls: Vec<RigidBodyHandle>,
+    pub impulse_joints: ImpulseJointSet,
+    pub multibody_joints: MultibodyJointSet,
+    pub ccd_solver: CCDSolver,
+    // kept up to date by every step, for the wall check after it
+    query_pipeline: QueryPipeline,
+    pub width: f32,
+    pub height: f32,
+    pub kickoff: HashMap<RigidBodyHandle, Vector<f32>>,
+    pub watchdog_resets: u64,
+    pub max_angvel: f32,
+    pub control_mode: ControlMode,
+    pub walls: Vec<WallSpec>,
+    // half extents of the box around every wall, nets included
+    pub bounds: Vector<f32>,
+    // scoring slots since the last take_goals
+    goals: Vec<usize>,
+    pub record_stats: bool,
+    pub physics_stats: PhysicsStats,
+    // impulse magnitude already applied to each puck since the last step
+    impulse_used: HashMap<RigidBodyHandle, f32>,
+    pub max_shot_speed: f32,
+    pub power_up_config: Option<PowerUpConfig>,
+    // uncollected power-ups on the field and effects still running
+    pub power_ups: Vec<PowerUp>,
+    pub active_effects: Vec<ActivePowerUp>,
+    // collider of every puck and ball
+    body_colliders: HashMap<RigidBodyHandle, ColliderHandle>,
+    pub puck_radius: f32,
+    pub puck_damping: f32,
+    pub puck_restitution: f32,
+    // minimum time between two moves of the same puck
+    pub move_cooldown: Duration,
+    // time so far, kept in whole microseconds so a timer of `after` runs
+    // out on the same tick however the updates' f64 ms add up, and when
+    // each puck last accepted a move
+    clock_us: u64,
+    last_move: HashMap<RigidBodyHandle, f64>,
+    pub boost_speed: f32,
+    pub boost_cooldown: Duration,
+    // clock_ms of each player's last accepted Boost
+    last_boost: HashMap<usize, f64>,
+    // player slots steered by drive_bots
+    pub bots: Vec<usize>,
+    pub practice: bool,
+    // ms until the next power-up spawn
+    next_power_up: f64,
+    next_power_up_id: u32,
+    events: Vec<WsMessage>,
+    // resets since the last take_history
+    history: Vec<HistoryEvent>,
+    // since the last take_timer_changes
+    timer_changes: Vec<TimerChange>,
+    rng: GameRng,
+    // sensor behind each goal mouth and the side that defends it
+    goal_sensors: HashMap<ColliderHandle, Side>,
+    // intersection events from the last step
+    collisions: CollisionCollector,
+    // balls that scored and haven't left their goal sensor yet
+    scored: HashSet<RigidBodyHandle>,
+    pub phase: SoccerPhase,
+    // updates run so far; phase deadlines count in these
+    tick: u64,
+    pub goal_reset_ticks: u64,
+    pub kickoff_freeze_ticks: u64,
+    pub settle_ticks: u64,
+    pub max_body_speed: Option<f32>,
+    pub serve_speed: Option<f32>,
+    pub ball_damping: Option<f32>,
+    pub quadratic_drag: f32,
+    game_type: u8,
+    pub gravity: Vector<f32>,
+    pub max_shot_vy: Option<f32>,
+    pub anti_stall: Option<AntiStallConfig>,
+    // clock_ms each slow ball went slow at, and where each ball settled and
+    // when for pinned_radius
+    stalled_since: HashMap<RigidBodyHandle, f64>,
+    pinned_since: HashMap<RigidBodyHandle, (Vector<f32>, f64)>,
+    pub bounce_decay: Option<f32>,
+    pub team_colors: Vec<u8>,
+    pub stepping: Stepping,
+    // elapsed time Fixed stepping hasn't stepped through yet
+    step_backlog_us: u64,
+    pub match_duration: Option<Duration>,
+    pub max_catch_up: Duration,
+    // match time played: the dt of every physics step taken outside
+    // warm-up, never the wall clock, so a stall can only use up as much of
+    // the match as max_catch_up lets it step
+    played_us: u64,
+    // slot whose puck touched a ball most recently, any ball; None after a
+    // reset until someone does
+    pub last_ball_toucher: Option<usize>,
+    // the tick last_ball_toucher took over, and each slot's SlotStats so far
+    // this match; possession is credited when the toucher changes rather
+    // than counted every tick
+    possession_since: u64,
+    input_stats: HashMap<usize, InputTally>,
+    // the same per ball, with the puck and when, which is what goals are
+    // credited from
+    ball_touchers: HashMap<RigidBodyHandle, BallTouch>,
+    pub touch_window_ticks: Option<u64>,
+    // physics run for a lone player waiting for an opponent: goals reset
+    // the ball but don't score, and nothing is recorded
+    pub warm_up: bool,
+    // the server's soccer tuning version the game was built from, or was
+    // last retuned to
+    pub tuning_version: u64,
+    pub turn_ticks: Option<u64>,
+    // the slot whose turn it is and the tick the turn ends at; None before
+    // the first one
+    turn: Option<(usize, u64)>,
+    // what set_handicap was last given, clamped; each team holds its half
+    pub handicap: Option<HandicapConfig>,
+    // since the last take_messages
+    messages: Vec<(Recipient, WsMessage)>,
+    // what the world was built from, and its body and collider counts
+    // straight after, so a pooled world can be matched and checked
+    config: SoccerGameConfig,
+    built: (usize, usize),
+}
+
+// One puck or ball as a crash-recovery save keeps it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BodyState {
+    pub position: [f32; 2],
+    pub linvel: [f32; 2],
+    pub angvel: f32,
+}
+
+// Where a soccer game is between goals. Moves and Boosts are only taken in
+// Play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoccerPhase {
+    Play,
+    // the ball sits in the goal until everything goes back to its spot
+    GoalScored { resetting_until_tick: u64 },
+    // positions are reset; input opens again once the freeze ends
+    Kickoff { until_tick: u64 },
+}
+
+// phase byte of a snapshot taken during warm-up
+pub const WARM_UP_PHASE_CODE: u8 = 3;
+
+impl SoccerPhase {
+    // the phase byte of v4 snapshots
+    pub fn code(&self) -> u8 {
+        return match self {
+            SoccerPhase::Play => 0,
+            SoccerPhase::GoalScored { .. } => 1,
+            SoccerPhase::Kickoff { .. } => 2,
+        };
+    }
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            SoccerPhase::Play => "play",
+            SoccerPhase::GoalScored { .. } => "goal_scored",
+            SoccerPhase::Kickoff { .. } => "kickoff",
+        };
+    }
+}
+
+// Buffers collision events raised during a step; the pipeline only hands out
+// a shared reference, so they are drained after the step returns.
+#[derive(Default)]
+struct CollisionCollector(Mutex<Vec<CollisionEvent>>);
+
+impl EventHandler for CollisionCollector {
+    fn handle_collision_event(
+        &self,
+        _bodies: &RigidBodySet,
+        _colliders: &ColliderSet,
+        event: CollisionEvent,
+        _contact_pair: Option<&ContactPair>,
+    ) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn handle_contact_force_event(
+        &self,
+        _dt: Real,
+        _bodies: &RigidBodySet,
+        _colliders: &ColliderSet,
+        _contact_pair: &ContactPair,
+        _total_force_magnitude: Real,
+    ) {
+    }
+}
+
+// Total bodies respawned by the physics watchdog across all games.
+pub static WATCHDOG_RESETS: AtomicU64 = AtomicU64::new(0);
+
+pub const SOCCER_GAME_TYPE: u8 = 1;
+// soccer played side-on under gravity, with a net across the middle
+pub const VOLLEY_GAME_TYPE: u8 = 2;
+
+// How SoccerMove drives a puck. Velocity replaces the puck's velocity
+// outright; Impulse pushes it by vx/vy scaled by mass, with at most
+// max_impulse applied to any one puck per tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlMode {
+    Velocity,
+    Impulse { max_impulse: f32 },
+}
+
+impl Default for ControlMode {
+    fn default() -> Self {
+        return ControlMode::Velocity;
+    }
+}
+
+// Solver settings trading accuracy for step cost, for hosts running many
+// games at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhysicsPreset {
+    Accurate,
+    Balanced,
+    Fast,
+}
+
+impl Default for PhysicsPreset {
+    fn default() -> Self {
+        return PhysicsPreset::Balanced;
+    }
+}
+
+impl PhysicsPreset {
+    pub fn integration_parameters(&self) -> IntegrationParameters {
+        let mut params = IntegrationParameters::default();
+        let iterations = match self {
+            PhysicsPreset::Accurate => 8,
+            PhysicsPreset::Balanced => return params,
+            PhysicsPreset::Fast => 1,
+        };
+        params.num_solver_iterations = NonZeroUsize::new(iterations).unwrap();
+        return params;
+    }
+}
+
+// How update turns the time since the last one into physics steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stepping {
+    // one step of integration_parameters.dt per update however long it has
+    // been, so the physics runs at the tick rate
+    PerUpdate,
+    // steps of this many seconds, as many as the elapsed time covers, the
+    // rest carried over to the next update; the same inputs give the same
+    // world whatever the frame cadence
+    Fixed(f32),
+    // one step as long as the elapsed time
+    Variable,
+}
+
+impl Default for Stepping {
+    fn default() -> Self {
+        return Stepping::PerUpdate;
+    }
+}
+
+// game time one update catches up on by default; a Fixed backlog past it is
+// dropped rather than spent catching up
+pub const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+// longest Variable step, in seconds, so a stall can't throw bodies through
+// walls
+const MAX_VARIABLE_DT: f64 = 0.1;
+// ends the freeze after a goal's reset; the Game running a SoccerGame
+// delivers it. One updated on its own, whose timer changes nobody takes,
+// kicks off by its own tick count instead
+pub const KICKOFF_TIMER: GameTimerId = GameTimerId(1);
+
+// Cost of the most recent physics step, filled in by update when
+// record_stats is on.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhysicsStats {
+    pub steps: u64,
+    pub last_step: Duration,
+    pub contact_count: usize,
+}
+
+// The goal a team defends. Sides go by join order: slot 0 defends the left
+// goal, slot 1 the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left = 0,
+    Right = 1,
+}
+
+impl Side {
+    pub fn for_slot(slot: usize) -> Side {
+        if slot % 2 == 0 {
+            Side::Left
+        } else {
+            Side::Right
+        }
+    }
+    // -1.0 for the left half of the field, 1.0 for the right
+    pub fn sign(&self) -> f32 {
+        match self {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        }
+    }
+}
+
+pub const MAX_PUCKS_PER_TEAM: usize = 10;
+
+// Kickoff spots for one team on the right half; the left team mirrors them.
+const FORMATION: [(f32, f32); 5] = [
+    (200.0, -200.0),
+    (200.0, 0.0),
+    (200.0, 200.0),
+    (50.0, -150.0),
+    (50.0, 150.0),
+];
+// most pucks up by the halfway line, one back in goal
+const ATTACKING_FORMATION: [(f32, f32); 5] = [
+    (60.0, -200.0),
+    (60.0, 200.0),
+    (120.0, -80.0),
+    (120.0, 80.0),
+    (250.0, 0.0),
+];
+// a wall in front of the goal
+const DEFENSIVE_FORMATION: [(f32, f32); 5] = [
+    (230.0, -150.0),
+    (230.0, 0.0),
+    (230.0, 150.0),
+    (150.0, -75.0),
+    (150.0, 75.0),
+];
+
+// The kickoff layouts a player may pick for their pucks with SetFormation
+// before the match starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Formation {
+    #[default]
+    Balanced,
+    Attacking,
+    Defensive,
+}
+
+impl Formation {
+    pub const ALL: [Formation; 3] = [
+        Formation::Balanced,
+        Formation::Attacking,
+        Formation::Defensive,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Formation::ALL.into_iter().find(|f| f.name() == name);
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Formation::Balanced => "balanced",
+            Formation::Attacking => "attacking",
+            Formation::Defensive => "defensive",
+        };
+    }
+
+    fn spots(&self) -> &'static [(f32, f32); 5] {
+        return match self {
+            Formation::Balanced => &FORMATION,
+            Formation::Attacking => &ATTACKING_FORMATION,
+            Formation::Defensive => &DEFENSIVE_FORMATION,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamInfo {
+    pub player: usize,
+    pub side: Side,
+    pub pucks: Vec<RigidBodyHandle>,
+    pub score: u32,
+    // goals this side's pucks put in its own net, which count in the other
+    // side's score
+    pub own_goals: u32,
+    // what its shots and cooldown are scaled by, and the goals it started on
+    pub handicap: TeamHandicap,
+}
+
+// Running totals behind one slot's SlotStats.
+#[derive(Debug, Default)]
+struct InputTally {
+    shots: u32,
+    speed_total: f32,
+    pucks: HashSet<RigidBodyHandle>,
+    possession_ticks: u64,
+}
+
+// The last puck to touch a ball: its slot, its index in the slot's pucks
+// and the tick it happened on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BallTouch {
+    pub player: usize,
+    pub puck: usize,
+    pub tick: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallMaterial {
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+impl Default for WallMaterial {
+    fn default() -> Self {
+        return WallMaterial {
+            restitution: 0.7,
+            friction: 0.4,
+        };
+    }
+}
+
+// One fixed wall segment. A goal segment lets the ball through (pucks still
+// bounce off it) and a ball crossing it is a goal against the team defending
+// that side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallSpec {
+    pub center: Vector<f32>,
+    pub half_extents: Vector<f32>,
+    pub material: WallMaterial,
+    pub goal: Option<Side>,
+}
+
+// Four walls around a width x height arena, with a goal mouth of goal_width
+// cut into the middle of the left and right walls. Behind each mouth a net of
+// goal_depth, closed by side and back walls, keeps a scored ball in. The top
+// and bottom walls run past the side walls so the corners overlap instead of
+// leaving a seam a fast ball could squeeze through.
+pub fn default_walls(
+    width: f32,
+    height: f32,
+    goal_width: f32,
+    goal_depth: f32,
+    thickness: f32,
+) -> Vec<WallSpec> {
+    let material = WallMaterial::default();
+    let wall = |center: Vector<f32>, half_extents: Vector<f32>| WallSpec {
+        center,
+        half_extents,
+        material,
+        goal: None,
+    };
+    let side_half = (height - goal_width) / 4.0;
+    let mut walls = vec![
+        wall(
+            vector![0.0, height / 2.0 + thickness],
+            vector![width / 2.0 + 2.0 * thickness, thickness],
+        ),
+        wall(
+            vector![0.0, -height / 2.0 - thickness],
+            vector![width / 2.0 + 2.0 * thickness, thickness],
+        ),
+    ];
+    for side in [Side::Left, Side::Right] {
+        let x = side.sign() * (width / 2.0 + thickness);
+        for y in [goal_width / 2.0 + side_half, -goal_width / 2.0 - side_half] {
+            walls.push(wall(vector![x, y], vector![thickness, side_half]));
+        }
+        walls.push(WallSpec {
+            center: vector![x, 0.0],
+            half_extents: vector![thickness, goal_width / 2.0],
+            material,
+            goal: Some(side),
+        });
+        // net sides run from the goal line to the back wall, which covers
+        // their ends
+        let net_x = side.sign() * (width / 2.0 + 2.0 * thickness + goal_depth / 2.0);
+        for y in [goal_width / 2.0 + thickness, -goal_width / 2.0 - thickness] {
+            walls.push(wall(
+                vector![net_x, y],
+                vector![goal_depth / 2.0, thickness],
+            ));
+        }
+        walls.push(wall(
+            vector![
+                side.sign() * (width / 2.0 + 3.0 * thickness + goal_depth),
+                0.0
+            ],
+            vector![thickness, goal_width / 2.0 + 2.0 * thickness],
+        ));
+    }
+    return walls;
+}
+
+// Collision groups: the ball sits in its own group so goal segments can
+// filter it out while still stopping pucks, and goal sensors only see balls.
+const PUCK_GROUP: Group = Group::GROUP_1;
+const BALL_GROUP: Group = Group::GROUP_2;
+const GOAL_GROUP: Group = Group::GROUP_3;
+const GOAL_SENSOR_GROUP: Group = Group::GROUP_4;
+// how far behind the goal line the sensor reaches
+const GOAL_DEPTH: f32 = 30.0;
+
+pub const GOAL_WIDTH: f32 = 160.0;
+// room behind the goal line for a scored ball, and default wall thickness
+pub const GOAL_NET_DEPTH: f32 = 50.0;
+pub const WALL_THICKNESS: f32 = 10.0;
+const NET_THICKNESS: f32 = 4.0;
+const VOLLEY_GRAVITY: f32 = 600.0;
+const VOLLEY_MAX_SHOT_VY: f32 = 700.0;
+const VOLLEY_FLOOR_FRICTION: f32 = 1.0;
+// averaged with the puck's, so even a fully elastic puck loses half its
+// speed each time it lands and soon comes to rest
+const VOLLEY_FLOOR_RESTITUTION: f32 = 0.0;
+
+// Kickoff spots for `count` pucks on the right half: the layout's five
+// spots first, then any extra pucks spread along a midfield column.
+fn formation(layout: Formation, count: usize) -> Vec<(f32, f32)> {
+    let base = layout.spots();
+    let mut spots: Vec<(f32, f32)> = base.iter().copied().take(count).collect();
+    let extra = count.saturating_sub(base.len());
+    for i in 0..extra {
+        let y = -200.0 + 400.0 * (i + 1) as f32 / (extra + 1) as f32;
+        spots.push((125.0, y));
+    }
+    return spots;
+}
+
+// Kickoff spots for the balls: along the halfway line, 60 apart.
+fn ball_spots(count: usize) -> Vec<(f32, f32)> {
+    return (0..count)
+        .map(|i| (0.0, (i as f32 - (count as f32 - 1.0) / 2.0) * 60.0))
+        .collect();
+}
+
+pub const MAX_BALLS: usize = 5;
+
+// Timed pickups for the chaos variant. One spawns every `interval` at a
+// random spot while fewer than `max_on_field` are waiting; touching it with a
+// puck gives that puck's team the effect for `duration`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerUpConfig {
+    pub interval: Duration,
+    pub duration: Duration,
+    pub max_on_field: usize,
+    // puck radius multiplier while BigPuck is active
+    pub big_puck_scale: f32,
+    // max shot speed multiplier while SpeedBoost is active
+    pub speed_boost: f32,
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> Self {
+        return PowerUpConfig {
+            interval: Duration::from_secs(15),
+            duration: Duration::from_secs(10),
+            max_on_field: 2,
+            big_puck_scale: 1.5,
+            speed_boost: 1.5,
+        };
+    }
+}
+
+pub const STALL_NUDGE_SPEED: f32 = 150.0;
+
+// Keeps a ball that has come to rest from holding up play: once it has
+// stayed slower than `speed` for `after` of play, `action` gets it going.
+// With pinned_radius set, a ball that rattles around inside a circle that
+// size for `after`, say wedged in a corner behind pucks, counts too unless a
+// puck moving at `speed` or more hits it; DropBall is the action for that.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AntiStallConfig {
+    pub speed: f32,
+    pub after: Duration,
+    pub action: StallAction,
+    pub pinned_radius: Option<f32>,
+}
+
+impl Default for AntiStallConfig {
+    fn default() -> Self {
+        return AntiStallConfig {
+            speed: 5.0,
+            after: Duration::from_secs(5),
+            action: StallAction::Nudge(STALL_NUDGE_SPEED),
+            pinned_radius: None,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StallAction {
+    // sent off at this speed in a direction from the game's rng
+    Nudge(f32),
+    // back on its kickoff spot, at rest
+    Recenter,
+    // at rest on the free neutral spot nearest it; see drop_ball
+    DropBall,
+}
+
+// Where DropBall may put a ball, as fractions of the field's height along
+// the halfway line, the centre spot first.
+const NEUTRAL_SPOTS: [f32; 5] = [0.0, -0.25, 0.25, -0.375, 0.375];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerUp {
+    pub id: u32,
+    pub kind: PowerUpKind,
+    pub position: Vector<f32>,
+    collider: ColliderHandle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivePowerUp {
+    pub id: u32,
+    pub kind: PowerUpKind,
+    pub player: usize,
+    pub remaining_ms: f64,
+}
+
+const POWER_UP_RADIUS: f32 = 15.0;
+const BOT_SPEED: f32 = 200.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoccerGameConfig {
+    // size of the playing area the walls enclose
+    pub width: f32,
+    pub height: f32,
+    // goal segments are expected on the left and right walls
+    pub walls: Vec<WallSpec>,
+    // clamped to 1..=MAX_PUCKS_PER_TEAM; validate refuses anything outside
+    pub pucks_per_team: usize,
+    // clamped to 1..=MAX_BALLS like pucks_per_team; more than one needs
+    // protocol v3 to be rendered correctly
+    pub balls: usize,
+    // None disables power-ups
+    pub power_ups: Option<PowerUpConfig>,
+    // cap on the speed a SoccerMove can give a puck
+    pub max_shot_speed: f32,
+    // applied to pucks and balls alike
+    pub puck_radius: f32,
+    pub puck_damping: f32,
+    pub puck_restitution: f32,
+    // linear damping for balls; None gives them puck_damping
+    pub ball_damping: Option<f32>,
+    // drag proportional to speed squared, on top of the linear damping, so
+    // fast shots fall off hard while slow bodies keep sliding; 0 turns it
+    // off
+    pub quadratic_drag: f32,
+    pub move_cooldown: Duration,
+    // Boost: velocity change given to the puck, and how often each player
+    // may use it
+    pub boost_speed: f32,
+    pub boost_cooldown: Duration,
+    // solo free play: one player, no opposing pucks, goals don't score
+    pub practice: bool,
+    // updates between a goal and the reset to kickoff spots, then updates
+    // of kickoff freeze before moves are taken again
+    pub goal_reset_ticks: u64,
+    pub kickoff_freeze_ticks: u64,
+    // updates at the start of the game that pucks and balls barely moving
+    // off their kickoff spots are put back, so contact at spawn can't set
+    // them drifting; a moved puck or a served ball goes free. 0, the
+    // default, leaves them be
+    pub settle_ticks: u64,
+    // every body is slowed to this speed after each step, so bounces off
+    // fully elastic pucks can't keep adding energy; None leaves them be
+    pub max_body_speed: Option<f32>,
+    // speed the balls are sent off at, toward a random side, at the start
+    // and when play resumes after a goal; None leaves them still
+    pub serve_speed: Option<f32>,
+    // what the game reports itself as, so variants built from this config
+    // are matched separately
+    pub game_type: u8,
+    // zero for top-down play; side-on variants pull bodies toward the
+    // bottom wall
+    pub gravity: Vector<f32>,
+    // a thin fixed wall standing on the bottom wall at the halfway line,
+    // this tall; None leaves the field open
+    pub net_height: Option<f32>,
+    // cap on the vertical part of a SoccerMove, on top of max_shot_speed,
+    // so pucks can't be flung straight over everything
+    pub max_shot_vy: Option<f32>,
+    // None lets a ball sit still for as long as nobody touches it
+    pub anti_stall: Option<AntiStallConfig>,
+    // share of its speed a puck or ball keeps after each bounce, on top of
+    // restitution, so rallies wind down even between fully elastic bodies;
+    // None leaves bounces to restitution alone
+    pub bounce_decay: Option<f32>,
+    // a goal is credited to the last puck to touch the ball, as an own goal
+    // if it was the conceding side's, unless that touch is more than this
+    // many updates old; None credits any touch since the last reset
+    pub touch_window_ticks: Option<u64>,
+    // color id per side, left then right, told to clients with each puck in
+    // Welcome; a side past the end has none
+    pub team_colors: Vec<u8>,
+    pub stepping: Stepping,
+    // length of a match, counted in simulated time; None plays until
+    // someone leaves
+    pub match_duration: Option<Duration>,
+    // most game time one update catches up on after a stall; the rest is
+    // dropped, and doesn't come off the match clock either
+    pub max_catch_up: Duration,
+    // turn-based play: only one slot's moves are taken at a time, for this
+    // many updates, then the next slot's; None lets everyone move at once
+    pub turn_ticks: Option<u64>,
+}
+
+impl Default for SoccerGameConfig {
+    fn default() -> Self {
+        let width = 600.0; // X-axis boundaries
+        let height = 600.0;
+        return SoccerGameConfig {
+            width,
+            height,
+            walls: default_walls(width, height, GOAL_WIDTH, GOAL_NET_DEPTH, WALL_THICKNESS),
+            pucks_per_team: 5,
+            balls: 1,
+            power_ups: None,
+            max_shot_speed: f32::INFINITY,
+            puck_radius: RADIUS,
+            puck_damping: 0.1,
+            puck_restitution: 1.0,
+            ball_damping: None,
+            quadratic_drag: 0.0,
+            move_cooldown: Duration::ZERO,
+            boost_speed: 600.0,
+            boost_cooldown: Duration::from_secs(5),
+            practice: false,
+            goal_reset_ticks: 90,
+            kickoff_freeze_ticks: 60,
+            settle_ticks: 0,
+            max_body_speed: None,
+            serve_speed: None,
+            game_type: SOCCER_GAME_TYPE,
+            gravity: vector![0.0, 0.0],
+            net_height: None,
+            max_shot_vy: None,
+            anti_stall: None,
+            bounce_decay: None,
+            touch_window_ticks: Some(300),
+            team_colors: vec![],
+            stepping: Stepping::default(),
+            match_duration: None,
+            max_catch_up: MAX_CATCH_UP,
+            turn_ticks: None,
+        };
+    }
+}
+
+impl SoccerGameConfig {
+    // Three balls, power-ups on, and a shot speed cap for SpeedBoost to lift.
+    pub fn chaos() -> Self {
+        return SoccerGameConfig {
+            balls: 3,
+            power_ups: Some(PowerUpConfig::default()),
+            max_shot_speed: 400.0,
+            ..SoccerGameConfig::default()
+        };
+    }
+
+    // The volley variant on top of this config: gravity, a net, a grippier
+    // floor for pucks to roll on, and a cap on how high they can be thrown.
+    pub fn volley(mut self) -> Self {
+        self.game_type = VOLLEY_GAME_TYPE;
+        self.gravity = vector![0.0, -VOLLEY_GRAVITY];
+        self.net_height = Some(self.height / 5.0);
+        self.max_shot_vy = Some(VOLLEY_MAX_SHOT_VY);
+        let floor = self.height / 2.0;
+        for wall in &mut self.walls {
+            if wall.goal.is_none() && wall.center.y + wall.half_extents.y <= -floor {
+                wall.material.friction = VOLLEY_FLOOR_FRICTION;
+                wall.material.restitution = VOLLEY_FLOOR_RESTITUTION;
+            }
+        }
+        return self;
+    }
+
+    // What with_config would otherwise clamp, or build into a field nobody
+    // can play on: tunables SetGameParams would refuse, counts out of range,
+    // and kickoff spots that don't fit inside width x height with a
+    // puck_radius to spare.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_params(&self.params()).map_err(str::to_string)?;
+        if !(1..=MAX_PUCKS_PER_TEAM).contains(&self.pucks_per_team) {
+            return Err(format!(
+                "pucks_per_team is {}, expected 1 to {}",
+                self.pucks_per_team, MAX_PUCKS_PER_TEAM
+            ));
+        }
+        if !(1..=MAX_BALLS).contains(&self.balls) {
+            return Err(format!(
+                "balls is {}, expected 1 to {}",
+                self.balls, MAX_BALLS
+            ));
+        }
+        if let Some(decay) = self.bounce_decay {
+            if !(decay > 0.0 && decay <= 1.0) {
+                return Err(format!("bounce_decay is {}, expected (0, 1]", decay));
+            }
+        }
+        let spots = formation(Formation::Balanced, self.pucks_per_team)
+            .into_iter()
+            .chain(ball_spots(self.balls));
+        return self.check_kickoff(spots);
+    }
+
+    // Err naming the first spot a puck or ball put there would stick out of
+    // the field from.
+    fn check_kickoff(&self, spots: impl IntoIterator<Item = (f32, f32)>) -> Result<(), String> {
+        for (x, y) in spots {
+            let reach_x = x.abs() + self.puck_radius;
+            let reach_y = y.abs() + self.puck_radius;
+            if reach_x >= self.width / 2.0 || reach_y >= self.height / 2.0 {
+                return Err(format!(
+                    "a {}x{} field is too small for kickoff at ({}, {}) with puck_radius {}",
+                    self.width, self.height, x, y, self.puck_radius
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn params(&self) -> GameParams {
+        return GameParams {
+            puck_radius: Some(self.puck_radius),
+            puck_damping: Some(self.puck_damping),
+            puck_restitution: Some(self.puck_restitution),
+            max_shot_speed: Some(self.max_shot_speed),
+            move_cooldown_ms: Some(self.move_cooldown.as_millis() as u32),
+        };
+    }
+
+    // Expects params that passed validate_params.
+    pub fn apply_params(&mut self, params: &GameParams) {
+        if let Some(radius) = params.puck_radius {
+            self.puck_radius = radius;
+        }
+        if let Some(damping) = params.puck_damping {
+            self.puck_damping = damping;
+        }
+        if let Some(restitution) = params.puck_restitution {
+            self.puck_restitution = restitution;
+        }
+        if let Some(speed) = params.max_shot_speed {
+            self.max_shot_speed = speed;
+        }
+        if let Some(cooldown) = params.move_cooldown_ms {
+            self.move_cooldown = Duration::from_millis(cooldown as u64);
+        }
+    }
+}
+
+// Rejects values that would break the simulation. Every field is optional,
+// so only the ones present are checked.
+pub fn validate_params(params: &GameParams) -> Result<(), &'static str> {
+    if let Some(radius) = params.puck_radius {
+        if !(radius > 0.0 && radius <= 100.0) {
+            return Err("puck_radius must be in (0, 100]");
+        }
+    }
+    if let Some(damping) = params.puck_damping {
+        if !(damping >= 0.0 && damping.is_finite()) {
+            return Err("puck_damping must be a non-negative number");
+        }
+    }
+    if let Some(restitution) = params.puck_restitution {
+        if !(0.0..=1.0).contains(&restitution) {
+            return Err("puck_restitution must be in [0, 1]");
+        }
+    }
+    if let Some(speed) = params.max_shot_speed {
+        // infinity is allowed and means uncapped
+        if !(speed > 0.0) {
+            return Err("max_shot_speed must be positive");
+        }
+    }
+    return Ok(());
+}
+const RADIUS: f32 = 20.0;
+const PUCK_FRICTION: f32 = 0.5;
+const MAX_ANGVEL: f32 = 30.0;
+// how far outside the arena a body may drift before it is considered lost
+const WATCHDOG_MARGIN: f32 = 50.0;
+// fastest a body can be and still be held still by the settle phase
+const SETTLE_SPEED: f32 = 5.0;
+impl SoccerGame {
+    pub fn new() -> Self {
+        return SoccerGame::with_config(SoccerGameConfig::default());
+    }
+
+    pub fn with_config(config: SoccerGameConfig) -> Self {
+        let built_from = config.clone();
+        let SoccerGameConfig {
+            width: game_width,
+            height: game_height,
+            walls,
+            pucks_per_team,
+            balls: ball_count,
+            power_ups: power_up_config,
+            max_shot_speed,
+            puck_radius,
+            puck_damping,
+            puck_restitution,
+            ball_damping,
+            quadratic_drag,
+            move_cooldown,
+            boost_speed,
+            boost_cooldown,
+            practice,
+            goal_reset_ticks,
+            kickoff_freeze_ticks,
+            settle_ticks,
+            max_body_speed,
+            serve_speed,
+            game_type,
+            gravity,
+            net_height,
+            max_shot_vy,
+            anti_stall,
+            bounce_decay,
+            touch_window_ticks,
+            team_colors,
+            stepping,
+            match_duration,
+            max_catch_up,
+            turn_ticks,
+        } = config;
+        let pucks_per_team = pucks_per_team.clamp(1, MAX_PUCKS_PER_TEAM);
+        let ball_count = ball_count.clamp(1, MAX_BALLS);
+        let integration_parameters = IntegrationParameters::default();
+        let mut physics_pipeline = PhysicsPipeline::new();
+        let mut broad_phase = DefaultBroadPhase::new();
+        let mut island_manager = IslandManager::new();
+        let mut narrow_phase = NarrowPhase::new();
+        let mut bodies = RigidBodySet::new();
+        let mut colliders = ColliderSet::new();
+        let mut impulse_joints = ImpulseJointSet::new();
+        let mut multibody_joints = MultibodyJointSet::new();
+        let mut ccd_solver = CCDSolver::new();
+        // Function to create a moving ball
+        let mut kickoff = HashMap::new();
+        let mut body_colliders = HashMap::new();
+        let mut create_circle =
+            |x: f32, y: f32, groups: InteractionGroups, damping: f32| -> RigidBodyHandle {
+                let body = bodies.insert(
+                    RigidBodyBuilder::dynamic()
+                        .translation(vector![x, y]) // Start position
+                        .linvel(vector![0.0, 0.0]) // Initial velocity
+                        .linear_damping(damping) // friction
+                        // swept collisions so a hard shot can't tunnel a wall
+                        .ccd_enabled(true)
+                        .build(),
+                );
+                let collider = colliders.insert_with_parent(
+                    ColliderBuilder::ball(puck_radius)
+                        .restitution(puck_restitution) // 1.0 is a perfectly elastic bounce
+                        .friction(PUCK_FRICTION) // lets spin carry over on contact
+                        .collision_groups(groups)
+                        .build(),
+                    body,
+                    &mut bodies,
+                );
+                kickoff.insert(body, vector![x, y]);
+                body_colliders.insert(body, collider);
+                return body;
+            };
+        let pucks_groups = InteractionGroups::new(PUCK_GROUP, Group::ALL);
+        let mut create_team = |player: usize| -> TeamInfo {
+            let side = Side::for_slot(player);
+            // practice has nobody in the second slot to move its pucks
+            let count = if practice && player == 1 {
+                0
+            } else {
+                pucks_per_team
+            };
+            let pucks = formation(Formation::Balanced, count)
+                .into_iter()
+                .map(|(x, y)| create_circle(side.sign() * x, y, pucks_groups, puck_damping))
+                .collect();
+            return TeamInfo {
+                player,
+                side,
+                pucks,
+                score: 0,
+                own_goals: 0,
+                handicap: TeamHandicap::default(),
+            };
+        };
+        let teams = [create_team(0), create_team(1)];
+        let mut pucks = vec![];
+        for team in &teams {
+            pucks.extend_from_slice(&team.pucks);
+        }
+
+        let balls = ball_spots(ball_count)
+            .into_iter()
+            .map(|(x, y)| {
+                create_circle(
+                    x,
+                    y,
+                    InteractionGroups::new(BALL_GROUP, Group::ALL),
+                    ball_damping.unwrap_or(puck_damping),
+                )
+            })
+            .collect::<Vec<_>>();
+        // balls report their contacts so a goal can be credited to whoever
+        // touched the ball last
+        for ball in &balls {
+            colliders[body_colliders[ball]].set_active_events(ActiveEvents::COLLISION_EVENTS);
+        }
+        // and with bounce_decay so do pucks, which bounce off each other
+        if bounce_decay.is_some() {
+            for puck in &pucks {
+                colliders[body_colliders[puck]].set_active_events(ActiveEvents::COLLISION_EVENTS);
+            }
+        }
+        let mut goal_sensors = HashMap::new();
+        for wall in &walls {
+            if let Some(defender) = wall.goal {
+                // starts one ball radius behind the inner face, so a ball
+                // touches it once its center crosses the goal line
+                let inner_x = wall.center.x.abs() - wall.half_extents.x;
+                let x = wall.center.x.signum() * (inner_x + puck_radius + GOAL_DEPTH / 2.0);
+                let sensor = colliders.insert(
+                    ColliderBuilder::cuboid(GOAL_DEPTH / 2.0, wall.half_extents.y)
+                        .translation(vector![x, wall.center.y])
+                        .sensor(true)
+                        .active_events(ActiveEvents::COLLISION_EVENTS)
+                        .collision_groups(InteractionGroups::new(GOAL_SENSOR_GROUP, BALL_GROUP))
+                        .build(),
+                );
+                goal_sensors.insert(sensor, defender);
+            }
+            let body = bodies.insert(RigidBodyBuilder::fixed().translation(wall.center).build());
+            let groups = match wall.goal {
+                Some(_) => InteractionGroups::new(GOAL_GROUP, !BALL_GROUP),
+                None => InteractionGroups::all(),
+            };
+            colliders.insert_with_parent(
+                ColliderBuilder::cuboid(wall.half_extents.x, wall.half_extents.y)
+                    .restitution(wall.material.restitution)
+                    .friction(wall.material.friction)
+                    .collision_groups(groups)
+                    .build(),
+                body,
+                &mut bodies,
+            );
+        }
+        if let Some(net_height) = net_height {
+            let floor = -game_height / 2.0;
+            let body = bodies.insert(
+                RigidBodyBuilder::fixed()
+                    .translation(vector![0.0, floor + net_height / 2.0])
+                    .build(),
+            );
+            colliders.insert_with_parent(
+                ColliderBuilder::cuboid(NET_THICKNESS / 2.0, net_height / 2.0)
+                    .restitution(WallMaterial::default().restitution)
+                    .friction(WallMaterial::default().friction)
+                    .build(),
+                body,
+                &mut bodies,
+            );
+        }
+        let bounds = walls
+            .iter()
+            .fold(vector![0.0, 0.0], |bounds: Vector<f32>, wall| {
+                vector![
+                    bounds.x.max(wall.center.x.abs() + wall.half_extents.x),
+                    bounds.y.max(wall.center.y.abs() + wall.half_extents.y)
+                ]
+            });
+
+        let built = (bodies.len(), colliders.len());
+        SoccerGame {
+            pipeline: physics_pipeline,
+            colliders,
+            bodies,
+            pucks,
+            teams,
+            balls,
+            narrow_phase,
+            integration_parameters,
+            broad_phase,
+            island_manager,
+            impulse_joints,
+            multibody_joints,
+            ccd_solver,
+            query_pipeline: QueryPipeline::new(),
+            width: game_width,
+            height: game_height,
+            kickoff,
+            watchdog_resets: 0,
+            max_angvel: MAX_ANGVEL,
+            control_mode: ControlMode::default(),
+            walls,
+            bounds,
+            goals: vec![],
+            record_stats: false,
+            physics_stats: PhysicsStats::default(),
+            impulse_used: HashMap::new(),
+            max_shot_speed,
+            next_power_up: power_up_config
+                .as_ref()
+                .map_or(0.0, |config| config.interval.as_millis() as f64),
+            power_up_config,
+            power_ups: vec![],
+            active_effects: vec![],
+            body_colliders,
+            puck_radius,
+            puck_damping,
+            puck_restitution,
+            move_cooldown,
+            clock_us: 0,
+            last_move: HashMap::new(),
+            boost_speed,
+            boost_cooldown,
+            last_boost: HashMap::new(),
+            bots: vec![],
+            practice,
+            next_power_up_id: 1,
+            events: vec![],
+            history: vec![],
+            timer_changes: vec![],
+            rng: GameRng::new(fresh_seed()),
+            goal_sensors,
+            collisions: CollisionCollector::default(),
+            scored: HashSet::new(),
+            phase: SoccerPhase::Play,
+            tick: 0,
+            goal_reset_ticks,
+            kickoff_freeze_ticks,
+            settle_ticks,
+            max_body_speed,
+            serve_speed,
+            ball_damping,
+            quadratic_drag,
+            last_ball_toucher: None,
+            possession_since: 0,
+            input_stats: HashMap::new(),
+            ball_touchers: HashMap::new(),
+            touch_window_ticks,
+            game_type,
+            gravity,
+            max_shot_vy,
+            anti_stall,
+            stalled_since: HashMap::new(),
+            pinned_since: HashMap::new(),
+            bounce_decay,
+            team_colors,
+            stepping,
+            step_backlog_us: 0,
+            match_duration,
+            max_catch_up,
+            played_us: 0,
+            warm_up: false,
+            tuning_version: 0,
+            turn_ticks,
+            turn: None,
+            handicap: None,
+            messages: vec![],
+            config: built_from,
+            built,
+        }
+    }
+
+    pub fn config(&self) -> &SoccerGameConfig {
+        return &self.config;
+    }
+
+    // Every puck and ball, pucks first, in snapshot order.
+    pub fn body_states(&self) -> Vec<BodyState> {
+        return self
+            .pucks
+            .iter()
+            .chain(&self.balls)
+            .map(|handle| {
+                let body = &self.bodies[*handle];
+                BodyState {
+                    position: [body.translation().x, body.translation().y],
+                    linvel: [body.linvel().x, body.linvel().y],
+                    angvel: body.angvel(),
+                }
+            })
+            .collect();
+    }
+
+    // A world built the way this one was, to be stepped alongside it: the
+    // same config, tunables, kickoff spots and bots, served from seed. It
+    // only matches this one before kickoff; after that they have different
+    // pasts.
+    pub fn shadow(&self, seed: u64) -> SoccerGame {
+        let mut shadow = SoccerGame::with_config(self.config.clone());
+        shadow.control_mode = self.control_mode;
+        shadow.integration_parameters = self.integration_parameters;
+        shadow.record_stats = self.record_stats;
+        shadow.retune(&self.tuning());
+        let pucks: Vec<_> = shadow.pucks.iter().copied().zip(&self.pucks).collect();
+        for (copy, live) in pucks {
+            let spot = self.kickoff[live];
+            shadow.kickoff.insert(copy, spot);
+            shadow.bodies[copy].set_translation(spot, true);
+        }
+        shadow.bots = self.bots.clone();
+        if let Some(handicap) = self.handicap {
+            shadow.set_handicap(handicap);
+        }
+        shadow.reseed(seed);
+        return shadow;
+    }
+
+    // Puts bodies and scores back from a save. False, with nothing changed,
+    // if the save was taken from a different arena.
+    pub fn restore(&mut self, bodies: &[BodyState], scores: &[u32]) -> bool {
+        if bodies.len() != self.pucks.len() + self.balls.len() || scores.len() != self.teams.len() {
+            return false;
+        }
+        let handles: Vec<_> = self.pucks.iter().chain(&self.balls).copied().collect();
+        for (handle, saved) in handles.into_iter().zip(bodies) {
+            let body = &mut self.bodies[handle];
+            body.set_translation(vector![saved.position[0], saved.position[1]], true);
+            body.set_linvel(vector![saved.linvel[0], saved.linvel[1]], true);
+            body.set_angvel(saved.angvel, true);
+        }
+        for (team, score) in self.teams.iter_mut().zip(scores) {
+            team.score = *score;
+        }
+        self.phase = SoccerPhase::Play;
+        return true;
+    }
+
+    // Puts everything that changes during a match back the way with_config
+    // left it, keeping the geometry, so the world can host another game.
+    pub fn reset(&mut self) {
+        for power_up in std::mem::take(&mut self.power_ups) {
+            self.colliders.remove(
+                power_up.collider,
+                &mut self.island_manager,
+                &mut self.bodies,
+                true,
+            );
+        }
+        self.active_effects.clear();
+        for collider in self.body_colliders.values() {
+            self.colliders[*collider].set_shape(SharedShape::ball(self.puck_radius));
+        }
+        for player in self
+            .teams
+            .iter()
+            .map(|team| team.player)
+            .collect::<Vec<_>>()
+        {
+            self.freeze_pucks(player, false);
+            let _ = self.set_formation(player, Formation::Balanced);
+        }
+        let config = self.config.clone();
+        self.retune(&config);
+        self.reset_positions();
+        for team in &mut self.teams {
+            team.score = 0;
+            team.own_goals = 0;
+            team.handicap = TeamHandicap::default();
+        }
+        self.handicap = None;
+        self.goals.clear();
+        self.events.clear();
+        self.history.clear();
+        // a kickoff still pending in the game running this one is dropped
+        // along with any change not yet taken
+        self.timer_changes.clear();
+        self.timer_changes.push(TimerChange::Cancel(KICKOFF_TIMER));
+        self.collisions.0.lock().unwrap().clear();
+        self.scored.clear();
+        self.impulse_used.clear();
+        self.last_move.clear();
+        self.last_boost.clear();
+        self.bots.clear();
+        self.stalled_since.clear();
+        self.pinned_since.clear();
+        self.clock_us = 0;
+        self.tick = 0;
+        self.possession_since = 0;
+        self.input_stats.clear();
+        self.phase = SoccerPhase::Play;
+        self.next_power_up = config
+            .power_ups
+            .as_ref()
+            .map_or(0.0, |config| config.interval.as_millis() as f64);
+        self.next_power_up_id = 1;
+        self.watchdog_resets = 0;
+        self.physics_stats = PhysicsStats::default();
+        self.record_stats = false;
+        self.control_mode = ControlMode::default();
+        self.integration_parameters = IntegrationParameters::default();
+        self.team_colors = config.team_colors.clone();
+        self.stepping = config.stepping;
+        self.step_backlog_us = 0;
+        self.match_duration = config.match_duration;
+        self.max_catch_up = config.max_catch_up;
+        self.played_us = 0;
+        self.warm_up = false;
+        self.turn_ticks = config.turn_ticks;
+        self.turn = None;
+        self.messages.clear();
+    }
+
+    // Gives each side its half of handicap, clamped, and starts its score
+    // at the goals it is given. Meant for before kickoff: a score already
+    // run up is replaced.
+    pub fn set_handicap(&mut self, handicap: HandicapConfig) {
+        let handicap = handicap.clamped();
+        for team in &mut self.teams {
+            team.handicap = match team.side {
+                Side::Left => handicap.left,
+                Side::Right => handicap.right,
+            };
+            team.score = team.handicap.score;
+        }
+        self.handicap = Some(handicap);
+    }
+
+    // Moves the player's pucks onto the layout's spots, at rest, and makes
+    // those their kickoff spots for every reset after. Err when the field
+    // is too small for the layout.
+    pub fn set_formation(&mut self, player: usize, layout: Formation) -> Result<(), String> {
+        let team = match self.teams.iter().find(|team| team.player == player) {
+            Some(team) => team,
+            None => return Err(format!("No team for slot {}", player)),
+        };
+        let spots = formation(layout, team.pucks.len());
+        self.config.check_kickoff(spots.iter().copied())?;
+        let sign = team.side.sign();
+        for (handle, (x, y)) in team.pucks.iter().zip(spots) {
+            let spot = vector![sign * x, y];
+            self.kickoff.insert(*handle, spot);
+            let body = &mut self.bodies[*handle];
+            body.set_translation(spot, true);
+            body.set_linvel(vector![0.0, 0.0], true);
+            body.set_angvel(0.0, true);
+        }
+        return Ok(());
+    }
+
+    // Whether the world is as with_config built it: nothing added or left
+    // over, every body still on its kickoff spot, and nothing moving.
+    pub fn is_clean(&self) -> bool {
+        if (self.bodies.len(), self.colliders.len()) != self.built
+            || !self.impulse_joints.is_empty()
+            || self.multibody_joints.iter().next().is_some()
+            || !self.power_ups.is_empty()
+        {
+            return false;
+        }
+        if self.bodies.iter().any(|(_, body)| body.user_data != 0)
+            || self
+                .colliders
+                .iter()
+                .any(|(_, collider)| collider.user_data != 0)
+        {
+            return false;
+        }
+        return self.pucks.iter().chain(&self.balls).all(|handle| {
+            let body = &self.bodies[*handle];
+            *body.translation() == self.kickoff[handle]
+                && *body.linvel() == vector![0.0, 0.0]
+                && body.angvel() == 0.0
+        });
+    }
+
+    pub fn set_preset(&mut self, preset: PhysicsPreset) {
+        self.integration_parameters = preset.integration_parameters();
+    }
+
+    // Current values of the tunable parameters. Radius is reported but can
+    // only change for new games.
+    pub fn params(&self) -> GameParams {
+        return GameParams {
+            puck_radius: Some(self.puck_radius),
+            puck_damping: Some(self.puck_damping),
+            puck_restitution: Some(self.puck_restitution),
+            max_shot_speed: Some(self.max_shot_speed),
+            move_cooldown_ms: Some(self.move_cooldown.as_millis() as u32),
+        };
+    }
+
+    // Patches the parameters that are safe to change mid-match; anything
+    // touching geometry (radius, arena, puck count) is left alone. Expects
+    // params that passed validate_params.
+    pub fn apply_params(&mut self, params: &GameParams) {
+        if let Some(damping) = params.puck_damping {
+            self.puck_damping = damping;
+            for handle in &self.pucks {
+                self.bodies[*handle].set_linear_damping(damping);
+            }
+            // balls with their own damping keep it
+            if self.ball_damping.is_none() {
+                for handle in &self.balls {
+                    self.bodies[*handle].set_linear_damping(damping);
+                }
+            }
+        }
+        if let Some(restitution) = params.puck_restitution {
+            self.puck_restitution = restitution;
+            for collider in self.body_colliders.values() {
+                self.colliders[*collider].set_restitution(restitution);
+            }
+        }
+        if let Some(speed) = params.max_shot_speed {
+            self.max_shot_speed = speed;
+        }
+        if let Some(cooldown) = params.move_cooldown_ms {
+            self.move_cooldown = Duration::from_millis(cooldown as u64);
+        }
+    }
+
+    // Brings the game in line with config as far as that goes without a new
+    // world: damping, restitution, drag, bounce decay, the speed caps and
+    // the move and boost tunables. Field, walls, radius and body counts
+    // stay as built. The tunables become the game's own config too, so a
+    // reset goes back to them rather than to what it was built with.
+    pub fn retune(&mut self, config: &SoccerGameConfig) {
+        let params = GameParams {
+            puck_radius: None,
+            ..config.params()
+        };
+        self.config.apply_params(&params);
+        self.config.ball_damping = config.ball_damping;
+        self.config.quadratic_drag = config.quadratic_drag;
+        self.config.bounce_decay = config.bounce_decay;
+        self.config.max_body_speed = config.max_body_speed;
+        self.config.boost_speed = config.boost_speed;
+        self.config.boost_cooldown = config.boost_cooldown;
+        self.ball_damping = config.ball_damping;
+        self.apply_params(&params);
+        if let Some(damping) = self.ball_damping {
+            for handle in &self.balls {
+                self.bodies[*handle].set_linear_damping(damping);
+            }
+        }
+        self.quadratic_drag = config.quadratic_drag;
+        self.bounce_decay = config.bounce_decay;
+        self.max_body_speed = config.max_body_speed;
+        self.boost_speed = config.boost_speed;
+        self.boost_cooldown = config.boost_cooldown;
+    }
+
+    // The config that would retune another world to this one's tunables.
+    fn tuning(&self) -> SoccerGameConfig {
+        let mut config = self.config.clone();
+        config.apply_params(&self.params());
+        config.ball_damping = self.ball_damping;
+        config.quadratic_drag = self.quadratic_drag;
+        config.bounce_decay = self.bounce_decay;
+        config.max_body_speed = self.max_body_speed;
+        config.boost_speed = self.boost_speed;
+        config.boost_cooldown = self.boost_cooldown;
+        return config;
+    }
+
+    pub fn team(&self, player: usize) -> Option<&TeamInfo> {
+        self.teams.iter().find(|team| team.player == player)
+    }
+
+    // The bodies interest picks out for player, as a mask of indices in State
+    // order. Only bodies the game has are set, past the 64 a mask can hold.
+    pub fn interest_mask(&self, player: usize, interest: &SetInterestMessage) -> u64 {
+        let own = self.team(player).map_or(&[][..], |team| &team.pucks[..]);
+        let groups = self.pucks.iter().map(|puck| match own.contains(puck) {
+            true => InterestGroup::MyPucks,
+            false => InterestGroup::OpponentPucks,
+        });
+        let groups = groups.chain(self.balls.iter().map(|_| InterestGroup::Ball));
+        let mut mask = 0;
+        for (index, group) in groups.take(64).enumerate() {
+            if interest.bodies & (1 << index) != 0 || interest.groups & group.bit() != 0 {
+                mask |= 1 << index;
+            }
+        }
+        return mask;
+    }
+
+    // Pins a player's pucks where they stand, or lets them go again. Frozen
+    // pucks are fixed bodies, so no move, impulse or collision shifts them.
+    // False if the player has no side here.
+    pub fn freeze_pucks(&mut self, player: usize, frozen: bool) -> bool {
+        let pucks = match self.team(player) {
+            Some(team) => team.pucks.clone(),
+            None => return false,
+        };
+        for puck in pucks {
+            let body = &mut self.bodies[puck];
+            if frozen {
+                body.set_linvel(vector![0.0, 0.0], false);
+                body.set_angvel(0.0, false);
+                body.set_body_type(RigidBodyType::Fixed, false);
+            } else {
+                body.set_body_type(RigidBodyType::Dynamic, true);
+            }
+        }
+        return true;
+    }
+
+    pub fn is_frozen(&self, player: usize) -> bool {
+        return self.team(player).map_or(false, |team| {
+            team.pucks
+                .iter()
+                .any(|puck| !self.bodies[*puck].is_dynamic())
+        });
+    }
+
+    // The puck a player may move, or None if target isn't one of their own.
+    pub fn team_puck(&self, player: usize, target: u8) -> Option<RigidBodyHandle> {
+        return self.team(player)?.pucks.get(target as usize).copied();
+    }
+
+    // Where the pucks a player steers are and how fast they are going, by
+    // SoccerMove target.
+    pub fn own_pucks(&self, player: usize) -> Vec<OwnPuck> {
+        let pucks = match self.team(player) {
+            Some(team) => &team.pucks,
+            None => return vec![],
+        };
+        return pucks
+            .iter()
+            .enumerate()
+            .filter_map(|(target, handle)| {
+                let body = self.bodies.get(*handle)?;
+                return Some(OwnPuck {
+                    target: target as u8,
+                    x: body.translation().x,
+                    y: body.translation().y,
+                    vx: body.linvel().x,
+                    vy: body.linvel().y,
+                });
+            })
+            .collect();
+    }
+
+    pub fn apply_move(&mut self, handle: RigidBodyHandle, vx: f32, vy: f32, angular: f32) {
+        // frozen pucks don't take moves
+        if !self.bodies[handle].is_dynamic() {
+            return;
+        }
+        let angular = if angular.is_finite() {
+            angular.clamp(-self.max_angvel, self.max_angvel)
+        } else {
+            0.0
+        };
+        let team = self.teams.iter().find(|team| team.pucks.contains(&handle));
+        let handicap = team.map_or(TeamHandicap::default(), |team| team.handicap);
+        let shooter = team.map(|team| team.player);
+        let cooldown_ms = self.move_cooldown.as_secs_f64() * 1000.0 * handicap.move_cooldown as f64;
+        if let Some(last) = self.last_move.get(&handle) {
+            if cooldown_ms > 0.0 && self.clock_ms() - last < cooldown_ms {
+                return;
+            }
+        }
+        self.last_move.insert(handle, self.clock_ms());
+        let mut max_speed = self.max_shot_speed * handicap.shot_speed;
+        if let Some(player) = shooter {
+            max_speed *= self.effect_multiplier(player, PowerUpKind::SpeedBoost);
+        }
+        let (mut vx, mut vy) = (vx, vy);
+        let speed = (vx * vx + vy * vy).sqrt();
+        if speed > max_speed {
+            vx *= max_speed / speed;
+            vy *= max_speed / speed;
+        }
+        if let Some(max_vy) = self.max_shot_vy {
+            vy = vy.clamp(-max_vy, max_vy);
+        }
+        let speed = (vx * vx + vy * vy).sqrt();
+        if let (Some(player), false) = (shooter, self.warm_up) {
+            if speed > 0.0 && speed.is_finite() {
+                let tally = self.input_stats.entry(player).or_default();
+                tally.shots += 1;
+                tally.speed_total += speed;
+                tally.pucks.insert(handle);
+            }
+        }
+        let body = &mut self.bodies[handle];
+        match self.control_mode {
+            ControlMode::Velocity => body.set_linvel(vector![vx, vy], true),
+            ControlMode::Impulse { max_impulse } => {
+                let mut impulse = vector![vx, vy] * body.mass();
+                let used = self.impulse_used.entry(handle).or_insert(0.0);
+                let remaining = (max_impulse - *used).max(0.0);
+                let magnitude = impulse.norm();
+                if !magnitude.is_finite() {
+                    return;
+                }
+                if magnitude > remaining {
+                    impulse *= remaining / magnitude;
+                }
+                *used += impulse.norm();
+                body.apply_impulse(impulse, true);
+            }
+        }
+        body.set_angvel(angular, true);
+    }
+
+    // A deliberately simple opponent: each tick the bot's puck nearest the
+    // first ball drives straight at it, on its own turns only when the game
+    // is turn-based.
+    fn drive_bots(&mut self) {
+        let target = *self.bodies[self.balls[0]].translation();
+        for i in 0..self.bots.len() {
+            if !self.has_turn(self.bots[i]) {
+                continue;
+            }
+            let pucks = match self.team(self.bots[i]) {
+                Some(team) => team.pucks.clone(),
+                None => continue,
+            };
+            let nearest = pucks
+                .into_iter()
+                .map(|puck| (puck, target - self.bodies[puck].translation()))
+                .min_by(|(_, a), (_, b)| a.norm().total_cmp(&b.norm()));
+            if let Some((puck, toward)) = nearest {
+                let distance = toward.norm();
+                if distance > 0.0 {
+                    let velocity = toward / distance * BOT_SPEED;
+                    self.apply_move(puck, velocity.x, velocity.y, 0.0);
+                }
+            }
+        }
+    }
+
+    // ms until player may Boost again; 0 when it's available
+    pub fn boost_remaining_ms(&self, player: usize) -> u32 {
+        let cooldown_ms = self.boost_cooldown.as_secs_f64() * 1000.0;
+        return match self.last_boost.get(&player) {
+            Some(last) => (last + cooldown_ms - self.clock_ms()).max(0.0).ceil() as u32,
+            None => 0,
+        };
+    }
+
+    // Pushes one of player's pucks toward (dx, dy), adding boost_speed to its
+    // velocity. Err carries the cooldown left when the boost is refused; a
+    // target that isn't the player's puck or a zero direction is a no-op and
+    // doesn't start the cooldown.
+    pub fn boost(&mut self, player: usize, target: u8, dx: f32, dy: f32) -> Result<(), u32> {
+        let remaining_ms = self.boost_remaining_ms(player);
+        if remaining_ms > 0 {
+            return Err(remaining_ms);
+        }
+        let puck = match self.team_puck(player, target) {
+            Some(puck) => puck,
+            None => return Ok(()),
+        };
+        let length = (dx * dx + dy * dy).sqrt();
+        if !(length > 0.0 && length.is_finite()) {
+            return Ok(());
+        }
+        let body = &mut self.bodies[puck];
+        let impulse = vector![dx, dy] / length * self.boost_speed * body.mass();
+        body.apply_impulse(impulse, true);
+        self.last_boost.insert(player, self.clock_ms());
+        return Ok(());
+    }
+
+    // v2 snapshot: x, y, rotation angle and angular velocity per body.
+    pub fn to_bytes_v2(&self) -> Vec<u8> {
+        let mut data = Vec::<u8>::with_capacity((self.pucks.len() + self.balls.len()) * 16);
+        self.encode_bodies(&mut data);
+        return data;
+    }
+
+    // v3 snapshot: the v2 bodies behind explicit puck and ball counts,
+    // followed by power-ups on the field and running effects. The layout is
+    // spelled out on SoccerStateSnapshot.
+    pub fn to_bytes_v3(&self) -> Vec<u8> {
+        let mut data = vec![self.pucks.len() as u8, self.balls.len() as u8];
+        self.encode_bodies(&mut data);
+        let power_ups = &self.power_ups[..self.power_ups.len().min(u8::MAX as usize)];
+        data.push(power_ups.len() as u8);
+        for power_up in power_ups {
+            data.extend_from_slice(&power_up.id.to_le_bytes());
+            data.push(power_up.kind as u8);
+            data.extend_from_slice(&power_up.position.x.to_le_bytes());
+            data.extend_from_slice(&power_up.position.y.to_le_bytes());
+        }
+        let effects = &self.active_effects[..self.active_effects.len().min(u8::MAX as usize)];
+        data.push(effects.len() as u8);
+        for effect in effects {
+            data.push(effect.kind as u8);
+            data.push(effect.player as u8);
+            data.extend_from_slice(&(effect.remaining_ms.max(0.0) as u32).to_le_bytes());
+        }
+        return data;
+    }
+
+    // The quantized body for player's view, up to but not including the
+    // ack_seq and boost cooldown: v3's fields and the own pucks of v5 in
+    // fixed point. The layout is spelled out by QUANTIZED_VELOCITY_SCALE.
+    pub fn to_bytes_quantized(&self, player: Option<usize>) -> Vec<u8> {
+        let half_width = self.bounds.x.ceil() as u16;
+        let half_height = self.bounds.y.ceil() as u16;
+        let mut data = vec![];
+        for half in [half_width, half_height] {
+            data.extend_from_slice(&half.to_le_bytes());
+        }
+        let position = |data: &mut Vec<u8>, x: f32, y: f32| {
+            data.extend_from_slice(&quantize_position(x, half_width).to_le_bytes());
+            data.extend_from_slice(&quantize_position(y, half_height).to_le_bytes());
+        };
+        let own_pucks = player.map_or(vec![], |player| self.own_pucks(player));
+        data.push(own_pucks.len() as u8);
+        for puck in own_pucks {
+            data.push(puck.target);
+            position(&mut data, puck.x, puck.y);
+            for value in [puck.vx, puck.vy] {
+                data.extend_from_slice(&quantize(value, QUANTIZED_VELOCITY_SCALE).to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&[self.pucks.len() as u8, self.balls.len() as u8]);
+        for handle in self.pucks.iter().chain(&self.balls) {
+            if let Some(body) = self.bodies.get(*handle) {
+                position(&mut data, body.translation().x, body.translation().y);
+                let angle = quantize(body.rotation().angle(), QUANTIZED_ANGLE_SCALE);
+                data.extend_from_slice(&angle.to_le_bytes());
+                let angvel = quantize(body.angvel(), QUANTIZED_ANGVEL_SCALE);
+                data.extend_from_slice(&angvel.to_le_bytes());
+            }
+        }
+        let power_ups = &self.power_ups[..self.power_ups.len().min(u8::MAX as usize)];
+        data.push(power_ups.len() as u8);
+        for power_up in power_ups {
+            data.extend_from_slice(&power_up.id.to_le_bytes());
+            data.push(power_up.kind as u8);
+            position(&mut data, power_up.position.x, power_up.position.y);
+        }
+        let effects = &self.active_effects[..self.active_effects.len().min(u8::MAX as usize)];
+        data.push(effects.len() as u8);
+        for effect in effects {
+            data.push(effect.kind as u8);
+            data.push(effect.player as u8);
+            data.extend_from_slice(&(effect.remaining_ms.max(0.0) as u32).to_le_bytes());
+        }
+        return data;
+    }
+
+    fn encode_bodies(&self, data: &mut Vec<u8>) {
+        for handle in self.pucks.iter().chain(&self.balls) {
+            if let Some(body) = self.bodies.get(*handle) {
+                let pos = body.translation();
+                for value in [pos.x, pos.y, body.rotation().angle(), body.angvel()] {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    // A goal counts when a ball starts intersecting a goal sensor during
+    // Play. It can't score again until the sensor reports it has left, so
+    // overlapping for several steps or being reported twice in one step still
+    // counts once. Other balls reaching a goal before the reset don't count.
+    fn check_goals(&mut self, events: Vec<CollisionEvent>) {
+        for event in events {
+            match event {
+                CollisionEvent::Started(a, b, _) => {
+                    if let Some((ball, defender)) = self.goal_contact(a, b) {
+                        if self.scored.insert(ball) && self.phase == SoccerPhase::Play {
+                            self.score_goal(ball, defender);
+                        }
+                    } else if let Some((ball, touch)) = self.touch(a, b) {
+                        if self.hit_hard(touch) {
+                            self.pinned_since.remove(&ball);
+                        }
+                        self.ball_touchers.insert(ball, touch);
+                        if self.last_ball_toucher != Some(touch.player) {
+                            self.set_ball_toucher(Some(touch.player));
+                        }
+                    }
+                }
+                CollisionEvent::Stopped(a, b, _) => {
+                    if let Some((ball, _)) = self.goal_contact(a, b) {
+                        self.scored.remove(&ball);
+                    }
+                }
+            }
+        }
+    }
+
+    // The ball and defending side when one collider is a goal sensor and the
+    // other a ball. Pucks are filtered out by collision groups already.
+    fn goal_contact(
+        &self,
+        a: ColliderHandle,
+        b: ColliderHandle,
+    ) -> Option<(RigidBodyHandle, Side)> {
+        let (defender, other) = match (self.goal_sensors.get(&a), self.goal_sensors.get(&b)) {
+            (Some(side), None) => (*side, b),
+            (None, Some(side)) => (*side, a),
+            _ => return None,
+        };
+        let ball = self
+            .balls
+            .iter()
+            .find(|ball| self.body_colliders.get(ball) == Some(&other))?;
+        return Some((*ball, defender));
+    }
+
+    // The ball and the puck touching it when one collider is a ball and the
+    // other a puck.
+    fn touch(&self, a: ColliderHandle, b: ColliderHandle) -> Option<(RigidBodyHandle, BallTouch)> {
+        let body_of = |collider: ColliderHandle| self.colliders.get(collider)?.parent();
+        let (a, b) = (body_of(a)?, body_of(b)?);
+        let (ball, puck) = match (self.balls.contains(&a), self.balls.contains(&b)) {
+            (true, false) => (a, b),
+            (false, true) => (b, a),
+            _ => return None,
+        };
+        let team = self.teams.iter().find(|team| team.pucks.contains(&puck))?;
+        let touch = BallTouch {
+            player: team.player,
+            puck: team.pucks.iter().position(|handle| *handle == puck)?,
+            tick: self.tick,
+        };
+        return Some((ball, touch));
+    }
+
+    // Whoever touched a ball last, or None if nobody has since the last
+    // reset.
+    pub fn last_toucher(&self, ball: RigidBodyHandle) -> Option<usize> {
+        return self.ball_touchers.get(&ball).map(|touch| touch.player);
+    }
+
+    // The touch a goal by ball is credited to: the last one, unless it is
+    // older than touch_window_ticks.
+    pub fn goal_touch(&self, ball: RigidBodyHandle) -> Option<BallTouch> {
+        let touch = self.ball_touchers.get(&ball)?;
+        let window = self.touch_window_ticks.unwrap_or(u64::MAX);
+        return Some(*touch).filter(|touch| self.tick - touch.tick <= window);
+    }
+
+    fn score_goal(&mut self, ball: RigidBodyHandle, defender: Side) {
+        self.phase = SoccerPhase::GoalScored {
+            resetting_until_tick: self.tick + self.goal_reset_ticks,
+        };
+        // ball in the goal defended by one team scores for the other; in
+        // practice and warm-up nobody scores but the reset still happens
+        if self.practice || self.warm_up {
+            return;
+        }
+        let scorer = match self.teams.iter_mut().find(|team| team.side != defender) {
+            Some(team) => team,
+            None => return,
+        };
+        scorer.score += 1;
+        let scorer = scorer.player;
+        self.goals.push(scorer);
+        // the team gets the goal either way; the credit says whether its
+        // own player put it in or the defenders did it for them
+        let touch = self.goal_touch(ball);
+        let own_goal = touch.map_or(false, |touch| Side::for_slot(touch.player) == defender);
+        if let Some(touch) = touch {
+            self.history.push(HistoryEvent::GoalCredit {
+                player: touch.player,
+                own_goal,
+            });
+            if own_goal {
+                let conceding = self.teams.iter_mut().find(|team| team.side == defender);
+                if let Some(team) = conceding {
+                    team.own_goals += 1;
+                }
+            }
+        }
+        self.events.push(WsMessage::from_payload(
+            MessageType::GoalScored,
+            &GoalScoredMessage {
+                scorer: scorer as u8,
+                toucher: touch.map(|touch| touch.player as u8),
+                puck: touch.map(|touch| touch.puck as u8),
+                own_goal,
+            },
+        ));
+    }
+
+    // Moves GoalScored on to Kickoff, resetting every body, once its
+    // deadline passes. KICKOFF_TIMER moves Kickoff on to Play, or the
+    // deadline does if the timer was never taken to be set.
+    fn advance_phase(&mut self) {
+        match self.phase {
+            SoccerPhase::GoalScored {
+                resetting_until_tick,
+            } if self.tick >= resetting_until_tick => {
+                self.reset_positions();
+                if !self.warm_up {
+                    self.history.push(HistoryEvent::Reset { reason: "kickoff" });
+                }
+                self.phase = SoccerPhase::Kickoff {
+                    until_tick: self.tick + self.kickoff_freeze_ticks,
+                };
+                self.timer_changes.push(TimerChange::After {
+                    ticks: self.kickoff_freeze_ticks,
+                    id: KICKOFF_TIMER,
+                });
+            }
+            SoccerPhase::Kickoff { until_tick } if self.tick >= until_tick => {
+                let pending = self.timer_changes.len();
+                self.timer_changes
+                    .retain(|change| !matches!(change, TimerChange::After { id, .. } if *id == KICKOFF_TIMER));
+                if self.timer_changes.len() < pending {
+                    self.kick_off();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn kick_off(&mut self) {
+        self.phase = SoccerPhase::Play;
+        self.serve();
+    }
+
+    // Sends every ball off at serve_speed toward one side, picked by the
+    // game's rng, within 30 degrees of straight at it.
+    pub fn serve(&mut self) {
+        let speed = match self.serve_speed {
+            Some(speed) => speed,
+            None => return,
+        };
+        for handle in &self.balls {
+            let toward = if self.rng.next_f32() < 0.5 { -1.0 } else { 1.0 };
+            let angle = (self.rng.next_f32() - 0.5) * std::f32::consts::FRAC_PI_3;
+            let velocity = vector![toward * angle.cos(), angle.sin()] * speed;
+            self.bodies[*handle].set_linvel(velocity, true);
+        }
+    }
+
+    fn reset_positions(&mut self) {
+        self.ball_touchers.clear();
+        self.pinned_since.clear();
+        self.set_ball_toucher(None);
+        for handle in self.pucks.iter().chain(&self.balls) {
+            let kickoff = self.kickoff[handle];
+            let body = &mut self.bodies[*handle];
+            body.set_translation(kickoff, true);
+            body.set_linvel(vector![0.0, 0.0], true);
+            body.set_angvel(0.0, true);
+        }
+    }
+
+    // Credits the slot that had the ball with the ticks since it took it.
+    fn set_ball_toucher(&mut self, toucher: Option<usize>) {
+        if let (Some(holder), false) = (self.last_ball_toucher, self.warm_up) {
+            let held = self.tick.saturating_sub(self.possession_since);
+            self.input_stats.entry(holder).or_default().possession_ticks += held;
+        }
+        self.possession_since = self.tick;
+        self.last_ball_toucher = toucher;
+    }
+
+    // SlotStats for every slot in the match so far, in slot order, the
+    // possession in progress included. Cleared when warm-up starts or ends,
+    // so a match counts from its kickoff.
+    pub fn match_stats(&self) -> Vec<SlotStats> {
+        let mut players: Vec<usize> = self.teams.iter().map(|team| team.player).collect();
+        players.sort();
+        players.dedup();
+        return players
+            .into_iter()
+            .map(|player| {
+                let empty = InputTally::default();
+                let tally = self.input_stats.get(&player).unwrap_or(&empty);
+                let mut possession_ticks = tally.possession_ticks;
+                if self.last_ball_toucher == Some(player) && !self.warm_up {
+                    possession_ticks += self.tick.saturating_sub(self.possession_since);
+                }
+                return SlotStats {
+                    player: player as u8,
+                    shots: tally.shots,
+                    avg_shot_speed: if tally.shots > 0 {
+                        tally.speed_total / tally.shots as f32
+                    } else {
+                        0.0
+                    },
+                    pucks_used: tally.pucks.len() as u32,
+                    possession_ticks,
+                };
+            })
+            .collect();
+    }
+
+    pub fn accepts_input(&self) -> bool {
+        return self.phase == SoccerPhase::Play;
+    }
+
+    // Whether player's moves are taken now: always, unless the game is
+    // turn-based and out of warm-up, and then only on its own turn.
+    pub fn has_turn(&self, player: usize) -> bool {
+        if self.turn_ticks.is_none() || self.warm_up {
+            return true;
+        }
+        return self.turn.map_or(false, |(holder, _)| holder == player);
+    }
+
+    // Hands the turn to the next slot once the current one has run out, and
+    // tells that slot alone.
+    fn pass_turn(&mut self) {
+        let ticks = match self.turn_ticks {
+            Some(ticks) if !self.warm_up => ticks,
+            _ => return,
+        };
+        if self.turn.map_or(false, |(_, ends)| self.tick < ends) || self.teams.is_empty() {
+            return;
+        }
+        let next = match self.turn {
+            Some((holder, _)) => self
+                .teams
+                .iter()
+                .position(|team| team.player == holder)
+                .map_or(0, |at| (at + 1) % self.teams.len()),
+            None => 0,
+        };
+        let player = self.teams[next].player;
+        self.turn = Some((player, self.tick + ticks));
+        self.send_turn(player, ticks);
+    }
+
+    fn send_turn(&mut self, player: usize, ticks: u64) {
+        self.messages.push((
+            Recipient::Slot(player),
+            WsMessage::from_payload(
+                MessageType::Turn,
+                &TurnMessage {
+                    player: player as u8,
+                    ticks: ticks as u32,
+                },
+            ),
+        ));
+    }
+
+    // The phase byte of v4 snapshots, with warm-up marked as its own phase.
+    pub fn phase_code(&self) -> u8 {
+        return if self.warm_up {
+            WARM_UP_PHASE_CODE
+        } else {
+            self.phase.code()
+        };
+    }
+
+    pub fn phase_str(&self) -> &'static str {
+        return if self.warm_up {
+            "warm_up"
+        } else {
+            self.phase.as_str()
+        };
+    }
+
+    // Match time played so far, in simulated time.
+    pub fn played(&self) -> Duration {
+        return Duration::from_micros(self.played_us);
+    }
+
+    // How many physics steps elapsed ms of play calls for, with dt set to
+    // match.
+    fn steps_due(&mut self, elapsed: f64) -> u64 {
+        match self.stepping {
+            Stepping::PerUpdate => return 1,
+            Stepping::Variable => {
+                if !(elapsed > 0.0) {
+                    return 0;
+                }
+                let most = MAX_VARIABLE_DT.min(self.max_catch_up.as_secs_f64());
+                self.integration_parameters.dt = (elapsed / 1000.0).min(most) as f32;
+                return 1;
+            }
+            Stepping::Fixed(dt) => {
+                self.integration_parameters.dt = dt;
+                // whole microseconds, so frames that add up to the same time
+                // add up to the same steps
+                let dt_us = (dt as f64 * 1e6).round().max(1.0) as u64;
+                self.step_backlog_us += (elapsed.max(0.0) * 1000.0).round() as u64;
+                let steps = self.step_backlog_us / dt_us;
+                let most = (self.max_catch_up.as_micros() as u64 / dt_us).max(1);
+                if steps > most {
+                    self.step_backlog_us %= dt_us;
+                    return most;
+                }
+                self.step_backlog_us -= steps * dt_us;
+                return steps;
+            }
+        }
+    }
+
+    // One physics step and everything judged from its collisions.
+    fn step_physics(&mut self) {
+        let physics_hooks = ();
+        let started = Instant::now();
+        let before: Vec<(RigidBodyHandle, Vector<f32>)> = self
+            .pucks
+            .iter()
+            .chain(&self.balls)
+            .map(|handle| (*handle, *self.bodies[*handle].translation()))
+            .collect();
+        self.pipeline.step(
+            &self.gravity,
+            &self.integration_parameters,
+            &mut self.island_manager,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd_solver,
+            Some(&mut self.query_pipeline),
+            &physics_hooks,
+            &self.collisions,
+        );
+        if self.record_stats {
+            self.physics_stats = PhysicsStats {
+                steps: self.physics_stats.steps + 1,
+                last_step: started.elapsed(),
+                contact_count: self
+                    .narrow_phase
+                    .contact_pairs()
+                    .filter(|pair| pair.has_any_active_contact)
+                    .count(),
+            };
+        }
+        self.impulse_used.clear();
+        self.stop_tunnelling(&before);
+        let collisions = std::mem::take(&mut *self.collisions.0.lock().unwrap());
+        self.apply_drag();
+        self.decay_bounces(&collisions);
+        self.clamp_speeds();
+        self.settle();
+        self.check_goals(collisions);
+        self.run_watchdog();
+        self.check_stalls();
+    }
+
+    // Puts back any body whose centre went through a wall this step, which
+    // CCD doesn't rule out for one shoved by a crowd of others at speed,
+    // and sends it off the wall the way it came. A centre never reaches a
+    // wall it could collide with otherwise, as the body's edge gets there
+    // first.
+    fn stop_tunnelling(&mut self, before: &[(RigidBodyHandle, Vector<f32>)]) {
+        for (handle, from) in before {
+            let to = *self.bodies[*handle].translation();
+            let path = to - from;
+            let length = path.norm();
+            if !(length > 0.0) {
+                continue;
+            }
+            let groups = match self.body_colliders.get(handle) {
+                Some(collider) => self.colliders[*collider].collision_groups(),
+                None => continue,
+            };
+            let filter = QueryFilter::only_fixed().exclude_sensors().groups(groups);
+            let ray = Ray::new(point![from.x, from.y], path / length);
+            let hit = self.query_pipeline.cast_ray_and_get_normal(
+                &self.bodies,
+                &self.colliders,
+                &ray,
+                length,
+                true,
+                filter,
+            );
+            let normal = match hit {
+                Some((_, hit)) => hit.normal,
+                None => continue,
+            };
+            let body = &mut self.bodies[*handle];
+            let vel = *body.linvel();
+            body.set_translation(*from, true);
+            body.set_linvel(vel - normal * (2.0 * vel.dot(&normal)), true);
+        }
+    }
+
+    // Slows every body by quadratic_drag * speed^2 over one step. Solved
+    // implicitly, v / (1 + k|v|dt), so a large coefficient or a very fast
+    // body can only stop short, never turn around.
+    fn apply_drag(&mut self) {
+        if self.quadratic_drag <= 0.0 {
+            return;
+        }
+        let dt = self.integration_parameters.dt;
+        for handle in self.pucks.iter().chain(&self.balls) {
+            let body = &mut self.bodies[*handle];
+            let vel = *body.linvel();
+            let speed = vel.norm();
+            if speed > 0.0 {
+                body.set_linvel(vel / (1.0 + self.quadratic_drag * speed * dt), true);
+            }
+        }
+    }
+
+    // Takes bounce_decay off the speed of every puck and ball that started
+    // touching something this step. Goal sensors aren't something to bounce
+    // off, and a body that hit two things at once loses speed twice.
+    fn decay_bounces(&mut self, events: &[CollisionEvent]) {
+        let decay = match self.bounce_decay {
+            Some(decay) => decay,
+            None => return,
+        };
+        for event in events {
+            let (a, b) = match event {
+                CollisionEvent::Started(a, b, _) => (*a, *b),
+                CollisionEvent::Stopped(..) => continue,
+            };
+            if self.goal_sensors.contains_key(&a) || self.goal_sensors.contains_key(&b) {
+                continue;
+            }
+            for collider in [a, b] {
+                let body = match self.colliders.get(collider).and_then(|c| c.parent()) {
+                    Some(body) => body,
+                    None => continue,
+                };
+                if self.pucks.contains(&body) || self.balls.contains(&body) {
+                    let body = &mut self.bodies[body];
+                    let vel = *body.linvel();
+                    body.set_linvel(vel * decay, true);
+                }
+            }
+        }
+    }
+
+    // Puts every puck and ball that is only creeping away from its kickoff
+    // spot back on it at rest, for the first settle_ticks updates. Anything
+    // moved, served or sent off faster than SETTLE_SPEED is left alone.
+    fn settle(&mut self) {
+        if self.tick > self.settle_ticks {
+            return;
+        }
+        for handle in self.pucks.iter().chain(&self.balls) {
+            if self.last_move.contains_key(handle) {
+                continue;
+            }
+            let kickoff = self.kickoff[handle];
+            let body = &mut self.bodies[*handle];
+            let creeping = body.linvel().norm() < SETTLE_SPEED
+                && (body.translation() - kickoff).norm() < self.puck_radius;
+            if creeping {
+                body.set_translation(kickoff, false);
+                body.set_linvel(vector![0.0, 0.0], false);
+                body.set_angvel(0.0, false);
+            }
+        }
+    }
+
+    fn clamp_speeds(&mut self) {
+        let max_speed = match self.max_body_speed {
+            Some(max_speed) => max_speed,
+            None => return,
+        };
+        for handle in self.pucks.iter().chain(&self.balls) {
+            let body = &mut self.bodies[*handle];
+            let vel = *body.linvel();
+            let speed = vel.norm();
+            if speed > max_speed {
+                body.set_linvel(vel * (max_speed / speed), true);
+            }
+        }
+    }
+
+    // Respawns any body whose state went non-finite or that escaped the
+    // arena so a single bad step can't poison every later snapshot.
+    fn run_watchdog(&mut self) {
+        let max_x = self.bounds.x + WATCHDOG_MARGIN;
+        let max_y = self.bounds.y + WATCHDOG_MARGIN;
+        for i in 0..self.pucks.len() + self.balls.len() {
+            let handle = if i < self.pucks.len() {
+                self.pucks[i]
+            } else {
+                self.balls[i - self.pucks.len()]
+            };
+            let kickoff = self.kickoff[&handle];
+            let body = &mut self.bodies[handle];
+            let pos = *body.translation();
+            let vel = *body.linvel();
+            let healthy = pos.x.is_finite()
+                && pos.y.is_finite()
+                && vel.x.is_finite()
+                && vel.y.is_finite()
+                && body.angvel().is_finite()
+                && pos.x.abs() <= max_x
+                && pos.y.abs() <= max_y;
+            if healthy {
+                continue;
+            }
+            eprintln!(
+                "Watchdog reset body {} (pos: [{}, {}], vel: [{}, {}])",
+                i, pos.x, pos.y, vel.x, vel.y
+            );
+            body.set_translation(kickoff, true);
+            body.set_linvel(vector![0.0, 0.0], true);
+            body.set_angvel(0.0, true);
+            self.watchdog_resets += 1;
+            self.history
+                .push(HistoryEvent::Reset { reason: "watchdog" });
+            WATCHDOG_RESETS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // See AntiStallConfig. Only play counts, so a ball waiting out a goal
+    // reset or kickoff freeze is left alone.
+    fn check_stalls(&mut self) {
+        let config = match &self.anti_stall {
+            Some(config) if self.phase == SoccerPhase::Play => config.clone(),
+            _ => {
+                self.stalled_since.clear();
+                self.pinned_since.clear();
+                return;
+            }
+        };
+        let after_ms = config.after.as_secs_f64() * 1000.0;
+        let now = self.clock_ms();
+        for handle in self.balls.clone() {
+            let slow_for = if self.bodies[handle].linvel().norm() >= config.speed {
+                self.stalled_since.remove(&handle);
+                0.0
+            } else {
+                now - *self.stalled_since.entry(handle).or_insert(now)
+            };
+            let pinned_for = config
+                .pinned_radius
+                .map_or(0.0, |radius| self.pinned_for(handle, radius));
+            if slow_for < after_ms && pinned_for < after_ms {
+                continue;
+            }
+            // both start over, so a ball that stays stuck is seen to again
+            // after another `after` rather than every tick
+            self.stalled_since.remove(&handle);
+            self.pinned_since.remove(&handle);
+            match config.action {
+                StallAction::Nudge(speed) => {
+                    let angle = self.rng.next_f32() * std::f32::consts::TAU;
+                    let velocity = vector![angle.cos(), angle.sin()] * speed;
+                    self.bodies[handle].set_linvel(velocity, true);
+                }
+                StallAction::Recenter => {
+                    let kickoff = self.kickoff[&handle];
+                    self.place_ball(handle, kickoff);
+                }
+                StallAction::DropBall => {
+                    if !self.drop_ball(handle) {
+                        continue;
+                    }
+                }
+            }
+            self.history.push(HistoryEvent::Reset { reason: "stall" });
+        }
+    }
+
+    // How long ball has stayed within radius of where it settled, starting
+    // over whenever it gets further.
+    fn clock_ms(&self) -> f64 {
+        return self.clock_us as f64 / 1000.0;
+    }
+
+    fn pinned_for(&mut self, ball: RigidBodyHandle, radius: f32) -> f64 {
+        let (position, now) = (*self.bodies[ball].translation(), self.clock_ms());
+        let (anchor, since) = self.pinned_since.entry(ball).or_insert((position, now));
+        if (position - *anchor).norm() > radius {
+            *anchor = position;
+            *since = now;
+        }
+        return now - *since;
+    }
+
+    // A touch from a puck going fast enough to have been a real try at the
+    // ball, rather than one resting against it.
+    fn hit_hard(&self, touch: BallTouch) -> bool {
+        let speed = match &self.anti_stall {
+            Some(config) => config.speed,
+            None => return false,
+        };
+        return self
+            .team_puck(touch.player, touch.puck as u8)
+            .map_or(false, |puck| self.bodies[puck].linvel().norm() >= speed);
+    }
+
+    // Puts ball at rest on the neutral spot nearest it that no puck, ball or
+    // wall overlaps, asking the collider set rather than trusting the centre
+    // to be clear. False, leaving it be, when every spot is taken.
+    pub fn drop_ball(&mut self, ball: RigidBodyHandle) -> bool {
+        let collider = match self.body_colliders.get(&ball) {
+            Some(collider) => *collider,
+            None => return false,
+        };
+        let from = *self.bodies[ball].translation();
+        let mut spots: Vec<Vector<f32>> = NEUTRAL_SPOTS
+            .iter()
+            .map(|fraction| vector![0.0, fraction * self.height])
+            .collect();
+        spots.sort_by(|a, b| (a - from).norm().total_cmp(&(b - from).norm()));
+        let mut query = QueryPipeline::new();
+        query.update(&self.colliders);
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_rigid_body(ball);
+        let shape = self.colliders[collider].shape();
+        let free = spots.into_iter().find(|spot| {
+            let at = Isometry::translation(spot.x, spot.y);
+            return query
+                .intersection_with_shape(&self.bodies, &self.colliders, &at, shape, filter)
+                .is_none();
+        });
+        return match free {
+            Some(spot) => {
+                self.place_ball(ball, spot);
+                true
+            }
+            None => false,
+        };
+    }
+
+    // Moves ball to at, at rest, and tells the players.
+    fn place_ball(&mut self, ball: RigidBodyHandle, at: Vector<f32>) {
+        let body = &mut self.bodies[ball];
+        body.set_translation(at, true);
+        body.set_linvel(vector![0.0, 0.0], true);
+        body.set_angvel(0.0, true);
+        let index = self.balls.iter().position(|handle| *handle == ball);
+        self.events.push(WsMessage::from_payload(
+            MessageType::BallReset,
+            &BallResetMessage {
+                ball: index.unwrap_or(0) as u8,
+                x: at.x,
+                y: at.y,
+            },
+        ));
+    }
+
+    // The multiplier a running effect applies for this player, or 1.0.
+    fn effect_multiplier(&self, player: usize, kind: PowerUpKind) -> f32 {
+        let config = match &self.power_up_config {
+            Some(config) => config,
+            None => return 1.0,
+        };
+        let active = self
+            .active_effects
+            .iter()
+            .any(|effect| effect.player == player && effect.kind == kind);
+        if !active {
+            return 1.0;
+        }
+        match kind {
+            PowerUpKind::BigPuck => config.big_puck_scale,
+            PowerUpKind::SpeedBoost => config.speed_boost,
+        }
+    }
+
+    fn update_power_ups(&mut self, elapsed: f64) {
+        let config = match &self.power_up_config {
+            Some(config) => config.clone(),
+            None => return,
+        };
+        self.collect_power_ups(&config);
+        for effect in &mut self.active_effects {
+            effect.remaining_ms -= elapsed;
+        }
+        let (expired, active) = std::mem::take(&mut self.active_effects)
+            .into_iter()
+            .partition(|effect| effect.remaining_ms <= 0.0);
+        self.active_effects = active;
+        for effect in expired {
+            self.power_up_event(
+                effect.id,
+                effect.kind,
+                PowerUpAction::Expired,
+                None,
+                Some(effect.player),
+            );
+            if effect.kind == PowerUpKind::BigPuck {
+                self.resize_pucks(effect.player, 1.0);
+            }
+        }
+        self.next_power_up -= elapsed;
+        if self.next_power_up <= 0.0 {
+            self.next_power_up = config.interval.as_millis() as f64;
+            if self.power_ups.len() < config.max_on_field {
+                self.spawn_power_up();
+            }
+        }
+    }
+
+    fn spawn_power_up(&mut self) {
+        let kind = if self.rng.next_f32() < 0.5 {
+            PowerUpKind::BigPuck
+        } else {
+            PowerUpKind::SpeedBoost
+        };
+        // keep clear of the walls so a puck can always reach it
+        let margin = self.puck_radius * 2.0;
+        let x = (self.rng.next_f32() - 0.5) * (self.width - margin * 2.0);
+        let y = (self.rng.next_f32() - 0.5) * (self.height - margin * 2.0);
+        let collider = self.colliders.insert(
+            ColliderBuilder::ball(POWER_UP_RADIUS)
+                .sensor(true)
+                .translation(vector![x, y])
+                .build(),
+        );
+        let id = self.next_power_up_id;
+        self.next_power_up_id += 1;
+        self.power_ups.push(PowerUp {
+            id,
+            kind,
+            position: vector![x, y],
+            collider,
+        });
+        self.power_up_event(id, kind, PowerUpAction::Spawned, Some(vector![x, y]), None);
+    }
+
+    // A power-up goes to the team of the first puck overlapping its sensor.
+    // Collecting an effect the team already has restarts its timer.
+    fn collect_power_ups(&mut self, config: &PowerUpConfig) {
+        let mut i = 0;
+        while i < self.power_ups.len() {
+            let sensor = self.power_ups[i].collider;
+            let collector = self.teams.iter().find_map(|team| {
+                let touching = team.pucks.iter().any(|puck| {
+                    self.body_colliders.get(puck).map_or(false, |collider| {
+                        self.narrow_phase.intersection_pair(sensor, *collider) == Some(true)
+                    })
+                });
+                if touching {
+                    Some(team.player)
+                } else {
+                    None
+                }
+            });
+            let player = match collector {
+                Some(player) => player,
+                None => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let power_up = self.power_ups.remove(i);
+            self.colliders.remove(
+                power_up.collider,
+                &mut self.island_manager,
+                &mut self.bodies,
+                true,
+            );
+            self.power_up_event(
+                power_up.id,
+                power_up.kind,
+                PowerUpAction::Collected,
+                Some(power_up.position),
+                Some(player),
+            );
+            let remaining_ms = config.duration.as_millis() as f64;
+            match self
+                .active_effects
+                .iter_mut()
+                .find(|effect| effect.player == player && effect.kind == power_up.kind)
+            {
+                Some(effect) => {
+                    effect.id = power_up.id;
+                    effect.remaining_ms = remaining_ms;
+                }
+                None => self.active_effects.push(ActivePowerUp {
+                    id: power_up.id,
+                    kind: power_up.kind,
+                    player,
+                    remaining_ms,
+                }),
+            }
+            if power_up.kind == PowerUpKind::BigPuck {
+                self.resize_pucks(player, config.big_puck_scale);
+            }
+        }
+    }
+
+    fn resize_pucks(&mut self, player: usize, scale: f32) {
+        let pucks = match self.team(player) {
+            Some(team) => team.pucks.clone(),
+            None => return,
+        };
+        for puck in pucks {
+            if let Some(collider) = self.body_colliders.get(&puck) {
+                self.colliders[*collider].set_shape(SharedShape::ball(self.puck_radius * scale));
+            }
+        }
+    }
+
+    fn power_up_event(
+        &mut self,
+        id: u32,
+        kind: PowerUpKind,
+        action: PowerUpAction,
+        position: Option<Vector<f32>>,
+        player: Option<usize>,
+    ) {
+        let position = position.unwrap_or(vector![0.0, 0.0]);
+        self.events.push(WsMessage::from_payload(
+            MessageType::PowerUp,
+            &PowerUpMessage {
+                id,
+                kind,
+                action,
+                x: position.x,
+                y: position.y,
+                player: player.map(|index| index as u8),
+            },
+        ));
+    }
+}
+
+impl GameLogic for SoccerGame {
+    fn game_type(&self) -> u8 {
+        return self.game_type;
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        return self;
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        return self;
+    }
+    fn update(&mut self, elapsed: f64) {
+        self.clock_us += (elapsed.max(0.0) * 1000.0).round() as u64;
+        self.tick += 1;
+        self.advance_phase();
+        self.pass_turn();
+        if self.accepts_input() {
+            self.drive_bots();
+        }
+        for _ in 0..self.steps_due(elapsed) {
+            self.step_physics();
+            if !self.warm_up {
+                self.played_us += (self.integration_parameters.dt as f64 * 1e6).round() as u64;
+            }
+        }
+        // power-ups wait for the match
+        if !self.warm_up {
+            self.update_power_ups(elapsed);
+        }
+    }
+    fn take_goals(&mut self) -> Vec<usize> {
+        return std::mem::take(&mut self.goals);
+    }
+    fn take_events(&mut self) -> Vec<WsMessage> {
+        return std::mem::take(&mut self.events);
+    }
+    fn take_history(&mut self) -> Vec<HistoryEvent> {
+        return std::mem::take(&mut self.history);
+    }
+    fn take_timer_changes(&mut self) -> Vec<TimerChange> {
+        return std::mem::take(&mut self.timer_changes);
+    }
+    fn take_messages(&mut self) -> Vec<(Recipient, WsMessage)> {
+        return std::mem::take(&mut self.messages);
+    }
+    fn on_timer(&mut self, id: GameTimerId) {
+        if id == KICKOFF_TIMER && matches!(self.phase, SoccerPhase::Kickoff { .. }) {
+            self.kick_off();
+        }
+    }
+    // the first serve waits for the seed so clients can replay it
+    fn reseed(&mut self, seed: u64) {
+        self.rng = GameRng::new(seed);
+        self.serve();
+    }
+    fn add_bot(&mut self, player: usize) {
+        self.bots.push(player);
+    }
+    // a slot back while its turn runs hears about it again, with what's left
+    fn rejoined(&mut self, player: usize) {
+        if let Some((holder, ends)) = self.turn {
+            if holder == player && self.tick < ends && !self.warm_up {
+                self.send_turn(player, ends - self.tick);
+            }
+        }
+    }
+    fn max_players(&self) -> usize {
+        return if self.practice { 1 } else { PLAYERS_PER_GAME };
+    }
+    // each slot is a side of its own
+    fn team_of(&self, player: usize) -> Option<u8> {
+        return self.team(player).map(|team| team.side as u8);
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        return self.serialize(&CompactBinary::default(), &StateView::default());
+    }
+    fn replay_frame(&self) -> Option<Vec<u8>> {
+        return Some(self.serialize(
+            &CompactBinary(ProtocolVersion::V3, ByteOrder::Little),
+            &StateView::default(),
+        ));
+    }
+    // Warm-up starts from kickoff with a served ball and ends back at
+    // kickoff, with the match's first serve.
+    fn warm_up(&mut self, on: bool) -> bool {
+        self.warm_up = on;
+        self.reset_positions();
+        self.input_stats.clear();
+        self.scored.clear();
+        self.phase = SoccerPhase::Play;
+        self.serve();
+        return true;
+    }
+    fn scores(&self) -> Vec<u32> {
+        return self.teams.iter().map(|team| team.score).collect();
+    }
+    fn puck_owners(&self) -> Vec<PuckOwner> {
+        return self
+            .pucks
+            .iter()
+            .filter_map(|handle| {
+                let team = self.teams.iter().find(|team| team.pucks.contains(handle))?;
+                let target = team.pucks.iter().position(|puck| puck == handle)?;
+                return Some(PuckOwner {
+                    player: team.player as u8,
+                    target: target as u8,
+                    color: self.team_colors.get(team.side as usize).copied(),
+                });
+            })
+            .collect();
+    }
+    fn remaining_time(&self) -> Option<Duration> {
+        return self
+            .match_duration
+            .map(|duration| duration.saturating_sub(self.played()));
+    }
+    // played_ms is the match clock, counted in simulated steps; elapsed_ms
+    // is every update's wall time, stalls included.
+    fn describe(&self) -> GameDescription {
+        let teams: Vec<_> = self
+            .teams
+            .iter()
+            .map(|team| {
+                json!({
+                    "player": team.player,
+                    "side": match team.side {
+                        Side::Left => "left",
+                        Side::Right => "right",
+                    },
+                    "score": team.score,
+                    "own_goals": team.own_goals,
+                    "handicap": {
+                        "shot_speed": team.handicap.shot_speed,
+                        "move_cooldown": team.handicap.move_cooldown,
+                        "score": team.handicap.score,
+                    },
+                })
+            })
+            .collect();
+        let balls: Vec<_> = self
+            .balls
+            .iter()
+            .filter_map(|ball| self.bodies.get(*ball))
+            .map(|body| json!({ "x": body.translation().x, "y": body.translation().y }))
+            .collect();
+        let until_tick = match self.phase {
+            SoccerPhase::Play => None,
+            SoccerPhase::GoalScored {
+                resetting_until_tick,
+            } => Some(resetting_until_tick),
+            SoccerPhase::Kickoff { until_tick } => Some(until_tick),
+        };
+        return GameDescription {
+            game_type: self.game_type,
+            details: json!({
+                "phase": self.phase_str(),
+                "teams": teams,
+                "balls": balls,
+                "last_ball_toucher": self.last_ball_toucher,
+                "elapsed_ms": self.clock_ms() as u64,
+                "played_ms": self.played().as_millis() as u64,
+                "remaining_ms": self.remaining_time().map(|remaining| remaining.as_millis() as u64),
+                "phase_ticks_left": until_tick.map(|until| until.saturating_sub(self.tick)),
+            }),
+            ..GameDescription::default()
+        };
+    }
+}
+
+impl SoccerGame {
+    pub fn serialize(&self, serializer: &dyn StateSerializer, view: &StateView) -> Vec<u8> {
+        return serializer.encode(self, view);
+    }
+
+    // v1 snapshot: x and y of every puck, then every ball.
+    pub fn to_bytes_v1(&self) -> Vec<u8> {
+        return self.state_payload().encode();
+    }
+
+    pub fn state_payload(&self) -> StatePayload {
+        let positions = self
+            .pucks
+            .iter()
+            .chain(&self.balls)
+            .filter_map(|handle| self.bodies.get(*handle))
+            .map(|body| (body.translation().x, body.translation().y))
+            .collect();
+        return StatePayload { positions };
     }
 }
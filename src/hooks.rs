@@ -0,0 +1,55 @@
+use crate::message::{GameOverMessage, SoccerMoveMessage};
+use crate::middleware::ConnCtx;
+use futures::future::BoxFuture;
+
+// Callbacks an embedding application gets at the points of a connection's
+// life it is likely to want to tell another system about: analytics,
+// presence, an outside matchmaker. Every method defaults to doing nothing,
+// so hooks only implement what they need.
+//
+// Each is awaited where it happens, so a slow hook holds that up: on_move
+// delays the move, and on_game_over a time-up runs on the tick loop. Long
+// work belongs on a task of the hook's own.
+pub trait ServerHooks: Send + Sync {
+    // past the handshake, authentication and any Hello, before the
+    // connection is placed anywhere
+    fn on_connect<'a>(&'a self, _ctx: &'a ConnCtx) -> BoxFuture<'a, ()> {
+        return Box::pin(async {});
+    }
+    // placed into ctx's game in slot player, right before its Welcome;
+    // again for every game it goes on to
+    fn on_join_game<'a>(&'a self, _ctx: &'a ConnCtx, _player: usize) -> BoxFuture<'a, ()> {
+        return Box::pin(async {});
+    }
+    // a SoccerMove from slot player, before the game has seen it, so one
+    // the game then refuses or drops still comes through here
+    fn on_move<'a>(
+        &'a self,
+        _ctx: &'a ConnCtx,
+        _player: usize,
+        _soccer_move: &'a SoccerMoveMessage,
+    ) -> BoxFuture<'a, ()> {
+        return Box::pin(async {});
+    }
+    // out of ctx's game: left says it asked to with LeaveGame, rather than
+    // dropping, which keeps a started match's slot for a reconnect. Not
+    // called for a game that closed under the connection
+    fn on_leave<'a>(&'a self, _ctx: &'a ConnCtx, _left: bool) -> BoxFuture<'a, ()> {
+        return Box::pin(async {});
+    }
+    // a match decided by a forfeit or its clock, with what its players
+    // were sent
+    fn on_game_over<'a>(
+        &'a self,
+        _game_id: usize,
+        _game_over: &'a GameOverMessage,
+    ) -> BoxFuture<'a, ()> {
+        return Box::pin(async {});
+    }
+}
+
+// What the server runs until Server::set_hooks is given something else.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NoHooks;
+
+impl ServerHooks for NoHooks {}
@@ -0,0 +1,779 @@
+use crate::audit::AUDIT_DIVERGENCES;
+use crate::degrade;
+use crate::disconnects::DisconnectRecord;
+use crate::game::{
+    tokens_match, Game, HistoryEvent, PlayerSlot, Side, SoccerGame, WATCHDOG_RESETS,
+};
+use crate::message::{DegradeLevel, VersionMessage};
+use crate::profiling::{Percentiles, PhaseSummary};
+use crate::server::{parse_query_params, ServerState, PROTOCOL_STRIKES, UNSOLICITED_PONGS};
+use crate::traffic::{Direction, TrafficSummary, TRAFFIC};
+use rapier2d::prelude::RigidBodyHandle;
+use serde_json::json;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+
+// Plain HTTP debug surface, served on its own port so curl and dashboards
+// can inspect games without a websocket client.
+//
+//   GET /games      {"version": ..., "games": [...]}: the build and
+//                   protocol range as /version has them, and every game's
+//                   GameDescription with its id, token and creation time
+//   GET /game/{id}  JSON snapshot of one game, 404 if it doesn't exist
+//   GET /game/{id}/history
+//                   the game's recent joins, leaves, goals, resets and
+//                   forfeits, oldest first
+//   GET /game/{id}/roster
+//                   every slot up to the game's player count, with who
+//                   holds it and whether they are connected, reconnecting,
+//                   a bot or the slot is empty; admin only, with the admin
+//                   token sent as "Authorization: Bearer <token>"
+//   GET /ticks      tick timing percentiles over the profiler window, in
+//                   total and by phase
+//   GET /leaderboard?limit=N
+//                   top N players by wins (default 10, at most 100)
+//   GET /owners     live games counted against each player's
+//                   max_games_per_identity, most first
+//   GET /disconnects?name=N&ip=A
+//                   recently ended connections, how long they lasted and
+//                   why they ended, newest first, optionally only those of
+//                   one name or address; admin only, like the roster
+//   GET /version    the build and protocol range, as a Version request
+//                   returns them
+pub async fn serve(addr: SocketAddr, state: Arc<ServerState>) {
+    listen(addr, state, Surface::Debug).await;
+}
+
+// Probes for container orchestrators, on a port of their own so they can be
+// exposed without the debug surface. Each request runs on its own task, so
+// a wedged tick loop can't take these down with it.
+//
+//   GET /ready    200 once the listener is bound and a tick has completed
+//   GET /live     503 when no tick has completed for live_tick_periods
+//   GET /metrics  Prometheus text format
+//   GET /version  the same JSON as on the debug surface
+pub async fn serve_health(addr: SocketAddr, state: Arc<ServerState>) {
+    listen(addr, state, Surface::Health).await;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Surface {
+    Debug,
+    Health,
+}
+
+const JSON: &str = "application/json";
+const TEXT: &str = "text/plain; version=0.0.4";
+
+async fn listen(addr: SocketAddr, state: Arc<ServerState>, surface: Surface) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!(
+                "Failed to bind {:?} HTTP listener on {}: {}",
+                surface, addr, e
+            );
+            return;
+        }
+    };
+    println!("{:?} HTTP listening on {}", surface, addr);
+    while let Ok((stream, _)) = listener.accept().await {
+        let state = state.clone();
+        tokio::spawn(async move {
+            handle_request(stream, &state, surface).await;
+        });
+    }
+}
+
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+async fn handle_request(mut stream: TcpStream, state: &ServerState, surface: Surface) {
+    let mut buffer = vec![0u8; MAX_REQUEST_SIZE];
+    let mut len = 0;
+    // only the request line and headers matter; bodies are ignored
+    while !buffer[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buffer[len..]).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => len += n,
+        }
+        if len == buffer.len() {
+            let _ = respond(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                JSON,
+                "{}",
+            )
+            .await;
+            return;
+        }
+    }
+    let request = String::from_utf8_lossy(&buffer[..len]);
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (status, content_type, body) = match surface {
+        Surface::Debug => {
+            let admin = is_admin(&request, state);
+            let (status, body) = route(method, path, admin, state).await;
+            (status, JSON, body)
+        }
+        Surface::Health => route_health(method, path, state),
+    };
+    let _ = respond(&mut stream, status, content_type, &body).await;
+}
+
+// Whether the request carries the configured admin token as a bearer
+// credential. Never true while no admin token is set.
+fn is_admin(request: &str, state: &ServerState) -> bool {
+    let config = state.config();
+    let expected = match &config.admin_token {
+        Some(expected) => expected,
+        None => return false,
+    };
+    return request
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .filter_map(|(_, value)| value.trim().strip_prefix("Bearer "))
+        .any(|token| tokens_match(token.trim(), expected));
+}
+
+async fn route(
+    method: &str,
+    path: &str,
+    admin: bool,
+    state: &ServerState,
+) -> (&'static str, String) {
+    if method != "GET" {
+        return ("405 Method Not Allowed", error_json("method not allowed"));
+    }
+    let segments: Vec<&str> = path
+        .split('?')
+        .next()
+        .unwrap_or("")
+        .trim_matches('/')
+        .split('/')
+        .collect();
+    match segments.as_slice() {
+        ["game", id] => match find_game(state, id).await {
+            Some((id, game)) => ("200 OK", game_json(id, &*game.read().await)),
+            None => ("404 Not Found", error_json("game not found")),
+        },
+        ["game", id, "history"] => match find_game(state, id).await {
+            Some((_, game)) => ("200 OK", history_json(&*game.read().await)),
+            None => ("404 Not Found", error_json("game not found")),
+        },
+        ["game", _, "roster"] if !admin => ("401 Unauthorized", error_json("admin only")),
+        ["game", id, "roster"] => match find_game(state, id).await {
+            Some((_, game)) => ("200 OK", roster_json(&game.read().await.roster())),
+            None => ("404 Not Found", error_json("game not found")),
+        },
+        ["games"] => ("200 OK", games_json(state).await),
+        ["ticks"] => ("200 OK", ticks_json(state)),
+        ["owners"] => ("200 OK", owners_json(state)),
+        ["version"] => ("200 OK", version_json()),
+        ["disconnects"] if !admin => ("401 Unauthorized", error_json("admin only")),
+        ["disconnects"] => {
+            let params = path
+                .split_once('?')
+                .map(|(_, query)| parse_query_params(query))
+                .unwrap_or_default();
+            let ip = match params.get("ip").map(|ip| ip.parse::<IpAddr>()) {
+                Some(Ok(ip)) => Some(ip),
+                Some(Err(_)) => return ("400 Bad Request", error_json("invalid ip")),
+                None => None,
+            };
+            let records = state
+                .disconnects
+                .find(params.get("name").map(String::as_str), ip);
+            ("200 OK", disconnects_json(&records))
+        }
+        ["leaderboard"] => {
+            let limit = path
+                .split_once('?')
+                .map(|(_, query)| parse_query_params(query))
+                .and_then(|params| params.get("limit").and_then(|l| l.parse::<usize>().ok()))
+                .unwrap_or(LEADERBOARD_LIMIT)
+                .min(MAX_LEADERBOARD_LIMIT);
+            ("200 OK", leaderboard_json(state, limit).await)
+        }
+        _ => ("404 Not Found", error_json("not found")),
+    }
+}
+
+// Never takes the games lock, so a deadlock there shows up as /live failing
+// rather than as a hung probe.
+fn route_health(
+    method: &str,
+    path: &str,
+    state: &ServerState,
+) -> (&'static str, &'static str, String) {
+    if method != "GET" {
+        return (
+            "405 Method Not Allowed",
+            TEXT,
+            "method not allowed\n".into(),
+        );
+    }
+    let probe = |ok: bool| match ok {
+        true => ("200 OK", TEXT, "ok\n".to_string()),
+        false => ("503 Service Unavailable", TEXT, "unavailable\n".to_string()),
+    };
+    match path.split('?').next().unwrap_or("") {
+        "/ready" => probe(state.is_ready()),
+        "/live" => probe(state.is_live()),
+        "/metrics" => ("200 OK", TEXT, metrics_text(state)),
+        "/version" => ("200 OK", JSON, version_json()),
+        _ => ("404 Not Found", TEXT, "not found\n".into()),
+    }
+}
+
+fn metrics_text(state: &ServerState) -> String {
+    let summary = state.ticks.lock().unwrap().summary();
+    let games = state
+        .games
+        .try_read()
+        .map_or(f32::NAN, |games| games.len() as f32);
+    let metrics = [
+        ("asyncws_ready", "gauge", state.is_ready() as u8 as f32),
+        ("asyncws_live", "gauge", state.is_live() as u8 as f32),
+        (
+            "asyncws_ticks_total",
+            "counter",
+            state.ticks_completed.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_tick_restarts_total",
+            "counter",
+            state.tick_restarts.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_tick_p50_seconds",
+            "gauge",
+            summary.p50.as_secs_f32(),
+        ),
+        (
+            "asyncws_tick_p95_seconds",
+            "gauge",
+            summary.p95.as_secs_f32(),
+        ),
+        (
+            "asyncws_tick_max_seconds",
+            "gauge",
+            summary.max.as_secs_f32(),
+        ),
+        ("asyncws_slow_ticks", "gauge", summary.slow_ticks as f32),
+        // NaN while the games lock is held elsewhere
+        ("asyncws_games", "gauge", games),
+        // as of the last tick; dormant games have nobody connected
+        (
+            "asyncws_games_active",
+            "gauge",
+            state.active_games.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_games_dormant",
+            "gauge",
+            state.dormant_games.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_watchdog_resets_total",
+            "counter",
+            WATCHDOG_RESETS.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_unsolicited_pongs_total",
+            "counter",
+            UNSOLICITED_PONGS.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_protocol_strikes_total",
+            "counter",
+            PROTOCOL_STRIKES.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_audit_divergences_total",
+            "counter",
+            AUDIT_DIVERGENCES.load(Ordering::Relaxed) as f32,
+        ),
+        (
+            "asyncws_banned_ips",
+            "gauge",
+            state.ip_limiter.banned() as f32,
+        ),
+    ];
+    let mut out = String::new();
+    for (name, kind, value) in metrics {
+        let _ = writeln!(out, "# TYPE {} {}\n{} {}", name, kind, name, value);
+    }
+    let version = VersionMessage::current();
+    let _ = writeln!(
+        out,
+        "# TYPE asyncws_build_info gauge\nasyncws_build_info{{version=\"{}\",git_hash=\"{}\",min_protocol=\"{}\",max_protocol=\"{}\"}} 1",
+        version.version, version.git_hash, version.min_protocol, version.max_protocol
+    );
+    // connections at each level of snapshot degradation right now, and the
+    // steps between levels since start
+    // tick time by phase, summed over every game
+    let _ = writeln!(out, "# TYPE asyncws_tick_phase_seconds gauge");
+    for (phase, times) in phases(&summary.phases) {
+        for (quantile, time) in [("0.5", times.p50), ("0.95", times.p95), ("1", times.max)] {
+            let _ = writeln!(
+                out,
+                "asyncws_tick_phase_seconds{{phase=\"{}\",quantile=\"{}\"}} {}",
+                phase,
+                quantile,
+                time.as_secs_f32()
+            );
+        }
+    }
+    let _ = writeln!(out, "# TYPE asyncws_degrade_connections gauge");
+    for level in DegradeLevel::ALL {
+        let _ = writeln!(
+            out,
+            "asyncws_degrade_connections{{level=\"{}\"}} {}",
+            level.as_str(),
+            degrade::connections_at(level)
+        );
+    }
+    let _ = writeln!(out, "# TYPE asyncws_degrade_steps_total counter");
+    for (direction, steps) in [("down", &degrade::STEPS_DOWN), ("up", &degrade::STEPS_UP)] {
+        let _ = writeln!(
+            out,
+            "asyncws_degrade_steps_total{{direction=\"{}\"}} {}",
+            direction,
+            steps.load(Ordering::Relaxed)
+        );
+    }
+    // labelled by message type and direction, for the types seen so far
+    for (name, pick) in [
+        ("asyncws_messages_total", 0),
+        ("asyncws_message_bytes_total", 1),
+    ] {
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for direction in [Direction::In, Direction::Out] {
+            for (msg_type, messages, bytes) in TRAFFIC.by_type(direction) {
+                let _ = writeln!(
+                    out,
+                    "{}{{type=\"{:?}\",direction=\"{}\"}} {}",
+                    name,
+                    msg_type,
+                    direction.label(),
+                    [messages, bytes][pick]
+                );
+            }
+        }
+    }
+    return out;
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    return stream.shutdown().await;
+}
+
+async fn find_game(state: &ServerState, id: &str) -> Option<(usize, Arc<RwLock<Game>>)> {
+    let id = id.parse::<usize>().ok()?;
+    let game = state.games.read().await.get(&id).cloned()?;
+    return Some((id, game));
+}
+
+fn version_json() -> String {
+    return serde_json::to_string(&VersionMessage::current()).unwrap_or_default();
+}
+
+fn error_json(message: &str) -> String {
+    return format!("{{\"error\":{}}}", json_string(message));
+}
+
+const LEADERBOARD_LIMIT: usize = 10;
+const MAX_LEADERBOARD_LIMIT: usize = 100;
+
+async fn leaderboard_json(state: &ServerState, limit: usize) -> String {
+    let entries: Vec<String> = state
+        .stats
+        .read()
+        .await
+        .leaderboard(limit)
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"name\":{},\"wins\":{},\"losses\":{},\"goals_scored\":{},\"goals_conceded\":{},\"own_goals\":{}}}",
+                json_string(&entry.name),
+                entry.record.wins,
+                entry.record.losses,
+                entry.record.goals_scored,
+                entry.record.goals_conceded,
+                entry.record.own_goals
+            )
+        })
+        .collect();
+    return format!("[{}]", entries.join(","));
+}
+
+fn owners_json(state: &ServerState) -> String {
+    let entries: Vec<String> = state
+        .game_owners
+        .list()
+        .iter()
+        .map(|(owner, games)| {
+            let games: Vec<String> = games.iter().map(|id| id.to_string()).collect();
+            format!(
+                "{{\"owner\":{},\"games\":[{}]}}",
+                json_string(&owner.to_string()),
+                games.join(",")
+            )
+        })
+        .collect();
+    return format!("[{}]", entries.join(","));
+}
+
+fn disconnects_json(records: &[DisconnectRecord]) -> String {
+    let entries: Vec<serde_json::Value> = records
+        .iter()
+        .map(|record| {
+            let at_ms = record
+                .at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            let duration_ms = record
+                .at
+                .duration_since(record.connected_at)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            // None when the client hung up or sent its own close
+            let close = record.reason.map(|reason| {
+                json!({
+                    "code": reason.code(),
+                    "reason": reason.reason(),
+                })
+            });
+            json!({
+                "at_ms": at_ms,
+                "client_id": record.client_id,
+                "name": record.name,
+                "identity": record.identity,
+                "peer": record.peer.to_string(),
+                "game_id": record.game_id,
+                "duration_ms": duration_ms,
+                "close": close,
+                "traffic": traffic_value(&record.traffic),
+            })
+        })
+        .collect();
+    return serde_json::to_string(&entries).unwrap_or_default();
+}
+
+fn history_json(game: &Game) -> String {
+    let entries: Vec<String> = game
+        .history()
+        .map(|entry| {
+            let event = match &entry.event {
+                HistoryEvent::Joined { player, name } => format!(
+                    "\"event\":\"joined\",\"player\":{},\"name\":{}",
+                    player,
+                    json_string(name)
+                ),
+                HistoryEvent::Rejoined { player, name } => format!(
+                    "\"event\":\"rejoined\",\"player\":{},\"name\":{}",
+                    player,
+                    json_string(name)
+                ),
+                HistoryEvent::Disconnected { player } => {
+                    format!("\"event\":\"disconnected\",\"player\":{}", player)
+                }
+                HistoryEvent::Left { player } => {
+                    format!("\"event\":\"left\",\"player\":{}", player)
+                }
+                HistoryEvent::Forfeit { winner } => {
+                    format!("\"event\":\"forfeit\",\"winner\":{}", winner)
+                }
+                HistoryEvent::TimeUp { winner } => match winner {
+                    Some(winner) => format!("\"event\":\"time_up\",\"winner\":{}", winner),
+                    None => "\"event\":\"time_up\",\"winner\":null".to_string(),
+                },
+                HistoryEvent::Goal { player } => {
+                    format!("\"event\":\"goal\",\"player\":{}", player)
+                }
+                HistoryEvent::GoalCredit { player, own_goal } => format!(
+                    "\"event\":\"goal_credit\",\"player\":{},\"own_goal\":{}",
+                    player, own_goal
+                ),
+                HistoryEvent::Reset { reason } => {
+                    format!("\"event\":\"reset\",\"reason\":{}", json_string(reason))
+                }
+            };
+            format!(
+                "{{\"at_ms\":{},\"tick\":{},{}}}",
+                entry.at_ms, entry.tick, event
+            )
+        })
+        .collect();
+    return format!("[{}]", entries.join(","));
+}
+
+fn roster_json(slots: &[PlayerSlot]) -> String {
+    let entries: Vec<String> = slots
+        .iter()
+        .map(|slot| {
+            format!(
+                "{{\"index\":{},\"name\":{},\"id\":{},\"status\":\"{}\"}}",
+                slot.index,
+                slot.name.as_deref().map_or("null".to_string(), json_string),
+                slot.id
+                    .as_ref()
+                    .map_or("null".to_string(), |id| json_string(&id.to_string())),
+                slot.status.as_str()
+            )
+        })
+        .collect();
+    return format!("[{}]", entries.join(","));
+}
+
+fn ticks_json(state: &ServerState) -> String {
+    let summary = state.ticks.lock().unwrap().summary();
+    let ms = |duration: Duration| json_number(duration.as_secs_f32() * 1000.0);
+    let worst_game = match summary.worst_game {
+        Some((game_id, time)) => format!("{{\"id\":{},\"ms\":{}}}", game_id, ms(time)),
+        None => "null".to_string(),
+    };
+    let phases: Vec<String> = phases(&summary.phases)
+        .iter()
+        .map(|(phase, times)| {
+            format!(
+                "\"{}\":{{\"p50_ms\":{},\"p95_ms\":{},\"max_ms\":{}}}",
+                phase,
+                ms(times.p50),
+                ms(times.p95),
+                ms(times.max)
+            )
+        })
+        .collect();
+    return format!(
+        "{{\"samples\":{},\"p50_ms\":{},\"p95_ms\":{},\"max_ms\":{},\"phases\":{{{}}},\"budget_ms\":{},\"slow_ticks\":{},\"worst_game\":{}}}",
+        summary.samples,
+        ms(summary.p50),
+        ms(summary.p95),
+        ms(summary.max),
+        phases.join(","),
+        ms(summary.budget),
+        summary.slow_ticks,
+        worst_game
+    );
+}
+
+fn phases(summary: &PhaseSummary) -> [(&'static str, Percentiles); 4] {
+    return [
+        ("input", summary.input),
+        ("step", summary.step),
+        ("encode", summary.encode),
+        ("broadcast", summary.broadcast),
+    ];
+}
+
+fn traffic_json(summary: &TrafficSummary) -> String {
+    return traffic_value(summary).to_string();
+}
+
+fn traffic_value(summary: &TrafficSummary) -> serde_json::Value {
+    return json!({
+        "messages_in": summary.messages_in,
+        "bytes_in": summary.bytes_in,
+        "messages_out": summary.messages_out,
+        "bytes_out": summary.bytes_out,
+        "seconds": summary.duration.as_secs(),
+    });
+}
+
+// Every game as its GameDescription, by id.
+async fn games_json(state: &ServerState) -> String {
+    let games: Vec<(usize, Arc<RwLock<Game>>)> = state
+        .games
+        .read()
+        .await
+        .iter()
+        .map(|(id, game)| (*id, game.clone()))
+        .collect();
+    let mut listing = Vec::with_capacity(games.len());
+    for (id, game) in games {
+        let game = game.read().await;
+        let mut entry = serde_json::to_value(game.describe()).unwrap_or_default();
+        entry["id"] = id.into();
+        entry["game_token"] = game.token.clone().into();
+        entry["created_ms"] = game.created_ms().into();
+        listing.push(entry);
+    }
+    let version = serde_json::to_value(VersionMessage::current()).unwrap_or_default();
+    return serde_json::json!({ "version": version, "games": listing }).to_string();
+}
+
+fn game_json(id: usize, game: &Game) -> String {
+    let phase = game.phase.as_str();
+    let players: Vec<String> = game
+        .players
+        .iter()
+        .map(|player| {
+            format!(
+                "{{\"name\":{},\"index\":{},\"connected\":{},\"ready\":{},\"bot\":{},\"rtt_ms\":{},\"traffic\":{}}}",
+                json_string(&player.name),
+                player.index,
+                player.connected,
+                player.ready,
+                player.bot,
+                player
+                    .rtt_ms
+                    .map_or("null".to_string(), |rtt| rtt.to_string()),
+                player
+                    .traffic
+                    .as_ref()
+                    .map_or("null".to_string(), |traffic| traffic_json(&traffic.summary()))
+            )
+        })
+        .collect();
+    let state = match game.downcast::<SoccerGame>() {
+        Some(soccer_game) => soccer_json(soccer_game),
+        None => "null".to_string(),
+    };
+    return format!(
+        "{{\"id\":{},\"game_token\":{},\"created_ms\":{},\"game_type\":{},\"phase\":\"{}\",\"tick_mode\":\"{}\",\"players\":[{}],\"state\":{}}}",
+        id,
+        json_string(&game.token),
+        game.created_ms(),
+        game.game_type,
+        phase,
+        game.tick_mode_str(),
+        players.join(","),
+        state
+    );
+}
+
+pub(crate) fn soccer_json(game: &SoccerGame) -> String {
+    let teams: Vec<String> = game
+        .teams
+        .iter()
+        .map(|team| {
+            let side = match team.side {
+                Side::Left => "left",
+                Side::Right => "right",
+            };
+            format!(
+                "{{\"player\":{},\"side\":\"{}\",\"score\":{},\"own_goals\":{}}}",
+                team.player, side, team.score, team.own_goals
+            )
+        })
+        .collect();
+    let pucks: Vec<String> = game
+        .pucks
+        .iter()
+        .map(|puck| body_json(game, *puck))
+        .collect();
+    let balls: Vec<String> = game
+        .balls
+        .iter()
+        .map(|ball| body_json(game, *ball))
+        .collect();
+    let power_ups: Vec<String> = game
+        .power_ups
+        .iter()
+        .map(|power_up| {
+            format!(
+                "{{\"id\":{},\"kind\":\"{:?}\",\"x\":{},\"y\":{}}}",
+                power_up.id,
+                power_up.kind,
+                json_number(power_up.position.x),
+                json_number(power_up.position.y)
+            )
+        })
+        .collect();
+    let effects: Vec<String> = game
+        .active_effects
+        .iter()
+        .map(|effect| {
+            format!(
+                "{{\"id\":{},\"kind\":\"{:?}\",\"player\":{},\"remaining_ms\":{}}}",
+                effect.id,
+                effect.kind,
+                effect.player,
+                json_number(effect.remaining_ms as f32)
+            )
+        })
+        .collect();
+    return format!(
+        "{{\"phase\":\"{}\",\"teams\":[{}],\"pucks\":[{}],\"balls\":[{}],\"last_ball_toucher\":{},\"power_ups\":[{}],\"effects\":[{}]}}",
+        game.phase_str(),
+        teams.join(","),
+        pucks.join(","),
+        balls.join(","),
+        game.last_ball_toucher
+            .map_or("null".to_string(), |player| player.to_string()),
+        power_ups.join(","),
+        effects.join(",")
+    );
+}
+
+fn body_json(game: &SoccerGame, handle: RigidBodyHandle) -> String {
+    let body = match game.bodies.get(handle) {
+        Some(body) => body,
+        None => return "null".to_string(),
+    };
+    let pos = body.translation();
+    let vel = body.linvel();
+    return format!(
+        "{{\"x\":{},\"y\":{},\"vx\":{},\"vy\":{},\"angle\":{},\"angvel\":{}}}",
+        json_number(pos.x),
+        json_number(pos.y),
+        json_number(vel.x),
+        json_number(vel.y),
+        json_number(body.rotation().angle()),
+        json_number(body.angvel())
+    );
+}
+
+// JSON has no NaN or infinity
+pub(crate) fn json_number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    return out;
+}
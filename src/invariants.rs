@@ -0,0 +1,261 @@
+use crate::game::{Game, Games};
+use crate::server::ServerState;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+
+// A game is closed under its own lock and taken out of the map right after,
+// so one seen closed in the map is only wrong once it has stayed there this
+// long.
+pub const CLOSED_LINGER: Duration = Duration::from_secs(5);
+// The tick loop updates every open game each frame, and a panicked one is
+// back within a second, so a game left alone this long, and for
+// STALL_PERIODS of the loop's own period, has no tick loop.
+pub const TICK_STALL: Duration = Duration::from_secs(5);
+pub const STALL_PERIODS: u32 = 3;
+
+// How long a game may go without an update when the loop runs once every
+// loop_period, which a debug tick rate can stretch well past TICK_STALL.
+pub fn tick_stall(loop_period: Duration) -> Duration {
+    return TICK_STALL.max(loop_period * STALL_PERIODS);
+}
+
+// What the server does about a check that finds the games corrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantChecks {
+    Off,
+    // logs every violation with a dump of the games involved
+    Log,
+    // logs the same, then panics
+    Panic,
+}
+
+impl Default for InvariantChecks {
+    // debug builds fail on the spot; release ones don't pay for the walk
+    fn default() -> Self {
+        return if cfg!(debug_assertions) {
+            InvariantChecks::Panic
+        } else {
+            InvariantChecks::Off
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    // more players than the game type takes
+    OverCapacity {
+        players: usize,
+        max: usize,
+    },
+    // a player in a slot the game type doesn't have
+    SlotOutOfRange {
+        index: usize,
+        max: usize,
+    },
+    // two players in the same slot
+    SlotTaken {
+        index: usize,
+    },
+    // one connection bound to a second slot, in this game or another
+    ConnectionTwice {
+        client_id: usize,
+        index: usize,
+        also: (usize, usize),
+    },
+    // closed, so nothing steps it or lets anyone in, yet still in the map
+    ClosedInMap {
+        closed_for: Duration,
+    },
+    // open, but not updated for TICK_STALL: its tick loop is dead or stuck
+    NotTicking {
+        stalled_for: Duration,
+    },
+    // an id create_game never handed out
+    UnknownId {
+        last_game_id: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Problem::OverCapacity { players, max } => {
+                write!(f, "{} players in a game for {}", players, max)
+            }
+            Problem::SlotOutOfRange { index, max } => {
+                write!(f, "player in slot {} of a game for {}", index, max)
+            }
+            Problem::SlotTaken { index } => write!(f, "slot {} held twice", index),
+            Problem::ConnectionTwice {
+                client_id,
+                index,
+                also: (game_id, other),
+            } => write!(
+                f,
+                "client {} bound to slot {} and to slot {} of game {}",
+                client_id, index, other, game_id
+            ),
+            Problem::ClosedInMap { closed_for } => {
+                write!(f, "closed {:?} ago but still in the map", closed_for)
+            }
+            Problem::NotTicking { stalled_for } => {
+                write!(f, "open but not updated for {:?}", stalled_for)
+            }
+            Problem::UnknownId { last_game_id } => {
+                write!(f, "id past the last one handed out ({})", last_game_id)
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub game_id: usize,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "game {}: {}", self.game_id, self.problem);
+    }
+}
+
+// Walks every game in the map and its roster: each game within its player
+// cap, every player in a slot of its own that the game has, no connection
+// bound twice across all games, nothing closed lingering, every open game
+// still being ticked by a loop that runs every loop_period, and no id past
+// last_game_id. Takes each game's lock in turn, so the caller must hold none
+// of them.
+pub async fn check(
+    games: &Games,
+    last_game_id: &AtomicUsize,
+    loop_period: Duration,
+) -> Vec<Violation> {
+    let stall = tick_stall(loop_period);
+    let games: Vec<(usize, Arc<RwLock<Game>>)> = games
+        .read()
+        .await
+        .iter()
+        .map(|(id, game)| (*id, Arc::clone(game)))
+        .collect();
+    // read after the map, so a game created meanwhile can't look unknown
+    let last_game_id = last_game_id.load(Ordering::Relaxed);
+    let mut violations = vec![];
+    let mut bound: HashMap<usize, (usize, usize)> = HashMap::new();
+    for (game_id, game) in games {
+        let game = game.read().await;
+        let mut found = |problem| violations.push(Violation { game_id, problem });
+        if game_id > last_game_id {
+            found(Problem::UnknownId { last_game_id });
+        }
+        let max = game.logic.max_players();
+        if game.players.len() > max {
+            found(Problem::OverCapacity {
+                players: game.players.len(),
+                max,
+            });
+        }
+        let mut slots = vec![];
+        for player in &game.players {
+            if player.index >= max {
+                found(Problem::SlotOutOfRange {
+                    index: player.index,
+                    max,
+                });
+            }
+            if slots.contains(&player.index) {
+                found(Problem::SlotTaken {
+                    index: player.index,
+                });
+            }
+            slots.push(player.index);
+            if let Some(connection) = &player.connection {
+                let here = (game_id, player.index);
+                if let Some(also) = bound.insert(connection.client_id, here) {
+                    found(Problem::ConnectionTwice {
+                        client_id: connection.client_id,
+                        index: player.index,
+                        also,
+                    });
+                }
+            }
+        }
+        match game.closed_for() {
+            Some(closed_for) if closed_for >= CLOSED_LINGER => {
+                found(Problem::ClosedInMap { closed_for });
+            }
+            Some(_) => (),
+            None if game.unvisited_for() >= stall => {
+                found(Problem::NotTicking {
+                    stalled_for: game.unvisited_for(),
+                });
+            }
+            None => (),
+        }
+    }
+    return violations;
+}
+
+// Everything about a game's roster a violation might be about.
+pub fn dump(game: &Game) -> String {
+    let description = serde_json::to_string_pretty(&game.describe()).unwrap_or_default();
+    let bindings: Vec<String> = game
+        .players
+        .iter()
+        .map(|player| {
+            format!(
+                "slot {}: {:?} client {:?}",
+                player.index,
+                player.id,
+                player.connection.as_ref().map(|c| c.client_id)
+            )
+        })
+        .collect();
+    return format!(
+        "{}\nclosed: {:?}\n{}",
+        description,
+        game.closed_for(),
+        bindings.join("\n")
+    );
+}
+
+// Runs check after a change to the games, as the server's config asks, and
+// reports what it finds with a dump of every game involved. after names
+// the change, for the log.
+pub async fn enforce(state: &ServerState, after: &str) {
+    let mode = state.config().check_invariants;
+    if mode == InvariantChecks::Off {
+        return;
+    }
+    let loop_period = state.loop_period();
+    let violations = check(&state.games, &state.last_game_id, loop_period).await;
+    if violations.is_empty() {
+        return;
+    }
+    let mut involved = BTreeSet::new();
+    for violation in &violations {
+        log::error!("Games corrupt after {}: {}", after, violation);
+        involved.insert(violation.game_id);
+        if let Problem::ConnectionTwice { also, .. } = violation.problem {
+            involved.insert(also.0);
+        }
+    }
+    for game_id in involved {
+        let game = state.games.read().await.get(&game_id).cloned();
+        if let Some(game) = game {
+            log::error!("Game {}:\n{}", game_id, dump(&*game.read().await));
+        }
+    }
+    if mode == InvariantChecks::Panic {
+        panic!(
+            "{} invariant violations after {}, first: {}",
+            violations.len(),
+            after,
+            violations[0]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+pub mod audit;
+pub mod auth;
+pub mod client;
+pub mod config;
+pub mod degrade;
+pub mod disconnects;
+pub mod events;
+pub mod frame_dump;
+pub mod game;
+pub mod hooks;
+pub mod http;
+pub mod invariants;
+pub mod limiter;
+pub mod matchmaking;
+pub mod message;
+pub mod middleware;
+pub mod outbox;
+pub mod persistence;
+pub mod pool;
+pub mod profiling;
+pub mod serializer;
+pub mod server;
+pub mod stats;
+pub mod timers;
+pub mod traffic;
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+// Why a connection was turned away before the websocket handshake.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Refusal {
+    Banned { remaining: Duration },
+    TooManyConnections,
+}
+
+// What a protocol error cost the address it came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strike {
+    // strikes now held, still short of a ban
+    Counted(u32),
+    Banned,
+}
+
+#[derive(Debug, Default)]
+struct IpEntry {
+    connections: usize,
+    // protocol errors since the last ban, forgotten once the last one is a
+    // ban period old
+    strikes: u32,
+    last_strike: Option<Instant>,
+    banned_until: Option<Instant>,
+}
+
+impl IpEntry {
+    fn banned(&self, now: Instant) -> Option<Duration> {
+        return self
+            .banned_until
+            .filter(|until| *until > now)
+            .map(|until| until - now);
+    }
+
+    fn expire(&mut self, now: Instant, ban: Duration) {
+        if self.banned_until.map_or(false, |until| until <= now) {
+            self.banned_until = None;
+        }
+        if self.last_strike.map_or(false, |last| now - last >= ban) {
+            self.strikes = 0;
+            self.last_strike = None;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        return self.connections == 0 && self.strikes == 0 && self.banned_until.is_none();
+    }
+}
+
+// Open connections, protocol-error strikes and temporary bans, per remote
+// address. Entries go away once an address has no connections, strikes or
+// ban left.
+#[derive(Default)]
+pub struct IpLimiter {
+    entries: Mutex<HashMap<IpAddr, IpEntry>>,
+}
+
+impl IpLimiter {
+    // Counts a new connection from ip unless it is banned or already at
+    // max_connections. The slot is given back when the IpSlot drops.
+    pub fn admit(
+        &self,
+        ip: IpAddr,
+        max_connections: Option<usize>,
+        ban: Duration,
+    ) -> Result<IpSlot<'_>, Refusal> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(ip).or_default();
+        entry.expire(now, ban);
+        if let Some(remaining) = entry.banned(now) {
+            return Err(Refusal::Banned { remaining });
+        }
+        if max_connections.map_or(false, |max| entry.connections >= max) {
+            if entry.is_idle() {
+                entries.remove(&ip);
+            }
+            return Err(Refusal::TooManyConnections);
+        }
+        entry.connections += 1;
+        return Ok(IpSlot { limiter: self, ip });
+    }
+
+    // Records a protocol error from ip. The strike that reaches max_strikes
+    // bans the address for ban; 0 never bans.
+    pub fn strike(&self, ip: IpAddr, max_strikes: u32, ban: Duration) -> Strike {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(ip).or_default();
+        entry.expire(now, ban);
+        entry.strikes += 1;
+        entry.last_strike = Some(now);
+        if max_strikes == 0 || entry.strikes < max_strikes {
+            return Strike::Counted(entry.strikes);
+        }
+        entry.strikes = 0;
+        entry.last_strike = None;
+        entry.banned_until = Some(now + ban);
+        // bans are rare, so this is when addresses that went quiet are swept
+        entries.retain(|_, entry| {
+            entry.expire(now, ban);
+            !entry.is_idle()
+        });
+        return Strike::Banned;
+    }
+
+    pub fn strikes(&self, ip: IpAddr) -> u32 {
+        return self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&ip)
+            .map_or(0, |entry| entry.strikes);
+    }
+
+    pub fn banned(&self) -> usize {
+        let now = Instant::now();
+        return self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.banned(now).is_some())
+            .count();
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(&ip) {
+            entry.connections = entry.connections.saturating_sub(1);
+            if entry.is_idle() {
+                entries.remove(&ip);
+            }
+        }
+    }
+}
+
+// A connection counted against its address by IpLimiter::admit.
+pub struct IpSlot<'a> {
+    limiter: &'a IpLimiter,
+    ip: IpAddr,
+}
+
+impl Drop for IpSlot<'_> {
+    fn drop(&mut self) {
+        self.limiter.release(self.ip);
+    }
+}
+
+// Allowance for something a connection may do often but not without limit:
+// up to burst at once, refilled at rate_hz per second.
+#[derive(Debug)]
+pub struct TokenBucket {
+    burst: f64,
+    rate_hz: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(burst: u32, rate_hz: u32) -> Self {
+        return TokenBucket {
+            burst: burst as f64,
+            rate_hz: rate_hz as f64,
+            tokens: burst as f64,
+            refilled: Instant::now(),
+        };
+    }
+
+    // Spends one token; false when there is none left yet.
+    pub fn take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = (now - self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate_hz).min(self.burst);
+        self.refilled = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        return true;
+    }
+}
@@ -0,0 +1,506 @@
+use crate::game::Game;
+use crate::message::{ErrorCode, HandicapConfig, WsMessage};
+use crate::stats::PlayerId;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot, Notify, RwLock};
+use tokio::time::{Duration, Instant};
+
+// Where the matchmaker put a queued player.
+pub struct Match {
+    pub game_id: usize,
+    pub game: Arc<RwLock<Game>>,
+    pub player_index: usize,
+}
+
+pub struct QueueEntry {
+    pub client_id: usize,
+    pub owner: Owner,
+    pub name: String,
+    pub joined: Instant,
+    // false for ?lobby=1 connections, which only leave through a challenge
+    pub pairable: bool,
+    matched: oneshot::Sender<Result<Match, ErrorCode>>,
+}
+
+impl QueueEntry {
+    // Hands the match to the waiting connection. Err means the connection
+    // went away after it was taken off the queue.
+    pub fn notify(self, placed: Match) -> Result<(), Match> {
+        return match self.matched.send(Ok(placed)) {
+            Ok(()) => Ok(()),
+            Err(Ok(placed)) => Err(placed),
+            Err(Err(_)) => unreachable!(),
+        };
+    }
+
+    // Tells the waiting connection it won't be matched.
+    pub fn reject(self, code: ErrorCode) {
+        let _ = self.matched.send(Err(code));
+    }
+}
+
+// Players waiting for an opponent, oldest first, with one FIFO per game type.
+// Connections enqueue themselves and wait on the receiver they get back; the
+// matchmaker task pairs entries off the front.
+#[derive(Default)]
+pub struct MatchQueue {
+    queues: Mutex<HashMap<u8, VecDeque<QueueEntry>>>,
+    // wakes the matchmaker whenever someone joins
+    joined: Notify,
+}
+
+impl MatchQueue {
+    pub fn enqueue(
+        &self,
+        client_id: usize,
+        game_type: u8,
+        owner: Owner,
+        name: String,
+        pairable: bool,
+    ) -> oneshot::Receiver<Result<Match, ErrorCode>> {
+        let (matched, receiver) = oneshot::channel();
+        self.queues
+            .lock()
+            .unwrap()
+            .entry(game_type)
+            .or_default()
+            .push_back(QueueEntry {
+                client_id,
+                owner,
+                name,
+                joined: Instant::now(),
+                pairable,
+                matched,
+            });
+        self.joined.notify_one();
+        return receiver;
+    }
+
+    // Connections of this owner still waiting for a match, of any game type.
+    pub fn queued_as(&self, owner: &Owner) -> Vec<usize> {
+        let queues = self.queues.lock().unwrap();
+        return queues
+            .values()
+            .flatten()
+            .filter(|entry| entry.owner == *owner && !entry.matched.is_closed())
+            .map(|entry| entry.client_id)
+            .collect();
+    }
+
+    // Takes a connection out of the queue. False means it wasn't waiting,
+    // either because it never queued or because the matchmaker already
+    // paired it and its Match is on the way.
+    pub fn remove(&self, client_id: usize) -> bool {
+        let mut queues = self.queues.lock().unwrap();
+        for queue in queues.values_mut() {
+            if let Some(position) = queue.iter().position(|entry| entry.client_id == client_id) {
+                queue.remove(position);
+                return true;
+            }
+        }
+        return false;
+    }
+
+    // 1-based position in its queue and how long the connection has waited.
+    pub fn status(&self, client_id: usize) -> Option<(usize, Duration)> {
+        let queues = self.queues.lock().unwrap();
+        for queue in queues.values() {
+            if let Some(position) = queue.iter().position(|entry| entry.client_id == client_id) {
+                return Some((position + 1, queue[position].joined.elapsed()));
+            }
+        }
+        return None;
+    }
+
+    // Pops the two oldest pairable entries of any game type that has two
+    // waiting, skipping pairs of the same player, who can't share a game.
+    // Entries whose connection already dropped are discarded on the way.
+    pub fn take_pair(&self) -> Option<(u8, QueueEntry, QueueEntry)> {
+        let mut queues = self.queues.lock().unwrap();
+        for (game_type, queue) in queues.iter_mut() {
+            queue.retain(|entry| !entry.matched.is_closed());
+            let pairable: Vec<usize> = (0..queue.len()).filter(|&i| queue[i].pairable).collect();
+            let pair = pairable.iter().enumerate().find_map(|(n, &first)| {
+                let second = pairable[n + 1..]
+                    .iter()
+                    .find(|&&second| queue[second].owner.id != queue[first].owner.id)?;
+                return Some((first, *second));
+            });
+            if let Some((first, second)) = pair {
+                let second = queue.remove(second)?;
+                let first = queue.remove(first)?;
+                return Some((*game_type, first, second));
+            }
+        }
+        return None;
+    }
+
+    // The connection of another player waiting for game_type under name, and
+    // that player, if there is one. Told apart by player id, so the
+    // challenger's own connections are never found, whatever name they go
+    // by.
+    pub fn find(
+        &self,
+        game_type: u8,
+        name: &str,
+        challenger: &PlayerId,
+    ) -> Option<(usize, PlayerId)> {
+        let queues = self.queues.lock().unwrap();
+        return queues
+            .get(&game_type)?
+            .iter()
+            .find(|entry| {
+                entry.name == name && entry.owner.id != *challenger && !entry.matched.is_closed()
+            })
+            .map(|entry| (entry.client_id, entry.owner.id.clone()));
+    }
+
+    // Pops two connections waiting for the same game type together, or
+    // neither if either isn't waiting any more.
+    pub fn take_both(&self, first: usize, second: usize) -> Option<(u8, QueueEntry, QueueEntry)> {
+        let mut queues = self.queues.lock().unwrap();
+        for (game_type, queue) in queues.iter_mut() {
+            let a = queue.iter().position(|entry| entry.client_id == first);
+            let b = queue.iter().position(|entry| entry.client_id == second);
+            if let (Some(a), Some(b)) = (a, b) {
+                // the later one goes first so the other's position holds
+                let (first, second) = if a > b {
+                    let first = queue.remove(a)?;
+                    (first, queue.remove(b)?)
+                } else {
+                    let second = queue.remove(b)?;
+                    (queue.remove(a)?, second)
+                };
+                return Some((*game_type, first, second));
+            }
+        }
+        return None;
+    }
+
+    // Puts an entry taken by take_pair back at the front, keeping its place.
+    pub fn requeue(&self, game_type: u8, entry: QueueEntry) {
+        self.queues
+            .lock()
+            .unwrap()
+            .entry(game_type)
+            .or_default()
+            .push_front(entry);
+        self.joined.notify_one();
+    }
+
+    // Resolves once someone has joined since the last call.
+    pub async fn wait_for_join(&self) {
+        self.joined.notified().await;
+    }
+}
+
+// A Challenge its target hasn't answered yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub id: u32,
+    pub challenger: usize,
+    pub target: usize,
+    // the rules the game it starts is played with, if uneven
+    pub handicap: Option<HandicapConfig>,
+}
+
+// Open challenges between queued connections, and an inbox for each queued
+// connection so one connection's task can push messages to another. A
+// connection registers when it starts waiting and forgets itself when it
+// stops, which ends every challenge it was part of.
+#[derive(Default)]
+pub struct Challenges {
+    book: Mutex<ChallengeBook>,
+    // what the next challenge game between two players who have played one
+    // is played with when its challenge names no handicap, keyed by who was
+    // on the left
+    rematches: Mutex<HashMap<(PlayerId, PlayerId), HandicapConfig>>,
+}
+
+#[derive(Default)]
+struct ChallengeBook {
+    next_id: u32,
+    inboxes: HashMap<usize, mpsc::UnboundedSender<WsMessage>>,
+    open: Vec<Challenge>,
+}
+
+impl Challenges {
+    pub fn register(&self, client_id: usize) -> mpsc::UnboundedReceiver<WsMessage> {
+        let (inbox, receiver) = mpsc::unbounded_channel();
+        self.book.lock().unwrap().inboxes.insert(client_id, inbox);
+        return receiver;
+    }
+
+    // Opens a challenge, unless the challenger already has one out or the
+    // target is already answering one.
+    pub fn open(
+        &self,
+        challenger: usize,
+        target: usize,
+        handicap: Option<HandicapConfig>,
+    ) -> Option<Challenge> {
+        let mut book = self.book.lock().unwrap();
+        let busy = book
+            .open
+            .iter()
+            .any(|challenge| challenge.challenger == challenger || challenge.target == target);
+        if busy || !book.inboxes.contains_key(&target) {
+            return None;
+        }
+        book.next_id = book.next_id.wrapping_add(1);
+        let challenge = Challenge {
+            id: book.next_id,
+            challenger,
+            target,
+            handicap,
+        };
+        book.open.push(challenge.clone());
+        return Some(challenge);
+    }
+
+    // Closes challenge id if it was sent to target; None when it already
+    // ended some other way.
+    pub fn answer(&self, id: u32, target: usize) -> Option<Challenge> {
+        let mut book = self.book.lock().unwrap();
+        let position = book
+            .open
+            .iter()
+            .position(|challenge| challenge.id == id && challenge.target == target)?;
+        return Some(book.open.remove(position));
+    }
+
+    // Closes challenge id unanswered; None when it already ended.
+    pub fn expire(&self, id: u32) -> Option<Challenge> {
+        let mut book = self.book.lock().unwrap();
+        let position = book.open.iter().position(|challenge| challenge.id == id)?;
+        return Some(book.open.remove(position));
+    }
+
+    // Queues message for a registered connection. False means it has
+    // stopped waiting.
+    pub fn send(&self, client_id: usize, message: WsMessage) -> bool {
+        return match self.book.lock().unwrap().inboxes.get(&client_id) {
+            Some(inbox) => inbox.send(message).is_ok(),
+            None => false,
+        };
+    }
+
+    // Remembers the handicap a challenge game between left and right was
+    // started with, for their next one.
+    pub fn played(&self, left: &PlayerId, right: &PlayerId, handicap: Option<HandicapConfig>) {
+        let mut rematches = self.rematches.lock().unwrap();
+        rematches.remove(&(right.clone(), left.clone()));
+        let key = (left.clone(), right.clone());
+        match handicap {
+            Some(handicap) => rematches.insert(key, handicap.rematch()),
+            None => rematches.remove(&key),
+        };
+    }
+
+    // What a challenge from challenger to target that names no handicap is
+    // played with: the rematch of the last game between them, kept or
+    // swapped as that one's swap_on_rematch says, the challenger on the
+    // left. None after an even game or none at all.
+    pub fn rematch(&self, challenger: &PlayerId, target: &PlayerId) -> Option<HandicapConfig> {
+        let rematches = self.rematches.lock().unwrap();
+        if let Some(handicap) = rematches.get(&(challenger.clone(), target.clone())) {
+            return Some(*handicap);
+        }
+        return rematches
+            .get(&(target.clone(), challenger.clone()))
+            .map(|handicap| handicap.mirrored());
+    }
+
+    // Unregisters a connection and closes the challenges it was part of,
+    // which come back so the other side can be told.
+    pub fn forget(&self, client_id: usize) -> Vec<Challenge> {
+        let mut book = self.book.lock().unwrap();
+        book.inboxes.remove(&client_id);
+        let (ended, open) = book.open.drain(..).partition(|challenge| {
+            challenge.challenger == client_id || challenge.target == client_id
+        });
+        book.open = open;
+        return ended;
+    }
+}
+
+// Disconnected player slots by player, so a returning player is found with
+// one lookup instead of locking every game. A player disconnected from two
+// games at once maps to the most recent.
+#[derive(Default)]
+pub struct OpenSlots {
+    slots: Mutex<HashMap<PlayerId, usize>>,
+}
+
+impl OpenSlots {
+    pub fn insert(&self, id: PlayerId, game_id: usize) {
+        self.slots.lock().unwrap().insert(id, game_id);
+    }
+
+    pub fn get(&self, id: &PlayerId) -> Option<usize> {
+        return self.slots.lock().unwrap().get(id).copied();
+    }
+
+    // Forgets a slot once its player is back, unless the player has since
+    // been recorded for another game.
+    pub fn remove(&self, id: &PlayerId, game_id: usize) {
+        let mut slots = self.slots.lock().unwrap();
+        if slots.get(id) == Some(&game_id) {
+            slots.remove(id);
+        }
+    }
+
+    pub fn remove_game(&self, game_id: usize) {
+        self.slots.lock().unwrap().retain(|_, id| *id != game_id);
+    }
+}
+
+// Who a game counts against for the per-identity cap. Guests are only known
+// by name, which anyone can pick, so their address is part of it too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Owner {
+    pub id: PlayerId,
+    pub ip: Option<IpAddr>,
+}
+
+impl Owner {
+    pub fn new(id: PlayerId, ip: IpAddr) -> Self {
+        let ip = match id {
+            PlayerId::Account(_) | PlayerId::Subject(_) | PlayerId::Bot(_) => None,
+            PlayerId::Guest(_) => Some(ip),
+        };
+        return Owner { id, ip };
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self.ip {
+            Some(ip) => write!(f, "{}@{}", self.id, ip),
+            None => write!(f, "{}", self.id),
+        };
+    }
+}
+
+// Live games per owner, so one identity can't allocate physics worlds
+// without limit. Game ids are kept rather than a count: an owner can
+// legitimately be in several games at once (one finished but not yet
+// removed, say), and removing a game is exact however many it was in.
+#[derive(Default)]
+pub struct GameOwners {
+    games: Mutex<HashMap<Owner, BTreeSet<usize>>>,
+}
+
+impl GameOwners {
+    // Counts game_id against every owner, unless that takes any of them
+    // past max; then nothing is recorded and the positions of the owners
+    // already at the cap come back.
+    pub fn claim(
+        &self,
+        owners: &[Owner],
+        game_id: usize,
+        max: Option<usize>,
+    ) -> Result<(), Vec<usize>> {
+        let mut games = self.games.lock().unwrap();
+        if let Some(max) = max {
+            let over: Vec<usize> = owners
+                .iter()
+                .enumerate()
+                .filter(|(_, owner)| games.get(*owner).map_or(0, BTreeSet::len) >= max)
+                .map(|(i, _)| i)
+                .collect();
+            if !over.is_empty() {
+                return Err(over);
+            }
+        }
+        for owner in owners {
+            games.entry(owner.clone()).or_default().insert(game_id);
+        }
+        return Ok(());
+    }
+
+    pub fn count(&self, owner: &Owner) -> usize {
+        return self
+            .games
+            .lock()
+            .unwrap()
+            .get(owner)
+            .map_or(0, BTreeSet::len);
+    }
+
+    pub fn remove_game(&self, game_id: usize) {
+        self.games.lock().unwrap().retain(|_, games| {
+            games.remove(&game_id);
+            !games.is_empty()
+        });
+    }
+
+    // Every owner with a live game, most games first.
+    pub fn list(&self) -> Vec<(Owner, Vec<usize>)> {
+        let mut owners: Vec<_> = self
+            .games
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(owner, games)| (owner.clone(), games.iter().copied().collect::<Vec<_>>()))
+            .collect();
+        owners.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+        return owners;
+    }
+}
+
+// A game a player being matched could be put in: one of the game type they
+// asked for with a free slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub game_id: usize,
+    pub players: usize,
+    pub max_players: usize,
+    // past the ready check, so joining means coming in mid-match
+    pub started: bool,
+}
+
+// The player a strategy is placing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joining {
+    pub id: PlayerId,
+    pub name: String,
+    pub game_type: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    // into this candidate
+    Join(usize),
+    // a new game of their own, to wait in for whoever is placed with them
+    Create,
+    // the queue, to be paired with the next player of the same type
+    Queue,
+}
+
+// Decides where a player without a game or a slot to reclaim goes. Runs on
+// the connection's task for every such join, so it must not block. A Join
+// for a game that filled up in the meantime queues the player instead.
+pub trait MatchmakingStrategy: Send + Sync {
+    fn place(&self, candidates: &[Candidate], player: &Joining) -> Placement;
+}
+
+// The fullest game still in its ready check, oldest first on a tie; the
+// queue when there is none.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FillFirst;
+
+impl MatchmakingStrategy for FillFirst {
+    fn place(&self, candidates: &[Candidate], _player: &Joining) -> Placement {
+        return candidates
+            .iter()
+            .filter(|candidate| !candidate.started && candidate.players < candidate.max_players)
+            .min_by_key(|candidate| (candidate.max_players - candidate.players, candidate.game_id))
+            .map_or(Placement::Queue, |candidate| {
+                Placement::Join(candidate.game_id)
+            });
+    }
+}
@@ -1,20 +1,126 @@
+use bincode::Options;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+// Largest payload encode_payload/decode_payload will produce or accept.
+pub const MAX_PAYLOAD_SIZE: u64 = 64 * 1024;
+
+// Every payload struct goes through this one bincode configuration so the
+// wire format never depends on bincode's defaults: little-endian, fixed-size
+// integers, and a size limit so a bogus length prefix can't allocate
+// unbounded memory. Trailing bytes are ignored so newer peers can append
+// fields without breaking older decoders.
+fn payload_options() -> impl Options {
+    return bincode::DefaultOptions::new()
+        .with_little_endian()
+        .with_fixint_encoding()
+        .with_limit(MAX_PAYLOAD_SIZE)
+        .allow_trailing_bytes();
+}
+
+pub fn encode_payload<T: Serialize>(payload: &T) -> Option<Vec<u8>> {
+    return payload_options().serialize(payload).ok();
+}
+
+pub fn decode_payload<T: DeserializeOwned>(data: &[u8]) -> Option<T> {
+    return payload_options().deserialize(data).ok();
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub enum MessageType {
     Ping = 0,
     Pong = 1,
     State = 2,
     SoccerMove = 3,
+    GameOver = 4,
+    LeaveGame = 5,
+    GetStats = 6,
+    PauseRequest = 7,
+    ResumeRequest = 8,
+    GamePaused = 9,
+    GameResuming = 10,
+    Error = 11,
+    ServerInfo = 12,
+    Subscribe = 13,
+    Welcome = 14,
+    Ready = 15,
+    PowerUp = 16,
+    TimeSync = 17,
+    SetGameParams = 18,
+    GameParamsChanged = 19,
+    Boost = 20,
+    QueueStatus = 21,
+    LeaveQueue = 22,
+    PlayerJoined = 23,
+    PlayerLeft = 24,
+    Event = 25,
+    EventsSince = 26,
+    Chat = 27,
+    Mute = 28,
+    ReplayBurst = 29,
+    ReloadConfig = 30,
+    SubscribeLobby = 31,
+    LobbyUpdate = 32,
+    ModeChanged = 33,
+    Queued = 34,
+    WaitingForPlayer = 35,
+    SubscribeAll = 36,
+    MultiState = 37,
+    WhoAmI = 38,
+    Hello = 39,
+    Echo = 40,
+    SetFormation = 41,
+    Challenge = 42,
+    ChallengeReceived = 43,
+    ChallengeReply = 44,
+    ChallengeResult = 45,
+    Announce = 46,
+    Announcement = 47,
+    GoalScored = 48,
+    Roster = 49,
+    SetTickRate = 50,
+    StepGame = 51,
+    BallReset = 52,
+    AuditGame = 53,
+    SetInterest = 54,
+    PartialState = 55,
+    LockstepInput = 56,
+    LockstepStep = 57,
+    FreezeOpponent = 58,
+    Version = 59,
+    TuneSoccer = 60,
+    Turn = 61,
+    OpponentDisconnected = 62,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// Frame layout: one msg_type byte followed by the payload. Payload structs
+// are encoded with encode_payload; State is the raw little-endian f32 layout
+// described on SoccerStateSnapshot.
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WsMessage {
     pub msg_type: MessageType,
     pub payload: Vec<u8>,
 }
 
 impl WsMessage {
+    pub fn from_payload<T: Serialize>(msg_type: MessageType, payload: &T) -> Self {
+        return WsMessage {
+            msg_type,
+            payload: encode_payload(payload).unwrap_or_default(),
+        };
+    }
+    pub fn decode<T: DeserializeOwned>(&self) -> Option<T> {
+        return decode_payload(&self.payload);
+    }
+    pub fn error(code: ErrorCode, message: &str) -> Self {
+        return WsMessage::from_payload(
+            MessageType::Error,
+            &ErrorMessage {
+                code,
+                message: message.to_string(),
+            },
+        );
+    }
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(1 + self.payload.len());
         bytes.push(self.msg_type as u8);
@@ -22,32 +128,94 @@ impl WsMessage {
         return bytes;
     }
     pub fn from_bytes(data: &[u8]) -> Option<Self> {
-        if data.is_empty() {
-            return None;
-        }
-        let msg_type = match data[0] {
-            0 => MessageType::Ping,
-            1 => MessageType::Pong,
-            2 => MessageType::State,
-            3 => MessageType::SoccerMove,
-            _ => return None,
-        };
-
-        Some(WsMessage {
+        let msg_type = MessageType::try_from(*data.first()?).ok()?;
+        return Some(WsMessage {
             msg_type,
             payload: data[1..].to_vec(),
-        })
+        });
     }
+    // The type byte of a frame that has one this build doesn't know, which
+    // from_bytes would otherwise turn into None along with an empty frame.
+    pub fn unknown_type(data: &[u8]) -> Option<u8> {
+        return data
+            .first()
+            .copied()
+            .filter(|msg_type| MessageType::try_from(*msg_type).is_err());
+    }
+}
+
+impl MessageType {
+    // the highest type id this build knows; keep it on the last variant
+    pub const LAST: MessageType = MessageType::OpponentDisconnected;
 }
 
 impl TryFrom<u8> for MessageType {
     type Error = ();
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+    fn try_from(value: u8) -> Result<Self, ()> {
         match value {
             0 => Ok(MessageType::Ping),
             1 => Ok(MessageType::Pong),
             2 => Ok(MessageType::State),
             3 => Ok(MessageType::SoccerMove),
+            4 => Ok(MessageType::GameOver),
+            5 => Ok(MessageType::LeaveGame),
+            6 => Ok(MessageType::GetStats),
+            7 => Ok(MessageType::PauseRequest),
+            8 => Ok(MessageType::ResumeRequest),
+            9 => Ok(MessageType::GamePaused),
+            10 => Ok(MessageType::GameResuming),
+            11 => Ok(MessageType::Error),
+            12 => Ok(MessageType::ServerInfo),
+            13 => Ok(MessageType::Subscribe),
+            14 => Ok(MessageType::Welcome),
+            15 => Ok(MessageType::Ready),
+            16 => Ok(MessageType::PowerUp),
+            17 => Ok(MessageType::TimeSync),
+            18 => Ok(MessageType::SetGameParams),
+            19 => Ok(MessageType::GameParamsChanged),
+            20 => Ok(MessageType::Boost),
+            21 => Ok(MessageType::QueueStatus),
+            22 => Ok(MessageType::LeaveQueue),
+            23 => Ok(MessageType::PlayerJoined),
+            24 => Ok(MessageType::PlayerLeft),
+            25 => Ok(MessageType::Event),
+            26 => Ok(MessageType::EventsSince),
+            27 => Ok(MessageType::Chat),
+            28 => Ok(MessageType::Mute),
+            29 => Ok(MessageType::ReplayBurst),
+            30 => Ok(MessageType::ReloadConfig),
+            31 => Ok(MessageType::SubscribeLobby),
+            32 => Ok(MessageType::LobbyUpdate),
+            33 => Ok(MessageType::ModeChanged),
+            34 => Ok(MessageType::Queued),
+            35 => Ok(MessageType::WaitingForPlayer),
+            36 => Ok(MessageType::SubscribeAll),
+            37 => Ok(MessageType::MultiState),
+            38 => Ok(MessageType::WhoAmI),
+            39 => Ok(MessageType::Hello),
+            40 => Ok(MessageType::Echo),
+            41 => Ok(MessageType::SetFormation),
+            42 => Ok(MessageType::Challenge),
+            43 => Ok(MessageType::ChallengeReceived),
+            44 => Ok(MessageType::ChallengeReply),
+            45 => Ok(MessageType::ChallengeResult),
+            46 => Ok(MessageType::Announce),
+            47 => Ok(MessageType::Announcement),
+            48 => Ok(MessageType::GoalScored),
+            49 => Ok(MessageType::Roster),
+            50 => Ok(MessageType::SetTickRate),
+            51 => Ok(MessageType::StepGame),
+            52 => Ok(MessageType::BallReset),
+            53 => Ok(MessageType::AuditGame),
+            54 => Ok(MessageType::SetInterest),
+            55 => Ok(MessageType::PartialState),
+            56 => Ok(MessageType::LockstepInput),
+            57 => Ok(MessageType::LockstepStep),
+            58 => Ok(MessageType::FreezeOpponent),
+            59 => Ok(MessageType::Version),
+            60 => Ok(MessageType::TuneSoccer),
+            61 => Ok(MessageType::Turn),
+            62 => Ok(MessageType::OpponentDisconnected),
             _ => Err(()),
         }
     }
@@ -58,9 +226,2496 @@ impl From<MessageType> for u8 {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct SoccerMoveMessage {
     pub vx: f32,
     pub vy: f32,
     pub target: u8,
+    // trailing fields added after v1; shorter payloads from older clients
+    // decode with no spin and no sequence number
+    pub angular: f32,
+    // client sequence number echoed back in v2 State frames; 0 means the
+    // client isn't tracking acks
+    pub seq: u32,
+}
+
+#[derive(Deserialize)]
+struct SoccerMoveMessageV1 {
+    vx: f32,
+    vy: f32,
+    target: u8,
+}
+
+#[derive(Deserialize)]
+struct SoccerMoveMessageV2 {
+    vx: f32,
+    vy: f32,
+    target: u8,
+    angular: f32,
+}
+
+pub const SOCCER_MOVE_V1_LEN: usize = 9;
+pub const SOCCER_MOVE_V2_LEN: usize = 13;
+
+impl SoccerMoveMessage {
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() > SOCCER_MOVE_V2_LEN {
+            return decode_payload::<SoccerMoveMessage>(data);
+        }
+        if data.len() > SOCCER_MOVE_V1_LEN {
+            let v2 = decode_payload::<SoccerMoveMessageV2>(data)?;
+            return Some(SoccerMoveMessage {
+                vx: v2.vx,
+                vy: v2.vy,
+                target: v2.target,
+                angular: v2.angular,
+                seq: 0,
+            });
+        }
+        let v1 = decode_payload::<SoccerMoveMessageV1>(data)?;
+        return Some(SoccerMoveMessage {
+            vx: v1.vx,
+            vy: v1.vy,
+            target: v1.target,
+            angular: 0.0,
+            seq: 0,
+        });
+    }
+
+    // from_bytes for a payload whose numbers are in order.
+    pub fn decode(order: ByteOrder, data: &[u8]) -> Option<Self> {
+        if order == ByteOrder::Little {
+            return SoccerMoveMessage::from_bytes(data);
+        }
+        let mut data = data.to_vec();
+        order.swap_move(&mut data);
+        return SoccerMoveMessage::from_bytes(&data);
+    }
+
+    pub fn encode(&self, order: ByteOrder) -> Vec<u8> {
+        let mut payload = encode_payload(self).unwrap_or_default();
+        order.swap_move(&mut payload);
+        return payload;
+    }
+}
+
+// Byte order of the numbers in State and SoccerMove payloads, picked per
+// connection with ?byte_order=le|be; everything else stays little-endian.
+// Payloads are built and parsed little-endian as always, and a big-endian
+// connection has each field reversed in place on the way out and on the
+// way in. Reversing is its own inverse, so the same walk over the layout
+// serves both directions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    pub fn from_param(value: &str) -> Option<Self> {
+        return match value {
+            "le" => Some(ByteOrder::Little),
+            "be" => Some(ByteOrder::Big),
+            _ => None,
+        };
+    }
+
+    pub fn as_param(&self) -> &'static str {
+        return match self {
+            ByteOrder::Little => "le",
+            ByteOrder::Big => "be",
+        };
+    }
+
+    pub fn code(&self) -> u8 {
+        return match self {
+            ByteOrder::Little => 0,
+            ByteOrder::Big => 1,
+        };
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        return match code {
+            0 => Some(ByteOrder::Little),
+            1 => Some(ByteOrder::Big),
+            _ => None,
+        };
+    }
+
+    // Converts a SoccerMove payload of any length between little-endian and
+    // this order.
+    pub fn swap_move(&self, payload: &mut [u8]) {
+        if *self == ByteOrder::Little {
+            return;
+        }
+        // shorter, older payloads just run out early
+        let _ = FieldSwapper { data: payload }.soccer_move();
+    }
+
+    // Converts a State payload in protocol's layout between little-endian
+    // and this order. False if the payload is too short for the counts it
+    // carries, in which case it is left partly converted and won't decode
+    // anyway.
+    pub fn swap_state(&self, protocol: ProtocolVersion, payload: &mut [u8]) -> bool {
+        if *self == ByteOrder::Little {
+            return true;
+        }
+        let mut fields = FieldSwapper { data: payload };
+        return match protocol {
+            // nothing but 4 byte fields: f32s, and v2's trailing ack_seq
+            ProtocolVersion::V1 | ProtocolVersion::V2 => {
+                let len = fields.data.len();
+                len % 4 == 0 && fields.flip_each(4, len / 4).is_some()
+            }
+            ProtocolVersion::V3 => fields.v3().is_some(),
+            ProtocolVersion::V4 => fields.header().and_then(|_| fields.v3()).is_some(),
+            ProtocolVersion::V5 | ProtocolVersion::V6 => fields
+                .header()
+                .and_then(|_| {
+                    let own_pucks = fields.count()?;
+                    for _ in 0..own_pucks {
+                        fields.skip(1)?;
+                        fields.flip_each(4, 4)?;
+                    }
+                    return fields.v3();
+                })
+                .is_some(),
+            // the match phase byte, then v5
+            ProtocolVersion::V7 => {
+                fields.skip(1).is_some() && self.swap_state(ProtocolVersion::V5, fields.data)
+            }
+            // the snapshot header, then v5
+            ProtocolVersion::V8 => {
+                fields.snapshot_header().is_some()
+                    && self.swap_state(ProtocolVersion::V5, fields.data)
+            }
+            // the snapshot header and its degrade level, then v5; quantized
+            // from DegradeLevel::Quantized on, which swap_quantized_state
+            // is for
+            ProtocolVersion::V9 | ProtocolVersion::V10 | ProtocolVersion::V11 => {
+                fields
+                    .snapshot_header()
+                    .and_then(|_| fields.skip(1))
+                    .is_some()
+                    && self.swap_state(ProtocolVersion::V5, fields.data)
+            }
+        };
+    }
+
+    // swap_state for a quantized State.
+    pub fn swap_quantized_state(&self, protocol: ProtocolVersion, payload: &mut [u8]) -> bool {
+        if *self == ByteOrder::Little {
+            return true;
+        }
+        let mut fields = FieldSwapper { data: payload };
+        return match protocol {
+            ProtocolVersion::V5 | ProtocolVersion::V6 => {
+                fields.header().and_then(|_| fields.quantized()).is_some()
+            }
+            ProtocolVersion::V7 => {
+                fields.skip(1).is_some()
+                    && self.swap_quantized_state(ProtocolVersion::V5, fields.data)
+            }
+            ProtocolVersion::V8 => {
+                fields.snapshot_header().is_some()
+                    && self.swap_quantized_state(ProtocolVersion::V5, fields.data)
+            }
+            ProtocolVersion::V9 | ProtocolVersion::V10 | ProtocolVersion::V11 => {
+                fields
+                    .snapshot_header()
+                    .and_then(|_| fields.skip(1))
+                    .is_some()
+                    && self.swap_quantized_state(ProtocolVersion::V5, fields.data)
+            }
+            // quantized State starts at v5
+            _ => false,
+        };
+    }
+
+    // Converts the SnapshotHeader at the front of a v8 State, leaving the
+    // body after it alone. False if the payload is too short for it. The
+    // byte v9 adds needs no converting.
+    pub fn swap_snapshot_header(&self, payload: &mut [u8]) -> bool {
+        if *self == ByteOrder::Little {
+            return true;
+        }
+        return FieldSwapper { data: payload }.snapshot_header().is_some();
+    }
+
+    // swap_state for a PartialState sent over protocol.
+    pub fn swap_partial_state(&self, protocol: ProtocolVersion, payload: &mut [u8]) -> bool {
+        if *self == ByteOrder::Little {
+            return true;
+        }
+        let mut fields = FieldSwapper { data: payload };
+        return fields
+            .snapshot_header()
+            .and_then(|_| {
+                if protocol >= ProtocolVersion::V9 {
+                    fields.skip(1)?;
+                }
+                // a mask has as many bits set whichever way round it is
+                let interest = u64::from_le_bytes(fields.data.get(..8)?.try_into().ok()?);
+                fields.flip(8)?;
+                return fields.flip_each(4, interest.count_ones() as usize * 2);
+            })
+            .is_some();
+    }
+}
+
+// Cursor over a payload that reverses each multi-byte field it passes.
+struct FieldSwapper<'a> {
+    data: &'a mut [u8],
+}
+
+impl<'a> FieldSwapper<'a> {
+    fn skip(&mut self, n: usize) -> Option<()> {
+        if self.data.len() < n {
+            return None;
+        }
+        let data = std::mem::take(&mut self.data);
+        self.data = &mut data[n..];
+        return Some(());
+    }
+    fn flip(&mut self, n: usize) -> Option<()> {
+        self.data.get_mut(..n)?.reverse();
+        return self.skip(n);
+    }
+    fn flip_each(&mut self, n: usize, count: usize) -> Option<()> {
+        for _ in 0..count {
+            self.flip(n)?;
+        }
+        return Some(());
+    }
+    fn count(&mut self) -> Option<usize> {
+        let count = *self.data.first()? as usize;
+        self.skip(1)?;
+        return Some(count);
+    }
+    // vx, vy, target, then angular and seq when present
+    fn soccer_move(&mut self) -> Option<()> {
+        self.flip_each(4, 2)?;
+        self.skip(1)?;
+        return self.flip_each(4, 2);
+    }
+    // tick u32, server_time_us u64, phase u8
+    fn header(&mut self) -> Option<()> {
+        self.flip(4)?;
+        self.flip(8)?;
+        return self.skip(1);
+    }
+    // tick u32, match phase u8, remaining_ds u16, then a u8 per team
+    fn snapshot_header(&mut self) -> Option<()> {
+        self.flip(4)?;
+        self.skip(1)?;
+        self.flip(2)?;
+        let teams = self.count()?;
+        return self.skip(teams);
+    }
+    fn v3(&mut self) -> Option<()> {
+        let pucks = self.count()?;
+        let balls = self.count()?;
+        self.flip_each(4, (pucks + balls) * 4)?;
+        for _ in 0..self.count()? {
+            // id, kind, x, y
+            self.flip(4)?;
+            self.skip(1)?;
+            self.flip_each(4, 2)?;
+        }
+        for _ in 0..self.count()? {
+            // kind, player, remaining_ms
+            self.skip(2)?;
+            self.flip(4)?;
+        }
+        // ack_seq, boost_cooldown_ms
+        return self.flip_each(4, 2);
+    }
+    // the quantized body, from the arena extents on
+    fn quantized(&mut self) -> Option<()> {
+        self.flip_each(2, 2)?;
+        for _ in 0..self.count()? {
+            // target, x, y, vx, vy
+            self.skip(1)?;
+            self.flip_each(2, 4)?;
+        }
+        let pucks = self.count()?;
+        let balls = self.count()?;
+        self.flip_each(2, (pucks + balls) * 4)?;
+        for _ in 0..self.count()? {
+            self.flip(4)?;
+            self.skip(1)?;
+            self.flip_each(2, 2)?;
+        }
+        for _ in 0..self.count()? {
+            self.skip(2)?;
+            self.flip(4)?;
+        }
+        return self.flip_each(4, 2);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverReason {
+    // the other player sent LeaveGame during the match
+    Forfeit,
+    // the match clock ran out; the side ahead wins, level is a draw
+    TimeUp,
+}
+
+// A one-off strong push on one of the sender's pucks in direction (dx, dy),
+// usable once per cooldown. The remaining cooldown is in v3 State frames.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct BoostMessage {
+    pub target: u8,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+// Picks the kickoff layout for the sender's pucks during the ready check:
+// "balanced" (the default), "attacking" or "defensive". The pucks move
+// straight away and go back there after every goal.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SetFormationMessage {
+    pub formation: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GameOverMessage {
+    pub winner: Option<u8>,
+    pub reason: GameOverReason,
+    // one entry per slot, for the post-game summary; empty for game types
+    // that don't count inputs
+    pub stats: Vec<SlotStats>,
+}
+
+// What one slot did over the match. Shots are moves that set a puck going,
+// pucks_used how many of the slot's pucks took at least one, and possession
+// the ticks during which one of the slot's pucks was the last to touch a
+// ball. Warm-up doesn't count.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct SlotStats {
+    pub player: u8,
+    pub shots: u32,
+    pub avg_shot_speed: f32,
+    pub pucks_used: u32,
+    pub possession_ticks: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    GameFull,
+    GameNotFound,
+    NameTaken,
+    GamePaused,
+    NotPaused,
+    PauseUnavailable,
+    // a text frame arrived; the protocol is binary only
+    BinaryExpected,
+    // the requested game's game_type doesn't match the client's ?mode=, or
+    // the server has no game of that type
+    WrongGameType,
+    // an admin-only message from a connection without the admin token
+    Unauthorized,
+    InvalidParams,
+    // a Boost arrived before the player's cooldown ran out
+    BoostCooldown,
+    // team chat in a game type without teams
+    NoTeams,
+    // a move or Boost between a goal and the end of the kickoff freeze; the
+    // message is the phase name, "goal_scored" or "kickoff"
+    InputFrozen,
+    // the config file failed to load or validate; the old config stays
+    ReloadFailed,
+    // a new game was needed but this player already holds
+    // max_games_per_identity; joining an existing game still works
+    TooManyGames,
+    // the server is running max_games; sent to a practice request, and to a
+    // queued connection once full_queue_timeout runs out
+    ServerFull,
+    // a Hello asked for something the server can't do; the message says
+    // what, and the connection is closed
+    UnsupportedHello,
+    // the connection's Echo budget is spent for now; it refills over time
+    RateLimited,
+    // a SetFormation after the ready check; formations are picked before
+    // the match starts
+    FormationLocked,
+    // a Challenge named no one waiting for the same game type
+    PlayerNotFound,
+    // a Challenge while the sender already has one out, or to someone
+    // already answering another
+    ChallengeBusy,
+    // SetTickRate, StepGame or AuditGame on a server with debug_ticks off
+    DebugDisabled,
+    // a frame with a type byte past MessageType::LAST; sent as an
+    // UnsupportedTypeMessage and the connection stays open
+    UnsupportedMessageType,
+    // input that doesn't fit the game's lockstep: a SoccerMove to a
+    // lockstep game, a LockstepInput to one that isn't, or one for a tick
+    // already stepped or too far ahead
+    LockstepInput,
+    // a move in a turn-based game from a slot whose turn it isn't
+    NotYourTurn,
+    // the player already has a live connection, in its slot or in the
+    // queue, and the server keeps the first (DuplicateConnection::Reject)
+    AlreadyConnected,
+}
+
+// Why the server closed a connection, sent as the websocket close code and
+// reason. Codes in the 4000 range are specific to this server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    NormalLobbyExit,
+    IdleTimeout,
+    HeartbeatTimeout,
+    KickedByAdmin,
+    ServerShutdown,
+    ProtocolViolation,
+    MessageTooBig,
+    ServerFull,
+    InternalError,
+    // the client stopped reading and its control frames piled up
+    TooSlow,
+    // a ConnectionMiddleware refused something the client sent
+    PolicyViolation,
+    // the same player has another connection, in its slot or in the queue:
+    // sent to the older one when the server lets the newest win, and to
+    // the newer one when it keeps the first
+    ReplacedByNewConnection,
+}
+
+impl CloseReason {
+    pub const ALL: [CloseReason; 12] = [
+        CloseReason::NormalLobbyExit,
+        CloseReason::IdleTimeout,
+        CloseReason::HeartbeatTimeout,
+        CloseReason::KickedByAdmin,
+        CloseReason::ServerShutdown,
+        CloseReason::ProtocolViolation,
+        CloseReason::MessageTooBig,
+        CloseReason::ServerFull,
+        CloseReason::InternalError,
+        CloseReason::TooSlow,
+        CloseReason::PolicyViolation,
+        CloseReason::ReplacedByNewConnection,
+    ];
+
+    pub fn code(&self) -> u16 {
+        match self {
+            CloseReason::NormalLobbyExit => 1000,
+            CloseReason::ServerShutdown => 1001,
+            CloseReason::ProtocolViolation => 1002,
+            CloseReason::PolicyViolation => 1008,
+            CloseReason::MessageTooBig => 1009,
+            CloseReason::InternalError => 1011,
+            CloseReason::ServerFull => 1013,
+            CloseReason::IdleTimeout => 4000,
+            CloseReason::HeartbeatTimeout => 4001,
+            CloseReason::KickedByAdmin => 4002,
+            CloseReason::TooSlow => 4003,
+            CloseReason::ReplacedByNewConnection => 4004,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            CloseReason::NormalLobbyExit => "normal lobby exit",
+            CloseReason::IdleTimeout => "idle timeout",
+            CloseReason::HeartbeatTimeout => "heartbeat timeout",
+            CloseReason::KickedByAdmin => "kicked by admin",
+            CloseReason::ServerShutdown => "server shutdown",
+            CloseReason::ProtocolViolation => "protocol violation",
+            CloseReason::MessageTooBig => "message too big",
+            CloseReason::ServerFull => "server full",
+            CloseReason::InternalError => "internal error",
+            CloseReason::TooSlow => "too slow",
+            CloseReason::PolicyViolation => "policy violation",
+            CloseReason::ReplacedByNewConnection => "replaced by new connection",
+        }
+    }
+
+    pub fn from_code(code: u16) -> Option<Self> {
+        return CloseReason::ALL
+            .iter()
+            .copied()
+            .find(|reason| reason.code() == code);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ErrorMessage {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+// The Error for a frame of unknown type. It leads with ErrorMessage's fields
+// so a client that only knows that can still read it; a newer client learns
+// which types and protocol version this server speaks and can fall back.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UnsupportedTypeMessage {
+    pub code: ErrorCode,
+    pub message: String,
+    pub msg_type: u8,
+    pub max_type: u8,
+    // the newest protocol version the server speaks, not the connection's
+    pub protocol: u8,
+}
+
+// Broadcast when a player pauses; the game auto-resumes after max_duration_ms.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GamePausedMessage {
+    pub by: u8,
+    pub max_duration_ms: u32,
+    pub pauses_left: u8,
+}
+
+// Broadcast when a match freezes because player_index dropped and the
+// server's short_handed policy is to wait. They forfeit if still gone after
+// forfeit_in_ms; None waits for them indefinitely. GameResuming follows if
+// they rejoin.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct WaitingForPlayerMessage {
+    pub player_index: u8,
+    pub forfeit_in_ms: Option<u32>,
+}
+
+// Sent to the players still connected while a match waits for
+// player_index to rejoin, when it starts waiting and again every
+// PauseConfig::reconnect_notice_every until it stops. The counts are what
+// is left of the wait, None when it has no end.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct OpponentDisconnectedMessage {
+    pub player_index: u8,
+    pub forfeit_in_ms: Option<u32>,
+    pub forfeit_in_ticks: Option<u32>,
+}
+
+// Broadcast when physics will (re)start after countdown_ms. `by` is None when
+// the server started on its own: the match kicking off after the ready check,
+// or a pause running out.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GameResumingMessage {
+    pub by: Option<u8>,
+    pub countdown_ms: u32,
+}
+
+// Capabilities and host stats returned for a ServerInfo request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ServerInfoMessage {
+    pub version: String,
+    pub protocol_versions: Vec<u8>,
+    pub game_types: Vec<u8>,
+    pub max_players_per_game: u8,
+    pub tick_rate: u16,
+    pub total_memory_mb: u64,
+    pub available_memory_mb: u64,
+    pub physical_cores: u16,
+    pub logical_threads: u16,
+}
+
+// Which build a connection is talking to, returned for a Version request.
+// git_hash and built_at come from build.rs; a build without it says
+// "unknown" and 0.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VersionMessage {
+    pub version: String,
+    pub git_hash: String,
+    // unix seconds
+    pub built_at: u64,
+    pub min_protocol: u8,
+    pub max_protocol: u8,
+}
+
+impl VersionMessage {
+    pub fn current() -> Self {
+        return VersionMessage {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("BUILD_GIT_HASH")
+                .unwrap_or("unknown")
+                .to_string(),
+            built_at: option_env!("BUILD_TIMESTAMP")
+                .and_then(|secs| secs.parse().ok())
+                .unwrap_or(0),
+            min_protocol: ProtocolVersion::MIN as u8,
+            max_protocol: ProtocolVersion::MAX as u8,
+        };
+    }
+
+    pub fn supports(&self, protocol: u8) -> bool {
+        return (self.min_protocol..=self.max_protocol).contains(&protocol);
+    }
+}
+
+// First message after a connection is placed in a game. Everyone in the
+// game gets the same seed so cosmetic randomness can match the server.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WelcomeMessage {
+    pub game_id: u32,
+    pub player_index: u8,
+    pub seed: u64,
+    // goal this player defends: 0 left, 1 right
+    pub side: u8,
+    // needed to reclaim the slot after a disconnect (?session=) and to leave
+    pub session_token: String,
+    // goes with game_id in links to the game (?game=&game_token=)
+    pub game_token: String,
+    // last so clients that predate it still decode the rest
+    pub connection: WhoAmIMessage,
+    // after connection for the same reason
+    pub phase: MatchPhase,
+    // every puck in State order, so clients needn't guess which are whose;
+    // after phase for the same reason, and empty for game types without
+    // pucks
+    pub pucks: Vec<PuckOwner>,
+    // the server closes a player who sends no Ping for this long; 0 when
+    // it doesn't require them. After pucks for the same reason
+    pub ping_interval_ms: u32,
+    // the protocol versions the server speaks, after ping_interval_ms for
+    // the same reason
+    pub min_protocol: u8,
+    pub max_protocol: u8,
+    // the game's uneven rules, None when both sides play by the same ones;
+    // after the protocol range for the same reason
+    pub handicap: Option<HandicapConfig>,
+}
+
+// Who a puck belongs to: the slot that steers it, its SoccerMove target
+// among that slot's pucks, and its team's color id when colors are set.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PuckOwner {
+    pub player: u8,
+    pub target: u8,
+    pub color: Option<u8>,
+}
+
+// Where a game stands as far as a client's screen goes: still matchmaking,
+// everyone here and about to start, under way (pauses included), or done.
+// In every Welcome, and at the front of v7 State.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchPhase {
+    #[default]
+    Waiting,
+    Ready,
+    Playing,
+    Over,
+}
+
+impl MatchPhase {
+    pub fn code(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        return match code {
+            0 => Some(MatchPhase::Waiting),
+            1 => Some(MatchPhase::Ready),
+            2 => Some(MatchPhase::Playing),
+            3 => Some(MatchPhase::Over),
+            _ => None,
+        };
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            MatchPhase::Waiting => "waiting",
+            MatchPhase::Ready => "ready",
+            MatchPhase::Playing => "playing",
+            MatchPhase::Over => "over",
+        };
+    }
+}
+
+// How far the server has cut back a connection's snapshots because it
+// can't keep up, each level on top of the ones before it: every other
+// snapshot, then quantized encoding for a binary connection, then only the
+// ball and the receiver's own pucks. At the end of a v9 SnapshotHeader.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DegradeLevel {
+    #[default]
+    Full,
+    HalfRate,
+    Quantized,
+    InterestOnly,
+}
+
+impl DegradeLevel {
+    pub const ALL: [DegradeLevel; 4] = [
+        DegradeLevel::Full,
+        DegradeLevel::HalfRate,
+        DegradeLevel::Quantized,
+        DegradeLevel::InterestOnly,
+    ];
+
+    pub fn code(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        return DegradeLevel::ALL.get(code as usize).copied();
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            DegradeLevel::Full => "full",
+            DegradeLevel::HalfRate => "half_rate",
+            DegradeLevel::Quantized => "quantized",
+            DegradeLevel::InterestOnly => "interest_only",
+        };
+    }
+
+    // The next level down, or up; None past either end.
+    pub fn lower(&self) -> Option<Self> {
+        return DegradeLevel::from_code(self.code() + 1);
+    }
+
+    pub fn higher(&self) -> Option<Self> {
+        return DegradeLevel::from_code(self.code().checked_sub(1)?);
+    }
+}
+
+// What the server sees of a connection, in every Welcome and as the reply to
+// an empty WhoAmI. address is the client's as forwarded by a trusted proxy,
+// when it came through one; port is 0 then, as proxies don't pass it on.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WhoAmIMessage {
+    pub address: String,
+    pub port: u16,
+    pub protocol: u8,
+    pub server_version: String,
+}
+
+// The first frame of a v6 connection, saying what the client wants, and the
+// server's answer with what was agreed. state_version is the protocol
+// version whose State layout to use, up to the connection's own; build is
+// the sender's, a client build string one way and the server's version the
+// other.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HelloMessage {
+    pub protocol: u8,
+    pub build: String,
+    pub state_version: u8,
+    // StateFormat::code
+    pub format: u8,
+    // ByteOrder::code
+    pub byte_order: u8,
+    // Role::code
+    pub role: u8,
+}
+
+// What a connection is for, picked in Hello.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Player,
+    // watches every game through SubscribeAll; needs the admin token
+    Firehose,
+}
+
+impl Role {
+    pub fn code(&self) -> u8 {
+        return match self {
+            Role::Player => 0,
+            Role::Firehose => 1,
+        };
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        return match code {
+            0 => Some(Role::Player),
+            1 => Some(Role::Firehose),
+            _ => None,
+        };
+    }
+}
+
+// LeaveGame payload; the session token from Welcome proves the sender owns
+// the slot it is forfeiting.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LeaveGameMessage {
+    pub session_token: String,
+}
+
+// Roster changes, broadcast to the game. A connection also gets PlayerJoined
+// for everyone already connected when it is placed, so the same join can
+// arrive twice and should be treated as idempotent.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PlayerJoinedMessage {
+    pub player_index: u8,
+    pub name: String,
+    pub bot: bool,
+}
+
+// `left` is true for LeaveGame and false for a dropped connection that may
+// still rejoin its slot.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PlayerLeftMessage {
+    pub player_index: u8,
+    pub name: String,
+    pub left: bool,
+}
+
+// Every game broadcast is wrapped in an Event: frame is the broadcast
+// message's full encoding (type byte and payload) and seq counts up from 1
+// per game. Clients remember the last seq so they can catch up after a
+// reconnect.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EventMessage {
+    pub seq: u64,
+    pub frame: Vec<u8>,
+}
+
+impl EventMessage {
+    // The type byte of the frame inside an encoded Event frame, read in
+    // place: it follows the Event's type byte, the seq and the frame's
+    // length, both u64.
+    pub fn inner_type(data: &[u8]) -> Option<u8> {
+        if data.first() != Some(&(MessageType::Event as u8)) {
+            return None;
+        }
+        return data.get(17).copied();
+    }
+}
+
+// Client to server: resend the events after seq. The server replays what it
+// still has as Event frames, then answers with EventsSinceResponse.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct EventsSinceMessage {
+    pub seq: u64,
+}
+
+// Server to client: a probe sent to a connection that has gone quiet. The
+// client answers with a Pong carrying the same payload so the server can
+// tell which Ping it belongs to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct PingMessage {
+    pub id: u32,
+}
+
+// One recorded State payload in the v3 layout (ack_seq and boost cooldown
+// left 0) and the tick it was taken on.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplayFrame {
+    pub tick: u32,
+    pub state: Vec<u8>,
+}
+
+// Server to client after a goal: the seconds leading up to it, oldest first
+// and thinned out to at most MAX_REPLAY_FRAMES. The last frame is the goal
+// tick. Meant to be played back at half speed during the kickoff freeze.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplayBurstMessage {
+    pub goal_tick: u32,
+    pub frames: Vec<ReplayFrame>,
+}
+
+pub const MAX_REPLAY_FRAMES: usize = 20;
+
+// Broadcast when a ball goes in: scorer is the slot the goal counts for.
+// toucher and puck are the slot and index among its pucks of the last puck
+// to touch the ball, None when nobody touched it within the game's touch
+// window. own_goal is set when that puck belongs to the conceding side.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct GoalScoredMessage {
+    pub scorer: u8,
+    pub toucher: Option<u8>,
+    pub puck: Option<u8>,
+    pub own_goal: bool,
+}
+
+// Broadcast when anti-stall moves a stuck ball, so clients can say why it
+// jumped: ball is its index among the State's balls and (x, y) where it was
+// put, at rest.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct BallResetMessage {
+    pub ball: u8,
+    pub x: f32,
+    pub y: f32,
+}
+
+// Broadcast when the server swaps the game being played without moving
+// anyone: every slot is kept, but state from now on is for game_type and
+// randomness restarts from seed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ModeChangedMessage {
+    pub game_type: u8,
+    pub seed: u64,
+}
+
+// Admin only. Sent empty to make the server re-read its config file; the
+// reply lists changed settings that need a restart to apply.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConfigReloadedMessage {
+    pub restart_required: Vec<String>,
+}
+
+// Admin only: sends text to every open connection as an Announcement. The
+// reply, under the same type, says how many connections it went to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AnnounceMessage {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AnnouncedMessage {
+    pub delivered: u32,
+}
+
+// Pushed to every connection, queued, playing or watching, when an admin
+// announces something ("maintenance in 5 minutes").
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AnnouncementMessage {
+    pub text: String,
+}
+
+// Admin only, with debug_ticks on: runs one game, or with game_id None the
+// whole tick loop, at hz updates a second in slow motion, each update still
+// one normal tick of game time. hz 0 goes back to the configured rate, and
+// takes a game out of manual stepping. The reply, under the same type, is
+// the rate now in effect.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SetTickRateMessage {
+    pub game_id: Option<u32>,
+    pub hz: f32,
+}
+
+// Admin only, with debug_ticks on: stops a game ticking on its own and
+// advances it steps updates, one tick apart so every snapshot goes out.
+// steps 0 just stops it. The game stays stepped by hand until a
+// SetTickRate for it. Answered with a GameSteppedMessage under the same
+// type.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct StepGameMessage {
+    pub game_id: u32,
+    pub steps: u32,
+}
+
+// Admin only, with debug_ticks on: steps a shadow copy of a soccer game
+// alongside it and compares the two every `every` ticks, or with every 0
+// stops. Only taken before kickoff. A divergence is logged and published
+// as a ServerEvent; the reply, under the same type, is the request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AuditGameMessage {
+    pub game_id: u32,
+    pub every: u32,
+}
+
+// Admin only, with debug_ticks on: pins one slot's pucks of a soccer game
+// where they stand, or with frozen false lets them go, so the other side
+// can practise shots against a defense that doesn't move. The reply, under
+// the same type, is the request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct FreezeOpponentMessage {
+    pub game_id: u32,
+    pub player: u8,
+    pub frozen: bool,
+}
+
+// One puck's move within a LockstepInput, as a SoccerMove would give it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct LockstepMove {
+    pub target: u8,
+    pub vx: f32,
+    pub vy: f32,
+    pub angular: f32,
+}
+
+// Everything the sender does on one tick of a lockstep game, in place of
+// SoccerMove; moves may be empty, which still counts as having sent. Input
+// for tick is applied by the update that takes the game from tick to the
+// next, so a client that has seen State for tick sends for tick. Inputs may
+// be sent up to LOCKSTEP_WINDOW ticks ahead.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockstepInputMessage {
+    pub tick: u32,
+    pub moves: Vec<LockstepMove>,
+}
+
+pub const LOCKSTEP_WINDOW: u32 = 120;
+
+// Broadcast as a lockstep game in play steps: every input applied on tick,
+// by player, so both ends can run the same simulation. missing are the
+// connected players the wait for input gave up on.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockstepStepMessage {
+    pub tick: u32,
+    pub inputs: Vec<(u8, Vec<LockstepMove>)>,
+    pub missing: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct GameSteppedMessage {
+    pub game_id: u32,
+    // the game's tick once the last step is taken; the reply doesn't wait
+    // for it
+    pub tick: u64,
+}
+
+// Client to server: who holds each slot of a game. game_id None asks about
+// the sender's own game; any other needs the admin token. Answered with a
+// RosterMessage under the same type.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct RosterRequest {
+    pub game_id: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SlotStatus {
+    Connected,
+    // dropped and still within its reconnect timeout
+    Reconnecting,
+    // played by the server
+    Bot,
+    Empty,
+}
+
+impl SlotStatus {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            SlotStatus::Connected => "connected",
+            SlotStatus::Reconnecting => "reconnecting",
+            SlotStatus::Bot => "bot",
+            SlotStatus::Empty => "empty",
+        };
+    }
+}
+
+// name is empty for an Empty slot.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RosterEntry {
+    pub index: u8,
+    pub name: String,
+    pub status: SlotStatus,
+}
+
+// Every slot of the game, in index order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RosterMessage {
+    pub game_id: u32,
+    pub slots: Vec<RosterEntry>,
+}
+
+// complete is false when events after the requested seq already fell out of
+// the server's log, so the client needs a full resync.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct EventsSinceResponse {
+    pub latest_seq: u64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ChatScope {
+    All,
+    // only players on the sender's team
+    Team,
+}
+
+pub const MAX_CHAT_LEN: usize = 200;
+
+// Both directions: clients send a line with `from` ignored, and the server
+// relays it with the sender's slot filled in, echoing it to the sender too.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub scope: ChatScope,
+    pub from: u8,
+    pub text: String,
+}
+
+// Stops the server relaying chat from `slot` to this player. Mutes belong to
+// the player's slot, so they survive a reconnect with the session token.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct MuteMessage {
+    pub slot: u8,
+}
+
+// Sent once to a connection put in the matchmaking queue while the server
+// is running max_games, or still in it when the server fills up. It is
+// matched as games end, and turned away with ServerFull if that takes
+// longer than max_wait_ms; None waits indefinitely. QueueStatus keeps
+// position current from then on.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct QueuedMessage {
+    pub position: u32,
+    pub max_wait_ms: Option<u32>,
+}
+
+// Sent periodically to a connection waiting in the matchmaking queue.
+// position is 1-based within the queue for the requested game type.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct QueueStatusMessage {
+    pub position: u32,
+    pub waited_ms: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LobbyStatus {
+    // waiting for players
+    Open,
+    Full,
+    // every player has disconnected; the slots can still be reclaimed
+    Empty,
+    Removed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LobbyGame {
+    pub game_id: u32,
+    pub game_type: u8,
+    pub status: LobbyStatus,
+    // connected players, bots included
+    pub players: u8,
+    pub game_token: String,
+    // milliseconds since the Unix epoch
+    pub created_ms: u64,
+}
+
+// Pushed to queued connections that sent SubscribeLobby (empty payload)
+// whenever games of the type they queued for change. Only games whose status
+// changed since the last update are listed, and updates are batched so
+// bursts arrive together.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LobbyUpdateMessage {
+    pub games: Vec<LobbyGame>,
+}
+
+// Sent by a queued connection to play a named player waiting for the same
+// game type, usually one that connected with ?lobby=1 so the matchmaker
+// leaves it alone. Each side has at most one challenge pending.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChallengeMessage {
+    pub target_name: String,
+    // uneven rules for the game the challenge starts, the challenger on the
+    // left; None plays it even, or as the rematch of the two players' last
+    // handicapped challenge game
+    pub handicap: Option<HandicapConfig>,
+}
+
+impl ChallengeMessage {
+    // A Challenge from a client that predates handicaps is the name alone.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        return decode_payload::<ChallengeMessage>(payload).or_else(|| {
+            return Some(ChallengeMessage {
+                target_name: decode_payload::<String>(payload)?,
+                handicap: None,
+            });
+        });
+    }
+}
+
+// Pushed to the target of a Challenge. Unanswered after timeout_ms, it is
+// withdrawn with a ChallengeResult.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChallengeReceivedMessage {
+    pub challenge_id: u32,
+    pub from: String,
+    pub timeout_ms: u32,
+    // what the challenger asked for, clamped, so the target knows the rules
+    // before accepting; last so older clients still decode the rest
+    pub handicap: Option<HandicapConfig>,
+}
+
+// Smallest and largest a handicap multiplier is clamped to, and the most
+// goals a side can be given to start with.
+pub const MIN_HANDICAP: f32 = 0.25;
+pub const MAX_HANDICAP: f32 = 4.0;
+pub const MAX_HANDICAP_SCORE: u32 = 5;
+
+// One side's handicap: multipliers on the game's shot speed cap and move
+// cooldown, and goals it starts the match with.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TeamHandicap {
+    pub shot_speed: f32,
+    pub move_cooldown: f32,
+    pub score: u32,
+}
+
+impl Default for TeamHandicap {
+    fn default() -> Self {
+        return TeamHandicap {
+            shot_speed: 1.0,
+            move_cooldown: 1.0,
+            score: 0,
+        };
+    }
+}
+
+impl TeamHandicap {
+    // Multipliers within MIN_HANDICAP..=MAX_HANDICAP, one that isn't a
+    // number back at 1, and the score at most MAX_HANDICAP_SCORE.
+    pub fn clamped(self) -> Self {
+        let clamp = |multiplier: f32| {
+            if multiplier.is_nan() {
+                return 1.0;
+            }
+            return multiplier.clamp(MIN_HANDICAP, MAX_HANDICAP);
+        };
+        return TeamHandicap {
+            shot_speed: clamp(self.shot_speed),
+            move_cooldown: clamp(self.move_cooldown),
+            score: self.score.min(MAX_HANDICAP_SCORE),
+        };
+    }
+}
+
+// Uneven rules for a match between players of different strength, by
+// side: left is slot 0. swap_on_rematch has the next match between the same
+// players give each side the other's handicap instead of the same one.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct HandicapConfig {
+    pub left: TeamHandicap,
+    pub right: TeamHandicap,
+    pub swap_on_rematch: bool,
+}
+
+impl HandicapConfig {
+    pub fn clamped(self) -> Self {
+        return HandicapConfig {
+            left: self.left.clamped(),
+            right: self.right.clamped(),
+            swap_on_rematch: self.swap_on_rematch,
+        };
+    }
+
+    // The same rules for each player with the two on the other sides.
+    pub fn mirrored(self) -> Self {
+        return HandicapConfig {
+            left: self.right,
+            right: self.left,
+            swap_on_rematch: self.swap_on_rematch,
+        };
+    }
+
+    // The handicap a rematch between the same players is played with.
+    pub fn rematch(self) -> Self {
+        if !self.swap_on_rematch {
+            return self;
+        }
+        return self.mirrored();
+    }
+}
+
+// The target's answer. Accepting puts both players in a new game, which
+// each learns of through the usual Welcome.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ChallengeReplyMessage {
+    pub challenge_id: u32,
+    pub accept: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeOutcome {
+    // to the challenger: the target said no
+    Declined,
+    // to the challenger: no answer within the timeout
+    TimedOut,
+    // to the challenger: the target left or was placed in another game
+    // before answering; to a target that accepted: the challenger had
+    // already gone
+    Unavailable,
+    // to the target: the challenger left, timed out or was placed elsewhere
+    Withdrawn,
+}
+
+// How a challenge ended, when it didn't end in a game.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ChallengeResultMessage {
+    pub challenge_id: u32,
+    pub outcome: ChallengeOutcome,
+}
+
+// Asks the server to push State snapshots at this rate. The server clamps it
+// to its configured range; an empty payload selects the default rate.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SubscribeMessage {
+    pub state_rate_hz: u8,
+}
+
+// Admin only, on a connection opened with ?firehose=1: asks for a
+// MultiState batch of every running game at rate_hz, which the server caps
+// at its max_firehose_rate_hz. Sending it again changes the rate.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SubscribeAllMessage {
+    pub rate_hz: u8,
+}
+
+// One game's entry in a MultiState batch. snapshot is the game's own State
+// payload, as its players' generic State would carry it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSnapshot {
+    pub game_id: u32,
+    pub game_type: u8,
+    pub snapshot: Vec<u8>,
+}
+
+// Firehose batch of running games. Not bincode, so the server can copy each
+// game's cached snapshot straight in; all little-endian:
+//
+//   u16 game count, then per game:
+//     game_id u32, game_type u8, snapshot length u32, snapshot bytes
+//
+// A round that doesn't fit in one message is split across several.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiStateMessage {
+    pub games: Vec<GameSnapshot>,
+}
+
+const MULTI_STATE_ENTRY_HEADER: usize = 4 + 1 + 4;
+
+impl MultiStateMessage {
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let count = u16::from_le_bytes(payload.get(..2)?.try_into().ok()?) as usize;
+        let mut rest = &payload[2..];
+        let mut games = Vec::with_capacity(count.min(rest.len() / MULTI_STATE_ENTRY_HEADER));
+        for _ in 0..count {
+            let header = rest.get(..MULTI_STATE_ENTRY_HEADER)?;
+            let game_id = u32::from_le_bytes(header[0..4].try_into().ok()?);
+            let game_type = header[4];
+            let len = u32::from_le_bytes(header[5..9].try_into().ok()?) as usize;
+            let end = MULTI_STATE_ENTRY_HEADER.checked_add(len)?;
+            let snapshot = rest.get(MULTI_STATE_ENTRY_HEADER..end)?.to_vec();
+            rest = &rest[end..];
+            games.push(GameSnapshot {
+                game_id,
+                game_type,
+                snapshot,
+            });
+        }
+        return Some(MultiStateMessage { games });
+    }
+
+    // Whole MultiState frames for (game_id, game_type, snapshot) entries,
+    // starting a new frame whenever the next entry would take the payload
+    // past max_payload. An entry bigger than that on its own still goes
+    // out, alone in its frame.
+    pub fn frames<'a>(
+        entries: impl IntoIterator<Item = (u32, u8, &'a [u8])>,
+        max_payload: usize,
+    ) -> Vec<Vec<u8>> {
+        let mut frames = vec![];
+        let mut frame: Vec<u8> = vec![];
+        let mut count: u16 = 0;
+        for (game_id, game_type, snapshot) in entries {
+            let size = MULTI_STATE_ENTRY_HEADER + snapshot.len();
+            let payload_len = frame.len().saturating_sub(1);
+            if count > 0 && (payload_len + size > max_payload || count == u16::MAX) {
+                frame[1..3].copy_from_slice(&count.to_le_bytes());
+                frames.push(std::mem::take(&mut frame));
+                count = 0;
+            }
+            if count == 0 {
+                frame.push(MessageType::MultiState as u8);
+                frame.extend_from_slice(&[0, 0]);
+            }
+            frame.extend_from_slice(&game_id.to_le_bytes());
+            frame.push(game_type);
+            frame.extend_from_slice(&(snapshot.len() as u32).to_le_bytes());
+            frame.extend_from_slice(snapshot);
+            count += 1;
+        }
+        if count > 0 {
+            frame[1..3].copy_from_slice(&count.to_le_bytes());
+            frames.push(frame);
+        }
+        return frames;
+    }
+}
+
+// Clock alignment, NTP style. The client sends TimeSyncRequest stamped with
+// its own clock and the server answers straight away with TimeSyncResponse,
+// echoing that stamp next to its own clock and the game's current tick.
+// Both are plain little-endian u64s:
+//
+//   request:  client_time_us
+//   response: client_time_us, server_time_us, tick
+//
+// server_time_us counts from server start on a monotonic clock, the same
+// one that drives ticks, so it never jumps with wall-clock changes.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TimeSyncRequest {
+    pub client_time_us: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TimeSyncResponse {
+    pub client_time_us: u64,
+    pub server_time_us: u64,
+    pub tick: u64,
+}
+
+// The answer to an Echo, for checking a connection end to end without
+// touching any game. An Echo's payload is any bytes, up to the server's
+// max_echo_bytes; the reply is that payload verbatim followed by
+//
+//   u64 received_us, when the server read the Echo
+//   u64 sent_us, when it answered
+//
+// on the TimeSync clock and in the connection's byte order, so one round
+// trip shows the time spent each way and on the server. ?format=json
+// connections send UTF-8 text and get
+// {"payload": text, "received_us": n, "sent_us": n} back instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EchoReply {
+    pub payload: Vec<u8>,
+    pub received_us: u64,
+    pub sent_us: u64,
+}
+
+impl EchoReply {
+    pub fn to_bytes(&self, order: ByteOrder) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.payload.len() + 16);
+        data.extend_from_slice(&self.payload);
+        for stamp in [self.received_us, self.sent_us] {
+            match order {
+                ByteOrder::Little => data.extend_from_slice(&stamp.to_le_bytes()),
+                ByteOrder::Big => data.extend_from_slice(&stamp.to_be_bytes()),
+            }
+        }
+        return data;
+    }
+
+    pub fn from_bytes(data: &[u8], order: ByteOrder) -> Option<Self> {
+        let (payload, stamps) = data.split_at(data.len().checked_sub(16)?);
+        let (received, sent) = stamps.split_at(8);
+        let stamp = |bytes: &[u8]| {
+            let bytes: [u8; 8] = bytes.try_into().unwrap();
+            return match order {
+                ByteOrder::Little => u64::from_le_bytes(bytes),
+                ByteOrder::Big => u64::from_be_bytes(bytes),
+            };
+        };
+        return Some(EchoReply {
+            payload: payload.to_vec(),
+            received_us: stamp(received),
+            sent_us: stamp(sent),
+        });
+    }
+
+    // None when the payload isn't UTF-8.
+    pub fn to_json(&self) -> Option<String> {
+        let text = std::str::from_utf8(&self.payload).ok()?;
+        let json = serde_json::json!({
+            "payload": text,
+            "received_us": self.received_us,
+            "sent_us": self.sent_us,
+        });
+        return Some(json.to_string());
+    }
+}
+
+// Tunable soccer parameters. Absent fields are left as they are.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct GameParams {
+    pub puck_radius: Option<f32>,
+    pub puck_damping: Option<f32>,
+    pub puck_restitution: Option<f32>,
+    pub max_shot_speed: Option<f32>,
+    pub move_cooldown_ms: Option<u32>,
+}
+
+impl GameParams {
+    // (name, value) for every field, for logging changes
+    pub fn fields(&self) -> [(&'static str, Option<f32>); 5] {
+        return [
+            ("puck_radius", self.puck_radius),
+            ("puck_damping", self.puck_damping),
+            ("puck_restitution", self.puck_restitution),
+            ("max_shot_speed", self.max_shot_speed),
+            (
+                "move_cooldown_ms",
+                self.move_cooldown_ms.map(|cooldown| cooldown as f32),
+            ),
+        ];
+    }
+}
+
+// Admin only. Updates the config used for new games and, when game_id is
+// set, the safe-to-change parameters of that running game. The running
+// game's players get GameParamsChanged with its full parameter set. A
+// handicap, clamped, is for the soccer game game_id names alone, and only
+// before kickoff.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SetGameParamsMessage {
+    pub game_id: Option<u32>,
+    pub params: GameParams,
+    // last so older clients' requests still decode
+    pub handicap: Option<HandicapConfig>,
+}
+
+impl SetGameParamsMessage {
+    // A SetGameParams from a client that predates handicaps stops at params.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        return decode_payload::<SetGameParamsMessage>(payload).or_else(|| {
+            let (game_id, params) = decode_payload::<(Option<u32>, GameParams)>(payload)?;
+            return Some(SetGameParamsMessage {
+                game_id,
+                params,
+                handicap: None,
+            });
+        });
+    }
+}
+
+// Admin only. Replaces the soccer config new games are built from with
+// the one soccer describes: the body of a [soccer] config section, keys and
+// checks as in the config file, anything left out at its default. With
+// reapply every running soccer game takes the new tunables too, as far as
+// SoccerGame::retune can without a new world. Answered with SoccerTuned.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TuneSoccerMessage {
+    pub soccer: String,
+    pub reapply: bool,
+}
+
+// The soccer tuning version new games now get, and the running games that
+// were retuned to it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SoccerTunedMessage {
+    pub version: u64,
+    pub reapplied: Vec<u32>,
+}
+
+// Sent to the slot whose turn it now is in turn-based soccer, and to no one
+// else: its moves are taken for the next ticks updates.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TurnMessage {
+    pub player: u8,
+    pub ticks: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PlayerRecord {
+    pub wins: u32,
+    pub losses: u32,
+    pub goals_scored: u32,
+    pub goals_conceded: u32,
+    // of goals_conceded, the ones the player's own pucks put in
+    pub own_goals: u32,
+    // SlotStats summed over every recorded match, the average weighted by
+    // each match's shots
+    pub shots: u32,
+    pub avg_shot_speed: f32,
+    pub possession_ticks: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub record: PlayerRecord,
+}
+
+// Reply to GetStats: the requester's own record plus the top players by wins.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StatsResponse {
+    pub record: PlayerRecord,
+    pub leaderboard: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    // the collecting team's pucks grow for the effect's duration
+    BigPuck = 0,
+    // raises the collecting team's max shot speed for the effect's duration
+    SpeedBoost = 1,
+}
+
+impl PowerUpKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(PowerUpKind::BigPuck),
+            1 => Some(PowerUpKind::SpeedBoost),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpAction {
+    Spawned,
+    Collected,
+    Expired,
+}
+
+// Broadcast whenever a power-up appears on the field, is picked up, or its
+// effect runs out. `player` is the collecting slot for Collected and Expired.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PowerUpMessage {
+    pub id: u32,
+    pub kind: PowerUpKind,
+    pub action: PowerUpAction,
+    pub x: f32,
+    pub y: f32,
+    pub player: Option<u8>,
+}
+
+// A power-up lying on the field waiting to be collected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerUpState {
+    pub id: u32,
+    pub kind: PowerUpKind,
+    pub x: f32,
+    pub y: f32,
+}
+
+// A collected power-up whose effect is still running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    pub player: u8,
+    pub remaining_ms: u32,
+}
+
+// The v1 State payload, shared by the server's encoder and clients so the
+// two can't drift apart. The layout is stable:
+//
+//   (x, y) little-endian f32 per puck, then per ball
+//
+// There is no count or separator, so a payload is any whole number of
+// 8-byte pairs, at least one; which are balls is up to the reader.
+// SoccerStateSnapshot::from_bytes takes the last as the classic single ball.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatePayload {
+    pub positions: Vec<(f32, f32)>,
+}
+
+impl StatePayload {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.positions.len() * 8);
+        for (x, y) in &self.positions {
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+        return data;
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let pairs = decode_f32_groups(data, 2)?;
+        return Some(StatePayload {
+            positions: pairs.into_iter().map(|g| (g[0], g[1])).collect(),
+        });
+    }
+}
+
+// Decoded form of the State payload produced by SoccerGame::to_bytes:
+// one little-endian (x, y) f32 pair per puck followed by the balls, as laid
+// out by StatePayload. The v2
+// payload from to_bytes_v2 appends (angle, angvel) to every body, which ends
+// up in `spin` in the same order, and ends with a little-endian u32: the
+// seq of the receiving player's last applied SoccerMove. v1 snapshots leave
+// spin empty and ack_seq 0.
+//
+// v1 and v2 carry no counts, so their decoders assume the classic single
+// ball. The v3 payload from to_bytes_v3 spells everything out:
+//
+//   u8 puck count, u8 ball count
+//   (x, y, angle, angvel) f32 per puck, then per ball
+//   u8 count, then (u32 id, u8 kind, f32 x, f32 y) per power-up on the field
+//   u8 count, then (u8 kind, u8 player, u32 remaining_ms) per active effect
+//   u32 ack_seq
+//   u32 ms until the receiving player can Boost again, 0 when ready
+//
+// v4 puts a STATE_HEADER_LEN header in front of the v3 payload so clients
+// can order frames and spot drops:
+//
+//   u32 tick, counting the game's updates from 0
+//   u64 server_time_us, on the clock TimeSync reports
+//   u8 phase: 0 play, 1 goal scored, 2 kickoff; moves only count in play.
+//   3 is warm-up: a lone player knocking the ball around while the game
+//   waits for an opponent, where nothing counts
+//
+// Earlier versions leave all three 0.
+//
+// v5 follows the header with what a client predicting its own pucks needs
+// to reconcile against, as of the ack_seq further on:
+//
+//   u8 count, then (u8 target, f32 x, f32 y, f32 vx, f32 vy) per puck the
+//   receiving player controls, target being the index SoccerMove uses
+//
+// Earlier versions leave own_pucks empty.
+//
+// v7 puts one byte in front of the v5 payload, so a client can tell a game
+// still waiting for players from one being played:
+//
+//   u8 match phase, MatchPhase::code
+//
+// Earlier versions leave match_phase None.
+//
+// v8 replaces that byte with the SnapshotHeader every game type's State
+// starts with, and sets match_phase from it.
+//
+// Earlier versions leave header None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoccerStateSnapshot {
+    pub tick: u32,
+    pub server_time_us: u64,
+    pub phase: u8,
+    pub pucks: Vec<(f32, f32)>,
+    pub balls: Vec<(f32, f32)>,
+    pub spin: Vec<(f32, f32)>,
+    pub ack_seq: u32,
+    pub power_ups: Vec<PowerUpState>,
+    pub effects: Vec<ActiveEffect>,
+    pub boost_cooldown_ms: u32,
+    pub own_pucks: Vec<OwnPuck>,
+    pub match_phase: Option<MatchPhase>,
+    pub header: Option<SnapshotHeader>,
+}
+
+// Authoritative state of one of the receiving player's pucks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OwnPuck {
+    pub target: u8,
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+}
+
+pub const STATE_HEADER_LEN: usize = 13;
+
+// Put in front of every v8 State by the server, not the game, so any game
+// type's clients get the match's clock and score the same way:
+//
+//   u32 tick, counting the game's updates from 0
+//   u8 match phase, MatchPhase::code
+//   u16 deciseconds of match time left, UNTIMED without a match clock
+//   u8 team count, then u8 score per team, capped at 255
+//   v9: u8 DegradeLevel::code the connection is being sent at
+//
+// The game's own State body follows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    pub tick: u32,
+    pub phase: MatchPhase,
+    pub remaining_ds: Option<u16>,
+    pub scores: Vec<u8>,
+    // written when Some; read back by from_bytes_v9 only
+    pub level: Option<DegradeLevel>,
+}
+
+impl SnapshotHeader {
+    pub const UNTIMED: u16 = 0xFFFF;
+
+    // Little-endian; ByteOrder::swap_snapshot_header converts it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(8 + self.scores.len());
+        data.extend_from_slice(&self.tick.to_le_bytes());
+        data.push(self.phase.code());
+        let remaining_ds = self.remaining_ds.unwrap_or(SnapshotHeader::UNTIMED);
+        data.extend_from_slice(&remaining_ds.to_le_bytes());
+        data.push(self.scores.len() as u8);
+        data.extend_from_slice(&self.scores);
+        if let Some(level) = self.level {
+            data.push(level.code());
+        }
+        return data;
+    }
+
+    // The header at the front of a little-endian v8 State, and the game's
+    // body after it.
+    pub fn from_bytes(data: &[u8]) -> Option<(Self, &[u8])> {
+        let mut reader = ByteReader { data };
+        let tick = reader.u32()?;
+        let phase = MatchPhase::from_code(reader.u8()?)?;
+        let remaining_ds = Some(reader.u16()?).filter(|ds| *ds != SnapshotHeader::UNTIMED);
+        let teams = reader.u8()? as usize;
+        if reader.data.len() < teams {
+            return None;
+        }
+        let (scores, body) = reader.data.split_at(teams);
+        let header = SnapshotHeader {
+            tick,
+            phase,
+            remaining_ds,
+            scores: scores.to_vec(),
+            level: None,
+        };
+        return Some((header, body));
+    }
+
+    // The same at the front of a v9 State, level included.
+    pub fn from_bytes_v9(data: &[u8]) -> Option<(Self, &[u8])> {
+        let (mut header, body) = SnapshotHeader::from_bytes(data)?;
+        let (&level, body) = body.split_first()?;
+        header.level = Some(DegradeLevel::from_code(level)?);
+        return Some((header, body));
+    }
+
+    // from_bytes or from_bytes_v9, as protocol lays it out.
+    pub fn read(protocol: ProtocolVersion, data: &[u8]) -> Option<(Self, &[u8])> {
+        if protocol >= ProtocolVersion::V9 {
+            return SnapshotHeader::from_bytes_v9(data);
+        }
+        return SnapshotHeader::from_bytes(data);
+    }
+
+    // The level of a v9 header in either byte order, without converting
+    // it, to tell how the body after it is encoded.
+    pub fn peek_level(data: &[u8]) -> Option<DegradeLevel> {
+        let teams = *data.get(7)? as usize;
+        return DegradeLevel::from_code(*data.get(8 + teams)?);
+    }
+}
+
+// Narrows the sender's snapshots to some of its game's bodies, for a minimap
+// that only wants the ball, say. bodies is a mask of body indices in State
+// order, pucks then balls, bit 0 for the first puck; groups adds whole
+// InterestGroups, worked out again for every frame. Both 0 goes back to
+// every body, the default. A narrowed connection is sent PartialState
+// instead of State, until it sets full interest again or leaves its game.
+// Soccer only.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetInterestMessage {
+    pub bodies: u64,
+    pub groups: u8,
+}
+
+impl SetInterestMessage {
+    pub fn is_full(&self) -> bool {
+        return self.bodies == 0 && self.groups == 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterestGroup {
+    // the pucks the sender steers
+    MyPucks,
+    // every other puck
+    OpponentPucks,
+    Ball,
+}
+
+impl InterestGroup {
+    // its bit in SetInterestMessage::groups
+    pub fn bit(&self) -> u8 {
+        return match self {
+            InterestGroup::MyPucks => 1,
+            InterestGroup::OpponentPucks => 2,
+            InterestGroup::Ball => 4,
+        };
+    }
+}
+
+// The snapshot a connection that sent SetInterest gets, whatever its
+// protocol and format:
+//
+//   SnapshotHeader, as in front of a v8 State, or a v9 one on v9
+//   u64 interest: bit i set when body i, in State order, follows; only
+//   bodies the game has are set, so the count is the bits set
+//   (f32 x, f32 y) per body of interest, lowest index first
+//
+// In the connection's byte order, like State.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialState {
+    pub header: SnapshotHeader,
+    pub interest: u64,
+    pub positions: Vec<(f32, f32)>,
+}
+
+impl PartialState {
+    // Little-endian; ByteOrder::swap_partial_state converts it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.header.to_bytes();
+        data.extend_from_slice(&self.interest.to_le_bytes());
+        for (x, y) in &self.positions {
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&y.to_le_bytes());
+        }
+        return data;
+    }
+
+    // With the v8 header.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        return PartialState::decode(ProtocolVersion::V8, data);
+    }
+
+    pub fn decode(protocol: ProtocolVersion, data: &[u8]) -> Option<Self> {
+        let (header, body) = SnapshotHeader::read(protocol, data)?;
+        let mut reader = ByteReader { data: body };
+        let interest = reader.u64()?;
+        let mut positions = Vec::with_capacity(interest.count_ones() as usize);
+        for _ in 0..interest.count_ones() {
+            positions.push((reader.f32()?, reader.f32()?));
+        }
+        if !reader.data.is_empty() {
+            return None;
+        }
+        return Some(PartialState {
+            header,
+            interest,
+            positions,
+        });
+    }
+
+    // The position of body, an index in State order, when it is here.
+    pub fn body(&self, body: usize) -> Option<(f32, f32)> {
+        if body >= 64 || self.interest & (1 << body) == 0 {
+            return None;
+        }
+        let before = (self.interest & ((1 << body) - 1)).count_ones() as usize;
+        return self.positions.get(before).copied();
+    }
+}
+
+impl SoccerStateSnapshot {
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut pucks = StatePayload::decode(data)?.positions;
+        let ball = pucks.pop()?;
+        Some(SoccerStateSnapshot {
+            tick: 0,
+            server_time_us: 0,
+            phase: 0,
+            pucks,
+            balls: vec![ball],
+            spin: vec![],
+            ack_seq: 0,
+            power_ups: vec![],
+            effects: vec![],
+            boost_cooldown_ms: 0,
+            own_pucks: vec![],
+            match_phase: None,
+            header: None,
+        })
+    }
+
+    pub fn from_bytes_v2(data: &[u8]) -> Option<Self> {
+        let (data, ack) = data.split_at(data.len().checked_sub(4)?);
+        let ack_seq = u32::from_le_bytes(ack.try_into().ok()?);
+        let bodies = decode_f32_groups(data, 4)?;
+        let (ball, pucks) = bodies.split_last()?;
+        Some(SoccerStateSnapshot {
+            tick: 0,
+            server_time_us: 0,
+            phase: 0,
+            pucks: pucks.iter().map(|g| (g[0], g[1])).collect(),
+            balls: vec![(ball[0], ball[1])],
+            spin: bodies.iter().map(|g| (g[2], g[3])).collect(),
+            ack_seq,
+            power_ups: vec![],
+            effects: vec![],
+            boost_cooldown_ms: 0,
+            own_pucks: vec![],
+            match_phase: None,
+            header: None,
+        })
+    }
+
+    pub fn from_bytes_v3(data: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader { data };
+        let puck_count = reader.u8()? as usize;
+        let ball_count = reader.u8()? as usize;
+        let mut positions = Vec::with_capacity(puck_count + ball_count);
+        let mut spin = Vec::with_capacity(puck_count + ball_count);
+        for _ in 0..puck_count + ball_count {
+            positions.push((reader.f32()?, reader.f32()?));
+            spin.push((reader.f32()?, reader.f32()?));
+        }
+        let balls = positions.split_off(puck_count);
+        let mut power_ups = vec![];
+        for _ in 0..reader.u8()? {
+            power_ups.push(PowerUpState {
+                id: reader.u32()?,
+                kind: PowerUpKind::from_u8(reader.u8()?)?,
+                x: reader.f32()?,
+                y: reader.f32()?,
+            });
+        }
+        let mut effects = vec![];
+        for _ in 0..reader.u8()? {
+            effects.push(ActiveEffect {
+                kind: PowerUpKind::from_u8(reader.u8()?)?,
+                player: reader.u8()?,
+                remaining_ms: reader.u32()?,
+            });
+        }
+        let ack_seq = reader.u32()?;
+        let boost_cooldown_ms = reader.u32()?;
+        if !reader.data.is_empty() {
+            return None;
+        }
+        Some(SoccerStateSnapshot {
+            tick: 0,
+            server_time_us: 0,
+            phase: 0,
+            pucks: positions,
+            balls,
+            spin,
+            ack_seq,
+            power_ups,
+            effects,
+            boost_cooldown_ms,
+            own_pucks: vec![],
+            match_phase: None,
+            header: None,
+        })
+    }
+
+    pub fn from_bytes_v4(data: &[u8]) -> Option<Self> {
+        if data.len() < STATE_HEADER_LEN {
+            return None;
+        }
+        let (header, body) = data.split_at(STATE_HEADER_LEN);
+        let mut reader = ByteReader { data: header };
+        let tick = reader.u32()?;
+        let server_time_us = reader.u64()?;
+        let phase = reader.u8()?;
+        let mut snapshot = SoccerStateSnapshot::from_bytes_v3(body)?;
+        snapshot.tick = tick;
+        snapshot.server_time_us = server_time_us;
+        snapshot.phase = phase;
+        Some(snapshot)
+    }
+
+    pub fn from_bytes_v5(data: &[u8]) -> Option<Self> {
+        if data.len() < STATE_HEADER_LEN {
+            return None;
+        }
+        let (header, body) = data.split_at(STATE_HEADER_LEN);
+        let mut reader = ByteReader { data: body };
+        let mut own_pucks = vec![];
+        for _ in 0..reader.u8()? {
+            own_pucks.push(OwnPuck {
+                target: reader.u8()?,
+                x: reader.f32()?,
+                y: reader.f32()?,
+                vx: reader.f32()?,
+                vy: reader.f32()?,
+            });
+        }
+        let mut snapshot = SoccerStateSnapshot::from_bytes_v4(&[header, reader.data].concat())?;
+        snapshot.own_pucks = own_pucks;
+        Some(snapshot)
+    }
+
+    pub fn from_bytes_v7(data: &[u8]) -> Option<Self> {
+        let (&match_phase, body) = data.split_first()?;
+        let mut snapshot = SoccerStateSnapshot::from_bytes_v5(body)?;
+        snapshot.match_phase = Some(MatchPhase::from_code(match_phase)?);
+        Some(snapshot)
+    }
+
+    pub fn from_bytes_v8(data: &[u8]) -> Option<Self> {
+        let (header, body) = SnapshotHeader::from_bytes(data)?;
+        let mut snapshot = SoccerStateSnapshot::from_bytes_v5(body)?;
+        snapshot.match_phase = Some(header.phase);
+        snapshot.header = Some(header);
+        Some(snapshot)
+    }
+
+    pub fn from_bytes_v9(data: &[u8]) -> Option<Self> {
+        let (header, body) = SnapshotHeader::from_bytes_v9(data)?;
+        let mut snapshot = SoccerStateSnapshot::from_bytes_v5(body)?;
+        snapshot.match_phase = Some(header.phase);
+        snapshot.header = Some(header);
+        Some(snapshot)
+    }
+
+    // The payload of the Json serializer, for ?format=json connections.
+    // Bodies come with their velocities, which the snapshot has no room for;
+    // angle and angvel go in spin as in v2 and later. A null number, which
+    // is what JSON makes of NaN and infinity, reads as NaN.
+    pub fn from_json(data: &[u8]) -> Option<Self> {
+        let json: serde_json::Value = serde_json::from_slice(data).ok()?;
+        let number = |value: &serde_json::Value| -> Option<f32> {
+            if value.is_null() {
+                return Some(f32::NAN);
+            }
+            return value.as_f64().map(|value| value as f32);
+        };
+        let int = |value: &serde_json::Value| value.as_u64();
+        let state = &json["state"];
+        let bodies: Vec<&serde_json::Value> = state["pucks"]
+            .as_array()?
+            .iter()
+            .chain(state["balls"].as_array()?)
+            .collect();
+        let mut spin = vec![];
+        for body in &bodies {
+            spin.push((number(&body["angle"])?, number(&body["angvel"])?));
+        }
+        let position = |body: &serde_json::Value| Some((number(&body["x"])?, number(&body["y"])?));
+        let pucks = state["pucks"]
+            .as_array()?
+            .iter()
+            .map(position)
+            .collect::<Option<_>>()?;
+        let balls = state["balls"]
+            .as_array()?
+            .iter()
+            .map(position)
+            .collect::<Option<_>>()?;
+        let kind = |value: &serde_json::Value| match value.as_str()? {
+            "BigPuck" => Some(PowerUpKind::BigPuck),
+            "SpeedBoost" => Some(PowerUpKind::SpeedBoost),
+            _ => None,
+        };
+        let power_ups = state["power_ups"]
+            .as_array()?
+            .iter()
+            .map(|power_up| {
+                return Some(PowerUpState {
+                    id: int(&power_up["id"])? as u32,
+                    kind: kind(&power_up["kind"])?,
+                    x: number(&power_up["x"])?,
+                    y: number(&power_up["y"])?,
+                });
+            })
+            .collect::<Option<_>>()?;
+        let effects = state["effects"]
+            .as_array()?
+            .iter()
+            .map(|effect| {
+                return Some(ActiveEffect {
+                    kind: kind(&effect["kind"])?,
+                    player: int(&effect["player"])? as u8,
+                    remaining_ms: number(&effect["remaining_ms"])? as u32,
+                });
+            })
+            .collect::<Option<_>>()?;
+        let own_pucks = json["own_pucks"]
+            .as_array()?
+            .iter()
+            .map(|puck| {
+                return Some(OwnPuck {
+                    target: int(&puck["target"])? as u8,
+                    x: number(&puck["x"])?,
+                    y: number(&puck["y"])?,
+                    vx: number(&puck["vx"])?,
+                    vy: number(&puck["vy"])?,
+                });
+            })
+            .collect::<Option<_>>()?;
+        // the names phase_str gives the phase byte's values
+        let phase = match state["phase"].as_str()? {
+            "play" => 0,
+            "goal_scored" => 1,
+            "kickoff" => 2,
+            "warm_up" => 3,
+            _ => return None,
+        };
+        let match_phase = json["match_phase"].as_str()?;
+        let match_phase = (0..=u8::MAX)
+            .map_while(MatchPhase::from_code)
+            .find(|phase| phase.as_str() == match_phase)?;
+        return Some(SoccerStateSnapshot {
+            tick: int(&json["tick"])? as u32,
+            server_time_us: int(&json["server_time_us"])?,
+            phase,
+            pucks,
+            balls,
+            spin,
+            ack_seq: int(&json["ack_seq"])? as u32,
+            power_ups,
+            effects,
+            boost_cooldown_ms: int(&json["boost_cooldown_ms"])? as u32,
+            own_pucks,
+            match_phase: Some(match_phase),
+            header: None,
+        });
+    }
+
+    pub fn decode(protocol: ProtocolVersion, data: &[u8]) -> Option<Self> {
+        match protocol {
+            ProtocolVersion::V1 => SoccerStateSnapshot::from_bytes(data),
+            ProtocolVersion::V2 => SoccerStateSnapshot::from_bytes_v2(data),
+            ProtocolVersion::V3 => SoccerStateSnapshot::from_bytes_v3(data),
+            ProtocolVersion::V4 => SoccerStateSnapshot::from_bytes_v4(data),
+            ProtocolVersion::V5 | ProtocolVersion::V6 => SoccerStateSnapshot::from_bytes_v5(data),
+            ProtocolVersion::V7 => SoccerStateSnapshot::from_bytes_v7(data),
+            ProtocolVersion::V8 => SoccerStateSnapshot::from_bytes_v8(data),
+            ProtocolVersion::V9 | ProtocolVersion::V10 | ProtocolVersion::V11 => {
+                SoccerStateSnapshot::from_bytes_v9(data)
+            }
+        }
+    }
+
+    // A quantized State in the layout of protocol, v5 or later; see
+    // QUANTIZED_VELOCITY_SCALE.
+    pub fn decode_quantized(protocol: ProtocolVersion, data: &[u8]) -> Option<Self> {
+        return match protocol {
+            ProtocolVersion::V5 | ProtocolVersion::V6 => {
+                if data.len() < STATE_HEADER_LEN {
+                    return None;
+                }
+                let (header, body) = data.split_at(STATE_HEADER_LEN);
+                let mut reader = ByteReader { data: header };
+                let mut snapshot = SoccerStateSnapshot::from_quantized_body(body)?;
+                snapshot.tick = reader.u32()?;
+                snapshot.server_time_us = reader.u64()?;
+                snapshot.phase = reader.u8()?;
+                Some(snapshot)
+            }
+            ProtocolVersion::V7 => {
+                let (&match_phase, body) = data.split_first()?;
+                let mut snapshot =
+                    SoccerStateSnapshot::decode_quantized(ProtocolVersion::V5, body)?;
+                snapshot.match_phase = Some(MatchPhase::from_code(match_phase)?);
+                Some(snapshot)
+            }
+            ProtocolVersion::V8
+            | ProtocolVersion::V9
+            | ProtocolVersion::V10
+            | ProtocolVersion::V11 => {
+                let (header, body) = SnapshotHeader::read(protocol, data)?;
+                let mut snapshot =
+                    SoccerStateSnapshot::decode_quantized(ProtocolVersion::V5, body)?;
+                snapshot.match_phase = Some(header.phase);
+                snapshot.header = Some(header);
+                Some(snapshot)
+            }
+            _ => None,
+        };
+    }
+
+    fn from_quantized_body(data: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader { data };
+        let (half_width, half_height) = (reader.u16()?, reader.u16()?);
+        let position = |reader: &mut ByteReader| -> Option<(f32, f32)> {
+            let x = dequantize_position(reader.u16()?, half_width);
+            return Some((x, dequantize_position(reader.u16()?, half_height)));
+        };
+        let mut own_pucks = vec![];
+        for _ in 0..reader.u8()? {
+            let target = reader.u8()?;
+            let (x, y) = position(&mut reader)?;
+            own_pucks.push(OwnPuck {
+                target,
+                x,
+                y,
+                vx: dequantize(reader.i16()?, QUANTIZED_VELOCITY_SCALE),
+                vy: dequantize(reader.i16()?, QUANTIZED_VELOCITY_SCALE),
+            });
+        }
+        let puck_count = reader.u8()? as usize;
+        let ball_count = reader.u8()? as usize;
+        let mut positions = Vec::with_capacity(puck_count + ball_count);
+        let mut spin = Vec::with_capacity(puck_count + ball_count);
+        for _ in 0..puck_count + ball_count {
+            positions.push(position(&mut reader)?);
+            spin.push((
+                dequantize(reader.i16()?, QUANTIZED_ANGLE_SCALE),
+                dequantize(reader.i16()?, QUANTIZED_ANGVEL_SCALE),
+            ));
+        }
+        let balls = positions.split_off(puck_count);
+        let mut power_ups = vec![];
+        for _ in 0..reader.u8()? {
+            let id = reader.u32()?;
+            let kind = PowerUpKind::from_u8(reader.u8()?)?;
+            let (x, y) = position(&mut reader)?;
+            power_ups.push(PowerUpState { id, kind, x, y });
+        }
+        let mut effects = vec![];
+        for _ in 0..reader.u8()? {
+            effects.push(ActiveEffect {
+                kind: PowerUpKind::from_u8(reader.u8()?)?,
+                player: reader.u8()?,
+                remaining_ms: reader.u32()?,
+            });
+        }
+        let ack_seq = reader.u32()?;
+        let boost_cooldown_ms = reader.u32()?;
+        if !reader.data.is_empty() {
+            return None;
+        }
+        Some(SoccerStateSnapshot {
+            tick: 0,
+            server_time_us: 0,
+            phase: 0,
+            pucks: positions,
+            balls,
+            spin,
+            ack_seq,
+            power_ups,
+            effects,
+            boost_cooldown_ms,
+            own_pucks,
+            match_phase: None,
+            header: None,
+        })
+    }
+}
+
+// Quantized State, StateFormat::Quantized, keeps the headers of the v5 or
+// later layout it is sent in and replaces the f32 soccer body after them
+// with fixed point, a little over half the size:
+//
+//   u16 half width, u16 half height: the arena's extents, rounded up
+//   u8 count, then (u8 target, u16 x, u16 y, i16 vx, i16 vy) per puck the
+//   receiving player controls
+//   u8 puck count, u8 ball count
+//   (u16 x, u16 y, i16 angle, i16 angvel) per puck, then per ball
+//   u8 count, then (u32 id, u8 kind, u16 x, u16 y) per power-up
+//   u8 count, then (u8 kind, u8 player, u32 remaining_ms) per active effect
+//   u32 ack_seq
+//   u32 boost_cooldown_ms
+//
+// A position maps [-half, half] onto 0..=65535, so it comes back within
+// half / 65535 of what was sent, give or take f32 rounding: 0.005 units on
+// a 600x600 field. The i16s
+// are value * scale, so velocities come back within 1 / 16 unit/s, angles
+// within 0.00005 rad and angular velocities within 1 / 1024 rad/s. Anything
+// out of range saturates at the nearest end rather than wrapping, and NaN
+// is sent as a raw 0.
+pub const QUANTIZED_VELOCITY_SCALE: f32 = 8.0;
+pub const QUANTIZED_ANGLE_SCALE: f32 = i16::MAX as f32 / std::f32::consts::PI;
+pub const QUANTIZED_ANGVEL_SCALE: f32 = 512.0;
+const QUANTIZED_POSITION_STEPS: f32 = u16::MAX as f32;
+
+// Float to int casts saturate and send NaN to 0, which is the clamping the
+// layout promises.
+pub fn quantize_position(value: f32, half: u16) -> u16 {
+    let half = half.max(1) as f32;
+    return ((value + half) / (2.0 * half) * QUANTIZED_POSITION_STEPS).round() as u16;
+}
+
+pub fn dequantize_position(value: u16, half: u16) -> f32 {
+    let half = half.max(1) as f32;
+    return value as f32 / QUANTIZED_POSITION_STEPS * 2.0 * half - half;
+}
+
+pub fn quantize(value: f32, scale: f32) -> i16 {
+    return (value * scale).round() as i16;
+}
+
+pub fn dequantize(value: i16, scale: f32) -> f32 {
+    return value as f32 / scale;
+}
+
+// Little-endian cursor over a raw payload; every read fails once the data
+// runs out.
+struct ByteReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.data.len() < N {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(N);
+        self.data = rest;
+        return bytes.try_into().ok();
+    }
+    fn u8(&mut self) -> Option<u8> {
+        return Some(self.take::<1>()?[0]);
+    }
+    fn u16(&mut self) -> Option<u16> {
+        return Some(u16::from_le_bytes(self.take()?));
+    }
+    fn i16(&mut self) -> Option<i16> {
+        return Some(i16::from_le_bytes(self.take()?));
+    }
+    fn u32(&mut self) -> Option<u32> {
+        return Some(u32::from_le_bytes(self.take()?));
+    }
+    fn u64(&mut self) -> Option<u64> {
+        return Some(u64::from_le_bytes(self.take()?));
+    }
+    fn f32(&mut self) -> Option<f32> {
+        return Some(f32::from_le_bytes(self.take()?));
+    }
+}
+
+fn decode_f32_groups(data: &[u8], group: usize) -> Option<Vec<Vec<f32>>> {
+    let stride = group * 4;
+    if data.len() < stride || data.len() % stride != 0 {
+        return None;
+    }
+    return Some(
+        data.chunks_exact(stride)
+            .map(|chunk| {
+                chunk
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                    .collect()
+            })
+            .collect(),
+    );
+}
+
+// Wire dialects negotiated through the Sec-WebSocket-Protocol header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    V1 = 1,
+    V2 = 2,
+    // explicit ball and power-up counts for the multi-ball variant
+    V3 = 3,
+    // tick and server time ahead of every State payload
+    V4 = 4,
+    // the receiving player's own pucks, for client-side prediction
+    V5 = 5,
+    // v5 State; the client opens with Hello instead of passing format, byte
+    // order and firehose as query params
+    V6 = 6,
+    // v5 State led by the match phase; opens with Hello like v6
+    V7 = 7,
+    // every State, of any game type, led by a SnapshotHeader; v5 body for
+    // soccer. Opens with Hello like v6
+    V8 = 8,
+    // v8 with the connection's DegradeLevel closing the SnapshotHeader, a
+    // binary State quantized while it is Quantized or lower
+    V9 = 9,
+    // v9, with game broadcasts wrapped in a sequenced Event for EventsSince;
+    // earlier versions get the frames bare
+    V10 = 10,
+    // v10 plus GoalScored, which earlier versions aren't sent
+    V11 = 11,
+}
+
+impl ProtocolVersion {
+    // ordered from most to least preferred
+    pub const SUPPORTED: [ProtocolVersion; 11] = [
+        ProtocolVersion::V11,
+        ProtocolVersion::V10,
+        ProtocolVersion::V9,
+        ProtocolVersion::V8,
+        ProtocolVersion::V7,
+        ProtocolVersion::V6,
+        ProtocolVersion::V5,
+        ProtocolVersion::V4,
+        ProtocolVersion::V3,
+        ProtocolVersion::V2,
+        ProtocolVersion::V1,
+    ];
+
+    pub const MIN: ProtocolVersion = ProtocolVersion::V1;
+    pub const MAX: ProtocolVersion = ProtocolVersion::V11;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProtocolVersion::V1 => "asyncws.v1",
+            ProtocolVersion::V2 => "asyncws.v2",
+            ProtocolVersion::V3 => "asyncws.v3",
+            ProtocolVersion::V4 => "asyncws.v4",
+            ProtocolVersion::V5 => "asyncws.v5",
+            ProtocolVersion::V6 => "asyncws.v6",
+            ProtocolVersion::V7 => "asyncws.v7",
+            ProtocolVersion::V8 => "asyncws.v8",
+            ProtocolVersion::V9 => "asyncws.v9",
+            ProtocolVersion::V10 => "asyncws.v10",
+            ProtocolVersion::V11 => "asyncws.v11",
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        return ProtocolVersion::SUPPORTED
+            .iter()
+            .copied()
+            .find(|version| *version as u8 == value);
+    }
+
+    pub fn from_subprotocol(value: &str) -> Option<Self> {
+        return ProtocolVersion::SUPPORTED
+            .iter()
+            .copied()
+            .find(|version| version.as_str() == value.trim());
+    }
+
+    // Picks the highest supported version out of a comma separated
+    // Sec-WebSocket-Protocol header value.
+    pub fn negotiate(header: &str) -> Option<Self> {
+        return header
+            .split(',')
+            .filter_map(ProtocolVersion::from_subprotocol)
+            .max();
+    }
 }
@@ -0,0 +1,146 @@
+use crate::message::{ChatMessage, CloseReason, MessageType, WsMessage};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+// What a middleware decided about one message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MiddlewareDecision {
+    // hand it to the next middleware, and then on as usual
+    Continue,
+    // swallow it; the connection carries on
+    Drop,
+    // swallow it and close the connection with reason
+    Close(CloseReason),
+}
+
+// Hooks an embedding application can put on every connection, to veto or
+// rewrite messages without touching the connection loop. Inbound messages
+// are seen right after they are decoded and before the server acts on them;
+// outbound ones right before they are written, after any queueing. Both
+// default to Continue so a middleware only implements the side it needs.
+//
+// These run on the connection's task for every message, so they must not
+// block.
+pub trait ConnectionMiddleware: Send + Sync {
+    fn on_inbound(&self, _ctx: &ConnCtx, _msg: &WsMessage) -> MiddlewareDecision {
+        return MiddlewareDecision::Continue;
+    }
+    fn on_outbound(&self, _ctx: &ConnCtx, _msg: &mut WsMessage) -> MiddlewareDecision {
+        return MiddlewareDecision::Continue;
+    }
+}
+
+// The connection a middleware is looking at. game_id is None until the
+// connection is placed in a game, and again while it waits in the queue.
+#[derive(Debug, Clone)]
+pub struct ConnCtx {
+    client_id: usize,
+    name: Option<String>,
+    game_id: Option<usize>,
+    peer: IpAddr,
+}
+
+impl ConnCtx {
+    pub fn new(client_id: usize, peer: IpAddr) -> Self {
+        return ConnCtx {
+            client_id,
+            name: None,
+            game_id: None,
+            peer,
+        };
+    }
+
+    pub fn client_id(&self) -> usize {
+        return self.client_id;
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        return self.name.as_deref();
+    }
+
+    pub fn game_id(&self) -> Option<usize> {
+        return self.game_id;
+    }
+
+    pub fn peer(&self) -> IpAddr {
+        return self.peer;
+    }
+}
+
+impl Default for ConnCtx {
+    fn default() -> Self {
+        return ConnCtx::new(0, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+}
+
+// The middleware a connection was opened with, in the order they were
+// registered, and the context they are shown. The first decision other
+// than Continue wins and the rest don't see the message.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    ctx: ConnCtx,
+    stack: Arc<[Arc<dyn ConnectionMiddleware>]>,
+}
+
+impl MiddlewareChain {
+    pub fn new(stack: Arc<[Arc<dyn ConnectionMiddleware>]>, ctx: ConnCtx) -> Self {
+        return MiddlewareChain { ctx, stack };
+    }
+
+    pub fn ctx(&self) -> &ConnCtx {
+        return &self.ctx;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.stack.is_empty();
+    }
+
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.ctx.name = name;
+    }
+
+    pub fn set_game(&mut self, game_id: Option<usize>) {
+        self.ctx.game_id = game_id;
+    }
+
+    pub fn inbound(&self, msg: &WsMessage) -> MiddlewareDecision {
+        for middleware in self.stack.iter() {
+            match middleware.on_inbound(&self.ctx, msg) {
+                MiddlewareDecision::Continue => (),
+                decision => return decision,
+            }
+        }
+        return MiddlewareDecision::Continue;
+    }
+
+    pub fn outbound(&self, msg: &mut WsMessage) -> MiddlewareDecision {
+        for middleware in self.stack.iter() {
+            match middleware.on_outbound(&self.ctx, msg) {
+                MiddlewareDecision::Continue => (),
+                decision => return decision,
+            }
+        }
+        return MiddlewareDecision::Continue;
+    }
+}
+
+// Drops chat lines longer than max_chars before the server sees them, for a
+// tighter limit than MAX_CHAT_LEN. Unlike the server's own check the sender
+// isn't told; the line just never appears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChatLengthFilter {
+    pub max_chars: usize,
+}
+
+impl ConnectionMiddleware for ChatLengthFilter {
+    fn on_inbound(&self, _ctx: &ConnCtx, msg: &WsMessage) -> MiddlewareDecision {
+        if !matches!(msg.msg_type, MessageType::Chat) {
+            return MiddlewareDecision::Continue;
+        }
+        return match msg.decode::<ChatMessage>() {
+            Some(chat) if chat.text.chars().count() > self.max_chars => MiddlewareDecision::Drop,
+            // a Chat that doesn't decode is the server's to reject
+            _ => MiddlewareDecision::Continue,
+        };
+    }
+}
@@ -0,0 +1,81 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+
+// How urgently a frame has to reach its client. Callers pick one for every
+// frame they queue, so a new message type decides where it belongs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    // replies, acks, pings and game events, in the order they were queued;
+    // a client that lets CONTROL_CAPACITY of these pile up is dropped
+    Control,
+    // State snapshots; a newer one replaces any still waiting
+    State,
+    // chat and replay bursts; the oldest are dropped past BULK_CAPACITY
+    Bulk,
+}
+
+pub const CONTROL_CAPACITY: usize = 256;
+pub const BULK_CAPACITY: usize = 64;
+
+// The control queue is full; the connection can't keep up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overflow;
+
+// Frames waiting for one connection's writer. Everything at Control goes
+// out before the pending snapshot, and that before any Bulk frame, so a
+// replay burst or a chat backlog never holds up an ack.
+#[derive(Default)]
+pub struct Outbox {
+    control: VecDeque<Bytes>,
+    state: Option<Bytes>,
+    bulk: VecDeque<Bytes>,
+    // snapshots replaced and bulk frames dropped before they were written
+    pub discarded: u64,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        return Outbox::default();
+    }
+
+    pub fn push(&mut self, priority: Priority, frame: Bytes) -> Result<(), Overflow> {
+        match priority {
+            Priority::Control => {
+                if self.control.len() >= CONTROL_CAPACITY {
+                    return Err(Overflow);
+                }
+                self.control.push_back(frame);
+            }
+            Priority::State => {
+                if self.state.replace(frame).is_some() {
+                    self.discarded += 1;
+                }
+            }
+            Priority::Bulk => {
+                if self.bulk.len() >= BULK_CAPACITY {
+                    self.bulk.pop_front();
+                    self.discarded += 1;
+                }
+                self.bulk.push_back(frame);
+            }
+        }
+        return Ok(());
+    }
+
+    // The next frame to write, highest priority first.
+    pub fn pop(&mut self) -> Option<Bytes> {
+        return self
+            .control
+            .pop_front()
+            .or_else(|| self.state.take())
+            .or_else(|| self.bulk.pop_front());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.control.is_empty() && self.state.is_none() && self.bulk.is_empty();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.control.len() + self.state.is_some() as usize + self.bulk.len();
+    }
+}
@@ -0,0 +1,490 @@
+use crate::events::ServerEvent;
+use crate::game::{BodyState, Game, GameRng, SoccerGame};
+use crate::matchmaking::Owner;
+use crate::message::{HandicapConfig, PlayerRecord};
+use crate::server::{configure_game, ServerState};
+use crate::stats::{PlayerId, StatsStore};
+use crate::timers::GameTimerId;
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// Bumped whenever Snapshot or anything saved in it changes shape; a file
+// from a version load doesn't know is refused rather than misread.
+const SNAPSHOT_VERSION: u8 = 6;
+
+// What survives a restart: every leaderboard record, a summary of the games
+// that were running, and the last game id handed out, which the next run
+// counts on from so ids from both runs never collide. With
+// game_state_interval set the soccer games' bodies are kept too, and those
+// games are resumed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub records: Vec<SavedRecord>,
+    pub games: Vec<SavedGame>,
+    pub resumable: Vec<SavedMatch>,
+    pub last_game_id: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedRecord {
+    pub id: PlayerId,
+    pub name: String,
+    pub record: PlayerRecord,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub id: usize,
+    pub game_type: u8,
+    pub seed: u64,
+    pub players: Vec<String>,
+    // per team, for game types that keep score
+    pub scores: Vec<u32>,
+}
+
+// A soccer game as it stood at the last save, enough to rebuild it and let
+// its players back into their slots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedMatch {
+    pub id: usize,
+    pub game_type: u8,
+    pub seed: u64,
+    pub token: String,
+    pub practice: bool,
+    pub slots: Vec<SavedSlot>,
+    pub scores: Vec<u32>,
+    pub bodies: Vec<BodyState>,
+    // pending timers with the ticks each had left
+    pub timers: Vec<(GameTimerId, u64)>,
+    // what the game was set up with, so the resumed one plays by the same
+    // rules and its scores, which include the head start, still add up
+    pub handicap: Option<HandicapConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedSlot {
+    pub id: PlayerId,
+    pub name: String,
+    pub session_token: String,
+    pub bot: bool,
+}
+
+// A SavedRecord from before PlayerRecord counted own goals, which version 1
+// holds, and version 2 until own goals were added without a new number.
+// bincode lays the nested record out flat, so its fields follow name
+// directly.
+#[derive(Deserialize)]
+struct LegacyRecord {
+    id: PlayerId,
+    name: String,
+    wins: u32,
+    losses: u32,
+    goals_scored: u32,
+    goals_conceded: u32,
+}
+
+impl From<LegacyRecord> for SavedRecord {
+    fn from(legacy: LegacyRecord) -> Self {
+        return SavedRecord {
+            id: legacy.id,
+            name: legacy.name,
+            record: PlayerRecord {
+                wins: legacy.wins,
+                losses: legacy.losses,
+                goals_scored: legacy.goals_scored,
+                goals_conceded: legacy.goals_conceded,
+                ..PlayerRecord::default()
+            },
+        };
+    }
+}
+
+// A SavedRecord with own goals but from before shots and possession, which
+// the rest of version 2 holds.
+#[derive(Deserialize)]
+struct OwnGoalsRecord {
+    id: PlayerId,
+    name: String,
+    wins: u32,
+    losses: u32,
+    goals_scored: u32,
+    goals_conceded: u32,
+    own_goals: u32,
+}
+
+impl From<OwnGoalsRecord> for SavedRecord {
+    fn from(legacy: OwnGoalsRecord) -> Self {
+        return SavedRecord {
+            id: legacy.id,
+            name: legacy.name,
+            record: PlayerRecord {
+                wins: legacy.wins,
+                losses: legacy.losses,
+                goals_scored: legacy.goals_scored,
+                goals_conceded: legacy.goals_conceded,
+                own_goals: legacy.own_goals,
+                ..PlayerRecord::default()
+            },
+        };
+    }
+}
+
+fn upgrade<R: Into<SavedRecord>>(records: Vec<R>) -> Vec<SavedRecord> {
+    return records.into_iter().map(R::into).collect();
+}
+
+// Version 2 files come in both record layouts under the one number. Read
+// strictly, only the right layout uses up the whole file, so each is tried
+// in turn.
+fn load_v2(rest: &[u8]) -> bincode::Result<(Vec<SavedRecord>, Vec<SavedGame>, Vec<LegacyMatch>)> {
+    let strict = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes();
+    return strict
+        .deserialize::<(Vec<OwnGoalsRecord>, Vec<SavedGame>, Vec<LegacyMatch>)>(rest)
+        .map(|(records, games, resumable)| (upgrade(records), games, resumable))
+        .or_else(|_| {
+            strict
+                .deserialize::<(Vec<LegacyRecord>, Vec<SavedGame>, Vec<LegacyMatch>)>(rest)
+                .map(|(records, games, resumable)| (upgrade(records), games, resumable))
+        });
+}
+
+// A SavedMatch from before games had timers, which versions 2 and 3 hold.
+#[derive(Deserialize)]
+struct LegacyMatch {
+    id: usize,
+    game_type: u8,
+    seed: u64,
+    token: String,
+    practice: bool,
+    slots: Vec<SavedSlot>,
+    scores: Vec<u32>,
+    bodies: Vec<BodyState>,
+}
+
+impl From<LegacyMatch> for SavedMatch {
+    fn from(legacy: LegacyMatch) -> Self {
+        return SavedMatch {
+            id: legacy.id,
+            game_type: legacy.game_type,
+            seed: legacy.seed,
+            token: legacy.token,
+            practice: legacy.practice,
+            slots: legacy.slots,
+            scores: legacy.scores,
+            bodies: legacy.bodies,
+            timers: vec![],
+            handicap: None,
+        };
+    }
+}
+
+// A SavedMatch from before handicaps were kept, which versions 4 and 5
+// hold. Those games were resumed even, so they still are.
+#[derive(Deserialize)]
+struct TimedMatch {
+    id: usize,
+    game_type: u8,
+    seed: u64,
+    token: String,
+    practice: bool,
+    slots: Vec<SavedSlot>,
+    scores: Vec<u32>,
+    bodies: Vec<BodyState>,
+    timers: Vec<(GameTimerId, u64)>,
+}
+
+impl From<TimedMatch> for SavedMatch {
+    fn from(legacy: TimedMatch) -> Self {
+        return SavedMatch {
+            id: legacy.id,
+            game_type: legacy.game_type,
+            seed: legacy.seed,
+            token: legacy.token,
+            practice: legacy.practice,
+            slots: legacy.slots,
+            scores: legacy.scores,
+            bodies: legacy.bodies,
+            timers: legacy.timers,
+            handicap: None,
+        };
+    }
+}
+
+fn upgrade_matches<M: Into<SavedMatch>>(matches: Vec<M>) -> Vec<SavedMatch> {
+    return matches.into_iter().map(M::into).collect();
+}
+
+impl Snapshot {
+    pub fn restore_stats(&self, stats: &mut StatsStore) {
+        for saved in &self.records {
+            stats.insert(saved.id.clone(), &saved.name, saved.record.clone());
+        }
+    }
+
+    // files from before the counter was kept only have the running games'
+    // ids to go by
+    pub fn last_game_id(&self) -> usize {
+        let running = self.games.iter().map(|game| game.id).max().unwrap_or(0);
+        return self.last_game_id.max(running);
+    }
+}
+
+// Copies the leaderboard and game summaries out of a running server. Each
+// game is locked on its own, never while holding the games map.
+pub async fn capture(state: &ServerState) -> Snapshot {
+    let records = state
+        .stats
+        .read()
+        .await
+        .iter()
+        .map(|(id, name, record)| SavedRecord {
+            id: id.clone(),
+            name: name.to_string(),
+            record: record.clone(),
+        })
+        .collect();
+    let games: Vec<_> = state
+        .games
+        .read()
+        .await
+        .iter()
+        .map(|(id, game)| (*id, Arc::clone(game)))
+        .collect();
+    let with_bodies = state.config().game_state_interval.is_some();
+    let mut saved = Vec::with_capacity(games.len());
+    let mut resumable = vec![];
+    for (id, game) in games {
+        let game = game.read().await;
+        let soccer = game.downcast::<SoccerGame>();
+        let scores = soccer.map_or(vec![], |soccer| {
+            soccer.teams.iter().map(|t| t.score).collect()
+        });
+        if let Some(soccer) = soccer.filter(|_| with_bodies) {
+            resumable.push(SavedMatch {
+                id,
+                game_type: game.game_type,
+                seed: game.seed,
+                token: game.token.clone(),
+                practice: game.practice,
+                slots: game
+                    .players
+                    .iter()
+                    .map(|p| SavedSlot {
+                        id: p.id.clone(),
+                        name: p.name.clone(),
+                        session_token: game.session_token(p.index).unwrap_or_default().to_string(),
+                        bot: p.bot,
+                    })
+                    .collect(),
+                scores: scores.clone(),
+                bodies: soccer.body_states(),
+                timers: game.timers.remaining(game.tick()),
+                handicap: soccer.handicap,
+            });
+        }
+        saved.push(SavedGame {
+            id,
+            game_type: game.game_type,
+            seed: game.seed,
+            players: game.players.iter().map(|p| p.name.clone()).collect(),
+            scores,
+        });
+    }
+    return Snapshot {
+        records,
+        games: saved,
+        resumable,
+        last_game_id: state.last_game_id.load(Ordering::Relaxed),
+    };
+}
+
+// Rebuilds saved matches as the server starts. Each comes back under its
+// old id, token and seed with its bodies where they were, and its players
+// get their slots back by reconnecting as they would after a dropped
+// connection. Returns how many were resumed.
+pub async fn resume(state: &ServerState, saved: Vec<SavedMatch>) -> usize {
+    let mut resumed = 0;
+    for saved in &saved {
+        let logic = match state.create_logic(saved.game_type, saved.practice) {
+            Some(logic) => logic,
+            None => {
+                eprintln!("Game {} has an unknown type and isn't resumed", saved.id);
+                continue;
+            }
+        };
+        let players = saved
+            .slots
+            .iter()
+            .map(|slot| (slot.id.clone(), slot.name.clone()))
+            .collect();
+        let mut game = Game::with_logic(logic, players);
+        game.practice = saved.practice;
+        configure_game(&mut game, state, &state.config());
+        game.seed = saved.seed;
+        game.rng = GameRng::new(saved.seed);
+        game.logic.reseed(saved.seed);
+        game.token = saved.token.clone();
+        // the handicap first, since it sets the scores restore puts back
+        let restored = game.downcast_mut::<SoccerGame>().map_or(false, |soccer| {
+            if let Some(handicap) = saved.handicap {
+                soccer.set_handicap(handicap);
+            }
+            return soccer.restore(&saved.bodies, &saved.scores);
+        });
+        if !restored {
+            eprintln!(
+                "Game {} was saved from a different arena and isn't resumed",
+                saved.id
+            );
+            continue;
+        }
+        for (id, ticks_left) in &saved.timers {
+            game.timers.schedule_at(game.tick() + ticks_left, *id);
+        }
+        for (index, slot) in saved.slots.iter().enumerate() {
+            game.restore_slot(index, slot.session_token.clone(), slot.bot);
+            if !slot.bot {
+                state.open_slots.insert(slot.id.clone(), saved.id);
+            }
+        }
+        // counted against max_games_per_identity as before the restart,
+        // never refused for it; guests' addresses aren't saved, so only
+        // signed-in players are
+        let owners: Vec<Owner> = saved
+            .slots
+            .iter()
+            .filter(|slot| !slot.bot && !matches!(slot.id, PlayerId::Guest(_)))
+            .map(|slot| Owner {
+                id: slot.id.clone(),
+                ip: None,
+            })
+            .collect();
+        let _ = state.game_owners.claim(&owners, saved.id, None);
+        let created_at = game.created_at;
+        let game = Arc::new(RwLock::new(game));
+        state.games.write().await.insert(saved.id, game);
+        state.emit(ServerEvent::GameCreated {
+            game_id: saved.id,
+            created_at,
+        });
+        resumed += 1;
+    }
+    return resumed;
+}
+
+// Writes to a temporary file next to path and renames it over, so a crash
+// mid-write leaves the previous snapshot intact.
+pub fn save(path: &Path, snapshot: &Snapshot) -> io::Result<()> {
+    let mut data = vec![SNAPSHOT_VERSION];
+    bincode::serialize_into(&mut data, snapshot)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    return std::fs::rename(&tmp, path);
+}
+
+// None when there is no snapshot yet.
+pub fn load(path: &Path) -> io::Result<Option<Snapshot>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    return match data.split_first() {
+        Some((&SNAPSHOT_VERSION, rest)) => bincode::deserialize(rest)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        // the fifth didn't keep handicaps
+        Some((&5, rest)) => bincode::deserialize(rest)
+            .map(
+                |(records, games, resumable, last_game_id): (_, _, Vec<TimedMatch>, _)| {
+                    Some(Snapshot {
+                        records,
+                        games,
+                        resumable: upgrade_matches(resumable),
+                        last_game_id,
+                    })
+                },
+            )
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        // the fourth didn't keep the game id counter either
+        Some((&4, rest)) => bincode::deserialize(rest)
+            .map(|(records, games, resumable): (_, _, Vec<TimedMatch>)| {
+                Some(Snapshot {
+                    records,
+                    games,
+                    resumable: upgrade_matches(resumable),
+                    last_game_id: 0,
+                })
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        // the third had no timers
+        Some((&3, rest)) => bincode::deserialize(rest)
+            .map(|(records, games, resumable): (_, _, Vec<LegacyMatch>)| {
+                Some(Snapshot {
+                    records,
+                    games,
+                    resumable: upgrade_matches(resumable),
+                    last_game_id: 0,
+                })
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Some((&2, rest)) => load_v2(rest)
+            .map(|(records, games, resumable)| {
+                Some(Snapshot {
+                    records,
+                    games,
+                    resumable: upgrade_matches(resumable),
+                    last_game_id: 0,
+                })
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        // the first version had no resumable matches
+        Some((&1, rest)) => bincode::deserialize(rest)
+            .map(|(records, games): (Vec<LegacyRecord>, _)| {
+                Some(Snapshot {
+                    records: upgrade(records),
+                    games,
+                    resumable: vec![],
+                    last_game_id: 0,
+                })
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown snapshot version",
+        )),
+    };
+}
+
+pub async fn save_state(state: &ServerState) {
+    let path = match state.config().persist_path.clone() {
+        Some(path) => path,
+        None => return,
+    };
+    let snapshot = capture(state).await;
+    let (records, games) = (snapshot.records.len(), snapshot.games.len());
+    // encoding and writing a big leaderboard can take a while; it runs on a
+    // blocking thread so the tick loop and connections aren't held up
+    let saved = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || save(&path, &snapshot)).await
+    };
+    match saved {
+        Ok(Ok(())) => println!(
+            "Saved {} records and {} games to {}",
+            records,
+            games,
+            path.display()
+        ),
+        Ok(Err(e)) => eprintln!("Failed to save {}: {}", path.display(), e),
+        Err(e) => eprintln!("Failed to save {}: {}", path.display(), e),
+    }
+}
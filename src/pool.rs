@@ -0,0 +1,69 @@
+use crate::game::{GameLogic, SoccerGame, SoccerGameConfig};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub const SOCCER_POOL_SIZE: usize = 8;
+
+// Soccer worlds from removed games, reset and waiting to host a new one, so
+// a busy server doesn't rebuild the pipeline and every body for each match.
+// Only a world built from the same config as the new game is reused.
+#[derive(Default)]
+pub struct SoccerPool {
+    worlds: Mutex<VecDeque<Box<dyn GameLogic>>>,
+    // creations served from the pool and built from scratch
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+}
+
+impl SoccerPool {
+    // A pooled world built from config, if there is one still clean. None
+    // means the caller builds its own.
+    pub fn take(&self, config: &SoccerGameConfig) -> Option<Box<dyn GameLogic>> {
+        let mut worlds = self.worlds.lock().unwrap();
+        while let Some(index) = worlds
+            .iter()
+            .position(|world| soccer(world.as_ref()).config() == config)
+        {
+            let world = worlds.remove(index).unwrap();
+            if soccer(world.as_ref()).is_clean() {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Some(world);
+            }
+            eprintln!("Dropped a pooled soccer world that wasn't clean");
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+
+    // Resets the logic of a removed game and keeps it if it is soccer. Past
+    // capacity the oldest world goes, which is also how worlds built from
+    // a config nobody asks for any more leave.
+    pub fn put(&self, mut logic: Box<dyn GameLogic>, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        match logic.as_any_mut().downcast_mut::<SoccerGame>() {
+            Some(soccer_game) => soccer_game.reset(),
+            None => return,
+        }
+        let mut worlds = self.worlds.lock().unwrap();
+        while worlds.len() >= capacity {
+            worlds.pop_front();
+        }
+        worlds.push_back(logic);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.worlds.lock().unwrap().len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
+
+// Everything in the pool is soccer; put checks on the way in.
+fn soccer(world: &dyn GameLogic) -> &SoccerGame {
+    return world.as_any().downcast_ref::<SoccerGame>().unwrap();
+}
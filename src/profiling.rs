@@ -0,0 +1,167 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Rolling window of recent tick durations, total and per phase. Recording
+// only writes into preallocated rings, so it's cheap enough to call every
+// tick; percentiles are computed when someone asks for them.
+pub struct TickProfiler {
+    total: Window,
+    input: Window,
+    step: Window,
+    encode: Window,
+    broadcast: Window,
+    budget: Duration,
+    slow_ticks: u64,
+    worst_game: Option<(usize, Duration)>,
+}
+
+// Where a tick's game updates spent their time, summed over every game.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimes {
+    // queued commands, timers and lockstep inputs
+    pub input: Duration,
+    // the logic's update
+    pub step: Duration,
+    // State payloads encoded for connections since the previous update
+    pub encode: Duration,
+    // events and logic messages handed to connections
+    pub broadcast: Duration,
+}
+
+impl PhaseTimes {
+    pub fn add(&mut self, other: PhaseTimes) {
+        self.input += other.input;
+        self.step += other.step;
+        self.encode += other.encode;
+        self.broadcast += other.broadcast;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseSummary {
+    pub input: Percentiles,
+    pub step: Percentiles,
+    pub encode: Percentiles,
+    pub broadcast: Percentiles,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TickSummary {
+    pub samples: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    pub phases: PhaseSummary,
+    pub budget: Duration,
+    pub slow_ticks: u64,
+    // slowest single game update seen in the last slow tick
+    pub worst_game: Option<(usize, Duration)>,
+}
+
+pub type TickStats = Arc<Mutex<TickProfiler>>;
+
+struct Window {
+    samples: Vec<Duration>,
+    next: usize,
+    filled: bool,
+}
+
+impl Window {
+    fn new(len: usize) -> Self {
+        return Window {
+            samples: vec![Duration::ZERO; len.max(1)],
+            next: 0,
+            filled: false,
+        };
+    }
+
+    fn record(&mut self, sample: Duration) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % self.samples.len();
+        self.filled |= self.next == 0;
+    }
+
+    fn len(&self) -> usize {
+        if self.filled {
+            return self.samples.len();
+        }
+        return self.next;
+    }
+
+    fn percentiles(&self) -> Percentiles {
+        let mut sorted = self.samples[..self.len()].to_vec();
+        sorted.sort();
+        let percentile = |p: usize| -> Duration {
+            if sorted.is_empty() {
+                return Duration::ZERO;
+            }
+            return sorted[(sorted.len() - 1) * p / 100];
+        };
+        return Percentiles {
+            p50: percentile(50),
+            p95: percentile(95),
+            max: sorted.last().copied().unwrap_or_default(),
+        };
+    }
+}
+
+impl TickProfiler {
+    pub fn new(window: usize, budget: Duration) -> Self {
+        return TickProfiler {
+            total: Window::new(window),
+            input: Window::new(window),
+            step: Window::new(window),
+            encode: Window::new(window),
+            broadcast: Window::new(window),
+            budget,
+            slow_ticks: 0,
+            worst_game: None,
+        };
+    }
+
+    // Returns true when the tick went over budget.
+    pub fn record(
+        &mut self,
+        tick: Duration,
+        phases: PhaseTimes,
+        worst_game: Option<(usize, Duration)>,
+    ) -> bool {
+        self.total.record(tick);
+        self.input.record(phases.input);
+        self.step.record(phases.step);
+        self.encode.record(phases.encode);
+        self.broadcast.record(phases.broadcast);
+        if tick <= self.budget {
+            return false;
+        }
+        self.slow_ticks += 1;
+        self.worst_game = worst_game;
+        return true;
+    }
+
+    pub fn summary(&self) -> TickSummary {
+        let total = self.total.percentiles();
+        return TickSummary {
+            samples: self.total.len(),
+            p50: total.p50,
+            p95: total.p95,
+            max: total.max,
+            phases: PhaseSummary {
+                input: self.input.percentiles(),
+                step: self.step.percentiles(),
+                encode: self.encode.percentiles(),
+                broadcast: self.broadcast.percentiles(),
+            },
+            budget: self.budget,
+            slow_ticks: self.slow_ticks,
+            worst_game: self.worst_game,
+        };
+    }
+}
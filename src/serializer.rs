@@ -0,0 +1,202 @@
+use crate::game::SoccerGame;
+use crate::http;
+use crate::message::{ByteOrder, MatchPhase, ProtocolVersion, STATE_HEADER_LEN};
+
+// Turns a soccer game into a State payload. Each connection picks a format
+// with ?format=, so the wire encoding stays out of the simulation.
+pub trait StateSerializer: Send + Sync {
+    fn encode(&self, game: &SoccerGame, view: &StateView) -> Vec<u8>;
+}
+
+// The parts of a snapshot that depend on who it is for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StateView {
+    // slot the frame is for; None for frames everyone shares
+    pub player: Option<usize>,
+    pub ack_seq: u32,
+    pub boost_cooldown_ms: u32,
+    // the game's update count and the server clock when the frame was built
+    pub tick: u32,
+    pub server_time_us: u64,
+    pub match_phase: MatchPhase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateFormat {
+    Binary,
+    Json,
+    // fixed point instead of f32 for soccer State, v5 and later; see
+    // QUANTIZED_VELOCITY_SCALE
+    Quantized,
+}
+
+impl StateFormat {
+    pub fn from_param(value: &str) -> Option<Self> {
+        return match value {
+            "binary" => Some(StateFormat::Binary),
+            "json" => Some(StateFormat::Json),
+            "quantized" => Some(StateFormat::Quantized),
+            _ => None,
+        };
+    }
+
+    pub fn code(&self) -> u8 {
+        return match self {
+            StateFormat::Binary => 0,
+            StateFormat::Json => 1,
+            StateFormat::Quantized => 2,
+        };
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        return match code {
+            0 => Some(StateFormat::Binary),
+            1 => Some(StateFormat::Json),
+            2 => Some(StateFormat::Quantized),
+            _ => None,
+        };
+    }
+}
+
+// f32 packing in the layout of the negotiated protocol version (see
+// SoccerStateSnapshot) and the connection's byte order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompactBinary(pub ProtocolVersion, pub ByteOrder);
+
+impl Default for CompactBinary {
+    fn default() -> Self {
+        return CompactBinary(ProtocolVersion::V1, ByteOrder::Little);
+    }
+}
+
+impl StateSerializer for CompactBinary {
+    fn encode(&self, game: &SoccerGame, view: &StateView) -> Vec<u8> {
+        let mut payload = little_endian(self.0, game, view);
+        self.1.swap_state(body_layout(self.0), &mut payload);
+        return payload;
+    }
+}
+
+// The quantized body behind the headers of a v5 or later protocol, in the
+// connection's byte order. Earlier protocols have no quantized layout and
+// get the v5 one; the server doesn't agree to them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantized(pub ProtocolVersion, pub ByteOrder);
+
+impl StateSerializer for Quantized {
+    fn encode(&self, game: &SoccerGame, view: &StateView) -> Vec<u8> {
+        let mut payload = match self.0 {
+            ProtocolVersion::V7 => vec![view.match_phase.code()],
+            _ => vec![],
+        };
+        payload.extend_from_slice(&state_header(game, view));
+        payload.extend_from_slice(&game.to_bytes_quantized(view.player));
+        payload.extend_from_slice(&view.ack_seq.to_le_bytes());
+        payload.extend_from_slice(&view.boost_cooldown_ms.to_le_bytes());
+        let layout = match self.0 {
+            ProtocolVersion::V7 => ProtocolVersion::V7,
+            _ => ProtocolVersion::V5,
+        };
+        self.1.swap_quantized_state(layout, &mut payload);
+        return payload;
+    }
+}
+
+fn little_endian(protocol: ProtocolVersion, game: &SoccerGame, view: &StateView) -> Vec<u8> {
+    return match protocol {
+        ProtocolVersion::V1 => game.to_bytes_v1(),
+        ProtocolVersion::V2 => {
+            let mut payload = game.to_bytes_v2();
+            payload.extend_from_slice(&view.ack_seq.to_le_bytes());
+            payload
+        }
+        ProtocolVersion::V3 => {
+            let mut payload = game.to_bytes_v3();
+            payload.extend_from_slice(&view.ack_seq.to_le_bytes());
+            payload.extend_from_slice(&view.boost_cooldown_ms.to_le_bytes());
+            payload
+        }
+        ProtocolVersion::V4 => {
+            let mut payload = state_header(game, view);
+            payload.extend_from_slice(&little_endian(ProtocolVersion::V3, game, view));
+            payload
+        }
+        ProtocolVersion::V5 | ProtocolVersion::V6 => {
+            let mut payload = state_header(game, view);
+            let own_pucks = view.player.map_or(vec![], |player| game.own_pucks(player));
+            payload.push(own_pucks.len() as u8);
+            for puck in own_pucks {
+                payload.push(puck.target);
+                for value in [puck.x, puck.y, puck.vx, puck.vy] {
+                    payload.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            payload.extend_from_slice(&little_endian(ProtocolVersion::V3, game, view));
+            payload
+        }
+        ProtocolVersion::V7 => {
+            let mut payload = vec![view.match_phase.code()];
+            payload.extend_from_slice(&little_endian(ProtocolVersion::V5, game, view));
+            payload
+        }
+        ProtocolVersion::V8 | ProtocolVersion::V9 | ProtocolVersion::V10 | ProtocolVersion::V11 => {
+            little_endian(ProtocolVersion::V5, game, view)
+        }
+    };
+}
+
+// The layout encode produces for protocol. v8's SnapshotHeader, and v9's,
+// is put in front by the server for every game type, so the soccer body is
+// v5's.
+fn body_layout(protocol: ProtocolVersion) -> ProtocolVersion {
+    return match protocol {
+        ProtocolVersion::V8 | ProtocolVersion::V9 | ProtocolVersion::V10 | ProtocolVersion::V11 => {
+            ProtocolVersion::V5
+        }
+        protocol => protocol,
+    };
+}
+
+fn state_header(game: &SoccerGame, view: &StateView) -> Vec<u8> {
+    let mut header = Vec::with_capacity(STATE_HEADER_LEN);
+    header.extend_from_slice(&view.tick.to_le_bytes());
+    header.extend_from_slice(&view.server_time_us.to_le_bytes());
+    header.push(game.phase_code());
+    return header;
+}
+
+// UTF-8 JSON, the same shape the HTTP /game endpoint uses for state, for
+// debugging and clients without a binary decoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Json;
+
+impl StateSerializer for Json {
+    fn encode(&self, game: &SoccerGame, view: &StateView) -> Vec<u8> {
+        let own_pucks: Vec<String> = view
+            .player
+            .map_or(vec![], |player| game.own_pucks(player))
+            .iter()
+            .map(|puck| {
+                format!(
+                    "{{\"target\":{},\"x\":{},\"y\":{},\"vx\":{},\"vy\":{}}}",
+                    puck.target,
+                    http::json_number(puck.x),
+                    http::json_number(puck.y),
+                    http::json_number(puck.vx),
+                    http::json_number(puck.vy)
+                )
+            })
+            .collect();
+        return format!(
+            "{{\"tick\":{},\"server_time_us\":{},\"match_phase\":\"{}\",\"ack_seq\":{},\"boost_cooldown_ms\":{},\"own_pucks\":[{}],\"state\":{}}}",
+            view.tick,
+            view.server_time_us,
+            view.match_phase.as_str(),
+            view.ack_seq,
+            view.boost_cooldown_ms,
+            own_pucks.join(","),
+            http::soccer_json(game)
+        )
+        .into_bytes();
+    }
+}
@@ -0,0 +1,5163 @@
+use crate::audit::{AuditInput, Divergence};
+use crate::auth::{self, AuthError};
+use crate::config::{ConfigError, ConfigSource, SoccerSection};
+use crate::degrade::{DegradeConfig, Degrader};
+use crate::disconnects::{DisconnectLog, DisconnectRecord, DISCONNECT_LOG_SIZE};
+use crate::events::{ServerEvent, ServerEvents, EVENT_BUS_CAPACITY};
+use crate::frame_dump::{DumpFormat, FrameDumper};
+use crate::game::{
+    tokens_match, validate_params, Client, CommandLink, Connections, ControlMode,
+    DuplicateConnection, Formation, Game, GameLogic, GamePhase, Games, HistoryEvent, PauseConfig,
+    PhysicsPreset, Player, PlayerSlot, Side, SlotConnection, SoccerGame, SoccerGameConfig,
+    TickMode, EVENT_LOG_SIZE, HISTORY_SIZE, SOCCER_GAME_TYPE, VOLLEY_GAME_TYPE,
+};
+use crate::hooks::{NoHooks, ServerHooks};
+use crate::http;
+use crate::invariants::{self, InvariantChecks};
+use crate::limiter::{IpLimiter, Refusal, Strike, TokenBucket};
+use crate::matchmaking::{
+    Candidate, Challenge, Challenges, FillFirst, GameOwners, Joining, Match, MatchQueue,
+    MatchmakingStrategy, OpenSlots, Owner, Placement, QueueEntry,
+};
+use crate::message::{
+    AnnounceMessage, AnnouncedMessage, AnnouncementMessage, AuditGameMessage, BoostMessage,
+    ByteOrder, ChallengeMessage, ChallengeOutcome, ChallengeReceivedMessage, ChallengeReplyMessage,
+    ChallengeResultMessage, ChatMessage, ChatScope, CloseReason, ConfigReloadedMessage,
+    DegradeLevel, EchoReply, ErrorCode, ErrorMessage, EventMessage, EventsSinceMessage,
+    FreezeOpponentMessage, GameOverMessage, GameOverReason, GameParams, GameSteppedMessage,
+    HelloMessage, InterestGroup, LeaveGameMessage, LobbyGame, LobbyStatus, LobbyUpdateMessage,
+    LockstepInputMessage, MessageType, MultiStateMessage, MuteMessage, PingMessage,
+    PlayerJoinedMessage, PlayerLeftMessage, PlayerRecord, ProtocolVersion, QueueStatusMessage,
+    QueuedMessage, Role, RosterMessage, RosterRequest, ServerInfoMessage, SetFormationMessage,
+    SetGameParamsMessage, SetInterestMessage, SetTickRateMessage, SlotStats, SoccerMoveMessage,
+    SoccerTunedMessage, StatsResponse, StepGameMessage, SubscribeAllMessage, SubscribeMessage,
+    TimeSyncRequest, TimeSyncResponse, TuneSoccerMessage, UnsupportedTypeMessage, VersionMessage,
+    WelcomeMessage, WhoAmIMessage, WsMessage, MAX_CHAT_LEN,
+};
+use crate::middleware::{ConnCtx, ConnectionMiddleware, MiddlewareChain, MiddlewareDecision};
+use crate::outbox::{Outbox, Priority};
+use crate::persistence::{self, SavedMatch};
+use crate::pool::{SoccerPool, SOCCER_POOL_SIZE};
+use crate::profiling::{PhaseTimes, TickProfiler, TickStats, TickSummary};
+use crate::serializer::{CompactBinary, Json, Quantized, StateFormat, StateSerializer, StateView};
+use crate::stats::{Competitor, PlayerId, Stats, StatsStore};
+use crate::traffic::Direction;
+use arc_swap::ArcSwap;
+use bytes::Bytes;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+use sysinfo::System;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, RwLock};
+use tokio::time::{
+    interval, interval_at, sleep, timeout, timeout_at, Duration, Instant, Interval,
+    MissedTickBehavior,
+};
+use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::{
+    accept_hdr_async_with_config, tungstenite::protocol::Message, WebSocketStream,
+};
+use url;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+// Frames the server received but had no use for (websocket-level pings,
+// binary frames without a message type), across all connections.
+pub static IGNORED_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+// Pongs that matched no outstanding server Ping (never sent, answered twice
+// or expired), across all connections.
+pub static UNSOLICITED_PONGS: AtomicU64 = AtomicU64::new(0);
+
+// Malformed or oversized frames that closed a connection, across all
+// connections.
+pub static PROTOCOL_STRIKES: AtomicU64 = AtomicU64::new(0);
+
+// PEM files for serving wss://.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub addr: String,
+    // debug HTTP endpoints (see http.rs); None disables them
+    pub http_addr: Option<String>,
+    // /ready, /live and /metrics for orchestrators; None disables them
+    pub health_addr: Option<String>,
+    // /live fails once no tick has completed for this many tick periods
+    pub live_tick_periods: u32,
+    pub tick_rate: u64,
+    // how many seconds of tick timings the profiler keeps
+    pub tick_window_secs: u64,
+    // when false a player who sends LeaveGame goes back through matchmaking
+    // instead of having their socket closed
+    pub close_on_leave: bool,
+    pub collect_stats: bool,
+    pub max_stats_entries: usize,
+    pub pause: PauseConfig,
+    // games waiting for an opponent let the players already there move
+    // around a live board; false keeps them frozen
+    pub warm_up: bool,
+    // what a second connection to an occupied slot gets
+    pub duplicate_connection: DuplicateConnection,
+    // control mode given to newly created soccer games
+    pub control_mode: ControlMode,
+    pub soccer: SoccerGameConfig,
+    pub physics_preset: PhysicsPreset,
+    // record per-step timing and contact counts on every soccer game
+    pub record_physics_stats: bool,
+    // worlds of removed soccer games kept for reuse; 0 keeps none
+    pub soccer_pool_size: usize,
+    // how ended connections the disconnect log keeps; 0 keeps none
+    pub disconnect_log_size: usize,
+    // bounds and default for the State push rate picked with Subscribe
+    pub min_state_rate_hz: u8,
+    pub max_state_rate_hz: u8,
+    pub default_state_rate_hz: u8,
+    // steps a connection that can't keep up down through DegradeLevels and
+    // back; None sends everyone everything
+    pub degrade: Option<DegradeConfig>,
+    // a connection that sends no frame at all for this long is closed,
+    // regardless of the application heartbeat
+    pub idle_timeout: Duration,
+    // a connection silent for this long is sent a Ping, and again every
+    // interval while it stays silent; None never probes
+    pub server_ping_interval: Option<Duration>,
+    // a player who goes this long without a Ping, or a Pong to one of the
+    // server's, is closed with 4001; announced in Welcome so clients know
+    // the cadence they owe. None leaves pinging up to the client
+    pub required_ping_interval: Option<Duration>,
+    // a websocket-level ping goes out this often and a connection that
+    // hasn't answered one by the time the next is due is closed; this is
+    // what finds half-open TCP connections. None never sends them
+    pub ws_ping_interval: Option<Duration>,
+    // a write to a connection's socket that hasn't gone through after this
+    // long closes the connection as too slow; a peer that keeps TCP up but
+    // stops reading would otherwise pin its task, and its slot, until the
+    // OS gives up. None waits as long as the write takes
+    pub send_timeout: Option<Duration>,
+    pub handshake_timeout: Duration,
+    // a full game starts once both players send Ready or this runs out
+    pub ready_timeout: Duration,
+    // a game nobody is connected to stops being stepped, and is removed
+    // once it has stayed that way this long
+    pub dormant_timeout: Duration,
+    // larger messages or frames close the connection with 1009
+    pub max_message_size: usize,
+    pub max_frame_size: usize,
+    // largest Echo payload answered, and how many Echoes a connection may
+    // send at once and per second after that
+    pub max_echo_bytes: usize,
+    pub echo_burst: u32,
+    pub echo_rate_hz: u32,
+    // open connections allowed from one address; None is unlimited
+    pub max_connections_per_ip: Option<usize>,
+    // load balancers whose X-Forwarded-For is believed when reporting a
+    // client's address back to it
+    pub trusted_proxies: Vec<IpAddr>,
+    // certificate and key for wss://, checked to exist when the config is
+    // loaded. The server doesn't terminate TLS itself yet, so it refuses to
+    // start with these set rather than serve plain ws in their place
+    pub tls: Option<TlsFiles>,
+    // env_logger filter main starts logging with, unless RUST_LOG is set
+    pub log_level: Option<String>,
+    // web pages allowed to open a socket, by Origin ("https://example.com");
+    // any other origin is refused with 403. Empty allows every origin.
+    // Requests without an Origin don't come from a browser and are let
+    // through
+    pub allowed_origins: Vec<String>,
+    // an address whose connections break the websocket protocol this many
+    // times is refused for protocol_ban; 0 never bans
+    pub protocol_strikes: u32,
+    pub protocol_ban: Duration,
+    // live games that can be created for one player (auth identity, or
+    // name and address for guests); None is unlimited
+    pub max_games_per_identity: Option<usize>,
+    // connections whose auth header carries this token may send admin
+    // messages; None disables them
+    pub admin_token: Option<String>,
+    // lets admins slow the tick loop or a game down and step games by hand
+    // with SetTickRate and StepGame, audit them with AuditGame and freeze
+    // a side's pucks with FreezeOpponent; off in production
+    pub debug_ticks: bool,
+    // walks every game after each create, join, reconnect, leave and
+    // removal looking for corrupt rosters (see invariants.rs); panics on
+    // one in debug builds, off in release ones unless turned on
+    pub check_invariants: InvariantChecks,
+    // header the auth token is read from, and the scheme in front of it
+    // ("Bearer" for "Bearer <token>", the default); None takes the whole
+    // value
+    pub auth_header: String,
+    pub auth_scheme: Option<String>,
+    // when set, every connection needs a token the service at this URL
+    // accepts, and is named and keyed by the identity it answers with;
+    // ?name= only counts while this is None, for local testing
+    pub auth_url: Option<String>,
+    // how long a connection waits on the auth service before it is closed
+    pub auth_timeout: Duration,
+    // longest auth header value taken; a longer one fails the handshake
+    // with 431 before it is copied anywhere
+    pub max_auth_header_bytes: usize,
+    // longest ?name=, in characters
+    pub max_name_len: usize,
+    // a queued player waiting this long gets a bot opponent instead; None
+    // waits indefinitely
+    pub queue_timeout: Option<Duration>,
+    // live games across the server; once reached, new matches wait in the
+    // queue for one to end. None is unlimited
+    pub max_games: Option<usize>,
+    // a connection queued while the server is full is turned away after
+    // this long; None waits indefinitely
+    pub full_queue_timeout: Option<Duration>,
+    // a Challenge not answered within this is withdrawn
+    pub challenge_timeout: Duration,
+    // broadcasts each game keeps for clients catching up with EventsSince
+    pub event_log_size: usize,
+    // ticks of play each game records for the replay sent after a goal; 0
+    // turns replays off
+    pub replay_ticks: usize,
+    // entries each game keeps for GET /game/{id}/history
+    pub history_size: usize,
+    // ticks each game holds a move before applying it, to even out moves
+    // that arrive in bursts; 0 applies them as they come
+    pub input_buffer_ticks: u64,
+    // Some makes every game lockstep: in play each update waits for
+    // LockstepInput from every connected player, for at most this long; for
+    // LAN matches where both ends run the same simulation
+    pub lockstep_timeout: Option<Duration>,
+    // leaderboard and game summaries are saved here every persist_interval
+    // and on shutdown, and loaded on startup; None keeps everything in
+    // memory only
+    pub persist_path: Option<PathBuf>,
+    pub persist_interval: Duration,
+    // with a persist_path, also save where every body of each soccer game
+    // is this often, and resume those games on startup; None saves
+    // summaries only
+    pub game_state_interval: Option<Duration>,
+    // firehose batches go out this often at most; slower SubscribeAll
+    // rates get every nth batch
+    pub max_firehose_rate_hz: u8,
+    // MultiState payloads are kept under this, splitting a batch across
+    // messages when needed
+    pub firehose_batch_bytes: usize,
+    // every game writes its ticks to game-{id}.ndjson (or .bin) here, for
+    // rendering clips offline; None dumps nothing
+    pub frame_dump_dir: Option<PathBuf>,
+    pub frame_dump_format: DumpFormat,
+    // frames each dump stops at; None runs until the game ends
+    pub frame_dump_limit: Option<u64>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        return ServerConfig {
+            addr: "0.0.0.0:8080".to_string(),
+            http_addr: Some("0.0.0.0:8081".to_string()),
+            health_addr: Some("0.0.0.0:8082".to_string()),
+            live_tick_periods: 10,
+            // 60hz
+            tick_rate: 60,
+            tick_window_secs: 10,
+            close_on_leave: true,
+            collect_stats: true,
+            max_stats_entries: 10_000,
+            pause: PauseConfig::default(),
+            warm_up: true,
+            duplicate_connection: DuplicateConnection::Reject,
+            control_mode: ControlMode::default(),
+            soccer: SoccerGameConfig::default(),
+            physics_preset: PhysicsPreset::default(),
+            record_physics_stats: false,
+            soccer_pool_size: SOCCER_POOL_SIZE,
+            disconnect_log_size: DISCONNECT_LOG_SIZE,
+            min_state_rate_hz: 1,
+            max_state_rate_hz: 60,
+            default_state_rate_hz: 60,
+            degrade: None,
+            idle_timeout: Duration::from_secs(60),
+            server_ping_interval: Some(Duration::from_secs(15)),
+            required_ping_interval: None,
+            ws_ping_interval: Some(Duration::from_secs(10)),
+            send_timeout: Some(Duration::from_secs(10)),
+            handshake_timeout: Duration::from_secs(10),
+            ready_timeout: Duration::from_secs(10),
+            dormant_timeout: Duration::from_secs(60),
+            max_message_size: 64 * 1024,
+            max_frame_size: 64 * 1024,
+            max_echo_bytes: 4 * 1024,
+            echo_burst: 100,
+            echo_rate_hz: 20,
+            max_connections_per_ip: None,
+            trusted_proxies: vec![],
+            allowed_origins: vec![],
+            tls: None,
+            log_level: None,
+            protocol_strikes: 3,
+            protocol_ban: Duration::from_secs(300),
+            max_games_per_identity: Some(3),
+            admin_token: None,
+            debug_ticks: false,
+            check_invariants: InvariantChecks::default(),
+            auth_header: "Authorization".to_string(),
+            auth_scheme: Some("Bearer".to_string()),
+            auth_url: None,
+            auth_timeout: Duration::from_secs(5),
+            max_auth_header_bytes: 4096,
+            max_name_len: 32,
+            queue_timeout: Some(Duration::from_secs(30)),
+            max_games: None,
+            full_queue_timeout: Some(Duration::from_secs(120)),
+            challenge_timeout: Duration::from_secs(30),
+            event_log_size: EVENT_LOG_SIZE,
+            // three seconds at 60hz
+            replay_ticks: 180,
+            history_size: HISTORY_SIZE,
+            input_buffer_ticks: 0,
+            lockstep_timeout: None,
+            persist_path: None,
+            persist_interval: Duration::from_secs(60),
+            game_state_interval: None,
+            max_firehose_rate_hz: 10,
+            firehose_batch_bytes: 60 * 1024,
+            frame_dump_dir: None,
+            frame_dump_format: DumpFormat::Json,
+            frame_dump_limit: None,
+        };
+    }
+}
+
+pub struct ConnectionInfo {
+    // remote address, for the per-address limits
+    pub ip: IpAddr,
+    // the client's address as reported back to it: the socket's peer, or
+    // what a trusted proxy forwarded
+    pub peer: SocketAddr,
+    pub auth_token: Option<String>,
+    pub game: Option<usize>,
+    pub name: Option<String>,
+    pub player_index: usize,
+    pub protocol: ProtocolVersion,
+    // State payload layout: the protocol's own, or an older one a v6 Hello
+    // asked for
+    pub state_version: ProtocolVersion,
+    // game type the client expects, from ?mode=; soccer when absent
+    pub game_type: u8,
+    // ?practice=1 asks for a solo game instead of matchmaking
+    pub practice: bool,
+    // ?lobby=1 waits in the queue without being matched, to send or answer
+    // a Challenge
+    pub lobby: bool,
+    // State payload encoding, from ?format=binary|json|quantized
+    pub format: StateFormat,
+    // numbers in binary State and SoccerMove payloads, from ?byte_order=le|be
+    pub byte_order: ByteOrder,
+    // from ?session=, to reclaim a slot after a disconnect
+    pub session_token: Option<String>,
+    // from ?game_token=; when given, game must be the game it was handed
+    // out with
+    pub game_token: Option<String>,
+    // from the auth service's subject, the auth token, or the name for
+    // guests; set once the handshake is done and used for every lookup after
+    // that
+    pub player_id: PlayerId,
+    // ?firehose=1 opens an admin connection that watches every game
+    // through SubscribeAll instead of playing
+    pub firehose: bool,
+}
+
+impl ConnectionInfo {
+    pub fn owner(&self) -> Owner {
+        return Owner::new(self.player_id.clone(), self.ip);
+    }
+
+    pub fn who_am_i(&self) -> WhoAmIMessage {
+        return WhoAmIMessage {
+            address: self.peer.ip().to_string(),
+            port: self.peer.port(),
+            protocol: self.protocol as u8,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+    }
+}
+
+// What the connection loop should do after a message has been handled.
+pub enum Response {
+    Reply(WsMessage),
+    Nothing,
+    Close(CloseReason),
+    Leave,
+}
+
+// Why a connection stopped playing in its current game.
+enum PlayEnd {
+    Disconnected,
+    Left,
+    GameClosed,
+    // a newer connection took the slot, and this one has been closed
+    Replaced,
+}
+
+type WsSender = SplitSink<WebSocketStream<TcpStream>, Message>;
+type WsReceiver = SplitStream<WebSocketStream<TcpStream>>;
+
+// The soccer config new games are built from, counted up on every change
+// so a game's tuning_version says which one it has.
+#[derive(Debug, Clone, Default)]
+pub struct SoccerTuning {
+    pub version: u64,
+    pub config: SoccerGameConfig,
+}
+
+impl SoccerTuning {
+    // Moves on to config and returns its version.
+    pub fn replace(&mut self, config: SoccerGameConfig) -> u64 {
+        self.config = config;
+        self.version += 1;
+        return self.version;
+    }
+}
+
+// Everything a connection task needs that outlives a single connection.
+pub struct ServerState {
+    // swapped whole by reload_config; read it through config() so every
+    // use sees the latest
+    config: ArcSwap<ServerConfig>,
+    // what reload_config re-reads; None when the config didn't come from a
+    // file
+    pub config_source: Option<ConfigSource>,
+    pub games: Games,
+    pub stats: Stats,
+    pub events: ServerEvents,
+    pub ticks: TickStats,
+    // origin of the clock reported in TimeSync replies
+    pub started: Instant,
+    // config for newly created soccer games; starts as config.soccer at
+    // version 0 and is updated by SetGameParams, TuneSoccer and reloads
+    pub soccer: Mutex<SoccerTuning>,
+    pub soccer_pool: SoccerPool,
+    pub disconnects: DisconnectLog,
+    pub queue: MatchQueue,
+    // every admitted connection, for broadcast_all
+    pub connections: Connections,
+    // challenges between queued connections, ?lobby=1 ones especially
+    pub challenges: Challenges,
+    pub open_slots: OpenSlots,
+    pub game_owners: GameOwners,
+    // LobbyUpdate frames for queued connections that sent SubscribeLobby,
+    // one per game type; each holds a receiver until it leaves the queue
+    pub lobby: broadcast::Sender<(u8, Bytes)>,
+    // MultiState rounds for firehose connections that sent SubscribeAll
+    pub firehose: broadcast::Sender<FirehoseRound>,
+    // how many of those are subscribed; every game counts them as connected
+    pub spectators: Arc<AtomicUsize>,
+    // how to build the logic for each game type a client can ask for
+    game_types: Mutex<GameTypes>,
+    // run on every connection opened from now on, in order
+    middleware: Mutex<Vec<Arc<dyn ConnectionMiddleware>>>,
+    // where players without a game go
+    matchmaking: Mutex<Arc<dyn MatchmakingStrategy>>,
+    // held by place from looking for an open game to joining or opening
+    // one, so two joins can't each find none and open one apiece
+    placing: tokio::sync::Mutex<()>,
+    // told about connections joining, moving and leaving, and games ending
+    hooks: Mutex<Arc<dyn ServerHooks>>,
+    pub ip_limiter: IpLimiter,
+    // set once the websocket listener is bound
+    pub listening: AtomicBool,
+    // ticks completed, and clock_us when the last one finished
+    pub ticks_completed: AtomicU64,
+    pub last_tick_us: AtomicU64,
+    // games stepped by the last tick, and games it skipped because nobody
+    // is connected to them
+    pub active_games: AtomicUsize,
+    pub dormant_games: AtomicUsize,
+    // times the tick loop died and was restarted
+    pub tick_restarts: AtomicU64,
+    // a debug rate for the whole tick loop set by SetTickRate, in hz
+    pub tick_rate_override: watch::Sender<Option<f32>>,
+    // highest game id handed out, including by a previous run
+    pub last_game_id: AtomicUsize,
+    // matches saved by a previous run, rebuilt when the server starts so
+    // game types registered after new are known
+    resumable: Mutex<Vec<SavedMatch>>,
+}
+
+// Builds the logic for a new game from the server's current config; the
+// flag asks for a practice game.
+pub type GameFactory = Arc<dyn Fn(&ServerState, bool) -> Box<dyn GameLogic> + Send + Sync>;
+
+// Every game type a client can ask for, by its byte and, for those
+// registered with one, by the name ?mode= also takes.
+#[derive(Default)]
+struct GameTypes {
+    factories: BTreeMap<u8, GameFactory>,
+    names: BTreeMap<String, u8>,
+}
+
+impl ServerState {
+    pub fn config(&self) -> Arc<ServerConfig> {
+        return self.config.load_full();
+    }
+    pub fn game_factory(&self, game_type: u8) -> Option<GameFactory> {
+        return self
+            .game_types
+            .lock()
+            .unwrap()
+            .factories
+            .get(&game_type)
+            .cloned();
+    }
+    pub fn game_types(&self) -> Vec<u8> {
+        return self
+            .game_types
+            .lock()
+            .unwrap()
+            .factories
+            .keys()
+            .copied()
+            .collect();
+    }
+    // The game type a ?mode= value names: a registered name, or the byte
+    // itself. A byte nobody registered still resolves, and is refused when
+    // the connection tries to join.
+    pub fn resolve_mode(&self, mode: &str) -> Option<u8> {
+        if let Ok(game_type) = mode.parse::<u8>() {
+            return Some(game_type);
+        }
+        return self.game_types.lock().unwrap().names.get(mode).copied();
+    }
+    // Builds a game type's logic as a new game would get it.
+    pub fn create_logic(&self, game_type: u8, practice: bool) -> Option<Box<dyn GameLogic>> {
+        return self
+            .game_factory(game_type)
+            .map(|factory| factory(self, practice));
+    }
+    pub fn matchmaking(&self) -> Arc<dyn MatchmakingStrategy> {
+        return Arc::clone(&self.matchmaking.lock().unwrap());
+    }
+    pub fn hooks(&self) -> Arc<dyn ServerHooks> {
+        return Arc::clone(&self.hooks.lock().unwrap());
+    }
+    // Games of game_type with a free slot that player isn't already in, so
+    // nobody is matched against themselves from a second connection.
+    pub async fn candidates(&self, game_type: u8, player: &PlayerId) -> Vec<Candidate> {
+        let games: Vec<(usize, Arc<RwLock<Game>>)> = self
+            .games
+            .read()
+            .await
+            .iter()
+            .map(|(game_id, game)| (*game_id, Arc::clone(game)))
+            .collect();
+        let mut candidates = vec![];
+        for (game_id, game) in games {
+            let game = game.read().await;
+            let max_players = game.logic.max_players();
+            // a practice game stays solo whatever its logic allows, and a
+            // private one keeps to the players it was made for
+            let full = game.practice || game.private || game.players.len() >= max_players;
+            let seated = game.players.iter().any(|p| p.id == *player);
+            if game.game_type != game_type || game.is_closed() || full || seated {
+                continue;
+            }
+            candidates.push(Candidate {
+                game_id,
+                players: game.players.len(),
+                max_players,
+                started: !matches!(game.phase, GamePhase::ReadyCheck { .. }),
+            });
+        }
+        return candidates;
+    }
+    pub fn middleware(&self) -> Arc<[Arc<dyn ConnectionMiddleware>]> {
+        return self.middleware.lock().unwrap().as_slice().into();
+    }
+    // False while max_games are running. A quick look before doing the work
+    // of a match; create_game checks again as it registers the game, so
+    // games started at the same moment can't overshoot.
+    pub async fn has_room(&self) -> bool {
+        return match self.config().max_games {
+            Some(max) => self.games.read().await.len() < max,
+            None => true,
+        };
+    }
+
+    pub fn emit(&self, event: ServerEvent) {
+        // no subscribers is fine
+        let _ = self.events.send(event);
+    }
+    pub fn clock_us(&self) -> u64 {
+        return self.started.elapsed().as_micros() as u64;
+    }
+    // Accepting connections and the tick loop has run at least once.
+    pub fn is_ready(&self) -> bool {
+        return self.listening.load(Ordering::Relaxed)
+            && self.ticks_completed.load(Ordering::Relaxed) > 0;
+    }
+    // How often the tick loop runs, debug tick rate included.
+    pub fn loop_period(&self) -> Duration {
+        return loop_budget(&self.config(), *self.tick_rate_override.borrow());
+    }
+    // A tick finished within the last live_tick_periods periods, counting
+    // from startup until the first one does. Only atomics are read, so a
+    // deadlocked games lock can't hang the check itself.
+    pub fn is_live(&self) -> bool {
+        let window = self.loop_period() * self.config().live_tick_periods;
+        let since_tick = self
+            .clock_us()
+            .saturating_sub(self.last_tick_us.load(Ordering::Relaxed));
+        return since_tick <= window.as_micros() as u64;
+    }
+    // Sends message to every open connection, whatever it is doing, and
+    // returns how many it went to. Connections that are mid-handshake get
+    // it once they are admitted; ones that went away are skipped.
+    pub fn broadcast_all(&self, message: WsMessage) -> usize {
+        return self.connections.broadcast(&message);
+    }
+
+    pub fn is_admin(&self, conn_info: &ConnectionInfo) -> bool {
+        let config = self.config();
+        let (expected, token) = match (&config.admin_token, &conn_info.auth_token) {
+            (Some(expected), Some(token)) => (expected, token),
+            _ => return false,
+        };
+        return tokens_match(token, expected);
+    }
+}
+
+pub struct Server {
+    state: Arc<ServerState>,
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Self {
+        return Server::build(config, None);
+    }
+
+    // Like new, but SIGHUP and the admin ReloadConfig message re-read path
+    // and apply it without a restart.
+    pub fn with_config_file(config: ServerConfig, path: PathBuf) -> Self {
+        return Server::build(config, Some(ConfigSource::new(path)));
+    }
+
+    // Like with_config_file, keeping the profile and command line
+    // overrides source was loaded with for reloads too.
+    pub fn with_config_source(config: ServerConfig, source: ConfigSource) -> Self {
+        return Server::build(config, Some(source));
+    }
+
+    fn build(config: ServerConfig, config_source: Option<ConfigSource>) -> Self {
+        let mut stats = StatsStore::new(config.max_stats_entries);
+        let mut last_game_id = 0;
+        let mut resumable = vec![];
+        if let Some(path) = &config.persist_path {
+            match persistence::load(path) {
+                Ok(Some(snapshot)) => {
+                    snapshot.restore_stats(&mut stats);
+                    last_game_id = snapshot.last_game_id();
+                    println!(
+                        "Loaded {} records from {}; {} games were running, {} can be resumed",
+                        snapshot.records.len(),
+                        path.display(),
+                        snapshot.games.len(),
+                        snapshot.resumable.len()
+                    );
+                    resumable = snapshot.resumable;
+                }
+                Ok(None) => (),
+                Err(e) => eprintln!("Failed to load {}: {}", path.display(), e),
+            }
+        }
+        let stats = Arc::new(RwLock::new(stats));
+        let ticks = Arc::new(Mutex::new(TickProfiler::new(
+            (config.tick_rate * config.tick_window_secs) as usize,
+            tick_budget(&config),
+        )));
+        let server = Server {
+            state: Arc::new(ServerState {
+                soccer: Mutex::new(SoccerTuning {
+                    version: 0,
+                    config: config.soccer.clone(),
+                }),
+                soccer_pool: SoccerPool::default(),
+                disconnects: DisconnectLog::default(),
+                queue: MatchQueue::default(),
+                connections: Connections::default(),
+                challenges: Challenges::default(),
+                open_slots: OpenSlots::default(),
+                placing: tokio::sync::Mutex::new(()),
+                game_owners: GameOwners::default(),
+                lobby: broadcast::channel(LOBBY_CAPACITY).0,
+                firehose: broadcast::channel(FIREHOSE_CAPACITY).0,
+                spectators: Arc::new(AtomicUsize::new(0)),
+                game_types: Mutex::new(GameTypes::default()),
+                middleware: Mutex::new(vec![]),
+                matchmaking: Mutex::new(Arc::new(FillFirst)),
+                hooks: Mutex::new(Arc::new(NoHooks)),
+                ip_limiter: IpLimiter::default(),
+                config: ArcSwap::from_pointee(config),
+                config_source,
+                games: Arc::new(RwLock::new(BTreeMap::new())),
+                stats,
+                events: broadcast::channel(EVENT_BUS_CAPACITY).0,
+                ticks,
+                started: Instant::now(),
+                listening: AtomicBool::new(false),
+                ticks_completed: AtomicU64::new(0),
+                last_tick_us: AtomicU64::new(0),
+                active_games: AtomicUsize::new(0),
+                dormant_games: AtomicUsize::new(0),
+                tick_restarts: AtomicU64::new(0),
+                tick_rate_override: watch::channel(None).0,
+                last_game_id: AtomicUsize::new(last_game_id),
+                resumable: Mutex::new(resumable),
+            }),
+        };
+        server.register_mode("soccer", SOCCER_GAME_TYPE, soccer_logic);
+        server.register_mode("volley", VOLLEY_GAME_TYPE, volley_logic);
+        return server;
+    }
+
+    pub fn games(&self) -> Games {
+        return self.state.games.clone();
+    }
+
+    pub fn stats(&self) -> Stats {
+        return self.state.stats.clone();
+    }
+
+    // See ServerState::broadcast_all.
+    pub fn broadcast_all(&self, message: WsMessage) -> usize {
+        return self.state.broadcast_all(message);
+    }
+
+    pub fn tick_summary(&self) -> TickSummary {
+        return self.state.ticks.lock().unwrap().summary();
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ServerEvent> {
+        return self.state.events.subscribe();
+    }
+
+    // Lets clients ask for game_type with ?mode=, replacing whatever built
+    // that type before. Soccer and volley are registered from the start.
+    pub fn register_game_type(
+        &self,
+        game_type: u8,
+        factory: impl Fn(&ServerState, bool) -> Box<dyn GameLogic> + Send + Sync + 'static,
+    ) {
+        self.state
+            .game_types
+            .lock()
+            .unwrap()
+            .factories
+            .insert(game_type, Arc::new(factory));
+    }
+
+    // Like register_game_type, and ?mode=name asks for it too. A name
+    // registered again moves to the new game type.
+    pub fn register_mode(
+        &self,
+        name: &str,
+        game_type: u8,
+        factory: impl Fn(&ServerState, bool) -> Box<dyn GameLogic> + Send + Sync + 'static,
+    ) {
+        self.register_game_type(game_type, factory);
+        self.state
+            .game_types
+            .lock()
+            .unwrap()
+            .names
+            .insert(name.to_string(), game_type);
+    }
+
+    // Runs middleware on every connection opened after this, after any
+    // registered before it. Connections already open keep what they had.
+    pub fn add_middleware(&self, middleware: impl ConnectionMiddleware + 'static) {
+        self.state
+            .middleware
+            .lock()
+            .unwrap()
+            .push(Arc::new(middleware));
+    }
+
+    // Replaces how players without a game are placed, FillFirst to begin
+    // with. Joins already being placed finish with the old strategy.
+    pub fn set_matchmaking(&self, strategy: Box<dyn MatchmakingStrategy>) {
+        *self.state.matchmaking.lock().unwrap() = Arc::from(strategy);
+    }
+
+    // Replaces the lifecycle hooks, NoHooks to begin with. Meant to be set
+    // before run; events already being told finish with the old ones.
+    pub fn set_hooks(&self, hooks: Box<dyn ServerHooks>) {
+        *self.state.hooks.lock().unwrap() = Arc::from(hooks);
+    }
+
+    pub async fn run(self) {
+        if let Err(reason) = startable(&self.state.config()) {
+            eprintln!("Refusing to start: {}", reason);
+            return;
+        }
+        let addr: SocketAddr = self.state.config().addr.parse().expect("Invalid Address");
+        let resumable = std::mem::take(&mut *self.state.resumable.lock().unwrap());
+        if !resumable.is_empty() {
+            let resumed = persistence::resume(&self.state, resumable).await;
+            println!("Resumed {} games", resumed);
+            invariants::enforce(&self.state, "resume").await;
+        }
+
+        let listener = TcpListener::bind(addr).await.expect("Failed to bind");
+
+        println!("Listening on {}", addr);
+        self.state.listening.store(true, Ordering::Relaxed);
+        if let Some(http_addr) = &self.state.config().http_addr {
+            let http_addr: SocketAddr = http_addr.parse().expect("Invalid HTTP Address");
+            tokio::spawn(http::serve(http_addr, self.state.clone()));
+        }
+        if let Some(health_addr) = &self.state.config().health_addr {
+            let health_addr: SocketAddr = health_addr.parse().expect("Invalid health Address");
+            tokio::spawn(http::serve_health(health_addr, self.state.clone()));
+        }
+        tokio::spawn(supervise_ticks(self.state.clone()));
+        tokio::spawn(run_matchmaker(self.state.clone()));
+        tokio::spawn(run_lobby(self.state.clone()));
+        tokio::spawn(run_firehose(self.state.clone()));
+        let persisting = self.state.config().persist_path.is_some();
+        if persisting {
+            tokio::spawn(persist_periodically(self.state.clone()));
+        }
+        if self.state.config_source.is_some() {
+            tokio::spawn(reload_on_hangup(self.state.clone()));
+        }
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(_) => break,
+                    };
+                    let state = self.state.clone();
+                    tokio::spawn(async move {
+                        handle_connection(stream, peer, state).await;
+                    });
+                }
+                // without persistence there is nothing to flush, so the
+                // signal keeps its default behaviour
+                _ = &mut shutdown, if persisting => {
+                    println!("Shutting down");
+                    break;
+                }
+            }
+        }
+        persistence::save_state(&self.state).await;
+    }
+}
+
+// Settings a config can hold that this server can't honour. A config that
+// asks for wss:// is refused, not quietly served as ws.
+pub fn startable(config: &ServerConfig) -> Result<(), String> {
+    if let Some(tls) = &config.tls {
+        return Err(format!(
+            "tls_cert '{}' is set, but TLS is not terminated by this server; \
+             terminate it in a proxy in front and unset tls_cert and tls_key",
+            tls.cert.display()
+        ));
+    }
+    return Ok(());
+}
+
+pub fn server_info(config: &ServerConfig) -> ServerInfoMessage {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    return ServerInfoMessage {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_versions: ProtocolVersion::SUPPORTED
+            .iter()
+            .map(|version| *version as u8)
+            .collect(),
+        game_types: vec![SOCCER_GAME_TYPE, VOLLEY_GAME_TYPE],
+        max_players_per_game: 2,
+        tick_rate: config.tick_rate as u16,
+        total_memory_mb: sys.total_memory() / 1024 / 1024,
+        available_memory_mb: sys.available_memory() / 1024 / 1024,
+        physical_cores: num_cpus::get_physical() as u16,
+        logical_threads: num_cpus::get() as u16,
+    };
+}
+
+async fn persist_periodically(state: Arc<ServerState>) {
+    let config = state.config();
+    let every = match config.game_state_interval {
+        Some(game_state) => game_state.min(config.persist_interval),
+        None => config.persist_interval,
+    };
+    let mut interval = interval(every.max(Duration::from_secs(1)));
+    // the first tick fires straight away; nothing has changed yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        persistence::save_state(&state).await;
+    }
+}
+
+// Settings only read at startup. A reload that changes one keeps the running
+// value and names it in the result instead.
+fn pin_startup_settings(new: &mut ServerConfig, old: &ServerConfig) -> Vec<String> {
+    let mut pinned = vec![];
+    pin(&mut pinned, "addr", &mut new.addr, &old.addr);
+    pin(&mut pinned, "http_addr", &mut new.http_addr, &old.http_addr);
+    pin(
+        &mut pinned,
+        "health_addr",
+        &mut new.health_addr,
+        &old.health_addr,
+    );
+    pin(&mut pinned, "tick_rate", &mut new.tick_rate, &old.tick_rate);
+    pin(
+        &mut pinned,
+        "tick_window_secs",
+        &mut new.tick_window_secs,
+        &old.tick_window_secs,
+    );
+    pin(
+        &mut pinned,
+        "max_stats_entries",
+        &mut new.max_stats_entries,
+        &old.max_stats_entries,
+    );
+    pin(
+        &mut pinned,
+        "persist_path",
+        &mut new.persist_path,
+        &old.persist_path,
+    );
+    pin(
+        &mut pinned,
+        "persist_interval",
+        &mut new.persist_interval,
+        &old.persist_interval,
+    );
+    pin(
+        &mut pinned,
+        "game_state_interval",
+        &mut new.game_state_interval,
+        &old.game_state_interval,
+    );
+    pin(&mut pinned, "tls", &mut new.tls, &old.tls);
+    pin(&mut pinned, "log_level", &mut new.log_level, &old.log_level);
+    return pinned;
+}
+
+fn pin<T: PartialEq + Clone>(pinned: &mut Vec<String>, name: &str, new: &mut T, old: &T) {
+    if new != old {
+        *new = old.clone();
+        pinned.push(name.to_string());
+    }
+}
+
+// Re-reads the config file and swaps it in. A file that doesn't parse or
+// validate leaves the running config untouched. Returns the changed settings
+// that only take effect after a restart.
+pub fn reload_config(state: &ServerState) -> Result<Vec<String>, String> {
+    let result = match &state.config_source {
+        Some(source) => source
+            .load()
+            .and_then(|config| {
+                for warning in &config.warnings {
+                    log::warn!("{}: {}", source.path.display(), warning);
+                }
+                return config.server_config();
+            })
+            .map_err(|e| format!("{}: {}", source.path.display(), e)),
+        None => Err("server was not started from a config file".to_string()),
+    };
+    let mut config = match result {
+        Ok(config) => config,
+        Err(reason) => {
+            println!("Config reload rejected: {}", reason);
+            state.emit(ServerEvent::ConfigReloadFailed {
+                reason: reason.clone(),
+            });
+            return Err(reason);
+        }
+    };
+    let old = state.config();
+    let restart_required = pin_startup_settings(&mut config, &old);
+    // games made from here on use the new soccer settings; unchanged ones
+    // keep whatever SetGameParams did to them
+    if config.soccer != old.soccer {
+        state.soccer.lock().unwrap().replace(config.soccer.clone());
+    }
+    state.config.store(Arc::new(config));
+    if restart_required.is_empty() {
+        println!("Config reloaded");
+    } else {
+        println!(
+            "Config reloaded; needs a restart to change: {}",
+            restart_required.join(", ")
+        );
+    }
+    state.emit(ServerEvent::ConfigReloaded {
+        restart_required: restart_required.clone(),
+    });
+    return Ok(restart_required);
+}
+
+async fn reload_on_hangup(state: Arc<ServerState>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(_) => return,
+        };
+        while hangup.recv().await.is_some() {
+            let _ = reload_config(&state);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+// Ctrl-C, or SIGTERM from a process manager.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => (),
+                _ = terminate.recv() => (),
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+fn tick_budget(config: &ServerConfig) -> Duration {
+    return Duration::from_millis(1000 / config.tick_rate);
+}
+
+// How often the tick loop runs: tick_budget, or a debug rate set by an
+// admin.
+fn loop_budget(config: &ServerConfig, rate: Option<f32>) -> Duration {
+    return rate.map_or(tick_budget(config), |hz| Duration::from_secs_f32(1.0 / hz));
+}
+
+const TICK_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+// Runs the tick loop on its own task and starts a fresh one whenever it
+// panics, so one bad update can't freeze every game for good. The loop never
+// returns on its own; a clean exit means the runtime is shutting down.
+async fn supervise_ticks(state: Arc<ServerState>) {
+    loop {
+        match tokio::spawn(start_periodic_task(state.clone())).await {
+            Err(e) if e.is_panic() => {
+                let restarts = state.tick_restarts.fetch_add(1, Ordering::Relaxed) + 1;
+                println!(
+                    "Tick loop panicked, restarting in {:?} (restart {})",
+                    TICK_RESTART_DELAY, restarts
+                );
+                sleep(TICK_RESTART_DELAY).await;
+            }
+            _ => return,
+        }
+    }
+}
+
+async fn start_periodic_task(state: Arc<ServerState>) {
+    let mut rate = state.tick_rate_override.subscribe();
+    let mut budget = loop_budget(&state.config(), *rate.borrow_and_update());
+    let mut interval = interval(budget);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            Ok(()) = rate.changed() => {
+                budget = loop_budget(&state.config(), *rate.borrow_and_update());
+                interval = tokio::time::interval(budget);
+                continue;
+            }
+        }
+        let started = Instant::now();
+        let FrameReport {
+            worst_game,
+            phases,
+            forfeits,
+            time_ups,
+            active,
+            dormant,
+        } = handle_frame(state.games.clone(), &state.events).await;
+        let elapsed = started.elapsed();
+        let slow = state
+            .ticks
+            .lock()
+            .unwrap()
+            .record(elapsed, phases, worst_game);
+        state.ticks_completed.fetch_add(1, Ordering::Relaxed);
+        state
+            .last_tick_us
+            .store(state.clock_us(), Ordering::Relaxed);
+        if slow {
+            let (game_id, game_time) = worst_game.unwrap_or((0, Duration::ZERO));
+            println!(
+                "Slow tick: {:?} over a {:?} budget, worst game {} took {:?} ({:?})",
+                elapsed, budget, game_id, game_time, phases
+            );
+        }
+        for (game_id, player_index) in forfeits {
+            let game = state.games.read().await.get(&game_id).cloned();
+            if let Some(game) = game {
+                println!(
+                    "Player {} didn't come back to game {} in time",
+                    player_index, game_id
+                );
+                leave_game(&state, game_id, &game, player_index).await;
+            }
+        }
+        for game_id in time_ups {
+            let game = state.games.read().await.get(&game_id).cloned();
+            if let Some(game) = game {
+                end_on_time(&state, game_id, &game).await;
+            }
+        }
+        state.active_games.store(active, Ordering::Relaxed);
+        state.dormant_games.store(dormant.len(), Ordering::Relaxed);
+        let dormant_timeout = state.config().dormant_timeout;
+        for (game_id, _) in dormant
+            .into_iter()
+            .filter(|(_, idle)| *idle >= dormant_timeout)
+        {
+            let game = state.games.read().await.get(&game_id).cloned();
+            if let Some(game) = game {
+                // someone may have come back since the tick looked
+                let missing = {
+                    let game = game.read().await;
+                    if game.live_connections() > 0 {
+                        continue;
+                    }
+                    match game.phase {
+                        GamePhase::WaitingForPlayers { missing, .. } => Some(missing),
+                        _ => {
+                            game.close();
+                            None
+                        }
+                    }
+                };
+                println!(
+                    "Removing game {} after {:?} with nobody connected",
+                    game_id, dormant_timeout
+                );
+                match missing {
+                    // a match everyone walked out of: the first to go
+                    // forfeits, as they would have with the other still there
+                    Some(missing) => leave_game(&state, game_id, &game, missing).await,
+                    None => remove_game(&state, game_id, &game).await,
+                }
+            }
+        }
+    }
+}
+
+// What a tick found beyond moving the games along.
+pub struct FrameReport {
+    // the slowest game's id and update time
+    pub worst_game: Option<(usize, Duration)>,
+    // every game's update time, by phase
+    pub phases: PhaseTimes,
+    // (game id, slot) for players who ran out their reconnect wait and
+    // forfeit
+    pub forfeits: Vec<(usize, usize)>,
+    // games whose match clock ran out
+    pub time_ups: Vec<usize>,
+    // games that were stepped
+    pub active: usize,
+    // (game id, how long) for games skipped because nobody is connected
+    pub dormant: Vec<(usize, Duration)>,
+}
+
+// Steps every game once. Forfeits and dormant games are only reported, and
+// looked up in the map again before anything is done about them, as a game
+// can be removed while the frame is still running.
+pub async fn handle_frame(games: Games, events: &ServerEvents) -> FrameReport {
+    let mut worst_game: Option<(usize, Duration)> = None;
+    let mut phases = PhaseTimes::default();
+    let mut forfeits = vec![];
+    let mut time_ups = vec![];
+    let (mut active, mut dormant) = (0, vec![]);
+    // copied out so the map isn't held while each game's lock is awaited,
+    // which would keep every join and removal waiting on the whole frame
+    let list: Vec<(usize, Arc<RwLock<Game>>)> = games
+        .read()
+        .await
+        .iter()
+        .map(|(game_id, game)| (*game_id, Arc::clone(game)))
+        .collect();
+    for (game_id, value) in list {
+        let mut game = value.write().await;
+        // removed since the list was taken
+        if game.is_closed() {
+            continue;
+        }
+        let started = Instant::now();
+        game.update();
+        let elapsed = started.elapsed();
+        phases.add(game.phase_times());
+        match game.dormant_for() {
+            Some(idle) => dormant.push((game_id, idle)),
+            None => active += 1,
+        }
+        if worst_game.map_or(true, |(_, worst)| elapsed > worst) {
+            worst_game = Some((game_id, elapsed));
+        }
+        if let Some(player_index) = game.take_forfeit() {
+            forfeits.push((game_id, player_index));
+        }
+        if game.take_time_up() {
+            time_ups.push(game_id);
+        }
+        let goals = game.take_goals();
+        if !goals.is_empty() {
+            game.broadcast_replay();
+        }
+        for player_index in goals {
+            let _ = events.send(ServerEvent::GoalScored {
+                game_id,
+                player_index,
+            });
+        }
+        if let Some(Divergence { tick, body }) = game.take_divergence() {
+            log::warn!(
+                "Game {} diverged from its audit shadow at tick {}, body {:?}",
+                game_id,
+                tick,
+                body
+            );
+            let _ = events.send(ServerEvent::SimulationDiverged {
+                game_id,
+                tick,
+                body,
+            });
+        }
+    }
+    return FrameReport {
+        worst_game,
+        phases,
+        forfeits,
+        time_ups,
+        active,
+        dormant,
+    };
+}
+
+pub async fn handle_message(
+    ws_msg: WsMessage,
+    client: &mut Client,
+    conn_info: &ConnectionInfo,
+    game: &Arc<RwLock<Game>>,
+    state: &ServerState,
+) -> Response {
+    match ws_msg.msg_type {
+        MessageType::Ping => {
+            client.update_ping();
+            return Response::Reply(WsMessage {
+                msg_type: MessageType::Pong,
+                payload: vec![],
+            });
+        }
+        MessageType::Pong => {
+            let rtt = ws_msg
+                .decode::<PingMessage>()
+                .and_then(|pong| client.record_pong(pong.id));
+            match rtt {
+                Some(_) => {
+                    let rtt_ms = client.rtt.map(|rtt| rtt.as_millis() as u32);
+                    game.write().await.set_rtt(conn_info.player_index, rtt_ms);
+                }
+                // counted in the metrics; a client can send these as fast
+                // as it likes, so they're only logged at debug
+                None => {
+                    UNSOLICITED_PONGS.fetch_add(1, Ordering::Relaxed);
+                    log::debug!("Unsolicited Pong from client {}", client.id);
+                }
+            }
+        }
+        MessageType::State => {
+            let game = game.read().await;
+            // asked for, so sent in full
+            let full = DegradeLevel::Full;
+            if let Some(snapshot) = state_message(&game, conn_info, &client.interest, state, full) {
+                return Response::Reply(snapshot);
+            }
+        }
+        MessageType::SetInterest => {
+            let interest = match ws_msg.decode::<SetInterestMessage>() {
+                Some(interest) => interest,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            let soccer = game.read().await.downcast::<SoccerGame>().is_some();
+            if !soccer && !interest.is_full() {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::InvalidParams,
+                    "Only soccer snapshots can be narrowed",
+                ));
+            }
+            client.interest = interest;
+        }
+        MessageType::Chat => {
+            let mut chat = match ws_msg.decode::<ChatMessage>() {
+                Some(chat) => chat,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            let text_len = chat.text.chars().count();
+            if text_len == 0 || text_len > MAX_CHAT_LEN {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::InvalidParams,
+                    &format!("Chat must be 1 to {} characters", MAX_CHAT_LEN),
+                ));
+            }
+            let from = conn_info.player_index;
+            let game = game.read().await;
+            let team = match chat.scope {
+                ChatScope::All => None,
+                ChatScope::Team => match game.logic.team_of(from) {
+                    Some(team) => Some(team),
+                    None => {
+                        return Response::Reply(WsMessage::error(
+                            ErrorCode::NoTeams,
+                            "This game has no teams to chat with",
+                        ))
+                    }
+                },
+            };
+            chat.from = from as u8;
+            game.send_chat(from, team, &chat);
+        }
+        MessageType::Mute => {
+            let mute = match ws_msg.decode::<MuteMessage>() {
+                Some(mute) => mute,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            let slot = mute.slot as usize;
+            if slot == conn_info.player_index {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::InvalidParams,
+                    "You can't mute yourself",
+                ));
+            }
+            let mut game = game.write().await;
+            if game.player(slot).is_none() {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::InvalidParams,
+                    &format!("No player in slot {}", slot),
+                ));
+            }
+            if let Some(player) = game
+                .players
+                .iter_mut()
+                .find(|p| p.index == conn_info.player_index)
+            {
+                player.muted.insert(slot);
+            }
+        }
+        MessageType::Boost => {
+            let boost = match ws_msg.decode::<BoostMessage>() {
+                Some(boost) => boost,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            // applied by the tick loop like a move, which sends back a
+            // refusal
+            if let Some(link) = &client.commands {
+                if !link.send_boost(conn_info.player_index, &boost) {
+                    log::debug!("Dropped a boost from client {}", client.id);
+                }
+            }
+        }
+        MessageType::SoccerMove => {
+            let soccer_move_message =
+                match SoccerMoveMessage::decode(conn_info.byte_order, &ws_msg.payload) {
+                    Some(message) => message,
+                    None => {
+                        return Response::Close(CloseReason::ProtocolViolation);
+                    }
+                };
+            state
+                .hooks()
+                .on_move(
+                    client.middleware.ctx(),
+                    conn_info.player_index,
+                    &soccer_move_message,
+                )
+                .await;
+            // applied by the tick loop before the next step; a full queue
+            // drops the move like a lost packet
+            if let Some(link) = &client.commands {
+                if !link.send_move(conn_info.player_index, &soccer_move_message) {
+                    log::debug!("Dropped a move from client {}", client.id);
+                }
+            }
+        }
+        MessageType::LockstepInput => {
+            let input = match ws_msg.decode::<LockstepInputMessage>() {
+                Some(input) => input,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            // checked against the game's tick by the tick loop, which sends
+            // back a refusal
+            if let Some(link) = &client.commands {
+                if !link.send_input(conn_info.player_index, input) {
+                    log::debug!("Dropped lockstep input from client {}", client.id);
+                }
+            }
+        }
+        MessageType::LeaveGame => {
+            let owns_slot = match ws_msg.decode::<LeaveGameMessage>() {
+                Some(leave) => game
+                    .read()
+                    .await
+                    .check_session(conn_info.player_index, &leave.session_token),
+                None => false,
+            };
+            if !owns_slot {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::Unauthorized,
+                    "LeaveGame requires the session token",
+                ));
+            }
+            return Response::Leave;
+        }
+        MessageType::Ready => {
+            game.write().await.mark_ready(conn_info.player_index);
+        }
+        MessageType::SetFormation => {
+            let request = match ws_msg.decode::<SetFormationMessage>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            let formation = match Formation::from_name(&request.formation) {
+                Some(formation) => formation,
+                None => {
+                    let names: Vec<&str> = Formation::ALL.iter().map(|f| f.name()).collect();
+                    return Response::Reply(WsMessage::error(
+                        ErrorCode::InvalidParams,
+                        &format!(
+                            "Unknown formation '{}', expected one of {}",
+                            request.formation,
+                            names.join(", ")
+                        ),
+                    ));
+                }
+            };
+            let mut game_lock = game.write().await;
+            if !matches!(game_lock.phase, GamePhase::ReadyCheck { .. }) {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::FormationLocked,
+                    "Formations are picked before the match starts",
+                ));
+            }
+            let set = match game_lock.downcast_mut::<SoccerGame>() {
+                Some(soccer_game) => soccer_game.set_formation(conn_info.player_index, formation),
+                None => Err("This game type has no formations".to_string()),
+            };
+            if let Err(reason) = set {
+                return Response::Reply(WsMessage::error(ErrorCode::InvalidParams, &reason));
+            }
+            game_lock.audit_input(AuditInput::Formation {
+                player: conn_info.player_index,
+                formation,
+            });
+        }
+        MessageType::PauseRequest => {
+            if let Err(code) = game.write().await.request_pause(conn_info.player_index) {
+                return Response::Reply(WsMessage::error(code, "Pause not available"));
+            }
+        }
+        MessageType::ResumeRequest => {
+            if let Err(code) = game.write().await.request_resume(conn_info.player_index) {
+                return Response::Reply(WsMessage::error(code, "Game is not paused"));
+            }
+        }
+        MessageType::Subscribe => {
+            let requested = if ws_msg.payload.is_empty() {
+                state.config().default_state_rate_hz
+            } else {
+                match ws_msg.decode::<SubscribeMessage>() {
+                    Some(subscribe) => subscribe.state_rate_hz,
+                    None => return Response::Close(CloseReason::ProtocolViolation),
+                }
+            };
+            let rate = requested.clamp(
+                state.config().min_state_rate_hz,
+                state.config().max_state_rate_hz,
+            );
+            game.write()
+                .await
+                .set_state_rate(conn_info.player_index, rate);
+        }
+        MessageType::ServerInfo => {
+            return Response::Reply(WsMessage::from_payload(
+                MessageType::ServerInfo,
+                &ServerInfoMessage {
+                    game_types: state.game_types(),
+                    ..server_info(&state.config())
+                },
+            ));
+        }
+        MessageType::Version => {
+            return Response::Reply(version());
+        }
+        MessageType::WhoAmI => {
+            return Response::Reply(WsMessage::from_payload(
+                MessageType::WhoAmI,
+                &conn_info.who_am_i(),
+            ));
+        }
+        MessageType::GetStats => {
+            let response = if state.config().collect_stats {
+                let stats = state.stats.read().await;
+                StatsResponse {
+                    record: stats.get(&conn_info.player_id),
+                    leaderboard: stats.leaderboard(10),
+                }
+            } else {
+                StatsResponse {
+                    record: PlayerRecord::default(),
+                    leaderboard: vec![],
+                }
+            };
+            return Response::Reply(WsMessage::from_payload(MessageType::GetStats, &response));
+        }
+        MessageType::Announce => {
+            return Response::Reply(announce(state, conn_info, &ws_msg));
+        }
+        MessageType::Roster => {
+            let request = match ws_msg.decode::<RosterRequest>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            return Response::Reply(roster(state, client, conn_info, game, request).await);
+        }
+        MessageType::ReloadConfig => {
+            if !state.is_admin(conn_info) {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::Unauthorized,
+                    "ReloadConfig requires the admin token",
+                ));
+            }
+            return match reload_config(state) {
+                Ok(restart_required) => Response::Reply(WsMessage::from_payload(
+                    MessageType::ReloadConfig,
+                    &ConfigReloadedMessage { restart_required },
+                )),
+                Err(reason) => Response::Reply(WsMessage::error(ErrorCode::ReloadFailed, &reason)),
+            };
+        }
+        MessageType::SetTickRate => {
+            if let Err(refusal) = check_debug_ticks(state, conn_info, "SetTickRate") {
+                return Response::Reply(refusal);
+            }
+            let request = match ws_msg.decode::<SetTickRateMessage>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            return Response::Reply(set_tick_rate(state, request).await);
+        }
+        MessageType::StepGame => {
+            if let Err(refusal) = check_debug_ticks(state, conn_info, "StepGame") {
+                return Response::Reply(refusal);
+            }
+            let request = match ws_msg.decode::<StepGameMessage>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            return Response::Reply(step_game(state, request).await);
+        }
+        MessageType::AuditGame => {
+            if let Err(refusal) = check_debug_ticks(state, conn_info, "AuditGame") {
+                return Response::Reply(refusal);
+            }
+            let request = match ws_msg.decode::<AuditGameMessage>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            return Response::Reply(audit_game(state, request).await);
+        }
+        MessageType::FreezeOpponent => {
+            if let Err(refusal) = check_debug_ticks(state, conn_info, "FreezeOpponent") {
+                return Response::Reply(refusal);
+            }
+            let request = match ws_msg.decode::<FreezeOpponentMessage>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            return Response::Reply(freeze_opponent(state, request).await);
+        }
+        MessageType::SetGameParams => {
+            if !state.is_admin(conn_info) {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::Unauthorized,
+                    "SetGameParams requires the admin token",
+                ));
+            }
+            let request = match SetGameParamsMessage::decode(&ws_msg.payload) {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            if let Err(reason) = validate_params(&request.params) {
+                return Response::Reply(WsMessage::error(ErrorCode::InvalidParams, reason));
+            }
+            return set_game_params(state, &request).await;
+        }
+        MessageType::TuneSoccer => {
+            if !state.is_admin(conn_info) {
+                return Response::Reply(WsMessage::error(
+                    ErrorCode::Unauthorized,
+                    "TuneSoccer requires the admin token",
+                ));
+            }
+            let request = match ws_msg.decode::<TuneSoccerMessage>() {
+                Some(request) => request,
+                None => return Response::Close(CloseReason::ProtocolViolation),
+            };
+            return tune_soccer(state, &request).await;
+        }
+        _ => {
+            println!("Received message type: {:?}", ws_msg.msg_type);
+        }
+    }
+    return Response::Nothing;
+}
+
+// SetTickRate, StepGame, AuditGame and FreezeOpponent need the admin token
+// and debug_ticks on.
+fn check_debug_ticks(
+    state: &ServerState,
+    conn_info: &ConnectionInfo,
+    what: &str,
+) -> Result<(), WsMessage> {
+    if !state.is_admin(conn_info) {
+        return Err(WsMessage::error(
+            ErrorCode::Unauthorized,
+            &format!("{} requires the admin token", what),
+        ));
+    }
+    if !state.config().debug_ticks {
+        return Err(WsMessage::error(
+            ErrorCode::DebugDisabled,
+            &format!("{} needs debug_ticks on", what),
+        ));
+    }
+    return Ok(());
+}
+
+// the slowest a debug tick rate goes, and the fastest the whole loop can
+// be set to
+const MIN_DEBUG_HZ: f32 = 0.1;
+const MAX_DEBUG_HZ: f32 = 1000.0;
+// the most updates one StepGame advances a game by
+const MAX_STEPS: u32 = 600;
+
+async fn find_game(state: &ServerState, game_id: u32) -> Result<Arc<RwLock<Game>>, WsMessage> {
+    return match state.games.read().await.get(&(game_id as usize)) {
+        Some(game) => Ok(game.clone()),
+        None => Err(WsMessage::error(
+            ErrorCode::GameNotFound,
+            "No running game with that id",
+        )),
+    };
+}
+
+// A game can't be ticked faster than the loop that ticks it, so at the
+// configured rate or above it just goes back to normal.
+async fn set_tick_rate(state: &ServerState, request: SetTickRateMessage) -> WsMessage {
+    let config = state.config();
+    let hz = request.hz;
+    let fastest = match request.game_id {
+        Some(_) => config.tick_rate as f32,
+        None => MAX_DEBUG_HZ,
+    };
+    if hz != 0.0 && !(MIN_DEBUG_HZ..=fastest).contains(&hz) {
+        return WsMessage::error(
+            ErrorCode::InvalidParams,
+            &format!("hz must be 0 or from {} to {}", MIN_DEBUG_HZ, fastest),
+        );
+    }
+    let normal = hz == 0.0 || hz == config.tick_rate as f32;
+    let effective = if hz == 0.0 {
+        config.tick_rate as f32
+    } else {
+        hz
+    };
+    match request.game_id {
+        Some(game_id) => {
+            let game = match find_game(state, game_id).await {
+                Ok(game) => game,
+                Err(error) => return error,
+            };
+            let mode = if normal {
+                TickMode::Normal
+            } else {
+                TickMode::Slowed {
+                    every: Duration::from_secs_f32(1.0 / hz),
+                }
+            };
+            game.write().await.set_tick_mode(mode);
+            println!("Game {} now ticking {}", game_id, mode.as_str());
+        }
+        None => {
+            let rate = if normal { None } else { Some(hz) };
+            state.tick_rate_override.send_replace(rate);
+            let games: Vec<_> = state.games.read().await.values().cloned().collect();
+            for game in games {
+                game.write().await.slow_motion = rate.is_some();
+            }
+            println!("Tick loop now at {} hz", effective);
+        }
+    }
+    return WsMessage::from_payload(
+        MessageType::SetTickRate,
+        &SetTickRateMessage {
+            game_id: request.game_id,
+            hz: effective,
+        },
+    );
+}
+
+// Takes a game off the tick loop and steps it by hand, a tick budget apart
+// so every connection sees each step and sends its snapshot. The steps are
+// taken on a task of their own, so the reply goes out straight away with
+// the tick they end on.
+async fn step_game(state: &ServerState, request: StepGameMessage) -> WsMessage {
+    if request.steps > MAX_STEPS {
+        return WsMessage::error(
+            ErrorCode::InvalidParams,
+            &format!("At most {} steps at a time", MAX_STEPS),
+        );
+    }
+    let game = match find_game(state, request.game_id).await {
+        Ok(game) => game,
+        Err(error) => return error,
+    };
+    let tick = {
+        let mut game = game.write().await;
+        game.set_tick_mode(TickMode::Manual);
+        game.tick() + request.steps as u64
+    };
+    let budget = tick_budget(&state.config());
+    tokio::spawn(async move {
+        for _ in 0..request.steps {
+            sleep(budget).await;
+            // put back to ticking on its own meanwhile
+            if !game.write().await.step() {
+                break;
+            }
+        }
+    });
+    return WsMessage::from_payload(
+        MessageType::StepGame,
+        &GameSteppedMessage {
+            game_id: request.game_id,
+            tick,
+        },
+    );
+}
+
+async fn audit_game(state: &ServerState, request: AuditGameMessage) -> WsMessage {
+    let game = match find_game(state, request.game_id).await {
+        Ok(game) => game,
+        Err(error) => return error,
+    };
+    let mut game = game.write().await;
+    if request.every == 0 {
+        if game.stop_audit() {
+            println!("Game {} no longer audited", request.game_id);
+        }
+    } else {
+        if let Err(reason) = game.start_audit(request.every as u64) {
+            return WsMessage::error(ErrorCode::InvalidParams, reason);
+        }
+        println!(
+            "Game {} audited for determinism every {} ticks",
+            request.game_id, request.every
+        );
+    }
+    return WsMessage::from_payload(MessageType::AuditGame, &request);
+}
+
+async fn freeze_opponent(state: &ServerState, request: FreezeOpponentMessage) -> WsMessage {
+    let game = match find_game(state, request.game_id).await {
+        Ok(game) => game,
+        Err(error) => return error,
+    };
+    let mut game = game.write().await;
+    let player = request.player as usize;
+    let froze = match game.downcast_mut::<SoccerGame>() {
+        Some(soccer_game) => soccer_game.freeze_pucks(player, request.frozen),
+        None => {
+            return WsMessage::error(ErrorCode::InvalidParams, "Only soccer pucks can be frozen")
+        }
+    };
+    if !froze {
+        return WsMessage::error(ErrorCode::InvalidParams, "No such player in that game");
+    }
+    game.audit_input(AuditInput::Freeze {
+        player,
+        frozen: request.frozen,
+    });
+    println!(
+        "Game {}: player {}'s pucks {}",
+        request.game_id,
+        player,
+        if request.frozen { "frozen" } else { "released" }
+    );
+    return WsMessage::from_payload(MessageType::FreezeOpponent, &request);
+}
+
+// The slots of the sender's own game, or of any game for an admin.
+async fn roster(
+    state: &ServerState,
+    client: &Client,
+    conn_info: &ConnectionInfo,
+    game: &Arc<RwLock<Game>>,
+    request: RosterRequest,
+) -> WsMessage {
+    let own_id = client.middleware.ctx().game_id();
+    let game_id = request.game_id.map_or(own_id, |id| Some(id as usize));
+    let (game_id, game) = match game_id {
+        Some(game_id) if Some(game_id) == own_id => (game_id, game.clone()),
+        Some(game_id) => {
+            if !state.is_admin(conn_info) {
+                return WsMessage::error(
+                    ErrorCode::Unauthorized,
+                    "Another game's Roster requires the admin token",
+                );
+            }
+            match state.games.read().await.get(&game_id).cloned() {
+                Some(game) => (game_id, game),
+                None => {
+                    return WsMessage::error(
+                        ErrorCode::GameNotFound,
+                        "No running game with that id",
+                    )
+                }
+            }
+        }
+        None => return WsMessage::error(ErrorCode::GameNotFound, "Not in a game"),
+    };
+    let slots = game.read().await.roster();
+    return WsMessage::from_payload(
+        MessageType::Roster,
+        &RosterMessage {
+            game_id: game_id as u32,
+            slots: slots.iter().map(PlayerSlot::to_entry).collect(),
+        },
+    );
+}
+
+async fn set_game_params(state: &ServerState, request: &SetGameParamsMessage) -> Response {
+    // the game is checked first so a bad id changes nothing, new games
+    // included
+    let game = match request.game_id {
+        Some(game_id) => {
+            let game = state.games.read().await.get(&(game_id as usize)).cloned();
+            match game {
+                Some(game) => Some((game_id, game.write_owned().await)),
+                None => {
+                    return Response::Reply(WsMessage::error(
+                        ErrorCode::GameNotFound,
+                        "No running game with that id",
+                    ))
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some((_, game)) = &game {
+        if game.downcast::<SoccerGame>().is_none() {
+            return Response::Reply(WsMessage::error(
+                ErrorCode::WrongGameType,
+                "Only soccer games take game params",
+            ));
+        }
+    }
+    if request.handicap.is_some() {
+        let refusal = match &game {
+            None => Some("A handicap is for one game; set game_id"),
+            Some((_, game)) if !matches!(game.phase, GamePhase::ReadyCheck { .. }) => {
+                Some("A handicap is set before kickoff")
+            }
+            Some(_) => None,
+        };
+        if let Some(refusal) = refusal {
+            return Response::Reply(WsMessage::error(ErrorCode::InvalidParams, refusal));
+        }
+    }
+    // a handicap alone leaves new games, and their tuning version, be
+    if request.params != GameParams::default() {
+        let mut soccer = state.soccer.lock().unwrap();
+        let old = soccer.config.params();
+        let mut config = soccer.config.clone();
+        config.apply_params(&request.params);
+        log_param_changes("new games", &old, &config.params());
+        soccer.replace(config);
+    }
+    if let Some((game_id, mut game)) = game {
+        let soccer_game = game.downcast_mut::<SoccerGame>().unwrap();
+        if let Some(handicap) = request.handicap {
+            soccer_game.set_handicap(handicap);
+            println!(
+                "Game {} handicap set to {:?}",
+                game_id, soccer_game.handicap
+            );
+        }
+        let old = soccer_game.params();
+        soccer_game.apply_params(&request.params);
+        let params = soccer_game.params();
+        log_param_changes(&format!("game {}", game_id), &old, &params);
+        game.audit_input(AuditInput::Params(request.params));
+        game.broadcast(WsMessage::from_payload(
+            MessageType::GameParamsChanged,
+            &params,
+        ));
+    }
+    // echoed back as the acknowledgement
+    return Response::Reply(WsMessage::from_payload(MessageType::SetGameParams, request));
+}
+
+async fn tune_soccer(state: &ServerState, request: &TuneSoccerMessage) -> Response {
+    // the section goes on top of the current config, under the lock so two
+    // tunes at once both land
+    let tuned = {
+        let mut soccer = state.soccer.lock().unwrap();
+        toml::from_str::<SoccerSection>(&request.soccer)
+            .map_err(ConfigError::Parse)
+            .and_then(|section| section.apply_to(soccer.config.clone()))
+            .and_then(|config| {
+                config.validate().map_err(ConfigError::Invalid)?;
+                return Ok(config);
+            })
+            .map(|config| {
+                log_param_changes("new games", &soccer.config.params(), &config.params());
+                let version = soccer.replace(config.clone());
+                return (config, version);
+            })
+    };
+    let (config, version) = match tuned {
+        Ok(tuned) => tuned,
+        Err(e) => {
+            return Response::Reply(WsMessage::error(ErrorCode::InvalidParams, &e.to_string()))
+        }
+    };
+    println!("Soccer tuning v{} for new games", version);
+    let mut reapplied = vec![];
+    if request.reapply {
+        let games: Vec<(usize, Arc<RwLock<Game>>)> = state
+            .games
+            .read()
+            .await
+            .iter()
+            .map(|(id, game)| (*id, game.clone()))
+            .collect();
+        for (game_id, game) in games {
+            let mut game = game.write().await;
+            let params = match game.downcast_mut::<SoccerGame>() {
+                Some(soccer_game) => {
+                    soccer_game.retune(&config);
+                    soccer_game.tuning_version = version;
+                    soccer_game.params()
+                }
+                None => continue,
+            };
+            game.audit_input(AuditInput::Retune(Box::new(config.clone())));
+            game.broadcast(WsMessage::from_payload(
+                MessageType::GameParamsChanged,
+                &params,
+            ));
+            reapplied.push(game_id as u32);
+        }
+        println!("Retuned {} running games to v{}", reapplied.len(), version);
+    }
+    return Response::Reply(WsMessage::from_payload(
+        MessageType::TuneSoccer,
+        &SoccerTunedMessage { version, reapplied },
+    ));
+}
+
+fn log_param_changes(target: &str, old: &GameParams, new: &GameParams) {
+    for ((name, old), (_, new)) in old.fields().iter().zip(new.fields().iter()) {
+        if old != new {
+            println!(
+                "Params for {}: {} {} -> {}",
+                target,
+                name,
+                old.unwrap_or_default(),
+                new.unwrap_or_default()
+            );
+        }
+    }
+}
+
+// The client's address for a connection from peer. Behind a trusted proxy
+// that is the rightmost X-Forwarded-For entry the proxies didn't add
+// themselves, without a port; anyone else's header is ignored, as is one
+// that doesn't parse.
+pub fn forwarded_peer(
+    peer: SocketAddr,
+    forwarded_for: Option<&str>,
+    trusted: &[IpAddr],
+) -> SocketAddr {
+    if !trusted.contains(&peer.ip()) {
+        return peer;
+    }
+    let client = forwarded_for.and_then(|header| {
+        header
+            .rsplit(',')
+            .map(|entry| entry.trim().parse::<IpAddr>())
+            .find(|entry| !matches!(entry, Ok(ip) if trusted.contains(ip)))
+            .and_then(Result::ok)
+    });
+    return match client {
+        Some(ip) => SocketAddr::new(ip, 0),
+        None => peer,
+    };
+}
+
+// The token in an auth header, checked before anything is copied out of
+// it: too long is 431, a token that isn't all visible ASCII is 400, and with
+// an auth_url a value without the auth_scheme in front is 401.
+fn auth_token(value: &HeaderValue, config: &ServerConfig) -> Result<String, (StatusCode, String)> {
+    if value.len() > config.max_auth_header_bytes {
+        return Err((
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            format!(
+                "{} is over {} bytes",
+                config.auth_header, config.max_auth_header_bytes
+            ),
+        ));
+    }
+    let invalid = || {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("{} must be visible ASCII", config.auth_header),
+        );
+    };
+    let value = value.to_str().map_err(|_| invalid())?.trim();
+    let scheme = config.auth_scheme.as_deref();
+    let mut token = strip_auth_scheme(value, scheme);
+    // the parser trims "Bearer " down to the bare scheme
+    if scheme.map_or(false, |scheme| value.eq_ignore_ascii_case(scheme)) {
+        token.clear();
+    }
+    if let (Some(scheme), Some(_)) = (scheme, &config.auth_url) {
+        if token.len() == value.len() {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                format!("{} must be \"{} <token>\"", config.auth_header, scheme),
+            ));
+        }
+    }
+    if token.is_empty() || !token.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(invalid());
+    }
+    return Ok(token);
+}
+
+// Names are what matchmaking, challenges and logs go by, so they are kept
+// short and plain: letters, digits, '_', '-', '.' and single spaces between
+// words.
+pub fn check_name(name: &str, max_len: usize) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > max_len {
+        return Err(format!(
+            "Invalid name, expected 1 to {} characters",
+            max_len
+        ));
+    }
+    let plain = name
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' '));
+    if !plain || name.starts_with(' ') || name.ends_with(' ') || name.contains("  ") {
+        return Err(format!(
+            "Invalid name '{}', expected letters, digits, '_', '-', '.' and single spaces",
+            name.escape_default()
+        ));
+    }
+    return Ok(());
+}
+
+fn reject(status: StatusCode, message: String) -> ErrorResponse {
+    let mut reject = ErrorResponse::new(Some(message));
+    *reject.status_mut() = status;
+    return reject;
+}
+
+// Origins are compared without regard to case or a trailing '/'; an empty
+// list allows any.
+pub fn origin_allowed(origin: &str, allowed: &[String]) -> bool {
+    let origin = origin.trim_end_matches('/');
+    return allowed.is_empty()
+        || allowed
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin));
+}
+
+// The token part of an auth header value. The scheme is matched without
+// regard to case; a value without it is taken whole.
+pub fn strip_auth_scheme(value: &str, scheme: Option<&str>) -> String {
+    let value = value.trim();
+    if let Some(scheme) = scheme {
+        if let Some((prefix, token)) = value.split_once(' ') {
+            if prefix.eq_ignore_ascii_case(scheme) {
+                return token.trim_start().to_string();
+            }
+        }
+    }
+    return value.to_string();
+}
+
+// The furthest a connection's snapshots are cut back. Before v9, or as
+// JSON, it can't be told the level, nor be sent quantized State or
+// PartialState it didn't ask for, so only the rate drops.
+fn deepest_degrade(conn_info: &ConnectionInfo) -> DegradeLevel {
+    if conn_info.state_version >= ProtocolVersion::V9 && conn_info.format != StateFormat::Json {
+        return DegradeLevel::InterestOnly;
+    }
+    return DegradeLevel::HalfRate;
+}
+
+// level is how far the connection's snapshots are cut back: from Quantized
+// on a binary v9 connection gets quantized State, and at InterestOnly a
+// full interest is narrowed to the ball and the connection's own pucks.
+// v9 is told the level in the header.
+fn state_message(
+    game: &Game,
+    conn_info: &ConnectionInfo,
+    interest: &SetInterestMessage,
+    state: &ServerState,
+    level: DegradeLevel,
+) -> Option<WsMessage> {
+    let narrowed = SetInterestMessage {
+        bodies: 0,
+        groups: InterestGroup::MyPucks.bit() | InterestGroup::Ball.bit(),
+    };
+    let interest = match level {
+        DegradeLevel::InterestOnly
+            if interest.is_full() && deepest_degrade(conn_info) == DegradeLevel::InterestOnly =>
+        {
+            &narrowed
+        }
+        _ => interest,
+    };
+    if let (false, Some(soccer_game)) = (interest.is_full(), game.downcast::<SoccerGame>()) {
+        let interest = soccer_game.interest_mask(conn_info.player_index, interest);
+        return Some(partial_state(game, interest, conn_info, level));
+    }
+    let quantize = conn_info.format == StateFormat::Quantized
+        || (conn_info.format == StateFormat::Binary
+            && conn_info.state_version >= ProtocolVersion::V9
+            && level >= DegradeLevel::Quantized);
+    let body = match game.downcast::<SoccerGame>() {
+        Some(soccer_game) => {
+            let view = StateView {
+                player: Some(conn_info.player_index),
+                ack_seq: game
+                    .player(conn_info.player_index)
+                    .map_or(0, |p| p.last_move_seq),
+                boost_cooldown_ms: soccer_game.boost_remaining_ms(conn_info.player_index),
+                tick: game.tick() as u32,
+                server_time_us: state.clock_us(),
+                match_phase: game.match_phase(),
+            };
+            let compact = CompactBinary(conn_info.state_version, conn_info.byte_order);
+            let quantized = Quantized(conn_info.state_version, conn_info.byte_order);
+            let serializer: &dyn StateSerializer = match (conn_info.format, quantize) {
+                (StateFormat::Json, _) => &Json,
+                (_, true) => &quantized,
+                (_, false) => &compact,
+            };
+            soccer_game.serialize(serializer, &view)
+        }
+        // other game types have no views or formats yet
+        None => game.snapshot().to_vec(),
+    };
+    // v8 leads every binary State with the same header, whatever the game
+    let payload = match (conn_info.state_version, conn_info.format) {
+        (
+            ProtocolVersion::V8 | ProtocolVersion::V9 | ProtocolVersion::V10 | ProtocolVersion::V11,
+            StateFormat::Binary | StateFormat::Quantized,
+        ) => {
+            let mut header = game.snapshot_header();
+            if conn_info.state_version >= ProtocolVersion::V9 {
+                header.level = Some(level);
+            }
+            let mut payload = header.to_bytes();
+            conn_info.byte_order.swap_snapshot_header(&mut payload);
+            payload.extend_from_slice(&body);
+            payload
+        }
+        _ => body,
+    };
+    return Some(WsMessage {
+        msg_type: MessageType::State,
+        payload,
+    });
+}
+
+// A PartialState cut from the game's shared snapshot, which is built once a
+// tick however many connections read it: soccer's is the v1 layout, an
+// (x, y) f32 pair per body in State order.
+fn partial_state(
+    game: &Game,
+    interest: u64,
+    conn_info: &ConnectionInfo,
+    level: DegradeLevel,
+) -> WsMessage {
+    let snapshot = game.snapshot();
+    let mut header = game.snapshot_header();
+    if conn_info.state_version >= ProtocolVersion::V9 {
+        header.level = Some(level);
+    }
+    let mut payload = header.to_bytes();
+    let header_len = payload.len();
+    payload.extend_from_slice(&[0; 8]);
+    let mut sent = 0u64;
+    for (body, position) in snapshot.chunks_exact(8).enumerate().take(64) {
+        if interest & (1 << body) != 0 {
+            payload.extend_from_slice(position);
+            sent |= 1 << body;
+        }
+    }
+    // bodies the snapshot came up short of are left out of the echo too
+    payload[header_len..header_len + 8].copy_from_slice(&sent.to_le_bytes());
+    conn_info
+        .byte_order
+        .swap_partial_state(conn_info.state_version, &mut payload);
+    return WsMessage {
+        msg_type: MessageType::PartialState,
+        payload,
+    };
+}
+
+async fn handle_connection(stream: TcpStream, peer: SocketAddr, state: Arc<ServerState>) {
+    let ip = peer.ip();
+    let client_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    // dropped without a handshake; a banned client gets nothing to parse
+    let _slot = match state.ip_limiter.admit(
+        ip,
+        state.config().max_connections_per_ip,
+        state.config().protocol_ban,
+    ) {
+        Ok(slot) => slot,
+        Err(Refusal::Banned { remaining }) => {
+            log::info!("Refused {}: banned for another {:?}", ip, remaining);
+            return;
+        }
+        Err(Refusal::TooManyConnections) => {
+            log::info!("Refused {}: too many connections", ip);
+            return;
+        }
+    };
+    let connected_at = SystemTime::now();
+    let mut conn_info = ConnectionInfo {
+        ip,
+        peer,
+        auth_token: None,
+        game: None,
+        name: None,
+        player_index: 0,
+        protocol: ProtocolVersion::V1,
+        state_version: ProtocolVersion::V1,
+        game_type: SOCCER_GAME_TYPE,
+        session_token: None,
+        game_token: None,
+        practice: false,
+        lobby: false,
+        format: StateFormat::Binary,
+        byte_order: ByteOrder::Little,
+        player_id: PlayerId::Guest(String::new()),
+        firehose: false,
+    };
+    let mut client = Client::new(client_id);
+    client.middleware = MiddlewareChain::new(state.middleware(), ConnCtx::new(client_id, ip));
+    let config = state.config();
+    client.echo_budget = Mutex::new(TokenBucket::new(config.echo_burst, config.echo_rate_hz));
+    client.send_timeout = config.send_timeout;
+    state.connections.insert(client.slot_connection());
+    serve_connection(stream, &state, &mut client, &mut conn_info).await;
+    state.connections.remove(client_id);
+    record_disconnect(&state, &client, &conn_info, connected_at);
+}
+
+// Runs once per admitted connection, after serve_connection has returned
+// from whichever exit it took.
+fn record_disconnect(
+    state: &ServerState,
+    client: &Client,
+    conn_info: &ConnectionInfo,
+    connected_at: SystemTime,
+) {
+    let record = DisconnectRecord {
+        at: SystemTime::now(),
+        connected_at,
+        client_id: client.id,
+        name: conn_info.name.clone(),
+        identity: conn_info.player_id.to_string(),
+        peer: conn_info.peer,
+        game_id: client.middleware.ctx().game_id(),
+        reason: client.close_reason.get().copied(),
+        traffic: client.traffic.summary(),
+    };
+    state
+        .disconnects
+        .push(record.clone(), state.config().disconnect_log_size);
+    state.emit(ServerEvent::ConnectionClosed { record });
+}
+
+// A connection from the websocket handshake until it ends. Every way out
+// returns here, so handle_connection records how it ended exactly once.
+async fn serve_connection(
+    stream: TcpStream,
+    state: &Arc<ServerState>,
+    client: &mut Client,
+    conn_info: &mut ConnectionInfo,
+) {
+    let (peer, client_id) = (conn_info.peer, client.id);
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(state.config().max_message_size),
+        max_frame_size: Some(state.config().max_frame_size),
+        ..WebSocketConfig::default()
+    };
+    let handshake = accept_hdr_async_with_config(
+        stream,
+        |req: &tokio_tungstenite::tungstenite::http::Request<()>,
+         mut res: tokio_tungstenite::tungstenite::http::Response<()>| {
+            if let Some(origin) = req.headers().get("Origin") {
+                let origin = origin.to_str().unwrap_or("");
+                if !origin_allowed(origin, &state.config().allowed_origins) {
+                    log::info!("Refused {}: origin '{}' not allowed", peer, origin);
+                    return Err(reject(
+                        StatusCode::FORBIDDEN,
+                        format!("Origin '{}' is not allowed", origin),
+                    ));
+                }
+            }
+            // clients that don't ask for a subprotocol speak v1
+            if let Some(requested) = req.headers().get("Sec-WebSocket-Protocol") {
+                let requested = requested.to_str().unwrap_or("");
+                match ProtocolVersion::negotiate(requested) {
+                    Some(version) => {
+                        conn_info.protocol = version;
+                        conn_info.state_version = version;
+                        res.headers_mut().insert(
+                            "Sec-WebSocket-Protocol",
+                            HeaderValue::from_static(version.as_str()),
+                        );
+                    }
+                    None => {
+                        let supported: Vec<&str> = ProtocolVersion::SUPPORTED
+                            .iter()
+                            .map(|version| version.as_str())
+                            .collect();
+                        let message = format!(
+                            "Unsupported subprotocol '{}', expected one of: {}",
+                            requested,
+                            supported.join(", ")
+                        );
+                        return Err(reject(StatusCode::BAD_REQUEST, message));
+                    }
+                }
+            }
+            conn_info.peer = forwarded_peer(
+                peer,
+                req.headers()
+                    .get("X-Forwarded-For")
+                    .and_then(|h| h.to_str().ok()),
+                &state.config().trusted_proxies,
+            );
+            if let Some(value) = req.headers().get(state.config().auth_header.as_str()) {
+                match auth_token(value, &state.config()) {
+                    Ok(token) => conn_info.auth_token = Some(token),
+                    Err((status, message)) => return Err(reject(status, message)),
+                }
+            }
+            match req.uri().query() {
+                Some(query) => {
+                    let query_params = parse_query_params(query);
+                    if let Some(game) = query_params.get("game") {
+                        match game.parse::<usize>() {
+                            Ok(game) => conn_info.game = Some(game),
+                            Err(_) => {
+                                let message = "Invalid game, expected a game id".to_string();
+                                return Err(reject(StatusCode::BAD_REQUEST, message));
+                            }
+                        }
+                    }
+                    if let Some(name) = query_params.get("name") {
+                        if let Err(message) = check_name(name, state.config().max_name_len) {
+                            return Err(reject(StatusCode::BAD_REQUEST, message));
+                        }
+                        conn_info.name = Some(name.clone());
+                    }
+                    conn_info.session_token = query_params.get("session").cloned();
+                    conn_info.game_token = query_params.get("game_token").cloned();
+                    conn_info.practice =
+                        query_params.get("practice").map(String::as_str) == Some("1");
+                    conn_info.lobby = query_params.get("lobby").map(String::as_str) == Some("1");
+                    conn_info.firehose =
+                        query_params.get("firehose").map(String::as_str) == Some("1");
+                    if let Some(format) = query_params.get("format") {
+                        match StateFormat::from_param(format) {
+                            Some(format) => conn_info.format = format,
+                            None => {
+                                let message = format!("Invalid format '{}'", format);
+                                return Err(reject(StatusCode::BAD_REQUEST, message));
+                            }
+                        }
+                    }
+                    if let Some(order) = query_params.get("byte_order") {
+                        match ByteOrder::from_param(order) {
+                            Some(order) => conn_info.byte_order = order,
+                            None => {
+                                let message = format!("Invalid byte_order '{}'", order);
+                                return Err(reject(StatusCode::BAD_REQUEST, message));
+                            }
+                        }
+                    }
+                    let quantized = conn_info.format == StateFormat::Quantized;
+                    if quantized && conn_info.protocol < ProtocolVersion::V5 {
+                        let message = "Quantized State needs protocol v5 or later".to_string();
+                        return Err(reject(StatusCode::BAD_REQUEST, message));
+                    }
+                    if let Some(mode) = query_params.get("mode") {
+                        match state.resolve_mode(mode) {
+                            Some(game_type) => conn_info.game_type = game_type,
+                            None => {
+                                let message = format!("Invalid mode '{}'", mode);
+                                return Err(reject(StatusCode::BAD_REQUEST, message));
+                            }
+                        }
+                    }
+                }
+                None => (),
+            }
+            Ok(res)
+        },
+        Some(ws_config),
+    );
+    // a client that opens TCP and never finishes the upgrade would otherwise
+    // pin this task forever
+    let ws_stream = match timeout(state.config().handshake_timeout, handshake).await {
+        Ok(Ok(ws)) => ws,
+        Ok(Err(e)) => {
+            println!("Error during the websocket handshake: {}", e);
+            return;
+        }
+        Err(_) => {
+            println!(
+                "Client {} timed out during the websocket handshake",
+                client_id
+            );
+            return;
+        }
+    };
+    println!(
+        "Client {} connected from {} using protocol {}",
+        client_id,
+        conn_info.peer,
+        conn_info.protocol.as_str()
+    );
+    let (mut sender, mut receiver) = ws_stream.split();
+    if !authenticate(&state, &client, conn_info, &mut sender).await {
+        return;
+    }
+    client.middleware.set_name(conn_info.name.clone());
+    if conn_info.protocol >= ProtocolVersion::V6
+        && !hello(&state, &client, conn_info, &mut sender, &mut receiver).await
+    {
+        return;
+    }
+    state.hooks().on_connect(client.middleware.ctx()).await;
+    if conn_info.firehose {
+        if !state.is_admin(&conn_info) {
+            let error = WsMessage::error(
+                ErrorCode::Unauthorized,
+                "The firehose requires the admin token",
+            );
+            let _ = send_message(&mut sender, &client, &error).await;
+            close_with(&mut sender, &client, CloseReason::PolicyViolation).await;
+            return;
+        }
+        watch_all(
+            &state,
+            client_id,
+            client,
+            &conn_info,
+            &mut sender,
+            &mut receiver,
+        )
+        .await;
+        return;
+    }
+    loop {
+        client.middleware.set_game(None);
+        let joined = match join_game(&state, conn_info, client.slot_connection()).await {
+            Ok(Some(joined)) => Some(joined),
+            Ok(None) => {
+                wait_in_queue(
+                    &state,
+                    client_id,
+                    client,
+                    conn_info,
+                    &mut sender,
+                    &mut receiver,
+                )
+                .await
+            }
+            Err(code) => {
+                refuse_join(&mut sender, &client, code).await;
+                return;
+            }
+        };
+        let (game_id, game) = match joined {
+            Some(joined) => joined,
+            None => return,
+        };
+        client.middleware.set_game(Some(game_id));
+        state
+            .hooks()
+            .on_join_game(client.middleware.ctx(), conn_info.player_index)
+            .await;
+        // one guard for the lot: a second read() in the same statement
+        // would wait behind a joiner's queued write while this one holds
+        let g = game.read().await;
+        let welcome = WelcomeMessage {
+            game_id: game_id as u32,
+            player_index: conn_info.player_index as u8,
+            seed: g.seed,
+            side: Side::for_slot(conn_info.player_index) as u8,
+            session_token: g
+                .session_token(conn_info.player_index)
+                .unwrap_or_default()
+                .to_string(),
+            game_token: g.token.clone(),
+            connection: conn_info.who_am_i(),
+            phase: g.match_phase(),
+            pucks: g.logic.puck_owners(),
+            ping_interval_ms: state
+                .config()
+                .required_ping_interval
+                .map_or(0, |every| every.as_millis() as u32),
+            min_protocol: ProtocolVersion::MIN as u8,
+            max_protocol: ProtocolVersion::MAX as u8,
+            handicap: g
+                .downcast::<SoccerGame>()
+                .and_then(|soccer| soccer.handicap),
+        };
+        drop(g);
+        let welcome = WsMessage::from_payload(MessageType::Welcome, &welcome);
+        let welcomed = send_message(&mut sender, &client, &welcome).await;
+        let end = if welcomed {
+            play(
+                &state,
+                client_id,
+                game_id,
+                &game,
+                client,
+                &conn_info,
+                &mut sender,
+                &mut receiver,
+            )
+            .await
+        } else {
+            PlayEnd::Disconnected
+        };
+        match end {
+            PlayEnd::Left => {
+                state.hooks().on_leave(client.middleware.ctx(), true).await;
+                leave_game(&state, game_id, &game, conn_info.player_index).await;
+                drop(game);
+                if state.config().close_on_leave {
+                    close_with(&mut sender, &client, CloseReason::NormalLobbyExit).await;
+                    return;
+                }
+                println!(
+                    "Client {} left game {}, matchmaking again",
+                    client_id, game_id
+                );
+                conn_info.game = None;
+                conn_info.practice = false;
+            }
+            PlayEnd::Disconnected => {
+                let name = conn_info.name.clone().unwrap_or_default();
+                // Decided under the lock joins take, and the game closed
+                // before it is let go, so a flapping connection can't have
+                // someone placed into a game on its way out.
+                let (freed, empty) = {
+                    let mut game = game.write().await;
+                    // a connection that replaced this one owns the slot now
+                    if !game.unbind_connection(conn_info.player_index, client_id) {
+                        return;
+                    }
+                    game.set_connected(conn_info.player_index, false);
+                    // before kickoff the slot isn't held for a reconnect; it
+                    // goes back to matchmaking
+                    let freed = matches!(game.phase, GamePhase::ReadyCheck { .. });
+                    if freed {
+                        game.remove_player(conn_info.player_index);
+                    }
+                    game.broadcast(player_left(conn_info.player_index, &name, freed));
+                    // bots don't keep a game alive
+                    let empty = game
+                        .players
+                        .iter()
+                        .all(|p| p.bot || p.index == conn_info.player_index);
+                    if empty {
+                        game.close();
+                    }
+                    (freed, empty)
+                };
+                // only once the slot is known to be this connection's, so a
+                // replaced one doesn't report leaving after its successor
+                // joined
+                state.hooks().on_leave(client.middleware.ctx(), false).await;
+                state.emit(ServerEvent::PlayerLeft {
+                    game_id,
+                    player_index: conn_info.player_index,
+                    name,
+                    left: freed,
+                });
+                if empty {
+                    remove_game(&state, game_id, &game).await;
+                    println!("Removed game {game_id} because last player disconnected");
+                } else if !freed {
+                    state
+                        .open_slots
+                        .insert(conn_info.player_id.clone(), game_id);
+                }
+                invariants::enforce(&state, "disconnect").await;
+                return;
+            }
+            PlayEnd::GameClosed | PlayEnd::Replaced => return,
+        }
+    }
+}
+
+// Places a connection that named a game or asked for practice, or one whose
+// name matches a disconnected player so they get their slot back. Ok(None) means the
+// connection has nowhere to go yet and should wait in the queue.
+async fn join_game(
+    state: &ServerState,
+    conn_info: &mut ConnectionInfo,
+    connection: SlotConnection,
+) -> Result<Option<(usize, Arc<RwLock<Game>>)>, ErrorCode> {
+    let games = &state.games;
+    let name = conn_info.name.clone().unwrap_or_default();
+    let (game_id, game, player_index) = match &conn_info.game {
+        // practice games are made on the spot and never take a second player
+        _ if conn_info.practice => {
+            let factory = state
+                .game_factory(conn_info.game_type)
+                .ok_or(ErrorCode::WrongGameType)?;
+            // practice has no one to wait for, so it isn't queued
+            if !state.has_room().await {
+                return Err(ErrorCode::ServerFull);
+            }
+            let players = vec![(conn_info.owner(), name.clone())];
+            let (id, game) = create_game(state, factory, players, true)
+                .await
+                .map_err(Refused::code)?;
+            println!("Player {} started practice game {}", name, id);
+            (id, game, 0)
+        }
+        Some(id) => {
+            let game = match games.read().await.get(id) {
+                Some(game) => Arc::clone(game),
+                None => {
+                    println!("Game not found");
+                    return Err(ErrorCode::GameNotFound);
+                }
+            };
+            let mut g = game.write().await;
+            // the id was reused by a game the link wasn't for
+            let stale = conn_info
+                .game_token
+                .as_deref()
+                .map_or(false, |token| !tokens_match(token, &g.token));
+            if stale {
+                println!("Game {} has a different token than requested", id);
+                return Err(ErrorCode::GameNotFound);
+            }
+            // closed on its way out of the map
+            if g.is_closed() {
+                println!("Game {} is closing", id);
+                return Err(ErrorCode::GameNotFound);
+            }
+            // checked before rejoin so a mismatched join can't mark a
+            // disconnected player as back
+            if g.game_type != conn_info.game_type {
+                println!(
+                    "Game {} is type {}, client asked for {}",
+                    id, g.game_type, conn_info.game_type
+                );
+                return Err(ErrorCode::WrongGameType);
+            }
+            let rejoined = g.rejoin(
+                &conn_info.player_id,
+                &name,
+                conn_info.session_token.as_deref(),
+                connection.clone(),
+            )?;
+            let player_index = match rejoined {
+                Some(index) => index,
+                None if g.private => {
+                    println!("Game {} is private", id);
+                    return Err(ErrorCode::GameFull);
+                }
+                None if g.players.len() < g.logic.max_players() => {
+                    let index = g.add_player(conn_info.player_id.clone(), name.clone());
+                    g.bind_connection(index, connection);
+                    index
+                }
+                None => {
+                    println!("Game {} is full", id);
+                    return Err(ErrorCode::GameFull);
+                }
+            };
+            drop(g);
+            (*id, game, player_index)
+        }
+        None => {
+            // the queue for a type nobody can build would never move
+            if state.game_factory(conn_info.game_type).is_none() {
+                return Err(ErrorCode::WrongGameType);
+            }
+            // only the game holding this player's open slot is locked
+            let mut found = None;
+            if let Some(id) = state.open_slots.get(&conn_info.player_id) {
+                let game = games.read().await.get(&id).cloned();
+                if let Some(game) = game {
+                    let mut g = game.write().await;
+                    // a game closed on its way out of the map has no slot
+                    // to go back to
+                    let rejoined = if g.is_closed() {
+                        Ok(None)
+                    } else {
+                        g.rejoin(
+                            &conn_info.player_id,
+                            &name,
+                            conn_info.session_token.as_deref(),
+                            connection.clone(),
+                        )
+                    };
+                    drop(g);
+                    // a wrong token here means a guest picked a name that
+                    // belongs to someone else's slot
+                    let index = match rejoined {
+                        Err(ErrorCode::Unauthorized) => None,
+                        result => result?,
+                    };
+                    if let Some(index) = index {
+                        println!("Found game {} for player {}", id, name);
+                        found = Some((id, game, index));
+                    }
+                }
+            }
+            // refused here rather than after a wait in the queue
+            let owner = conn_info.owner();
+            let max = state.config().max_games_per_identity;
+            match found {
+                Some(found) => found,
+                None if max.map_or(false, |max| state.game_owners.count(&owner) >= max) => {
+                    println!("Player {} ({}) has too many games", name, owner);
+                    return Err(ErrorCode::TooManyGames);
+                }
+                // only a challenge takes a lobby connection out of the queue
+                None if conn_info.lobby => return Ok(None),
+                None => match place(state, conn_info, &name, connection).await? {
+                    Some(placed) => placed,
+                    None => return Ok(None),
+                },
+            }
+        }
+    };
+    println!(
+        "Game {} joined with {} players",
+        game_id,
+        game.read().await.players.len()
+    );
+    conn_info.player_index = player_index;
+    state.open_slots.remove(&conn_info.player_id, game_id);
+    state.emit(ServerEvent::PlayerJoined {
+        game_id,
+        player_index,
+        name,
+    });
+
+    return Ok(Some((game_id, game)));
+}
+
+const QUEUE_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Tells a queued connection it is waiting for a game to end, not for an
+// opponent. False when the send failed.
+async fn send_queued(
+    state: &ServerState,
+    client_id: usize,
+    sender: &mut WsSender,
+    client: &mut Client,
+) -> bool {
+    let queued = QueuedMessage {
+        position: state.queue.status(client_id).map_or(0, |(p, _)| p as u32),
+        max_wait_ms: state
+            .config()
+            .full_queue_timeout
+            .map(|wait| wait.as_millis() as u32),
+    };
+    let queued = WsMessage::from_payload(MessageType::Queued, &queued);
+    return send_message(sender, client, &queued).await;
+}
+
+// Holds a connection in the matchmaking queue until the matchmaker pairs it,
+// sending QueueStatus every QUEUE_STATUS_INTERVAL, or until a challenge it
+// sent or was sent is accepted. Returns None when the client cancels with
+// LeaveQueue or goes away; its entry is off the queue either way. A Ready
+// sent while waiting is carried over to the game.
+async fn wait_in_queue(
+    state: &ServerState,
+    client_id: usize,
+    client: &mut Client,
+    conn_info: &mut ConnectionInfo,
+    sender: &mut WsSender,
+    receiver: &mut WsReceiver,
+) -> Option<(usize, Arc<RwLock<Game>>)> {
+    let name = conn_info.name.clone().unwrap_or_default();
+    // the same player waiting twice: the newest wins under Replace, the
+    // first under Reject
+    let earlier = state.queue.queued_as(&conn_info.owner());
+    if !earlier.is_empty() {
+        if state.config().duplicate_connection == DuplicateConnection::Reject {
+            let error = WsMessage::error(
+                ErrorCode::AlreadyConnected,
+                "This player is already waiting for a match",
+            );
+            let _ = send_message(sender, client, &error).await;
+            close_with(sender, client, CloseReason::ReplacedByNewConnection).await;
+            return None;
+        }
+        for earlier in earlier {
+            state
+                .connections
+                .evict(earlier, CloseReason::ReplacedByNewConnection);
+        }
+    }
+    let mut matched = state.queue.enqueue(
+        client_id,
+        conn_info.game_type,
+        conn_info.owner(),
+        name.clone(),
+        !conn_info.lobby,
+    );
+    let mut inbox = state.challenges.register(client_id);
+    let _challenges = ChallengeGuard { state, client_id };
+    match conn_info.lobby {
+        true => println!("Player {} is waiting in the lobby", name),
+        false => println!("Player {} queued for a match", name),
+    }
+    // told once, on arrival or as soon as the server fills up behind it;
+    // QueueStatus carries the position from there
+    let mut full = !state.has_room().await;
+    if full && !send_queued(state, client_id, sender, client).await {
+        return leave_queue(state, client_id, &mut matched).await;
+    }
+    let turn_away = sleep(
+        state
+            .config()
+            .full_queue_timeout
+            .unwrap_or(Duration::from_secs(3600)),
+    );
+    tokio::pin!(turn_away);
+    let mut status = interval(QUEUE_STATUS_INTERVAL);
+    let give_up = sleep(
+        state
+            .config()
+            .queue_timeout
+            .unwrap_or(Duration::from_secs(3600)),
+    );
+    tokio::pin!(give_up);
+    let mut timed_out = false;
+    // the challenge this connection has out, which expires when its
+    // timer does
+    let mut challenging: Option<u32> = None;
+    let expire = sleep(state.config().challenge_timeout);
+    tokio::pin!(expire);
+    let mut ready = false;
+    // dropped with this function, so leaving the queue unsubscribes
+    let mut lobby: Option<broadcast::Receiver<(u8, Bytes)>> = None;
+    let mut keepalive = WsKeepalive::new(state.config().ws_ping_interval);
+    let placed = loop {
+        tokio::select! {
+            placed = &mut matched => match placed {
+                Ok(Ok(placed)) => break placed,
+                Ok(Err(code)) => {
+                    refuse_join(sender, client, code).await;
+                    return None;
+                }
+                Err(_) => return None,
+            },
+            Some(reason) = client.evicted.recv() => {
+                close_with(sender, client, reason).await;
+                return leave_queue(state, client_id, &mut matched).await;
+            }
+            _ = keepalive.due() => {
+                if keepalive.awaiting_pong {
+                    println!("Player {} stopped answering websocket pings", name);
+                    close_with(sender, client, CloseReason::HeartbeatTimeout).await;
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+                if !keepalive.ping(sender, client).await {
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+            }
+            update = async { lobby.as_mut().unwrap().recv().await }, if lobby.is_some() => {
+                // a subscriber that fell behind just misses some deltas
+                let frame = update
+                    .ok()
+                    .filter(|(game_type, _)| *game_type == conn_info.game_type);
+                if let Some((_, frame)) = frame {
+                    if !send_frame(sender, client, &frame).await {
+                        return leave_queue(state, client_id, &mut matched).await;
+                    }
+                }
+            }
+            Some(message) = inbox.recv() => {
+                if !send_message(sender, client, &message).await {
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+                if is_server_full(&message) {
+                    close_with(sender, client, CloseReason::ServerFull).await;
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+            }
+            Some(announcement) = client.announcements.recv() => {
+                if !send_message(sender, client, &announcement).await {
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+            }
+            _ = &mut expire, if challenging.is_some() => {
+                let id = challenging.take().unwrap();
+                // None means it was answered or ended some other way
+                if let Some(challenge) = state.challenges.expire(id) {
+                    println!("Player {}'s challenge went unanswered", name);
+                    let withdrawn = challenge_result(id, ChallengeOutcome::Withdrawn);
+                    state.challenges.send(challenge.target, withdrawn);
+                    let unanswered = challenge_result(id, ChallengeOutcome::TimedOut);
+                    if !send_message(sender, client, &unanswered).await {
+                        return leave_queue(state, client_id, &mut matched).await;
+                    }
+                }
+            }
+            // a lobby connection's place in the queue means nothing
+            _ = status.tick(), if !conn_info.lobby => {
+                if !full && !state.has_room().await {
+                    full = true;
+                    if let Some(wait) = state.config().full_queue_timeout {
+                        turn_away.as_mut().reset(Instant::now() + wait);
+                    }
+                    if !send_queued(state, client_id, sender, client).await {
+                        return leave_queue(state, client_id, &mut matched).await;
+                    }
+                }
+                if let Some((position, waited)) = state.queue.status(client_id) {
+                    let status = QueueStatusMessage {
+                        position: position as u32,
+                        waited_ms: waited.as_millis() as u32,
+                    };
+                    let status = WsMessage::from_payload(MessageType::QueueStatus, &status);
+                    if !send_message(sender, client, &status).await {
+                        return leave_queue(state, client_id, &mut matched).await;
+                    }
+                }
+            }
+            _ = &mut turn_away, if full && state.config().full_queue_timeout.is_some() => {
+                full = false;
+                // false means a game freed up and the match is on its way
+                if state.queue.remove(client_id) {
+                    println!("Player {} gave up waiting for a full server", name);
+                    let error = WsMessage::error(
+                        ErrorCode::ServerFull,
+                        "Server is running as many games as it can",
+                    );
+                    let _ = send_message(sender, client, &error).await;
+                    close_with(sender, client, CloseReason::ServerFull).await;
+                    return None;
+                }
+            }
+            // a bot game needs room too; until then keep checking
+            _ = &mut give_up, if !timed_out
+                && !conn_info.lobby
+                && state.config().queue_timeout.is_some() => {
+                if !state.has_room().await {
+                    give_up.as_mut().reset(Instant::now() + QUEUE_STATUS_INTERVAL);
+                    continue;
+                }
+                timed_out = true;
+                // false means the matchmaker got there first and the match
+                // is already on its way
+                if state.queue.remove(client_id) {
+                    println!("Player {} waited too long, starting a bot match", name);
+                    match bot_match(state, conn_info.game_type, conn_info.owner(), name.clone()).await {
+                        Ok(placed) => break placed,
+                        // the server may have filled up since the look above
+                        Err(code) => {
+                            refuse_join(sender, client, code).await;
+                            return None;
+                        }
+                    }
+                }
+            }
+            msg = receiver.next() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    if let Some(msg_type) = WsMessage::unknown_type(&data) {
+                        client.traffic.record(Direction::In, msg_type, data.len());
+                        let error = unsupported_type(msg_type);
+                        if !send_message(sender, client, &error).await {
+                            return leave_queue(state, client_id, &mut matched).await;
+                        }
+                        continue;
+                    }
+                    let ws_msg = WsMessage::from_bytes(&data);
+                    if let Some(ws_msg) = &ws_msg {
+                        client
+                            .traffic
+                            .record(Direction::In, ws_msg.msg_type.into(), data.len());
+                        match client.middleware.inbound(ws_msg) {
+                            MiddlewareDecision::Continue => (),
+                            MiddlewareDecision::Drop => continue,
+                            MiddlewareDecision::Close(reason) => {
+                                close_with(sender, client, reason).await;
+                                return leave_queue(state, client_id, &mut matched).await;
+                            }
+                        }
+                    }
+                    match ws_msg.as_ref().map(|message| message.msg_type) {
+                        Some(MessageType::Echo) => {
+                            let reply = echo(state, client, conn_info, ws_msg.as_ref().unwrap());
+                            if !send_message(sender, client, &reply).await {
+                                return leave_queue(state, client_id, &mut matched).await;
+                            }
+                        }
+                        Some(MessageType::Version) => {
+                            if !send_message(sender, client, &version()).await {
+                                return leave_queue(state, client_id, &mut matched).await;
+                            }
+                        }
+                        Some(MessageType::LeaveQueue) => {
+                            if state.queue.remove(client_id) {
+                                println!("Player {} left the queue", name);
+                                close_with(sender, client, CloseReason::NormalLobbyExit).await;
+                                return None;
+                            }
+                        }
+                        Some(MessageType::Ping) => {
+                            let pong = WsMessage {
+                                msg_type: MessageType::Pong,
+                                payload: vec![],
+                            };
+                            if !send_message(sender, client, &pong).await {
+                                return leave_queue(state, client_id, &mut matched).await;
+                            }
+                        }
+                        Some(MessageType::Ready) => ready = true,
+                        Some(MessageType::Challenge) => {
+                            let request = ws_msg.as_ref().unwrap();
+                            match challenge(state, client_id, conn_info, &name, request) {
+                                Ok(id) => {
+                                    challenging = Some(id);
+                                    let timeout = state.config().challenge_timeout;
+                                    expire.as_mut().reset(Instant::now() + timeout);
+                                }
+                                Err(error) => {
+                                    if !send_message(sender, client, &error).await {
+                                        return leave_queue(state, client_id, &mut matched).await;
+                                    }
+                                }
+                            }
+                        }
+                        Some(MessageType::ChallengeReply) => {
+                            let reply = ws_msg.as_ref().unwrap();
+                            if let Some(error) = answer_challenge(state, client_id, &name, reply).await {
+                                if !send_message(sender, client, &error).await {
+                                    return leave_queue(state, client_id, &mut matched).await;
+                                }
+                                if is_server_full(&error) {
+                                    close_with(sender, client, CloseReason::ServerFull).await;
+                                    return leave_queue(state, client_id, &mut matched).await;
+                                }
+                            }
+                        }
+                        Some(MessageType::SubscribeLobby) if lobby.is_none() => {
+                            lobby = Some(state.lobby.subscribe());
+                            let snapshot = lobby_snapshot(state, conn_info.game_type).await;
+                            let snapshot =
+                                WsMessage::from_payload(MessageType::LobbyUpdate, &snapshot);
+                            if !send_message(sender, client, &snapshot).await {
+                                return leave_queue(state, client_id, &mut matched).await;
+                            }
+                        }
+                        _ => ignore_frame(client_id, "queued"),
+                    }
+                }
+                Some(Err(e)) => {
+                    read_failed(state, sender, client, conn_info.ip, e).await;
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    return leave_queue(state, client_id, &mut matched).await;
+                }
+                Some(Ok(Message::Pong(_))) => keepalive.pong(),
+                Some(Ok(_)) => (),
+            },
+        }
+    };
+    let Match {
+        game_id,
+        game,
+        player_index,
+    } = placed;
+    {
+        let mut game = game.write().await;
+        if ready {
+            game.mark_ready(player_index);
+        }
+    }
+    conn_info.player_index = player_index;
+    state.emit(ServerEvent::PlayerJoined {
+        game_id,
+        player_index,
+        name,
+    });
+    return Some((game_id, game));
+}
+
+// Takes a connection that is going away off the queue. One the matchmaker
+// already took has its Match on the way in matched, for a slot nobody will
+// play; it is let go like a disconnect before kickoff.
+async fn leave_queue(
+    state: &ServerState,
+    client_id: usize,
+    matched: &mut oneshot::Receiver<Result<Match, ErrorCode>>,
+) -> Option<(usize, Arc<RwLock<Game>>)> {
+    if !state.queue.remove(client_id) {
+        if let Ok(Ok(placed)) = matched.await {
+            abandon_match(state, placed).await;
+        }
+    }
+    return None;
+}
+
+// Frees the slot of a matched player whose connection went before it got
+// its Match, and removes the game if that leaves nobody in it.
+async fn abandon_match(state: &ServerState, placed: Match) {
+    let Match {
+        game_id,
+        game,
+        player_index,
+    } = placed;
+    let (name, empty) = {
+        let mut game = game.write().await;
+        let name = game
+            .remove_player(player_index)
+            .map_or(String::new(), |p| p.name);
+        game.broadcast(player_left(player_index, &name, true));
+        let empty = game.players.iter().all(|p| p.bot);
+        if empty {
+            game.close();
+        }
+        (name, empty)
+    };
+    state.emit(ServerEvent::PlayerLeft {
+        game_id,
+        player_index,
+        name,
+        left: true,
+    });
+    if empty {
+        remove_game(state, game_id, &game).await;
+        println!("Removed game {game_id}, its players went before the match");
+    }
+}
+
+// Ends a queued connection's challenges however it stops waiting, matched
+// or gone, and tells the other side of each.
+struct ChallengeGuard<'a> {
+    state: &'a ServerState,
+    client_id: usize,
+}
+
+impl Drop for ChallengeGuard<'_> {
+    fn drop(&mut self) {
+        for challenge in self.state.challenges.forget(self.client_id) {
+            let (other, outcome) = if challenge.challenger == self.client_id {
+                (challenge.target, ChallengeOutcome::Withdrawn)
+            } else {
+                (challenge.challenger, ChallengeOutcome::Unavailable)
+            };
+            let result = challenge_result(challenge.id, outcome);
+            self.state.challenges.send(other, result);
+        }
+    }
+}
+
+fn challenge_result(challenge_id: u32, outcome: ChallengeOutcome) -> WsMessage {
+    return WsMessage::from_payload(
+        MessageType::ChallengeResult,
+        &ChallengeResultMessage {
+            challenge_id,
+            outcome,
+        },
+    );
+}
+
+// Opens the challenge a queued connection sent and passes it on to its
+// target. Err is the Error to answer with.
+fn challenge(
+    state: &ServerState,
+    client_id: usize,
+    conn_info: &ConnectionInfo,
+    name: &str,
+    request: &WsMessage,
+) -> Result<u32, WsMessage> {
+    let request = ChallengeMessage::decode(&request.payload)
+        .ok_or_else(|| WsMessage::error(ErrorCode::InvalidParams, "Bad Challenge"))?;
+    let (target, target_id) = state
+        .queue
+        .find(
+            conn_info.game_type,
+            &request.target_name,
+            &conn_info.player_id,
+        )
+        .ok_or_else(|| {
+            // only a different player going by the same name can be found
+            if request.target_name == name {
+                return WsMessage::error(ErrorCode::InvalidParams, "You can't challenge yourself");
+            }
+            let reason = format!("No one called {} is waiting", request.target_name);
+            return WsMessage::error(ErrorCode::PlayerNotFound, &reason);
+        })?;
+    let handicap = match request.handicap {
+        Some(handicap) => Some(handicap.clamped()),
+        None => state.challenges.rematch(&conn_info.player_id, &target_id),
+    };
+    let challenge = state
+        .challenges
+        .open(client_id, target, handicap)
+        .ok_or_else(|| {
+            let reason = format!(
+                "You have a challenge out, or {} is answering one",
+                request.target_name
+            );
+            WsMessage::error(ErrorCode::ChallengeBusy, &reason)
+        })?;
+    let received = ChallengeReceivedMessage {
+        challenge_id: challenge.id,
+        from: name.to_string(),
+        timeout_ms: state.config().challenge_timeout.as_millis() as u32,
+        handicap,
+    };
+    let received = WsMessage::from_payload(MessageType::ChallengeReceived, &received);
+    state.challenges.send(target, received);
+    println!("Player {} challenged {}", name, request.target_name);
+    return Ok(challenge.id);
+}
+
+// A ServerFull Error ends a queued connection with the matching close, as
+// it does a join.
+fn is_server_full(message: &WsMessage) -> bool {
+    return matches!(message.msg_type, MessageType::Error)
+        && message
+            .decode::<ErrorMessage>()
+            .map_or(false, |error| error.code == ErrorCode::ServerFull);
+}
+
+// Acts on a queued connection's answer to a challenge it was sent.
+// Accepting takes both players off the queue into a new game, which reaches
+// each of them as a Match. Some is a message to answer with.
+async fn answer_challenge(
+    state: &ServerState,
+    client_id: usize,
+    name: &str,
+    request: &WsMessage,
+) -> Option<WsMessage> {
+    let reply = match request.decode::<ChallengeReplyMessage>() {
+        Some(reply) => reply,
+        None => {
+            return Some(WsMessage::error(
+                ErrorCode::InvalidParams,
+                "Bad ChallengeReply",
+            ))
+        }
+    };
+    // an answer to a challenge that already ended, which the target has
+    // been told about
+    let challenge = state.challenges.answer(reply.challenge_id, client_id)?;
+    if !reply.accept {
+        println!("Player {} declined challenge {}", name, challenge.id);
+        let declined = challenge_result(challenge.id, ChallengeOutcome::Declined);
+        state.challenges.send(challenge.challenger, declined);
+        return None;
+    }
+    if !state.has_room().await {
+        let full = WsMessage::error(
+            ErrorCode::ServerFull,
+            "Server is running as many games as it can",
+        );
+        state.challenges.send(challenge.challenger, full.clone());
+        return Some(full);
+    }
+    return match state.queue.take_both(challenge.challenger, client_id) {
+        Some((game_type, first, second)) => {
+            println!("Player {} accepted challenge {}", name, challenge.id);
+            // the challenger is first, so on the left
+            start_match(state, game_type, first, second, Some(&challenge)).await;
+            None
+        }
+        // the challenger went away or was matched before the answer
+        None => Some(challenge_result(
+            challenge.id,
+            ChallengeOutcome::Unavailable,
+        )),
+    };
+}
+
+// Asks the matchmaking strategy where a player with nowhere to go belongs.
+// None sends them to the queue.
+async fn place(
+    state: &ServerState,
+    conn_info: &ConnectionInfo,
+    name: &str,
+    connection: SlotConnection,
+) -> Result<Option<(usize, Arc<RwLock<Game>>, usize)>, ErrorCode> {
+    let joining = Joining {
+        id: conn_info.player_id.clone(),
+        name: name.to_string(),
+        game_type: conn_info.game_type,
+    };
+    let _placing = state.placing.lock().await;
+    let candidates = state
+        .candidates(conn_info.game_type, &conn_info.player_id)
+        .await;
+    match state.matchmaking().place(&candidates, &joining) {
+        Placement::Queue => return Ok(None),
+        Placement::Create => {
+            let factory = state
+                .game_factory(conn_info.game_type)
+                .ok_or(ErrorCode::WrongGameType)?;
+            // a full server queues them like anyone else
+            if !state.has_room().await {
+                return Ok(None);
+            }
+            let players = vec![(conn_info.owner(), name.to_string())];
+            let (id, game) = match create_game(state, factory, players, false).await {
+                Ok(created) => created,
+                // filled up since the look above
+                Err(Refused::ServerFull) => return Ok(None),
+                Err(refused) => return Err(refused.code()),
+            };
+            println!("Player {} opened game {}", name, id);
+            return Ok(Some((id, game, 0)));
+        }
+        Placement::Join(id) => {
+            let game = match state.games.read().await.get(&id) {
+                Some(game) => Arc::clone(game),
+                None => return Ok(None),
+            };
+            let mut g = game.write().await;
+            // filled or closed since the candidates were taken
+            let open = g.game_type == conn_info.game_type
+                && !g.practice
+                && !g.private
+                && !g.is_closed()
+                && g.players.len() < g.logic.max_players()
+                && !g.players.iter().any(|p| p.id == conn_info.player_id);
+            if !open {
+                return Ok(None);
+            }
+            let index = g.add_player(conn_info.player_id.clone(), name.to_string());
+            g.bind_connection(index, connection);
+            drop(g);
+            return Ok(Some((id, game, index)));
+        }
+    }
+}
+
+// Pairs the two oldest queued players of a game type into a fresh game,
+// waking whenever someone joins the queue. This task is the only place
+// matchmade games are created and take_pair pops both players under the
+// queue lock, so near-simultaneous joins can't each open a game of their own
+// and wait on it alone.
+async fn run_matchmaker(state: Arc<ServerState>) {
+    let mut events = state.events.subscribe();
+    loop {
+        // a full server leaves everyone queued until a game goes away
+        while state.has_room().await {
+            let (game_type, first, second) = match state.queue.take_pair() {
+                Some(pair) => pair,
+                None => break,
+            };
+            start_match(&state, game_type, first, second, None).await;
+        }
+        tokio::select! {
+            _ = state.queue.wait_for_join() => (),
+            _ = game_removed(&mut events) => (),
+        }
+    }
+}
+
+// Puts two players taken off the queue together in a fresh game and hands
+// each waiting connection its Match. A game started by a challenge is
+// private, and its handicap is applied to a soccer game before either
+// player is placed in it; other game types play it even. The handicap is
+// kept for the two players' next challenge, which is their rematch.
+async fn start_match(
+    state: &ServerState,
+    game_type: u8,
+    first: QueueEntry,
+    second: QueueEntry,
+    challenge: Option<&Challenge>,
+) {
+    // join_game only queues types that have a factory
+    let factory = match state.game_factory(game_type) {
+        Some(factory) => factory,
+        None => {
+            first.reject(ErrorCode::WrongGameType);
+            second.reject(ErrorCode::WrongGameType);
+            return;
+        }
+    };
+    let players = vec![
+        (first.owner.clone(), first.name.clone()),
+        (second.owner.clone(), second.name.clone()),
+    ];
+    let (game_id, game) = match create_game(state, factory, players, false).await {
+        Ok(created) => created,
+        // the queue only admits players under the cap, but several
+        // connections of one player can be queued at once
+        Err(Refused::ServerFull) => {
+            state.queue.requeue(game_type, first);
+            state.queue.requeue(game_type, second);
+            return;
+        }
+        Err(Refused::Players(over)) => {
+            for (position, entry) in [first, second].into_iter().enumerate() {
+                if over.contains(&position) {
+                    entry.reject(ErrorCode::TooManyGames);
+                } else {
+                    state.queue.requeue(game_type, entry);
+                }
+            }
+            return;
+        }
+    };
+    if let Some(challenge) = challenge {
+        state
+            .challenges
+            .played(&first.owner.id, &second.owner.id, challenge.handicap);
+        let mut game = game.write().await;
+        game.private = true;
+        if let (Some(handicap), Some(soccer)) =
+            (challenge.handicap, game.downcast_mut::<SoccerGame>())
+        {
+            soccer.set_handicap(handicap);
+        }
+    }
+    println!(
+        "Matched {} and {} in game {}",
+        first.name, second.name, game_id
+    );
+    for (player_index, entry) in [first, second].into_iter().enumerate() {
+        let placed = Match {
+            game_id,
+            game: Arc::clone(&game),
+            player_index,
+        };
+        if let Err(placed) = entry.notify(placed) {
+            // dropped right after pairing, before it was sent a
+            // session token, so the slot can't be reclaimed
+            abandon_match(state, placed).await;
+        }
+    }
+}
+
+// Resolves when a game is removed, or when events were missed and one may
+// have been.
+async fn game_removed(events: &mut broadcast::Receiver<ServerEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(ServerEvent::GameRemoved { .. }) | Err(RecvError::Lagged(_)) => return,
+            Ok(_) => (),
+            Err(RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
+
+// Counted in ServerState::spectators while a firehose connection is
+// subscribed, so the games it watches don't go dormant under it.
+struct Spectating(Arc<AtomicUsize>);
+
+impl Spectating {
+    fn new(spectators: &Arc<AtomicUsize>) -> Spectating {
+        spectators.fetch_add(1, Ordering::Relaxed);
+        return Spectating(spectators.clone());
+    }
+}
+
+impl Drop for Spectating {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A firehose connection: never queued or placed in a game, so it holds no
+// slot in any of them. Once it sends SubscribeAll it is sent the rounds of
+// run_firehose that fall due at the rate it asked for.
+async fn watch_all(
+    state: &ServerState,
+    client_id: usize,
+    client: &mut Client,
+    conn_info: &ConnectionInfo,
+    sender: &mut WsSender,
+    receiver: &mut WsReceiver,
+) {
+    let mut rounds: Option<(broadcast::Receiver<FirehoseRound>, Spectating)> = None;
+    let mut every: u64 = 1;
+    let mut keepalive = WsKeepalive::new(state.config().ws_ping_interval);
+    loop {
+        tokio::select! {
+            _ = keepalive.due() => {
+                if keepalive.awaiting_pong {
+                    println!("Client {} stopped answering websocket pings", client_id);
+                    close_with(sender, client, CloseReason::HeartbeatTimeout).await;
+                    return;
+                }
+                if !keepalive.ping(sender, client).await {
+                    return;
+                }
+            }
+            Some(announcement) = client.announcements.recv() => {
+                if !send_message(sender, client, &announcement).await {
+                    return;
+                }
+            }
+            round = async { rounds.as_mut().unwrap().0.recv().await }, if rounds.is_some() => {
+                match round {
+                    Ok((round, frames)) => {
+                        if round % every != 0 {
+                            continue;
+                        }
+                        for frame in frames.iter() {
+                            if !send_frame(sender, client, frame).await {
+                                return;
+                            }
+                        }
+                    }
+                    // a watcher that fell behind skips rounds rather than
+                    // catching up on stale ones
+                    Err(RecvError::Lagged(_)) => (),
+                    Err(RecvError::Closed) => return,
+                }
+            }
+            msg = receiver.next() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    if let Some(msg_type) = WsMessage::unknown_type(&data) {
+                        client.traffic.record(Direction::In, msg_type, data.len());
+                        let error = unsupported_type(msg_type);
+                        if !send_message(sender, client, &error).await {
+                            return;
+                        }
+                        continue;
+                    }
+                    let ws_msg = WsMessage::from_bytes(&data);
+                    if let Some(ws_msg) = &ws_msg {
+                        client
+                            .traffic
+                            .record(Direction::In, ws_msg.msg_type.into(), data.len());
+                        match client.middleware.inbound(ws_msg) {
+                            MiddlewareDecision::Continue => (),
+                            MiddlewareDecision::Drop => continue,
+                            MiddlewareDecision::Close(reason) => {
+                                close_with(sender, client, reason).await;
+                                return;
+                            }
+                        }
+                    }
+                    match ws_msg {
+                        Some(ws_msg) if matches!(ws_msg.msg_type, MessageType::SubscribeAll) => {
+                            let subscribe = match ws_msg.decode::<SubscribeAllMessage>() {
+                                Some(subscribe) => subscribe,
+                                None => {
+                                    close_with(sender, client, CloseReason::ProtocolViolation)
+                                        .await;
+                                    return;
+                                }
+                            };
+                            let max_rate = state.config().max_firehose_rate_hz.max(1);
+                            let rate = subscribe.rate_hz.clamp(1, max_rate);
+                            // rounded up so a watcher never gets more than it
+                            // asked for
+                            every = max_rate.div_ceil(rate) as u64;
+                            if rounds.is_none() {
+                                rounds = Some((
+                                    state.firehose.subscribe(),
+                                    Spectating::new(&state.spectators),
+                                ));
+                            }
+                            println!(
+                                "Client {} ({}) watching every game at {}hz",
+                                client_id,
+                                conn_info.name.clone().unwrap_or_default(),
+                                max_rate as u64 / every
+                            );
+                        }
+                        Some(ws_msg) if matches!(ws_msg.msg_type, MessageType::Ping) => {
+                            let pong = WsMessage {
+                                msg_type: MessageType::Pong,
+                                payload: vec![],
+                            };
+                            if !send_message(sender, client, &pong).await {
+                                return;
+                            }
+                        }
+                        Some(ws_msg) if matches!(ws_msg.msg_type, MessageType::Echo) => {
+                            let reply = echo(state, client, conn_info, &ws_msg);
+                            if !send_message(sender, client, &reply).await {
+                                return;
+                            }
+                        }
+                        Some(ws_msg) if matches!(ws_msg.msg_type, MessageType::Version) => {
+                            if !send_message(sender, client, &version()).await {
+                                return;
+                            }
+                        }
+                        Some(ws_msg) if matches!(ws_msg.msg_type, MessageType::Announce) => {
+                            let reply = announce(state, conn_info, &ws_msg);
+                            if !send_message(sender, client, &reply).await {
+                                return;
+                            }
+                        }
+                        _ => ignore_frame(client_id, "firehose"),
+                    }
+                }
+                Some(Err(e)) => {
+                    read_failed(state, sender, client, conn_info.ip, e).await;
+                    return;
+                }
+                Some(Ok(Message::Close(_))) | None => return,
+                Some(Ok(Message::Pong(_))) => keepalive.pong(),
+                Some(Ok(_)) => (),
+            },
+        }
+    }
+}
+
+const FIREHOSE_CAPACITY: usize = 4;
+
+// A firehose round's number, counting up at max_firehose_rate_hz, and its
+// MultiState frames.
+pub type FirehoseRound = (u64, Arc<[Bytes]>);
+
+// Batches every running game for firehose connections at
+// max_firehose_rate_hz. Snapshots come from each game's per-tick cache, so
+// a game is encoded at most once a tick however many watchers there are,
+// and not at all while nobody is watching. Players' own States are built
+// per connection and don't share it.
+async fn run_firehose(state: Arc<ServerState>) {
+    let period = |state: &ServerState| {
+        return Duration::from_secs(1) / state.config().max_firehose_rate_hz.max(1) as u32;
+    };
+    let mut current = period(&state);
+    let mut rounds = interval(current);
+    rounds.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut round: u64 = 0;
+    loop {
+        rounds.tick().await;
+        // a reloaded config can change the rate
+        if period(&state) != current {
+            current = period(&state);
+            rounds = interval(current);
+            rounds.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        }
+        if state.firehose.receiver_count() == 0 {
+            continue;
+        }
+        let games: Vec<(usize, Arc<RwLock<Game>>)> = state
+            .games
+            .read()
+            .await
+            .iter()
+            .map(|(game_id, game)| (*game_id, game.clone()))
+            .collect();
+        let mut snapshots = Vec::with_capacity(games.len());
+        for (game_id, game) in games {
+            let game = game.read().await;
+            if game.is_running() {
+                snapshots.push((game_id as u32, game.game_type, game.snapshot()));
+            }
+        }
+        let frames = MultiStateMessage::frames(
+            snapshots
+                .iter()
+                .map(|(game_id, game_type, snapshot)| (*game_id, *game_type, &snapshot[..])),
+            state.config().firehose_batch_bytes,
+        );
+        let frames: Arc<[Bytes]> = frames.into_iter().map(Bytes::from).collect();
+        let _ = state.firehose.send((round, frames));
+        round += 1;
+    }
+}
+
+const LOBBY_CAPACITY: usize = 16;
+// lobby changes are batched and sent at most this often
+const LOBBY_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+// Follows the event bus and pushes the games whose lobby status changed to
+// lobby subscribers, batched so a burst of churn is one update. Games are
+// looked at when the batch goes out, so a game created and filled within
+// one interval is only ever reported as full.
+async fn run_lobby(state: Arc<ServerState>) {
+    let mut events = state.events.subscribe();
+    let mut dirty = BTreeSet::new();
+    let mut sent: HashMap<usize, LobbyGame> = HashMap::new();
+    let mut flush = interval(LOBBY_UPDATE_INTERVAL);
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => dirty.extend(lobby_game_id(&event)),
+                // lost track of what changed, so look at everything
+                Err(RecvError::Lagged(_)) => {
+                    dirty.extend(sent.keys().copied());
+                    dirty.extend(state.games.read().await.keys().copied());
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = flush.tick(), if !dirty.is_empty() => {
+                let mut by_type: BTreeMap<u8, Vec<LobbyGame>> = BTreeMap::new();
+                for game_id in std::mem::take(&mut dirty) {
+                    let game = state.games.read().await.get(&game_id).cloned();
+                    let entry = match game {
+                        Some(game) => lobby_game(game_id, &*game.read().await),
+                        // never reported, so nobody needs to hear it's gone
+                        None => match sent.remove(&game_id) {
+                            Some(last) => LobbyGame {
+                                status: LobbyStatus::Removed,
+                                players: 0,
+                                ..last
+                            },
+                            None => continue,
+                        },
+                    };
+                    if entry.status != LobbyStatus::Removed {
+                        if sent.get(&game_id) == Some(&entry) {
+                            continue;
+                        }
+                        sent.insert(game_id, entry.clone());
+                    }
+                    by_type.entry(entry.game_type).or_default().push(entry);
+                }
+                if state.lobby.receiver_count() > 0 {
+                    for (game_type, games) in by_type {
+                        let update = WsMessage::from_payload(
+                            MessageType::LobbyUpdate,
+                            &LobbyUpdateMessage { games },
+                        );
+                        let _ = state.lobby.send((game_type, Bytes::from(update.to_bytes())));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn lobby_game_id(event: &ServerEvent) -> Option<usize> {
+    return match event {
+        ServerEvent::GameCreated { game_id, .. }
+        | ServerEvent::PlayerJoined { game_id, .. }
+        | ServerEvent::PlayerLeft { game_id, .. }
+        | ServerEvent::GameRemoved { game_id } => Some(*game_id),
+        _ => None,
+    };
+}
+
+fn lobby_game(game_id: usize, game: &Game) -> LobbyGame {
+    let description = game.describe();
+    let connected = description.players.iter().filter(|p| p.connected).count();
+    let status = if connected == 0 && !description.players.is_empty() {
+        LobbyStatus::Empty
+    } else if description.players.len() >= description.max_players {
+        LobbyStatus::Full
+    } else {
+        LobbyStatus::Open
+    };
+    return LobbyGame {
+        game_id: game_id as u32,
+        game_type: description.game_type,
+        status,
+        players: connected as u8,
+        game_token: game.token.clone(),
+        created_ms: game.created_ms(),
+    };
+}
+
+// Every current game of a type, sent once to a new lobby subscriber so
+// later deltas have something to apply to.
+async fn lobby_snapshot(state: &ServerState, game_type: u8) -> LobbyUpdateMessage {
+    let games: Vec<_> = state
+        .games
+        .read()
+        .await
+        .iter()
+        .map(|(id, game)| (*id, Arc::clone(game)))
+        .collect();
+    let mut entries = Vec::with_capacity(games.len());
+    for (game_id, game) in games {
+        let game = game.read().await;
+        if game.game_type == game_type {
+            entries.push(lobby_game(game_id, &game));
+        }
+    }
+    return LobbyUpdateMessage { games: entries };
+}
+
+async fn bot_match(
+    state: &ServerState,
+    game_type: u8,
+    owner: Owner,
+    name: String,
+) -> Result<Match, ErrorCode> {
+    let factory = state
+        .game_factory(game_type)
+        .ok_or(ErrorCode::WrongGameType)?;
+    let (game_id, game) = create_game(state, factory, vec![(owner, name)], false)
+        .await
+        .map_err(Refused::code)?;
+    game.write().await.add_bot();
+    return Ok(Match {
+        game_id,
+        game,
+        player_index: 0,
+    });
+}
+
+// Why create_game made no game.
+#[derive(Debug)]
+enum Refused {
+    // the positions of players already at max_games_per_identity
+    Players(Vec<usize>),
+    // max_games are already running
+    ServerFull,
+}
+
+impl Refused {
+    fn code(self) -> ErrorCode {
+        return match self {
+            Refused::Players(_) => ErrorCode::TooManyGames,
+            Refused::ServerFull => ErrorCode::ServerFull,
+        };
+    }
+}
+
+// Builds a game with factory and registers it. The game counts against
+// each player's max_games_per_identity and the server's max_games, both
+// checked before any physics world is built. The server cap is checked
+// again under the games lock as the game goes in, so a flood of joins
+// can't each see room and all get a game.
+async fn create_game(
+    state: &ServerState,
+    factory: GameFactory,
+    players: Vec<(Owner, String)>,
+    practice: bool,
+) -> Result<(usize, Arc<RwLock<Game>>), Refused> {
+    // one config for the whole game, even if a reload lands meanwhile
+    let config = state.config();
+    if !state.has_room().await {
+        return Err(Refused::ServerFull);
+    }
+    // ids always grow, so one from before a restart is never reused; a
+    // refused game just skips one
+    let game_id = state.last_game_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let owners: Vec<Owner> = players.iter().map(|(owner, _)| owner.clone()).collect();
+    state
+        .game_owners
+        .claim(&owners, game_id, config.max_games_per_identity)
+        .map_err(Refused::Players)?;
+    let players = players
+        .into_iter()
+        .map(|(owner, name)| (owner.id, name))
+        .collect();
+    let mut game = Game::with_logic(factory(state, practice), players);
+    game.practice = practice;
+    configure_game(&mut game, state, &config);
+    let mut games = state.games.write().await;
+    if config.max_games.map_or(false, |max| games.len() >= max) {
+        drop(games);
+        state.game_owners.remove_game(game_id);
+        return Err(Refused::ServerFull);
+    }
+    let created_at = game.created_at;
+    let game = Arc::new(RwLock::new(game));
+    games.insert(game_id, Arc::clone(&game));
+    drop(games);
+    // opened once the map is let go; the game is still in its ready check,
+    // so no tick of play goes by unrecorded
+    if let Some(dir) = &config.frame_dump_dir {
+        let format = config.frame_dump_format;
+        let path = dir.join(format!("game-{}.{}", game_id, format.extension()));
+        match FrameDumper::create(&path, format, config.frame_dump_limit).await {
+            Ok(dumper) => game.write().await.attach_frame_dumper(dumper),
+            Err(e) => log::warn!("Can't dump game {} to {}: {}", game_id, path.display(), e),
+        }
+    }
+    state.emit(ServerEvent::GameCreated {
+        game_id,
+        created_at,
+    });
+    invariants::enforce(state, "create").await;
+    return Ok((game_id, game));
+}
+
+// The server-wide settings every new game starts with.
+pub(crate) fn configure_game(game: &mut Game, state: &ServerState, config: &ServerConfig) {
+    game.pause_config = config.pause.clone();
+    game.warm_up = config.warm_up;
+    game.duplicate_connection = config.duplicate_connection;
+    game.ready_timeout = config.ready_timeout;
+    game.event_log_size = config.event_log_size;
+    game.replay_ticks = config.replay_ticks;
+    game.history_size = config.history_size;
+    game.input_buffer_ticks = config.input_buffer_ticks;
+    game.lockstep = config.lockstep_timeout;
+    game.tick_ms = 1000.0 / config.tick_rate as f64;
+    game.slow_motion = state.tick_rate_override.borrow().is_some();
+    game.spectators = state.spectators.clone();
+}
+
+// Soccer from the server's current config, as changed by SetGameParams
+// and TuneSoccer.
+fn soccer_logic(state: &ServerState, practice: bool) -> Box<dyn GameLogic> {
+    let tuning = state.soccer.lock().unwrap().clone();
+    return soccer_with(state, tuning, practice);
+}
+
+// Volley on the same arena and tunables as soccer.
+fn volley_logic(state: &ServerState, practice: bool) -> Box<dyn GameLogic> {
+    let mut tuning = state.soccer.lock().unwrap().clone();
+    tuning.config = tuning.config.volley();
+    return soccer_with(state, tuning, practice);
+}
+
+fn soccer_with(state: &ServerState, tuning: SoccerTuning, practice: bool) -> Box<dyn GameLogic> {
+    let SoccerTuning {
+        version,
+        mut config,
+    } = tuning;
+    config.practice = practice;
+    let mut logic = match state.soccer_pool.take(&config) {
+        Some(logic) => logic,
+        None => Box::new(SoccerGame::with_config(config)),
+    };
+    if let Some(soccer_game) = logic.as_any_mut().downcast_mut::<SoccerGame>() {
+        soccer_game.control_mode = state.config().control_mode;
+        soccer_game.set_preset(state.config().physics_preset);
+        soccer_game.record_stats = state.config().record_physics_stats;
+        soccer_game.tuning_version = version;
+    }
+    return logic;
+}
+
+// Takes a game out of the map and everything that refers to it, and tells
+// its connections it is gone. Takes the games lock, so the caller must not
+// hold the game's.
+async fn remove_game(state: &ServerState, game_id: usize, game: &Arc<RwLock<Game>>) {
+    state.games.write().await.remove(&game_id);
+    state.open_slots.remove_game(game_id);
+    state.game_owners.remove_game(game_id);
+    game.read().await.close();
+    recycle(state, game).await;
+    state.emit(ServerEvent::GameRemoved { game_id });
+    invariants::enforce(state, "remove").await;
+}
+
+async fn recycle(state: &ServerState, game: &Arc<RwLock<Game>>) {
+    let logic = game.write().await.retire_logic();
+    state
+        .soccer_pool
+        .put(logic, state.config().soccer_pool_size);
+}
+
+async fn play(
+    state: &ServerState,
+    client_id: usize,
+    game_id: usize,
+    game: &Arc<RwLock<Game>>,
+    client: &mut Client,
+    conn_info: &ConnectionInfo,
+    sender: &mut WsSender,
+    receiver: &mut WsReceiver,
+) -> PlayEnd {
+    // interest doesn't carry over from a game this connection was in before
+    client.interest = SetInterestMessage::default();
+    // announce this player before subscribing so it doesn't hear itself,
+    // and catch up on whoever was connected before it
+    let (mut game_closed, mut events, mut chat, mut ticks, roster) = {
+        let game = game.read().await;
+        if let Some(player) = game.player(conn_info.player_index) {
+            game.broadcast(player_joined(player));
+        }
+        let roster: Vec<WsMessage> = game
+            .players
+            .iter()
+            .filter(|p| p.index != conn_info.player_index && p.connected)
+            .map(player_joined)
+            .collect();
+        (
+            game.subscribe_closed(),
+            game.subscribe(),
+            game.subscribe_chat(),
+            game.subscribe_ticks(),
+            roster,
+        )
+    };
+    {
+        let mut game = game.write().await;
+        game.set_traffic(conn_info.player_index, Arc::clone(&client.traffic));
+        game.bind_connection(conn_info.player_index, client.slot_connection());
+    }
+    // once per join, with the connection bound to its slot
+    invariants::enforce(state, "join").await;
+    // moves go to the game's queue; whatever it refuses comes back here
+    let (replies, mut refused) = mpsc::unbounded_channel();
+    client.commands = Some(CommandLink {
+        commands: game.read().await.commands(),
+        replies,
+    });
+    // frames wait here for the writer branch below, which takes them by
+    // priority whenever the socket is free
+    let mut outbox = Outbox::new();
+    for message in roster {
+        let _ = outbox.push(Priority::Control, Bytes::from(message.to_bytes()));
+    }
+    let mut last_state_tick: Option<u64> = None;
+    let mut degrader = state
+        .config()
+        .degrade
+        .clone()
+        .map(|config| Degrader::new(config, deepest_degrade(conn_info)));
+    // reset on every incoming frame of any kind, independent of Ping
+    let idle = sleep(state.config().idle_timeout);
+    tokio::pin!(idle);
+    // a quiet connection is probed; one being degraded is probed on a fixed
+    // schedule instead, so its RTT keeps up however much it sends
+    let fixed_probe = degrader
+        .as_ref()
+        .map(|degrader| degrader.config().probe_every);
+    let ping_interval = match (state.config().server_ping_interval, fixed_probe) {
+        (Some(quiet), Some(fixed)) => Some(quiet.min(fixed)),
+        (quiet, fixed) => quiet.or(fixed),
+    };
+    let probe = sleep(ping_interval.unwrap_or(state.config().idle_timeout));
+    tokio::pin!(probe);
+    // counted from Welcome, then from each Ping or answered probe
+    let required_ping = state.config().required_ping_interval;
+    let silence = sleep(required_ping.unwrap_or(state.config().idle_timeout));
+    tokio::pin!(silence);
+    let mut last_ping = client.last_ping;
+    let mut keepalive = WsKeepalive::new(state.config().ws_ping_interval);
+    loop {
+        let msg = tokio::select! {
+            _ = std::future::ready(()), if !outbox.is_empty() => {
+                let frame = outbox.pop().unwrap_or_default();
+                match unless_closed(&game_closed, send_frame(sender, client, &frame)).await {
+                    Some(true) => continue,
+                    Some(false) => return PlayEnd::Disconnected,
+                    None => return PlayEnd::GameClosed,
+                }
+            }
+            msg = receiver.next() => match msg {
+                Some(msg) => {
+                    idle.as_mut().reset(Instant::now() + state.config().idle_timeout);
+                    if let (Some(ping_interval), None) = (ping_interval, fixed_probe) {
+                        probe.as_mut().reset(Instant::now() + ping_interval);
+                    }
+                    msg
+                }
+                None => return PlayEnd::Disconnected,
+            },
+            _ = &mut idle => {
+                close_with(sender, client, CloseReason::IdleTimeout).await;
+                return PlayEnd::Disconnected;
+            }
+            _ = &mut silence, if required_ping.is_some() => {
+                println!(
+                    "Client {} sent no Ping in {:?}",
+                    client_id,
+                    required_ping.unwrap_or_default()
+                );
+                close_with(sender, client, CloseReason::HeartbeatTimeout).await;
+                return PlayEnd::Disconnected;
+            }
+            _ = &mut probe, if ping_interval.is_some() => {
+                let ping = PingMessage { id: client.start_ping() };
+                let ping = WsMessage::from_payload(MessageType::Ping, &ping);
+                if !enqueue(&mut outbox, client_id, Priority::Control, ping.to_bytes().into()) {
+                    close_with(sender, client, CloseReason::TooSlow).await;
+                    return PlayEnd::Disconnected;
+                }
+                probe.as_mut().reset(Instant::now() + ping_interval.unwrap_or_default());
+                continue;
+            }
+            _ = keepalive.due() => {
+                if keepalive.awaiting_pong {
+                    println!("Client {} stopped answering websocket pings", client_id);
+                    close_with(sender, client, CloseReason::HeartbeatTimeout).await;
+                    return PlayEnd::Disconnected;
+                }
+                match unless_closed(&game_closed, keepalive.ping(sender, client)).await {
+                    Some(true) => continue,
+                    Some(false) => return PlayEnd::Disconnected,
+                    None => return PlayEnd::GameClosed,
+                }
+            }
+            event = events.recv() => {
+                let frame = event
+                    .ok()
+                    .and_then(|frame| for_protocol(conn_info.protocol, frame));
+                if let Some(frame) = frame {
+                    if !enqueue(&mut outbox, client_id, broadcast_priority(&frame), frame) {
+                        close_with(sender, client, CloseReason::TooSlow).await;
+                        return PlayEnd::Disconnected;
+                    }
+                }
+                continue;
+            }
+            Some(reason) = client.evicted.recv() => {
+                close_with(sender, client, reason).await;
+                return PlayEnd::Replaced;
+            }
+            Some(announcement) = client.announcements.recv() => {
+                let announcement = Bytes::from(announcement.to_bytes());
+                if !enqueue(&mut outbox, client_id, Priority::Control, announcement) {
+                    close_with(sender, client, CloseReason::TooSlow).await;
+                    return PlayEnd::Disconnected;
+                }
+                continue;
+            }
+            Some(refusal) = refused.recv() => {
+                let refusal = Bytes::from(refusal.to_bytes());
+                if !enqueue(&mut outbox, client_id, Priority::Control, refusal) {
+                    close_with(sender, client, CloseReason::TooSlow).await;
+                    return PlayEnd::Disconnected;
+                }
+                continue;
+            }
+            line = chat.recv() => {
+                if let Ok(line) = line {
+                    let visible = game.read().await.chat_visible(&line, conn_info.player_index);
+                    if visible {
+                        let _ = outbox.push(Priority::Bulk, line.frame);
+                    }
+                }
+                continue;
+            }
+            _ = ticks.changed() => {
+                // the rate is re-read every tick so a new Subscribe applies
+                // straight away
+                let tick = *ticks.borrow_and_update();
+                let level = match degrader.as_mut() {
+                    Some(degrader) => {
+                        let was = degrader.level();
+                        let rtt = client.recent_rtt(degrader.config().rtt_max_age);
+                        let level = degrader.observe(outbox.len(), rtt);
+                        if level != was {
+                            println!(
+                                "Client {} snapshots went from {} to {}",
+                                client_id,
+                                was.as_str(),
+                                level.as_str()
+                            );
+                        }
+                        level
+                    }
+                    None => DegradeLevel::Full,
+                };
+                let snapshot = {
+                    let game = game.read().await;
+                    let rate = game
+                        .player(conn_info.player_index)
+                        .map_or(0, |p| p.state_rate_hz) as u64;
+                    let mut every = (state.config().tick_rate / rate.max(1)).max(1);
+                    if level >= DegradeLevel::HalfRate {
+                        every *= 2;
+                    }
+                    let due = last_state_tick.map_or(true, |last| tick - last >= every);
+                    if rate > 0 && due {
+                        state_message(&game, conn_info, &client.interest, state, level)
+                    } else {
+                        None
+                    }
+                };
+                if let Some(message) = snapshot {
+                    last_state_tick = Some(tick);
+                    let _ = outbox.push(Priority::State, Bytes::from(message.to_bytes()));
+                }
+                continue;
+            }
+            _ = game_closed.changed() => {
+                // flush anything broadcast right before the game went away
+                while let Ok(frame) = events.try_recv() {
+                    let _ = outbox.push(Priority::Control, frame);
+                }
+                let goodbye = async {
+                    while let Some(frame) = outbox.pop() {
+                        if !send_frame(sender, client, &frame).await {
+                            return;
+                        }
+                    }
+                    println!("Game {} ended, closing connection {}", game_id, client_id);
+                    close_with(sender, client, CloseReason::NormalLobbyExit).await;
+                };
+                let _ = unless_closed(&game_closed, goodbye).await;
+                return PlayEnd::GameClosed;
+            }
+        };
+        match msg {
+            Ok(Message::Binary(data)) => {
+                if let Some(msg_type) = WsMessage::unknown_type(&data) {
+                    client.traffic.record(Direction::In, msg_type, data.len());
+                    let error = unsupported_type(msg_type);
+                    if !enqueue(
+                        &mut outbox,
+                        client_id,
+                        Priority::Control,
+                        error.to_bytes().into(),
+                    ) {
+                        close_with(sender, client, CloseReason::TooSlow).await;
+                        return PlayEnd::Disconnected;
+                    }
+                    continue;
+                }
+                if let Some(ws_msg) = WsMessage::from_bytes(&data) {
+                    client
+                        .traffic
+                        .record(Direction::In, ws_msg.msg_type.into(), data.len());
+                    match client.middleware.inbound(&ws_msg) {
+                        MiddlewareDecision::Continue => (),
+                        MiddlewareDecision::Drop => continue,
+                        MiddlewareDecision::Close(reason) => {
+                            close_with(sender, client, reason).await;
+                            return PlayEnd::Disconnected;
+                        }
+                    }
+                    if let MessageType::Echo = ws_msg.msg_type {
+                        let reply = echo(state, client, conn_info, &ws_msg);
+                        if !enqueue(
+                            &mut outbox,
+                            client_id,
+                            Priority::Control,
+                            reply.to_bytes().into(),
+                        ) {
+                            close_with(sender, client, CloseReason::TooSlow).await;
+                            return PlayEnd::Disconnected;
+                        }
+                        continue;
+                    }
+                    // answered here rather than in handle_message so the
+                    // reply never waits on the game lock
+                    if let MessageType::TimeSync = ws_msg.msg_type {
+                        let request = match ws_msg.decode::<TimeSyncRequest>() {
+                            Some(request) => request,
+                            None => {
+                                close_with(sender, client, CloseReason::ProtocolViolation).await;
+                                return PlayEnd::Disconnected;
+                            }
+                        };
+                        let reply = TimeSyncResponse {
+                            client_time_us: request.client_time_us,
+                            server_time_us: state.clock_us(),
+                            tick: *ticks.borrow(),
+                        };
+                        let reply = WsMessage::from_payload(MessageType::TimeSync, &reply);
+                        if !enqueue(
+                            &mut outbox,
+                            client_id,
+                            Priority::Control,
+                            reply.to_bytes().into(),
+                        ) {
+                            close_with(sender, client, CloseReason::TooSlow).await;
+                            return PlayEnd::Disconnected;
+                        }
+                        continue;
+                    }
+                    // several frames go back, so this can't be a Response
+                    if let MessageType::EventsSince = ws_msg.msg_type {
+                        let request = match ws_msg.decode::<EventsSinceMessage>() {
+                            Some(request) => request,
+                            None => {
+                                close_with(sender, client, CloseReason::ProtocolViolation).await;
+                                return PlayEnd::Disconnected;
+                            }
+                        };
+                        let (frames, response) = game.read().await.events_since(request.seq);
+                        let response = WsMessage::from_payload(MessageType::EventsSince, &response);
+                        let queued = frames
+                            .into_iter()
+                            .filter_map(|frame| for_protocol(conn_info.protocol, frame))
+                            .chain([Bytes::from(response.to_bytes())])
+                            .all(|frame| enqueue(&mut outbox, client_id, Priority::Control, frame));
+                        if !queued {
+                            close_with(sender, client, CloseReason::TooSlow).await;
+                            return PlayEnd::Disconnected;
+                        }
+                        continue;
+                    }
+                    let response = handle_message(ws_msg, client, conn_info, game, state).await;
+                    if let (Some(every), true) = (required_ping, client.last_ping != last_ping) {
+                        last_ping = client.last_ping;
+                        silence.as_mut().reset(Instant::from_std(last_ping) + every);
+                    }
+                    match response {
+                        Response::Reply(response) => {
+                            let reply = Bytes::from(response.to_bytes());
+                            if !enqueue(&mut outbox, client_id, Priority::Control, reply) {
+                                close_with(sender, client, CloseReason::TooSlow).await;
+                                return PlayEnd::Disconnected;
+                            }
+                        }
+                        Response::Nothing => (),
+                        Response::Close(reason) => {
+                            close_with(sender, client, reason).await;
+                            return PlayEnd::Disconnected;
+                        }
+                        Response::Leave => return PlayEnd::Left,
+                    }
+                } else {
+                    ignore_frame(client_id, "empty binary");
+                }
+            }
+            Ok(Message::Text(_)) => {
+                let error = WsMessage::error(ErrorCode::BinaryExpected, "binary protocol expected");
+                if !enqueue(
+                    &mut outbox,
+                    client_id,
+                    Priority::Control,
+                    error.to_bytes().into(),
+                ) {
+                    close_with(sender, client, CloseReason::TooSlow).await;
+                    return PlayEnd::Disconnected;
+                }
+            }
+            // tungstenite answers websocket pings on its own
+            Ok(Message::Ping(_)) => ignore_frame(client_id, "ping"),
+            Ok(Message::Pong(_)) => keepalive.pong(),
+            Ok(Message::Close(_)) => return PlayEnd::Disconnected,
+            Err(e) => {
+                read_failed(state, sender, client, conn_info.ip, e).await;
+                return PlayEnd::Disconnected;
+            }
+        }
+    }
+}
+
+// Settles who the connection is. With an auth_url the auth service decides,
+// and its name replaces whatever ?name= asked for, so a valid token can't
+// pass itself off as another player; without one the token digest or the
+// name stands in. False when the connection was closed.
+async fn authenticate(
+    state: &ServerState,
+    client: &Client,
+    conn_info: &mut ConnectionInfo,
+    sender: &mut WsSender,
+) -> bool {
+    let config = state.config();
+    let url = match &config.auth_url {
+        Some(url) => url,
+        None => {
+            // one that left off ?name= plays as a guest named after its
+            // client id, so two of them aren't taken for the same player
+            let name = conn_info
+                .name
+                .get_or_insert_with(|| format!("guest-{}", client.id));
+            conn_info.player_id = PlayerId::new(conn_info.auth_token.as_deref(), name);
+            return true;
+        }
+    };
+    let validated = match &conn_info.auth_token {
+        Some(token) => {
+            auth::validate(
+                url,
+                &config.auth_header,
+                config.auth_scheme.as_deref(),
+                token,
+                config.auth_timeout,
+            )
+            .await
+        }
+        None => Err(AuthError::Rejected),
+    };
+    match validated {
+        Ok(identity) => {
+            if conn_info
+                .name
+                .as_deref()
+                .map_or(false, |name| name != identity.name)
+            {
+                println!(
+                    "Client {} asked for name {:?} but is {}",
+                    client.id, conn_info.name, identity.name
+                );
+            }
+            conn_info.name = Some(identity.name);
+            conn_info.player_id = PlayerId::Subject(identity.subject);
+            return true;
+        }
+        Err(AuthError::Rejected) => {
+            println!("Client {} failed authentication", client.id);
+            let error = WsMessage::error(ErrorCode::Unauthorized, "Authentication failed");
+            let _ = send_message(sender, client, &error).await;
+            close_with(sender, client, CloseReason::PolicyViolation).await;
+            return false;
+        }
+        Err(e) => {
+            log::warn!("Client {} could not be authenticated: {}", client.id, e);
+            let error = WsMessage::error(ErrorCode::Unauthorized, "Authentication unavailable");
+            let _ = send_message(sender, client, &error).await;
+            close_with(sender, client, CloseReason::InternalError).await;
+            return false;
+        }
+    }
+}
+
+// v6 connections open with Hello. Anything else first, or nothing within
+// handshake_timeout, is a protocol violation, except Version requests,
+// answered while the Hello is awaited; a Hello asking for what the
+// server can't do gets an UnsupportedHello saying what before the close.
+// False when the connection was closed.
+async fn hello(
+    state: &ServerState,
+    client: &Client,
+    conn_info: &mut ConnectionInfo,
+    sender: &mut WsSender,
+    receiver: &mut WsReceiver,
+) -> bool {
+    let deadline = Instant::now() + state.config().handshake_timeout;
+    let first = loop {
+        let read = async {
+            loop {
+                match receiver.next().await {
+                    // tungstenite answers websocket pings on its own
+                    Some(Ok(Message::Ping(_))) | Some(Ok(Message::Pong(_))) => continue,
+                    other => return other,
+                }
+            }
+        };
+        let first = match timeout_at(deadline, read).await {
+            Ok(Some(Ok(Message::Binary(data)))) => {
+                client.traffic.record(
+                    Direction::In,
+                    data.first().copied().unwrap_or(0),
+                    data.len(),
+                );
+                WsMessage::from_bytes(&data)
+            }
+            Ok(Some(Ok(Message::Close(_)))) | Ok(None) => return false,
+            Ok(Some(Ok(_))) => None,
+            Ok(Some(Err(e))) => {
+                read_failed(state, sender, client, conn_info.ip, e).await;
+                return false;
+            }
+            Err(_) => {
+                println!("Client {} sent no Hello", client.id);
+                close_with(sender, client, CloseReason::ProtocolViolation).await;
+                return false;
+            }
+        };
+        match first {
+            Some(first) if matches!(first.msg_type, MessageType::Version) => {
+                if !send_message(sender, client, &version()).await {
+                    return false;
+                }
+            }
+            first => break first,
+        }
+    };
+    let hello = first
+        .filter(|first| matches!(first.msg_type, MessageType::Hello))
+        .and_then(|first| first.decode::<HelloMessage>());
+    let hello = match hello {
+        Some(hello) => hello,
+        None => {
+            println!("Client {} didn't open with Hello", client.id);
+            close_with(sender, client, CloseReason::ProtocolViolation).await;
+            return false;
+        }
+    };
+    match negotiate(conn_info, &hello) {
+        Ok(agreed) => {
+            println!(
+                "Client {} says hello from {:?}: State v{}, {:?}, {:?}",
+                client.id,
+                hello.build,
+                agreed.state_version,
+                conn_info.format,
+                conn_info.byte_order
+            );
+            let agreed = WsMessage::from_payload(MessageType::Hello, &agreed);
+            return send_message(sender, client, &agreed).await;
+        }
+        Err(message) => {
+            println!(
+                "Client {} sent an unsupported Hello: {}",
+                client.id, message
+            );
+            let error = WsMessage::error(ErrorCode::UnsupportedHello, &message);
+            let _ = send_message(sender, client, &error).await;
+            close_with(sender, client, CloseReason::ProtocolViolation).await;
+            return false;
+        }
+    }
+}
+
+// Applies what a Hello asks for to the connection and returns the answer, or
+// why it can't be done, with the connection left as it was.
+fn negotiate(conn_info: &mut ConnectionInfo, hello: &HelloMessage) -> Result<HelloMessage, String> {
+    if hello.protocol != conn_info.protocol as u8 {
+        return Err(format!(
+            "Hello is for protocol {} but the connection speaks {}",
+            hello.protocol,
+            conn_info.protocol.as_str()
+        ));
+    }
+    let state_version = ProtocolVersion::from_u8(hello.state_version)
+        .filter(|version| *version <= conn_info.protocol)
+        .ok_or_else(|| format!("Unsupported state_version {}", hello.state_version))?;
+    let format = StateFormat::from_code(hello.format)
+        .ok_or_else(|| format!("Unknown format {}", hello.format))?;
+    let byte_order = ByteOrder::from_code(hello.byte_order)
+        .ok_or_else(|| format!("Unknown byte_order {}", hello.byte_order))?;
+    let role = Role::from_code(hello.role).ok_or_else(|| format!("Unknown role {}", hello.role))?;
+    if format == StateFormat::Json && byte_order != ByteOrder::Little {
+        return Err("byte_order only applies to binary State".to_string());
+    }
+    if format == StateFormat::Quantized && state_version < ProtocolVersion::V5 {
+        return Err("Quantized State needs state_version 5 or later".to_string());
+    }
+    conn_info.state_version = state_version;
+    conn_info.format = format;
+    conn_info.byte_order = byte_order;
+    conn_info.firehose = role == Role::Firehose;
+    return Ok(HelloMessage {
+        protocol: conn_info.protocol as u8,
+        build: env!("CARGO_PKG_VERSION").to_string(),
+        state_version: state_version as u8,
+        format: format.code(),
+        byte_order: byte_order.code(),
+        role: role.code(),
+    });
+}
+
+// Game events are control traffic; replay bursts are the one broadcast
+// that can wait, whether or not they came wrapped in an Event.
+fn broadcast_priority(frame: &Bytes) -> Priority {
+    let msg_type = EventMessage::inner_type(frame).or(frame.first().copied());
+    return match msg_type {
+        Some(msg_type) if msg_type == u8::from(MessageType::ReplayBurst) => Priority::Bulk,
+        _ => Priority::Control,
+    };
+}
+
+// A game broadcast as the connection's protocol has it: the sequenced Event
+// from v10, the frame inside it before that. None for a message the
+// protocol predates, which is GoalScored before v11.
+fn for_protocol(protocol: ProtocolVersion, frame: Bytes) -> Option<Bytes> {
+    let msg_type = EventMessage::inner_type(&frame).or(frame.first().copied());
+    if msg_type == Some(u8::from(MessageType::GoalScored)) && protocol < ProtocolVersion::V11 {
+        return None;
+    }
+    if protocol >= ProtocolVersion::V10 {
+        return Some(frame);
+    }
+    let bare = WsMessage::from_bytes(&frame)
+        .filter(|message| matches!(message.msg_type, MessageType::Event))
+        .and_then(|message| message.decode::<EventMessage>())
+        .map_or(frame, |event| Bytes::from(event.frame));
+    return Some(bare);
+}
+
+// False when the frame didn't fit and the connection should be closed.
+fn enqueue(outbox: &mut Outbox, client_id: usize, priority: Priority, frame: Bytes) -> bool {
+    if outbox.push(priority, frame).is_err() {
+        println!(
+            "Client {} has {} frames queued and isn't reading them",
+            client_id,
+            outbox.len()
+        );
+        return false;
+    }
+    return true;
+}
+
+// Answers a Version request, which any connection may send at any point,
+// even before its Hello.
+fn version() -> WsMessage {
+    return WsMessage::from_payload(MessageType::Version, &VersionMessage::current());
+}
+
+// Admin only: turns an Announce into an Announcement for every connection.
+// The reply says how many it went to.
+fn announce(state: &ServerState, conn_info: &ConnectionInfo, request: &WsMessage) -> WsMessage {
+    if !state.is_admin(conn_info) {
+        return WsMessage::error(ErrorCode::Unauthorized, "Announce requires the admin token");
+    }
+    let text = match request.decode::<AnnounceMessage>() {
+        Some(request) if !request.text.is_empty() => request.text,
+        _ => return WsMessage::error(ErrorCode::InvalidParams, "Announce needs some text"),
+    };
+    let announcement = WsMessage::from_payload(
+        MessageType::Announcement,
+        &AnnouncementMessage { text: text.clone() },
+    );
+    let delivered = state.broadcast_all(announcement);
+    println!("Announced to {} connections: {}", delivered, text);
+    return WsMessage::from_payload(
+        MessageType::Announce,
+        &AnnouncedMessage {
+            delivered: delivered as u32,
+        },
+    );
+}
+
+// The answer to an Echo, in whatever state the connection is in: the
+// payload back with the server's stamps, or an Error when it is too big or
+// the connection has echoed too much lately. Not counted against the move
+// limiter, so a probe never costs a player their inputs.
+fn echo(
+    state: &ServerState,
+    client: &Client,
+    conn_info: &ConnectionInfo,
+    request: &WsMessage,
+) -> WsMessage {
+    let received_us = state.clock_us();
+    let max = state.config().max_echo_bytes;
+    if request.payload.len() > max {
+        let reason = format!("Echo payload over {} bytes", max);
+        return WsMessage::error(ErrorCode::InvalidParams, &reason);
+    }
+    if !client.echo_budget.lock().unwrap().take() {
+        return WsMessage::error(ErrorCode::RateLimited, "Too many Echoes, try again later");
+    }
+    let mut reply = EchoReply {
+        payload: request.payload.clone(),
+        received_us,
+        sent_us: 0,
+    };
+    reply.sent_us = state.clock_us();
+    let payload = match conn_info.format {
+        StateFormat::Binary | StateFormat::Quantized => reply.to_bytes(conn_info.byte_order),
+        StateFormat::Json => match reply.to_json() {
+            Some(json) => json.into_bytes(),
+            None => return WsMessage::error(ErrorCode::InvalidParams, "Echo payload isn't UTF-8"),
+        },
+    };
+    return WsMessage {
+        msg_type: MessageType::Echo,
+        payload,
+    };
+}
+
+// A read that failed because the client sent something malformed gets a
+// close with the matching code and a strike against its address; one that
+// failed because the connection went away just ends it.
+async fn read_failed(
+    state: &ServerState,
+    sender: &mut WsSender,
+    client: &Client,
+    ip: IpAddr,
+    error: WsError,
+) {
+    let client_id = client.id;
+    let reason = match &error {
+        WsError::Capacity(_) => CloseReason::MessageTooBig,
+        // the peer dropped TCP without a close frame, which is a hangup
+        WsError::Protocol(ProtocolError::ResetWithoutClosingHandshake) => {
+            log::info!("Client {} disconnected: {}", client_id, error);
+            return;
+        }
+        WsError::Protocol(_) | WsError::Utf8 => CloseReason::ProtocolViolation,
+        _ => {
+            log::info!("Client {} disconnected: {}", client_id, error);
+            return;
+        }
+    };
+    PROTOCOL_STRIKES.fetch_add(1, Ordering::Relaxed);
+    close_with(sender, client, reason).await;
+    let config = state.config();
+    match state
+        .ip_limiter
+        .strike(ip, config.protocol_strikes, config.protocol_ban)
+    {
+        Strike::Counted(strikes) => log::warn!(
+            "Client {} ({}) sent a bad frame: {} (strike {})",
+            client_id,
+            ip,
+            error,
+            strikes
+        ),
+        Strike::Banned => log::warn!(
+            "Client {} ({}) sent a bad frame: {}; banned for {:?}",
+            client_id,
+            ip,
+            error,
+            config.protocol_ban
+        ),
+    }
+}
+
+// Native websocket pings, which proxies and load balancers understand and
+// browsers answer on their own, unlike the app-level Ping kept for RTT. A
+// peer still owing a pong when the next ping is due is taken to be gone.
+struct WsKeepalive {
+    ticks: Option<Interval>,
+    awaiting_pong: bool,
+}
+
+impl WsKeepalive {
+    fn new(period: Option<Duration>) -> Self {
+        return WsKeepalive {
+            ticks: period.map(|period| interval_at(Instant::now() + period, period)),
+            awaiting_pong: false,
+        };
+    }
+
+    // Resolves when the next ping is due; never, without a period.
+    async fn due(&mut self) {
+        match &mut self.ticks {
+            Some(ticks) => {
+                ticks.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    // False when the send failed.
+    async fn ping(&mut self, sender: &mut WsSender, client: &Client) -> bool {
+        self.awaiting_pong = true;
+        return send_within(sender, client, Message::Ping(vec![]))
+            .await
+            .is_ok();
+    }
+
+    fn pong(&mut self) {
+        self.awaiting_pong = false;
+    }
+}
+
+// The answer to a frame of a type newer than this server. It isn't a
+// protocol violation: the client may just be ahead of us, so it's told what
+// we speak and carries on.
+fn unsupported_type(msg_type: u8) -> WsMessage {
+    let error = UnsupportedTypeMessage {
+        code: ErrorCode::UnsupportedMessageType,
+        message: format!("unknown message type {}", msg_type),
+        msg_type,
+        max_type: MessageType::LAST as u8,
+        protocol: ProtocolVersion::MAX as u8,
+    };
+    return WsMessage::from_payload(MessageType::Error, &error);
+}
+
+fn ignore_frame(client_id: usize, kind: &str) {
+    IGNORED_FRAMES.fetch_add(1, Ordering::Relaxed);
+    log::debug!("Ignored {} frame from client {}", kind, client_id);
+}
+
+// How long a write may hold a connection open once its game has closed, or
+// once the connection is being closed anyway. A peer that stopped reading
+// never finishes one, and its task would keep the game, logic and all, in
+// memory long after it was removed.
+const CLOSE_GRACE: Duration = Duration::from_secs(1);
+
+// Runs a write to the socket, giving up on it CLOSE_GRACE after the game
+// closes. None means it was given up on.
+async fn unless_closed<T>(
+    closed: &watch::Receiver<bool>,
+    write: impl Future<Output = T>,
+) -> Option<T> {
+    // a clone, so the caller still sees the change itself
+    let mut closed = closed.clone();
+    let given_up = async {
+        if !*closed.borrow_and_update() {
+            let _ = closed.changed().await;
+        }
+        sleep(CLOSE_GRACE).await;
+    };
+    return tokio::select! {
+        done = write => Some(done),
+        _ = given_up => None,
+    };
+}
+
+// Tells a connection why it couldn't be put in a game, and closes it with
+// the matching code.
+async fn refuse_join(sender: &mut WsSender, client: &Client, code: ErrorCode) {
+    let message = match code {
+        ErrorCode::WrongGameType => "Game is a different mode than requested",
+        ErrorCode::Unauthorized => "Session token does not match this slot",
+        ErrorCode::TooManyGames => "Too many games open for this player",
+        ErrorCode::ServerFull => "Server is running as many games as it can",
+        ErrorCode::NameTaken => "That name is already playing in this game",
+        ErrorCode::AlreadyConnected => "This player is already connected",
+        _ => "Unable to join game",
+    };
+    let error = WsMessage::error(code, message);
+    let _ = send_message(sender, client, &error).await;
+    let reason = match code {
+        ErrorCode::GameFull | ErrorCode::ServerFull => CloseReason::ServerFull,
+        ErrorCode::AlreadyConnected => CloseReason::ReplacedByNewConnection,
+        _ => CloseReason::NormalLobbyExit,
+    };
+    close_with(sender, client, reason).await;
+}
+
+// Every server-initiated close goes through here so clients always get a
+// code and reason they can act on.
+async fn close_with(sender: &mut WsSender, client: &Client, reason: CloseReason) {
+    let _ = client.close_reason.set(reason);
+    println!(
+        "Closing connection {}: {} ({})",
+        client.id,
+        reason.reason(),
+        reason.code()
+    );
+    let frame = CloseFrame {
+        code: CloseCode::from(reason.code()),
+        reason: reason.reason().into(),
+    };
+    // the close frame is a write like any other
+    let grace = client
+        .send_timeout
+        .map_or(CLOSE_GRACE, |limit| limit.min(CLOSE_GRACE));
+    let _ = timeout(grace, sender.send(Message::Close(Some(frame)))).await;
+}
+
+// Writes to the socket within the connection's send_timeout. A write that
+// runs out of time closes the connection as TooSlow, as far as a socket
+// nobody reads lets it, and is reported as an error like any failed send.
+async fn send_within(
+    sender: &mut WsSender,
+    client: &Client,
+    message: Message,
+) -> Result<(), String> {
+    let limit = match client.send_timeout {
+        Some(limit) => limit,
+        None => return sender.send(message).await.map_err(|e| e.to_string()),
+    };
+    return match timeout(limit, sender.send(message)).await {
+        Ok(sent) => sent.map_err(|e| e.to_string()),
+        Err(_) => {
+            close_with(sender, client, CloseReason::TooSlow).await;
+            Err(format!("no progress in {:?}", limit))
+        }
+    };
+}
+
+// A failed send means the peer is gone; callers treat false as a disconnect
+// and go through the normal cleanup instead of panicking the task.
+async fn send_message(sender: &mut WsSender, client: &Client, message: &WsMessage) -> bool {
+    let data = match filter_outbound(client, message.to_bytes()) {
+        Ok(Some(data)) => data,
+        Ok(None) => return true,
+        Err(reason) => {
+            close_with(sender, client, reason).await;
+            return false;
+        }
+    };
+    let (msg_type, len) = (data[0], data.len());
+    match send_within(sender, client, Message::Binary(data)).await {
+        Ok(()) => {
+            client.traffic.record(Direction::Out, msg_type, len);
+            return true;
+        }
+        Err(e) => {
+            println!(
+                "Failed to send {:?} to client {}: {}",
+                message.msg_type, client.id, e
+            );
+            return false;
+        }
+    }
+}
+
+// Runs an encoded frame past the connection's middleware. Ok(None) means it
+// was dropped and Err that the connection should be closed. Frames are only
+// decoded when there is middleware to show them to, and middleware sees
+// the message inside an Event rather than the envelope, which keeps its seq.
+fn filter_outbound(client: &Client, data: Vec<u8>) -> Result<Option<Vec<u8>>, CloseReason> {
+    if client.middleware.is_empty() {
+        return Ok(Some(data));
+    }
+    let mut message = match WsMessage::from_bytes(&data) {
+        Some(message) => message,
+        None => return Ok(Some(data)),
+    };
+    if matches!(message.msg_type, MessageType::Event) {
+        let mut event = match message.decode::<EventMessage>() {
+            Some(event) => event,
+            None => return Ok(Some(data)),
+        };
+        let inner = filter_outbound(client, std::mem::take(&mut event.frame))?;
+        return Ok(inner.map(|frame| {
+            event.frame = frame;
+            return WsMessage::from_payload(MessageType::Event, &event).to_bytes();
+        }));
+    }
+    return match client.middleware.outbound(&mut message) {
+        MiddlewareDecision::Continue => Ok(Some(message.to_bytes())),
+        MiddlewareDecision::Drop => Ok(None),
+        MiddlewareDecision::Close(reason) => Err(reason),
+    };
+}
+
+// Sends a frame shared through a game broadcast. This tungstenite takes an
+// owned Vec, so the one copy per connection happens here at the sink.
+async fn send_frame(sender: &mut WsSender, client: &Client, frame: &Bytes) -> bool {
+    let data = match filter_outbound(client, frame.to_vec()) {
+        Ok(Some(data)) => data,
+        Ok(None) => return true,
+        Err(reason) => {
+            close_with(sender, client, reason).await;
+            return false;
+        }
+    };
+    let (msg_type, len) = (data.first().copied(), data.len());
+    match send_within(sender, client, Message::Binary(data)).await {
+        Ok(()) => {
+            if let Some(msg_type) = msg_type {
+                client.traffic.record(Direction::Out, msg_type, len);
+            }
+            return true;
+        }
+        Err(e) => {
+            println!("Failed to send broadcast to client {}: {}", client.id, e);
+            return false;
+        }
+    }
+}
+
+// Leaving mid-match hands the win to the opponent and ends the game; leaving
+// a game that is still waiting for an opponent just frees the slot.
+async fn leave_game(
+    state: &ServerState,
+    game_id: usize,
+    game: &Arc<RwLock<Game>>,
+    player_index: usize,
+) {
+    let (game_over, forfeit) = {
+        let mut game = game.write().await;
+        let in_match = game.players.len() == 2;
+        let opponent = game
+            .players
+            .iter()
+            .find(|p| p.index != player_index)
+            .cloned();
+        let leaver = game.remove_player(player_index);
+        let name = leaver.as_ref().map_or(String::new(), |p| p.name.clone());
+        game.broadcast(player_left(player_index, &name, true));
+        state.emit(ServerEvent::PlayerLeft {
+            game_id,
+            player_index,
+            name: name.clone(),
+            left: true,
+        });
+        let mut forfeit = None;
+        if let (true, Some(opponent)) = (in_match, opponent) {
+            let stats = game
+                .downcast::<SoccerGame>()
+                .map_or(vec![], |soccer_game| soccer_game.match_stats());
+            if let Some(leaver) = &leaver {
+                record_result(state, &game, &opponent, leaver, &stats, true).await;
+            }
+            game.record(HistoryEvent::Forfeit {
+                winner: opponent.index,
+            });
+            let winner = opponent.index as u8;
+            let game_over = GameOverMessage {
+                winner: Some(winner),
+                reason: GameOverReason::Forfeit,
+                stats,
+            };
+            game.broadcast(WsMessage::from_payload(MessageType::GameOver, &game_over));
+            state.emit(ServerEvent::GameOver {
+                game_id,
+                winner: Some(opponent.index),
+                reason: GameOverReason::Forfeit,
+            });
+            println!("Player {} forfeited game {}", name, game_id);
+            forfeit = Some(game_over);
+        }
+        let game_over = in_match || game.players.is_empty();
+        // closed under the lock so nobody is placed into it meanwhile
+        if game_over {
+            game.close();
+        }
+        (game_over, forfeit)
+    };
+    if let Some(forfeit) = forfeit {
+        state.hooks().on_game_over(game_id, &forfeit).await;
+    }
+    if game_over {
+        remove_game(state, game_id, game).await;
+        println!("Removed game {}", game_id);
+    }
+    invariants::enforce(state, "leave").await;
+}
+
+// Puts a finished match on the leaderboard, unless stats are off or a bot
+// played in it. A forfeit counts no goals for either side; otherwise each
+// side is credited what it scored, less any handicap it started with.
+async fn record_result(
+    state: &ServerState,
+    game: &Game,
+    winner: &Player,
+    loser: &Player,
+    stats: &[SlotStats],
+    forfeit: bool,
+) {
+    if !state.config().collect_stats || winner.bot || loser.bot {
+        return;
+    }
+    fn competitor<'a>(
+        game: &Game,
+        player: &'a Player,
+        stats: &[SlotStats],
+        forfeit: bool,
+    ) -> Competitor<'a> {
+        let team = game
+            .downcast::<SoccerGame>()
+            .and_then(|soccer_game| soccer_game.team(player.index));
+        let goals = match (forfeit, team) {
+            (false, Some(team)) => team.score.saturating_sub(team.handicap.score),
+            _ => 0,
+        };
+        return Competitor {
+            id: &player.id,
+            name: &player.name,
+            goals,
+            own_goals: team.map_or(0, |team| team.own_goals),
+            stats: stats
+                .iter()
+                .find(|slot| slot.player as usize == player.index)
+                .copied()
+                .unwrap_or_default(),
+        };
+    }
+    state.stats.write().await.record_result(
+        competitor(game, winner, stats, forfeit),
+        competitor(game, loser, stats, forfeit),
+    );
+}
+
+// Ends a match whose clock ran out. The side ahead wins; level scores are a
+// draw, which the leaderboard doesn't count.
+async fn end_on_time(state: &ServerState, game_id: usize, game: &Arc<RwLock<Game>>) {
+    let game_over = {
+        let mut game = game.write().await;
+        let scores = game.logic.scores();
+        let best = scores.iter().copied().max().unwrap_or(0);
+        let leaders: Vec<usize> = (0..scores.len()).filter(|&t| scores[t] == best).collect();
+        let winner = match leaders[..] {
+            [team] => game
+                .players
+                .iter()
+                .find(|p| game.logic.team_of(p.index) == Some(team as u8))
+                .cloned(),
+            _ => None,
+        };
+        // someone on another side; a teammate of the winner didn't lose
+        let loser = winner.as_ref().and_then(|winner| {
+            let winning_team = game.logic.team_of(winner.index);
+            return game
+                .players
+                .iter()
+                .find(|p| game.logic.team_of(p.index) != winning_team)
+                .cloned();
+        });
+        let stats = game
+            .downcast::<SoccerGame>()
+            .map_or(vec![], |soccer_game| soccer_game.match_stats());
+        if let (Some(winner), Some(loser)) = (&winner, &loser) {
+            record_result(state, &game, winner, loser, &stats, false).await;
+        }
+        let winner = winner.map(|winner| winner.index);
+        game.record(HistoryEvent::TimeUp { winner });
+        let game_over = GameOverMessage {
+            winner: winner.map(|winner| winner as u8),
+            reason: GameOverReason::TimeUp,
+            stats,
+        };
+        game.broadcast(WsMessage::from_payload(MessageType::GameOver, &game_over));
+        state.emit(ServerEvent::GameOver {
+            game_id,
+            winner,
+            reason: GameOverReason::TimeUp,
+        });
+        println!("Game {} ran out of time, winner {:?}", game_id, winner);
+        game.close();
+        game_over
+    };
+    state.hooks().on_game_over(game_id, &game_over).await;
+    remove_game(state, game_id, game).await;
+}
+
+fn player_joined(player: &Player) -> WsMessage {
+    let joined = PlayerJoinedMessage {
+        player_index: player.index as u8,
+        name: player.name.clone(),
+        bot: player.bot,
+    };
+    return WsMessage::from_payload(MessageType::PlayerJoined, &joined);
+}
+
+fn player_left(player_index: usize, name: &str, left: bool) -> WsMessage {
+    let left = PlayerLeftMessage {
+        player_index: player_index as u8,
+        name: name.to_string(),
+        left,
+    };
+    return WsMessage::from_payload(MessageType::PlayerLeft, &left);
+}
+
+pub(crate) fn parse_query_params(query: &str) -> HashMap<String, String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
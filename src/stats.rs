@@ -0,0 +1,189 @@
+use crate::message::{LeaderboardEntry, PlayerRecord, SlotStats};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
+
+pub type Stats = Arc<RwLock<StatsStore>>;
+
+// Who a player is, as opposed to the name they show. Game slots, reconnects,
+// matchmaking and leaderboard records are all keyed by it, so a connection
+// that sent an auth token keeps its slot and record across name changes; one
+// without is only known by its name. Only a digest of the token is kept.
+// With an auth_url the auth service's subject id is used instead, so a token
+// can't claim someone else's record by asking for their name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PlayerId {
+    Account(u64),
+    Guest(String),
+    Subject(String),
+    // a server-driven player, by its slot in its game; never a connection,
+    // so it can't collide with a guest who calls themselves "bot"
+    Bot(usize),
+}
+
+impl PlayerId {
+    pub fn new(auth_token: Option<&str>, name: &str) -> Self {
+        return match auth_token {
+            // SHA-256 rather than std's hasher, whose output may change
+            // between Rust releases and would orphan every saved record
+            Some(token) => {
+                let digest = Sha256::digest(token.as_bytes());
+                let mut head = [0; 8];
+                head.copy_from_slice(&digest[..8]);
+                PlayerId::Account(u64::from_be_bytes(head))
+            }
+            None => PlayerId::Guest(name.to_string()),
+        };
+    }
+}
+
+impl fmt::Display for PlayerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            PlayerId::Account(digest) => write!(f, "account:{:016x}", digest),
+            PlayerId::Guest(name) => write!(f, "guest:{}", name),
+            PlayerId::Subject(subject) => write!(f, "subject:{}", subject),
+            PlayerId::Bot(slot) => write!(f, "bot:{}", slot),
+        };
+    }
+}
+
+// One side of a finished match.
+pub struct Competitor<'a> {
+    pub id: &'a PlayerId,
+    pub name: &'a str,
+    pub goals: u32,
+    // of the other side's goals, the ones this side put in itself
+    pub own_goals: u32,
+    // default for game types that don't count inputs
+    pub stats: SlotStats,
+}
+
+struct StoredRecord {
+    record: PlayerRecord,
+    // shown on the leaderboard; the latest name the player used
+    name: String,
+    touched: u64,
+}
+
+// In-memory per-player match records keyed by PlayerId. The store is capped
+// so drive-by connections can't grow it forever; when full, the record that
+// was updated longest ago is evicted.
+pub struct StatsStore {
+    records: HashMap<PlayerId, StoredRecord>,
+    max_entries: usize,
+    clock: u64,
+}
+
+impl StatsStore {
+    pub fn new(max_entries: usize) -> Self {
+        return StatsStore {
+            records: HashMap::new(),
+            max_entries,
+            clock: 0,
+        };
+    }
+
+    pub fn record_result(&mut self, winner: Competitor, loser: Competitor) {
+        let winner_record = self.entry(winner.id, winner.name);
+        winner_record.wins += 1;
+        winner_record.goals_scored += winner.goals;
+        winner_record.goals_conceded += loser.goals;
+        winner_record.own_goals += winner.own_goals;
+        add_inputs(winner_record, &winner.stats);
+        let loser_record = self.entry(loser.id, loser.name);
+        loser_record.losses += 1;
+        loser_record.goals_scored += loser.goals;
+        loser_record.goals_conceded += winner.goals;
+        loser_record.own_goals += loser.own_goals;
+        add_inputs(loser_record, &loser.stats);
+    }
+
+    pub fn get(&self, id: &PlayerId) -> PlayerRecord {
+        return self
+            .records
+            .get(id)
+            .map(|stored| stored.record.clone())
+            .unwrap_or_default();
+    }
+
+    pub fn leaderboard(&self, count: usize) -> Vec<LeaderboardEntry> {
+        let mut entries: Vec<LeaderboardEntry> = self
+            .records
+            .values()
+            .map(|stored| LeaderboardEntry {
+                name: stored.name.clone(),
+                record: stored.record.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.record
+                .wins
+                .cmp(&a.record.wins)
+                .then_with(|| a.record.losses.cmp(&b.record.losses))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        entries.truncate(count);
+        return entries;
+    }
+
+    // Every record with the name it is shown under.
+    pub fn iter(&self) -> impl Iterator<Item = (&PlayerId, &str, &PlayerRecord)> {
+        return self
+            .records
+            .iter()
+            .map(|(id, stored)| (id, stored.name.as_str(), &stored.record));
+    }
+
+    // Puts back a saved record, replacing any for the same player.
+    pub fn insert(&mut self, id: PlayerId, name: &str, record: PlayerRecord) {
+        *self.entry(&id, name) = record;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.records.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.records.is_empty();
+    }
+
+    fn entry(&mut self, id: &PlayerId, name: &str) -> &mut PlayerRecord {
+        self.clock += 1;
+        if !self.records.contains_key(id) && self.records.len() >= self.max_entries {
+            let oldest = self
+                .records
+                .iter()
+                .min_by_key(|(_, stored)| stored.touched)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                self.records.remove(&oldest);
+            }
+        }
+        let clock = self.clock;
+        let stored = self
+            .records
+            .entry(id.clone())
+            .or_insert_with(|| StoredRecord {
+                record: PlayerRecord::default(),
+                name: String::new(),
+                touched: clock,
+            });
+        stored.touched = clock;
+        stored.name = name.to_string();
+        return &mut stored.record;
+    }
+}
+
+fn add_inputs(record: &mut PlayerRecord, stats: &SlotStats) {
+    let shots = record.shots + stats.shots;
+    if shots > 0 {
+        record.avg_shot_speed = (record.avg_shot_speed * record.shots as f32
+            + stats.avg_shot_speed * stats.shots as f32)
+            / shots as f32;
+    }
+    record.shots = shots;
+    record.possession_ticks += stats.possession_ticks;
+}
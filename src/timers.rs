@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Names a timer of one game. Each game type picks its own; timers due on
+// the same update go off in id order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GameTimerId(pub u32);
+
+// What game logic asks of its game's timers, handed over through
+// GameLogic::take_timer_changes. Logic counts its own updates rather than
+// the game's ticks, so it asks for a delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerChange {
+    // goes off at the start of the update this many after the one being
+    // played; 0 is the next one, like 1
+    After { ticks: u64, id: GameTimerId },
+    Cancel(GameTimerId),
+}
+
+// "Do this at tick N" for a game, in place of a counter field per feature.
+// A Game owns one and delivers what is due to GameLogic::on_timer at the
+// start of each update, before input is applied or anything is stepped.
+// Each id is pending at most once: scheduling it again moves it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameTimers {
+    at: BTreeMap<GameTimerId, u64>,
+}
+
+impl GameTimers {
+    // Sets id to go off at the start of the update that plays tick, in place
+    // of whenever it was set for. A tick already reached goes off at the
+    // start of the next update.
+    pub fn schedule_at(&mut self, tick: u64, id: GameTimerId) {
+        self.at.insert(id, tick);
+    }
+
+    // False if id wasn't pending.
+    pub fn cancel(&mut self, id: GameTimerId) -> bool {
+        return self.at.remove(&id).is_some();
+    }
+
+    pub fn due_at(&self, id: GameTimerId) -> Option<u64> {
+        return self.at.get(&id).copied();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.at.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.at.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.at.clear();
+    }
+
+    // Takes every timer due by tick, in id order.
+    pub fn take_due(&mut self, tick: u64) -> Vec<GameTimerId> {
+        let due: Vec<GameTimerId> = self
+            .at
+            .iter()
+            .filter(|(_, at)| **at <= tick)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &due {
+            self.at.remove(id);
+        }
+        return due;
+    }
+
+    // Carries out what logic asked for while tick was being played.
+    pub fn apply(&mut self, tick: u64, changes: Vec<TimerChange>) {
+        for change in changes {
+            match change {
+                TimerChange::After { ticks, id } => self.schedule_at(tick + ticks, id),
+                TimerChange::Cancel(id) => {
+                    self.cancel(id);
+                }
+            }
+        }
+    }
+
+    // Every pending timer with the ticks it has left from tick, in id order;
+    // what a save keeps, since a resumed game counts its ticks from 0.
+    pub fn remaining(&self, tick: u64) -> Vec<(GameTimerId, u64)> {
+        return self
+            .at
+            .iter()
+            .map(|(id, at)| (*id, at.saturating_sub(tick)))
+            .collect();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::message::MessageType;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // decoded off a socket
+    In,
+    // written to a socket
+    Out,
+}
+
+impl Direction {
+    pub fn label(&self) -> &'static str {
+        return match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        };
+    }
+}
+
+const TYPE_SLOTS: usize = 256;
+
+// Messages and encoded bytes for the whole server, by direction and
+// MessageType. Counted where frames are decoded and where they are written,
+// so every connection shows up no matter what state it is in.
+pub struct TrafficCounters {
+    messages: [[AtomicU64; TYPE_SLOTS]; 2],
+    bytes: [[AtomicU64; TYPE_SLOTS]; 2],
+}
+
+pub static TRAFFIC: TrafficCounters = TrafficCounters::new();
+
+impl TrafficCounters {
+    #[allow(clippy::declare_interior_mutable_const)]
+    pub const fn new() -> Self {
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        const SLOTS: [AtomicU64; TYPE_SLOTS] = [ZERO; TYPE_SLOTS];
+        return TrafficCounters {
+            messages: [SLOTS; 2],
+            bytes: [SLOTS; 2],
+        };
+    }
+
+    pub fn record(&self, direction: Direction, msg_type: u8, bytes: usize) {
+        let slot = msg_type as usize;
+        self.messages[direction as usize][slot].fetch_add(1, Ordering::Relaxed);
+        self.bytes[direction as usize][slot].fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    // (type, messages, bytes) for every type seen in direction so far.
+    pub fn by_type(&self, direction: Direction) -> Vec<(MessageType, u64, u64)> {
+        return (0..TYPE_SLOTS)
+            .filter_map(|slot| {
+                let messages = self.messages[direction as usize][slot].load(Ordering::Relaxed);
+                if messages == 0 {
+                    return None;
+                }
+                let msg_type = MessageType::try_from(slot as u8).ok()?;
+                let bytes = self.bytes[direction as usize][slot].load(Ordering::Relaxed);
+                return Some((msg_type, messages, bytes));
+            })
+            .collect();
+    }
+}
+
+impl Default for TrafficCounters {
+    fn default() -> Self {
+        return TrafficCounters::new();
+    }
+}
+
+// One connection's share of TRAFFIC. The Client owns it and its game's
+// Player holds a clone, so the debug listing can read it while the
+// connection is still open.
+#[derive(Debug)]
+pub struct ConnectionTraffic {
+    pub opened: Instant,
+    messages_in: AtomicU64,
+    bytes_in: AtomicU64,
+    messages_out: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrafficSummary {
+    pub messages_in: u64,
+    pub bytes_in: u64,
+    pub messages_out: u64,
+    pub bytes_out: u64,
+    pub duration: Duration,
+}
+
+impl ConnectionTraffic {
+    pub fn new() -> Self {
+        return ConnectionTraffic {
+            opened: Instant::now(),
+            messages_in: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            messages_out: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+        };
+    }
+
+    // Counts against this connection and the server-wide totals. Only the
+    // connection's own task records, so its counters are bumped with a
+    // plain load and store rather than a locked add; readers elsewhere just
+    // see them a message late.
+    pub fn record(&self, direction: Direction, msg_type: u8, bytes: usize) {
+        TRAFFIC.record(direction, msg_type, bytes);
+        let (messages, total) = match direction {
+            Direction::In => (&self.messages_in, &self.bytes_in),
+            Direction::Out => (&self.messages_out, &self.bytes_out),
+        };
+        messages.store(messages.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+        total.store(
+            total.load(Ordering::Relaxed) + bytes as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn summary(&self) -> TrafficSummary {
+        return TrafficSummary {
+            messages_in: self.messages_in.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            messages_out: self.messages_out.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            duration: self.opened.elapsed(),
+        };
+    }
+}
+
+impl Default for ConnectionTraffic {
+    fn default() -> Self {
+        return ConnectionTraffic::new();
+    }
+}
+
+// Equal when opened at the same moment with the same counts so far.
+impl PartialEq for ConnectionTraffic {
+    fn eq(&self, other: &Self) -> bool {
+        let (ours, theirs) = (self.summary(), other.summary());
+        return self.opened == other.opened
+            && ours.messages_in == theirs.messages_in
+            && ours.bytes_in == theirs.bytes_in
+            && ours.messages_out == theirs.messages_out
+            && ours.bytes_out == theirs.bytes_out;
+    }
+}
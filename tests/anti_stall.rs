@@ -0,0 +1,46 @@
+use rust_backend::game::{AntiStallConfig, GameLogic, SoccerGame, SoccerGameConfig, StallAction};
+use std::time::Duration;
+
+const TICK_MS: f64 = 1000.0 / 60.0;
+const NUDGE_SPEED: f32 = 120.0;
+
+// A ball left at rest gets nudged on the first tick it has been still for
+// `after`, the same way for the same seed; without anti_stall it stays
+// where it is.
+#[test]
+fn nudges_a_still_ball() {
+    let anti_stall = AntiStallConfig {
+        speed: 1.0,
+        after: Duration::from_secs(1),
+        action: StallAction::Nudge(NUDGE_SPEED),
+        pinned_radius: None,
+    };
+    // the ticks until the ball first moved, and how it moved
+    let run = |anti_stall: Option<AntiStallConfig>, seed: u64| {
+        let mut game = SoccerGame::with_config(SoccerGameConfig {
+            anti_stall,
+            ..SoccerGameConfig::default()
+        });
+        game.reseed(seed);
+        for tick in 1..=120 {
+            game.update(TICK_MS);
+            let velocity = *game.bodies[game.balls[0]].linvel();
+            if velocity.norm() > 0.0 {
+                return Some((tick, velocity));
+            }
+        }
+        return None;
+    };
+
+    let (tick, velocity) = run(Some(anti_stall.clone()), 42).expect("the ball was never nudged");
+    // first seen still on tick 1, so still for a second on tick 61
+    assert_eq!(tick, 61, "nudged after {:.0}ms", tick as f64 * TICK_MS);
+    assert!(
+        (velocity.norm() - NUDGE_SPEED).abs() < 0.01,
+        "nudged to {:?}",
+        velocity
+    );
+    assert_eq!(run(Some(anti_stall), 42), Some((tick, velocity)));
+
+    assert_eq!(run(None, 42), None);
+}
@@ -0,0 +1,69 @@
+use rapier2d::prelude::*;
+use rust_backend::game::{AntiStallConfig, GameLogic, SoccerGame, SoccerGameConfig, StallAction};
+use rust_backend::message::{BallResetMessage, MessageType};
+use std::time::Duration;
+
+const TICK_MS: f64 = 1000.0 / 60.0;
+const RATTLE_SPEED: f32 = 200.0;
+
+// A ball wedged in a corner and rattling back and forth there is never slow,
+// but with pinned_radius it is dropped once it has been stuck for `after`:
+// at rest on the nearest neutral spot that isn't covered, here the centre,
+// since pucks sit on the two nearer ones. It happens once, not on every
+// tick after, and players hear about it. Without pinned_radius the ball
+// rattles on.
+#[test]
+fn drops_a_pinned_ball_once() {
+    let anti_stall = AntiStallConfig {
+        speed: 5.0,
+        after: Duration::from_secs(1),
+        action: StallAction::DropBall,
+        pinned_radius: Some(20.0),
+    };
+    let (resets, ball) = rattle(Some(anti_stall.clone()));
+    assert_eq!(resets.len(), 1, "{:?}", resets);
+    let (tick, reset) = resets[0];
+    // first seen in the corner on tick 1, so stuck for a second on tick 61
+    assert_eq!(tick, 61, "dropped after {:.0}ms", tick as f64 * TICK_MS);
+    assert_eq!((reset.ball, reset.x, reset.y), (0, 0.0, 0.0));
+    assert!(ball.norm() < 20.0, "ball at {:?}", ball);
+
+    let (resets, _) = rattle(Some(AntiStallConfig {
+        pinned_radius: None,
+        ..anti_stall
+    }));
+    assert!(resets.is_empty(), "{:?}", resets);
+}
+
+// Wedges the ball in the bottom-left corner with pucks on the two neutral
+// spots nearest it, then shakes it for 110 ticks, returning each BallReset
+// with the tick it came on, and where the ball ended up.
+fn rattle(anti_stall: Option<AntiStallConfig>) -> (Vec<(u64, BallResetMessage)>, Vector<f32>) {
+    let mut game = SoccerGame::with_config(SoccerGameConfig {
+        anti_stall,
+        serve_speed: None,
+        ..SoccerGameConfig::default()
+    });
+    game.reseed(7);
+    let (width, height) = (game.width, game.height);
+    let ball = game.balls[0];
+    let corner = vector![40.0 - width / 2.0, 40.0 - height / 2.0];
+    game.bodies[ball].set_translation(corner, true);
+    for (puck, fraction) in game.teams[0].pucks.clone().into_iter().zip([-0.375, -0.25]) {
+        let body = &mut game.bodies[puck];
+        body.set_translation(vector![0.0, fraction * height], true);
+        body.set_linvel(vector![0.0, 0.0], true);
+    }
+    let mut resets = vec![];
+    for tick in 1..=110 {
+        let toward = if tick % 2 == 0 { 1.0 } else { -1.0 };
+        game.bodies[ball].set_linvel(vector![toward * RATTLE_SPEED, 0.0], true);
+        game.update(TICK_MS);
+        for event in game.take_events() {
+            if event.msg_type as u8 == MessageType::BallReset as u8 {
+                resets.push((tick, event.decode::<BallResetMessage>().unwrap()));
+            }
+        }
+    }
+    return (resets, *game.bodies[ball].translation());
+}
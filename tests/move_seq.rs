@@ -0,0 +1,90 @@
+#[path = "../examples/common/mod.rs"]
+mod common;
+
+use common::{kick_off, States};
+use futures::StreamExt;
+use rust_backend::client::{ClientOptions, GameClient};
+use rust_backend::game::PauseConfig;
+use rust_backend::message::{ByteOrder, SoccerMoveMessage, SOCCER_MOVE_V1_LEN};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18141";
+
+// A move's seq survives encoding in either byte order, and a move from a
+// client too old to send one decodes with seq 0.
+#[test]
+fn seq_round_trips() {
+    let shot = SoccerMoveMessage {
+        vx: 120.0,
+        vy: -40.0,
+        target: 1,
+        angular: 0.5,
+        seq: 41,
+    };
+    for order in [ByteOrder::Little, ByteOrder::Big] {
+        let decoded = SoccerMoveMessage::decode(order, &shot.encode(order)).unwrap();
+        assert_eq!(decoded.seq, 41);
+    }
+    let old = &shot.encode(ByteOrder::Little)[..SOCCER_MOVE_V1_LEN];
+    assert_eq!(SoccerMoveMessage::from_bytes(old).unwrap().seq, 0);
+}
+
+// Over the wire, each sequenced move comes back as the ack_seq of the first
+// State after it is applied, in the order they were sent, and an
+// unsequenced move leaves the last ack where it was.
+#[tokio::test]
+async fn acks_in_order() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        // moves are refused until the countdown after the ready check ends
+        pause: PauseConfig {
+            resume_countdown: Duration::ZERO,
+            ..PauseConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let options = ClientOptions {
+        practice: true,
+        reconnect: false,
+        ..ClientOptions::default()
+    };
+    let client = GameClient::connect(&format!("ws://{}/", ADDR), "sequencer", options)
+        .await
+        .unwrap();
+    let mut states: States = Box::pin(client.subscribe_state());
+    kick_off(&client, &mut states).await;
+    let mut acks = vec![];
+    for seq in [1, 2, 3] {
+        client.send_move_sequenced(0, 50.0 * seq as f32, 0.0, 0.0, seq);
+        let acked = async {
+            while let Some(snapshot) = states.next().await {
+                acks.push(snapshot.ack_seq);
+                if snapshot.ack_seq == seq {
+                    return;
+                }
+            }
+            panic!("stream ended");
+        };
+        timeout(Duration::from_secs(5), acked)
+            .await
+            .expect("move never acked");
+    }
+    acks.dedup();
+    assert_eq!(acks.last(), Some(&3));
+    assert!(acks.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", acks);
+
+    client.send_move(0, -50.0, 0.0);
+    for _ in 0..10 {
+        let snapshot = timeout(Duration::from_secs(5), states.next())
+            .await
+            .expect("no State")
+            .expect("stream ended");
+        assert_eq!(snapshot.ack_seq, 3);
+    }
+}
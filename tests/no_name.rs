@@ -0,0 +1,29 @@
+#[path = "../examples/common/mod.rs"]
+mod common;
+
+use common::{raw_connect, raw_welcome};
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, Duration};
+
+const ADDR: &str = "127.0.0.1:18168";
+
+// Without an auth service ?name= can be left off, query string and all:
+// two such connections are matched into a game like any others.
+#[tokio::test]
+async fn connects_without_a_query_string() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut first = raw_connect(ADDR, "").await;
+    let mut second = raw_connect(ADDR, "").await;
+    let first = raw_welcome(&mut first).await;
+    let second = raw_welcome(&mut second).await;
+    assert_eq!(first.game_id, second.game_id);
+    assert_ne!(first.player_index, second.player_index);
+}
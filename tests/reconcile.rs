@@ -0,0 +1,62 @@
+#[path = "../examples/common/mod.rs"]
+mod common;
+
+use common::{kick_off, States};
+use futures::StreamExt;
+use rust_backend::client::{ClientOptions, GameClient};
+use rust_backend::game::PauseConfig;
+use rust_backend::server::{Server, ServerConfig};
+use tokio::time::{sleep, timeout, Duration};
+
+const ADDR: &str = "127.0.0.1:18084";
+const SEQ: u32 = 7;
+
+// A practice client sends one sequenced move; the first State frame that
+// acks it must carry the player's own pucks, so a predicting client can
+// replay anything it sent after SEQ on top of them.
+#[tokio::test]
+async fn acked_state_carries_own_pucks() {
+    let config = ServerConfig {
+        addr: ADDR.to_string(),
+        http_addr: None,
+        health_addr: None,
+        // moves are refused until the countdown after the ready check ends
+        pause: PauseConfig {
+            resume_countdown: Duration::ZERO,
+            ..PauseConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    tokio::spawn(Server::new(config).run());
+    sleep(Duration::from_millis(200)).await;
+
+    let options = ClientOptions {
+        practice: true,
+        reconnect: false,
+        ..ClientOptions::default()
+    };
+    let client = GameClient::connect(&format!("ws://{}/", ADDR), "predictor", options)
+        .await
+        .unwrap();
+    let mut states: States = Box::pin(client.subscribe_state());
+    kick_off(&client, &mut states).await;
+    client.send_move_sequenced(0, 100.0, 0.0, 0.0, SEQ);
+
+    let acked = async {
+        while let Some(snapshot) = states.next().await {
+            if snapshot.ack_seq == SEQ {
+                return Some(snapshot);
+            }
+        }
+        return None;
+    };
+    let snapshot = timeout(Duration::from_secs(5), acked)
+        .await
+        .expect("move never acked")
+        .expect("stream ended");
+    assert!(!snapshot.own_pucks.is_empty(), "no pucks to reconcile");
+    let puck = snapshot.own_pucks[0];
+    assert_eq!(puck.target, 0);
+    // the move has been applied by the time it is acked
+    assert!(puck.vx > 0.0, "puck 0 moving [{}, {}]", puck.vx, puck.vy);
+}